@@ -1,9 +1,20 @@
-use crate::memory::{MemoryEngine, RecallArgs, RememberArgs};
+use crate::memory::{
+    parse_metadata, stage_chat_log, ArchiveArgs, ConflictAction, ConflictPolicy, ExportDump, KeywordMatchMode, KeywordSort, KeywordsCompleteArgs, MemoryEngine,
+    MemoryItem, MemoryKind, MemorySource, NamespaceSettings, Retention, StorageBackend,
+    check_half_life_days, RecallArgs, RecallFormat, RecallRanking, RememberArgs, StagedDistill, DEFAULT_SESSION_GAP_MINUTES,
+    KEYWORD_WEIGHT_MAX, RECALL_DEFAULT_LIMIT,
+};
+use crate::schema;
 use clap::{Args, CommandFactory, Parser, Subcommand};
 use serde_json::Value;
-use std::io::{self, Write};
+use std::collections::BTreeMap;
+use std::io::{self, Read, Write};
 use std::path::{Path, PathBuf};
 
+mod output;
+
+use output::OutputArgs;
+
 #[derive(Parser, Debug)]
 #[command(
     name = "memory",
@@ -12,6 +23,11 @@ use std::path::{Path, PathBuf};
     disable_help_subcommand = true
 )]
 pub struct Cli {
+    /// 存储后端：jsonl（默认）/ sqlite / memory（后两者需 sqlite 特性；覆盖 MEMORY_BACKEND）。
+    /// sqlite / memory 目前只支持 remember / recall / get / history / update / forget / transaction，其余子命令报错
+    #[arg(long, global = true, value_parser = StorageBackend::parse)]
+    pub backend: Option<StorageBackend>,
+
     #[command(subcommand)]
     pub command: Option<Command>,
 }
@@ -29,35 +45,120 @@ pub enum Command {
 
     /// 关键字管理（列出）
     Keywords(KeywordsCommand),
+
+    /// namespace 管理（列出）
+    Namespaces(NamespacesCommand),
+
+    /// 统计 namespace（条数/字节/关键字/重要度分布/时间覆盖/索引新鲜度）
+    Stats(StatsCommand),
+
+    /// 从存储中彻底清除已过期（expires_at）的记忆并重建索引
+    PurgeExpired(PurgeExpiredCommand),
+
+    /// 压缩 memories.jsonl：只保留当前版本并重建索引
+    Compact(CompactCommand),
+
+    /// 重建索引：丢弃 index.json 后从 memories.jsonl 全量重建
+    Reindex(ReindexCommand),
+
+    /// 重要度衰减：从未被召回的旧记忆按半衰期降低 importance
+    Maintain(MaintainCommand),
+
+    /// 检查存储完整性（索引条目/倒排/重复记录/残缺末尾），可选 --repair 修复
+    Verify(VerifyCommand),
+
+    /// 归档记忆（按 id 或时间范围）：归档后默认不再出现在 recall 结果中
+    Archive(ArchiveCommand),
+
+    /// 列出最少被 recall 返回的记忆（找出长期无用的记忆）
+    LeastRecalled(LeastRecalledCommand),
+
+    /// 将旧版本存储原地升级到当前格式（并重建索引）
+    Migrate(MigrateCommand),
+
+    /// 依据 WAL 将 namespace 恢复到指定时间点（需启用 MEMORY_WAL），或用 --from 从 backup 快照恢复
+    Restore(RestoreCommand),
+
+    /// 导出 namespace 为 JSONL（首行为文档头）；--since 只导出其后的变更
+    Export(ExportCommand),
+
+    /// 备份：把数据文件与索引复制到 --out 下的时间戳快照目录（可选保留策略）
+    Backup(BackupCommand),
+
+    /// 导入 export 生成的文档（JSONL 或单个 JSON），按 id 去重后追加并增量索引
+    Import(ImportCommand),
+
+    /// 与另一个存储目录双向同步：交换缺少的记忆，冲突按最后变更时间取较新的一方，并重建索引
+    Sync(SyncCommand),
+
+    /// tool 契约的 JSON Schema（inputSchema / outputSchema / 记忆记录）
+    Schema(SchemaCommand),
+
+    /// 从聊天记录（JSONL）按会话切分生成候选记忆暂存文件；审阅后用 --apply 写入
+    Distill(DistillCommand),
 }
 
 #[derive(Args, Debug)]
 pub struct RememberCommand {
-    #[arg(long)]
-    pub namespace: String,
+    /// --stdin-json 时作为 JSON 中未指定 namespace 的默认值
+    #[arg(long, required_unless_present = "stdin_json")]
+    pub namespace: Option<String>,
+
+    /// 从 stdin 读取完整的 remember 参数（JSON 对象，字段同 remember tool）；不能再与记忆内容相关的参数同用
+    #[arg(
+        long = "stdin-json",
+        conflicts_with_all = [
+            "keywords", "weights", "tags", "kind", "slice", "slice_file", "diary", "diary_file", "occurred_at",
+            "importance", "source", "source_kind", "source_id", "source_url", "metadata", "relates_to",
+            "supersedes", "expires_at", "pinned"
+        ]
+    )]
+    pub stdin_json: bool,
 
     /// 关键字（可重复；至少 1 个）
-    #[arg(long = "keyword", short = 'k', required = true, num_args = 1..)]
+    #[arg(long = "keyword", short = 'k', required_unless_present = "stdin_json", num_args = 1..)]
     pub keywords: Vec<String>,
 
-    #[arg(long, required_unless_present = "slice_file", conflicts_with = "slice_file")]
+    /// 关键字权重：KEYWORD=N（可重复；N 为 1~10，未指定的关键字权重为 1）
+    #[arg(long = "weight", value_name = "KEYWORD=N")]
+    pub weights: Vec<String>,
+
+    /// 组织用标签（可重复；单独索引，只用于过滤，不参与关键字召回）
+    #[arg(long = "tag")]
+    pub tags: Vec<String>,
+
+    /// 记忆类型：fact / preference / event / task / decision
+    #[arg(long, value_parser = MemoryKind::parse)]
+    pub kind: Option<MemoryKind>,
+
+    /// 内容切片（`-` 表示从 stdin 读取）
+    #[arg(
+        long,
+        required_unless_present_any = ["slice_file", "stdin_json"],
+        conflicts_with = "slice_file"
+    )]
     pub slice: Option<String>,
 
     #[arg(
         long = "slice-file",
         value_name = "PATH",
-        required_unless_present = "slice",
+        required_unless_present_any = ["slice", "stdin_json"],
         conflicts_with = "slice"
     )]
     pub slice_file: Option<PathBuf>,
 
-    #[arg(long, required_unless_present = "diary_file", conflicts_with = "diary_file")]
+    /// 日记（`-` 表示从 stdin 读取；slice 与 diary 只能有一个来自 stdin）
+    #[arg(
+        long,
+        required_unless_present_any = ["diary_file", "stdin_json"],
+        conflicts_with = "diary_file"
+    )]
     pub diary: Option<String>,
 
     #[arg(
         long = "diary-file",
         value_name = "PATH",
-        required_unless_present = "diary",
+        required_unless_present_any = ["diary", "stdin_json"],
         conflicts_with = "diary"
     )]
     pub diary_file: Option<PathBuf>,
@@ -68,16 +169,44 @@ pub struct RememberCommand {
     #[arg(long)]
     pub importance: Option<u8>,
 
+    /// 来源显示名
     #[arg(long)]
     pub source: Option<String>,
 
-    /// 输出 JSON（Pretty）
-    #[arg(long)]
-    pub pretty: bool,
+    /// 来源类型（如 conversation / document）
+    #[arg(long = "source-kind")]
+    pub source_kind: Option<String>,
+
+    /// 来源系统中的 id（会话 id、文档 id 等）
+    #[arg(long = "source-id")]
+    pub source_id: Option<String>,
+
+    /// 回到原始内容的链接
+    #[arg(long = "source-url")]
+    pub source_url: Option<String>,
+
+    /// 附加的结构化数据（JSON 对象），如 '{"ticket":"ERP-12"}'
+    #[arg(long, value_name = "JSON")]
+    pub metadata: Option<String>,
+
+    /// 相关记忆的 id（可重复）
+    #[arg(long = "relates-to", value_name = "ID")]
+    pub relates_to: Vec<String>,
+
+    /// 被本记忆取代的旧记忆 id（旧记忆默认不再出现在 recall 结果中）
+    #[arg(long, value_name = "ID")]
+    pub supersedes: Option<String>,
+
+    /// 过期时间（RFC3339 或 YYYY-MM-DD）：过期后不再出现在 recall 结果中，可用 purge-expired 清除
+    #[arg(long = "expires-at", value_name = "TIME")]
+    pub expires_at: Option<String>,
 
-    /// 输出文本摘要（如果同时提供 --pretty，则以 --text 为准）
+    /// 置顶：recall 时优先返回
     #[arg(long)]
-    pub text: bool,
+    pub pinned: bool,
+
+    #[command(flatten)]
+    pub format: OutputArgs,
 }
 
 #[derive(Args, Debug)]
@@ -89,6 +218,14 @@ pub struct RecallCommand {
     #[arg(long = "keyword", short = 'k')]
     pub keywords: Vec<String>,
 
+    /// 仅返回同时带有这些标签的记忆（可重复）
+    #[arg(long = "tag")]
+    pub tags: Vec<String>,
+
+    /// 仅返回属于这些类型之一的记忆（可重复）
+    #[arg(long = "kind", value_parser = MemoryKind::parse)]
+    pub kinds: Vec<MemoryKind>,
+
     #[arg(long)]
     pub start: Option<String>,
 
@@ -98,30 +235,73 @@ pub struct RecallCommand {
     #[arg(long)]
     pub query: Option<String>,
 
+    /// 将 --query 视为正则表达式（匹配 slice/diary/source，默认不区分大小写）
+    #[arg(long)]
+    pub regex: bool,
+
+    /// 语义召回：按 --query 与记忆的向量相似度排序（需通过 MEMORY_EMBEDDING_CONFIG 配置向量化后端）
+    #[arg(long)]
+    pub semantic: bool,
+
+    /// 单页条数（上限默认 100，可由 MEMORY_RECALL_MAX_LIMIT 或 namespaces settings 调整）
     #[arg(long, default_value_t = 20)]
     pub limit: usize,
 
+    /// 分页：跳过前 N 条命中结果
+    #[arg(long, default_value_t = 0)]
+    pub offset: usize,
+
+    /// 分页：上一页返回的 next_cursor（优先于 --offset）
+    #[arg(long)]
+    pub cursor: Option<String>,
+
+    /// 单页结果的字节预算：超出时提前截断并给出 next_cursor（至少返回 1 条）
+    #[arg(long = "max-bytes")]
+    pub max_bytes: Option<usize>,
+
+    /// 同时返回已被其它记忆取代（supersedes）的旧记忆
+    #[arg(long = "include-superseded")]
+    pub include_superseded: bool,
+
+    /// 同时返回已归档的记忆
+    #[arg(long = "include-archived")]
+    pub include_archived: bool,
+
     #[arg(long = "include-diary")]
     pub include_diary: bool,
 
-    /// 输出 JSON（Pretty）
+    /// 紧凑格式：每条一行 `date|importance|keywords|slice`（不返回 data）
     #[arg(long)]
-    pub pretty: bool,
+    pub compact: bool,
 
-    /// 输出文本摘要（如果同时提供 --pretty，则以 --text 为准）
-    #[arg(long)]
-    pub text: bool,
+    /// 排序方式：relevance（默认）或 decay（时间衰减 + 访问加成）
+    #[arg(long, value_parser = RecallRanking::parse)]
+    pub ranking: Option<RecallRanking>,
+
+    /// decay 排序的半衰期（天，默认 30）
+    #[arg(long = "half-life-days", value_parser = parse_half_life_days)]
+    pub half_life_days: Option<f64>,
+
+    /// 仅返回由指定 agent 写入的记忆（匹配 written_by）
+    #[arg(long = "written-by")]
+    pub written_by: Option<String>,
+
+    /// 仅返回来源类型（source.kind）等于该值的记忆
+    #[arg(long = "source-kind")]
+    pub source_kind: Option<String>,
+
+    /// 仅返回来源 id（source.id）等于该值的记忆
+    #[arg(long = "source-id")]
+    pub source_id: Option<String>,
+
+    #[command(flatten)]
+    pub format: OutputArgs,
 }
 
 #[derive(Args, Debug)]
 pub struct NowCommand {
-    /// 输出 JSON（Pretty）
-    #[arg(long)]
-    pub pretty: bool,
-
-    /// 输出文本摘要（如果同时提供 --pretty，则以 --text 为准）
-    #[arg(long)]
-    pub text: bool,
+    #[command(flatten)]
+    pub format: OutputArgs,
 }
 
 #[derive(Args, Debug)]
@@ -137,6 +317,12 @@ pub enum KeywordsSubcommand {
 
     /// 列出全局已存在的关键字（跨 namespace 汇总）
     ListGlobal(KeywordsListGlobalCommand),
+
+    /// 按前缀/子串补全已存在的关键字（附带引用条数）
+    Complete(KeywordsCompleteCommand),
+
+    /// 关键字体检：标记疑似无效的关键字并给出合并/删除建议
+    Audit(KeywordsAuditCommand),
 }
 
 #[derive(Args, Debug)]
@@ -144,101 +330,1137 @@ pub struct KeywordsListCommand {
     #[arg(long)]
     pub namespace: String,
 
-    /// 输出 JSON（Pretty）
-    #[arg(long)]
-    pub pretty: bool,
+    /// 排序：length（默认）/ alpha / pinyin / frequency
+    #[arg(long, default_value = "length")]
+    pub sort: String,
 
-    /// 输出文本摘要（如果同时提供 --pretty，则以 --text 为准）
+    #[command(flatten)]
+    pub format: OutputArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct KeywordsAuditCommand {
     #[arg(long)]
-    pub text: bool,
+    pub namespace: String,
+
+    /// 最多列出的可疑关键字数
+    #[arg(long, default_value_t = 100)]
+    pub limit: usize,
+
+    #[command(flatten)]
+    pub format: OutputArgs,
 }
 
 #[derive(Args, Debug)]
 pub struct KeywordsListGlobalCommand {
-    /// 输出 JSON（Pretty）
+    /// 排序：length（默认）/ alpha / pinyin / frequency
+    #[arg(long, default_value = "length")]
+    pub sort: String,
+
+    #[command(flatten)]
+    pub format: OutputArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct KeywordsCompleteCommand {
+    /// 已输入的关键字片段
+    #[arg(value_name = "TEXT")]
+    pub fragment: String,
+
+    /// 限定 namespace；省略时在全局词表中查找
+    #[arg(long)]
+    pub namespace: Option<String>,
+
+    /// 匹配方式：prefix（默认）或 contains
+    #[arg(long = "match", default_value = "prefix")]
+    pub match_mode: String,
+
+    #[arg(long, default_value_t = 20)]
+    pub limit: usize,
+
+    #[command(flatten)]
+    pub format: OutputArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct NamespacesCommand {
+    #[command(subcommand)]
+    pub command: NamespacesSubcommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum NamespacesSubcommand {
+    /// 列出已存在的 namespace（含记忆条数与最近写入时间）
+    List(NamespacesListCommand),
+
+    /// 冻结 namespace（只读，仍可 recall）
+    Freeze(NamespacesFreezeCommand),
+
+    /// 解除 namespace 冻结
+    Unfreeze(NamespacesUnfreezeCommand),
+
+    /// 查看或修改 namespace 级设置（不带修改参数时只查看）
+    Settings(NamespacesSettingsCommand),
+}
+
+#[derive(Args, Debug)]
+pub struct NamespacesListCommand {
+    /// 遍历存储目录重建 catalog.json（发现直接拷入的 namespace）
+    #[arg(long)]
+    pub refresh: bool,
+
+    #[command(flatten)]
+    pub format: OutputArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct NamespacesFreezeCommand {
+    #[arg(long)]
+    pub namespace: String,
+
+    /// 冻结原因（可选）
+    #[arg(long)]
+    pub reason: Option<String>,
+
+    #[command(flatten)]
+    pub format: OutputArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct NamespacesUnfreezeCommand {
+    #[arg(long)]
+    pub namespace: String,
+
+    #[command(flatten)]
+    pub format: OutputArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct NamespacesSettingsCommand {
+    #[arg(long)]
+    pub namespace: String,
+
+    /// recall 单页条数上限（覆盖 MEMORY_RECALL_MAX_LIMIT）
+    #[arg(long = "recall-max-limit", conflicts_with = "reset_recall_max_limit")]
+    pub recall_max_limit: Option<usize>,
+
+    /// 清除 recall 单页上限设置，恢复服务端配置
+    #[arg(long = "reset-recall-max-limit")]
+    pub reset_recall_max_limit: bool,
+
+    #[command(flatten)]
+    pub format: OutputArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct LeastRecalledCommand {
+    #[arg(long)]
+    pub namespace: String,
+
+    #[arg(long, default_value_t = RECALL_DEFAULT_LIMIT)]
+    pub limit: usize,
+
+    #[command(flatten)]
+    pub format: OutputArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct ArchiveCommand {
+    #[arg(long)]
+    pub namespace: String,
+
+    /// 记忆 id（可重复）
+    #[arg(long = "id", value_name = "ID")]
+    pub ids: Vec<String>,
+
+    /// 时间范围起点（按 occurred_at ?? recorded_at；与 --id 取并集）
+    #[arg(long)]
+    pub start: Option<String>,
+
+    /// 时间范围终点
+    #[arg(long)]
+    pub end: Option<String>,
+
+    /// 取消归档
+    #[arg(long)]
+    pub unarchive: bool,
+
+    #[command(flatten)]
+    pub format: OutputArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct CompactCommand {
+    #[arg(long, required_unless_present = "all", conflicts_with = "all")]
+    pub namespace: Option<String>,
+
+    /// 压缩存储根目录下的全部 namespace（跳过已冻结的）
+    #[arg(long)]
+    pub all: bool,
+
+    #[command(flatten)]
+    pub format: OutputArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct ReindexCommand {
+    #[arg(long, required_unless_present = "all", conflicts_with = "all")]
+    pub namespace: Option<String>,
+
+    /// 重建存储根目录下的全部 namespace
+    #[arg(long)]
+    pub all: bool,
+
+    #[command(flatten)]
+    pub format: OutputArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct VerifyCommand {
+    #[arg(long)]
+    pub namespace: String,
+
+    /// 截掉数据文件末尾的残缺记录并全量重建索引
+    #[arg(long)]
+    pub repair: bool,
+
+    #[command(flatten)]
+    pub format: OutputArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct MaintainCommand {
+    #[arg(long)]
+    pub namespace: String,
+
+    /// 半衰期（天，默认 90，可由 MEMORY_IMPORTANCE_HALF_LIFE_DAYS 调整）
+    #[arg(long = "half-life-days", value_parser = parse_half_life_days)]
+    pub half_life_days: Option<f64>,
+
+    /// 只列出将要衰减的记忆，不写入
+    #[arg(long = "dry-run")]
+    pub dry_run: bool,
+
+    #[command(flatten)]
+    pub format: OutputArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct PurgeExpiredCommand {
+    #[arg(long)]
+    pub namespace: String,
+
+    #[command(flatten)]
+    pub format: OutputArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct StatsCommand {
+    #[arg(long)]
+    pub namespace: String,
+
+    #[command(flatten)]
+    pub format: OutputArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct MigrateCommand {
+    #[command(flatten)]
+    pub format: OutputArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct RestoreCommand {
+    /// 目标 namespace（--from 时可省略，恢复快照中的全部 namespace）
+    #[arg(long, required_unless_present = "from")]
+    pub namespace: Option<String>,
+
+    /// 恢复到的时间点（RFC3339 或 YYYY-MM-DD；日期按当天结束计）
+    #[arg(long, required_unless_present = "from", conflicts_with = "from")]
+    pub at: Option<String>,
+
+    /// 从 backup 快照恢复：快照目录，或 backup 的 --out 目录（取最新快照）
+    #[arg(long, value_name = "PATH")]
+    pub from: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub format: OutputArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct ExportCommand {
+    #[arg(long)]
+    pub namespace: String,
+
+    /// 增量起点：记录序号（上次导出文档头中的 next_seq）或时间点（RFC3339 / YYYY-MM-DD）
+    #[arg(long)]
+    pub since: Option<String>,
+
+    /// 写入文件（默认输出到 stdout）
+    #[arg(long, visible_alias = "out", value_name = "PATH")]
+    pub output: Option<PathBuf>,
+
+    /// 文档形式：jsonl（首行文档头，每行一条记录）、json（单个文档）；md（Markdown）/ csv（表格）只用于阅读，不能再导入
+    #[arg(long, default_value = "jsonl", value_parser = ["jsonl", "json", "md", "csv"])]
+    pub format: String,
+
+    /// format=json 时输出 Pretty JSON
     #[arg(long)]
     pub pretty: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct BackupCommand {
+    /// 只备份该 namespace（默认备份全部）
+    #[arg(long)]
+    pub namespace: Option<String>,
+
+    /// 快照存放目录（每次备份在其下新建 memory-backup-<UTC 时间> 子目录）
+    #[arg(long, value_name = "DIR")]
+    pub out: PathBuf,
 
-    /// 输出文本摘要（如果同时提供 --pretty，则以 --text 为准）
+    /// 最多保留的快照个数（含本次），更早的快照会被删除
     #[arg(long)]
-    pub text: bool,
+    pub keep: Option<usize>,
+
+    /// 删除早于该天数的快照（本次快照总会保留）
+    #[arg(long = "keep-days")]
+    pub keep_days: Option<u64>,
+
+    #[command(flatten)]
+    pub format: OutputArgs,
 }
 
-impl RememberCommand {
-    fn into_args(self) -> Result<RememberArgs, String> {
-        if let Some(n) = self.importance {
-            if !(1..=5).contains(&n) {
-                return Err("importance 必须在 1~5".to_string());
-            }
-        }
+#[derive(Args, Debug)]
+pub struct SyncCommand {
+    /// 另一个存储根目录（如同步盘/挂载的另一台机器上的 MEMORY_ROOT）
+    #[arg(long, value_name = "DIR")]
+    pub remote: PathBuf,
 
-        let slice = resolve_inline_or_file("slice", self.slice, self.slice_file)?;
-        let diary = resolve_inline_or_file("diary", self.diary, self.diary_file)?;
+    /// 只同步该 namespace（默认同步两侧的全部 namespace）
+    #[arg(long)]
+    pub namespace: Option<String>,
 
-        Ok(RememberArgs {
-            namespace: self.namespace,
-            keywords: self.keywords,
-            slice,
-            diary,
-            occurred_at: self.occurred_at,
-            importance: self.importance,
-            source: self.source,
-        })
+    /// 只报告将要交换的记录，不写入
+    #[arg(long = "dry-run")]
+    pub dry_run: bool,
+
+    #[command(flatten)]
+    pub format: OutputArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct ImportCommand {
+    /// 导出文档路径（`-` 表示从 stdin 读取）
+    #[arg(long, visible_alias = "from", value_name = "PATH")]
+    pub input: PathBuf,
+
+    /// 只报告将新增 / 删除 / 跳过（重复 id）/ 冲突的条数，不写入任何内容
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// 目标 namespace（默认使用文档头中的 namespace）
+    #[arg(long)]
+    pub namespace: Option<String>,
+
+    /// id 已存在且内容不同时的处理：ask（逐条询问）/ keep-existing / keep-newest / duplicate
+    #[arg(
+        long,
+        default_value = "keep-existing",
+        value_parser = ["ask", "keep-existing", "keep-newest", "duplicate"]
+    )]
+    pub on_conflict: String,
+
+    #[command(flatten)]
+    pub format: OutputArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct DistillCommand {
+    /// 聊天记录 JSONL（每行一条消息：role/content/timestamp/session_id）
+    #[arg(long, value_name = "PATH", required_unless_present = "apply", conflicts_with = "apply")]
+    pub from: Option<PathBuf>,
+
+    /// 写入审阅后的暂存文件（distill --from 的输出；accept=false 的条目跳过）
+    #[arg(long, value_name = "PATH")]
+    pub apply: Option<PathBuf>,
+
+    /// 目标 namespace（--from 时必填；--apply 时覆盖暂存文件中的值）
+    #[arg(long, required_unless_present = "apply")]
+    pub namespace: Option<String>,
+
+    /// 暂存文件写入路径（默认输出到 stdout）
+    #[arg(long, value_name = "PATH", conflicts_with = "apply")]
+    pub output: Option<PathBuf>,
+
+    /// 相邻消息间隔超过该分钟数即切分为新会话
+    #[arg(
+        long = "session-gap",
+        default_value_t = DEFAULT_SESSION_GAP_MINUTES,
+        value_parser = clap::value_parser!(i64).range(1..)
+    )]
+    pub session_gap: i64,
+
+    /// 消息数少于该值的会话不生成候选
+    #[arg(long = "min-messages", default_value_t = 2)]
+    pub min_messages: usize,
+
+    /// 输出格式（仅 --apply）
+    #[command(flatten)]
+    pub format: OutputArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct SchemaCommand {
+    #[command(subcommand)]
+    pub command: SchemaSubcommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum SchemaSubcommand {
+    /// 输出全部 tool 的 inputSchema/outputSchema 与 MemoryItem 记录 schema
+    Dump(SchemaDumpCommand),
+
+    /// 将 tool 定义转换为 OpenAI function calling / Anthropic tool use 清单
+    Manifest(SchemaManifestCommand),
+}
+
+#[derive(Args, Debug)]
+pub struct SchemaManifestCommand {
+    /// 目标格式：openai / anthropic
+    #[arg(long, default_value = "openai")]
+    pub target: String,
+
+    /// 写入文件（默认输出到 stdout）
+    #[arg(long, value_name = "PATH")]
+    pub output: Option<PathBuf>,
+
+    /// 输出 Pretty JSON
+    #[arg(long)]
+    pub pretty: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct SchemaDumpCommand {
+    /// 写入目录：每个 schema 一个独立文件（默认汇总为单个 JSON 输出到 stdout）
+    #[arg(long, value_name = "DIR")]
+    pub out_dir: Option<PathBuf>,
+
+    /// 输出 Pretty JSON（仅 stdout 模式）
+    #[arg(long)]
+    pub pretty: bool,
+}
+
+impl RememberCommand {
+    /// `stdin` 只在 `--stdin-json` 或 `--slice -` / `--diary -` 时读取。
+    fn into_args(self, stdin: &mut dyn Read) -> Result<RememberArgs, String> {
+        if self.stdin_json {
+            let text = read_stdin_text(stdin)?;
+            let mut v: Value =
+                serde_json::from_str(&text).map_err(|e| format!("stdin 不是有效的 JSON：{e}"))?;
+            let Some(obj) = v.as_object_mut() else {
+                return Err("stdin 必须是 remember 参数的 JSON 对象".to_string());
+            };
+            if let Some(namespace) = self.namespace {
+                obj.entry("namespace").or_insert(Value::String(namespace));
+            }
+            return RememberArgs::from_json(&v);
+        }
+
+        if let Some(n) = self.importance {
+            if !(1..=5).contains(&n) {
+                return Err("importance 必须在 1~5".to_string());
+            }
+        }
+
+        if self.slice.as_deref() == Some("-") && self.diary.as_deref() == Some("-") {
+            return Err("slice 与 diary 只能有一个来自 stdin（--slice - / --diary -）".to_string());
+        }
+        let mut resolve = |name: &str, inline: Option<String>, file: Option<PathBuf>| match inline.as_deref() {
+            Some("-") => read_stdin_text(stdin),
+            _ => resolve_inline_or_file(name, inline, file),
+        };
+        let slice = resolve("slice", self.slice, self.slice_file)?;
+        let diary = resolve("diary", self.diary, self.diary_file)?;
+        let keyword_weights = parse_keyword_weights(&self.weights, &self.keywords)?;
+        let source = MemorySource {
+            kind: self.source_kind,
+            id: self.source_id,
+            label: self.source,
+            url: self.source_url,
+        }
+        .normalized()?;
+        let metadata = match self.metadata.as_deref() {
+            Some(text) => {
+                let v: Value = serde_json::from_str(text).map_err(|e| format!("--metadata 不是有效的 JSON：{e}"))?;
+                parse_metadata(&v)?
+            }
+            None => Default::default(),
+        };
+
+        Ok(RememberArgs {
+            namespace: self.namespace.unwrap_or_default(),
+            keywords: self.keywords,
+            keyword_weights,
+            tags: self.tags,
+            kind: self.kind,
+            slice,
+            diary,
+            occurred_at: self.occurred_at,
+            importance: self.importance,
+            source,
+            metadata,
+            relates_to: self.relates_to,
+            supersedes: self.supersedes,
+            expires_at: self.expires_at,
+            pinned: self.pinned,
+        })
+    }
+}
+
+/// 解析 `--weight KEYWORD=N`；关键字需同时通过 `--keyword` 提供（大小写不敏感）。
+fn parse_half_life_days(text: &str) -> Result<f64, String> {
+    let days = text
+        .trim()
+        .parse::<f64>()
+        .map_err(|_| format!("half-life-days 不是数字：{text}"))?;
+    check_half_life_days(days)
+}
+
+fn parse_keyword_weights(specs: &[String], keywords: &[String]) -> Result<BTreeMap<String, u8>, String> {
+    let mut out = BTreeMap::new();
+    for spec in specs {
+        let Some((kw, n)) = spec.rsplit_once('=') else {
+            return Err(format!("--weight 格式应为 KEYWORD=N：{spec}"));
+        };
+        let kw = kw.trim();
+        if !keywords.iter().any(|x| x.trim().to_lowercase() == kw.to_lowercase()) {
+            return Err(format!("--weight 指定的关键字未通过 --keyword 提供：{kw}"));
+        }
+        let n: u8 = n
+            .trim()
+            .parse()
+            .ok()
+            .filter(|n| (1..=KEYWORD_WEIGHT_MAX).contains(n))
+            .ok_or_else(|| format!("weight 必须是 1~{KEYWORD_WEIGHT_MAX} 的整数（keyword={kw}）"))?;
+        out.insert(kw.to_string(), n);
+    }
+    Ok(out)
+}
+
+impl RecallCommand {
+    fn into_args(self) -> RecallArgs {
+        // 上限由引擎按服务端配置与 namespace 设置截断。
+        let limit = if self.limit == 0 { RECALL_DEFAULT_LIMIT } else { self.limit };
+
+        RecallArgs {
+            namespace: self.namespace,
+            keywords: self.keywords,
+            tags: self.tags,
+            kinds: self.kinds,
+            start: self.start,
+            end: self.end,
+            query: self.query,
+            regex: self.regex,
+            semantic: self.semantic,
+            limit,
+            max_bytes: self.max_bytes.filter(|&n| n > 0),
+            include_superseded: self.include_superseded,
+            include_archived: self.include_archived,
+            include_diary: self.include_diary,
+            format: if self.compact {
+                RecallFormat::Compact
+            } else {
+                RecallFormat::Full
+            },
+            ranking: self.ranking,
+            half_life_days: self.half_life_days,
+            if_none_match: None,
+            written_by: self.written_by,
+            source_kind: self.source_kind.map(|x| x.trim().to_lowercase()).filter(|x| !x.is_empty()),
+            source_id: self.source_id.map(|x| x.trim().to_string()).filter(|x| !x.is_empty()),
+            offset: self.offset,
+            cursor: self.cursor,
+        }
+    }
+}
+
+/// 一次调用使用的存储：数据根目录与 `--backend` 指定的后端（未指定时沿用 MEMORY_BACKEND）。
+struct Store {
+    root_dir: PathBuf,
+    backend: Option<StorageBackend>,
+}
+
+impl Store {
+    fn open(self) -> MemoryEngine {
+        let engine = MemoryEngine::new(self.root_dir);
+        match self.backend {
+            Some(backend) => engine.with_backend(backend),
+            None => engine,
+        }
+    }
+}
+
+pub fn run_one_shot(root_dir: PathBuf, argv: Vec<String>) -> i32 {
+    let cli = match Cli::try_parse_from(&argv) {
+        Ok(v) => v,
+        Err(e) => {
+            let code = e.exit_code();
+            let _ = e.print();
+            return code;
+        }
+    };
+
+    let Some(cmd) = cli.command else {
+        let mut c = Cli::command();
+        let _ = c.print_help();
+        let _ = io::stdout().write_all(b"\n");
+        return 2;
+    };
+    let store = Store {
+        root_dir,
+        backend: cli.backend,
+    };
+
+    match cmd {
+        Command::Remember(cmd) => run_remember(store, cmd),
+        Command::Recall(cmd) => run_recall(store, cmd),
+        Command::Now(cmd) => run_now(store, cmd),
+        Command::Keywords(cmd) => run_keywords(store, cmd),
+        Command::Namespaces(cmd) => run_namespaces(store, cmd),
+        Command::Stats(cmd) => run_stats(store, cmd),
+        Command::PurgeExpired(cmd) => run_purge_expired(store, cmd),
+        Command::Compact(cmd) => run_compact(store, cmd),
+        Command::Reindex(cmd) => run_reindex(store, cmd),
+        Command::Maintain(cmd) => run_maintain(store, cmd),
+        Command::Verify(cmd) => run_verify(store, cmd),
+        Command::Archive(cmd) => run_archive(store, cmd),
+        Command::LeastRecalled(cmd) => run_least_recalled(store, cmd),
+        Command::Migrate(cmd) => run_migrate(store, cmd),
+        Command::Restore(cmd) => run_restore(store, cmd),
+        Command::Export(cmd) => run_export(store, cmd),
+        Command::Backup(cmd) => run_backup(store, cmd),
+        Command::Import(cmd) => run_import(store, cmd),
+        Command::Sync(cmd) => run_sync(store, cmd),
+        Command::Schema(cmd) => run_schema(cmd),
+        Command::Distill(cmd) => run_distill(store, cmd),
+    }
+}
+
+fn run_remember(store: Store, cmd: RememberCommand) -> i32 {
+    let format = cmd.format.resolve();
+    let args = match cmd.into_args(&mut io::stdin()) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("{e}");
+            return 1;
+        }
+    };
+
+    let mut engine = store.open();
+    let result = match engine.remember(args) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("{e}");
+            return 1;
+        }
+    };
+
+    match output::render(&result, format) {
+        Ok(text) => {
+            println!("{text}");
+            0
+        }
+        Err(e) => {
+            eprintln!("{e}");
+            1
+        }
+    }
+}
+
+fn run_recall(store: Store, cmd: RecallCommand) -> i32 {
+    let format = cmd.format.resolve();
+    let args = cmd.into_args();
+
+    let mut engine = store.open();
+    let result = match engine.recall(args) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("{e}");
+            return 1;
+        }
+    };
+
+    match output::render(&result, format) {
+        Ok(text) => {
+            println!("{text}");
+            0
+        }
+        Err(e) => {
+            eprintln!("{e}");
+            1
+        }
+    }
+}
+
+fn run_now(store: Store, cmd: NowCommand) -> i32 {
+    let format = cmd.format.resolve();
+    let engine = store.open();
+    let result = match engine.now() {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("{e}");
+            return 1;
+        }
+    };
+
+    match output::render(&result, format) {
+        Ok(text) => {
+            println!("{text}");
+            0
+        }
+        Err(e) => {
+            eprintln!("{e}");
+            1
+        }
+    }
+}
+
+fn run_keywords(store: Store, cmd: KeywordsCommand) -> i32 {
+    match cmd.command {
+        KeywordsSubcommand::List(cmd) => run_keywords_list(store, cmd),
+        KeywordsSubcommand::ListGlobal(cmd) => run_keywords_list_global(store, cmd),
+        KeywordsSubcommand::Complete(cmd) => run_keywords_complete(store, cmd),
+        KeywordsSubcommand::Audit(cmd) => run_keywords_audit(store, cmd),
+    }
+}
+
+fn run_keywords_audit(store: Store, cmd: KeywordsAuditCommand) -> i32 {
+    let format = cmd.format.resolve();
+    let mut engine = store.open();
+    let result = match engine.keywords_audit(cmd.namespace, cmd.limit.max(1)) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("{e}");
+            return 1;
+        }
+    };
+
+    match output::render(&result, format) {
+        Ok(text) => {
+            println!("{text}");
+            0
+        }
+        Err(e) => {
+            eprintln!("{e}");
+            1
+        }
+    }
+}
+
+fn run_namespaces(store: Store, cmd: NamespacesCommand) -> i32 {
+    match cmd.command {
+        NamespacesSubcommand::List(cmd) => run_namespaces_list(store, cmd),
+        NamespacesSubcommand::Freeze(cmd) => run_namespaces_freeze(store, cmd),
+        NamespacesSubcommand::Unfreeze(cmd) => run_namespaces_unfreeze(store, cmd),
+        NamespacesSubcommand::Settings(cmd) => run_namespaces_settings(store, cmd),
+    }
+}
+
+fn run_namespaces_list(store: Store, cmd: NamespacesListCommand) -> i32 {
+    let format = cmd.format.resolve();
+    let engine = store.open();
+    let result = match engine.namespaces_list(cmd.refresh) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("{e}");
+            return 1;
+        }
+    };
+
+    match output::render(&result, format) {
+        Ok(text) => {
+            println!("{text}");
+            0
+        }
+        Err(e) => {
+            eprintln!("{e}");
+            1
+        }
+    }
+}
+
+fn run_namespaces_freeze(store: Store, cmd: NamespacesFreezeCommand) -> i32 {
+    let format = cmd.format.resolve();
+    let mut engine = store.open();
+    let result = match engine.namespace_freeze(cmd.namespace, cmd.reason) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("{e}");
+            return 1;
+        }
+    };
+
+    match output::render(&result, format) {
+        Ok(text) => {
+            println!("{text}");
+            0
+        }
+        Err(e) => {
+            eprintln!("{e}");
+            1
+        }
+    }
+}
+
+fn run_namespaces_unfreeze(store: Store, cmd: NamespacesUnfreezeCommand) -> i32 {
+    let format = cmd.format.resolve();
+    let mut engine = store.open();
+    let result = match engine.namespace_unfreeze(cmd.namespace) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("{e}");
+            return 1;
+        }
+    };
+
+    match output::render(&result, format) {
+        Ok(text) => {
+            println!("{text}");
+            0
+        }
+        Err(e) => {
+            eprintln!("{e}");
+            1
+        }
+    }
+}
+
+fn run_namespaces_settings(store: Store, cmd: NamespacesSettingsCommand) -> i32 {
+    let format = cmd.format.resolve();
+    let update = (cmd.recall_max_limit.is_some() || cmd.reset_recall_max_limit).then_some(NamespaceSettings {
+        recall_max_limit: cmd.recall_max_limit,
+    });
+    let mut engine = store.open();
+    let result = match engine.namespace_settings(cmd.namespace, update) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("{e}");
+            return 1;
+        }
+    };
+
+    match output::render(&result, format) {
+        Ok(text) => {
+            println!("{text}");
+            0
+        }
+        Err(e) => {
+            eprintln!("{e}");
+            1
+        }
+    }
+}
+
+fn run_stats(store: Store, cmd: StatsCommand) -> i32 {
+    let format = cmd.format.resolve();
+    let mut engine = store.open();
+    let result = match engine.stats(cmd.namespace) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("{e}");
+            return 1;
+        }
+    };
+
+    match output::render(&result, format) {
+        Ok(text) => {
+            println!("{text}");
+            0
+        }
+        Err(e) => {
+            eprintln!("{e}");
+            1
+        }
+    }
+}
+
+fn run_least_recalled(store: Store, cmd: LeastRecalledCommand) -> i32 {
+    let format = cmd.format.resolve();
+    let limit = if cmd.limit == 0 { RECALL_DEFAULT_LIMIT } else { cmd.limit };
+    let mut engine = store.open();
+    let result = match engine.least_recalled(cmd.namespace, limit) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("{e}");
+            return 1;
+        }
+    };
+
+    match output::render(&result, format) {
+        Ok(text) => {
+            println!("{text}");
+            0
+        }
+        Err(e) => {
+            eprintln!("{e}");
+            1
+        }
+    }
+}
+
+fn run_archive(store: Store, cmd: ArchiveCommand) -> i32 {
+    let format = cmd.format.resolve();
+    let args = ArchiveArgs {
+        namespace: cmd.namespace,
+        ids: cmd.ids,
+        start: cmd.start,
+        end: cmd.end,
+        archived: !cmd.unarchive,
+    };
+    let mut engine = store.open();
+    let result = match engine.archive(args) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("{e}");
+            return 1;
+        }
+    };
+
+    match output::render(&result, format) {
+        Ok(text) => {
+            println!("{text}");
+            0
+        }
+        Err(e) => {
+            eprintln!("{e}");
+            1
+        }
+    }
+}
+
+fn run_compact(store: Store, cmd: CompactCommand) -> i32 {
+    let format = cmd.format.resolve();
+    let mut engine = store.open();
+    let result = match cmd.namespace {
+        Some(namespace) => engine.compact(namespace),
+        None => engine.compact_all(),
+    };
+    let result = match result {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("{e}");
+            return 1;
+        }
+    };
+
+    match output::render(&result, format) {
+        Ok(text) => {
+            println!("{text}");
+            0
+        }
+        Err(e) => {
+            eprintln!("{e}");
+            1
+        }
+    }
+}
+
+fn run_reindex(store: Store, cmd: ReindexCommand) -> i32 {
+    let format = cmd.format.resolve();
+    let mut engine = store.open();
+    let result = match engine.reindex(cmd.namespace.as_deref()) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("{e}");
+            return 1;
+        }
+    };
+
+    match output::render(&result, format) {
+        Ok(text) => {
+            println!("{text}");
+            0
+        }
+        Err(e) => {
+            eprintln!("{e}");
+            1
+        }
+    }
+}
+
+fn run_verify(store: Store, cmd: VerifyCommand) -> i32 {
+    let format = cmd.format.resolve();
+    let mut engine = store.open();
+    let result = match engine.verify(cmd.namespace, cmd.repair) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("{e}");
+            return 1;
+        }
+    };
+
+    match output::render(&result, format) {
+        Ok(text) => {
+            println!("{text}");
+            0
+        }
+        Err(e) => {
+            eprintln!("{e}");
+            1
+        }
+    }
+}
+
+fn run_maintain(store: Store, cmd: MaintainCommand) -> i32 {
+    let format = cmd.format.resolve();
+    let mut engine = store.open();
+    let result = match engine.maintain(cmd.namespace, cmd.half_life_days, cmd.dry_run) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("{e}");
+            return 1;
+        }
+    };
+
+    match output::render(&result, format) {
+        Ok(text) => {
+            println!("{text}");
+            0
+        }
+        Err(e) => {
+            eprintln!("{e}");
+            1
+        }
+    }
+}
+
+fn run_purge_expired(store: Store, cmd: PurgeExpiredCommand) -> i32 {
+    let format = cmd.format.resolve();
+    let mut engine = store.open();
+    let result = match engine.purge_expired(cmd.namespace) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("{e}");
+            return 1;
+        }
+    };
+
+    match output::render(&result, format) {
+        Ok(text) => {
+            println!("{text}");
+            0
+        }
+        Err(e) => {
+            eprintln!("{e}");
+            1
+        }
+    }
+}
+
+fn run_keywords_list(store: Store, cmd: KeywordsListCommand) -> i32 {
+    let format = cmd.format.resolve();
+    let sort = match KeywordSort::parse(&cmd.sort) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("{e}");
+            return 1;
+        }
+    };
+
+    let mut engine = store.open();
+    let result = match engine.keywords_list(cmd.namespace, sort) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("{e}");
+            return 1;
+        }
+    };
+
+    match output::render(&result, format) {
+        Ok(text) => {
+            println!("{text}");
+            0
+        }
+        Err(e) => {
+            eprintln!("{e}");
+            1
+        }
     }
 }
 
-impl RecallCommand {
-    fn into_args(self) -> RecallArgs {
-        let mut limit = self.limit;
-        if limit == 0 {
-            limit = 20;
+fn run_keywords_list_global(store: Store, cmd: KeywordsListGlobalCommand) -> i32 {
+    let format = cmd.format.resolve();
+    let sort = match KeywordSort::parse(&cmd.sort) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("{e}");
+            return 1;
         }
-        if limit > 100 {
-            limit = 100;
+    };
+
+    let engine = store.open();
+    let result = match engine.keywords_list_global(sort) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("{e}");
+            return 1;
         }
+    };
 
-        RecallArgs {
-            namespace: self.namespace,
-            keywords: self.keywords,
-            start: self.start,
-            end: self.end,
-            query: self.query,
-            limit,
-            include_diary: self.include_diary,
+    match output::render(&result, format) {
+        Ok(text) => {
+            println!("{text}");
+            0
+        }
+        Err(e) => {
+            eprintln!("{e}");
+            1
         }
     }
 }
 
-pub fn run_one_shot(root_dir: PathBuf, argv: Vec<String>) -> i32 {
-    let cli = match Cli::try_parse_from(&argv) {
+fn run_keywords_complete(store: Store, cmd: KeywordsCompleteCommand) -> i32 {
+    let format = cmd.format.resolve();
+    let mode = match KeywordMatchMode::parse(&cmd.match_mode) {
         Ok(v) => v,
         Err(e) => {
-            let code = e.exit_code();
-            let _ = e.print();
-            return code;
+            eprintln!("{e}");
+            return 1;
         }
     };
+    let args = KeywordsCompleteArgs {
+        namespace: cmd.namespace,
+        text: cmd.fragment,
+        mode,
+        limit: cmd.limit.clamp(1, 100),
+    };
 
-    let Some(cmd) = cli.command else {
-        let mut c = Cli::command();
-        let _ = c.print_help();
-        let _ = io::stdout().write_all(b"\n");
-        return 2;
+    let mut engine = store.open();
+    let result = match engine.keywords_complete(args) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("{e}");
+            return 1;
+        }
     };
 
-    match cmd {
-        Command::Remember(cmd) => run_remember(root_dir, cmd),
-        Command::Recall(cmd) => run_recall(root_dir, cmd),
-        Command::Now(cmd) => run_now(root_dir, cmd),
-        Command::Keywords(cmd) => run_keywords(root_dir, cmd),
+    match output::render(&result, format) {
+        Ok(text) => {
+            println!("{text}");
+            0
+        }
+        Err(e) => {
+            eprintln!("{e}");
+            1
+        }
     }
 }
 
-fn run_remember(root_dir: PathBuf, cmd: RememberCommand) -> i32 {
-    let prefer_text = cmd.text;
-    let pretty = cmd.pretty && !prefer_text;
-
-    let args = match cmd.into_args() {
+fn run_migrate(store: Store, cmd: MigrateCommand) -> i32 {
+    let format = cmd.format.resolve();
+    let mut engine = store.open();
+    let result = match engine.migrate() {
         Ok(v) => v,
         Err(e) => {
             eprintln!("{e}");
@@ -246,8 +1468,27 @@ fn run_remember(root_dir: PathBuf, cmd: RememberCommand) -> i32 {
         }
     };
 
-    let mut engine = MemoryEngine::new(root_dir);
-    let result = match engine.remember(args) {
+    match output::render(&result, format) {
+        Ok(text) => {
+            println!("{text}");
+            0
+        }
+        Err(e) => {
+            eprintln!("{e}");
+            1
+        }
+    }
+}
+
+fn run_restore(store: Store, cmd: RestoreCommand) -> i32 {
+    let format = cmd.format.resolve();
+    let mut engine = store.open();
+    let result = match (&cmd.from, &cmd.namespace, &cmd.at) {
+        (Some(from), namespace, _) => engine.restore_from(from, namespace.as_deref()),
+        (None, Some(namespace), Some(at)) => engine.restore_at(namespace, at),
+        _ => Err("restore 需要 --from，或同时提供 --namespace 与 --at".to_string()),
+    };
+    let result = match result {
         Ok(v) => v,
         Err(e) => {
             eprintln!("{e}");
@@ -255,9 +1496,9 @@ fn run_remember(root_dir: PathBuf, cmd: RememberCommand) -> i32 {
         }
     };
 
-    match format_tool_result(&result, prefer_text, pretty) {
+    match output::render(&result, format) {
         Ok(text) => {
-            print!("{text}\n");
+            println!("{text}");
             0
         }
         Err(e) => {
@@ -267,14 +1508,14 @@ fn run_remember(root_dir: PathBuf, cmd: RememberCommand) -> i32 {
     }
 }
 
-fn run_recall(root_dir: PathBuf, cmd: RecallCommand) -> i32 {
-    let prefer_text = cmd.text;
-    let pretty = cmd.pretty && !prefer_text;
-
-    let args = cmd.into_args();
-
-    let mut engine = MemoryEngine::new(root_dir);
-    let result = match engine.recall(args) {
+fn run_backup(store: Store, cmd: BackupCommand) -> i32 {
+    let format = cmd.format.resolve();
+    let engine = store.open();
+    let retention = Retention {
+        keep: cmd.keep,
+        keep_days: cmd.keep_days,
+    };
+    let result = match engine.backup(cmd.namespace.as_deref(), &cmd.out, retention) {
         Ok(v) => v,
         Err(e) => {
             eprintln!("{e}");
@@ -282,9 +1523,9 @@ fn run_recall(root_dir: PathBuf, cmd: RecallCommand) -> i32 {
         }
     };
 
-    match format_tool_result(&result, prefer_text, pretty) {
+    match output::render(&result, format) {
         Ok(text) => {
-            print!("{text}\n");
+            println!("{text}");
             0
         }
         Err(e) => {
@@ -294,12 +1535,10 @@ fn run_recall(root_dir: PathBuf, cmd: RecallCommand) -> i32 {
     }
 }
 
-fn run_now(root_dir: PathBuf, cmd: NowCommand) -> i32 {
-    let prefer_text = cmd.text;
-    let pretty = cmd.pretty && !prefer_text;
-
-    let engine = MemoryEngine::new(root_dir);
-    let result = match engine.now() {
+fn run_sync(store: Store, cmd: SyncCommand) -> i32 {
+    let format = cmd.format.resolve();
+    let mut engine = store.open();
+    let result = match engine.sync(&cmd.remote, cmd.namespace.as_deref(), cmd.dry_run) {
         Ok(v) => v,
         Err(e) => {
             eprintln!("{e}");
@@ -307,9 +1546,9 @@ fn run_now(root_dir: PathBuf, cmd: NowCommand) -> i32 {
         }
     };
 
-    match format_tool_result(&result, prefer_text, pretty) {
+    match output::render(&result, format) {
         Ok(text) => {
-            print!("{text}\n");
+            println!("{text}");
             0
         }
         Err(e) => {
@@ -319,19 +1558,102 @@ fn run_now(root_dir: PathBuf, cmd: NowCommand) -> i32 {
     }
 }
 
-fn run_keywords(root_dir: PathBuf, cmd: KeywordsCommand) -> i32 {
-    match cmd.command {
-        KeywordsSubcommand::List(cmd) => run_keywords_list(root_dir, cmd),
-        KeywordsSubcommand::ListGlobal(cmd) => run_keywords_list_global(root_dir, cmd),
+fn run_export(store: Store, cmd: ExportCommand) -> i32 {
+    let engine = store.open();
+    let dump = match engine.export_dump(&cmd.namespace, cmd.since.as_deref()) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("{e}");
+            return 1;
+        }
+    };
+
+    let write = |w: &mut dyn Write| -> io::Result<()> {
+        let mut w = io::BufWriter::new(w);
+        match cmd.format.as_str() {
+            "json" => dump.write_json(&mut w, cmd.pretty)?,
+            "md" => dump.write_markdown(&mut w)?,
+            "csv" => dump.write_csv(&mut w)?,
+            _ => dump.write_jsonl(&mut w)?,
+        }
+        w.flush()
+    };
+    let written = match cmd.output.as_deref() {
+        Some(path) => std::fs::File::create(path).and_then(|mut f| write(&mut f)),
+        None => write(&mut io::stdout().lock()),
+    };
+    if let Err(e) = written {
+        eprintln!("导出失败：{e}");
+        return 1;
+    }
+
+    if let Some(path) = cmd.output.as_deref() {
+        println!(
+            "已导出 namespace={}：{} 条记忆，{} 条删除标记 -> {}（next_seq={}）",
+            dump.header.namespace,
+            dump.header.items,
+            dump.header.deletes,
+            path.display(),
+            dump.header.next_seq
+        );
     }
+    0
 }
 
-fn run_keywords_list(root_dir: PathBuf, cmd: KeywordsListCommand) -> i32 {
-    let prefer_text = cmd.text;
-    let pretty = cmd.pretty && !prefer_text;
+fn run_import(store: Store, cmd: ImportCommand) -> i32 {
+    let format = cmd.format.resolve();
+    // 预演不逐条询问：冲突按保留现有版本报告。
+    let interactive = cmd.on_conflict == "ask" && !cmd.dry_run;
+    if interactive && cmd.input.as_os_str() == "-" {
+        eprintln!("--on-conflict ask 需要从 stdin 读取回答：请用 --input 指定文件");
+        return 1;
+    }
+    let policy = if cmd.on_conflict == "ask" {
+        ConflictPolicy::default()
+    } else {
+        match ConflictPolicy::parse(&cmd.on_conflict) {
+            Ok(v) => v,
+            Err(e) => {
+                eprintln!("{e}");
+                return 1;
+            }
+        }
+    };
+
+    let text = if cmd.input.as_os_str() == "-" {
+        let mut buf = String::new();
+        io::stdin().read_to_string(&mut buf).map(|_| buf)
+    } else {
+        std::fs::read_to_string(&cmd.input)
+    };
+    let text = match text {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("读取导出文档失败：{e}");
+            return 1;
+        }
+    };
+
+    // 交互模式下大写回答会应用到后续全部冲突。
+    let mut sticky: Option<ConflictAction> = None;
+    let mut resolve = |existing: &MemoryItem, incoming: &MemoryItem| {
+        if !interactive {
+            return policy.decide(existing, incoming);
+        }
+        if let Some(action) = sticky {
+            return action;
+        }
+        let (action, all) = prompt_conflict(existing, incoming);
+        if all {
+            sticky = Some(action);
+        }
+        action
+    };
 
-    let mut engine = MemoryEngine::new(root_dir);
-    let result = match engine.keywords_list(cmd.namespace) {
+    let mut engine = store.open();
+    let result = match ExportDump::parse(&text)
+        .and_then(|dump| engine.import(cmd.namespace, dump, &mut resolve, cmd.dry_run))
+    {
         Ok(v) => v,
         Err(e) => {
             eprintln!("{e}");
@@ -339,9 +1661,9 @@ fn run_keywords_list(root_dir: PathBuf, cmd: KeywordsListCommand) -> i32 {
         }
     };
 
-    match format_tool_result(&result, prefer_text, pretty) {
+    match output::render(&result, format) {
         Ok(text) => {
-            print!("{text}\n");
+            println!("{text}");
             0
         }
         Err(e) => {
@@ -351,12 +1673,126 @@ fn run_keywords_list(root_dir: PathBuf, cmd: KeywordsListCommand) -> i32 {
     }
 }
 
-fn run_keywords_list_global(root_dir: PathBuf, cmd: KeywordsListGlobalCommand) -> i32 {
-    let prefer_text = cmd.text;
-    let pretty = cmd.pretty && !prefer_text;
+/// 在 stderr 展示冲突双方并从 stdin 读取选择；返回 (处理方式, 是否应用到后续全部冲突)。
+fn prompt_conflict(existing: &MemoryItem, incoming: &MemoryItem) -> (ConflictAction, bool) {
+    let describe = |item: &MemoryItem| {
+        format!(
+            "revision={} 变更于 {} keywords={} slice={}",
+            item.revision,
+            item.updated_at.as_deref().unwrap_or(&item.recorded_at),
+            item.keywords.join(","),
+            item.slice.replace('\n', " ")
+        )
+    };
+
+    eprintln!("id 冲突：{}", existing.id);
+    eprintln!("  现有：{}", describe(existing));
+    eprintln!("  导入：{}", describe(incoming));
+    loop {
+        eprint!("保留现有 [e] / 采用导入 [i] / 另存副本 [d]（大写表示后续全部冲突同样处理）：");
+        let _ = io::stderr().flush();
+
+        let mut answer = String::new();
+        match io::stdin().read_line(&mut answer) {
+            // stdin 已关闭：保守地保留现有版本。
+            Ok(0) | Err(_) => return (ConflictAction::KeepExisting, true),
+            Ok(_) => {}
+        }
+        match answer.trim() {
+            "e" => return (ConflictAction::KeepExisting, false),
+            "i" => return (ConflictAction::Replace, false),
+            "d" => return (ConflictAction::Duplicate, false),
+            "E" => return (ConflictAction::KeepExisting, true),
+            "I" => return (ConflictAction::Replace, true),
+            "D" => return (ConflictAction::Duplicate, true),
+            _ => {}
+        }
+    }
+}
+
+fn run_distill(store: Store, cmd: DistillCommand) -> i32 {
+    let Some(from) = cmd.from.as_deref() else {
+        return run_distill_apply(store, cmd);
+    };
+    let namespace = cmd.namespace.as_deref().unwrap_or_default();
+
+    let text = match std::fs::read_to_string(from) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("读取聊天记录失败：{e}");
+            return 1;
+        }
+    };
+    let source_name = from
+        .file_name()
+        .map(|x| x.to_string_lossy().into_owned())
+        .unwrap_or_else(|| from.display().to_string());
+    let (staged, skipped) =
+        stage_chat_log(&text, namespace, &source_name, cmd.session_gap, cmd.min_messages);
+
+    let json = match serde_json::to_string_pretty(&staged) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("输出暂存文件失败：{e}");
+            return 1;
+        }
+    };
+
+    let mut summary = format!("已生成 {} 条候选记忆", staged.proposals.len());
+    if !skipped.is_empty() {
+        summary.push_str(&format!("（跳过 {} 行无法解析的记录）", skipped.len()));
+    }
+
+    let Some(path) = cmd.output.as_deref() else {
+        println!("{json}");
+        eprintln!("{summary}");
+        return 0;
+    };
+    match std::fs::write(path, json + "\n") {
+        Ok(()) => {
+            println!(
+                "{summary} -> {}；审阅（可修改或将 accept 设为 false）后执行 distill --apply {} 写入",
+                path.display(),
+                path.display()
+            );
+            0
+        }
+        Err(e) => {
+            eprintln!("写入暂存文件失败：{e}");
+            1
+        }
+    }
+}
+
+fn run_distill_apply(store: Store, cmd: DistillCommand) -> i32 {
+    let format = cmd.format.resolve();
+    let Some(path) = cmd.apply.as_deref() else {
+        eprintln!("需要 --from 或 --apply");
+        return 1;
+    };
+    let staged = match std::fs::read_to_string(path)
+        .map_err(|e| format!("读取暂存文件失败：{e}"))
+        .and_then(|text| StagedDistill::parse(&text))
+    {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("{e}");
+            return 1;
+        }
+    };
+
+    let items: Vec<Result<RememberArgs, String>> = staged
+        .into_remember_args(cmd.namespace.as_deref())
+        .into_iter()
+        .map(Ok)
+        .collect();
+    if items.is_empty() {
+        println!("暂存文件中没有已接受的候选记忆。");
+        return 0;
+    }
 
-    let engine = MemoryEngine::new(root_dir);
-    let result = match engine.keywords_list_global() {
+    let mut engine = store.open();
+    let result = match engine.remember_batch(items) {
         Ok(v) => v,
         Err(e) => {
             eprintln!("{e}");
@@ -364,9 +1800,9 @@ fn run_keywords_list_global(root_dir: PathBuf, cmd: KeywordsListGlobalCommand) -
         }
     };
 
-    match format_tool_result(&result, prefer_text, pretty) {
+    match output::render(&result, format) {
         Ok(text) => {
-            print!("{text}\n");
+            println!("{text}");
             0
         }
         Err(e) => {
@@ -376,35 +1812,82 @@ fn run_keywords_list_global(root_dir: PathBuf, cmd: KeywordsListGlobalCommand) -
     }
 }
 
-fn format_tool_result(result: &Value, prefer_text: bool, pretty: bool) -> Result<String, String> {
-    if prefer_text {
-        if let Some(text) = extract_primary_text(result) {
-            return Ok(text);
-        }
+fn run_schema(cmd: SchemaCommand) -> i32 {
+    match cmd.command {
+        SchemaSubcommand::Dump(cmd) => run_schema_dump(cmd),
+        SchemaSubcommand::Manifest(cmd) => run_schema_manifest(cmd),
     }
-
-    format_json(result, pretty)
 }
 
-fn format_json(v: &Value, pretty: bool) -> Result<String, String> {
-    if pretty {
-        serde_json::to_string_pretty(v).map_err(|e| format!("输出 JSON 失败：{e}"))
+fn run_schema_manifest(cmd: SchemaManifestCommand) -> i32 {
+    let target = match schema::ManifestTarget::parse(&cmd.target) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("{e}");
+            return 1;
+        }
+    };
+
+    let manifest = schema::manifest(target);
+    let text = if cmd.pretty {
+        serde_json::to_string_pretty(&manifest)
     } else {
-        Ok(v.to_string())
+        serde_json::to_string(&manifest)
+    };
+    let text = match text {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("{e}");
+            return 1;
+        }
+    };
+
+    let Some(path) = cmd.output.as_deref() else {
+        println!("{text}");
+        return 0;
+    };
+    match std::fs::write(path, text + "\n") {
+        Ok(()) => {
+            println!("已写入 {} 个 tool 定义 -> {}", manifest.as_array().map_or(0, |x| x.len()), path.display());
+            0
+        }
+        Err(e) => {
+            eprintln!("写入清单失败：{e}");
+            1
+        }
     }
 }
 
-fn extract_primary_text(result: &Value) -> Option<String> {
-    let content = result.get("content")?.as_array()?;
-    for item in content {
-        if let Some(text) = item.get("text").and_then(|x| x.as_str()) {
-            let text = text.trim();
-            if !text.is_empty() {
-                return Some(text.to_string());
+fn run_schema_dump(cmd: SchemaDumpCommand) -> i32 {
+    let Some(dir) = cmd.out_dir.as_deref() else {
+        let bundle = schema::bundle();
+        let text = if cmd.pretty {
+            serde_json::to_string_pretty(&bundle)
+        } else {
+            serde_json::to_string(&bundle)
+        };
+        return match text {
+            Ok(text) => {
+                println!("{text}");
+                0
             }
+            Err(e) => {
+                eprintln!("{e}");
+                1
+            }
+        };
+    };
+
+    match schema::write_all(dir) {
+        Ok(files) => {
+            println!("已写入 {} 个 schema 文件 -> {}", files.len(), dir.display());
+            0
+        }
+        Err(e) => {
+            eprintln!("{e}");
+            1
         }
     }
-    None
 }
 
 fn resolve_inline_or_file(
@@ -424,6 +1907,14 @@ fn resolve_inline_or_file(
         .map_err(|e| format!("读取 {name} 失败：{e}"))
 }
 
+/// 读取 stdin 的全部内容（去掉 UTF-8 BOM 与末尾换行）。
+fn read_stdin_text(stdin: &mut dyn Read) -> Result<String, String> {
+    let mut bytes = Vec::new();
+    stdin.read_to_end(&mut bytes).map_err(|e| format!("读取 stdin 失败：{e}"))?;
+    let text = String::from_utf8(strip_utf8_bom(&bytes).to_vec()).map_err(|e| format!("stdin 不是有效的 UTF-8：{e}"))?;
+    Ok(text.trim_end_matches(['\r', '\n']).to_string())
+}
+
 fn read_utf8_file_strip_bom(path: &Path) -> Result<String, String> {
     let bytes = std::fs::read(path)
         .map_err(|e| format!("{}: {e}", path.display()))?;
@@ -461,6 +1952,21 @@ mod tests {
         assert!(Cli::try_parse_from(args).is_err());
     }
 
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn backend_flag_should_reach_the_engine_without_setting_env() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let argv = [
+            "memory", "--backend", "memory", "remember", "--namespace", "u1/p1", "--keyword", "k", "--slice", "s",
+            "--diary", "d",
+        ];
+        let code = run_one_shot(dir.path().to_path_buf(), argv.iter().map(|x| x.to_string()).collect());
+        assert_eq!(code, 0);
+        // 内存后端不落盘；进程环境也不被改写。
+        assert!(fs::read_dir(dir.path()).expect("read dir").next().is_none());
+        assert!(std::env::var_os("MEMORY_BACKEND").is_none());
+    }
+
     #[test]
     fn cli_parse_now_should_work() {
         let args = ["memory", "now"];
@@ -479,6 +1985,164 @@ mod tests {
         assert!(Cli::try_parse_from(args).is_ok());
     }
 
+    #[test]
+    fn cli_parse_keywords_complete_should_work() {
+        let args = ["memory", "keywords", "complete", "erp", "--match", "contains", "--text"];
+        assert!(Cli::try_parse_from(args).is_ok());
+        assert!(Cli::try_parse_from(["memory", "keywords", "complete"]).is_err());
+    }
+
+    #[test]
+    fn cli_parse_namespaces_list_should_work() {
+        let args = ["memory", "namespaces", "list", "--pretty"];
+        assert!(Cli::try_parse_from(args).is_ok());
+    }
+
+    #[test]
+    fn cli_parse_namespaces_freeze_should_require_namespace() {
+        assert!(Cli::try_parse_from(["memory", "namespaces", "freeze"]).is_err());
+        let args = ["memory", "namespaces", "freeze", "--namespace", "u1/p1", "--reason", "归档"];
+        assert!(Cli::try_parse_from(args).is_ok());
+    }
+
+    #[test]
+    fn cli_parse_stats_should_require_namespace() {
+        assert!(Cli::try_parse_from(["memory", "stats"]).is_err());
+        assert!(Cli::try_parse_from(["memory", "stats", "--namespace", "u1/p1", "--text"]).is_ok());
+    }
+
+    #[test]
+    fn cli_parse_export_since_should_work() {
+        let args = ["memory", "export", "--namespace", "u1/p1", "--since", "42"];
+        let cli = Cli::try_parse_from(args).expect("parse");
+        let Some(Command::Export(cmd)) = cli.command else {
+            panic!("expected export");
+        };
+        assert_eq!(cmd.since.as_deref(), Some("42"));
+        assert_eq!(cmd.format, "jsonl");
+
+        let args = ["memory", "export", "--namespace", "u1/p1", "--format", "json"];
+        let cli = Cli::try_parse_from(args).expect("parse");
+        let Some(Command::Export(cmd)) = cli.command else {
+            panic!("expected export");
+        };
+        assert_eq!(cmd.format, "json");
+
+        let args = ["memory", "export", "--namespace", "u1/p1", "--format", "csv", "--out", "a.csv"];
+        let cli = Cli::try_parse_from(args).expect("parse");
+        let Some(Command::Export(cmd)) = cli.command else {
+            panic!("expected export");
+        };
+        assert_eq!(cmd.format, "csv");
+        assert_eq!(cmd.output, Some(PathBuf::from("a.csv")));
+
+        let args = ["memory", "export", "--namespace", "u1/p1", "--format", "xml"];
+        assert!(Cli::try_parse_from(args).is_err());
+    }
+
+    #[test]
+    fn cli_parse_import_should_work() {
+        let args = ["memory", "import", "--input", "-", "--namespace", "u1/p2"];
+        let cli = Cli::try_parse_from(args).expect("parse");
+        let Some(Command::Import(cmd)) = cli.command else {
+            panic!("expected import");
+        };
+        assert_eq!(cmd.input, PathBuf::from("-"));
+        assert_eq!(cmd.namespace.as_deref(), Some("u1/p2"));
+        assert_eq!(cmd.on_conflict, "keep-existing");
+
+        let args = ["memory", "import", "--input", "d.jsonl", "--on-conflict", "keep-newest"];
+        let cli = Cli::try_parse_from(args).expect("parse");
+        let Some(Command::Import(cmd)) = cli.command else {
+            panic!("expected import");
+        };
+        assert_eq!(cmd.on_conflict, "keep-newest");
+        assert!(!cmd.dry_run);
+
+        let args = ["memory", "import", "--namespace", "u1/p1", "--from", "d.jsonl", "--dry-run"];
+        let cli = Cli::try_parse_from(args).expect("parse");
+        let Some(Command::Import(cmd)) = cli.command else {
+            panic!("expected import");
+        };
+        assert_eq!(cmd.input, PathBuf::from("d.jsonl"));
+        assert!(cmd.dry_run);
+    }
+
+    #[test]
+    fn cli_parse_schema_dump_should_work() {
+        let args = ["memory", "schema", "dump", "--out-dir", "schemas"];
+        let cli = Cli::try_parse_from(args).expect("parse");
+        let Some(Command::Schema(SchemaCommand {
+            command: SchemaSubcommand::Dump(cmd),
+        })) = cli.command
+        else {
+            panic!("expected schema dump");
+        };
+        assert_eq!(cmd.out_dir, Some(PathBuf::from("schemas")));
+
+        let args = ["memory", "schema", "manifest", "--target", "anthropic"];
+        let cli = Cli::try_parse_from(args).expect("parse");
+        let Some(Command::Schema(SchemaCommand {
+            command: SchemaSubcommand::Manifest(cmd),
+        })) = cli.command
+        else {
+            panic!("expected schema manifest");
+        };
+        assert_eq!(cmd.target, "anthropic");
+    }
+
+    #[test]
+    fn cli_parse_distill_should_require_source_or_apply() {
+        let args = ["memory", "distill", "--from", "chat.jsonl", "--namespace", "u1/p1"];
+        let cli = Cli::try_parse_from(args).expect("parse");
+        let Some(Command::Distill(cmd)) = cli.command else {
+            panic!("expected distill");
+        };
+        assert_eq!(cmd.from, Some(PathBuf::from("chat.jsonl")));
+        assert_eq!(cmd.session_gap, DEFAULT_SESSION_GAP_MINUTES);
+        assert_eq!(cmd.min_messages, 2);
+
+        let args = ["memory", "distill", "--apply", "staged.json"];
+        assert!(Cli::try_parse_from(args).is_ok());
+
+        let missing_namespace = ["memory", "distill", "--from", "chat.jsonl"];
+        assert!(Cli::try_parse_from(missing_namespace).is_err());
+        let both = ["memory", "distill", "--from", "a", "--apply", "b", "--namespace", "u1/p1"];
+        assert!(Cli::try_parse_from(both).is_err());
+        let zero_gap = ["memory", "distill", "--apply", "b", "--session-gap", "0"];
+        assert!(Cli::try_parse_from(zero_gap).is_err());
+    }
+
+    #[test]
+    fn cli_parse_migrate_should_work() {
+        let args = ["memory", "migrate", "--text"];
+        assert!(Cli::try_parse_from(args).is_ok());
+    }
+
+    #[test]
+    fn cli_parse_compact_should_require_namespace_or_all() {
+        assert!(Cli::try_parse_from(["memory", "compact", "--namespace", "u1/p1"]).is_ok());
+        assert!(Cli::try_parse_from(["memory", "compact", "--all"]).is_ok());
+        assert!(Cli::try_parse_from(["memory", "compact"]).is_err());
+        assert!(Cli::try_parse_from(["memory", "compact", "--namespace", "u1/p1", "--all"]).is_err());
+    }
+
+    #[test]
+    fn cli_parse_reindex_should_require_namespace_or_all() {
+        assert!(Cli::try_parse_from(["memory", "reindex", "--namespace", "u1/p1"]).is_ok());
+        assert!(Cli::try_parse_from(["memory", "reindex", "--all", "--format", "table"]).is_ok());
+        assert!(Cli::try_parse_from(["memory", "reindex"]).is_err());
+    }
+
+    #[test]
+    fn cli_parse_restore_should_require_at() {
+        let ok = ["memory", "restore", "--namespace", "u1/p1", "--at", "2025-05-01"];
+        assert!(Cli::try_parse_from(ok).is_ok());
+
+        let missing = ["memory", "restore", "--namespace", "u1/p1"];
+        assert!(Cli::try_parse_from(missing).is_err());
+    }
+
     #[test]
     fn read_utf8_file_strip_bom_should_work() {
         let dir = tempfile::TempDir::new().expect("create temp dir");
@@ -503,8 +2167,12 @@ mod tests {
         fs::write(&diary_path, "diary").expect("write diary");
 
         let cmd = RememberCommand {
-            namespace: "u1/p1".to_string(),
+            namespace: Some("u1/p1".to_string()),
+            stdin_json: false,
             keywords: vec!["项目".to_string()],
+            weights: vec!["项目=3".to_string()],
+            tags: vec!["Project-Alpha".to_string()],
+            kind: Some(MemoryKind::Decision),
             slice: None,
             slice_file: Some(slice_path),
             diary: None,
@@ -512,18 +2180,57 @@ mod tests {
             occurred_at: Some("2025-01-02".to_string()),
             importance: Some(3),
             source: Some("test".to_string()),
-            pretty: false,
-            text: false,
+            source_kind: Some("Conversation".to_string()),
+            source_id: Some(" c-42 ".to_string()),
+            source_url: None,
+            metadata: Some(r#"{"ticket":"ERP-12"}"#.to_string()),
+            relates_to: Vec::new(),
+            supersedes: None,
+            expires_at: None,
+            pinned: false,
+            format: OutputArgs::default(),
         };
 
-        let args = cmd.into_args().expect("into args");
+        let args = cmd.into_args(&mut io::empty()).expect("into args");
         assert_eq!(args.slice, "slice");
         assert_eq!(args.diary, "diary");
         assert_eq!(args.importance, Some(3));
+        assert_eq!(args.keyword_weights.get("项目"), Some(&3));
+        let source = args.source.expect("source");
+        assert_eq!(source.kind.as_deref(), Some("conversation"));
+        assert_eq!(source.id.as_deref(), Some("c-42"));
+        assert_eq!(source.label.as_deref(), Some("test"));
+        assert_eq!(args.metadata.get("ticket"), Some(&Value::from("ERP-12")));
+    }
+
+    #[test]
+    fn remember_command_should_read_content_from_stdin() {
+        let parse = |args: &[&str]| {
+            let Some(Command::Remember(cmd)) = Cli::try_parse_from(args).expect("parse").command else {
+                panic!("expected remember");
+            };
+            cmd
+        };
+
+        let cmd = parse(&["memory", "remember", "--namespace", "u1/p1", "-k", "k", "--slice", "s", "--diary", "-"]);
+        let args = cmd.into_args(&mut "管道里的\n日记\n".as_bytes()).expect("into args");
+        assert_eq!(args.diary, "管道里的\n日记");
+
+        let cmd = parse(&["memory", "remember", "--namespace", "u1/p1", "-k", "k", "--slice", "-", "--diary", "-"]);
+        assert!(cmd.into_args(&mut io::empty()).is_err());
+
+        let cmd = parse(&["memory", "remember", "--stdin-json", "--namespace", "u1/p1"]);
+        let json = r#"{"keywords":["k"],"slice":"s","diary":"d","importance":4}"#;
+        let args = cmd.into_args(&mut json.as_bytes()).expect("into args");
+        assert_eq!(args.namespace, "u1/p1");
+        assert_eq!(args.importance, Some(4));
+
+        assert!(Cli::try_parse_from(["memory", "remember", "--stdin-json", "--slice", "s"]).is_err());
+        assert!(Cli::try_parse_from(["memory", "remember", "--namespace", "u1/p1", "-k", "k"]).is_err());
     }
 
     #[test]
-    fn extract_primary_text_should_find_summary() {
+    fn primary_text_should_find_summary() {
         let dir = tempfile::TempDir::new().expect("create temp dir");
         let mut engine = MemoryEngine::new(dir.path().to_path_buf());
 
@@ -536,6 +2243,7 @@ mod tests {
                 occurred_at: None,
                 importance: None,
                 source: None,
+                ..Default::default()
             })
             .expect("remember");
 
@@ -548,10 +2256,11 @@ mod tests {
                 query: None,
                 limit: 20,
                 include_diary: false,
+                ..Default::default()
             })
             .expect("recall");
 
-        let text = extract_primary_text(&out).expect("text");
+        let text = output::primary_text(&out).expect("text");
         assert!(text.contains("命中"));
     }
 }
@@ -0,0 +1,356 @@
+//! CLI 输出格式（`--format`）：各子命令共用的 tool 结果渲染。
+//!
+//! - json / json-pretty：完整的 tool 结果（`content` + `data`）；
+//! - text：文本摘要（`content[].text`），没有时退回 JSON；
+//! - table / csv：`data` 中的记录列表（recall 的 `items`、namespaces 的列表等），每条一行；没有列表时按字段 / 值两列输出；
+//! - yaml：`data`（结构化结果）。
+
+use clap::{Args, ValueEnum};
+use serde_json::Value;
+
+/// 表格单元格的最大显示宽度（超出截断并以 `…` 结尾；CSV 不截断）。
+const TABLE_CELL_MAX_WIDTH: usize = 48;
+
+/// 记忆记录在表格 / CSV 中展示的列（按顺序，缺失的列跳过）。
+const MEMORY_COLUMNS: [&str; 6] = ["id", "recorded_at", "occurred_at", "importance", "keywords", "slice"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Json,
+    JsonPretty,
+    Text,
+    Table,
+    Yaml,
+    Csv,
+}
+
+/// 输出格式参数（以 `#[command(flatten)]` 加入各子命令）。
+#[derive(Args, Debug, Clone, Default)]
+pub struct OutputArgs {
+    /// 输出格式：json（默认）/ json-pretty / text（文本摘要）/ table（对齐的表格）/ yaml / csv
+    #[arg(long, value_enum, value_name = "FORMAT")]
+    pub format: Option<OutputFormat>,
+
+    /// 同 --format json-pretty（兼容旧参数）
+    #[arg(long, hide = true)]
+    pub pretty: bool,
+
+    /// 同 --format text（兼容旧参数；与 --pretty 同时提供时以 --text 为准）
+    #[arg(long, hide = true)]
+    pub text: bool,
+}
+
+impl OutputArgs {
+    /// 显式的 `--format` 优先，其次是旧的 `--text` / `--pretty`。
+    pub fn resolve(&self) -> OutputFormat {
+        match self.format {
+            Some(format) => format,
+            None if self.text => OutputFormat::Text,
+            None if self.pretty => OutputFormat::JsonPretty,
+            None => OutputFormat::Json,
+        }
+    }
+}
+
+/// 按输出格式渲染一个 tool 结果（`{ content, data }`）。
+pub fn render(result: &Value, format: OutputFormat) -> Result<String, String> {
+    let data = result.get("data");
+    match format {
+        OutputFormat::Json => Ok(result.to_string()),
+        OutputFormat::JsonPretty => serde_json::to_string_pretty(result).map_err(|e| format!("输出 JSON 失败：{e}")),
+        OutputFormat::Text => match primary_text(result) {
+            Some(text) => Ok(text),
+            None => Ok(result.to_string()),
+        },
+        OutputFormat::Yaml => Ok(yaml(data.unwrap_or(result))),
+        // 没有结构化结果（如 recall 的 compact 格式）时退回文本摘要。
+        OutputFormat::Table | OutputFormat::Csv => {
+            let Some(data) = data else {
+                return Ok(primary_text(result).unwrap_or_else(|| result.to_string()));
+            };
+            let (columns, rows) = tabulate(data);
+            Ok(if format == OutputFormat::Table {
+                table(&columns, &rows)
+            } else {
+                csv(&columns, &rows)
+            })
+        }
+    }
+}
+
+/// 第一个非空的文本内容块。
+pub fn primary_text(result: &Value) -> Option<String> {
+    let content = result.get("content")?.as_array()?;
+    content
+        .iter()
+        .filter_map(|item| item.get("text").and_then(|x| x.as_str()))
+        .map(str::trim)
+        .find(|text| !text.is_empty())
+        .map(str::to_string)
+}
+
+/// 取出表格的列与行：优先 `items`，其次第一个由对象组成的数组字段；都没有时为字段 / 值两列。
+fn tabulate(data: &Value) -> (Vec<String>, Vec<Vec<String>>) {
+    let records = match data {
+        Value::Array(rows) => Some(rows),
+        Value::Object(obj) => obj
+            .get("items")
+            .and_then(|x| x.as_array())
+            .or_else(|| obj.values().filter_map(|x| x.as_array()).find(|x| x.first().is_some_and(Value::is_object))),
+        _ => None,
+    };
+
+    let Some(records) = records.filter(|rows| rows.iter().all(Value::is_object)) else {
+        let columns = vec!["field".to_string(), "value".to_string()];
+        let rows = match data {
+            Value::Object(obj) => obj.iter().map(|(k, v)| vec![k.clone(), cell(v)]).collect(),
+            other => vec![vec![String::new(), cell(other)]],
+        };
+        return (columns, rows);
+    };
+
+    let is_memory = records.iter().any(|x| x.get("id").is_some() && x.get("slice").is_some());
+    let mut columns: Vec<String> = Vec::new();
+    if is_memory {
+        columns.extend(
+            MEMORY_COLUMNS
+                .iter()
+                .filter(|c| records.iter().any(|x| x.get(**c).is_some_and(|v| !v.is_null())))
+                .map(|c| c.to_string()),
+        );
+    } else {
+        for key in records.iter().filter_map(|x| x.as_object()).flat_map(|x| x.keys()) {
+            if !columns.contains(key) {
+                columns.push(key.clone());
+            }
+        }
+    }
+
+    let rows = records
+        .iter()
+        .map(|record| columns.iter().map(|c| record.get(c).map(cell).unwrap_or_default()).collect())
+        .collect();
+    (columns, rows)
+}
+
+/// 单元格文本：字符串原样，标量数组以 `; ` 连接，其余为紧凑 JSON。
+fn cell(v: &Value) -> String {
+    match v {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        Value::Array(items) if items.iter().all(|x| !x.is_object() && !x.is_array()) => {
+            items.iter().map(cell).collect::<Vec<_>>().join("; ")
+        }
+        other => other.to_string(),
+    }
+}
+
+/// 按显示宽度对齐的表格（中日韩等宽字符按 2 列计算），过长的单元格截断。
+fn table(columns: &[String], rows: &[Vec<String>]) -> String {
+    let fit = |text: &str| truncate(&text.split_whitespace().collect::<Vec<_>>().join(" "), TABLE_CELL_MAX_WIDTH);
+    let rows: Vec<Vec<String>> = rows.iter().map(|row| row.iter().map(|x| fit(x)).collect()).collect();
+    let widths: Vec<usize> = columns
+        .iter()
+        .enumerate()
+        .map(|(i, c)| rows.iter().map(|row| display_width(&row[i])).chain([display_width(c)]).max().unwrap_or(0))
+        .collect();
+
+    let line = |cells: &[String]| {
+        let padded: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .map(|(text, &width)| format!("{text}{}", " ".repeat(width - display_width(text))))
+            .collect();
+        padded.join("  ").trim_end().to_string()
+    };
+    let rule: Vec<String> = widths.iter().map(|&w| "-".repeat(w)).collect();
+
+    let mut out = vec![line(columns), line(&rule)];
+    out.extend(rows.iter().map(|row| line(row)));
+    out.join("\n")
+}
+
+/// CSV（RFC 4180 引号规则；不截断单元格）。
+fn csv(columns: &[String], rows: &[Vec<String>]) -> String {
+    let line = |cells: &[String]| cells.iter().map(|x| csv_field(x)).collect::<Vec<_>>().join(",");
+    let mut out = vec![line(columns)];
+    out.extend(rows.iter().map(|row| line(row)));
+    out.join("\n")
+}
+
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+fn truncate(text: &str, max_width: usize) -> String {
+    if display_width(text) <= max_width {
+        return text.to_string();
+    }
+    let mut out = String::new();
+    let mut width = 0;
+    for c in text.chars() {
+        let w = char_width(c);
+        if width + w > max_width - 1 {
+            break;
+        }
+        out.push(c);
+        width += w;
+    }
+    out.push('…');
+    out
+}
+
+fn display_width(text: &str) -> usize {
+    text.chars().map(char_width).sum()
+}
+
+/// 终端显示宽度：东亚宽字符与全角字符占 2 列。
+fn char_width(c: char) -> usize {
+    match c as u32 {
+        0x1100..=0x115F
+        | 0x2E80..=0x303E
+        | 0x3041..=0x33FF
+        | 0x3400..=0x4DBF
+        | 0x4E00..=0x9FFF
+        | 0xA000..=0xA4CF
+        | 0xAC00..=0xD7A3
+        | 0xF900..=0xFAFF
+        | 0xFE30..=0xFE4F
+        | 0xFF00..=0xFF60
+        | 0xFFE0..=0xFFE6
+        | 0x1F300..=0x1F64F
+        | 0x1F900..=0x1F9FF
+        | 0x20000..=0x3FFFD => 2,
+        _ => 1,
+    }
+}
+
+/// YAML（块格式）：无法作为普通标量的字符串写成双引号形式（JSON 字符串也是合法的 YAML）。
+fn yaml(v: &Value) -> String {
+    let mut out = String::new();
+    match v {
+        Value::Object(obj) if !obj.is_empty() => yaml_block(v, 0, &mut out),
+        Value::Array(items) if !items.is_empty() => yaml_block(v, 0, &mut out),
+        other => out.push_str(&yaml_scalar(other)),
+    }
+    out.trim_end().to_string()
+}
+
+fn yaml_block(v: &Value, indent: usize, out: &mut String) {
+    let pad = " ".repeat(indent);
+    match v {
+        Value::Object(obj) => {
+            for (key, value) in obj {
+                out.push_str(&format!("{pad}{}:", yaml_string(key)));
+                yaml_child(value, indent, out);
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                if is_yaml_block(item) {
+                    // 子块缩进两格渲染后，把第一行的缩进换成 `- `。
+                    let mut child = String::new();
+                    yaml_block(item, indent + 2, &mut child);
+                    out.push_str(&format!("{pad}- {}", &child[indent + 2..]));
+                } else {
+                    out.push_str(&format!("{pad}- {}\n", yaml_scalar(item)));
+                }
+            }
+        }
+        other => out.push_str(&format!("{pad}{}\n", yaml_scalar(other))),
+    }
+}
+
+fn yaml_child(value: &Value, indent: usize, out: &mut String) {
+    if is_yaml_block(value) {
+        out.push('\n');
+        yaml_block(value, indent + 2, out);
+    } else {
+        out.push_str(&format!(" {}\n", yaml_scalar(value)));
+    }
+}
+
+fn is_yaml_block(v: &Value) -> bool {
+    match v {
+        Value::Object(obj) => !obj.is_empty(),
+        Value::Array(items) => !items.is_empty(),
+        _ => false,
+    }
+}
+
+fn yaml_scalar(v: &Value) -> String {
+    match v {
+        Value::Null => "null".to_string(),
+        Value::Bool(b) => b.to_string(),
+        Value::Number(n) => n.to_string(),
+        Value::String(s) => yaml_string(s),
+        Value::Array(_) => "[]".to_string(),
+        Value::Object(_) => "{}".to_string(),
+    }
+}
+
+fn yaml_string(s: &str) -> String {
+    let reserved = ["true", "false", "null", "yes", "no", "on", "off", "~"];
+    let plain = !s.is_empty()
+        && s.trim() == s
+        && !s.starts_with(['-', '?', ':', '!', '&', '*', '|', '>', '%', '@', '`', '\'', '"', '#', '[', '{'])
+        && !s.contains([':', '#', ',', '[', ']', '{', '}', '\n', '\r', '\t', '"', '\''])
+        && !reserved.contains(&s.to_lowercase().as_str())
+        && s.parse::<f64>().is_err();
+    if plain {
+        s.to_string()
+    } else {
+        serde_json::to_string(s).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn table_should_align_memory_columns_by_display_width() {
+        let result = json!({
+            "content": [{ "type": "text", "text": "命中 2 条记忆" }],
+            "data": { "total": 2, "items": [
+                { "id": "a1", "recorded_at": "2025-01-01T00:00:00Z", "keywords": ["项目", "erp"], "slice": "我们做过 ERP 项目", "diary": "d" },
+                { "id": "b22", "recorded_at": "2025-01-02T00:00:00Z", "importance": 5, "keywords": ["k"], "slice": "second\nline", "diary": "d" }
+            ] }
+        });
+
+        let text = render(&result, OutputFormat::Table).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "id   recorded_at           importance  keywords   slice");
+        assert_eq!(lines[2], "a1   2025-01-01T00:00:00Z              项目; erp  我们做过 ERP 项目");
+        assert_eq!(lines[3], "b22  2025-01-02T00:00:00Z  5           k          second line");
+
+        let csv = render(&result, OutputFormat::Csv).unwrap();
+        assert_eq!(csv.lines().next().unwrap(), "id,recorded_at,importance,keywords,slice");
+        assert!(csv.ends_with("b22,2025-01-02T00:00:00Z,5,k,\"second\nline\""));
+
+        assert_eq!(render(&result, OutputFormat::Text).unwrap(), "命中 2 条记忆");
+    }
+
+    #[test]
+    fn yaml_should_render_data_blocks_and_quote_ambiguous_strings() {
+        let result = json!({ "data": { "namespace": "u1/p1", "ids": ["a", "b"], "report": { "imported": 2, "note": "yes" }, "items": [{ "id": "x", "tags": [] }] } });
+        assert_eq!(
+            render(&result, OutputFormat::Yaml).unwrap(),
+            "ids:\n  - a\n  - b\nitems:\n  - id: x\n    tags: []\nnamespace: u1/p1\nreport:\n  imported: 2\n  note: \"yes\""
+        );
+    }
+
+    #[test]
+    fn legacy_flags_should_map_to_formats() {
+        let args = |format, pretty, text| OutputArgs { format, pretty, text };
+        assert_eq!(args(None, false, false).resolve(), OutputFormat::Json);
+        assert_eq!(args(None, true, false).resolve(), OutputFormat::JsonPretty);
+        assert_eq!(args(None, true, true).resolve(), OutputFormat::Text);
+        assert_eq!(args(Some(OutputFormat::Table), false, true).resolve(), OutputFormat::Table);
+    }
+}
@@ -0,0 +1,12 @@
+//! Memory MCP 记忆服务器：`memory` 可执行文件（stdio / HTTP+SSE MCP server、`--cli`）的实现。
+//!
+//! 启用 `testing` feature 后额外提供 [`testing`] 模块，供嵌入方编写不经过真实 stdio、也不读写数据目录的集成测试。
+
+pub mod cli;
+pub mod mcp;
+pub mod memory;
+mod schema;
+pub mod transport;
+
+#[cfg(feature = "testing")]
+pub mod testing;
@@ -1,8 +1,5 @@
-mod cli;
-mod mcp;
-mod memory;
-
-use std::io::{self, BufRead, Write};
+use memory_mcp::transport::stdio::{self, Framing};
+use memory_mcp::{cli, memory, transport};
 
 fn main() {
     let argv: Vec<String> = std::env::args().collect();
@@ -26,25 +23,145 @@ fn main() {
     }
 
     let mut engine = memory::MemoryEngine::new(root_dir);
+    // --backend jsonl|sqlite|memory：覆盖 MEMORY_BACKEND（同 CLI 模式的全局参数）；
+    // sqlite / memory 后端下 tools/list 只公布该后端支持的 tool。
+    match flag_value(&argv, "--backend").map(|x| memory::StorageBackend::parse(&x)).transpose() {
+        Ok(Some(backend)) => engine = engine.with_backend(backend),
+        Ok(None) => {}
+        Err(err) => {
+            eprintln!("memory: --backend {err}");
+            std::process::exit(2);
+        }
+    }
 
-    let stdin = io::stdin();
-    let mut stdout = io::stdout();
+    // 预热：--preload <namespace>（可重复）/ --preload-all / MEMORY_PRELOAD（逗号分隔，* 表示全部）。
+    let preload = preload_targets(&argv);
+    if !preload.is_empty() {
+        let started = std::time::Instant::now();
+        match engine.preload(&preload) {
+            Ok(report) => {
+                for (ns, err) in &report.failed {
+                    eprintln!("memory: preload {ns} failed: {err}");
+                }
+                // 就绪信号：仅在配置了预热时输出到 stderr（不影响 stdout 协议通道）。
+                eprintln!(
+                    "memory: ready (preloaded {} namespaces in {} ms)",
+                    report.loaded.len(),
+                    started.elapsed().as_millis()
+                );
+            }
+            Err(err) => eprintln!("memory: preload failed: {err}"),
+        }
+    }
 
-    for line in stdin.lock().lines() {
-        let Ok(text) = line else { break };
+    // --sse <addr>：改为 HTTP+SSE 传输（2024-11-05 规范），多个客户端会话共享同一个引擎。
+    // 网络传输收到终止信号后等待进行中的消息处理完毕、补写索引再退出。
+    // 浏览器来源默认只允许本机，--allow-origin <origin>（可重复）/ MEMORY_ALLOWED_ORIGINS（逗号分隔）额外放行。
+    if let Some(addr) = flag_value(&argv, "--sse") {
+        let bind = addr.clone();
+        let shared = transport::Shared::new(engine).with_allowed_origins(allowed_origins(&argv));
+        let result = transport::serve_until_shutdown(shared, move |shared| {
+            transport::sse::run(bind.as_str(), shared)
+        });
+        if let Err(err) = result {
+            eprintln!("memory: SSE transport failed on {addr}: {err}");
+            std::process::exit(1);
+        }
+        std::process::exit(0);
+    }
 
-        match mcp::handle_stdin_line(&mut engine, &text) {
-            Ok(Some(response_json_line)) => {
-                if stdout.write_all(response_json_line.as_bytes()).is_ok()
-                    && stdout.write_all(b"\n").is_ok()
-                {
-                    let _ = stdout.flush();
-                }
+    // --ws <addr>：WebSocket 传输（需以 `ws` 特性编译），多个会话共享同一个引擎；来源检查同 --sse。
+    if let Some(addr) = flag_value(&argv, "--ws") {
+        run_ws(&addr, transport::Shared::new(engine).with_allowed_origins(allowed_origins(&argv)));
+        std::process::exit(0);
+    }
+
+    // --framing auto|lines|content-length：默认按第一条消息自动判断（每行一条 JSON 或 LSP 风格的 Content-Length 头）。
+    let framing = match flag_value(&argv, "--framing").map(|x| Framing::parse(&x)).transpose() {
+        Ok(framing) => framing.unwrap_or_default(),
+        Err(err) => {
+            eprintln!("memory: {err}");
+            std::process::exit(2);
+        }
+    };
+
+    // stdin 结束或收到终止信号：处理完已读入的请求、补写索引后以 0 退出。
+    if let Err(err) = stdio::serve(engine, framing) {
+        eprintln!("memory: stdio server failed: {err}");
+        std::process::exit(1);
+    }
+}
+
+fn preload_targets(argv: &[String]) -> Vec<String> {
+    let mut out: Vec<String> = Vec::new();
+
+    if let Ok(value) = std::env::var("MEMORY_PRELOAD") {
+        out.extend(value.split(',').map(|x| x.trim().to_string()).filter(|x| !x.is_empty()));
+    }
+
+    let mut it = argv.iter().skip(1);
+    while let Some(a) = it.next() {
+        if a == "--preload-all" {
+            out.push("*".to_string());
+        } else if a == "--preload" {
+            if let Some(v) = it.next() {
+                out.push(v.clone());
             }
-            Ok(None) => {}
-            Err(_err) => {
-                // 兜底：避免 stderr 输出污染 MCP stdout 协议通道；因此这里静默丢弃错误。
+        } else if let Some(v) = a.strip_prefix("--preload=") {
+            out.push(v.to_string());
+        }
+    }
+
+    out
+}
+
+fn allowed_origins(argv: &[String]) -> Vec<String> {
+    let mut out: Vec<String> = Vec::new();
+
+    if let Ok(value) = std::env::var("MEMORY_ALLOWED_ORIGINS") {
+        out.extend(value.split(',').map(|x| x.trim().to_string()).filter(|x| !x.is_empty()));
+    }
+
+    let mut it = argv.iter().skip(1);
+    while let Some(a) = it.next() {
+        if a == "--allow-origin" {
+            if let Some(v) = it.next() {
+                out.push(v.clone());
             }
+        } else if let Some(v) = a.strip_prefix("--allow-origin=") {
+            out.push(v.to_string());
         }
     }
+
+    out
+}
+
+/// 取 `--name <value>` / `--name=<value>` 形式的参数值（出现多次时取最后一个）。
+fn flag_value(argv: &[String], name: &str) -> Option<String> {
+    let mut out = None;
+    let mut it = argv.iter().skip(1);
+    while let Some(a) = it.next() {
+        if a == name {
+            out = it.next().cloned();
+        } else if let Some(v) = a.strip_prefix(name).and_then(|rest| rest.strip_prefix('=')) {
+            out = Some(v.to_string());
+        }
+    }
+    out
+}
+
+#[cfg(feature = "ws")]
+fn run_ws(addr: &str, shared: transport::Shared) {
+    let bind = addr.to_string();
+    let result = transport::serve_until_shutdown(shared, move |shared| transport::ws::run(bind.as_str(), shared));
+    if let Err(err) = result {
+        eprintln!("memory: WebSocket transport failed on {addr}: {err}");
+        std::process::exit(1);
+    }
+}
+
+#[cfg(not(feature = "ws"))]
+fn run_ws(addr: &str, _shared: transport::Shared) {
+    eprintln!("memory: --ws {addr} 需要以 `ws` 特性编译（cargo build --features ws）");
+    std::process::exit(2);
 }
@@ -1,4 +1,7 @@
-use crate::memory::{MemoryEngine, RecallArgs, RememberArgs};
+use crate::memory::{
+    check_half_life_days, default_namespace, get_namespace, memory_item_schema, source_schema, ArchiveArgs, ConflictPolicy, ExportDump, ForgetArgs, KeywordSort, KeywordsCompleteArgs, Lang, log_error, LogLevel, MemoryEngine, prompt_accepts, prompt_definitions, resource_templates, MemoryKind, RecallArgs, RecallGlobalArgs,
+    RememberArgs, StorageBackend, TransactionArgs, UpdateArgs, KEYWORD_WEIGHT_MAX, RECALL_DEFAULT_LIMIT, RECALL_LIMIT_CEILING,
+};
 use serde_json::{json, Value};
 
 pub fn handle_stdin_line(engine: &mut MemoryEngine, line: &str) -> Result<Option<String>, String> {
@@ -8,23 +11,166 @@ pub fn handle_stdin_line(engine: &mut MemoryEngine, line: &str) -> Result<Option
     }
 
     let message: Value = serde_json::from_str(text).map_err(|e| format!("invalid json: {e}"))?;
-    let response = handle_message(engine, &message)?;
+    let response = match &message {
+        Value::Array(batch) => handle_batch(engine, batch)?,
+        _ => handle_message(engine, &message)?,
+    };
     Ok(response.map(|v| v.to_string()))
 }
 
+/// JSON-RPC 批量请求：依次处理每条消息，响应按顺序合并为数组；全是通知时不返回任何内容。
+fn handle_batch(engine: &mut MemoryEngine, batch: &[Value]) -> Result<Option<Value>, String> {
+    if batch.is_empty() {
+        return Ok(Some(json!({
+            "jsonrpc": "2.0",
+            "id": null,
+            "error": { "code": -32600, "message": "invalid request: empty batch" }
+        })));
+    }
+
+    let mut responses: Vec<Value> = Vec::with_capacity(batch.len());
+    for message in batch {
+        if !message.is_object() {
+            responses.push(json!({
+                "jsonrpc": "2.0",
+                "id": null,
+                "error": { "code": -32600, "message": "invalid request: batch entries must be objects" }
+            }));
+            continue;
+        }
+        // 单条失败（如工具执行出错）只影响它自己的响应，其余消息照常处理。
+        match handle_message(engine, message) {
+            Ok(response) => responses.extend(response),
+            Err(err) => {
+                report_error(&err);
+                responses.extend(error_response(message.get("id").cloned(), &err));
+            }
+        }
+    }
+    Ok((!responses.is_empty()).then_some(Value::Array(responses)))
+}
+
+/// 消息处理失败（工具执行出错等）时使用的 JSON-RPC 错误码（实现自定义的 server error 区间）。
+pub const HANDLER_ERROR_CODE: i64 = -32000;
+
+/// 消息处理失败时的 JSON-RPC 错误响应；通知（没有 id）不应答。
+pub fn error_response(id: Option<Value>, err: &str) -> Option<Value> {
+    id.map(|id| {
+        json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": HANDLER_ERROR_CODE, "message": err }
+        })
+    })
+}
+
+/// 记忆变更通知（服务端 → 客户端）：namespace 的变更代数变化时发送，提示客户端刷新缓存的视图。
+pub const CHANGED_NOTIFICATION: &str = "notifications/memory/changed";
+
+/// 结构化日志通知（服务端 → 客户端，MCP logging）：索引重建、慢 recall、同步、写入恢复与消息处理错误等。
+pub const LOG_NOTIFICATION: &str = "notifications/message";
+
+/// 处理完一条消息后调用：返回需要推送给客户端的通知行（先是不低于 `logging/setLevel` 级别的日志，
+/// 再是每个发生变化的 namespace 一条变更通知）。
+///
+/// `params.generation` 为当前变更代数；namespace 被删除/重命名/迁移后为 null（需整体刷新）。
+pub fn take_notifications(engine: &mut MemoryEngine) -> Vec<String> {
+    let mut out = take_log_notifications(engine);
+    out.extend(take_change_notifications(engine));
+    out
+}
+
+/// 本线程收集的日志通知（只属于发出请求的连接）。
+pub fn take_log_notifications(engine: &mut MemoryEngine) -> Vec<String> {
+    engine
+        .take_logs()
+        .into_iter()
+        .map(|entry| {
+            json!({
+                "jsonrpc": "2.0",
+                "method": LOG_NOTIFICATION,
+                "params": { "level": entry.level.as_str(), "logger": entry.logger, "data": entry.data }
+            })
+            .to_string()
+        })
+        .collect()
+}
+
+/// namespace 变更通知（多个连接共享一个引擎时应广播给全部连接）。
+pub fn take_change_notifications(engine: &mut MemoryEngine) -> Vec<String> {
+    engine
+        .take_changed_generations()
+        .into_iter()
+        .map(|(namespace, generation)| {
+            json!({
+                "jsonrpc": "2.0",
+                "method": CHANGED_NOTIFICATION,
+                "params": { "namespace": namespace, "generation": generation }
+            })
+            .to_string()
+        })
+        .collect()
+}
+
+/// 无法处理的输入（如非法 JSON）：记录 error 级日志，随后由 `take_notifications` 推送给客户端。
+pub fn report_error(err: &str) {
+    log_error("memory.mcp", err);
+}
+
+/// 不需要引擎即可应答的单条消息（工具/模板/prompt 列表等）：返回 None 表示需交给引擎按顺序处理。
+///
+/// 引擎忙于耗时的调用时，读取循环可直接应答这些消息。
+pub fn handle_without_engine(line: &str, backend: StorageBackend) -> Option<Option<String>> {
+    let message: Value = serde_json::from_str(line.trim()).ok()?;
+    let id = message.get("id").cloned();
+    if id.as_ref().is_some_and(|id| !(id.is_string() || id.is_number() || id.is_null())) {
+        return None;
+    }
+    let method = message.get("method").and_then(|x| x.as_str())?;
+    let response = handle_static(method, id, backend)?;
+    Some(response.map(|v| v.to_string()))
+}
+
+fn handle_static(method: &str, id: Option<Value>, backend: StorageBackend) -> Option<Option<Value>> {
+    let response = match method {
+        "initialized" => None,
+        // 存活检测：不经过引擎，引擎忙于耗时调用时也能立即应答。
+        "ping" => id.map(|id| json!({ "jsonrpc": "2.0", "id": id, "result": {} })),
+        "tools/list" => handle_tools_list(id, backend),
+        "resources/templates/list" => handle_resource_templates_list(id),
+        "prompts/list" => handle_prompts_list(id),
+        _ => return None,
+    };
+    Some(response)
+}
+
 fn handle_message(engine: &mut MemoryEngine, message: &Value) -> Result<Option<Value>, String> {
-    let id = message.get("id").and_then(|x| x.as_i64());
+    // id 原样回显：字符串、数字与 null 都是合法的 JSON-RPC id；没有 id 成员的是通知。
+    let id = message.get("id").cloned();
+    if id.as_ref().is_some_and(|id| !(id.is_string() || id.is_number() || id.is_null())) {
+        return Ok(Some(json!({
+            "jsonrpc": "2.0",
+            "id": null,
+            "error": { "code": -32600, "message": "invalid request: id must be a string, number or null" }
+        })));
+    }
     let method = message
         .get("method")
         .and_then(|x| x.as_str())
         .unwrap_or_default();
     let params = message.get("params").cloned().unwrap_or(Value::Null);
 
+    if let Some(response) = handle_static(method, id.clone(), engine.backend()) {
+        return Ok(response);
+    }
     match method {
-        "initialize" => handle_initialize(id, &params),
-        "initialized" => Ok(None),
-        "tools/list" => handle_tools_list(id),
+        "initialize" => handle_initialize(engine, id, &params),
         "tools/call" => handle_tools_call(engine, id, &params),
+        "resources/list" => handle_resources_list(engine, id, &params),
+        "resources/read" => handle_resources_read(engine, id, &params),
+        "logging/setLevel" => handle_logging_set_level(engine, id, &params),
+        "prompts/get" => handle_prompts_get(engine, id, &params),
+        "completion/complete" => handle_completion_complete(engine, id, &params),
         _ => Ok(id.map(|id| {
             json!({
                 "jsonrpc": "2.0",
@@ -35,7 +181,12 @@ fn handle_message(engine: &mut MemoryEngine, message: &Value) -> Result<Option<V
     }
 }
 
-fn handle_initialize(id: Option<i64>, params: &Value) -> Result<Option<Value>, String> {
+fn handle_initialize(engine: &mut MemoryEngine, id: Option<Value>, params: &Value) -> Result<Option<Value>, String> {
+    // 每个连接以客户端名称作为写入者身份（written_by），便于多 agent 场景区分记忆来源。
+    if let Some(name) = params.pointer("/clientInfo/name").and_then(|x| x.as_str()) {
+        engine.set_client_writer(name);
+    }
+
     let requested = params
         .get("protocolVersion")
         .and_then(|x| x.as_str())
@@ -45,6 +196,15 @@ fn handle_initialize(id: Option<i64>, params: &Value) -> Result<Option<Value>, S
         "2025-06-18" | "2024-11-05" => requested,
         _ => "2025-06-18",
     };
+    // `resource_link` 内容块自 2025-06-18 起才有定义；旧协议的客户端只收到文本块。
+    engine.set_resource_links(supported == "2025-06-18");
+    // 结构化结果：2025-06-18 放在 structuredContent，旧协议沿用 data；新客户端可声明 `experimental.toolResultData` 继续接收 data。
+    let structured = supported == "2025-06-18";
+    let keep_data = params
+        .pointer("/capabilities/experimental/toolResultData")
+        .and_then(|x| x.as_bool())
+        .unwrap_or(false);
+    engine.set_result_fields(structured, !structured || keep_data);
 
     Ok(id.map(|id| {
         json!({
@@ -53,51 +213,450 @@ fn handle_initialize(id: Option<i64>, params: &Value) -> Result<Option<Value>, S
             "result": {
                 "protocolVersion": supported,
                 "serverInfo": { "name": "Memory", "version": env!("CARGO_PKG_VERSION") },
-                "capabilities": {}
+                "capabilities": {
+                    "resources": {},
+                    "prompts": {},
+                    "logging": {},
+                    "completions": {},
+                    "experimental": {
+                        "memoryChanges": { "notification": CHANGED_NOTIFICATION }
+                    }
+                }
             }
         })
     }))
 }
 
-fn handle_tools_list(id: Option<i64>) -> Result<Option<Value>, String> {
-    Ok(id.map(|id| {
+/// 列出各 namespace 最近的记忆资源（按 namespace 分页，`cursor` 为上一页的 `nextCursor`）。
+fn handle_resources_list(engine: &mut MemoryEngine, id: Option<Value>, params: &Value) -> Result<Option<Value>, String> {
+    let Some(id) = id else {
+        return Ok(None);
+    };
+    let cursor = params.get("cursor").and_then(|x| x.as_str());
+    let response = match engine.list_resources(cursor) {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(e) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": -32602, "message": e }
+        }),
+    };
+    Ok(Some(response))
+}
+
+fn handle_resource_templates_list(id: Option<Value>) -> Option<Value> {
+    id.map(|id| {
         json!({
             "jsonrpc": "2.0",
             "id": id,
-            "result": {
-                "tools": [
-                    {
-                        "name": "now",
-                        "description": "获取当前时间（本地 + UTC），用于需要准确日期时间的回答/计算。",
-                        "inputSchema": now_schema()
-                    },
-                    {
-                        "name": "keywords_list",
-                        "description": "列出指定 namespace 下已存在的关键字（已归一化为小写，用于复用短关键字）。",
-                        "inputSchema": keywords_list_schema()
-                    },
-                    {
-                        "name": "keywords_list_global",
-                        "description": "列出全局已存在的关键字（跨 namespace 汇总；关键字已归一化为小写）。",
-                        "inputSchema": keywords_list_global_schema()
-                    },
-                    {
-                        "name": "remember",
-                        "description": "记录一条长期记忆（关键字会归一化为小写；时间类关键字会被忽略 + 内容切片 + AI 日记），用于后续检索。",
-                        "inputSchema": remember_schema()
-                    },
-                    {
-                        "name": "recall",
-                        "description": "按关键字/时间范围检索记忆，并返回最相关的若干条。",
-                        "inputSchema": recall_schema()
-                    }
-                ]
-            }
+            "result": { "resourceTemplates": resource_templates() }
         })
-    }))
+    })
+}
+
+/// 设置推送给客户端的最低日志级别（`notifications/message`，默认 warning）。
+fn handle_logging_set_level(engine: &mut MemoryEngine, id: Option<Value>, params: &Value) -> Result<Option<Value>, String> {
+    let level = params.get("level").and_then(|x| x.as_str()).unwrap_or_default();
+    let response = match LogLevel::parse(level) {
+        Ok(level) => {
+            engine.set_log_level(level);
+            id.map(|id| json!({ "jsonrpc": "2.0", "id": id, "result": {} }))
+        }
+        Err(e) => id.map(|id| {
+            json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": -32602, "message": e }
+            })
+        }),
+    };
+    Ok(response)
+}
+
+fn handle_prompts_list(id: Option<Value>) -> Option<Value> {
+    id.map(|id| {
+        json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "result": { "prompts": prompt_definitions() }
+        })
+    })
+}
+
+/// 生成预置 prompt：召回相关记忆并插入消息正文，客户端可一键发起记忆回顾。
+fn handle_prompts_get(engine: &mut MemoryEngine, id: Option<Value>, params: &Value) -> Result<Option<Value>, String> {
+    let Some(id) = id else {
+        return Ok(None);
+    };
+    let name = params.get("name").and_then(|x| x.as_str()).unwrap_or_default();
+    let empty = serde_json::Map::new();
+    let args = params.get("arguments").and_then(|x| x.as_object()).unwrap_or(&empty);
+    let response = match engine.get_prompt(name, args) {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(e) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": -32602, "message": e }
+        }),
+    };
+    Ok(Some(response))
+}
+
+/// `completion/complete` 单次返回的候选数上限（MCP 规定最多 100 个）。
+const COMPLETION_MAX_VALUES: usize = 100;
+
+/// 参数补全：prompt 参数（namespace / topic / days）与 `memory://` 模板变量（userId / projectId）。
+fn handle_completion_complete(engine: &mut MemoryEngine, id: Option<Value>, params: &Value) -> Result<Option<Value>, String> {
+    let Some(id) = id else {
+        return Ok(None);
+    };
+    let argument = params.pointer("/argument/name").and_then(|x| x.as_str()).unwrap_or_default();
+    let value = params.pointer("/argument/value").and_then(|x| x.as_str()).unwrap_or_default();
+    let empty = serde_json::Map::new();
+    let context = params
+        .pointer("/context/arguments")
+        .and_then(|x| x.as_object())
+        .unwrap_or(&empty);
+
+    let reference = params.get("ref").cloned().unwrap_or(Value::Null);
+    let valid_ref = match reference.get("type").and_then(|x| x.as_str()) {
+        Some("ref/prompt") => prompt_accepts(reference["name"].as_str().unwrap_or_default(), argument),
+        Some("ref/resource") => resource_templates()
+            .iter()
+            .any(|t| t["uriTemplate"] == reference["uri"]),
+        _ => false,
+    };
+    let response = if valid_ref {
+        engine.complete_argument(argument, value, context)
+    } else {
+        Err(format!("无法补全：未知的引用或参数（argument={argument}）"))
+    };
+    let response = match response {
+        Ok(mut values) => {
+            let total = values.len();
+            values.truncate(COMPLETION_MAX_VALUES);
+            json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "result": { "completion": { "values": values, "total": total, "hasMore": total > values.len() } }
+            })
+        }
+        Err(e) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": -32602, "message": e }
+        }),
+    };
+    Ok(Some(response))
+}
+
+/// 读取 `memory://{namespace}/{id}` 资源（recall 结果中的 resource_link 指向这里）。
+fn handle_resources_read(engine: &mut MemoryEngine, id: Option<Value>, params: &Value) -> Result<Option<Value>, String> {
+    let Some(id) = id else {
+        return Ok(None);
+    };
+    let uri = params.get("uri").and_then(|x| x.as_str()).unwrap_or_default();
+    let response = match engine.read_resource(uri) {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(e) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": -32002, "message": e, "data": { "uri": uri } }
+        }),
+    };
+    Ok(Some(response))
+}
+
+fn handle_tools_list(id: Option<Value>, backend: StorageBackend) -> Option<Value> {
+    id.map(|id| {
+        let tools = tools_for_backend(tool_definitions(), backend);
+        json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "result": { "tools": tools_with_default_namespace(tools, default_namespace().is_some()) }
+        })
+    })
+}
+
+/// 只公布当前存储后端支持的 tool（SQLite / memory 后端暂不支持索引维护、导入导出、namespace 管理等）。
+fn tools_for_backend(mut tools: Vec<Value>, backend: StorageBackend) -> Vec<Value> {
+    tools.retain(|tool| tool["name"].as_str().is_some_and(|name| backend.supports_tool(name)));
+    tools
+}
+
+/// 设置了默认 namespace 时，除 namespace_delete 外的 tool 不再要求传入 `namespace`。
+fn tools_with_default_namespace(mut tools: Vec<Value>, has_default: bool) -> Vec<Value> {
+    if !has_default {
+        return tools;
+    }
+    for tool in &mut tools {
+        if tool["name"] == "namespace_delete" {
+            continue;
+        }
+        if let Some(required) = tool["inputSchema"]["required"].as_array_mut() {
+            required.retain(|x| x != "namespace");
+        }
+    }
+    tools
 }
 
-fn handle_tools_call(engine: &mut MemoryEngine, id: Option<i64>, params: &Value) -> Result<Option<Value>, String> {
+/// tools/list 中公布的全部 tool（名称 / 描述 / inputSchema），也用于 `schema dump`。
+pub fn tool_definitions() -> Vec<Value> {
+    let tools = json!([
+        {
+            "name": "now",
+            "description": "获取当前时间（本地 + UTC），用于需要准确日期时间的回答/计算。",
+            "inputSchema": now_schema()
+        },
+        {
+            "name": "keywords_list",
+            "description": "列出指定 namespace 下已存在的关键字（已归一化为小写，用于复用短关键字）。",
+            "inputSchema": keywords_list_schema()
+        },
+        {
+            "name": "keywords_list_global",
+            "description": "列出全局已存在的关键字（跨 namespace 汇总；关键字已归一化为小写）。",
+            "inputSchema": keywords_list_global_schema()
+        },
+        {
+            "name": "keywords_complete",
+            "description": "关键字补全：按前缀（默认）或子串查找已存在的关键字并附带引用条数，可限定 namespace 或在全局词表中查找；用于在编写 remember 前提示可复用的标签。",
+            "inputSchema": keywords_complete_schema()
+        },
+        {
+            "name": "keywords_audit",
+            "description": "关键字体检：标记疑似无效的关键字（单字、纯数字、只用过一次、与已有关键字近似重复），给出合并/删除建议并列出引用它们的记忆 id（可用 update 替换关键字执行建议），用于保持词表整洁。",
+            "inputSchema": keywords_audit_schema()
+        },
+        {
+            "name": "namespaces_list",
+            "description": "列出存储中已存在的全部 namespace（{userId}/{projectId}），附带记忆条数与最近写入时间。",
+            "inputSchema": namespaces_list_schema()
+        },
+        {
+            "name": "namespace_freeze",
+            "description": "冻结 namespace（只读）：之后 remember/update/forget/transaction 均被拒绝，recall 不受影响。",
+            "inputSchema": namespace_freeze_schema()
+        },
+        {
+            "name": "namespace_unfreeze",
+            "description": "解除 namespace 的冻结状态，恢复可写。",
+            "inputSchema": namespace_unfreeze_schema()
+        },
+        {
+            "name": "namespace_rename",
+            "description": "重命名 namespace（如项目改名）：移动目录、改写记录中的 namespace 字段并重建索引；recorded_at 等字段保持不变。",
+            "inputSchema": namespace_rename_schema()
+        },
+        {
+            "name": "namespace_delete",
+            "description": "删除整个 namespace（全部记忆与索引，不可恢复）；必须显式传入 confirm=true。",
+            "inputSchema": namespace_delete_schema()
+        },
+        {
+            "name": "remember",
+            "description": "记录一条长期记忆（关键字会归一化为小写；时间类关键字会被忽略 + 内容切片 + AI 日记），用于后续检索。也可只传 namespace + preview_token，按 remember_preview 的预览结果原样写入。",
+            "inputSchema": remember_confirm_schema()
+        },
+        {
+            "name": "remember_preview",
+            "description": "预览一条记忆而不写入：执行与 remember 相同的归一化，并做敏感信息脱敏、疑似重复检测与已有关键字建议；返回将要写入的内容与 preview_token（一次性，10 分钟内有效），确认无误后调用 remember 传入该令牌写入。不确定是否值得记录时先用它。",
+            "inputSchema": remember_schema()
+        },
+        {
+            "name": "remember_batch",
+            "description": "批量记录多条记忆（例如一轮对话中提炼出的多个事实）：同一 namespace 的条目一次性追加写入并只保存一次索引；逐条返回 id 或错误，单条失败不影响其它条。",
+            "inputSchema": remember_batch_schema()
+        },
+        {
+            "name": "recall",
+            "description": "按关键字/时间范围检索记忆，并返回最相关的若干条。",
+            "inputSchema": recall_schema()
+        },
+        {
+            "name": "recall_global",
+            "description": "跨 namespace 召回：在存储根目录下的全部 namespace（或匹配 namespaces 模式，如 u1/*）中按同样的条件检索，合并排序后返回，每条附带所属 namespace。适用于不确定记忆属于哪个项目的场景。",
+            "inputSchema": recall_global_schema()
+        },
+        {
+            "name": "get",
+            "description": "按 id 读取一条记忆的完整内容（含 diary/source/importance/revision），适用于已从 recall 得到 id 的场景。",
+            "inputSchema": get_schema()
+        },
+        {
+            "name": "history",
+            "description": "按 id 列出一条记忆的全部修订（旧 -> 新，含各版本完整内容与时间戳）；已删除的 id 也可查看删除前的版本。",
+            "inputSchema": get_schema()
+        },
+        {
+            "name": "pin",
+            "description": "按 id 置顶一条记忆：关键字召回时排在最前，无关键字召回时不受时间顺序影响、优先返回（置顶条数有上限）。写入新修订，无需提供 revision。",
+            "inputSchema": get_schema()
+        },
+        {
+            "name": "unpin",
+            "description": "按 id 取消置顶一条记忆（写入新修订，无需提供 revision）。",
+            "inputSchema": get_schema()
+        },
+        {
+            "name": "archive",
+            "description": "归档记忆：按 ids 和/或时间范围（start/end，按 occurred_at ?? recorded_at，两者取并集）批量标记为已归档；归档的记忆默认不再出现在 recall 结果中（include_archived=true 时返回），仍可 get。archived=false 取消归档。",
+            "inputSchema": archive_schema()
+        },
+        {
+            "name": "update",
+            "description": "按 id 修改一条已有记忆：仅覆盖提供的字段（keywords/slice/diary/occurred_at/importance/source），写入新修订并返回新的 revision。",
+            "inputSchema": update_schema()
+        },
+        {
+            "name": "forget",
+            "description": "按 id 删除一条记忆（需携带 remember/recall 返回的 revision）；删除后不再出现在 recall 结果中。",
+            "inputSchema": forget_schema()
+        },
+        {
+            "name": "stats",
+            "description": "统计指定 namespace：记忆条数、字节数、关键字数、重要度分布、occurred_at 覆盖范围与索引新鲜度。",
+            "inputSchema": stats_schema()
+        },
+        {
+            "name": "least_recalled",
+            "description": "列出指定 namespace 中最少被 recall 返回的记忆（被返回次数升序，其次最近返回时间与记忆时间由远到近），附 recall_count / last_recalled_at，用于找出可归档或删除的无用记忆。",
+            "inputSchema": least_recalled_schema()
+        },
+        {
+            "name": "compact",
+            "description": "压缩指定 namespace 的 memories.jsonl：只保留每条记忆的当前版本（去掉旧修订、重复的删除标记与已过期的记忆；已删除的记忆保留一条删除标记，供 sync 传播删除），原子替换文件并重建索引；返回压缩前后的行数与字节数。压缩后 history 只能看到当前版本。",
+            "inputSchema": stats_schema()
+        },
+        {
+            "name": "maintain",
+            "description": "重要度衰减：指定 namespace 中从未被 recall 返回、未置顶的记忆，按半衰期降低 importance（自最后一次修改起算，最低为 1），以新修订写入；dry_run=true 时只返回将要变化的条目。服务端设置 MEMORY_MAINTAIN_ON_OPEN=1 时打开 namespace 会自动执行一次。",
+            "inputSchema": maintain_schema()
+        },
+        {
+            "name": "purge_expired",
+            "description": "从存储中彻底清除指定 namespace 下已过期（expires_at）的记忆（含历史修订），重写数据文件并重建索引；返回被清除的 id。",
+            "inputSchema": stats_schema()
+        },
+        {
+            "name": "health",
+            "description": "体检指定 namespace：逐条校验记忆的 checksum，报告被外部修改/损坏或无法读取的记录。",
+            "inputSchema": health_schema()
+        },
+        {
+            "name": "verify",
+            "description": "检查指定 namespace 的存储完整性（fsck）：索引条目能否读出可解析的记录、孤立的倒排项、重复的 (id, revision)、数据文件末尾的残缺记录；只报告，不做修改（修复见 verify_repair）。",
+            "inputSchema": verify_schema()
+        },
+        {
+            "name": "verify_repair",
+            "description": "先按 verify 检查指定 namespace，再截掉数据文件末尾的残缺记录并在有坏条目/孤立项时全量重建索引；重复记录只报告，不改动。",
+            "inputSchema": verify_schema()
+        },
+        {
+            "name": "export",
+            "description": "将指定 namespace 导出为可移植的 JSON 文档（带格式版本头），用于备份或跨机器迁移；可用 since 只导出某个序号/时间点之后的变更。",
+            "inputSchema": export_schema()
+        },
+        {
+            "name": "import",
+            "description": "导入 export 生成的文档：校验格式版本，按 id 去重后追加到目标 namespace 并增量索引；id 已存在且内容不同时保留现有版本并报告冲突。",
+            "inputSchema": import_schema()
+        },
+        {
+            "name": "transaction",
+            "description": "在同一 namespace 下原子地执行一组 remember/update/forget 步骤：要么全部生效，要么全部不生效。",
+            "inputSchema": transaction_schema()
+        }
+    ]);
+    let Value::Array(mut tools) = tools else {
+        return Vec::new();
+    };
+
+    // 所有 tool 都接受可选的 lang（只影响 content 中的文本摘要）。
+    for tool in &mut tools {
+        if let Some(props) = tool
+            .pointer_mut("/inputSchema/properties")
+            .and_then(|x| x.as_object_mut())
+        {
+            props.insert("lang".to_string(), lang_property());
+        }
+        let name = tool["name"].as_str().unwrap_or_default().to_string();
+        if let Some((title, annotations)) = tool_annotations(&name) {
+            tool["title"] = json!(title);
+            tool["annotations"] = annotations;
+        }
+        if let Some(schema) = output_schema(&name) {
+            tool["outputSchema"] = schema;
+        }
+    }
+    tools
+}
+
+/// tool 的显示名称与行为提示（MCP tool annotations），供客户端决定是否需要用户确认。
+///
+/// - readOnlyHint：不改变记忆与召回统计（打开 namespace 时的索引维护不计）；recall/recall_global 会记录召回统计，不是只读；
+/// - destructiveHint：可能删除或覆盖已有记忆（不保留历史修订）；
+/// - idempotentHint：以相同参数重复调用不会产生额外影响。
+fn tool_annotations(name: &str) -> Option<(&'static str, Value)> {
+    // (标题, 只读, 破坏性, 幂等)
+    let (title, read_only, destructive, idempotent) = match name {
+        "now" => ("当前时间", true, false, true),
+        "keywords_list" => ("列出关键字", true, false, true),
+        "keywords_list_global" => ("列出全局关键字", true, false, true),
+        "keywords_complete" => ("关键字补全", true, false, true),
+        "keywords_audit" => ("关键字体检", true, false, true),
+        "namespaces_list" => ("列出 namespace", true, false, true),
+        "namespace_freeze" => ("冻结 namespace", false, false, true),
+        "namespace_unfreeze" => ("解除冻结", false, false, true),
+        "namespace_rename" => ("重命名 namespace", false, false, false),
+        "namespace_delete" => ("删除 namespace", false, true, true),
+        "remember" => ("记住", false, false, false),
+        "remember_preview" => ("预览记忆", true, false, true),
+        "remember_batch" => ("批量记住", false, false, false),
+        "recall" => ("回忆", false, false, false),
+        "recall_global" => ("跨 namespace 回忆", false, false, false),
+        "get" => ("读取记忆", true, false, true),
+        "history" => ("修订历史", true, false, true),
+        "pin" => ("置顶", false, false, true),
+        "unpin" => ("取消置顶", false, false, true),
+        "archive" => ("归档", false, false, true),
+        "update" => ("修改记忆", false, false, false),
+        "forget" => ("删除记忆", false, true, true),
+        "stats" => ("统计", true, false, true),
+        "least_recalled" => ("最少召回", true, false, true),
+        "compact" => ("压缩存储", false, true, true),
+        "maintain" => ("重要度衰减", false, false, false),
+        "purge_expired" => ("清除过期记忆", false, true, true),
+        "health" => ("体检", true, false, true),
+        "verify" => ("完整性检查", true, false, true),
+        "verify_repair" => ("修复存储", false, true, true),
+        "export" => ("导出", true, false, true),
+        "import" => ("导入", false, true, false),
+        "transaction" => ("事务", false, true, false),
+        _ => return None,
+    };
+    let mut annotations = json!({
+        "title": title,
+        "readOnlyHint": read_only,
+        "openWorldHint": false
+    });
+    // destructiveHint / idempotentHint 只对会修改存储的 tool 有意义。
+    if !read_only {
+        annotations["destructiveHint"] = json!(destructive);
+        annotations["idempotentHint"] = json!(idempotent);
+    }
+    Some((title, annotations))
+}
+
+fn lang_property() -> Value {
+    json!({
+        "type": "string",
+        "description": "文本摘要（content.text）的语言：zh / en（可带地区后缀，如 en-US）；不影响存储内容与 data。缺省使用环境变量 MEMORY_LANG，未设置时为 zh。"
+    })
+}
+
+fn handle_tools_call(engine: &mut MemoryEngine, id: Option<Value>, params: &Value) -> Result<Option<Value>, String> {
     let Some(id) = id else {
         return Ok(None);
     };
@@ -105,21 +664,200 @@ fn handle_tools_call(engine: &mut MemoryEngine, id: Option<i64>, params: &Value)
     let tool_name = params.get("name").and_then(|x| x.as_str()).unwrap_or_default();
     let args = params.get("arguments").cloned().unwrap_or_else(|| json!({}));
 
+    // 每次调用单独指定文本摘要语言；未指定时回到默认语言（MEMORY_LANG）。
+    let lang = match args.get("lang").and_then(|x| x.as_str()) {
+        Some(text) if !text.trim().is_empty() => Some(Lang::parse(text)?),
+        _ => None,
+    };
+    engine.use_lang(lang);
+
+    let keyword_sort = || match args.get("sort").and_then(|x| x.as_str()) {
+        Some(text) => KeywordSort::parse(text),
+        None => Ok(KeywordSort::default()),
+    };
+
     let result = match tool_name {
         "now" => engine.now()?,
         "keywords_list" => {
+            let namespace = get_namespace(&args)?;
+            engine.keywords_list(namespace, keyword_sort()?)?
+        }
+        "keywords_list_global" => engine.keywords_list_global(keyword_sort()?)?,
+        "keywords_complete" => engine.keywords_complete(KeywordsCompleteArgs::from_json(&args)?)?,
+        "keywords_audit" => {
+            let namespace = get_namespace(&args)?;
+            let limit = args.get("limit").and_then(|x| x.as_u64()).unwrap_or(100).clamp(1, 1000) as usize;
+            engine.keywords_audit(namespace, limit)?
+        }
+        "namespaces_list" => {
+            let refresh = args.get("refresh").and_then(|x| x.as_bool()).unwrap_or(false);
+            engine.namespaces_list(refresh)?
+        }
+        "namespace_freeze" => {
+            let namespace = get_namespace(&args)?;
+            let reason = args
+                .get("reason")
+                .and_then(|x| x.as_str())
+                .map(|x| x.trim().to_string())
+                .filter(|x| !x.is_empty());
+            engine.namespace_freeze(namespace, reason)?
+        }
+        "namespace_unfreeze" => {
+            let namespace = get_namespace(&args)?;
+            engine.namespace_unfreeze(namespace)?
+        }
+        "namespace_rename" => {
+            let from = get_required_string(&args, "from")?;
+            let to = get_required_string(&args, "to")?;
+            engine.namespace_rename(from, to)?
+        }
+        "namespace_delete" => {
+            // 不可恢复的删除不使用默认 namespace，必须显式指定。
             let namespace = get_required_string(&args, "namespace")?;
-            engine.keywords_list(namespace)?
+            let confirm = args.get("confirm").and_then(|x| x.as_bool()).unwrap_or(false);
+            engine.namespace_delete(namespace, confirm)?
         }
-        "keywords_list_global" => engine.keywords_list_global()?,
-        "remember" => {
+        "remember" => match args.get("preview_token").and_then(|x| x.as_str()) {
+            Some(token) if !token.trim().is_empty() => {
+                let namespace = get_namespace(&args)?;
+                engine.remember_previewed(&namespace, token)?
+            }
+            _ => {
+                let parsed = RememberArgs::from_json(&args)?;
+                engine.remember(parsed)?
+            }
+        },
+        "remember_preview" => {
             let parsed = RememberArgs::from_json(&args)?;
-            engine.remember(parsed)?
+            engine.remember_preview(parsed)?
+        }
+        "remember_batch" => {
+            let Some(items) = args.get("items").and_then(|x| x.as_array()) else {
+                return Err("items 不能为空".to_string());
+            };
+            if items.is_empty() {
+                return Err("items 不能为空".to_string());
+            }
+
+            let default_namespace = args.get("namespace").cloned();
+            let parsed = items
+                .iter()
+                .map(|item| {
+                    let mut item = item.clone();
+                    if let (Some(obj), Some(ns)) = (item.as_object_mut(), &default_namespace) {
+                        obj.entry("namespace").or_insert_with(|| ns.clone());
+                    }
+                    RememberArgs::from_json(&item)
+                })
+                .collect();
+            engine.remember_batch(parsed)?
         }
         "recall" => {
             let parsed = RecallArgs::from_json(&args)?;
             engine.recall(parsed)?
         }
+        "recall_global" => {
+            let parsed = RecallGlobalArgs::from_json(&args)?;
+            engine.recall_global(parsed)?
+        }
+        "get" => {
+            let namespace = get_namespace(&args)?;
+            let memory_id = get_required_string(&args, "id")?;
+            let if_none_match = args.get("if_none_match").and_then(|x| x.as_str());
+            engine.get(namespace, memory_id, if_none_match)?
+        }
+        "archive" => {
+            let parsed = ArchiveArgs::from_json(&args)?;
+            engine.archive(parsed)?
+        }
+        "pin" | "unpin" => {
+            let namespace = get_namespace(&args)?;
+            let memory_id = get_required_string(&args, "id")?;
+            engine.set_pinned(namespace, memory_id, tool_name == "pin")?
+        }
+        "history" => {
+            let namespace = get_namespace(&args)?;
+            let memory_id = get_required_string(&args, "id")?;
+            engine.history(namespace, memory_id)?
+        }
+        "update" => {
+            let namespace = get_namespace(&args)?;
+            let parsed = UpdateArgs::from_json(&args)?;
+            engine.update(namespace, parsed)?
+        }
+        "forget" => {
+            let parsed = ForgetArgs::from_json(&args)?;
+            engine.forget(parsed)?
+        }
+        "stats" => {
+            let namespace = get_namespace(&args)?;
+            engine.stats(namespace)?
+        }
+        "least_recalled" => {
+            let namespace = get_namespace(&args)?;
+            let limit = match args.get("limit").and_then(|x| x.as_u64()) {
+                Some(n) if n > 0 => (n as usize).min(RECALL_LIMIT_CEILING),
+                _ => RECALL_DEFAULT_LIMIT,
+            };
+            engine.least_recalled(namespace, limit)?
+        }
+        "compact" => {
+            let namespace = get_namespace(&args)?;
+            engine.compact(namespace)?
+        }
+        "maintain" => {
+            let namespace = get_namespace(&args)?;
+            let half_life_days = match args.get("half_life_days") {
+                None | Some(Value::Null) => None,
+                Some(value) => {
+                    let days = value.as_f64().ok_or_else(|| "half_life_days 必须是数字".to_string())?;
+                    Some(check_half_life_days(days)?)
+                }
+            };
+            let dry_run = args.get("dry_run").and_then(|x| x.as_bool()).unwrap_or(false);
+            engine.maintain(namespace, half_life_days, dry_run)?
+        }
+        "purge_expired" => {
+            let namespace = get_namespace(&args)?;
+            engine.purge_expired(namespace)?
+        }
+        "health" => {
+            let namespace = get_namespace(&args)?;
+            engine.health(namespace)?
+        }
+        "verify" | "verify_repair" => {
+            let namespace = get_namespace(&args)?;
+            engine.verify(namespace, tool_name == "verify_repair")?
+        }
+        "export" => {
+            let namespace = get_namespace(&args)?;
+            let since = match args.get("since") {
+                Some(Value::String(s)) if !s.trim().is_empty() => Some(s.trim().to_string()),
+                Some(Value::Number(n)) => Some(n.to_string()),
+                _ => None,
+            };
+            engine.export(&namespace, since.as_deref())?
+        }
+        "import" => {
+            let dump = match args.get("document") {
+                Some(Value::String(text)) => ExportDump::parse(text)?,
+                Some(doc @ Value::Object(_)) => ExportDump::from_value(doc.clone())?,
+                _ => return Err("document 不能为空".to_string()),
+            };
+            let namespace = args
+                .get("namespace")
+                .and_then(|x| x.as_str())
+                .map(|x| x.to_string());
+            let policy = match args.get("on_conflict").and_then(|x| x.as_str()) {
+                Some(text) => ConflictPolicy::parse(text)?,
+                None => ConflictPolicy::default(),
+            };
+            engine.import(namespace, dump, &mut |existing, incoming| policy.decide(existing, incoming), false)?
+        }
+        "transaction" => {
+            let parsed = TransactionArgs::from_json(&args)?;
+            engine.transaction(parsed)?
+        }
         _ => {
             return Ok(Some(json!({
                 "jsonrpc": "2.0",
@@ -129,6 +867,16 @@ fn handle_tools_call(engine: &mut MemoryEngine, id: Option<i64>, params: &Value)
         }
     };
 
+    // MCP 2025-06-18：结构化结果放在 structuredContent（与 outputSchema 对应）；data 只发给旧协议或声明需要的客户端。
+    let mut result = result;
+    let (structured, keep_data) = engine.result_fields();
+    if structured {
+        let data = match result.as_object_mut() {
+            Some(obj) if !keep_data => obj.remove("data"),
+            _ => result.get("data").cloned(),
+        };
+        result["structuredContent"] = data.filter(|x| x.is_object()).unwrap_or_else(|| json!({}));
+    }
     Ok(Some(json!({
         "jsonrpc": "2.0",
         "id": id,
@@ -136,15 +884,277 @@ fn handle_tools_call(engine: &mut MemoryEngine, id: Option<i64>, params: &Value)
     })))
 }
 
-fn now_schema() -> Value {
+/// tools/call 结果中 `structuredContent` 的 JSON Schema（tools/list 的 `outputSchema`）；未知 tool 返回 None。
+///
+/// 只列出主要字段且均不要求必填：recall 的 compact 格式不返回结构化结果，此时 structuredContent 为空对象。
+fn output_schema(name: &str) -> Option<Value> {
+    let string = json!({ "type": "string" });
+    let nullable_string = json!({ "type": ["string", "null"] });
+    let integer = json!({ "type": "integer" });
+    let boolean = json!({ "type": "boolean" });
+    let object = json!({ "type": "object" });
+    let strings = json!({ "type": "array", "items": { "type": "string" } });
+    let objects = json!({ "type": "array", "items": { "type": "object" } });
+    let validators = |mut properties: Value| {
+        properties["generation"] = integer.clone();
+        properties["etag"] = string.clone();
+        properties["last_modified"] = string.clone();
+        properties["not_modified"] = boolean.clone();
+        properties
+    };
+    let properties = match name {
+        "now" => json!({
+            "utc_rfc3339": string,
+            "utc_ts": integer,
+            "local_rfc3339": string,
+            "local_offset_seconds": integer,
+            "local_offset_minutes": integer
+        }),
+        "keywords_list" => json!({
+            "namespace": { "type": "string", "description": "namespace 或模式（如 `u1/*`）。" },
+            "namespaces": { "type": "array", "items": { "type": "string" }, "description": "按模式列出时匹配到的 namespace。" },
+            "total": integer,
+            "sort": string,
+            "keywords": strings,
+            "hot_keywords": { "type": "array", "items": { "type": "object" }, "description": "近期召回热度最高的关键字（keyword + score）。" }
+        }),
+        "keywords_list_global" => json!({
+            "total": integer,
+            "sort": string,
+            "scanned_namespaces": integer,
+            "keywords": objects
+        }),
+        "keywords_complete" => json!({
+            "text": string,
+            "match": string,
+            "total": integer,
+            "namespace": string,
+            "keywords": objects
+        }),
+        "keywords_audit" => json!({
+            "namespace": string,
+            "vocabulary": integer,
+            "flagged": integer,
+            "by_reason": object,
+            "by_action": object,
+            "truncated": boolean,
+            "findings": objects
+        }),
+        "namespaces_list" => json!({
+            "total": integer,
+            "namespaces": objects
+        }),
+        "namespace_freeze" => json!({
+            "namespace": string,
+            "frozen": boolean,
+            "frozen_at": string,
+            "reason": nullable_string
+        }),
+        "namespace_unfreeze" => json!({
+            "namespace": string,
+            "frozen": boolean,
+            "was_frozen": boolean
+        }),
+        "namespace_rename" => json!({
+            "from": string,
+            "to": string,
+            "records": { "type": "integer", "description": "改写的记录行数。" },
+            "items": { "type": "integer", "description": "重命名后的记忆条数。" }
+        }),
+        "namespace_delete" => json!({
+            "namespace": string,
+            "deleted": boolean
+        }),
+        "remember" => json!({
+            "namespace": string,
+            "id": string,
+            "revision": integer,
+            "recorded_at": string,
+            "occurred_at": nullable_string,
+            "keywords": strings
+        }),
+        "remember_preview" => json!({
+            "namespace": string,
+            "preview_token": { "type": "string", "description": "传给 remember 的 preview_token 以确认写入。" },
+            "keywords": strings,
+            "tags": strings,
+            "kind": string,
+            "slice": string,
+            "diary": string,
+            "occurred_at": nullable_string,
+            "redactions": objects,
+            "duplicates": objects,
+            "suggested_keywords": objects,
+            "new_keywords": strings
+        }),
+        "remember_batch" => json!({
+            "written": integer,
+            "failed": integer,
+            "items": { "type": "array", "items": { "type": "object" }, "description": "按输入顺序的结果：ok=true 时带 id/revision，否则带 error。" }
+        }),
+        "recall" => validators(json!({
+            "namespace": string,
+            "total": { "type": "integer", "description": "本页返回的条数。" },
+            "offset": integer,
+            "next_cursor": nullable_string,
+            "items": objects,
+            "warnings": strings
+        })),
+        "recall_global" => json!({
+            "namespaces": { "type": "array", "items": { "type": "string" }, "description": "参与检索的 namespace。" },
+            "total": { "type": "integer", "description": "本页返回的条数。" },
+            "offset": integer,
+            "has_more": boolean,
+            "items": objects,
+            "warnings": { "type": "array", "items": { "type": "object" }, "description": "跳过的记录（namespace + warning）与无法读取的 namespace（namespace + error）。" }
+        }),
+        "get" => validators(json!({
+            "namespace": string,
+            "item": memory_item_schema(),
+            "superseded_by": strings
+        })),
+        "history" => json!({
+            "namespace": string,
+            "id": string,
+            "deleted": boolean,
+            "versions": objects
+        }),
+        "pin" | "unpin" => json!({
+            "namespace": string,
+            "id": string,
+            "pinned": boolean,
+            "revision": integer
+        }),
+        "archive" => json!({
+            "namespace": string,
+            "archived": boolean,
+            "changed": { "type": "array", "items": { "type": "object" }, "description": "状态发生变化的记忆（id + revision）。" }
+        }),
+        "update" => json!({
+            "namespace": string,
+            "id": string,
+            "revision": integer
+        }),
+        "forget" => json!({
+            "namespace": string,
+            "id": string,
+            "forgotten": boolean
+        }),
+        "stats" => json!({
+            "namespace": string,
+            "stats": object
+        }),
+        "least_recalled" => json!({
+            "namespace": string,
+            "items": objects,
+            "has_more": boolean,
+            "warnings": strings
+        }),
+        "compact" => json!({
+            "namespace": string,
+            "records_before": integer,
+            "records_after": integer,
+            "records_removed": integer,
+            "bytes_before": integer,
+            "bytes_after": integer
+        }),
+        "maintain" => json!({
+            "namespace": string,
+            "half_life_days": { "type": "number" },
+            "dry_run": boolean,
+            "decayed": objects
+        }),
+        "purge_expired" => json!({
+            "namespace": string,
+            "purged": integer
+        }),
+        "health" => json!({
+            "namespace": string,
+            "healthy": boolean,
+            "checksums": object
+        }),
+        "verify" | "verify_repair" => json!({
+            "namespace": string,
+            "clean": boolean,
+            "report": object
+        }),
+        "export" => json!({
+            "format": string,
+            "format_version": integer,
+            "namespace": string,
+            "exported_at": string,
+            "since": string,
+            "next_seq": integer,
+            "items": integer,
+            "deletes": integer,
+            "records": objects
+        }),
+        "import" => json!({
+            "namespace": string,
+            "source_namespace": string,
+            "format_version": integer,
+            "dry_run": boolean,
+            "report": object
+        }),
+        "transaction" => json!({
+            "namespace": string,
+            "steps": { "type": "array", "items": { "type": "object" }, "description": "按顺序执行的步骤（op + id + revision）。" }
+        }),
+        _ => return None,
+    };
+    Some(json!({
+        "type": "object",
+        "description": "结构化结果；字段含义见 README 中各 tool 的“返回”说明。",
+        "properties": properties
+    }))
+}
+
+/// 所有 tool 共用的返回结构：`content` 为给模型阅读的文本，`structuredContent` 或 `data` 为结构化结果（`structured` 为该 tool 的 outputSchema）。
+pub fn tool_result_schema(structured: Option<&Value>) -> Value {
+    let structured = structured.cloned().unwrap_or_else(|| json!({ "type": "object" }));
+    let mut structured_content = structured.clone();
+    structured_content["description"] =
+        json!("结构化结果（MCP 2025-06-18），符合该 tool 在 tools/list 中的 outputSchema；recall 的 compact 格式为空对象。");
+    let mut data = structured;
+    data["description"] = json!("与 structuredContent 相同的结构化结果，发给 2024-11-05 协议的客户端（新协议需在 initialize 中声明 experimental.toolResultData）。");
     json!({
         "type": "object",
-        "additionalProperties": false,
-        "properties": {}
+        "required": ["content"],
+        "properties": {
+            "content": {
+                "type": "array",
+                "items": {
+                    "oneOf": [
+                        {
+                            "type": "object",
+                            "required": ["type", "text"],
+                            "properties": {
+                                "type": { "const": "text" },
+                                "text": { "type": "string" }
+                            }
+                        },
+                        {
+                            "type": "object",
+                            "required": ["type", "uri", "name"],
+                            "description": "recall 结果中每条记忆的资源链接（仅 2025-06-18 协议），可用 resources/read 读取完整记录。",
+                            "properties": {
+                                "type": { "const": "resource_link" },
+                                "uri": { "type": "string", "description": "memory://{userId}/{projectId}/{id}" },
+                                "name": { "type": "string" },
+                                "description": { "type": "string" },
+                                "mimeType": { "type": "string" }
+                            }
+                        }
+                    ]
+                }
+            },
+            "structuredContent": structured_content,
+            "data": data
+        }
     })
 }
 
-fn keywords_list_global_schema() -> Value {
+fn now_schema() -> Value {
     json!({
         "type": "object",
         "additionalProperties": false,
@@ -152,141 +1162,1451 @@ fn keywords_list_global_schema() -> Value {
     })
 }
 
-fn keywords_list_schema() -> Value {
+fn keywords_list_global_schema() -> Value {
     json!({
         "type": "object",
         "additionalProperties": false,
-        "required": ["namespace"],
         "properties": {
-            "namespace": {
-                "type": "string",
-                "minLength": 1,
-                "description": "命名空间：必须为 {userId}/{projectId}（严格两段；会做分隔符归一化与路径净化）。"
-            }
+            "sort": keyword_sort_schema()
         }
     })
 }
 
-fn get_required_string(v: &Value, key: &str) -> Result<String, String> {
-    let Some(s) = v.get(key).and_then(|x| x.as_str()) else {
-        return Err(format!("{key} 不能为空"));
-    };
-    let s = s.trim().to_string();
-    if s.is_empty() {
-        return Err(format!("{key} 不能为空"));
-    }
-    Ok(s)
+fn keyword_sort_schema() -> Value {
+    json!({
+        "type": "string",
+        "enum": ["length", "alpha", "pinyin", "frequency"],
+        "default": "length",
+        "description": "排序：length=字符数升序（同长按引用条数降序）；alpha=字典序（Unicode 码位）；pinyin=中文按拼音（ICU 排序规则，汉字在前、拉丁字母在后）；frequency=引用条数降序。"
+    })
 }
 
-fn remember_schema() -> Value {
+fn keywords_complete_schema() -> Value {
     json!({
         "type": "object",
         "additionalProperties": false,
-        "required": ["namespace", "keywords", "slice", "diary"],
+        "required": ["text"],
         "properties": {
-            "namespace": {
-                "type": "string",
-                "description": "命名空间：必须为 {userId}/{projectId}（严格两段），用于隔离不同用户/项目的记忆；会做分隔符归一化与路径净化。"
-            },
-            "keywords": {
-                "type": "array",
-                "minItems": 1,
-                "items": { "type": "string" },
-                "description": "关键字列表（至少 1 个，建议 2~8 个；会做 trim+lowercase 并去重；时间类关键字会被忽略）。"
-            },
-            "slice": {
+            "text": {
                 "type": "string",
-                "description": "重要内容切片（短文本，可展示/可检索）。"
+                "minLength": 1,
+                "description": "已输入的关键字片段（会做 trim+lowercase）。"
             },
-            "diary": {
+            "namespace": {
                 "type": "string",
-                "description": "AI 日记（第一人称长文本，默认 recall 不返回）。"
+                "description": "可选：限定在该 namespace（{userId}/{projectId}）内查找；省略时在全局词表中查找。"
             },
-            "occurred_at": {
+            "match": {
                 "type": "string",
-                "description": "事件发生时间（RFC3339 或 YYYY-MM-DD）。"
+                "enum": ["prefix", "contains"],
+                "default": "prefix",
+                "description": "匹配方式：prefix=以输入开头；contains=包含输入（以输入开头的结果仍排在前面）。"
             },
-            "importance": {
+            "limit": {
                 "type": "integer",
                 "minimum": 1,
-                "maximum": 5,
-                "description": "重要度 1~5。"
-            },
-            "source": {
-                "type": "string",
-                "description": "来源信息（可选，例如会话/模块/页面）。"
+                "maximum": 100,
+                "default": 20,
+                "description": "最多返回条数。"
             }
         }
     })
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use serde_json::json;
-    use std::collections::HashSet;
-
-    #[test]
-    fn tools_list_should_include_keywords_tools() {
-        let dir = tempfile::TempDir::new().expect("create temp dir");
-        let mut engine = MemoryEngine::new(dir.path().to_path_buf());
-
-        let out = handle_stdin_line(
-            &mut engine,
+fn namespaces_list_schema() -> Value {
+    json!({
+        "type": "object",
+        "additionalProperties": false,
+        "properties": {
+            "refresh": {
+                "type": "boolean",
+                "default": false,
+                "description": "可选：遍历存储目录重建 catalog（用于发现绕过本服务直接拷入的 namespace）。"
+            }
+        }
+    })
+}
+
+fn namespace_freeze_schema() -> Value {
+    json!({
+        "type": "object",
+        "additionalProperties": false,
+        "required": ["namespace"],
+        "properties": {
+            "namespace": {
+                "type": "string",
+                "description": "要冻结的命名空间：{userId}/{projectId}（严格两段）。"
+            },
+            "reason": {
+                "type": "string",
+                "description": "可选：冻结原因（写入被拒绝时会出现在错误信息中）。"
+            }
+        }
+    })
+}
+
+fn namespace_unfreeze_schema() -> Value {
+    json!({
+        "type": "object",
+        "additionalProperties": false,
+        "required": ["namespace"],
+        "properties": {
+            "namespace": {
+                "type": "string",
+                "description": "要解除冻结的命名空间：{userId}/{projectId}（严格两段）。"
+            }
+        }
+    })
+}
+
+fn namespace_rename_schema() -> Value {
+    json!({
+        "type": "object",
+        "additionalProperties": false,
+        "required": ["from", "to"],
+        "properties": {
+            "from": {
+                "type": "string",
+                "description": "原命名空间：{userId}/{projectId}（严格两段）。"
+            },
+            "to": {
+                "type": "string",
+                "description": "新命名空间：{userId}/{projectId}（严格两段；不能已存在）。"
+            }
+        }
+    })
+}
+
+fn namespace_delete_schema() -> Value {
+    json!({
+        "type": "object",
+        "additionalProperties": false,
+        "required": ["namespace", "confirm"],
+        "properties": {
+            "namespace": {
+                "type": "string",
+                "description": "要删除的命名空间：{userId}/{projectId}（严格两段）。"
+            },
+            "confirm": {
+                "type": "boolean",
+                "description": "安全确认：必须为 true 才会执行删除。"
+            }
+        }
+    })
+}
+
+fn keywords_list_schema() -> Value {
+    json!({
+        "type": "object",
+        "additionalProperties": false,
+        "required": ["namespace"],
+        "properties": {
+            "namespace": {
+                "type": "string",
+                "minLength": 1,
+                "description": "命名空间：必须为 {userId}/{projectId}（严格两段；会做分隔符归一化与路径净化）。也可以是模式，两段各自可含通配符 *（如 u1/* 表示该用户的全部项目），此时合并所有匹配 namespace 的关键字。"
+            },
+            "sort": keyword_sort_schema()
+        }
+    })
+}
+
+fn keywords_audit_schema() -> Value {
+    json!({
+        "type": "object",
+        "additionalProperties": false,
+        "required": ["namespace"],
+        "properties": {
+            "namespace": {
+                "type": "string",
+                "minLength": 1,
+                "description": "命名空间：必须为 {userId}/{projectId}（严格两段；会做分隔符归一化与路径净化）。"
+            },
+            "limit": {
+                "type": "integer",
+                "minimum": 1,
+                "maximum": 1000,
+                "default": 100,
+                "description": "最多返回的可疑关键字数（按合并、删除、待确认排序；统计不受影响）。"
+            }
+        }
+    })
+}
+
+fn get_required_string(v: &Value, key: &str) -> Result<String, String> {
+    let Some(s) = v.get(key).and_then(|x| x.as_str()) else {
+        return Err(format!("{key} 不能为空"));
+    };
+    let s = s.trim().to_string();
+    if s.is_empty() {
+        return Err(format!("{key} 不能为空"));
+    }
+    Ok(s)
+}
+
+/// 关键字元素：字符串，或带权重的对象（权重越高越是该记忆的核心主题，影响 recall 排序）。
+fn weighted_keyword_schema() -> Value {
+    json!({
+        "oneOf": [
+            { "type": "string" },
+            {
+                "type": "object",
+                "additionalProperties": false,
+                "required": ["keyword"],
+                "properties": {
+                    "keyword": { "type": "string" },
+                    "weight": { "type": "integer", "minimum": 1, "maximum": KEYWORD_WEIGHT_MAX, "description": "权重（默认 1）。" }
+                }
+            }
+        ]
+    })
+}
+
+fn remember_schema() -> Value {
+    json!({
+        "type": "object",
+        "additionalProperties": false,
+        "required": ["namespace", "keywords", "slice", "diary"],
+        "properties": {
+            "namespace": {
+                "type": "string",
+                "description": "命名空间：必须为 {userId}/{projectId}（严格两段），用于隔离不同用户/项目的记忆；会做分隔符归一化与路径净化。"
+            },
+            "keywords": {
+                "type": "array",
+                "minItems": 1,
+                "items": weighted_keyword_schema(),
+                "description": "关键字列表（至少 1 个，建议 2~8 个；会做 trim+lowercase 并去重；时间类关键字会被忽略）。元素可为字符串，或 {keyword, weight} 对象标注核心关键字（weight 1~10，默认 1；recall 按命中关键字的权重和排序）。"
+            },
+            "tags": {
+                "type": "array",
+                "items": { "type": "string" },
+                "description": "组织用标签（可选，如 project-alpha、confidential；trim+lowercase 并去重）。单独索引，只用于 recall 过滤，不参与关键字召回与排序。"
+            },
+            "kind": {
+                "type": "string",
+                "enum": MemoryKind::names(),
+                "description": "记忆类型（可选）：fact 事实 / preference 偏好 / event 事件 / task 待办 / decision 决策；recall 可按类型过滤。"
+            },
+            "slice": {
+                "type": "string",
+                "description": "重要内容切片（短文本，可展示/可检索）。"
+            },
+            "diary": {
+                "type": "string",
+                "description": "AI 日记（第一人称长文本，默认 recall 不返回）。"
+            },
+            "occurred_at": {
+                "type": "string",
+                "description": "事件发生时间（RFC3339 或 YYYY-MM-DD）。"
+            },
+            "importance": {
+                "type": "integer",
+                "minimum": 1,
+                "maximum": 5,
+                "description": "重要度 1~5。"
+            },
+            "source": source_schema(),
+            "metadata": { "type": "object", "description": "附加的结构化数据（可选，JSON 对象，如 {\"ticket\":\"ERP-12\",\"path\":\"src/a.rs\"}；序列化后最多 8KB）。不进入索引，recall 结果原样返回，可在 query 中用 metadata.key=value 过滤。" },
+            "relates_to": {
+                "type": "array",
+                "items": { "type": "string" },
+                "description": "可选：相关记忆的 id（须为当前存在的记忆）；recall 结果的 links.relates_to 中返回，可用 get 跟随。"
+            },
+            "supersedes": {
+                "type": "string",
+                "description": "可选：本记忆取代的旧记忆 id（如更正后的结论取代旧结论）；旧记忆默认不再出现在 recall 结果中，仍可用 get 读取。"
+            },
+            "expires_at": {
+                "type": "string",
+                "description": "可选：过期时间（RFC3339，或 YYYY-MM-DD 表示当天结束）；过期后不再出现在 recall 结果中，可用 purge_expired 从存储中清除。"
+            },
+            "pinned": {
+                "type": "boolean",
+                "default": false,
+                "description": "可选：置顶。关键字召回时排在最前；无关键字召回时不受时间顺序影响、优先返回（条数有上限）。也可之后用 pin/unpin 切换。"
+            }
+        }
+    })
+}
+
+/// remember：在 remember_schema 的基础上允许只传 preview_token 确认预览内容。
+fn remember_confirm_schema() -> Value {
+    let mut schema = remember_schema();
+    schema["required"] = json!(["namespace"]);
+    schema["properties"]["preview_token"] = json!({
+        "type": "string",
+        "description": "可选：remember_preview 返回的 data.preview_token；提供时按预览内容原样写入，忽略其它字段（namespace 须与预览时一致）。未提供时 keywords/slice/diary 必填。"
+    });
+    schema
+}
+
+fn remember_batch_schema() -> Value {
+    let mut item = remember_schema();
+    item["required"] = json!(["keywords", "slice", "diary"]);
+    item["properties"]["namespace"]["description"] = json!("可选：覆盖顶层 namespace。");
+
+    json!({
+        "type": "object",
+        "additionalProperties": false,
+        "required": ["items"],
+        "properties": {
+            "namespace": {
+                "type": "string",
+                "description": "默认命名空间：{userId}/{projectId}（严格两段）；条目未提供 namespace 时使用。"
+            },
+            "items": {
+                "type": "array",
+                "minItems": 1,
+                "items": item,
+                "description": "待记录的记忆列表，字段同 remember。"
+            }
+        }
+    })
+}
+
+fn recall_schema() -> Value {
+    json!({
+        "type": "object",
+        "additionalProperties": false,
+        "required": ["namespace"],
+        "properties": {
+            "namespace": {
+                "type": "string",
+                "description": "命名空间：必须为 {userId}/{projectId}（严格两段；会做分隔符归一化与路径净化）。也可以是模式，两段各自可含通配符 *（如 u1/*），此时按 recall_global 跨 namespace 召回，每条结果附带 namespace，不支持 cursor / if_none_match。"
+            },
+            "keywords": {
+                "type": "array",
+                "items": { "type": "string" },
+                "description": "关键字列表（可选）。支持通配符 `*`（如 `erp*` 同时命中 erp、erp迁移、erp-v2；`*` 可出现在任意位置），通配符只匹配记忆的关键字，不匹配正文分词。"
+            },
+            "tags": {
+                "type": "array",
+                "items": { "type": "string" },
+                "description": "可选：仅返回同时带有全部这些标签的记忆（不区分大小写）。"
+            },
+            "kind": {
+                "oneOf": [
+                    { "type": "string", "enum": MemoryKind::names() },
+                    { "type": "array", "items": { "type": "string", "enum": MemoryKind::names() } }
+                ],
+                "description": "可选：仅返回属于这些类型之一的记忆（如 [\"preference\", \"decision\"]）；未标注类型的记忆不会命中。"
+            },
+            "start": {
+                "type": "string",
+                "description": "起始时间（RFC3339 或 YYYY-MM-DD）。"
+            },
+            "end": {
+                "type": "string",
+                "description": "结束时间（RFC3339 或 YYYY-MM-DD）。"
+            },
+            "query": {
+                "type": "string",
+                "description": "自由文本查询（可选，包含匹配 slice/diary/source；支持 time>=... / time<=... / time=a..b 时间表达式，以及 dow:mon..fri、hour>=18、hour=22..2 等星期/时段过滤）。支持布尔语法：a AND b、a OR b（大写；AND 优先）、-排除词、-( ... ) 与括号分组；相邻的普通词视为一个词组。用双引号包裹短语（如 \"库存 盘点\"）要求其在正文中连续出现。metadata.key=value 按 metadata 字段过滤（键不区分大小写，a.b 访问嵌套字段，可加 - 排除）。"
+            },
+            "regex": {
+                "type": "boolean",
+                "default": false,
+                "description": "可选：将 query 整体视为正则表达式（Rust regex 语法，默认不区分大小写，可用 (?-i) 关闭），分别匹配 slice/diary/source 原文；此时不解析布尔语法与时间表达式（时间请用 start/end）。模式过长或编译后过于复杂时报错。"
+            },
+            "semantic": {
+                "type": "boolean",
+                "default": false,
+                "description": "可选：语义召回，按 query 文本（时间表达式仍生效，不解析布尔语法）与记忆 slice+diary 的向量相似度排序，结果附带 score；keywords 此时只做过滤。需服务端通过 MEMORY_EMBEDDING_CONFIG 配置本地向量化后端，未配置时报错。"
+            },
+            "limit": {
+                "type": "integer",
+                "minimum": 1,
+                "maximum": RECALL_LIMIT_CEILING,
+                "default": RECALL_DEFAULT_LIMIT,
+                "description": "单页最多返回条数。超过服务端上限（默认 100，可由 MEMORY_RECALL_MAX_LIMIT 或 namespace 设置调整）时按上限截断；更多结果用 next_cursor 翻页。"
+            },
+            "include_superseded": {
+                "type": "boolean",
+                "default": false,
+                "description": "可选：同时返回已被其它记忆取代（supersedes）的旧记忆（其 links.superseded_by 列出取代者）。默认隐藏。"
+            },
+            "include_archived": {
+                "type": "boolean",
+                "default": false,
+                "description": "可选：同时返回已归档（archive）的记忆。默认跳过。"
+            },
+            "max_bytes": {
+                "type": "integer",
+                "minimum": 1,
+                "description": "可选：单页结果的字节预算（按 items 序列化大小累计）。超出时提前截断本页并返回 next_cursor（至少返回 1 条），适合配合较大的 limit 批量读取。"
+            },
+            "include_diary": {
+                "type": "boolean",
+                "default": false,
+                "description": "是否返回 diary 字段（默认 false）。"
+            },
+            "format": {
+                "type": "string",
+                "enum": ["full", "compact"],
+                "default": "full",
+                "description": "输出格式：full=文本摘要 + data；compact=每条一行 date|importance|keywords|slice，且不返回 data（大量召回时节省 token）。"
+            },
+            "ranking": {
+                "type": "string",
+                "enum": ["relevance", "decay"],
+                "description": "可选：排序方式。relevance=关键字命中权重 → 重要度 → 时间（无关键字时按时间倒序）；decay=相关度 × 时间衰减（按半衰期指数衰减）× 访问加成（被召回次数越多越靠前），让新近且常用的记忆排在前面。未提供时使用服务端默认（MEMORY_RECALL_RANKING，默认 relevance）；语义召回始终按相似度排序。置顶记忆仍优先。"
+            },
+            "half_life_days": {
+                "type": "number",
+                "exclusiveMinimum": 0,
+                "description": "可选：decay 排序的半衰期（天），记忆每过一个半衰期得分减半。默认由服务端 MEMORY_DECAY_HALF_LIFE_DAYS 决定（未配置时为 30）。"
+            },
+            "if_none_match": {
+                "type": "string",
+                "description": "条件召回：传入上次以相同条件召回时返回的 data.etag；若 namespace 未发生变化，则只返回 data.not_modified=true。etag 与召回条件绑定，换了条件需重新获取。"
+            },
+            "written_by": {
+                "type": "string",
+                "description": "可选：仅返回由指定 agent 写入的记忆（匹配记录的 written_by，即 MEMORY_WRITER 或客户端名称）。"
+            },
+            "source_kind": {
+                "type": "string",
+                "description": "可选：仅返回来源类型（source.kind，不区分大小写）等于该值的记忆。"
+            },
+            "source_id": {
+                "type": "string",
+                "description": "可选：仅返回来源 id（source.id）等于该值的记忆，例如同一会话产生的全部记忆。"
+            },
+            "offset": {
+                "type": "integer",
+                "minimum": 0,
+                "default": 0,
+                "description": "可选：分页，跳过前 N 条命中结果。"
+            },
+            "cursor": {
+                "type": "string",
+                "description": "可选：分页，传入上一页返回的 data.next_cursor 获取下一页（优先于 offset）；期间 namespace 有写入时游标失效，需从第一页重新召回。"
+            }
+        }
+    })
+}
+
+/// recall_global 的参数：recall 的检索条件，namespace 换成可选的 namespaces 模式；不支持游标、条件召回与排序选项。
+fn recall_global_schema() -> Value {
+    let mut schema = recall_schema();
+    schema["required"] = json!([]);
+    if let Some(properties) = schema["properties"].as_object_mut() {
+        for key in ["namespace", "format", "ranking", "half_life_days", "if_none_match", "cursor", "max_bytes"] {
+            properties.remove(key);
+        }
+        properties.insert(
+            "namespaces".to_string(),
+            json!({
+                "type": "string",
+                "description": "可选：namespace 模式，两段各自可含通配符 *（如 u1/* 表示该用户的全部项目，*/erp 表示所有用户的 erp 项目）；缺省时检索全部 namespace。"
+            }),
+        );
+    }
+    schema
+}
+
+fn get_schema() -> Value {
+    json!({
+        "type": "object",
+        "additionalProperties": false,
+        "required": ["namespace", "id"],
+        "properties": {
+            "namespace": {
+                "type": "string",
+                "description": "命名空间：必须为 {userId}/{projectId}（严格两段）。"
+            },
+            "id": {
+                "type": "string",
+                "description": "记忆 id（取自 remember/recall 返回值）。"
+            },
+            "if_none_match": {
+                "type": "string",
+                "description": "条件读取：传入上次读取同一条记忆时返回的 data.etag；若 namespace 未发生变化，则只返回 data.not_modified=true。"
+            }
+        }
+    })
+}
+
+fn update_schema() -> Value {
+    json!({
+        "type": "object",
+        "additionalProperties": false,
+        "required": ["namespace", "id", "revision"],
+        "properties": {
+            "namespace": {
+                "type": "string",
+                "description": "命名空间：必须为 {userId}/{projectId}（严格两段）。"
+            },
+            "id": {
+                "type": "string",
+                "description": "要修改的记忆 id（取自 remember/recall 返回值）。"
+            },
+            "revision": {
+                "type": "integer",
+                "minimum": 1,
+                "description": "当前修订号（取自 remember/recall 返回值）；与最新版本不一致时以冲突错误拒绝修改。"
+            },
+            "keywords": {
+                "type": "array",
+                "items": weighted_keyword_schema(),
+                "description": "新的关键字列表（整体替换，权重随之替换；时间类关键字会被忽略）。元素格式同 remember。"
+            },
+            "tags": {
+                "type": "array",
+                "items": { "type": "string" },
+                "description": "新的标签列表（整体替换；空数组表示清空）。"
+            },
+            "kind": { "type": "string", "enum": MemoryKind::names(), "description": "新的记忆类型。" },
+            "slice": { "type": "string", "description": "新的内容切片。" },
+            "diary": { "type": "string", "description": "新的 AI 日记。" },
+            "occurred_at": { "type": "string", "description": "新的事件发生时间（RFC3339 / YYYY-MM-DD / 中文日期）。" },
+            "importance": { "type": "integer", "minimum": 1, "maximum": 5, "description": "新的重要度。" },
+            "source": source_schema(),
+            "metadata": { "type": "object", "description": "新的 metadata（整体替换；空对象表示清空）。" },
+            "relates_to": { "type": "array", "items": { "type": "string" }, "description": "新的相关记忆 id 列表（整体替换；空数组表示清空）。" },
+            "supersedes": { "type": "string", "description": "新的被取代记忆 id（空字符串表示清除）。" },
+            "expires_at": { "type": "string", "description": "新的过期时间（RFC3339 或 YYYY-MM-DD；空字符串表示清除，即永不过期）。" },
+            "pinned": { "type": "boolean", "description": "置顶 / 取消置顶。" },
+            "archived": { "type": "boolean", "description": "归档 / 取消归档。" }
+        }
+    })
+}
+
+fn forget_schema() -> Value {
+    json!({
+        "type": "object",
+        "additionalProperties": false,
+        "required": ["namespace", "id", "revision"],
+        "properties": {
+            "namespace": {
+                "type": "string",
+                "description": "命名空间：必须为 {userId}/{projectId}（严格两段）。"
+            },
+            "id": {
+                "type": "string",
+                "description": "要删除的记忆 id（取自 remember/recall 返回值）。"
+            },
+            "revision": {
+                "type": "integer",
+                "minimum": 1,
+                "description": "当前修订号（取自 remember/recall 返回值）；与最新版本不一致时以冲突错误拒绝删除。"
+            }
+        }
+    })
+}
+
+fn maintain_schema() -> Value {
+    json!({
+        "type": "object",
+        "additionalProperties": false,
+        "required": ["namespace"],
+        "properties": {
+            "namespace": {
+                "type": "string",
+                "description": "命名空间：必须为 {userId}/{projectId}（严格两段）。"
+            },
+            "half_life_days": {
+                "type": "number",
+                "exclusiveMinimum": 0,
+                "description": "可选：重要度衰减的半衰期（天），默认由服务端 MEMORY_IMPORTANCE_HALF_LIFE_DAYS 决定（未配置时为 90）。"
+            },
+            "dry_run": {
+                "type": "boolean",
+                "default": false,
+                "description": "可选：只返回将要衰减的记忆（id、from、to），不写入。"
+            }
+        }
+    })
+}
+
+fn least_recalled_schema() -> Value {
+    json!({
+        "type": "object",
+        "additionalProperties": false,
+        "required": ["namespace"],
+        "properties": {
+            "namespace": {
+                "type": "string",
+                "description": "命名空间：必须为 {userId}/{projectId}（严格两段）。"
+            },
+            "limit": {
+                "type": "integer",
+                "minimum": 1,
+                "maximum": RECALL_LIMIT_CEILING,
+                "default": RECALL_DEFAULT_LIMIT,
+                "description": "最多返回条数。"
+            }
+        }
+    })
+}
+
+fn archive_schema() -> Value {
+    json!({
+        "type": "object",
+        "additionalProperties": false,
+        "required": ["namespace"],
+        "properties": {
+            "namespace": {
+                "type": "string",
+                "description": "命名空间：必须为 {userId}/{projectId}（严格两段）。"
+            },
+            "ids": {
+                "type": "array",
+                "items": { "type": "string" },
+                "description": "要归档的记忆 id（须为当前存在的记忆）。"
+            },
+            "start": {
+                "type": "string",
+                "description": "时间范围起点（RFC3339 或 YYYY-MM-DD）：归档该范围内的全部记忆。"
+            },
+            "end": {
+                "type": "string",
+                "description": "时间范围终点（RFC3339 或 YYYY-MM-DD，含当天）。"
+            },
+            "archived": {
+                "type": "boolean",
+                "default": true,
+                "description": "false 表示取消归档。"
+            }
+        }
+    })
+}
+
+fn stats_schema() -> Value {
+    json!({
+        "type": "object",
+        "additionalProperties": false,
+        "required": ["namespace"],
+        "properties": {
+            "namespace": {
+                "type": "string",
+                "description": "命名空间：必须为 {userId}/{projectId}（严格两段）。"
+            }
+        }
+    })
+}
+
+fn export_schema() -> Value {
+    json!({
+        "type": "object",
+        "additionalProperties": false,
+        "required": ["namespace"],
+        "properties": {
+            "namespace": {
+                "type": "string",
+                "description": "命名空间：必须为 {userId}/{projectId}（严格两段）。"
+            },
+            "since": {
+                "type": ["string", "integer"],
+                "description": "可选：增量起点。整数为记录序号（上次导出的 next_seq），否则按时间解析（RFC3339 / YYYY-MM-DD）；增量导出会包含删除标记。"
+            }
+        }
+    })
+}
+
+fn import_schema() -> Value {
+    json!({
+        "type": "object",
+        "additionalProperties": false,
+        "required": ["document"],
+        "properties": {
+            "document": {
+                "type": ["object", "string"],
+                "description": "导出文档：export 返回的 data 对象，或 JSON / JSONL 文本（format=memory-export）。"
+            },
+            "namespace": {
+                "type": "string",
+                "description": "可选：目标命名空间（{userId}/{projectId}）；缺省为文档头中的 namespace。"
+            },
+            "on_conflict": {
+                "type": "string",
+                "enum": ["keep-existing", "keep-newest", "duplicate"],
+                "description": "可选：id 已存在且内容不同时的处理策略（默认 keep-existing）。keep-newest 按 updated_at ?? recorded_at 保留较新的一方；duplicate 以新 id 另存导入的记录。"
+            }
+        }
+    })
+}
+
+fn health_schema() -> Value {
+    json!({
+        "type": "object",
+        "additionalProperties": false,
+        "required": ["namespace"],
+        "properties": {
+            "namespace": {
+                "type": "string",
+                "description": "命名空间：必须为 {userId}/{projectId}（严格两段）。"
+            }
+        }
+    })
+}
+
+fn verify_schema() -> Value {
+    json!({
+        "type": "object",
+        "additionalProperties": false,
+        "required": ["namespace"],
+        "properties": {
+            "namespace": {
+                "type": "string",
+                "description": "命名空间：必须为 {userId}/{projectId}（严格两段）。"
+            }
+        }
+    })
+}
+
+fn transaction_schema() -> Value {
+    json!({
+        "type": "object",
+        "additionalProperties": false,
+        "required": ["namespace", "steps"],
+        "properties": {
+            "namespace": {
+                "type": "string",
+                "description": "命名空间：必须为 {userId}/{projectId}（严格两段；所有步骤共享该 namespace）。"
+            },
+            "steps": {
+                "type": "array",
+                "minItems": 1,
+                "description": "步骤列表（按顺序执行）。op=remember 的字段同 remember 工具；op=update 需要 id + revision，并可提供 keywords/slice/diary/occurred_at/importance/source；op=forget 需要 id + revision。revision 取自 remember/recall 返回值，不一致时整个事务以冲突错误失败。",
+                "items": {
+                    "type": "object",
+                    "required": ["op"],
+                    "properties": {
+                        "op": { "type": "string", "enum": ["remember", "update", "forget"] },
+                        "id": { "type": "string" },
+                        "revision": { "type": "integer", "minimum": 1 },
+                        "keywords": { "type": "array", "items": weighted_keyword_schema() },
+                        "tags": { "type": "array", "items": { "type": "string" } },
+                        "kind": { "type": "string", "enum": MemoryKind::names() },
+                        "slice": { "type": "string" },
+                        "diary": { "type": "string" },
+                        "occurred_at": { "type": "string" },
+                        "importance": { "type": "integer", "minimum": 1, "maximum": 5 },
+                        "source": source_schema(),
+                        "metadata": { "type": "object" },
+                        "relates_to": { "type": "array", "items": { "type": "string" } },
+                        "supersedes": { "type": "string" },
+                        "expires_at": { "type": "string" },
+                        "pinned": { "type": "boolean" },
+                        "archived": { "type": "boolean" }
+                    }
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+#[allow(clippy::err_expect)]
+mod tests {
+    use super::*;
+    use crate::memory::namespace_or;
+    use serde_json::json;
+    use std::collections::HashSet;
+
+    #[test]
+    fn tools_list_should_include_keywords_tools() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let mut engine = MemoryEngine::new(dir.path().to_path_buf());
+
+        let out = handle_stdin_line(
+            &mut engine,
             r#"{"jsonrpc":"2.0","id":1,"method":"tools/list","params":{}}"#,
         )
         .expect("handle")
         .expect("response");
         let v: Value = serde_json::from_str(&out).expect("json");
 
-        let tools = v["result"]["tools"].as_array().expect("tools array");
-        let names: HashSet<&str> = tools
-            .iter()
-            .filter_map(|t| t.get("name").and_then(|x| x.as_str()))
-            .collect();
-        for name in [
-            "now",
-            "keywords_list",
-            "keywords_list_global",
-            "remember",
-            "recall",
+        let tools = v["result"]["tools"].as_array().expect("tools array");
+        let names: HashSet<&str> = tools
+            .iter()
+            .filter_map(|t| t.get("name").and_then(|x| x.as_str()))
+            .collect();
+        for name in [
+            "now",
+            "keywords_list",
+            "keywords_list_global",
+            "keywords_complete",
+            "namespaces_list",
+            "namespace_freeze",
+            "namespace_unfreeze",
+            "namespace_rename",
+            "namespace_delete",
+            "remember",
+            "remember_preview",
+            "remember_batch",
+            "recall",
+            "recall_global",
+            "get",
+            "history",
+            "pin",
+            "unpin",
+            "archive",
+            "update",
+            "forget",
+            "stats",
+            "least_recalled",
+            "compact",
+            "maintain",
+            "purge_expired",
+            "health",
+            "verify",
+            "verify_repair",
+            "export",
+            "import",
+            "transaction",
+        ] {
+            assert!(names.contains(name), "missing tool: {name}");
+        }
+
+        // 每个 tool 都带标题与行为提示；只读 tool 不声明 destructive/idempotent。
+        for tool in tools {
+            let name = tool["name"].as_str().unwrap_or_default();
+            assert!(tool["title"].is_string(), "missing title: {name}");
+            assert!(tool["annotations"]["readOnlyHint"].is_boolean(), "missing annotations: {name}");
+        }
+        let annotations = |name: &str| {
+            tools.iter().find(|t| t["name"] == name).map(|t| t["annotations"].clone()).expect("tool")
+        };
+        // recall 会写入召回统计，不是只读。
+        assert_eq!(annotations("recall")["readOnlyHint"], false);
+        assert_eq!(annotations("recall")["destructiveHint"], false);
+        assert_eq!(annotations("get")["readOnlyHint"], true);
+        assert!(annotations("get").get("destructiveHint").is_none());
+        assert_eq!(annotations("verify")["readOnlyHint"], true);
+        assert_eq!(annotations("verify_repair")["destructiveHint"], true);
+        assert_eq!(annotations("forget")["destructiveHint"], true);
+        assert_eq!(annotations("namespace_delete")["destructiveHint"], true);
+        assert_eq!(annotations("pin")["idempotentHint"], true);
+        assert_eq!(annotations("remember")["destructiveHint"], false);
+    }
+
+    #[test]
+    fn tools_call_now_should_return_time_fields() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let mut engine = MemoryEngine::new(dir.path().to_path_buf());
+
+        let out = handle_stdin_line(
+            &mut engine,
+            r#"{"jsonrpc":"2.0","id":2,"method":"tools/call","params":{"name":"now","arguments":{}}}"#,
+        )
+        .expect("handle")
+        .expect("response");
+        let v: Value = serde_json::from_str(&out).expect("json");
+
+        let data = &v["result"]["data"];
+        assert!(data.get("utc_rfc3339").and_then(|x| x.as_str()).is_some());
+        assert!(data.get("utc_ts").and_then(|x| x.as_i64()).is_some());
+        assert!(data
+            .get("local_rfc3339")
+            .and_then(|x| x.as_str())
+            .is_some());
+        assert!(data
+            .get("local_offset_seconds")
+            .and_then(|x| x.as_i64())
+            .is_some());
+        assert!(data
+            .get("local_offset_minutes")
+            .and_then(|x| x.as_i64())
+            .is_some());
+    }
+
+    #[test]
+    fn tools_call_keywords_list_should_work() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let mut engine = MemoryEngine::new(dir.path().to_path_buf());
+
+        let remember = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "tools/call",
+            "params": {
+                "name": "remember",
+                "arguments": {
+                    "namespace": "u1/p1",
+                    "keywords": ["ERP", "项目"],
+                    "slice": "slice",
+                    "diary": "diary"
+                }
+            }
+        })
+        .to_string();
+        let _ = handle_stdin_line(&mut engine, &remember)
+            .expect("handle")
+            .expect("response");
+
+        let list = json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "tools/call",
+            "params": {
+                "name": "keywords_list",
+                "arguments": { "namespace": "u1/p1" }
+            }
+        })
+        .to_string();
+        let out = handle_stdin_line(&mut engine, &list)
+            .expect("handle")
+            .expect("response");
+        let v: Value = serde_json::from_str(&out).expect("json");
+        let keywords = v["result"]["data"]["keywords"].as_array().expect("keywords");
+        assert_eq!(keywords[0].as_str().unwrap(), "项目");
+        assert_eq!(keywords[1].as_str().unwrap(), "erp");
+    }
+
+    #[test]
+    fn tools_call_keywords_list_should_work_with_noncanonical_namespace() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let mut engine = MemoryEngine::new(dir.path().to_path_buf());
+
+        let remember = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "tools/call",
+            "params": {
+                "name": "remember",
+                "arguments": {
+                    "namespace": "u1\\p1//",
+                    "keywords": ["ERP", "项目"],
+                    "slice": "slice",
+                    "diary": "diary"
+                }
+            }
+        })
+        .to_string();
+        let _ = handle_stdin_line(&mut engine, &remember)
+            .expect("handle")
+            .expect("response");
+
+        let list = json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "tools/call",
+            "params": {
+                "name": "keywords_list",
+                "arguments": { "namespace": "u1/p1" }
+            }
+        })
+        .to_string();
+        let out = handle_stdin_line(&mut engine, &list)
+            .expect("handle")
+            .expect("response");
+        let v: Value = serde_json::from_str(&out).expect("json");
+        assert_eq!(v["result"]["data"]["namespace"].as_str().unwrap(), "u1/p1");
+
+        let keywords = v["result"]["data"]["keywords"].as_array().expect("keywords");
+        assert_eq!(keywords[0].as_str().unwrap(), "项目");
+        assert_eq!(keywords[1].as_str().unwrap(), "erp");
+    }
+
+    #[test]
+    fn tools_call_keywords_list_global_should_include_keywords() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let mut engine = MemoryEngine::new(dir.path().to_path_buf());
+
+        let remember = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "tools/call",
+            "params": {
+                "name": "remember",
+                "arguments": {
+                    "namespace": "u1/p1",
+                    "keywords": ["ERP", "项目"],
+                    "slice": "slice",
+                    "diary": "diary"
+                }
+            }
+        })
+        .to_string();
+        let _ = handle_stdin_line(&mut engine, &remember)
+            .expect("handle")
+            .expect("response");
+
+        let list_global = json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "tools/call",
+            "params": { "name": "keywords_list_global", "arguments": {} }
+        })
+        .to_string();
+        let out = handle_stdin_line(&mut engine, &list_global)
+            .expect("handle")
+            .expect("response");
+        let v: Value = serde_json::from_str(&out).expect("json");
+
+        assert_eq!(
+            v["result"]["data"]["scanned_namespaces"].as_u64().unwrap(),
+            1
+        );
+
+        let kws = v["result"]["data"]["keywords"].as_array().expect("keywords");
+        assert!(kws.iter().any(|x| x.get("keyword").and_then(|v| v.as_str()) == Some("项目")));
+        assert!(kws.iter().any(|x| x.get("keyword").and_then(|v| v.as_str()) == Some("erp")));
+    }
+
+    #[test]
+    fn tools_call_recall_should_include_matched_keywords_when_keywords_provided() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let mut engine = MemoryEngine::new(dir.path().to_path_buf());
+
+        let remember = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "tools/call",
+            "params": {
+                "name": "remember",
+                "arguments": {
+                    "namespace": "u1/p1",
+                    "keywords": ["ERP", "项目"],
+                    "slice": "slice",
+                    "diary": "diary"
+                }
+            }
+        })
+        .to_string();
+        let _ = handle_stdin_line(&mut engine, &remember)
+            .expect("handle")
+            .expect("response");
+
+        let recall = json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "tools/call",
+            "params": {
+                "name": "recall",
+                "arguments": {
+                    "namespace": "u1/p1",
+                    "keywords": ["ERP", "项目"],
+                    "limit": 10
+                }
+            }
+        })
+        .to_string();
+        let out = handle_stdin_line(&mut engine, &recall)
+            .expect("handle")
+            .expect("response");
+        let v: Value = serde_json::from_str(&out).expect("json");
+        let items = v["result"]["data"]["items"].as_array().expect("items");
+        assert_eq!(items.len(), 1);
+        let mk = items[0]
+            .get("matched_keywords")
+            .and_then(|x| x.as_array())
+            .expect("matched_keywords");
+        assert_eq!(mk[0].as_str().unwrap(), "项目");
+        assert_eq!(mk[1].as_str().unwrap(), "erp");
+    }
+
+    #[test]
+    fn tools_call_recall_without_keywords_should_not_return_matched_keywords() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let mut engine = MemoryEngine::new(dir.path().to_path_buf());
+
+        let remember = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "tools/call",
+            "params": {
+                "name": "remember",
+                "arguments": {
+                    "namespace": "u1/p1",
+                    "keywords": ["ERP", "项目"],
+                    "slice": "slice",
+                    "diary": "diary"
+                }
+            }
+        })
+        .to_string();
+        let _ = handle_stdin_line(&mut engine, &remember)
+            .expect("handle")
+            .expect("response");
+
+        let recall = json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "tools/call",
+            "params": {
+                "name": "recall",
+                "arguments": { "namespace": "u1/p1", "limit": 10 }
+            }
+        })
+        .to_string();
+        let out = handle_stdin_line(&mut engine, &recall)
+            .expect("handle")
+            .expect("response");
+        let v: Value = serde_json::from_str(&out).expect("json");
+        let items = v["result"]["data"]["items"].as_array().expect("items");
+        assert_eq!(items.len(), 1);
+        assert!(items[0].get("matched_keywords").is_none());
+    }
+
+    #[test]
+    fn tools_call_remember_importance_out_of_range_should_error() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let mut engine = MemoryEngine::new(dir.path().to_path_buf());
+
+        let remember = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "tools/call",
+            "params": {
+                "name": "remember",
+                "arguments": {
+                    "namespace": "u1/p1",
+                    "keywords": ["项目"],
+                    "slice": "slice",
+                    "diary": "diary",
+                    "importance": 6
+                }
+            }
+        })
+        .to_string();
+
+        let err = handle_stdin_line(&mut engine, &remember)
+            .err()
+            .expect("should error");
+        assert!(err.contains("importance"), "unexpected err: {err}");
+    }
+
+    #[test]
+    fn tools_call_recall_should_support_query_time_expr() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let mut engine = MemoryEngine::new(dir.path().to_path_buf());
+
+        for (id, slice, occurred_at) in [
+            (1, "older", "2025-04-01"),
+            (2, "newer", "2025-05-01"),
         ] {
-            assert!(names.contains(name), "missing tool: {name}");
+            let remember = json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "method": "tools/call",
+                "params": {
+                    "name": "remember",
+                    "arguments": {
+                        "namespace": "u1/p1",
+                        "keywords": ["k"],
+                        "slice": slice,
+                        "diary": "diary",
+                        "occurred_at": occurred_at
+                    }
+                }
+            })
+            .to_string();
+            let _ = handle_stdin_line(&mut engine, &remember)
+                .expect("handle")
+                .expect("response");
+        }
+
+        let recall = json!({
+            "jsonrpc": "2.0",
+            "id": 3,
+            "method": "tools/call",
+            "params": {
+                "name": "recall",
+                "arguments": {
+                    "namespace": "u1/p1",
+                    "query": "time>=2025-05-01",
+                    "limit": 10
+                }
+            }
+        })
+        .to_string();
+        let out = handle_stdin_line(&mut engine, &recall)
+            .expect("handle")
+            .expect("response");
+        let v: Value = serde_json::from_str(&out).expect("json");
+        let items = v["result"]["data"]["items"].as_array().expect("items");
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0]["slice"].as_str().unwrap(), "newer");
+    }
+
+    #[test]
+    fn tools_call_transaction_should_be_all_or_nothing() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let mut engine = MemoryEngine::new(dir.path().to_path_buf());
+
+        let bad = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "tools/call",
+            "params": {
+                "name": "transaction",
+                "arguments": {
+                    "namespace": "u1/p1",
+                    "steps": [
+                        { "op": "remember", "keywords": ["k"], "slice": "first", "diary": "d" },
+                        { "op": "forget", "id": "missing", "revision": 1 }
+                    ]
+                }
+            }
+        })
+        .to_string();
+        let err = handle_stdin_line(&mut engine, &bad).expect_err("should error");
+        assert!(err.contains("steps[1]"), "unexpected err: {err}");
+
+        let good = json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "tools/call",
+            "params": {
+                "name": "transaction",
+                "arguments": {
+                    "namespace": "u1/p1",
+                    "steps": [
+                        { "op": "remember", "keywords": ["k"], "slice": "a", "diary": "d" },
+                        { "op": "remember", "keywords": ["k"], "slice": "b", "diary": "d" }
+                    ]
+                }
+            }
+        })
+        .to_string();
+        let out = handle_stdin_line(&mut engine, &good)
+            .expect("handle")
+            .expect("response");
+        let v: Value = serde_json::from_str(&out).expect("json");
+        assert_eq!(v["result"]["data"]["steps"].as_array().unwrap().len(), 2);
+
+        let recall = json!({
+            "jsonrpc": "2.0",
+            "id": 3,
+            "method": "tools/call",
+            "params": { "name": "recall", "arguments": { "namespace": "u1/p1", "keywords": ["k"] } }
+        })
+        .to_string();
+        let out = handle_stdin_line(&mut engine, &recall)
+            .expect("handle")
+            .expect("response");
+        let v: Value = serde_json::from_str(&out).expect("json");
+        let items = v["result"]["data"]["items"].as_array().expect("items");
+        assert_eq!(items.len(), 2);
+        assert!(items.iter().all(|x| x["slice"].as_str() != Some("first")));
+    }
+
+    #[test]
+    fn initialize_client_name_should_stamp_written_by() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let mut engine = MemoryEngine::new(dir.path().to_path_buf());
+
+        let init = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "initialize",
+            "params": { "protocolVersion": "2025-06-18", "clientInfo": { "name": "agent-a", "version": "1" } }
+        })
+        .to_string();
+        handle_stdin_line(&mut engine, &init).expect("handle").expect("response");
+
+        let remember = json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "tools/call",
+            "params": {
+                "name": "remember",
+                "arguments": { "namespace": "u1/p1", "keywords": ["k"], "slice": "s", "diary": "d" }
+            }
+        })
+        .to_string();
+        handle_stdin_line(&mut engine, &remember).expect("handle").expect("response");
+
+        for (writer, expected) in [("agent-a", 1), ("agent-b", 0)] {
+            let recall = json!({
+                "jsonrpc": "2.0",
+                "id": 3,
+                "method": "tools/call",
+                "params": { "name": "recall", "arguments": { "namespace": "u1/p1", "written_by": writer } }
+            })
+            .to_string();
+            let out = handle_stdin_line(&mut engine, &recall)
+                .expect("handle")
+                .expect("response");
+            let v: Value = serde_json::from_str(&out).expect("json");
+            let items = v["result"]["structuredContent"]["items"].as_array().expect("items");
+            assert_eq!(items.len(), expected, "writer {writer}");
+            if expected == 1 {
+                assert_eq!(items[0]["written_by"].as_str(), Some("agent-a"));
+            }
+        }
+    }
+
+    #[test]
+    fn tools_call_get_should_return_full_record() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let mut engine = MemoryEngine::new(dir.path().to_path_buf());
+
+        let remember = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "tools/call",
+            "params": {
+                "name": "remember",
+                "arguments": {
+                    "namespace": "u1/p1",
+                    "keywords": ["k"],
+                    "slice": "s",
+                    "diary": "long diary",
+                    "importance": 4,
+                    "source": "chat"
+                }
+            }
+        })
+        .to_string();
+        let out = handle_stdin_line(&mut engine, &remember)
+            .expect("handle")
+            .expect("response");
+        let v: Value = serde_json::from_str(&out).expect("json");
+        let id = v["result"]["data"]["id"].as_str().expect("id").to_string();
+
+        let get = json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "tools/call",
+            "params": { "name": "get", "arguments": { "namespace": "u1/p1", "id": id } }
+        })
+        .to_string();
+        let out = handle_stdin_line(&mut engine, &get)
+            .expect("handle")
+            .expect("response");
+        let v: Value = serde_json::from_str(&out).expect("json");
+        let item = &v["result"]["data"]["item"];
+        assert_eq!(item["diary"].as_str(), Some("long diary"));
+        assert_eq!(item["source"].as_str(), Some("chat"));
+        assert_eq!(item["importance"].as_u64(), Some(4));
+        assert_eq!(item["revision"].as_u64(), Some(1));
+
+        let missing = json!({
+            "jsonrpc": "2.0",
+            "id": 3,
+            "method": "tools/call",
+            "params": { "name": "get", "arguments": { "namespace": "u1/p1", "id": "nope" } }
+        })
+        .to_string();
+        let err = handle_stdin_line(&mut engine, &missing).expect_err("missing id");
+        assert!(err.contains("记忆不存在"), "unexpected err: {err}");
+    }
+
+    #[test]
+    fn tools_call_update_should_replace_fields_and_bump_revision() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let mut engine = MemoryEngine::new(dir.path().to_path_buf());
+
+        let remember = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "tools/call",
+            "params": {
+                "name": "remember",
+                "arguments": { "namespace": "u1/p1", "keywords": ["old"], "slice": "typo", "diary": "d", "importance": 2 }
+            }
+        })
+        .to_string();
+        let out = handle_stdin_line(&mut engine, &remember)
+            .expect("handle")
+            .expect("response");
+        let v: Value = serde_json::from_str(&out).expect("json");
+        let id = v["result"]["data"]["id"].as_str().expect("id").to_string();
+
+        let update = json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "tools/call",
+            "params": {
+                "name": "update",
+                "arguments": { "namespace": "u1/p1", "id": id, "revision": 1, "keywords": ["new"], "slice": "fixed" }
+            }
+        })
+        .to_string();
+        let out = handle_stdin_line(&mut engine, &update)
+            .expect("handle")
+            .expect("response");
+        let v: Value = serde_json::from_str(&out).expect("json");
+        assert_eq!(v["result"]["data"]["revision"].as_u64(), Some(2));
+
+        let err = handle_stdin_line(&mut engine, &update).expect_err("stale revision");
+        assert!(err.contains("revision 冲突"), "unexpected err: {err}");
+
+        for (keyword, expected) in [("old", 0), ("new", 1)] {
+            let recall = json!({
+                "jsonrpc": "2.0",
+                "id": 3,
+                "method": "tools/call",
+                "params": { "name": "recall", "arguments": { "namespace": "u1/p1", "keywords": [keyword], "include_diary": true } }
+            })
+            .to_string();
+            let out = handle_stdin_line(&mut engine, &recall)
+                .expect("handle")
+                .expect("response");
+            let v: Value = serde_json::from_str(&out).expect("json");
+            let items = v["result"]["data"]["items"].as_array().expect("items");
+            assert_eq!(items.len(), expected, "keyword {keyword}");
+            if expected == 1 {
+                assert_eq!(items[0]["slice"].as_str(), Some("fixed"));
+                assert_eq!(items[0]["importance"].as_u64(), Some(2));
+                assert_eq!(items[0]["revision"].as_u64(), Some(2));
+            }
         }
     }
 
-    #[test]
-    fn tools_call_now_should_return_time_fields() {
-        let dir = tempfile::TempDir::new().expect("create temp dir");
-        let mut engine = MemoryEngine::new(dir.path().to_path_buf());
+    #[test]
+    fn tools_call_forget_should_hide_item_from_recall() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let mut engine = MemoryEngine::new(dir.path().to_path_buf());
+
+        let mut ids = Vec::new();
+        for (i, slice) in ["keep", "drop"].iter().enumerate() {
+            let remember = json!({
+                "jsonrpc": "2.0",
+                "id": i + 1,
+                "method": "tools/call",
+                "params": {
+                    "name": "remember",
+                    "arguments": { "namespace": "u1/p1", "keywords": ["k"], "slice": slice, "diary": "d" }
+                }
+            })
+            .to_string();
+            let out = handle_stdin_line(&mut engine, &remember)
+                .expect("handle")
+                .expect("response");
+            let v: Value = serde_json::from_str(&out).expect("json");
+            ids.push(v["result"]["data"]["id"].as_str().expect("id").to_string());
+        }
+
+        let forget = json!({
+            "jsonrpc": "2.0",
+            "id": 10,
+            "method": "tools/call",
+            "params": { "name": "forget", "arguments": { "namespace": "u1/p1", "id": ids[1], "revision": 1 } }
+        })
+        .to_string();
+        let out = handle_stdin_line(&mut engine, &forget)
+            .expect("handle")
+            .expect("response");
+        let v: Value = serde_json::from_str(&out).expect("json");
+        assert_eq!(v["result"]["data"]["forgotten"].as_bool(), Some(true));
+
+        let err = handle_stdin_line(&mut engine, &forget).expect_err("already forgotten");
+        assert!(!err.is_empty());
 
-        let out = handle_stdin_line(
-            &mut engine,
-            r#"{"jsonrpc":"2.0","id":2,"method":"tools/call","params":{"name":"now","arguments":{}}}"#,
-        )
-        .expect("handle")
-        .expect("response");
+        let recall = json!({
+            "jsonrpc": "2.0",
+            "id": 11,
+            "method": "tools/call",
+            "params": { "name": "recall", "arguments": { "namespace": "u1/p1", "keywords": ["k"] } }
+        })
+        .to_string();
+        let out = handle_stdin_line(&mut engine, &recall)
+            .expect("handle")
+            .expect("response");
         let v: Value = serde_json::from_str(&out).expect("json");
-
-        let data = &v["result"]["data"];
-        assert!(data.get("utc_rfc3339").and_then(|x| x.as_str()).is_some());
-        assert!(data.get("utc_ts").and_then(|x| x.as_i64()).is_some());
-        assert!(data
-            .get("local_rfc3339")
-            .and_then(|x| x.as_str())
-            .is_some());
-        assert!(data
-            .get("local_offset_seconds")
-            .and_then(|x| x.as_i64())
-            .is_some());
-        assert!(data
-            .get("local_offset_minutes")
-            .and_then(|x| x.as_i64())
-            .is_some());
+        let items = v["result"]["data"]["items"].as_array().expect("items");
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0]["slice"].as_str(), Some("keep"));
     }
 
     #[test]
-    fn tools_call_keywords_list_should_work() {
+    fn tools_call_recall_compact_should_omit_data() {
         let dir = tempfile::TempDir::new().expect("create temp dir");
         let mut engine = MemoryEngine::new(dir.path().to_path_buf());
 
@@ -299,8 +2619,10 @@ mod tests {
                 "arguments": {
                     "namespace": "u1/p1",
                     "keywords": ["ERP", "项目"],
-                    "slice": "slice",
-                    "diary": "diary"
+                    "slice": "上线|复盘\n第二行",
+                    "diary": "diary",
+                    "occurred_at": "2025-05-01",
+                    "importance": 4
                 }
             }
         })
@@ -309,27 +2631,30 @@ mod tests {
             .expect("handle")
             .expect("response");
 
-        let list = json!({
+        let recall = json!({
             "jsonrpc": "2.0",
             "id": 2,
             "method": "tools/call",
             "params": {
-                "name": "keywords_list",
-                "arguments": { "namespace": "u1/p1" }
+                "name": "recall",
+                "arguments": { "namespace": "u1/p1", "format": "compact" }
             }
         })
         .to_string();
-        let out = handle_stdin_line(&mut engine, &list)
+        let out = handle_stdin_line(&mut engine, &recall)
             .expect("handle")
             .expect("response");
         let v: Value = serde_json::from_str(&out).expect("json");
-        let keywords = v["result"]["data"]["keywords"].as_array().expect("keywords");
-        assert_eq!(keywords[0].as_str().unwrap(), "项目");
-        assert_eq!(keywords[1].as_str().unwrap(), "erp");
+
+        assert!(v["result"].get("data").is_none());
+        let text = v["result"]["content"][0]["text"].as_str().expect("text");
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "date|importance|keywords|slice");
+        assert_eq!(lines[1], "2025-05-01|4|erp,项目|上线/复盘 第二行");
     }
 
     #[test]
-    fn tools_call_keywords_list_should_work_with_noncanonical_namespace() {
+    fn tools_call_recall_if_none_match_should_short_circuit() {
         let dir = tempfile::TempDir::new().expect("create temp dir");
         let mut engine = MemoryEngine::new(dir.path().to_path_buf());
 
@@ -339,301 +2664,799 @@ mod tests {
             "method": "tools/call",
             "params": {
                 "name": "remember",
-                "arguments": {
-                    "namespace": "u1\\p1//",
-                    "keywords": ["ERP", "项目"],
-                    "slice": "slice",
-                    "diary": "diary"
+                "arguments": { "namespace": "u1/p1", "keywords": ["k"], "slice": "s", "diary": "d" }
+            }
+        })
+        .to_string();
+        let recall = |etag: Option<&str>| {
+            json!({
+                "jsonrpc": "2.0",
+                "id": 2,
+                "method": "tools/call",
+                "params": {
+                    "name": "recall",
+                    "arguments": { "namespace": "u1/p1", "if_none_match": etag }
                 }
+            })
+            .to_string()
+        };
+
+        handle_stdin_line(&mut engine, &remember).expect("handle");
+        let out = handle_stdin_line(&mut engine, &recall(None))
+            .expect("handle")
+            .expect("response");
+        let v: Value = serde_json::from_str(&out).expect("json");
+        let etag = v["result"]["data"]["etag"].as_str().expect("etag").to_string();
+        assert!(v["result"]["data"]["last_modified"].as_str().unwrap().ends_with("GMT"));
+
+        let out = handle_stdin_line(&mut engine, &recall(Some(&etag)))
+            .expect("handle")
+            .expect("response");
+        let v: Value = serde_json::from_str(&out).expect("json");
+        assert_eq!(v["result"]["data"]["not_modified"], json!(true));
+
+        // etag 与召回条件绑定：换了条件的请求不会被上次的 etag 短路。
+        let filtered = json!({
+            "jsonrpc": "2.0",
+            "id": 3,
+            "method": "tools/call",
+            "params": {
+                "name": "recall",
+                "arguments": { "namespace": "u1/p1", "keywords": ["other"], "if_none_match": etag }
             }
         })
         .to_string();
-        let _ = handle_stdin_line(&mut engine, &remember)
+        let out = handle_stdin_line(&mut engine, &filtered).expect("handle").expect("response");
+        let v: Value = serde_json::from_str(&out).expect("json");
+        assert!(v["result"]["data"].get("not_modified").is_none());
+        assert_ne!(v["result"]["data"]["etag"], json!(etag));
+
+        handle_stdin_line(&mut engine, &remember).expect("handle");
+        let out = handle_stdin_line(&mut engine, &recall(Some(&etag)))
             .expect("handle")
             .expect("response");
+        let v: Value = serde_json::from_str(&out).expect("json");
+        assert!(v["result"]["data"].get("not_modified").is_none());
+        assert_eq!(v["result"]["data"]["items"].as_array().unwrap().len(), 2);
+    }
 
-        let list = json!({
+    #[test]
+    fn tools_call_get_if_none_match_should_short_circuit_per_memory() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let mut engine = MemoryEngine::new(dir.path().to_path_buf());
+        let mut call = |name: &str, arguments: Value| -> Value {
+            let line = json!({ "jsonrpc": "2.0", "id": 1, "method": "tools/call", "params": { "name": name, "arguments": arguments } });
+            let out = handle_stdin_line(&mut engine, &line.to_string()).expect("handle").expect("response");
+            serde_json::from_str::<Value>(&out).expect("json")["result"]["data"].clone()
+        };
+
+        let remember = json!({ "namespace": "u1/p1", "keywords": ["k"], "slice": "s", "diary": "d" });
+        let a = call("remember", remember.clone())["id"].as_str().unwrap().to_string();
+        let b = call("remember", remember.clone())["id"].as_str().unwrap().to_string();
+
+        let first = call("get", json!({ "namespace": "u1/p1", "id": a }));
+        let etag = first["etag"].as_str().expect("etag").to_string();
+        assert_eq!(first["item"]["id"], json!(a));
+
+        let again = call("get", json!({ "namespace": "u1/p1", "id": a, "if_none_match": etag }));
+        assert_eq!(again["not_modified"], json!(true));
+        assert!(again.get("item").is_none());
+
+        let other = call("get", json!({ "namespace": "u1/p1", "id": b, "if_none_match": etag }));
+        assert_eq!(other["item"]["id"], json!(b));
+
+        call("remember", remember);
+        let changed = call("get", json!({ "namespace": "u1/p1", "id": a, "if_none_match": etag }));
+        assert!(changed.get("not_modified").is_none());
+        assert_ne!(changed["etag"], json!(etag));
+    }
+
+    #[test]
+    fn tools_call_export_should_return_versioned_document() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let mut engine = MemoryEngine::new(dir.path().to_path_buf());
+
+        let call = |id: i64, name: &str, arguments: Value| {
+            json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "method": "tools/call",
+                "params": { "name": name, "arguments": arguments }
+            })
+            .to_string()
+        };
+
+        for (i, slice) in ["s1", "s2"].iter().enumerate() {
+            let line = call(
+                i as i64 + 1,
+                "remember",
+                json!({ "namespace": "u1/p1", "keywords": ["k"], "slice": slice, "diary": "d" }),
+            );
+            handle_stdin_line(&mut engine, &line).expect("handle");
+        }
+
+        let out = handle_stdin_line(&mut engine, &call(3, "export", json!({ "namespace": "u1/p1" })))
+            .expect("handle")
+            .expect("response");
+        let v: Value = serde_json::from_str(&out).expect("json");
+        let data = &v["result"]["data"];
+        assert_eq!(data["format"], "memory-export");
+        assert_eq!(data["format_version"], json!(1));
+        assert_eq!(data["next_seq"], json!(2));
+        assert_eq!(data["records"].as_array().unwrap().len(), 2);
+
+        let out = handle_stdin_line(
+            &mut engine,
+            &call(4, "export", json!({ "namespace": "u1/p1", "since": 1 })),
+        )
+        .expect("handle")
+        .expect("response");
+        let v: Value = serde_json::from_str(&out).expect("json");
+        let records = v["result"]["data"]["records"].as_array().unwrap().clone();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0]["slice"], "s2");
+    }
+
+    #[test]
+    fn tools_call_remember_batch_should_report_per_item_results() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let mut engine = MemoryEngine::new(dir.path().to_path_buf());
+
+        let line = json!({
             "jsonrpc": "2.0",
-            "id": 2,
+            "id": 1,
             "method": "tools/call",
             "params": {
-                "name": "keywords_list",
-                "arguments": { "namespace": "u1/p1" }
+                "name": "remember_batch",
+                "arguments": {
+                    "namespace": "u1/p1",
+                    "items": [
+                        { "keywords": ["a"], "slice": "s1", "diary": "d" },
+                        { "keywords": ["b"], "diary": "d" },
+                        { "keywords": ["c"], "slice": "s3", "diary": "d", "namespace": "u1/p2" },
+                        { "keywords": ["d"], "slice": "s4", "diary": "d" }
+                    ]
+                }
             }
         })
         .to_string();
-        let out = handle_stdin_line(&mut engine, &list)
+        let out = handle_stdin_line(&mut engine, &line)
             .expect("handle")
             .expect("response");
         let v: Value = serde_json::from_str(&out).expect("json");
-        assert_eq!(v["result"]["data"]["namespace"].as_str().unwrap(), "u1/p1");
+        let data = &v["result"]["data"];
+        assert_eq!(data["written"], json!(3));
+        assert_eq!(data["failed"], json!(1));
+
+        let items = data["items"].as_array().expect("items");
+        assert_eq!(items[0]["ok"], json!(true));
+        assert_eq!(items[1]["ok"], json!(false));
+        assert!(items[1]["error"].as_str().unwrap().contains("slice"));
+        assert_eq!(items[2]["namespace"], "u1/p2");
+        assert_eq!(items[3]["namespace"], "u1/p1");
+
+        let p1 = std::fs::read_to_string(dir.path().join("u1/p1/memories.jsonl")).expect("read");
+        assert_eq!(p1.lines().count(), 2);
+    }
+
+    #[test]
+    fn tools_call_recall_cursor_should_page_through_results() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let mut engine = MemoryEngine::new(dir.path().to_path_buf());
+
+        let call = |id: i64, name: &str, arguments: Value| {
+            json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "method": "tools/call",
+                "params": { "name": name, "arguments": arguments }
+            })
+            .to_string()
+        };
+        let items: Vec<Value> = (0..5)
+            .map(|i| json!({ "keywords": ["k"], "slice": format!("s{i}"), "diary": "d" }))
+            .collect();
+        handle_stdin_line(
+            &mut engine,
+            &call(1, "remember_batch", json!({ "namespace": "u1/p1", "items": items })),
+        )
+        .expect("handle");
+
+        let mut seen: Vec<String> = Vec::new();
+        let mut cursor: Option<String> = None;
+        for page in 0..3 {
+            let mut args = json!({ "namespace": "u1/p1", "keywords": ["k"], "limit": 2 });
+            if let Some(c) = cursor.as_deref() {
+                args["cursor"] = json!(c);
+            }
+            let out = handle_stdin_line(&mut engine, &call(2 + page, "recall", args))
+                .expect("handle")
+                .expect("response");
+            let v: Value = serde_json::from_str(&out).expect("json");
+            let data = &v["result"]["data"];
+            assert_eq!(data["offset"], json!(page * 2));
+            for item in data["items"].as_array().expect("items") {
+                seen.push(item["id"].as_str().unwrap().to_string());
+            }
+            cursor = data["next_cursor"].as_str().map(|x| x.to_string());
+        }
+        assert!(cursor.is_none());
+        seen.sort();
+        seen.dedup();
+        assert_eq!(seen.len(), 5);
+
+        // 分页期间写入：旧游标失效。
+        let out = handle_stdin_line(
+            &mut engine,
+            &call(9, "recall", json!({ "namespace": "u1/p1", "keywords": ["k"], "limit": 2 })),
+        )
+        .expect("handle")
+        .expect("response");
+        let v: Value = serde_json::from_str(&out).expect("json");
+        let stale = v["result"]["data"]["next_cursor"].as_str().expect("cursor").to_string();
+        handle_stdin_line(
+            &mut engine,
+            &call(10, "remember", json!({ "namespace": "u1/p1", "keywords": ["k"], "slice": "s", "diary": "d" })),
+        )
+        .expect("handle");
+        let err = handle_stdin_line(
+            &mut engine,
+            &call(11, "recall", json!({ "namespace": "u1/p1", "keywords": ["k"], "cursor": stale })),
+        )
+        .expect_err("stale cursor");
+        assert!(err.contains("cursor 已失效"), "{err}");
+    }
+
+    #[test]
+    fn tools_call_lang_should_switch_summary_language_per_call() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let mut engine = MemoryEngine::new(dir.path().to_path_buf());
+
+        let mut call = |id: i64, arguments: Value| {
+            let line = json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "method": "tools/call",
+                "params": { "name": "recall", "arguments": arguments }
+            })
+            .to_string();
+            handle_stdin_line(&mut engine, &line).map(|out| {
+                let v: Value = serde_json::from_str(&out.expect("response")).expect("json");
+                v["result"]["content"][0]["text"].as_str().unwrap_or_default().to_string()
+            })
+        };
+
+        let en = call(1, json!({ "namespace": "u1/p1", "lang": "en-US" })).expect("handle");
+        assert_eq!(en, "No memories matched.");
+        let zh = call(2, json!({ "namespace": "u1/p1" })).expect("handle");
+        assert_eq!(zh, "未命中记忆。");
+
+        let err = call(3, json!({ "namespace": "u1/p1", "lang": "fr" })).expect_err("invalid lang");
+        assert!(err.contains("不支持的 lang"), "{err}");
+
+        let tools = tool_definitions();
+        assert!(tools
+            .iter()
+            .all(|t| t.pointer("/inputSchema/properties/lang").is_some()));
+    }
+
+    #[test]
+    fn tools_call_remember_should_accept_weighted_keyword_objects() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let mut engine = MemoryEngine::new(dir.path().to_path_buf());
+
+        let mut call = |id: i64, name: &str, arguments: Value| {
+            let line = json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "method": "tools/call",
+                "params": { "name": name, "arguments": arguments }
+            })
+            .to_string();
+            handle_stdin_line(&mut engine, &line)
+                .map(|out| serde_json::from_str::<Value>(&out.expect("response")).expect("json"))
+        };
+
+        let keywords = json!(["部署", { "keyword": "ERP", "weight": 4 }, { "keyword": "财务" }]);
+        call(1, "remember", json!({ "namespace": "u1/p1", "keywords": keywords, "slice": "s", "diary": "d" }))
+            .expect("remember");
+        let v = call(2, "recall", json!({ "namespace": "u1/p1", "keywords": ["erp"] })).expect("recall");
+        let item = &v["result"]["data"]["items"][0];
+        assert_eq!(item["keywords"], json!(["部署", "erp", "财务"]));
+        assert_eq!(item["keyword_weights"], json!({ "erp": 4 }));
+
+        let bad = json!([{ "keyword": "erp", "weight": 11 }]);
+        let err = call(3, "remember", json!({ "namespace": "u1/p1", "keywords": bad, "slice": "s", "diary": "d" }))
+            .expect_err("invalid weight");
+        assert!(err.contains("weight 必须是 1~10"), "{err}");
+    }
+
+    #[test]
+    fn tools_call_keywords_complete_should_match_prefix_and_substring() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let mut engine = MemoryEngine::new(dir.path().to_path_buf());
+
+        let mut call = |id: i64, name: &str, arguments: Value| {
+            let line = json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "method": "tools/call",
+                "params": { "name": name, "arguments": arguments }
+            })
+            .to_string();
+            let out = handle_stdin_line(&mut engine, &line).expect("handle").expect("response");
+            serde_json::from_str::<Value>(&out).expect("json")["result"]["data"].take()
+        };
+
+        for (ns, keywords) in [("u1/p1", json!(["erp", "erp迁移"])), ("u1/p1", json!(["erp", "老erp"])), ("u2/p1", json!(["erp-v2"]))] {
+            call(1, "remember", json!({ "namespace": ns, "keywords": keywords, "slice": "s", "diary": "d" }));
+        }
+        let keywords = |data: &Value| -> Vec<String> {
+            data["keywords"]
+                .as_array()
+                .expect("keywords")
+                .iter()
+                .map(|x| x["keyword"].as_str().unwrap_or_default().to_string())
+                .collect()
+        };
+
+        let data = call(2, "keywords_complete", json!({ "namespace": "u1\\p1", "text": "ERP" }));
+        assert_eq!(data["namespace"], "u1/p1");
+        assert_eq!(keywords(&data), vec!["erp", "erp迁移"]);
+        assert_eq!(data["keywords"][0]["items"], 2);
+
+        let data = call(3, "keywords_complete", json!({ "namespace": "u1/p1", "text": "erp", "match": "contains" }));
+        assert_eq!(keywords(&data), vec!["erp", "erp迁移", "老erp"]);
+
+        let data = call(4, "keywords_complete", json!({ "text": "erp", "limit": 2 }));
+        assert_eq!(data["total"], 3);
+        assert_eq!(keywords(&data), vec!["erp", "erp迁移"]);
+        assert_eq!(data["keywords"][0]["namespaces"], 1);
+    }
+
+    #[test]
+    fn take_notifications_should_report_generation_changes() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let mut engine = MemoryEngine::new(dir.path().to_path_buf());
+
+        let mut call = |id: i64, name: &str, arguments: Value| {
+            let line = json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "method": "tools/call",
+                "params": { "name": name, "arguments": arguments }
+            })
+            .to_string();
+            handle_stdin_line(&mut engine, &line).expect("handle");
+            take_notifications(&mut engine)
+                .iter()
+                .map(|x| serde_json::from_str::<Value>(x).expect("json"))
+                .collect::<Vec<_>>()
+        };
+
+        let remember = json!({ "namespace": "u1/p1", "keywords": ["k"], "slice": "s", "diary": "d" });
+        let notes = call(1, "remember", remember.clone());
+        assert_eq!(notes.len(), 1);
+        assert_eq!(notes[0]["method"], CHANGED_NOTIFICATION);
+        assert_eq!(notes[0]["params"]["namespace"], "u1/p1");
+        let generation = notes[0]["params"]["generation"].as_u64().expect("generation");
+
+        assert!(call(2, "recall", json!({ "namespace": "u1/p1", "keywords": ["k"] })).is_empty());
+        let notes = call(3, "remember", remember);
+        assert!(notes[0]["params"]["generation"].as_u64().expect("generation") > generation);
+
+        let notes = call(4, "namespace_delete", json!({ "namespace": "u1/p1", "confirm": true }));
+        assert_eq!(notes.len(), 1);
+        assert!(notes[0]["params"]["generation"].is_null());
+    }
+
+    #[test]
+    fn tools_call_remember_preview_should_confirm_with_token() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let mut engine = MemoryEngine::new(dir.path().to_path_buf());
+
+        let mut call = |name: &str, arguments: Value| {
+            let line = json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "tools/call",
+                "params": { "name": name, "arguments": arguments }
+            })
+            .to_string();
+            handle_stdin_line(&mut engine, &line)
+                .map(|out| serde_json::from_str::<Value>(&out.expect("response")).expect("json")["result"]["data"].take())
+        };
+
+        call("remember", json!({ "namespace": "u1/p1", "keywords": ["部署"], "slice": "生产环境部署", "diary": "d" })).expect("remember");
+        let preview = json!({
+            "namespace": "u1/p1",
+            "keywords": [" 上线 "],
+            "slice": "生产环境部署",
+            "diary": "数据库 password=hunter2"
+        });
+        let data = call("remember_preview", preview.clone()).expect("preview");
+        assert_eq!(data["keywords"], json!(["上线"]));
+        assert_eq!(data["diary"], "数据库 password=[REDACTED:credential]");
+        assert_eq!(data["redactions"], json!([{ "field": "diary", "kind": "credential", "count": 1 }]));
+        assert_eq!(data["duplicates"][0]["same_diary"], false);
+        assert_eq!(data["suggested_keywords"][0]["keyword"], "部署");
+        assert_eq!(data["new_keywords"], json!(["上线"]));
+
+        let recall = json!({ "namespace": "u1/p1", "query": "生产环境部署" });
+        assert_eq!(call("recall", recall.clone()).expect("recall")["total"], 1);
+
+        let token = data["preview_token"].as_str().expect("token").to_string();
+        let err = call("remember", json!({ "namespace": "u2/p1", "preview_token": token })).unwrap_err();
+        assert!(err.contains("不一致"), "unexpected err: {err}");
+
+        let stored = call("remember", json!({ "namespace": "u1/p1", "preview_token": token })).expect("confirm");
+        let got = call("get", json!({ "namespace": "u1/p1", "id": stored["id"] })).expect("get");
+        assert_eq!(got["item"]["diary"], "数据库 password=[REDACTED:credential]");
+        assert_eq!(call("recall", recall).expect("recall")["total"], 2);
+
+        let err = call("remember", json!({ "namespace": "u1/p1", "preview_token": token })).unwrap_err();
+        assert!(err.contains("无效或已使用"), "unexpected err: {err}");
+    }
+
+    #[test]
+    fn recall_should_link_items_as_memory_resources() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let mut engine = MemoryEngine::new(dir.path().to_path_buf());
+        let mut send = |message: Value| -> Value {
+            let out = handle_stdin_line(&mut engine, &message.to_string())
+                .expect("handle")
+                .expect("response");
+            serde_json::from_str(&out).expect("json")
+        };
+        let initialize = |version: &str| {
+            json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": { "protocolVersion": version } })
+        };
+        let call = |name: &str, arguments: Value| {
+            json!({ "jsonrpc": "2.0", "id": 2, "method": "tools/call", "params": { "name": name, "arguments": arguments } })
+        };
+
+        let init = send(initialize("2025-06-18"));
+        assert!(init["result"]["capabilities"]["resources"].is_object());
+        let remembered = send(call(
+            "remember",
+            json!({ "namespace": "u1/p1", "keywords": ["k"], "slice": "上线计划", "diary": "d" }),
+        ));
+        let id = remembered["result"]["structuredContent"]["id"].as_str().expect("id").to_string();
+
+        let recalled = send(call("recall", json!({ "namespace": "u1/p1", "keywords": ["k"] })));
+        let content = recalled["result"]["content"].as_array().expect("content");
+        assert_eq!(content.len(), 2);
+        let uri = format!("memory://u1/p1/{id}");
+        assert_eq!(content[1]["type"], "resource_link");
+        assert_eq!(content[1]["uri"].as_str(), Some(uri.as_str()));
+        assert_eq!(content[1]["description"], "上线计划");
+
+        let read = send(json!({ "jsonrpc": "2.0", "id": 3, "method": "resources/read", "params": { "uri": uri } }));
+        let contents = &read["result"]["contents"][0];
+        assert_eq!(contents["mimeType"], "application/json");
+        let item: Value = serde_json::from_str(contents["text"].as_str().expect("text")).expect("item json");
+        assert_eq!(item["slice"], "上线计划");
+
+        let missing = send(json!({
+            "jsonrpc": "2.0", "id": 4, "method": "resources/read", "params": { "uri": "memory://u1/p1/missing" }
+        }));
+        assert_eq!(missing["error"]["code"], -32002);
+
+        let listed = send(json!({ "jsonrpc": "2.0", "id": 5, "method": "resources/list" }));
+        let resources = listed["result"]["resources"].as_array().expect("resources");
+        assert_eq!(resources.len(), 1);
+        assert_eq!(resources[0]["uri"].as_str(), Some(uri.as_str()));
+        assert_eq!(resources[0]["description"], "上线计划");
+        assert!(listed["result"].get("nextCursor").is_none());
+        let templates = send(json!({ "jsonrpc": "2.0", "id": 6, "method": "resources/templates/list" }));
+        assert_eq!(
+            templates["result"]["resourceTemplates"][0]["uriTemplate"],
+            "memory://{userId}/{projectId}/{id}"
+        );
 
-        let keywords = v["result"]["data"]["keywords"].as_array().expect("keywords");
-        assert_eq!(keywords[0].as_str().unwrap(), "项目");
-        assert_eq!(keywords[1].as_str().unwrap(), "erp");
+        let prompts = send(json!({ "jsonrpc": "2.0", "id": 7, "method": "prompts/list" }));
+        let names: Vec<&str> = prompts["result"]["prompts"]
+            .as_array()
+            .expect("prompts")
+            .iter()
+            .filter_map(|p| p["name"].as_str())
+            .collect();
+        assert_eq!(names, vec!["review_recent_memories", "summarize_topic"]);
+        let prompt = send(json!({
+            "jsonrpc": "2.0", "id": 8, "method": "prompts/get",
+            "params": { "name": "summarize_topic", "arguments": { "namespace": "u1/p1", "topic": "上线" } }
+        }));
+        let message = &prompt["result"]["messages"][0];
+        assert_eq!(message["role"], "user");
+        let text = message["content"]["text"].as_str().expect("text");
+        assert!(text.contains(&format!("id={id} 上线计划")), "unexpected prompt: {text}");
+        let recent = send(json!({
+            "jsonrpc": "2.0", "id": 9, "method": "prompts/get",
+            "params": { "name": "review_recent_memories", "arguments": { "namespace": "u1/p1" } }
+        }));
+        assert!(recent["result"]["messages"][0]["content"]["text"].as_str().expect("text").contains("上线计划"));
+        let unknown = send(json!({ "jsonrpc": "2.0", "id": 10, "method": "prompts/get", "params": { "name": "nope" } }));
+        assert_eq!(unknown["error"]["code"], -32602);
+
+        // 旧协议没有 resource_link 内容块：只返回文本。
+        send(initialize("2024-11-05"));
+        let recalled = send(call("recall", json!({ "namespace": "u1/p1", "keywords": ["k"] })));
+        assert_eq!(recalled["result"]["content"].as_array().map(Vec::len), Some(1));
     }
 
     #[test]
-    fn tools_call_keywords_list_global_should_include_keywords() {
+    fn logging_should_push_notifications_at_or_above_set_level() {
+        crate::memory::capture_logs();
         let dir = tempfile::TempDir::new().expect("create temp dir");
         let mut engine = MemoryEngine::new(dir.path().to_path_buf());
+        let send = |engine: &mut MemoryEngine, message: Value| -> Value {
+            let out = handle_stdin_line(engine, &message.to_string())
+                .expect("handle")
+                .expect("response");
+            serde_json::from_str(&out).expect("json")
+        };
+        let logs = |engine: &mut MemoryEngine| -> Vec<Value> {
+            take_notifications(engine)
+                .iter()
+                .map(|line| serde_json::from_str::<Value>(line).expect("json"))
+                .filter(|n| n["method"] == LOG_NOTIFICATION)
+                .collect()
+        };
 
-        let remember = json!({
-            "jsonrpc": "2.0",
-            "id": 1,
-            "method": "tools/call",
-            "params": {
-                "name": "remember",
-                "arguments": {
-                    "namespace": "u1/p1",
-                    "keywords": ["ERP", "项目"],
-                    "slice": "slice",
-                    "diary": "diary"
-                }
-            }
-        })
-        .to_string();
-        let _ = handle_stdin_line(&mut engine, &remember)
-            .expect("handle")
-            .expect("response");
+        let init = send(&mut engine, json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {} }));
+        assert!(init["result"]["capabilities"]["logging"].is_object());
+        send(
+            &mut engine,
+            json!({ "jsonrpc": "2.0", "id": 2, "method": "tools/call", "params": { "name": "remember", "arguments": {
+                "namespace": "u1/p1", "keywords": ["k"], "slice": "s", "diary": "d"
+            } } }),
+        );
+        let compact = json!({ "jsonrpc": "2.0", "id": 3, "method": "tools/call", "params": { "name": "compact", "arguments": { "namespace": "u1/p1" } } });
 
-        let list_global = json!({
-            "jsonrpc": "2.0",
-            "id": 2,
-            "method": "tools/call",
-            "params": { "name": "keywords_list_global", "arguments": {} }
-        })
-        .to_string();
-        let out = handle_stdin_line(&mut engine, &list_global)
-            .expect("handle")
-            .expect("response");
-        let v: Value = serde_json::from_str(&out).expect("json");
+        // 默认 warning：索引重建（info）不推送。
+        send(&mut engine, compact.clone());
+        assert!(logs(&mut engine).is_empty());
 
-        assert_eq!(
-            v["result"]["data"]["scanned_namespaces"].as_u64().unwrap(),
-            1
-        );
+        let invalid = send(&mut engine, json!({ "jsonrpc": "2.0", "id": 4, "method": "logging/setLevel", "params": { "level": "loud" } }));
+        assert_eq!(invalid["error"]["code"], -32602);
+        let set = send(&mut engine, json!({ "jsonrpc": "2.0", "id": 5, "method": "logging/setLevel", "params": { "level": "info" } }));
+        assert!(set["result"].is_object());
 
-        let kws = v["result"]["data"]["keywords"].as_array().expect("keywords");
-        assert!(kws.iter().any(|x| x.get("keyword").and_then(|v| v.as_str()) == Some("项目")));
-        assert!(kws.iter().any(|x| x.get("keyword").and_then(|v| v.as_str()) == Some("erp")));
+        send(&mut engine, compact);
+        let pushed = logs(&mut engine);
+        assert_eq!(pushed.len(), 1);
+        assert_eq!(pushed[0]["params"]["level"], "info");
+        assert_eq!(pushed[0]["params"]["logger"], "memory.store");
+        assert_eq!(pushed[0]["params"]["data"]["event"], "index_rebuilt");
+        assert_eq!(pushed[0]["params"]["data"]["namespace"], "u1/p1");
+
+        let err = handle_stdin_line(&mut engine, "{not json").unwrap_err();
+        report_error(&err);
+        let pushed = logs(&mut engine);
+        assert_eq!(pushed[0]["params"]["level"], "error");
+        assert!(pushed[0]["params"]["data"]["message"].as_str().unwrap_or_default().contains("invalid json"));
     }
 
     #[test]
-    fn tools_call_recall_should_include_matched_keywords_when_keywords_provided() {
+    fn batch_should_return_responses_in_order_and_skip_notifications() {
         let dir = tempfile::TempDir::new().expect("create temp dir");
         let mut engine = MemoryEngine::new(dir.path().to_path_buf());
-
-        let remember = json!({
-            "jsonrpc": "2.0",
-            "id": 1,
-            "method": "tools/call",
-            "params": {
-                "name": "remember",
-                "arguments": {
-                    "namespace": "u1/p1",
-                    "keywords": ["ERP", "项目"],
-                    "slice": "slice",
-                    "diary": "diary"
-                }
-            }
-        })
-        .to_string();
-        let _ = handle_stdin_line(&mut engine, &remember)
+        let batch = json!([
+            { "jsonrpc": "2.0", "id": 1, "method": "tools/list" },
+            { "jsonrpc": "2.0", "method": "initialized" },
+            { "jsonrpc": "2.0", "id": 2, "method": "no/such" },
+            7
+        ]);
+        let out = handle_stdin_line(&mut engine, &batch.to_string())
             .expect("handle")
             .expect("response");
+        let responses: Vec<Value> = serde_json::from_str(&out).expect("json array");
+        assert_eq!(responses.len(), 3);
+        assert_eq!(responses[0]["id"], 1);
+        assert!(responses[0]["result"]["tools"].is_array());
+        assert_eq!(responses[1]["error"]["code"], -32601);
+        assert_eq!(responses[2]["error"]["code"], -32600);
 
-        let recall = json!({
-            "jsonrpc": "2.0",
-            "id": 2,
-            "method": "tools/call",
-            "params": {
-                "name": "recall",
-                "arguments": {
-                    "namespace": "u1/p1",
-                    "keywords": ["ERP", "项目"],
-                    "limit": 10
-                }
-            }
-        })
-        .to_string();
-        let out = handle_stdin_line(&mut engine, &recall)
+        let only_notifications = json!([{ "jsonrpc": "2.0", "method": "initialized" }]);
+        assert!(handle_stdin_line(&mut engine, &only_notifications.to_string()).expect("handle").is_none());
+        let empty: Value = serde_json::from_str(&handle_stdin_line(&mut engine, "[]").expect("handle").expect("response")).expect("json");
+        assert_eq!(empty["error"]["code"], -32600);
+    }
+
+    #[test]
+    fn batch_should_answer_failed_entries_with_errors_and_keep_going() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let mut engine = MemoryEngine::new(dir.path().to_path_buf());
+        let call = |id: u64, name: &str, arguments: Value| {
+            json!({ "jsonrpc": "2.0", "id": id, "method": "tools/call", "params": { "name": name, "arguments": arguments } })
+        };
+        let batch = json!([
+            { "jsonrpc": "2.0", "id": 1, "method": "ping" },
+            call(2, "get", json!({ "namespace": "u1/p1", "id": "missing" })),
+            call(3, "remember", json!({ "namespace": "u1/p1", "keywords": ["k"], "slice": "s", "diary": "d" })),
+            { "jsonrpc": "2.0", "method": "tools/call", "params": { "name": "get", "arguments": { "namespace": "u1/p1", "id": "x" } } },
+            call(4, "recall", json!({ "namespace": "u1/p1", "keywords": ["k"] }))
+        ]);
+        let out = handle_stdin_line(&mut engine, &batch.to_string())
             .expect("handle")
             .expect("response");
-        let v: Value = serde_json::from_str(&out).expect("json");
-        let items = v["result"]["data"]["items"].as_array().expect("items");
-        assert_eq!(items.len(), 1);
-        let mk = items[0]
-            .get("matched_keywords")
-            .and_then(|x| x.as_array())
-            .expect("matched_keywords");
-        assert_eq!(mk[0].as_str().unwrap(), "项目");
-        assert_eq!(mk[1].as_str().unwrap(), "erp");
+        let responses: Vec<Value> = serde_json::from_str(&out).expect("json array");
+        let ids: Vec<u64> = responses.iter().map(|x| x["id"].as_u64().unwrap()).collect();
+        assert_eq!(ids, vec![1, 2, 3, 4]);
+        assert_eq!(responses[1]["error"]["code"], HANDLER_ERROR_CODE);
+        assert!(responses[1]["error"]["message"].as_str().is_some_and(|x| !x.is_empty()));
+        assert!(responses[2]["result"]["data"]["id"].is_string());
+        assert_eq!(responses[3]["result"]["data"]["total"], 1);
     }
 
     #[test]
-    fn tools_call_recall_without_keywords_should_not_return_matched_keywords() {
+    fn string_and_null_ids_should_be_echoed_verbatim() {
         let dir = tempfile::TempDir::new().expect("create temp dir");
         let mut engine = MemoryEngine::new(dir.path().to_path_buf());
+        let mut send = |message: Value| -> Value {
+            let out = handle_stdin_line(&mut engine, &message.to_string())
+                .expect("handle")
+                .expect("response");
+            serde_json::from_str(&out).expect("json")
+        };
 
-        let remember = json!({
-            "jsonrpc": "2.0",
-            "id": 1,
-            "method": "tools/call",
-            "params": {
-                "name": "remember",
-                "arguments": {
-                    "namespace": "u1/p1",
-                    "keywords": ["ERP", "项目"],
-                    "slice": "slice",
-                    "diary": "diary"
-                }
-            }
-        })
-        .to_string();
-        let _ = handle_stdin_line(&mut engine, &remember)
-            .expect("handle")
-            .expect("response");
+        let listed = send(json!({ "jsonrpc": "2.0", "id": "req-1", "method": "tools/list" }));
+        assert_eq!(listed["id"], "req-1");
+        assert!(listed["result"]["tools"].is_array());
 
-        let recall = json!({
-            "jsonrpc": "2.0",
-            "id": 2,
-            "method": "tools/call",
-            "params": {
-                "name": "recall",
-                "arguments": { "namespace": "u1/p1", "limit": 10 }
-            }
-        })
-        .to_string();
-        let out = handle_stdin_line(&mut engine, &recall)
-            .expect("handle")
-            .expect("response");
-        let v: Value = serde_json::from_str(&out).expect("json");
-        let items = v["result"]["data"]["items"].as_array().expect("items");
-        assert_eq!(items.len(), 1);
-        assert!(items[0].get("matched_keywords").is_none());
+        let missing = send(json!({ "jsonrpc": "2.0", "id": null, "method": "no/such" }));
+        assert!(missing.get("id").is_some_and(Value::is_null));
+        assert_eq!(missing["error"]["code"], -32601);
+
+        let big = send(json!({ "jsonrpc": "2.0", "id": 1.5, "method": "tools/list" }));
+        assert_eq!(big["id"], 1.5);
+
+        let invalid = send(json!({ "jsonrpc": "2.0", "id": { "x": 1 }, "method": "tools/list" }));
+        assert_eq!(invalid["error"]["code"], -32600);
     }
 
     #[test]
-    fn tools_call_remember_importance_out_of_range_should_error() {
+    fn tool_results_should_carry_structured_content_or_data_by_protocol() {
         let dir = tempfile::TempDir::new().expect("create temp dir");
         let mut engine = MemoryEngine::new(dir.path().to_path_buf());
+        let mut send = |message: Value| -> Value {
+            let out = handle_stdin_line(&mut engine, &message.to_string())
+                .expect("handle")
+                .expect("response");
+            serde_json::from_str(&out).expect("json")
+        };
+        let call = |name: &str, arguments: Value| {
+            json!({ "jsonrpc": "2.0", "id": 2, "method": "tools/call", "params": { "name": name, "arguments": arguments } })
+        };
 
-        let remember = json!({
-            "jsonrpc": "2.0",
-            "id": 1,
-            "method": "tools/call",
-            "params": {
-                "name": "remember",
-                "arguments": {
-                    "namespace": "u1/p1",
-                    "keywords": ["项目"],
-                    "slice": "slice",
-                    "diary": "diary",
-                    "importance": 6
-                }
-            }
-        })
-        .to_string();
+        let listed = send(json!({ "jsonrpc": "2.0", "id": 1, "method": "tools/list" }));
+        let tools = listed["result"]["tools"].as_array().expect("tools");
+        for tool in tools {
+            let properties = tool["outputSchema"]["properties"].as_object();
+            assert!(properties.is_some_and(|x| !x.is_empty()), "{}", tool["name"]);
+        }
+        let recall_schema = &tools.iter().find(|t| t["name"] == "recall").expect("recall")["outputSchema"];
+        assert_eq!(recall_schema["properties"]["items"]["type"], "array");
 
-        let err = handle_stdin_line(&mut engine, &remember)
-            .err()
-            .expect("should error");
-        assert!(err.contains("importance"), "unexpected err: {err}");
+        // 未握手（旧客户端）：只有 data。
+        send(call("remember", json!({ "namespace": "u1/p1", "keywords": ["k"], "slice": "s", "diary": "d" })));
+        let legacy = send(call("recall", json!({ "namespace": "u1/p1", "keywords": ["k"] })));
+        assert_eq!(legacy["result"]["data"]["items"][0]["slice"], "s");
+        assert!(legacy["result"].get("structuredContent").is_none());
+
+        // 2025-06-18：只有 structuredContent，不再重复 data。
+        send(json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": { "protocolVersion": "2025-06-18" } }));
+        let recalled = send(call("recall", json!({ "namespace": "u1/p1", "keywords": ["k"] })));
+        let result = &recalled["result"];
+        assert_eq!(result["structuredContent"]["items"][0]["slice"], "s");
+        assert!(result.get("data").is_none());
+        assert!(result["content"][0]["text"].is_string());
+
+        // compact 格式没有结构化结果：structuredContent 为空对象。
+        let compact = send(call("recall", json!({ "namespace": "u1/p1", "keywords": ["k"], "format": "compact" })));
+        assert_eq!(compact["result"]["structuredContent"], json!({}));
+
+        // 声明 toolResultData 的新客户端同时收到 data。
+        send(json!({
+            "jsonrpc": "2.0", "id": 1, "method": "initialize",
+            "params": { "protocolVersion": "2025-06-18", "capabilities": { "experimental": { "toolResultData": true } } }
+        }));
+        let both = send(call("recall", json!({ "namespace": "u1/p1", "keywords": ["k"] })));
+        assert_eq!(both["result"]["structuredContent"], both["result"]["data"]);
     }
 
     #[test]
-    fn tools_call_recall_should_support_query_time_expr() {
+    fn completion_should_suggest_namespaces_and_keywords() {
         let dir = tempfile::TempDir::new().expect("create temp dir");
         let mut engine = MemoryEngine::new(dir.path().to_path_buf());
-
-        for (id, slice, occurred_at) in [
-            (1, "older", "2025-04-01"),
-            (2, "newer", "2025-05-01"),
-        ] {
-            let remember = json!({
-                "jsonrpc": "2.0",
-                "id": id,
-                "method": "tools/call",
-                "params": {
-                    "name": "remember",
-                    "arguments": {
-                        "namespace": "u1/p1",
-                        "keywords": ["k"],
-                        "slice": slice,
-                        "diary": "diary",
-                        "occurred_at": occurred_at
-                    }
-                }
-            })
-            .to_string();
-            let _ = handle_stdin_line(&mut engine, &remember)
+        let mut send = |message: Value| -> Value {
+            let out = handle_stdin_line(&mut engine, &message.to_string())
                 .expect("handle")
                 .expect("response");
+            serde_json::from_str(&out).expect("json")
+        };
+        for (ns, keywords) in [("alice/web", json!(["deploy", "design"])), ("alice/api", json!(["deploy"])), ("bob/web", json!(["docs"]))] {
+            send(json!({ "jsonrpc": "2.0", "id": 1, "method": "tools/call", "params": { "name": "remember", "arguments": {
+                "namespace": ns, "keywords": keywords, "slice": "s", "diary": "d"
+            } } }));
         }
+        let mut complete = |reference: Value, argument: &str, value: &str, context: Value| -> Value {
+            send(json!({ "jsonrpc": "2.0", "id": 2, "method": "completion/complete", "params": {
+                "ref": reference, "argument": { "name": argument, "value": value }, "context": { "arguments": context }
+            } }))
+        };
+        let prompt = json!({ "type": "ref/prompt", "name": "summarize_topic" });
 
-        let recall = json!({
-            "jsonrpc": "2.0",
-            "id": 3,
-            "method": "tools/call",
-            "params": {
-                "name": "recall",
-                "arguments": {
-                    "namespace": "u1/p1",
-                    "query": "time>=2025-05-01",
-                    "limit": 10
-                }
-            }
-        })
-        .to_string();
-        let out = handle_stdin_line(&mut engine, &recall)
+        let namespaces = complete(prompt.clone(), "namespace", "al", json!({}));
+        assert_eq!(namespaces["result"]["completion"]["values"], json!(["alice/api", "alice/web"]));
+        assert_eq!(namespaces["result"]["completion"]["hasMore"], false);
+
+        // 限定 namespace 时只取其关键字；否则按全局引用条数排序。
+        let scoped = complete(prompt.clone(), "topic", "d", json!({ "namespace": "bob/web" }));
+        assert_eq!(scoped["result"]["completion"]["values"], json!(["docs"]));
+        let global = complete(prompt, "topic", "de", json!({}));
+        assert_eq!(global["result"]["completion"]["values"], json!(["deploy", "design"]));
+
+        let template = json!({ "type": "ref/resource", "uri": "memory://{userId}/{projectId}/{id}" });
+        let projects = complete(template.clone(), "projectId", "", json!({ "userId": "bob" }));
+        assert_eq!(projects["result"]["completion"]["values"], json!(["web"]));
+        let users = complete(template, "userId", "", json!({}));
+        assert_eq!(users["result"]["completion"]["values"], json!(["alice", "bob"]));
+
+        let unknown = complete(json!({ "type": "ref/prompt", "name": "nope" }), "namespace", "", json!({}));
+        assert_eq!(unknown["error"]["code"], -32602);
+    }
+
+    #[test]
+    fn ping_should_return_empty_result() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let mut engine = MemoryEngine::new(dir.path().to_path_buf());
+        let out = handle_stdin_line(&mut engine, r#"{"jsonrpc":"2.0","id":"p1","method":"ping"}"#)
             .expect("handle")
             .expect("response");
         let v: Value = serde_json::from_str(&out).expect("json");
-        let items = v["result"]["data"]["items"].as_array().expect("items");
-        assert_eq!(items.len(), 1);
-        assert_eq!(items[0]["slice"].as_str().unwrap(), "newer");
+        assert_eq!(v, json!({ "jsonrpc": "2.0", "id": "p1", "result": {} }));
+
+        let fast = handle_without_engine(r#"{"jsonrpc":"2.0","id":7,"method":"ping"}"#, StorageBackend::Jsonl).expect("static").expect("response");
+        assert_eq!(serde_json::from_str::<Value>(&fast).expect("json")["id"], 7);
     }
-}
 
-fn recall_schema() -> Value {
-    json!({
-        "type": "object",
-        "additionalProperties": false,
-        "required": ["namespace"],
-        "properties": {
-            "namespace": {
-                "type": "string",
-                "description": "命名空间：必须为 {userId}/{projectId}（严格两段；会做分隔符归一化与路径净化）。"
-            },
-            "keywords": {
-                "type": "array",
-                "items": { "type": "string" },
-                "description": "关键字列表（可选）。"
-            },
-            "start": {
-                "type": "string",
-                "description": "起始时间（RFC3339 或 YYYY-MM-DD）。"
-            },
-            "end": {
-                "type": "string",
-                "description": "结束时间（RFC3339 或 YYYY-MM-DD）。"
-            },
-            "query": {
-                "type": "string",
-                "description": "自由文本查询（可选，包含匹配 slice/diary/source；支持 time>=... / time<=... / time=a..b 时间表达式）。"
-            },
-            "limit": {
-                "type": "integer",
-                "minimum": 1,
-                "maximum": 100,
-                "default": 20
-            },
-            "include_diary": {
-                "type": "boolean",
-                "default": false,
-                "description": "是否返回 diary 字段（默认 false）。"
-            }
+    #[test]
+    fn tools_list_should_hide_tools_the_backend_does_not_support() {
+        let list = |backend: StorageBackend| -> Vec<String> {
+            let line = r#"{"jsonrpc":"2.0","id":1,"method":"tools/list"}"#;
+            let response = handle_without_engine(line, backend).expect("static").expect("response");
+            serde_json::from_str::<Value>(&response).expect("json")["result"]["tools"]
+                .as_array()
+                .expect("tools")
+                .iter()
+                .map(|tool| tool["name"].as_str().unwrap_or_default().to_string())
+                .collect()
+        };
+
+        assert_eq!(list(StorageBackend::Jsonl).len(), tool_definitions().len());
+        for backend in [StorageBackend::Sqlite, StorageBackend::Memory] {
+            assert_eq!(
+                list(backend),
+                vec!["now", "remember", "recall", "get", "history", "update", "forget", "transaction"]
+            );
         }
-    })
+    }
+
+    #[test]
+    fn default_namespace_should_make_namespace_optional() {
+        assert_eq!(namespace_or(&json!({}), Some("u1/p1".to_string())).unwrap(), "u1/p1");
+        assert_eq!(namespace_or(&json!({ "namespace": "u2/p2" }), Some("u1/p1".to_string())).unwrap(), "u2/p2");
+        let err = namespace_or(&json!({ "namespace": " " }), None).unwrap_err();
+        assert!(err.contains("MEMORY_DEFAULT_NAMESPACE"), "{err}");
+
+        let tools = tools_with_default_namespace(tool_definitions(), true);
+        let required = |name: &str| {
+            let tool = tools.iter().find(|t| t["name"] == name).expect("tool");
+            tool["inputSchema"]["required"].as_array().cloned().unwrap_or_default()
+        };
+        assert!(!required("remember").contains(&json!("namespace")));
+        assert!(!required("recall").contains(&json!("namespace")));
+        assert!(required("namespace_delete").contains(&json!("namespace")));
+    }
 }
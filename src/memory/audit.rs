@@ -0,0 +1,277 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+/// 参与编辑距离比较的最短关键字（按字符计）：更短的词（如 erp / crm）差一个字符往往是不同的词。
+const EDIT_DISTANCE_MIN_CHARS: usize = 5;
+
+/// 可疑关键字的判定原因。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JunkReason {
+    /// 单个字符。
+    SingleChar,
+    /// 纯数字（可含 `.`、`-`、`%` 等分隔符）。
+    Numeric,
+    /// 与已有关键字近似重复（仅分隔符/大小写/英文复数不同，或相差一次编辑）。
+    NearDuplicate,
+    /// 只被一条记忆使用。
+    OneOff,
+}
+
+impl JunkReason {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::SingleChar => "single_char",
+            Self::Numeric => "numeric",
+            Self::NearDuplicate => "near_duplicate",
+            Self::OneOff => "one_off",
+        }
+    }
+}
+
+/// 清理建议：合并到另一个关键字 / 删除 / 人工确认（仅为一次性用词，可能是合理的专有名词）。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Suggestion {
+    Merge { into: String },
+    Delete,
+    Review,
+}
+
+/// 一个可疑关键字。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct KeywordFinding {
+    pub keyword: String,
+    /// 当前引用该关键字的记忆条数。
+    pub count: usize,
+    pub reasons: Vec<JunkReason>,
+    pub suggestion: Suggestion,
+    /// 引用该关键字的记忆 id（按 update 逐条替换关键字即可执行建议）。
+    pub ids: Vec<String>,
+}
+
+/// 分析关键字词表（关键字 -> 引用条数），返回可疑关键字及建议（ids 为空，由调用方按倒排填充）。
+///
+/// 顺序：合并、删除、待确认；同类按关键字排序。
+pub fn audit_keywords(counts: &BTreeMap<String, usize>) -> Vec<KeywordFinding> {
+    let merges = near_duplicates(counts);
+
+    let mut out: Vec<KeywordFinding> = Vec::new();
+    for (keyword, &count) in counts {
+        let mut reasons: Vec<JunkReason> = Vec::new();
+        if keyword.chars().count() == 1 {
+            reasons.push(JunkReason::SingleChar);
+        }
+        if is_numeric(keyword) {
+            reasons.push(JunkReason::Numeric);
+        }
+        let merge_into = merges.get(keyword.as_str());
+        if merge_into.is_some() {
+            reasons.push(JunkReason::NearDuplicate);
+        }
+        if count == 1 {
+            reasons.push(JunkReason::OneOff);
+        }
+        if reasons.is_empty() {
+            continue;
+        }
+
+        let suggestion = match merge_into {
+            Some(into) => Suggestion::Merge { into: into.to_string() },
+            None if reasons.contains(&JunkReason::SingleChar) || reasons.contains(&JunkReason::Numeric) => {
+                Suggestion::Delete
+            }
+            None => Suggestion::Review,
+        };
+        out.push(KeywordFinding {
+            keyword: keyword.clone(),
+            count,
+            reasons,
+            suggestion,
+            ids: Vec::new(),
+        });
+    }
+
+    out.sort_by(|a, b| {
+        let rank = |s: &Suggestion| match s {
+            Suggestion::Merge { .. } => 0,
+            Suggestion::Delete => 1,
+            Suggestion::Review => 2,
+        };
+        rank(&a.suggestion)
+            .cmp(&rank(&b.suggestion))
+            .then_with(|| a.keyword.cmp(&b.keyword))
+    });
+    out
+}
+
+fn is_numeric(keyword: &str) -> bool {
+    keyword.chars().any(|c| c.is_ascii_digit())
+        && keyword
+            .chars()
+            .all(|c| c.is_ascii_digit() || matches!(c, '.' | ',' | '-' | '_' | ':' | '/' | '%' | '+'))
+}
+
+/// 近似重复的关键字 -> 建议合并到的关键字（组内引用最多者；同数时取更短、再按码位）。
+fn near_duplicates(counts: &BTreeMap<String, usize>) -> HashMap<&str, &str> {
+    let better = |a: &str, b: &str| -> bool {
+        let key = |k: &str| (std::cmp::Reverse(counts[k]), k.chars().count(), k.to_string());
+        key(a) < key(b)
+    };
+
+    // 并查集：同一组内的关键字互为近似重复。
+    let keywords: Vec<&str> = counts.keys().map(String::as_str).collect();
+    let mut parent: Vec<usize> = (0..keywords.len()).collect();
+    fn find(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+    let mut union = |a: usize, b: usize| {
+        let (ra, rb) = (find(&mut parent, a), find(&mut parent, b));
+        if ra != rb {
+            parent[ra] = rb;
+        }
+    };
+
+    // 1) 去掉分隔符与英文复数后相同。
+    let mut by_canonical: HashMap<String, usize> = HashMap::new();
+    for (i, kw) in keywords.iter().enumerate() {
+        let canonical = canonical_form(kw);
+        if canonical.is_empty() {
+            continue;
+        }
+        match by_canonical.get(&canonical) {
+            Some(&j) => union(i, j),
+            None => {
+                by_canonical.insert(canonical, i);
+            }
+        }
+    }
+
+    // 2) 相差一次编辑（插入/删除/替换）：以“删去一个字符”的变体作为桶，只比较同桶的词。
+    let mut buckets: HashMap<String, Vec<usize>> = HashMap::new();
+    for (i, kw) in keywords.iter().enumerate() {
+        let chars: Vec<char> = kw.chars().collect();
+        if chars.len() < EDIT_DISTANCE_MIN_CHARS || is_numeric(kw) {
+            continue;
+        }
+        buckets.entry(kw.to_string()).or_default().push(i);
+        for skip in 0..chars.len() {
+            let variant: String = chars.iter().enumerate().filter(|(j, _)| *j != skip).map(|(_, c)| c).collect();
+            buckets.entry(variant).or_default().push(i);
+        }
+    }
+    for members in buckets.values() {
+        for (n, &i) in members.iter().enumerate() {
+            for &j in &members[n + 1..] {
+                if i != j && within_one_edit(keywords[i], keywords[j]) {
+                    union(i, j);
+                }
+            }
+        }
+    }
+
+    let mut groups: HashMap<usize, Vec<&str>> = HashMap::new();
+    for (i, kw) in keywords.iter().enumerate() {
+        let root = find(&mut parent, i);
+        groups.entry(root).or_default().push(kw);
+    }
+
+    let mut out: HashMap<&str, &str> = HashMap::new();
+    for members in groups.into_values().filter(|m| m.len() > 1) {
+        let winner = members
+            .iter()
+            .copied()
+            .reduce(|a, b| if better(b, a) { b } else { a })
+            .unwrap_or_default();
+        for kw in members {
+            if kw != winner {
+                out.insert(kw, winner);
+            }
+        }
+    }
+    out
+}
+
+/// 归一化比较形式：去掉分隔符（空白、`-`、`_`、`.`、`·`、`/`），纯 ASCII 字母词去掉结尾的复数 `s`。
+fn canonical_form(keyword: &str) -> String {
+    let mut out: String = keyword
+        .chars()
+        .filter(|c| !c.is_whitespace() && !matches!(c, '-' | '_' | '.' | '·' | '/'))
+        .collect();
+    if out.len() > 3 && out.bytes().all(|b| b.is_ascii_lowercase()) && out.ends_with('s') && !out.ends_with("ss") {
+        out.pop();
+    }
+    out
+}
+
+/// 两个词是否恰好相差一次插入、删除或替换。
+fn within_one_edit(a: &str, b: &str) -> bool {
+    let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    let (short, long) = if a.len() <= b.len() { (&a, &b) } else { (&b, &a) };
+    if long.len() - short.len() > 1 || a == b {
+        return false;
+    }
+    let prefix = short.iter().zip(long.iter()).take_while(|(x, y)| x == y).count();
+    if short.len() == long.len() {
+        short[prefix + 1..] == long[prefix + 1..]
+    } else {
+        short[prefix..] == long[prefix + 1..]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn audit_keywords_should_flag_junk_and_suggest_merges() {
+        let counts: BTreeMap<String, usize> = [
+            ("deployment", 6),
+            ("deploymnt", 1),
+            ("erp", 9),
+            ("erp-v2", 3),
+            ("erpv2", 5),
+            ("crm", 4),
+            ("x", 2),
+            ("2024", 3),
+            ("releases", 1),
+            ("release", 2),
+            ("数据库", 1),
+            ("仓库", 3),
+        ]
+        .into_iter()
+        .map(|(k, n)| (k.to_string(), n))
+        .collect();
+
+        let findings = audit_keywords(&counts);
+        let summary: Vec<(&str, &Suggestion)> = findings.iter().map(|f| (f.keyword.as_str(), &f.suggestion)).collect();
+        let merge = |into: &str| Suggestion::Merge { into: into.to_string() };
+        assert_eq!(
+            summary,
+            vec![
+                ("deploymnt", &merge("deployment")),
+                ("erp-v2", &merge("erpv2")),
+                ("releases", &merge("release")),
+                ("2024", &Suggestion::Delete),
+                ("x", &Suggestion::Delete),
+                ("数据库", &Suggestion::Review),
+            ]
+        );
+        assert_eq!(findings[0].reasons, vec![JunkReason::NearDuplicate, JunkReason::OneOff]);
+        // erp / crm 过短，不做编辑距离比较。
+        assert!(!findings.iter().any(|f| f.keyword == "crm" || f.keyword == "erp"));
+    }
+
+    #[test]
+    fn within_one_edit_should_cover_insert_delete_replace() {
+        assert!(within_one_edit("deploy", "deploys"));
+        assert!(within_one_edit("deploys", "deploy"));
+        assert!(within_one_edit("deploy", "depl0y"));
+        assert!(!within_one_edit("deploy", "deploy"));
+        assert!(!within_one_edit("deploy", "dpeloy"));
+    }
+}
@@ -0,0 +1,61 @@
+use crate::memory::model::{MemoryItem, RecallArgs, RecallResult, RememberArgs, TransactionStep};
+#[cfg(feature = "sqlite")]
+use crate::memory::sqlite::SqliteStore;
+use crate::memory::store::{
+    CacheValidators, MemoryHistory, NamespaceState, RememberRecorded, TransactionOutcome,
+};
+
+/// 已打开的 namespace：按存储后端分派两种实现共有的操作（remember / recall / get / history / 事务）。
+pub enum NamespaceHandle<'a> {
+    Jsonl(&'a mut NamespaceState),
+    #[cfg(feature = "sqlite")]
+    Sqlite(&'a mut SqliteStore),
+}
+
+macro_rules! dispatch {
+    ($self:ident, $state:ident => $body:expr) => {
+        match $self {
+            NamespaceHandle::Jsonl($state) => $body,
+            #[cfg(feature = "sqlite")]
+            NamespaceHandle::Sqlite($state) => $body,
+        }
+    };
+}
+
+impl NamespaceHandle<'_> {
+    pub fn namespace(&self) -> &str {
+        dispatch!(self, state => state.namespace())
+    }
+
+    pub fn append_memory(&mut self, args: RememberArgs) -> Result<RememberRecorded, String> {
+        dispatch!(self, state => state.append_memory(args))
+    }
+
+    pub fn apply_transaction(&mut self, steps: Vec<TransactionStep>) -> Result<Vec<TransactionOutcome>, String> {
+        dispatch!(self, state => state.apply_transaction(steps))
+    }
+
+    pub fn get(&mut self, id: &str) -> Result<MemoryItem, String> {
+        dispatch!(self, state => state.get(id))
+    }
+
+    pub fn history(&mut self, id: &str) -> Result<MemoryHistory, String> {
+        dispatch!(self, state => state.history(id))
+    }
+
+    pub fn superseded_by(&self, id: &str) -> Vec<String> {
+        dispatch!(self, state => state.superseded_by(id))
+    }
+
+    pub fn recall(&mut self, args: RecallArgs) -> Result<RecallResult, String> {
+        dispatch!(self, state => state.recall(args))
+    }
+
+    pub fn cache_validators(&mut self) -> Result<CacheValidators, String> {
+        dispatch!(self, state => state.cache_validators())
+    }
+
+    pub fn record_recalled(&mut self, ids: &[String]) {
+        dispatch!(self, state => state.record_recalled(ids))
+    }
+}
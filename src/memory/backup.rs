@@ -0,0 +1,375 @@
+use crate::memory::format::{self, STORE_FORMAT_FILE};
+use crate::memory::store::StorePaths;
+use crate::memory::time::{self, DateBoundKind};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// 快照目录名前缀：`memory-backup-20250101T080000Z`（UTC 时间，按名称排序即按时间排序）。
+pub const SNAPSHOT_PREFIX: &str = "memory-backup-";
+/// 快照清单文件名（位于快照目录根部，最后写入）。
+pub const MANIFEST_FILE: &str = "backup.json";
+pub const BACKUP_FORMAT: &str = "memory-backup";
+pub const BACKUP_FORMAT_VERSION: u32 = 1;
+/// 恢复时的暂存目录与被替换下来的旧目录（位于存储根目录，完成后删除）。
+const RESTORE_STAGING_DIR: &str = ".restore-tmp";
+const RESTORE_OLD_DIR: &str = ".restore-old";
+
+/// 快照中的一个文件（路径相对快照目录，以 `/` 分隔）。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupFile {
+    pub path: String,
+    pub bytes: u64,
+    pub sha256: String,
+}
+
+/// backup.json：快照包含的 namespace 与文件清单（含哈希，恢复前据此校验）。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub format: String,
+    pub format_version: u32,
+    pub store_format_version: u32,
+    pub created_at: String,
+    pub namespaces: Vec<String>,
+    pub files: Vec<BackupFile>,
+}
+
+/// 快照保留策略：两项都给出时，同时满足才保留。
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Retention {
+    /// 最多保留的快照个数（含本次）。
+    pub keep: Option<usize>,
+    /// 删除早于该天数的快照（本次快照总会保留）。
+    pub keep_days: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BackupReport {
+    pub snapshot: PathBuf,
+    pub namespaces: Vec<String>,
+    pub files: usize,
+    pub bytes: u64,
+    /// 按保留策略删除的旧快照。
+    pub removed: Vec<PathBuf>,
+}
+
+/// 把给定 namespace 的数据文件与索引复制到 `out_dir` 下新的时间戳快照目录，再按保留策略清理旧快照。
+///
+/// 先写入 `<快照>.tmp` 目录，完成后整体改名，中断时不会留下看似完整的快照。
+/// 每个 namespace 先复制索引等文件、最后复制数据文件：复制期间有新的追加时，
+/// 快照中的索引只会落后于数据，恢复后打开时增量补齐。
+pub fn create_snapshot(
+    root_dir: &Path,
+    namespaces: &[String],
+    out_dir: &Path,
+    retention: Retention,
+) -> Result<BackupReport, String> {
+    let store_format_version = format::read_store_format(root_dir)?
+        .map_or(format::STORE_FORMAT_VERSION, |f| f.format_version);
+
+    fs::create_dir_all(out_dir).map_err(|e| format!("create {} failed: {e}", out_dir.display()))?;
+    let now = Utc::now();
+    let stamp = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let mut name = format!("{SNAPSHOT_PREFIX}{stamp}");
+    let mut n = 1;
+    while out_dir.join(&name).exists() {
+        n += 1;
+        name = format!("{SNAPSHOT_PREFIX}{stamp}-{n}");
+    }
+    let snapshot = out_dir.join(&name);
+    let staging = out_dir.join(format!("{name}.tmp"));
+    if staging.exists() {
+        fs::remove_dir_all(&staging).map_err(|e| format!("remove {} failed: {e}", staging.display()))?;
+    }
+
+    let mut files: Vec<BackupFile> = Vec::new();
+    let root_format = root_dir.join(STORE_FORMAT_FILE);
+    if root_format.is_file() {
+        files.push(copy_file(&root_format, &staging, STORE_FORMAT_FILE)?);
+    }
+    for ns in namespaces {
+        let paths = StorePaths::new(root_dir, ns)?;
+        let mut sources: Vec<(PathBuf, String)> = Vec::new();
+        collect_files(&paths.namespace_dir, &paths.namespace, &mut sources)?;
+        // 数据文件最后复制：快照中的索引不会超前于数据。
+        sources.sort_by_key(|(path, rel)| (is_data_file(path), rel.clone()));
+        for (path, rel) in sources {
+            files.push(copy_file(&path, &staging, &rel)?);
+        }
+    }
+
+    let manifest = BackupManifest {
+        format: BACKUP_FORMAT.to_string(),
+        format_version: BACKUP_FORMAT_VERSION,
+        store_format_version,
+        created_at: now.to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+        namespaces: namespaces.to_vec(),
+        files,
+    };
+    let text = serde_json::to_string_pretty(&manifest).map_err(|e| format!("serialize {MANIFEST_FILE} failed: {e}"))?;
+    fs::create_dir_all(&staging).map_err(|e| format!("create {} failed: {e}", staging.display()))?;
+    fs::write(staging.join(MANIFEST_FILE), text).map_err(|e| format!("write {MANIFEST_FILE} failed: {e}"))?;
+    fs::rename(&staging, &snapshot).map_err(|e| format!("rename {} failed: {e}", staging.display()))?;
+
+    let removed = apply_retention(out_dir, &name, retention, now.timestamp())?;
+    Ok(BackupReport {
+        snapshot,
+        namespaces: manifest.namespaces,
+        files: manifest.files.len(),
+        bytes: manifest.files.iter().map(|f| f.bytes).sum(),
+        removed,
+    })
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RestoreReport {
+    pub snapshot: PathBuf,
+    pub created_at: String,
+    pub namespaces: Vec<String>,
+    pub files: usize,
+    pub bytes: u64,
+}
+
+/// 从快照恢复 namespace（未指定时为快照中的全部），整体替换存储中的同名 namespace 目录。
+///
+/// `snapshot` 可以是快照目录，也可以是 backup 的 `--out` 目录（取其中最新的快照）。
+/// 先把全部文件按清单校验（字节数 + sha256）后复制到存储根目录下的暂存目录，全部通过才逐个
+/// namespace 以目录改名的方式替换：校验失败时存储不受影响。
+pub fn restore_snapshot(root_dir: &Path, snapshot: &Path, namespace: Option<&str>) -> Result<RestoreReport, String> {
+    let snapshot = resolve_snapshot(snapshot)?;
+    let manifest = read_manifest(&snapshot)?;
+    if manifest.store_format_version != format::STORE_FORMAT_VERSION {
+        return Err(format!(
+            "快照的存储格式版本 {} 与当前程序的版本 {} 不一致，无法直接恢复",
+            manifest.store_format_version,
+            format::STORE_FORMAT_VERSION
+        ));
+    }
+
+    let namespaces: Vec<String> = match namespace {
+        Some(ns) => {
+            let ns = StorePaths::new(root_dir, ns)?.namespace;
+            if !manifest.namespaces.contains(&ns) {
+                return Err(format!("快照中没有 namespace：{ns}"));
+            }
+            vec![ns]
+        }
+        None => manifest.namespaces.clone(),
+    };
+
+    let staging_root = root_dir.join(RESTORE_STAGING_DIR);
+    let _ = fs::remove_dir_all(&staging_root);
+    let mut files = 0usize;
+    let mut bytes = 0u64;
+    let staged = (|| {
+        for ns in &namespaces {
+            let prefix = format!("{ns}/");
+            for file in manifest.files.iter().filter(|f| f.path.starts_with(&prefix)) {
+                let src = snapshot.join(&file.path);
+                let content = fs::read(&src).map_err(|e| format!("快照文件缺失或不可读：{}（{e}）", file.path))?;
+                if content.len() as u64 != file.bytes || sha256_hex(&content) != file.sha256 {
+                    return Err(format!("快照文件校验失败（可能被修改或损坏）：{}", file.path));
+                }
+                let dest = staging_root.join(&file.path);
+                if let Some(parent) = dest.parent() {
+                    fs::create_dir_all(parent).map_err(|e| format!("create {} failed: {e}", parent.display()))?;
+                }
+                fs::write(&dest, &content).map_err(|e| format!("write {} failed: {e}", dest.display()))?;
+                files += 1;
+                bytes += file.bytes;
+            }
+            if !staging_root.join(ns).join("memories.jsonl").is_file() {
+                return Err(format!("快照中 namespace={ns} 缺少 memories.jsonl"));
+            }
+        }
+        Ok(())
+    })();
+    if let Err(e) = staged {
+        let _ = fs::remove_dir_all(&staging_root);
+        return Err(e);
+    }
+
+    let old_root = root_dir.join(RESTORE_OLD_DIR);
+    for ns in &namespaces {
+        let target = StorePaths::new(root_dir, ns)?.namespace_dir;
+        let old = old_root.join(ns);
+        if target.exists() {
+            if let Some(parent) = old.parent() {
+                fs::create_dir_all(parent).map_err(|e| format!("create {} failed: {e}", parent.display()))?;
+            }
+            fs::rename(&target, &old).map_err(|e| format!("move {} failed: {e}", target.display()))?;
+        } else if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("create {} failed: {e}", parent.display()))?;
+        }
+        if let Err(e) = fs::rename(staging_root.join(ns), &target) {
+            // 换入失败时放回原目录，保持该 namespace 不变。
+            let _ = fs::rename(&old, &target);
+            return Err(format!("restore namespace={ns} failed: {e}"));
+        }
+    }
+    let _ = fs::remove_dir_all(&old_root);
+    let _ = fs::remove_dir_all(&staging_root);
+
+    Ok(RestoreReport {
+        snapshot,
+        created_at: manifest.created_at,
+        namespaces,
+        files,
+        bytes,
+    })
+}
+
+/// 快照目录本身，或 backup 输出目录中最新的快照。
+fn resolve_snapshot(path: &Path) -> Result<PathBuf, String> {
+    if path.join(MANIFEST_FILE).is_file() {
+        return Ok(path.to_path_buf());
+    }
+    list_snapshots(path)
+        .pop()
+        .map(|(name, _)| path.join(name))
+        .ok_or_else(|| format!("{} 不是备份快照，其下也没有可用的快照", path.display()))
+}
+
+/// 读取快照清单（不校验文件内容）。
+pub fn read_manifest(snapshot: &Path) -> Result<BackupManifest, String> {
+    let path = snapshot.join(MANIFEST_FILE);
+    let text = fs::read_to_string(&path).map_err(|e| format!("read {} failed: {e}", path.display()))?;
+    let manifest: BackupManifest =
+        serde_json::from_str(&text).map_err(|e| format!("parse {} failed: {e}", path.display()))?;
+    if manifest.format != BACKUP_FORMAT {
+        return Err(format!("不是 memory 备份快照：format={}", manifest.format));
+    }
+    if manifest.format_version > BACKUP_FORMAT_VERSION {
+        return Err(format!(
+            "快照格式版本 {} 高于当前程序支持的 {}，请升级 memory",
+            manifest.format_version, BACKUP_FORMAT_VERSION
+        ));
+    }
+    Ok(manifest)
+}
+
+/// `out_dir` 下已完成的快照（有清单），按创建时间升序（同一秒内按序号）。
+pub fn list_snapshots(out_dir: &Path) -> Vec<(String, BackupManifest)> {
+    let Ok(entries) = fs::read_dir(out_dir) else {
+        return Vec::new();
+    };
+    let mut out: Vec<(String, BackupManifest)> = entries
+        .flatten()
+        .filter_map(|e| e.file_name().to_str().map(|x| x.to_string()))
+        .filter(|name| name.starts_with(SNAPSHOT_PREFIX) && !name.ends_with(".tmp"))
+        .filter_map(|name| read_manifest(&out_dir.join(&name)).ok().map(|m| (name, m)))
+        .collect();
+    out.sort_by(|a, b| (&a.1.created_at, a.0.len(), &a.0).cmp(&(&b.1.created_at, b.0.len(), &b.0)));
+    out
+}
+
+fn apply_retention(out_dir: &Path, current: &str, retention: Retention, now_ts: i64) -> Result<Vec<PathBuf>, String> {
+    let snapshots = list_snapshots(out_dir);
+    let total = snapshots.len();
+    let mut removed: Vec<PathBuf> = Vec::new();
+    for (i, (name, manifest)) in snapshots.into_iter().enumerate() {
+        if name == current {
+            continue;
+        }
+        let over_count = retention.keep.is_some_and(|keep| total - i > keep.max(1));
+        let too_old = retention.keep_days.is_some_and(|days| {
+            time::parse_time_to_ts_and_canonical(&manifest.created_at, DateBoundKind::Start)
+                .is_ok_and(|(ts, _)| now_ts - ts > days as i64 * 86_400)
+        });
+        if over_count || too_old {
+            let path = out_dir.join(&name);
+            fs::remove_dir_all(&path).map_err(|e| format!("remove {} failed: {e}", path.display()))?;
+            removed.push(path);
+        }
+    }
+    Ok(removed)
+}
+
+/// 递归列出目录下的文件（跳过写入中途的 `.tmp` 与写入锁 `commit.lock`），附带以 `/` 分隔的相对路径。
+fn collect_files(dir: &Path, rel: &str, out: &mut Vec<(PathBuf, String)>) -> Result<(), String> {
+    let entries = fs::read_dir(dir).map_err(|e| format!("read {} failed: {e}", dir.display()))?;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Some(name) = entry.file_name().to_str().map(|x| x.to_string()) else {
+            continue;
+        };
+        let child = format!("{rel}/{name}");
+        if path.is_dir() {
+            collect_files(&path, &child, out)?;
+        } else if !name.ends_with(".tmp") && name != "commit.lock" {
+            out.push((path, child));
+        }
+    }
+    Ok(())
+}
+
+fn is_data_file(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|x| x.to_str())
+        .is_some_and(|name| name == "segments.json" || (name.starts_with("memories") && name.ends_with(".jsonl")))
+}
+
+fn copy_file(src: &Path, staging: &Path, rel: &str) -> Result<BackupFile, String> {
+    let bytes = fs::read(src).map_err(|e| format!("read {} failed: {e}", src.display()))?;
+    let dest = staging.join(rel);
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("create {} failed: {e}", parent.display()))?;
+    }
+    fs::write(&dest, &bytes).map_err(|e| format!("write {} failed: {e}", dest.display()))?;
+    Ok(BackupFile {
+        path: rel.to_string(),
+        bytes: bytes.len() as u64,
+        sha256: sha256_hex(&bytes),
+    })
+}
+
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes).iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn create_snapshot_should_copy_namespace_and_apply_retention() {
+        let root = tempfile::tempdir().unwrap();
+        let out = tempfile::tempdir().unwrap();
+        let ns_dir = root.path().join("u1").join("p1");
+        fs::create_dir_all(ns_dir.join("blobs")).unwrap();
+        fs::write(ns_dir.join("memories.jsonl"), "{\"id\":\"a\"}\n").unwrap();
+        fs::write(ns_dir.join("index.json"), "{}").unwrap();
+        fs::write(ns_dir.join("index.json.tmp"), "partial").unwrap();
+        fs::write(ns_dir.join("blobs").join("ab"), "diary").unwrap();
+
+        let namespaces = vec!["u1/p1".to_string()];
+        let report = create_snapshot(root.path(), &namespaces, out.path(), Retention::default()).unwrap();
+        let manifest = read_manifest(&report.snapshot).unwrap();
+        let paths: Vec<&str> = manifest.files.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(paths, vec!["u1/p1/blobs/ab", "u1/p1/index.json", "u1/p1/memories.jsonl"]);
+        assert_eq!(
+            fs::read_to_string(report.snapshot.join("u1/p1/memories.jsonl")).unwrap(),
+            "{\"id\":\"a\"}\n"
+        );
+        assert_eq!(manifest.files[2].sha256, sha256_hex(b"{\"id\":\"a\"}\n"));
+
+        // 同一秒内的第二、三个快照带序号后缀；只保留最新的 2 个。
+        create_snapshot(root.path(), &namespaces, out.path(), Retention::default()).unwrap();
+        let report = create_snapshot(
+            root.path(),
+            &namespaces,
+            out.path(),
+            Retention {
+                keep: Some(2),
+                keep_days: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(report.removed.len(), 1);
+        let names: Vec<String> = list_snapshots(out.path()).into_iter().map(|(name, _)| name).collect();
+        assert_eq!(names.len(), 2);
+        assert!(names.iter().any(|n| report.snapshot.ends_with(n)));
+    }
+}
@@ -0,0 +1,212 @@
+use crate::memory::model::MemoryItem;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// diary 达到该字节数时转存为内容寻址的 blob，记录中只保留引用。
+pub const BLOB_MIN_BYTES: usize = 4096;
+/// blob 引用前缀（`sha256:<hex>`，hex 为 diary 内容的 SHA-256）。
+pub const BLOB_REF_PREFIX: &str = "sha256:";
+/// 启用压缩时，diary 达到该字节数才尝试 zstd 压缩（更短的内容压缩后加上 base64 往往反而变长）。
+#[cfg(feature = "zstd")]
+pub const ZSTD_MIN_BYTES: usize = 256;
+/// zstd 压缩级别（写入路径上偏向速度）。
+#[cfg(feature = "zstd")]
+const ZSTD_LEVEL: i32 = 3;
+
+/// namespace 的 blob 目录（与 memories.jsonl 同级的 `blobs/`，随 namespace 一同移动/删除）。
+pub fn blobs_dir(memories_path: &Path) -> PathBuf {
+    memories_path
+        .parent()
+        .map(|p| p.join("blobs"))
+        .unwrap_or_else(|| PathBuf::from("blobs"))
+}
+
+fn blob_path(dir: &Path, hex: &str) -> PathBuf {
+    dir.join(&hex[..2]).join(hex)
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes).iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// 写入前调用：diary 足够大时存入 blob（相同内容只存一份），返回改为引用形式并重新签名的记录；
+/// 否则在 `compress` 时尝试改为 zstd 压缩形式（压缩后更短才采用）；无需改写时返回 None（按原样写入）。
+pub fn externalize(dir: &Path, item: &MemoryItem, compress: bool) -> Result<Option<MemoryItem>, String> {
+    if item.diary_ref.is_some() || item.diary_zstd.is_some() {
+        return Ok(None);
+    }
+    if item.diary.len() < BLOB_MIN_BYTES {
+        return Ok(if compress { compress_diary(item)? } else { None });
+    }
+
+    let hex = sha256_hex(item.diary.as_bytes());
+    let path = blob_path(dir, &hex);
+    if !path.is_file() {
+        let parent = path.parent().unwrap_or(dir);
+        fs::create_dir_all(parent).map_err(|e| format!("create blobs dir failed: {e}"))?;
+        // 先写临时文件再 rename：并发写入同一内容时任一方完成即可，不会留下半截 blob。
+        let tmp = parent.join(format!("{hex}.{}.tmp", uuid::Uuid::new_v4()));
+        fs::write(&tmp, item.diary.as_bytes()).map_err(|e| format!("write blob failed: {e}"))?;
+        if let Err(e) = fs::rename(&tmp, &path) {
+            let _ = fs::remove_file(&tmp);
+            if !path.is_file() {
+                return Err(format!("write blob failed: {e}"));
+            }
+        }
+    }
+
+    let mut stored = item.clone();
+    stored.diary = String::new();
+    stored.diary_ref = Some(format!("{BLOB_REF_PREFIX}{hex}"));
+    stored.seal();
+    Ok(Some(stored))
+}
+
+/// 读取后调用：把 diary 引用还原为正文（校验 blob 内容哈希），或解压 zstd 压缩的 diary。
+///
+/// 引用/压缩形式的 checksum 校验通过时，还原后按内联形式重新签名，与写入前的记录完全一致。
+pub fn resolve(dir: &Path, item: &mut MemoryItem) -> Result<(), String> {
+    if item.diary_zstd.is_some() {
+        return decompress_diary(item);
+    }
+    let Some(reference) = item.diary_ref.clone() else {
+        return Ok(());
+    };
+    let hex = reference
+        .strip_prefix(BLOB_REF_PREFIX)
+        .filter(|x| x.len() == 64 && x.bytes().all(|b| b.is_ascii_hexdigit()))
+        .ok_or_else(|| format!("记忆 id={} 的 diary_ref 无效：{reference}", item.id))?;
+
+    let bytes = fs::read(blob_path(dir, hex))
+        .map_err(|e| format!("读取 diary blob 失败：id={} {reference}：{e}", item.id))?;
+    if sha256_hex(&bytes) != hex {
+        return Err(format!(
+            "diary blob 校验失败：id={} {reference} 内容与哈希不一致（可能被外部修改或损坏）",
+            item.id
+        ));
+    }
+    let diary = String::from_utf8(bytes).map_err(|_| format!("diary blob 不是有效的 UTF-8：{reference}"))?;
+
+    let signed = item.verify_checksum() == Some(true);
+    item.diary = diary;
+    item.diary_ref = None;
+    if signed {
+        item.seal();
+    }
+    Ok(())
+}
+
+#[cfg(feature = "zstd")]
+fn compress_diary(item: &MemoryItem) -> Result<Option<MemoryItem>, String> {
+    use base64::Engine;
+
+    if item.diary.len() < ZSTD_MIN_BYTES {
+        return Ok(None);
+    }
+    let compressed = zstd::encode_all(item.diary.as_bytes(), ZSTD_LEVEL).map_err(|e| format!("zstd 压缩 diary 失败：{e}"))?;
+    let encoded = base64::engine::general_purpose::STANDARD.encode(compressed);
+    if encoded.len() >= item.diary.len() {
+        return Ok(None);
+    }
+
+    let mut stored = item.clone();
+    stored.diary = String::new();
+    stored.diary_zstd = Some(encoded);
+    stored.seal();
+    Ok(Some(stored))
+}
+
+#[cfg(not(feature = "zstd"))]
+fn compress_diary(_item: &MemoryItem) -> Result<Option<MemoryItem>, String> {
+    Ok(None)
+}
+
+#[cfg(feature = "zstd")]
+fn decompress_diary(item: &mut MemoryItem) -> Result<(), String> {
+    use base64::Engine;
+
+    let encoded = item.diary_zstd.as_deref().unwrap_or_default();
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(encoded)
+        .map_err(|e| format!("记忆 id={} 的 diary_zstd 无效：{e}", item.id))?;
+    let raw = zstd::decode_all(bytes.as_slice()).map_err(|e| format!("解压 diary 失败：id={}：{e}", item.id))?;
+    let diary = String::from_utf8(raw).map_err(|_| format!("解压后的 diary 不是有效的 UTF-8：id={}", item.id))?;
+
+    let signed = item.verify_checksum() == Some(true);
+    item.diary = diary;
+    item.diary_zstd = None;
+    if signed {
+        item.seal();
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "zstd"))]
+fn decompress_diary(item: &mut MemoryItem) -> Result<(), String> {
+    Err(format!(
+        "记忆 id={} 的 diary 以 zstd 压缩存储，需以 `zstd` 特性编译才能读取",
+        item.id
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn externalize_should_roundtrip_and_share_blob() {
+        let dir = tempfile::tempdir().unwrap();
+        let item_of = |id: &str, diary: String| -> MemoryItem {
+            serde_json::from_value(serde_json::json!({
+                "id": id, "namespace": "u1/p1", "recorded_at": "2024-01-01T00:00:00Z",
+                "keywords": ["k"], "slice": "s", "diary": diary
+            }))
+            .unwrap()
+        };
+        let mut item = item_of("a", "模板".repeat(BLOB_MIN_BYTES));
+        item.seal();
+
+        let stored = externalize(dir.path(), &item, false).unwrap().unwrap();
+        assert!(stored.diary.is_empty());
+        assert_eq!(stored.verify_checksum(), Some(true));
+
+        let mut other = item.clone();
+        other.id = "b".to_string();
+        let other_stored = externalize(dir.path(), &other, false).unwrap().unwrap();
+        assert_eq!(other_stored.diary_ref, stored.diary_ref);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+
+        let mut resolved = stored.clone();
+        resolve(dir.path(), &mut resolved).unwrap();
+        assert_eq!(resolved.diary, item.diary);
+        assert_eq!(resolved.checksum, item.checksum);
+
+        let small = item_of("c", "短".to_string());
+        assert!(externalize(dir.path(), &small, false).unwrap().is_none());
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn externalize_should_compress_mid_sized_diary_with_zstd() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut item: MemoryItem = serde_json::from_value(serde_json::json!({
+            "id": "a", "namespace": "u1/p1", "recorded_at": "2024-01-01T00:00:00Z",
+            "keywords": ["k"], "slice": "s", "diary": "复盘：数据库迁移演练顺利。".repeat(40)
+        }))
+        .unwrap();
+        item.seal();
+
+        assert!(externalize(dir.path(), &item, false).unwrap().is_none());
+        let stored = externalize(dir.path(), &item, true).unwrap().unwrap();
+        assert!(stored.diary.is_empty() && stored.diary_ref.is_none());
+        assert!(stored.diary_zstd.as_ref().unwrap().len() < item.diary.len());
+        assert_eq!(stored.verify_checksum(), Some(true));
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+
+        let mut resolved = stored.clone();
+        resolve(dir.path(), &mut resolved).unwrap();
+        assert_eq!(resolved.diary, item.diary);
+        assert_eq!(resolved.checksum, item.checksum);
+    }
+}
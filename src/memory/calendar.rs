@@ -0,0 +1,247 @@
+use crate::memory::time::{self, DateBoundKind};
+use chrono::{DateTime, Datelike, Local, NaiveDate, Timelike, Weekday};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+/// 节假日表文件（可选）：通过环境变量 `MEMORY_HOLIDAYS_FILE` 指定路径。
+pub const HOLIDAYS_FILE_ENV: &str = "MEMORY_HOLIDAYS_FILE";
+
+/// 工作日日历：默认周一 ~ 周五为工作日；节假日表可额外声明放假日与调休上班日。
+#[derive(Debug, Clone, Default)]
+pub struct Calendar {
+    holidays: HashSet<NaiveDate>,
+    workdays: HashSet<NaiveDate>,
+}
+
+/// 节假日表文件格式：`{"holidays":["2025-05-01",...],"workdays":["2025-04-27",...]}`。
+#[derive(Debug, Default, Deserialize)]
+struct HolidaysFile {
+    #[serde(default)]
+    holidays: Vec<String>,
+    /// 调休上班日（落在周末但需要上班）。
+    #[serde(default)]
+    workdays: Vec<String>,
+}
+
+impl Calendar {
+    /// 读取 `MEMORY_HOLIDAYS_FILE`；未设置时仅按周末区分工作日。
+    pub fn from_env() -> Result<Self, String> {
+        match std::env::var(HOLIDAYS_FILE_ENV) {
+            Ok(path) if !path.trim().is_empty() => Self::load(Path::new(path.trim())),
+            _ => Ok(Self::default()),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("read holidays file failed ({}): {e}", path.display()))?;
+        let file: HolidaysFile = serde_json::from_str(&text)
+            .map_err(|e| format!("parse holidays file failed ({}): {e}", path.display()))?;
+
+        Ok(Self {
+            holidays: parse_dates(&file.holidays, "holidays")?,
+            workdays: parse_dates(&file.workdays, "workdays")?,
+        })
+    }
+
+    pub fn is_workday(&self, date: NaiveDate) -> bool {
+        if self.workdays.contains(&date) {
+            return true;
+        }
+        if self.holidays.contains(&date) {
+            return false;
+        }
+        !matches!(date.weekday(), Weekday::Sat | Weekday::Sun)
+    }
+
+    /// 按时间戳所在日期（与 `YYYY-MM-DD` 解析口径一致，按 UTC 日界）判断是否工作日。
+    pub fn is_workday_ts(&self, ts: i64) -> bool {
+        DateTime::from_timestamp(ts, 0)
+            .map(|dt| self.is_workday(dt.date_naive()))
+            .unwrap_or(false)
+    }
+}
+
+fn parse_dates(values: &[String], field: &str) -> Result<HashSet<NaiveDate>, String> {
+    values
+        .iter()
+        .map(|x| {
+            NaiveDate::parse_from_str(x.trim(), "%Y-%m-%d")
+                .map_err(|_| format!("holidays file: {field} 中的日期无效：{x}（需为 YYYY-MM-DD）"))
+        })
+        .collect()
+}
+
+/// 解析 `workdays:` 后的区间，返回 (start_ts, end_ts)。
+///
+/// 除通用时间写法（YYYY-MM-DD / 周 / 中文日期等）外，额外支持 `YYYY-MM` 表示整月。
+pub fn parse_period_ts(text: &str) -> Option<(i64, i64)> {
+    if let Some((start, end)) = parse_year_month(text) {
+        let (start_ts, _) = time::parse_time_to_ts_and_canonical(&start.to_string(), DateBoundKind::Start).ok()?;
+        let (end_ts, _) = time::parse_time_to_ts_and_canonical(&end.to_string(), DateBoundKind::End).ok()?;
+        return Some((start_ts, end_ts));
+    }
+
+    let (start_ts, _) = time::parse_time_to_ts_and_canonical(text, DateBoundKind::Start).ok()?;
+    let (end_ts, _) = time::parse_time_to_ts_and_canonical(text, DateBoundKind::End).ok()?;
+    Some((start_ts, end_ts))
+}
+
+fn parse_year_month(text: &str) -> Option<(NaiveDate, NaiveDate)> {
+    let (y, m) = text.trim().split_once('-')?;
+    if y.len() != 4 || m.is_empty() || m.len() > 2 {
+        return None;
+    }
+    if !y.chars().chain(m.chars()).all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    // 借用中文日期解析的整月区间逻辑（2025年5月）。
+    let today = Local::now().date_naive();
+    time::parse_zh_date_range(&format!("{y}年{m}月"), today)
+}
+
+/// 记录时间的星期（0=周一 … 6=周日）与小时（0~23）。
+///
+/// 带时间的 RFC3339（存储时统一为 UTC）按本机时区换算为本地钟点，与用户感知的“晚上/周末”一致；
+/// 只有日期（`YYYY-MM-DD`）时小时未知。
+pub fn weekday_and_hour(text: &str) -> (Option<u8>, Option<u8>) {
+    let text = text.trim();
+    if let Ok(dt) = DateTime::parse_from_rfc3339(text) {
+        let local = dt.with_timezone(&Local);
+        return (Some(local.weekday().num_days_from_monday() as u8), Some(local.hour() as u8));
+    }
+    match NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+        Ok(date) => (Some(date.weekday().num_days_from_monday() as u8), None),
+        Err(_) => (None, None),
+    }
+}
+
+/// 解析 `dow:` 后的星期集合，返回位掩码（bit0=周一 … bit6=周日）。
+///
+/// 以逗号分隔多项，每项为单个星期或 `a..b` 区间（可跨周末回绕，如 `fri..mon`）；
+/// 星期可写 `mon`/`monday`、`周一`/`星期一`（`周日`/`周天`）或 ISO 序号 `1`~`7`。
+pub fn parse_weekday_mask(text: &str) -> Option<u8> {
+    let mut mask = 0u8;
+    for part in text.split(',') {
+        let part = part.trim();
+        match part.split_once("..") {
+            Some((a, b)) => {
+                let (a, b) = (parse_weekday(a)?, parse_weekday(b)?);
+                let mut d = a;
+                loop {
+                    mask |= 1 << d;
+                    if d == b {
+                        break;
+                    }
+                    d = (d + 1) % 7;
+                }
+            }
+            None => mask |= 1 << parse_weekday(part)?,
+        }
+    }
+    Some(mask)
+}
+
+fn parse_weekday(text: &str) -> Option<u8> {
+    let t = text.trim().to_lowercase();
+    let t = t
+        .strip_prefix("星期")
+        .or_else(|| t.strip_prefix("礼拜"))
+        .or_else(|| t.strip_prefix('周'))
+        .unwrap_or(&t);
+    let d = match t {
+        "mon" | "monday" | "一" | "1" => 0,
+        "tue" | "tues" | "tuesday" | "二" | "2" => 1,
+        "wed" | "wednesday" | "三" | "3" => 2,
+        "thu" | "thur" | "thurs" | "thursday" | "四" | "4" => 3,
+        "fri" | "friday" | "五" | "5" => 4,
+        "sat" | "saturday" | "六" | "6" => 5,
+        "sun" | "sunday" | "日" | "天" | "7" => 6,
+        _ => return None,
+    };
+    Some(d)
+}
+
+/// 解析 `hour` 条件（运算符为 `>=` / `<=` / `=`），返回位掩码（bit h 表示 h 点）。
+///
+/// `=` 支持单个小时或 `a..b` 区间（可跨午夜回绕，如 `22..2`）；小时取 0~23。
+pub fn parse_hour_mask(op: &str, text: &str) -> Option<u32> {
+    let hour = |s: &str| s.trim().parse::<u32>().ok().filter(|h| *h < 24);
+    let range = |a: u32, b: u32| {
+        let mut mask = 0u32;
+        let mut h = a;
+        loop {
+            mask |= 1 << h;
+            if h == b {
+                break;
+            }
+            h = (h + 1) % 24;
+        }
+        mask
+    };
+    match op {
+        ">=" => Some(range(hour(text)?, 23)),
+        "<=" => Some(range(0, hour(text)?)),
+        "=" => match text.split_once("..") {
+            Some((a, b)) => Some(range(hour(a)?, hour(b)?)),
+            None => Some(1 << hour(text)?),
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn holidays_file_should_override_weekends() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let path = dir.path().join("holidays.json");
+        fs::write(
+            &path,
+            r#"{"holidays":["2025-05-01","2025-05-02"],"workdays":["2025-04-27"]}"#,
+        )
+        .expect("write holidays");
+
+        let cal = Calendar::load(&path).expect("load");
+        let d = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+        assert!(!cal.is_workday(d(2025, 5, 1)));
+        assert!(cal.is_workday(d(2025, 4, 27)));
+        assert!(!cal.is_workday(d(2025, 5, 3)));
+        assert!(cal.is_workday(d(2025, 5, 6)));
+    }
+
+    #[test]
+    fn parse_period_should_accept_year_month() {
+        let (start, end) = parse_period_ts("2025-05").expect("period");
+        let (expected_start, _) =
+            time::parse_time_to_ts_and_canonical("2025-05-01", DateBoundKind::Start).unwrap();
+        let (expected_end, _) =
+            time::parse_time_to_ts_and_canonical("2025-05-31", DateBoundKind::End).unwrap();
+        assert_eq!((start, end), (expected_start, expected_end));
+        assert!(parse_period_ts("2025-13").is_none());
+    }
+
+    #[test]
+    fn weekday_and_hour_masks_should_parse_ranges() {
+        let local = Local.with_ymd_and_hms(2025, 5, 2, 19, 30, 0).unwrap();
+        assert_eq!(weekday_and_hour(&local.to_rfc3339()), (Some(4), Some(19)));
+        assert_eq!(weekday_and_hour("2025-05-03"), (Some(5), None));
+        assert_eq!(weekday_and_hour("2025-05"), (None, None));
+
+        assert_eq!(parse_weekday_mask("mon..fri"), Some(0b001_1111));
+        assert_eq!(parse_weekday_mask("周六,周日"), Some(0b110_0000));
+        assert_eq!(parse_weekday_mask("fri..1"), Some(0b111_0001));
+        assert_eq!(parse_weekday_mask("funday"), None);
+
+        assert_eq!(parse_hour_mask(">=", "18"), Some(0b111111 << 18));
+        assert_eq!(parse_hour_mask("<=", "1"), Some(0b11));
+        assert_eq!(parse_hour_mask("=", "22..1"), Some((0b11 << 22) | 0b11));
+        assert_eq!(parse_hour_mask("=", "24"), None);
+    }
+}
@@ -0,0 +1,111 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// 根目录级 namespace 目录文件：列出已知 namespace 及其条数 / 最近写入时间，
+/// 使 namespaces_list、全局关键字等操作不必每次遍历整个存储目录树。
+pub const CATALOG_FILE: &str = "catalog.json";
+pub const CATALOG_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Catalog {
+    pub version: u32,
+    #[serde(default)]
+    pub namespaces: BTreeMap<String, CatalogEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CatalogEntry {
+    /// 当前有效记忆条数。
+    pub items: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_write: Option<String>,
+    /// 记录时 memories.jsonl 的字节数：与实际大小不一致说明有外部写入，条目需要刷新。
+    pub bytes: u64,
+}
+
+impl Default for Catalog {
+    fn default() -> Self {
+        Self {
+            version: CATALOG_VERSION,
+            namespaces: BTreeMap::new(),
+        }
+    }
+}
+
+impl Catalog {
+    pub fn path(root_dir: &Path) -> PathBuf {
+        root_dir.join(CATALOG_FILE)
+    }
+
+    /// 读取目录文件；不存在、无法解析或版本不一致时返回 None（调用方应全量重建）。
+    pub fn load(root_dir: &Path) -> Option<Self> {
+        let text = fs::read_to_string(Self::path(root_dir)).ok()?;
+        serde_json::from_str::<Self>(&text)
+            .ok()
+            .filter(|x| x.version == CATALOG_VERSION)
+    }
+
+    /// 先写临时文件再替换，避免并发读取到半截内容。
+    pub fn save(&self, root_dir: &Path) -> Result<(), String> {
+        let path = Self::path(root_dir);
+        let text = serde_json::to_string(self).map_err(|e| format!("serialize catalog failed: {e}"))?;
+
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, text).map_err(|e| format!("write catalog tmp failed: {e}"))?;
+        if let Err(e) = fs::rename(&tmp, &path) {
+            let _ = fs::remove_file(&path);
+            fs::rename(&tmp, &path).map_err(|_| format!("replace catalog failed: {e}"))?;
+        }
+        Ok(())
+    }
+
+    /// 写入/更新单个 namespace 的条目（目录文件尚不存在时不创建，留待首次列出时全量重建）。
+    pub fn upsert(root_dir: &Path, namespace: &str, entry: CatalogEntry) -> Result<(), String> {
+        let Some(mut catalog) = Self::load(root_dir) else {
+            return Ok(());
+        };
+        if catalog.namespaces.get(namespace) == Some(&entry) {
+            return Ok(());
+        }
+        catalog.namespaces.insert(namespace.to_string(), entry);
+        catalog.save(root_dir)
+    }
+
+    pub fn remove(root_dir: &Path, namespace: &str) -> Result<(), String> {
+        let Some(mut catalog) = Self::load(root_dir) else {
+            return Ok(());
+        };
+        if catalog.namespaces.remove(namespace).is_some() {
+            catalog.save(root_dir)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn upsert_should_only_touch_existing_catalog() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let entry = CatalogEntry {
+            items: 1,
+            last_write: None,
+            bytes: 10,
+        };
+
+        Catalog::upsert(dir.path(), "u1/p1", entry.clone()).expect("upsert");
+        assert!(Catalog::load(dir.path()).is_none());
+
+        Catalog::default().save(dir.path()).expect("save");
+        Catalog::upsert(dir.path(), "u1/p1", entry.clone()).expect("upsert");
+        let catalog = Catalog::load(dir.path()).expect("load");
+        assert_eq!(catalog.namespaces.get("u1/p1"), Some(&entry));
+
+        Catalog::remove(dir.path(), "u1/p1").expect("remove");
+        assert!(Catalog::load(dir.path()).expect("load").namespaces.is_empty());
+    }
+}
@@ -0,0 +1,434 @@
+use crate::memory::model::{MemorySource, RememberArgs};
+use crate::memory::time::{self, DateBoundKind};
+use chrono::DateTime;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// 暂存文件格式标识与版本：`distill --from` 生成，人工审阅后由 `distill --apply` 写入。
+pub const DISTILL_FORMAT: &str = "memory-distill";
+pub const DISTILL_FORMAT_VERSION: u32 = 1;
+
+/// 默认会话切分间隔：相邻两条消息相隔超过该分钟数即视为新会话。
+pub const DEFAULT_SESSION_GAP_MINUTES: i64 = 30;
+
+const SLICE_MAX_CHARS: usize = 200;
+const DIARY_LINE_MAX_CHARS: usize = 200;
+const DIARY_MAX_LINES: usize = 40;
+const MAX_KEYWORDS: usize = 5;
+const FALLBACK_KEYWORD: &str = "对话";
+
+const STOPWORDS: &[&str] = &[
+    "the", "and", "for", "you", "are", "this", "that", "with", "have", "was", "not", "but",
+    "can", "will", "what", "how", "your", "from", "they", "there", "about", "just", "like",
+    "would", "could", "should", "please", "thanks", "yes", "our", "its", "into", "then",
+    "我们", "你们", "他们", "这个", "那个", "一个", "可以", "没有", "什么", "怎么", "就是",
+    "因为", "所以", "但是", "如果", "然后", "还是", "已经", "现在", "需要", "一下", "不是",
+];
+
+/// 聊天记录中的一条消息（已从 JSONL 行解析）。
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChatMessage {
+    pub role: String,
+    pub content: String,
+    pub ts: Option<i64>,
+    pub session: Option<String>,
+}
+
+/// 一条待审阅的候选记忆；`accept=false` 的条目在 apply 时跳过，其余字段可直接手工修改。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Proposal {
+    #[serde(default = "default_accept")]
+    pub accept: bool,
+    /// 分段标识：`<日期>#<序号>`，或 `<session>#<序号>`。
+    pub segment: String,
+    #[serde(default)]
+    pub messages: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub occurred_at: Option<String>,
+    pub keywords: Vec<String>,
+    pub slice: String,
+    pub diary: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub importance: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<MemorySource>,
+}
+
+fn default_accept() -> bool {
+    true
+}
+
+/// 暂存文件：目标 namespace + 候选记忆列表。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StagedDistill {
+    pub format: String,
+    pub format_version: u32,
+    pub namespace: String,
+    pub from: String,
+    pub created_at: String,
+    pub proposals: Vec<Proposal>,
+}
+
+impl StagedDistill {
+    pub fn new(namespace: &str, from: &str, proposals: Vec<Proposal>) -> Self {
+        Self {
+            format: DISTILL_FORMAT.to_string(),
+            format_version: DISTILL_FORMAT_VERSION,
+            namespace: namespace.to_string(),
+            from: from.to_string(),
+            created_at: time::now_rfc3339_and_ts().0,
+            proposals,
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let staged: Self =
+            serde_json::from_str(text).map_err(|e| format!("暂存文件解析失败：{e}"))?;
+        if staged.format != DISTILL_FORMAT {
+            return Err(format!("不支持的暂存文件格式：{}", staged.format));
+        }
+        if staged.format_version == 0 || staged.format_version > DISTILL_FORMAT_VERSION {
+            return Err(format!("不支持的暂存文件版本：{}", staged.format_version));
+        }
+        Ok(staged)
+    }
+
+    /// 已接受的候选转换为 remember 参数（namespace 可覆盖暂存文件中的值）。
+    pub fn into_remember_args(self, namespace: Option<&str>) -> Vec<RememberArgs> {
+        let namespace = namespace.unwrap_or(&self.namespace).to_string();
+        self.proposals
+            .into_iter()
+            .filter(|p| p.accept)
+            .map(|p| RememberArgs {
+                namespace: namespace.clone(),
+                keywords: p.keywords,
+                slice: p.slice,
+                diary: p.diary,
+                occurred_at: p.occurred_at,
+                importance: p.importance,
+                source: p.source,
+                ..Default::default()
+            })
+            .collect()
+    }
+}
+
+/// 解析聊天记录 JSONL：每行一条消息，兼容常见字段名。
+///
+/// - 角色：`role` / `author` / `speaker`
+/// - 内容：`content` / `text` / `message`（字符串，或 `[{"type":"text","text":...}]` 分段数组）
+/// - 时间：`timestamp` / `time` / `created_at` / `ts`（RFC3339、YYYY-MM-DD 或 Unix 秒/毫秒）
+/// - 会话：`session` / `session_id` / `conversation_id`
+///
+/// 空行跳过；无法解析或内容为空的行计入第二个返回值（行号从 1 开始）。
+pub fn parse_chat_log(text: &str) -> (Vec<ChatMessage>, Vec<usize>) {
+    let mut out = Vec::new();
+    let mut skipped = Vec::new();
+
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<Value>(line).ok().and_then(|v| parse_message(&v)) {
+            Some(msg) => out.push(msg),
+            None => skipped.push(i + 1),
+        }
+    }
+
+    (out, skipped)
+}
+
+fn parse_message(v: &Value) -> Option<ChatMessage> {
+    let field = |names: &[&str]| names.iter().find_map(|n| v.get(*n)).filter(|x| !x.is_null());
+
+    let content = match field(&["content", "text", "message"])? {
+        Value::String(s) => s.trim().to_string(),
+        Value::Array(parts) => parts
+            .iter()
+            .filter_map(|p| p.as_str().or_else(|| p.get("text").and_then(|x| x.as_str())))
+            .collect::<Vec<_>>()
+            .join("\n")
+            .trim()
+            .to_string(),
+        _ => return None,
+    };
+    if content.is_empty() {
+        return None;
+    }
+
+    let role = field(&["role", "author", "speaker"])
+        .and_then(|x| x.as_str())
+        .map(|x| x.trim().to_lowercase())
+        .filter(|x| !x.is_empty())
+        .unwrap_or_else(|| "unknown".to_string());
+
+    let ts = field(&["timestamp", "time", "created_at", "ts"]).and_then(|x| match x {
+        Value::Number(n) => n.as_i64().map(|n| if n > 100_000_000_000 { n / 1000 } else { n }),
+        Value::String(s) => time::parse_time_to_ts_and_canonical(s, DateBoundKind::Start)
+            .ok()
+            .map(|x| x.0),
+        _ => None,
+    });
+
+    let session = field(&["session", "session_id", "conversation_id"]).and_then(|x| match x {
+        Value::String(s) => Some(s.trim().to_string()).filter(|s| !s.is_empty()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    });
+
+    Some(ChatMessage {
+        role,
+        content,
+        ts,
+        session,
+    })
+}
+
+/// 解析聊天记录并生成暂存内容；返回 (暂存文件, 被跳过的行号)。
+pub fn stage_chat_log(
+    text: &str,
+    namespace: &str,
+    source_name: &str,
+    gap_minutes: i64,
+    min_messages: usize,
+) -> (StagedDistill, Vec<usize>) {
+    let (messages, skipped) = parse_chat_log(text);
+    let segments = segment_messages(messages, gap_minutes);
+    let proposals = propose(&segments, min_messages, source_name);
+    (StagedDistill::new(namespace, source_name, proposals), skipped)
+}
+
+/// 按会话切分：session 变化、跨天（UTC 日期）或相邻消息间隔超过 `gap_minutes` 时开始新分段。
+/// 没有时间的消息归入前一分段。
+pub fn segment_messages(messages: Vec<ChatMessage>, gap_minutes: i64) -> Vec<Vec<ChatMessage>> {
+    let mut segments: Vec<Vec<ChatMessage>> = Vec::new();
+    let mut last_ts: Option<i64> = None;
+    let mut last_session: Option<String> = None;
+
+    for msg in messages {
+        let split = match segments.last() {
+            None => true,
+            Some(_) => {
+                let session_changed = msg.session.is_some() && msg.session != last_session;
+                let time_split = match (last_ts, msg.ts) {
+                    (Some(a), Some(b)) => utc_day(a) != utc_day(b) || b - a > gap_minutes * 60,
+                    _ => false,
+                };
+                session_changed || time_split
+            }
+        };
+
+        if msg.session.is_some() {
+            last_session = msg.session.clone();
+        }
+        if msg.ts.is_some() {
+            last_ts = msg.ts;
+        }
+
+        if split {
+            segments.push(vec![msg]);
+        } else if let Some(seg) = segments.last_mut() {
+            seg.push(msg);
+        }
+    }
+
+    segments
+}
+
+fn utc_day(ts: i64) -> Option<chrono::NaiveDate> {
+    DateTime::from_timestamp(ts, 0).map(|x| x.date_naive())
+}
+
+/// 从分段生成候选记忆（启发式）：slice 取首条用户消息，diary 为对话摘录，keywords 取高频词。
+///
+/// 消息数少于 `min_messages` 的分段被忽略。`source_name` 用于生成 `source` 字段。
+pub fn propose(segments: &[Vec<ChatMessage>], min_messages: usize, source_name: &str) -> Vec<Proposal> {
+    let mut out = Vec::new();
+    let mut seq_by_label: HashMap<String, usize> = HashMap::new();
+
+    for seg in segments {
+        if seg.is_empty() || seg.len() < min_messages {
+            continue;
+        }
+
+        let start_ts = seg.iter().find_map(|m| m.ts);
+        let end_ts = seg.iter().rev().find_map(|m| m.ts);
+        let label = match (&seg[0].session, start_ts.and_then(utc_day)) {
+            (Some(s), _) => s.clone(),
+            (None, Some(day)) => day.to_string(),
+            (None, None) => "chat".to_string(),
+        };
+        let seq = seq_by_label.entry(label.clone()).or_insert(0);
+        *seq += 1;
+        let segment = format!("{label}#{seq}");
+
+        let first = seg
+            .iter()
+            .find(|m| matches!(m.role.as_str(), "user" | "human"))
+            .unwrap_or(&seg[0]);
+        let slice = truncate_chars(&collapse_whitespace(&first.content), SLICE_MAX_CHARS);
+
+        let mut diary = format!("对话摘录（{} 条消息", seg.len());
+        if let (Some(a), Some(b)) = (start_ts, end_ts) {
+            diary.push_str(&format!("，{} ~ {}", time::ts_to_rfc3339(a), time::ts_to_rfc3339(b)));
+        }
+        diary.push_str("）：");
+        for m in seg.iter().take(DIARY_MAX_LINES) {
+            let line = truncate_chars(&collapse_whitespace(&m.content), DIARY_LINE_MAX_CHARS);
+            diary.push_str(&format!("\n{}: {}", m.role, line));
+        }
+        if seg.len() > DIARY_MAX_LINES {
+            diary.push_str(&format!("\n……（其余 {} 条省略）", seg.len() - DIARY_MAX_LINES));
+        }
+
+        out.push(Proposal {
+            accept: true,
+            segment: segment.clone(),
+            messages: seg.len(),
+            occurred_at: start_ts.map(time::ts_to_rfc3339),
+            keywords: extract_keywords(seg),
+            slice,
+            diary,
+            importance: None,
+            source: Some(MemorySource {
+                kind: Some("distill".to_string()),
+                id: Some(format!("{source_name}#{segment}")),
+                ..Default::default()
+            }),
+        });
+    }
+
+    out
+}
+
+/// 高频词启发式：英文/数字词（≥3 字符）与中文二元组，去停用词后按频次（同频按首次出现）取前几个；
+/// 仅出现一次的词不入选，全部落空时使用兜底关键字。
+pub fn extract_keywords(messages: &[ChatMessage]) -> Vec<String> {
+    // token -> (频次, 首次出现序号)
+    let mut counts: HashMap<String, (usize, usize)> = HashMap::new();
+    let mut bump = |token: String| {
+        if STOPWORDS.contains(&token.as_str()) {
+            return;
+        }
+        let next = counts.len();
+        let entry = counts.entry(token).or_insert((0, next));
+        entry.0 += 1;
+    };
+
+    for m in messages {
+        let text = m.content.to_lowercase();
+        let mut word = String::new();
+        let mut cjk: Vec<char> = Vec::new();
+        for c in text.chars().chain(std::iter::once(' ')) {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                word.push(c);
+            } else if !word.is_empty() {
+                if word.chars().count() >= 3 && !word.chars().all(|x| x.is_ascii_digit()) {
+                    bump(std::mem::take(&mut word));
+                }
+                word.clear();
+            }
+
+            if is_cjk(c) {
+                cjk.push(c);
+            } else if !cjk.is_empty() {
+                for w in cjk.windows(2) {
+                    bump(w.iter().collect());
+                }
+                cjk.clear();
+            }
+        }
+    }
+
+    let mut ranked: Vec<(String, (usize, usize))> =
+        counts.into_iter().filter(|(_, (n, _))| *n >= 2).collect();
+    ranked.sort_by(|a, b| b.1 .0.cmp(&a.1 .0).then_with(|| a.1 .1.cmp(&b.1 .1)));
+
+    let mut out: Vec<String> = ranked
+        .into_iter()
+        .take(MAX_KEYWORDS)
+        .map(|(k, _)| k)
+        .collect();
+    if out.is_empty() {
+        out.push(FALLBACK_KEYWORD.to_string());
+    }
+    out
+}
+
+fn is_cjk(c: char) -> bool {
+    matches!(c as u32, 0x4E00..=0x9FFF | 0x3400..=0x4DBF | 0xF900..=0xFAFF)
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn truncate_chars(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    let mut out: String = text.chars().take(max).collect();
+    out.push('…');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOG: &str = r#"
+{"role":"user","content":"帮我规划 ERP 项目的上线计划","timestamp":"2025-03-01T09:00:00Z"}
+{"role":"assistant","content":[{"type":"text","text":"好的，ERP 项目上线分三步"}],"timestamp":"2025-03-01T09:01:00Z"}
+not json
+{"role":"user","content":"ERP 项目上线要注意数据迁移","timestamp":"2025-03-01T09:05:00Z"}
+{"role":"user","content":"下午继续","timestamp":"2025-03-01T14:00:00Z"}
+{"role":"user","content":"第二天","timestamp":1740906000}
+{"role":"user","content":"另一个会话","timestamp":1740906060,"session_id":"s2"}
+"#;
+
+    #[test]
+    fn segment_should_split_by_gap_day_and_session() {
+        let (messages, skipped) = parse_chat_log(LOG);
+        assert_eq!(skipped, vec![4]);
+        assert_eq!(messages.len(), 6);
+        assert_eq!(messages[1].content, "好的，ERP 项目上线分三步");
+
+        let segments = segment_messages(messages, DEFAULT_SESSION_GAP_MINUTES);
+        let sizes: Vec<usize> = segments.iter().map(|x| x.len()).collect();
+        assert_eq!(sizes, vec![3, 1, 1, 1]);
+
+        let proposals = propose(&segments, 1, "chat.jsonl");
+        let labels: Vec<&str> = proposals.iter().map(|x| x.segment.as_str()).collect();
+        assert_eq!(labels, vec!["2025-03-01#1", "2025-03-01#2", "2025-03-02#1", "s2#1"]);
+
+        let first = &proposals[0];
+        assert_eq!(first.slice, "帮我规划 ERP 项目的上线计划");
+        assert_eq!(first.occurred_at.as_deref(), Some("2025-03-01T09:00:00Z"));
+        assert_eq!(first.keywords[..3], ["erp", "项目", "上线"]);
+        assert!(first.diary.contains("assistant: 好的"));
+        let source = first.source.as_ref().unwrap();
+        assert_eq!(source.kind.as_deref(), Some("distill"));
+        assert_eq!(source.id.as_deref(), Some("chat.jsonl#2025-03-01#1"));
+
+        assert_eq!(proposals[1].keywords, vec![FALLBACK_KEYWORD]);
+        assert_eq!(propose(&segments, 2, "chat.jsonl").len(), 1);
+    }
+
+    #[test]
+    fn staged_should_roundtrip_and_skip_rejected_proposals() {
+        let (messages, _) = parse_chat_log(LOG);
+        let mut proposals = propose(&segment_messages(messages, 30), 1, "chat.jsonl");
+        proposals[1].accept = false;
+
+        let staged = StagedDistill::new("u1/p1", "chat.jsonl", proposals);
+        let text = serde_json::to_string(&staged).unwrap();
+        let args = StagedDistill::parse(&text).unwrap().into_remember_args(Some("u2/p2"));
+        assert_eq!(args.len(), 3);
+        assert!(args.iter().all(|x| x.namespace == "u2/p2"));
+
+        let err = StagedDistill::parse(&text.replace(DISTILL_FORMAT, "other")).unwrap_err();
+        assert!(err.contains("不支持的暂存文件格式"), "{err}");
+    }
+}
@@ -0,0 +1,267 @@
+use crate::memory::segment;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// 向量化配置文件（可选）：通过环境变量 `MEMORY_EMBEDDING_CONFIG` 指定路径。
+pub const EMBEDDING_CONFIG_ENV: &str = "MEMORY_EMBEDDING_CONFIG";
+
+const DEFAULT_HASH_DIMENSIONS: usize = 256;
+const MAX_DIMENSIONS: usize = 4096;
+
+/// 语义召回使用的向量化后端；全部在本机完成，不发起网络请求。
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum Embedder {
+    /// 未配置：语义召回不可用。
+    #[default]
+    Disabled,
+    /// 内置特征哈希：分词 + 汉字二元组散列到固定维度，零依赖、适合离线/气隙环境的基础语义近似。
+    Hash { dimensions: usize },
+    /// 外部本地命令（例如封装 fastembed / ONNX Runtime 的脚本）：
+    /// stdin 收到 `{"texts":[...]}`，stdout 返回 `{"embeddings":[[...],...]}`。
+    Command {
+        program: String,
+        args: Vec<String>,
+        /// 模型标识：参与向量缓存的校验，切换模型后旧向量自动失效。
+        model: String,
+    },
+}
+
+/// 配置文件格式：
+/// `{"backend":"hash","dimensions":256}` 或 `{"backend":"command","command":["python3","embed.py"],"model":"bge-small-zh"}`。
+#[derive(Debug, Deserialize)]
+struct EmbeddingFile {
+    backend: String,
+    #[serde(default)]
+    dimensions: Option<usize>,
+    #[serde(default)]
+    command: Vec<String>,
+    #[serde(default)]
+    model: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CommandOutput {
+    embeddings: Vec<Vec<f32>>,
+}
+
+impl Embedder {
+    /// 读取 `MEMORY_EMBEDDING_CONFIG`；未设置时不启用语义召回。
+    pub fn from_env() -> Result<Self, String> {
+        match std::env::var(EMBEDDING_CONFIG_ENV) {
+            Ok(path) if !path.trim().is_empty() => Self::load(Path::new(path.trim())),
+            _ => Ok(Self::default()),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("read embedding config failed ({}): {e}", path.display()))?;
+        let file: EmbeddingFile = serde_json::from_str(&text)
+            .map_err(|e| format!("parse embedding config failed ({}): {e}", path.display()))?;
+        Self::from_file(file)
+    }
+
+    fn from_file(file: EmbeddingFile) -> Result<Self, String> {
+        match file.backend.trim().to_lowercase().as_str() {
+            "" | "none" | "disabled" => Ok(Self::Disabled),
+            "hash" => {
+                let dimensions = file.dimensions.unwrap_or(DEFAULT_HASH_DIMENSIONS);
+                if !(1..=MAX_DIMENSIONS).contains(&dimensions) {
+                    return Err(format!("embedding dimensions 必须在 1~{MAX_DIMENSIONS}"));
+                }
+                Ok(Self::Hash { dimensions })
+            }
+            "command" => {
+                let mut parts = file.command.into_iter();
+                let Some(program) = parts.next().filter(|x| !x.trim().is_empty()) else {
+                    return Err("embedding backend=command 时 command 不能为空".to_string());
+                };
+                let model = file.model.unwrap_or_else(|| program.clone());
+                Ok(Self::Command {
+                    program,
+                    args: parts.collect(),
+                    model,
+                })
+            }
+            other => Err(format!("不支持的 embedding backend：{other}（可选 none / hash / command）")),
+        }
+    }
+
+    /// 未配置后端时返回提示如何启用的错误。
+    pub fn ensure_enabled(&self) -> Result<(), String> {
+        match self {
+            Self::Disabled => Err(format!(
+                "语义召回未启用：请通过 {EMBEDDING_CONFIG_ENV} 指定向量化配置文件（backend 可选 hash / command）"
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// 后端标识：写入向量缓存，后端或模型变化时缓存整体失效。
+    pub fn fingerprint(&self) -> String {
+        match self {
+            Self::Disabled => "none".to_string(),
+            Self::Hash { dimensions } => format!("hash:{dimensions}"),
+            Self::Command { model, .. } => format!("command:{model}"),
+        }
+    }
+
+    /// 批量向量化；返回与输入一一对应的 L2 归一化向量。
+    pub fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, String> {
+        self.ensure_enabled()?;
+        if texts.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut out = match self {
+            Self::Disabled => Vec::new(),
+            Self::Hash { dimensions } => texts.iter().map(|t| hash_embedding(t, *dimensions)).collect(),
+            Self::Command { program, args, .. } => run_command(program, args, texts)?,
+        };
+        out.iter_mut().for_each(|v| normalize(v));
+        Ok(out)
+    }
+}
+
+fn run_command(program: &str, args: &[String], texts: &[String]) -> Result<Vec<Vec<f32>>, String> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .map_err(|e| format!("启动 embedding 命令失败（{program}）：{e}"))?;
+
+    let input = json!({ "texts": texts }).to_string();
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(input.as_bytes())
+            .map_err(|e| format!("写入 embedding 命令输入失败：{e}"))?;
+    }
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("等待 embedding 命令失败：{e}"))?;
+    if !output.status.success() {
+        return Err(format!("embedding 命令执行失败（{program}）：{}", output.status));
+    }
+
+    let parsed: CommandOutput = serde_json::from_slice(&output.stdout)
+        .map_err(|e| format!("解析 embedding 命令输出失败：{e}"))?;
+    if parsed.embeddings.len() != texts.len() {
+        return Err(format!(
+            "embedding 命令返回了 {} 个向量，期望 {} 个",
+            parsed.embeddings.len(),
+            texts.len()
+        ));
+    }
+    Ok(parsed.embeddings)
+}
+
+/// 特征哈希：词项（分词结果）与汉字二元组各自散列到一个维度，按哈希位决定正负号。
+fn hash_embedding(text: &str, dimensions: usize) -> Vec<f32> {
+    let mut v = vec![0.0f32; dimensions];
+    let lower = text.to_lowercase();
+    let mut add = |feature: &str| {
+        let h = fnv1a(feature.as_bytes());
+        let slot = (h % dimensions as u64) as usize;
+        v[slot] += if h >> 63 == 0 { 1.0 } else { -1.0 };
+    };
+
+    for term in segment::index_terms(&lower) {
+        add(&term);
+    }
+    let chars: Vec<char> = lower.chars().filter(|c| c.is_alphanumeric()).collect();
+    for pair in chars.windows(2) {
+        add(&pair.iter().collect::<String>());
+    }
+    v
+}
+
+/// FNV-1a：跨平台、跨版本稳定，保证缓存的向量可复用。
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |h, &b| (h ^ b as u64).wrapping_mul(0x100000001b3))
+}
+
+fn normalize(v: &mut [f32]) {
+    let norm = v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        v.iter_mut().for_each(|x| *x /= norm);
+    }
+}
+
+/// 余弦相似度（输入已归一化时即点积）；维度不一致时视为不相关。
+pub fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+/// namespace 的向量缓存（embeddings.json）：按记忆 id 保存向量及其对应内容的 checksum。
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EmbeddingCache {
+    pub fingerprint: String,
+    pub vectors: HashMap<String, CachedVector>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedVector {
+    pub checksum: String,
+    pub vector: Vec<f32>,
+}
+
+impl EmbeddingCache {
+    /// 读取缓存；文件缺失、损坏或后端已变化时返回空缓存。
+    pub fn load(path: &Path, fingerprint: &str) -> Self {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|text| serde_json::from_str::<Self>(&text).ok())
+            .filter(|c| c.fingerprint == fingerprint)
+            .unwrap_or_else(|| Self {
+                fingerprint: fingerprint.to_string(),
+                vectors: HashMap::new(),
+            })
+    }
+
+    /// 内容未变化（checksum 一致）时返回缓存的向量。
+    pub fn get(&self, id: &str, checksum: &str) -> Option<&[f32]> {
+        self.vectors
+            .get(id)
+            .filter(|x| x.checksum == checksum)
+            .map(|x| x.vector.as_slice())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hash_embedding_should_rank_related_text_higher() {
+        let embedder = Embedder::Hash { dimensions: 256 };
+        let texts: Vec<String> = ["数据库迁移到新集群", "把数据库迁移到新的集群上", "周末去爬山"]
+            .iter()
+            .map(|x| x.to_string())
+            .collect();
+        let v = embedder.embed(&texts).unwrap();
+        assert!(cosine(&v[0], &v[1]) > cosine(&v[0], &v[2]));
+        assert!((cosine(&v[0], &v[0]) - 1.0).abs() < 1e-5);
+        assert_eq!(embedder.embed(&texts).unwrap(), v);
+    }
+
+    #[test]
+    fn config_should_select_backend() {
+        let parse = |text: &str| Embedder::from_file(serde_json::from_str(text).unwrap());
+        assert_eq!(parse(r#"{"backend":"hash"}"#).unwrap(), Embedder::Hash { dimensions: 256 });
+        assert_eq!(parse(r#"{"backend":"none"}"#).unwrap(), Embedder::Disabled);
+        let cmd = parse(r#"{"backend":"command","command":["python3","embed.py"],"model":"bge"}"#).unwrap();
+        assert_eq!(cmd.fingerprint(), "command:bge");
+        assert!(parse(r#"{"backend":"command"}"#).unwrap_err().contains("command 不能为空"));
+        assert!(parse(r#"{"backend":"openai"}"#).unwrap_err().contains("不支持"));
+        assert!(Embedder::Disabled.embed(&["x".to_string()]).unwrap_err().contains("未启用"));
+    }
+}
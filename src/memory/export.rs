@@ -0,0 +1,558 @@
+use crate::memory::blob;
+use crate::memory::model::{MemoryItem, Tombstone};
+use crate::memory::rotation::DataFiles;
+use crate::memory::time::{self, DateBoundKind};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Write};
+
+/// 导出文档标识与格式版本（与内部 index.json / memories.jsonl 布局解耦）。
+pub const EXPORT_FORMAT: &str = "memory-export";
+pub const EXPORT_FORMAT_VERSION: u32 = 1;
+
+/// 导出文档头：JSONL 形式时为第一行。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportHeader {
+    pub format: String,
+    pub format_version: u32,
+    pub namespace: String,
+    pub exported_at: String,
+    /// 增量导出的起点（原样回显 `--since`）；全量导出时缺省。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub since: Option<String>,
+    /// 导出时 memories.jsonl 的记录数；下次增量导出可直接用作 `--since`。
+    pub next_seq: u64,
+    pub items: usize,
+    pub deletes: usize,
+}
+
+/// 增量导出起点：记录序号（memories.jsonl 中第几条记录，从 0 开始）或时间点。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportSince {
+    Seq(u64),
+    Ts(i64),
+}
+
+impl ExportSince {
+    /// 纯数字视为序号；否则按时间解析（RFC3339 / YYYY-MM-DD 等，日期按当天开始计）。
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let text = text.trim();
+        if !text.is_empty() && text.chars().all(|c| c.is_ascii_digit()) {
+            return text
+                .parse()
+                .map(Self::Seq)
+                .map_err(|_| format!("since 序号无效：{text}"));
+        }
+
+        let (ts, _) = time::parse_time_to_ts_and_canonical(text, DateBoundKind::Start)
+            .map_err(|e| format!("since 无效：{e}"))?;
+        Ok(Self::Ts(ts))
+    }
+}
+
+/// 导出的一条记录：记忆的当前版本，或（仅增量导出）删除标记。
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum ExportRecord {
+    Item(Box<MemoryItem>),
+    Delete(Tombstone),
+}
+
+impl ExportRecord {
+    /// 按结构体字段顺序序列化（与 memories.jsonl 中的行保持一致）。
+    pub fn id(&self) -> &str {
+        match self {
+            Self::Item(item) => &item.id,
+            Self::Delete(t) => &t.id,
+        }
+    }
+
+    /// 解析一条记录：`op=delete` 为删除标记，否则按记忆记录解析。
+    fn from_value(value: Value) -> Result<Self, String> {
+        if value.get("op").and_then(|x| x.as_str()) == Some(Tombstone::OP_DELETE) {
+            return serde_json::from_value(value)
+                .map(Self::Delete)
+                .map_err(|e| format!("删除标记无效：{e}"));
+        }
+
+        let item: MemoryItem =
+            serde_json::from_value(value).map_err(|e| format!("记忆记录无效：{e}"))?;
+        if item.id.trim().is_empty() {
+            return Err("记忆记录无效：id 不能为空".to_string());
+        }
+        Ok(Self::Item(Box::new(item)))
+    }
+
+    pub fn to_json_line(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string(self)
+    }
+
+    #[cfg(test)]
+    pub fn to_value(&self) -> Value {
+        serde_json::to_value(self).unwrap_or_default()
+    }
+}
+
+/// CSV 导出的列（顺序固定）。
+const CSV_COLUMNS: [&str; 13] = [
+    "id",
+    "recorded_at",
+    "occurred_at",
+    "updated_at",
+    "revision",
+    "keywords",
+    "tags",
+    "kind",
+    "importance",
+    "pinned",
+    "archived",
+    "slice",
+    "diary",
+];
+
+/// 含逗号、引号或换行的字段加双引号，内部引号写成两个。
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+/// Markdown 标题只能占一行。
+fn one_line(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// 单文档形式：文档头字段平铺在顶层，记录放在 `records` 数组中。
+#[derive(Serialize)]
+struct ExportDocument<'a> {
+    #[serde(flatten)]
+    header: &'a ExportHeader,
+    records: &'a [ExportRecord],
+}
+
+#[derive(Debug, Clone)]
+pub struct ExportDump {
+    pub header: ExportHeader,
+    pub records: Vec<ExportRecord>,
+}
+
+impl ExportDump {
+    /// 不含任何记录的文档（sync 中一侧没有该 namespace 时使用）。
+    pub fn empty(namespace: &str) -> Self {
+        Self {
+            header: ExportHeader {
+                format: EXPORT_FORMAT.to_string(),
+                format_version: EXPORT_FORMAT_VERSION,
+                namespace: namespace.to_string(),
+                exported_at: time::now_rfc3339_and_ts().0,
+                since: None,
+                next_seq: 0,
+                items: 0,
+                deletes: 0,
+            },
+            records: Vec::new(),
+        }
+    }
+
+    /// JSONL：首行为文档头，其后每行一条记录。
+    pub fn write_jsonl(&self, w: &mut impl Write) -> io::Result<()> {
+        writeln!(w, "{}", serde_json::to_string(&self.header).map_err(io::Error::other)?)?;
+        for record in &self.records {
+            writeln!(w, "{}", record.to_json_line().map_err(io::Error::other)?)?;
+        }
+        Ok(())
+    }
+
+    pub fn to_document(&self) -> serde_json::Value {
+        serde_json::to_value(self.document()).unwrap_or_default()
+    }
+
+    /// 单个 JSON 文档：`{...文档头, "records": [...]}`。
+    pub fn write_json(&self, w: &mut impl Write, pretty: bool) -> io::Result<()> {
+        let doc = self.document();
+        let text = if pretty {
+            serde_json::to_string_pretty(&doc)
+        } else {
+            serde_json::to_string(&doc)
+        };
+        writeln!(w, "{}", text.map_err(io::Error::other)?)
+    }
+
+    /// Markdown：每条记忆一节（标题为 slice，列出时间与关键字，正文为 diary），删除标记汇总在末尾。
+    ///
+    /// 与 CSV 一样只用于阅读与归档，不能再导入。
+    pub fn write_markdown(&self, w: &mut impl Write) -> io::Result<()> {
+        let header = &self.header;
+        writeln!(w, "# {}", header.namespace)?;
+        writeln!(w)?;
+        writeln!(w, "导出时间：{}｜记忆 {} 条", header.exported_at, header.items)?;
+        for record in &self.records {
+            let ExportRecord::Item(item) = record else {
+                continue;
+            };
+            writeln!(w)?;
+            writeln!(w, "## {}", one_line(&item.slice))?;
+            writeln!(w)?;
+            writeln!(w, "- id：`{}`", item.id)?;
+            writeln!(w, "- 记录时间：{}", item.recorded_at)?;
+            if let Some(occurred_at) = &item.occurred_at {
+                writeln!(w, "- 发生时间：{occurred_at}")?;
+            }
+            if let Some(updated_at) = &item.updated_at {
+                writeln!(w, "- 更新时间：{updated_at}（修订 {}）", item.revision)?;
+            }
+            writeln!(w, "- 关键字：{}", item.keywords.join("、"))?;
+            if !item.tags.is_empty() {
+                writeln!(w, "- 标签：{}", item.tags.join("、"))?;
+            }
+            if let Some(kind) = item.kind {
+                writeln!(w, "- 类型：{}", kind.as_str())?;
+            }
+            if let Some(importance) = item.importance {
+                writeln!(w, "- 重要度：{importance}")?;
+            }
+            if item.pinned {
+                writeln!(w, "- 置顶")?;
+            }
+            if item.archived {
+                writeln!(w, "- 已归档")?;
+            }
+            if !item.diary.trim().is_empty() {
+                writeln!(w)?;
+                writeln!(w, "{}", item.diary.trim_end())?;
+            }
+        }
+
+        if header.deletes > 0 {
+            writeln!(w)?;
+            writeln!(w, "## 已删除")?;
+            writeln!(w)?;
+            for record in &self.records {
+                if let ExportRecord::Delete(t) = record {
+                    writeln!(w, "- `{}`（{}）", t.id, t.deleted_at)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// CSV（RFC 4180，带 UTF-8 BOM 以便表格软件识别中文）：每条记忆一行，多值字段以 `; ` 连接；删除标记不输出。
+    pub fn write_csv(&self, w: &mut impl Write) -> io::Result<()> {
+        w.write_all("\u{feff}".as_bytes())?;
+        write!(w, "{}\r\n", CSV_COLUMNS.join(","))?;
+        for record in &self.records {
+            let ExportRecord::Item(item) = record else {
+                continue;
+            };
+            let fields = [
+                item.id.clone(),
+                item.recorded_at.clone(),
+                item.occurred_at.clone().unwrap_or_default(),
+                item.updated_at.clone().unwrap_or_default(),
+                item.revision.to_string(),
+                item.keywords.join("; "),
+                item.tags.join("; "),
+                item.kind.map(|k| k.as_str().to_string()).unwrap_or_default(),
+                item.importance.map(|n| n.to_string()).unwrap_or_default(),
+                item.pinned.to_string(),
+                item.archived.to_string(),
+                item.slice.clone(),
+                item.diary.clone(),
+            ];
+            let row: Vec<String> = fields.iter().map(|x| csv_field(x)).collect();
+            write!(w, "{}\r\n", row.join(","))?;
+        }
+        Ok(())
+    }
+
+    /// 解析导出文档：单个 JSON 文档（含 `records` 数组）或 JSONL（首行为文档头）。
+    pub fn parse(text: &str) -> Result<Self, String> {
+        if let Ok(Value::Object(doc)) = serde_json::from_str::<Value>(text) {
+            if doc.contains_key("records") {
+                return Self::from_value(Value::Object(doc));
+            }
+        }
+
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty());
+        let Some((_, first)) = lines.next() else {
+            return Err("导出文档为空".to_string());
+        };
+        let header = parse_header(
+            serde_json::from_str(first).map_err(|e| format!("文档头不是合法 JSON：{e}"))?,
+        )?;
+
+        let mut records = Vec::new();
+        for (i, line) in lines {
+            let value = serde_json::from_str(line)
+                .map_err(|e| format!("第 {} 行不是合法 JSON：{e}", i + 1))?;
+            records.push(ExportRecord::from_value(value).map_err(|e| format!("第 {} 行：{e}", i + 1))?);
+        }
+        Ok(Self { header, records })
+    }
+
+    /// 解析单个 JSON 文档形式（`export` tool 的 `data`）。
+    pub fn from_value(mut doc: Value) -> Result<Self, String> {
+        let records = match doc.as_object_mut().and_then(|x| x.remove("records")) {
+            Some(Value::Array(records)) => records,
+            _ => return Err("导出文档缺少 records 数组".to_string()),
+        };
+        let header = parse_header(doc)?;
+
+        let records = records
+            .into_iter()
+            .enumerate()
+            .map(|(i, v)| ExportRecord::from_value(v).map_err(|e| format!("records[{i}]：{e}")))
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(Self { header, records })
+    }
+
+    fn document(&self) -> ExportDocument<'_> {
+        ExportDocument {
+            header: &self.header,
+            records: &self.records,
+        }
+    }
+}
+
+/// 校验文档头：格式标识必须匹配，格式版本不能高于当前实现支持的版本。
+fn parse_header(value: Value) -> Result<ExportHeader, String> {
+    let format = value.get("format").and_then(|x| x.as_str()).unwrap_or_default();
+    if format != EXPORT_FORMAT {
+        return Err(format!("不支持的导出格式：{format}（需为 {EXPORT_FORMAT}）"));
+    }
+    let version = value.get("format_version").and_then(|x| x.as_u64()).unwrap_or(0);
+    if version == 0 || version > EXPORT_FORMAT_VERSION as u64 {
+        return Err(format!(
+            "不支持的导出格式版本：{version}（当前支持 1~{EXPORT_FORMAT_VERSION}）"
+        ));
+    }
+
+    serde_json::from_value(value).map_err(|e| format!("文档头无效：{e}"))
+}
+
+/// 扫描 memories.jsonl，按 id 折叠出每条记忆的最终状态。
+///
+/// - 全量导出：仅输出未删除记忆的当前版本；
+/// - 增量导出：输出最后一次变更发生在起点之后的记忆（当前版本或删除标记）。
+///
+/// 结果按最后一次变更的先后排序，便于下游按顺序应用。
+pub fn collect(
+    namespace: &str,
+    files: &DataFiles,
+    since: Option<&str>,
+) -> Result<ExportDump, String> {
+    let since_parsed = since.map(ExportSince::parse).transpose()?;
+
+    let file = files.reader_from(0).map_err(|e| format!("open memories.jsonl failed: {e}"))?;
+    let mut latest: HashMap<String, (u64, ExportRecord)> = HashMap::new();
+    let mut seq: u64 = 0;
+
+    for line in BufReader::new(file).lines() {
+        let line = line.map_err(|e| format!("read memories.jsonl failed: {e}"))?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let record = match serde_json::from_str::<Tombstone>(line) {
+            Ok(t) if t.op == Tombstone::OP_DELETE => Some(ExportRecord::Delete(t)),
+            _ => serde_json::from_str::<MemoryItem>(line)
+                .ok()
+                .map(|item| ExportRecord::Item(Box::new(item))),
+        };
+        if let Some(record) = record {
+            let id = match &record {
+                ExportRecord::Item(item) => item.id.clone(),
+                ExportRecord::Delete(t) => t.id.clone(),
+            };
+            latest.insert(id, (seq, record));
+        }
+        seq += 1;
+    }
+
+    let mut selected: Vec<(u64, ExportRecord)> = latest
+        .into_values()
+        .filter(|(record_seq, record)| match since_parsed {
+            None => matches!(record, ExportRecord::Item(_)),
+            Some(ExportSince::Seq(n)) => *record_seq >= n,
+            Some(ExportSince::Ts(ts)) => change_ts(record).is_some_and(|x| x > ts),
+        })
+        .collect();
+    selected.sort_by_key(|(record_seq, _)| *record_seq);
+
+    // 导出文档自包含：blob 引用还原为内联 diary，导入到其他存储时无需 blobs/ 目录。
+    let blobs = blob::blobs_dir(files.active_path());
+    let mut records: Vec<ExportRecord> = selected.into_iter().map(|(_, r)| r).collect();
+    for record in &mut records {
+        if let ExportRecord::Item(item) = record {
+            blob::resolve(&blobs, item)?;
+        }
+    }
+    let deletes = records
+        .iter()
+        .filter(|x| matches!(x, ExportRecord::Delete(_)))
+        .count();
+
+    Ok(ExportDump {
+        header: ExportHeader {
+            format: EXPORT_FORMAT.to_string(),
+            format_version: EXPORT_FORMAT_VERSION,
+            namespace: namespace.to_string(),
+            exported_at: time::now_rfc3339_and_ts().0,
+            since: since.map(|x| x.trim().to_string()),
+            next_seq: seq,
+            items: records.len() - deletes,
+            deletes,
+        },
+        records,
+    })
+}
+
+/// 记录最后一次变更的时间：更新时间 ?? 记录时间；删除标记取删除时间。
+pub fn change_ts(record: &ExportRecord) -> Option<i64> {
+    let text = match record {
+        ExportRecord::Item(item) => item.updated_at.as_deref().unwrap_or(&item.recorded_at),
+        ExportRecord::Delete(t) => &t.deleted_at,
+    };
+    time::parse_time_to_ts_and_canonical(text, DateBoundKind::Start)
+        .ok()
+        .map(|(ts, _)| ts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use crate::memory::store::StorePaths;
+    use std::fs;
+    use std::path::Path;
+
+    fn write_files(root: &Path, content: String) -> DataFiles {
+        let paths = StorePaths::new(root, "u1/p1").expect("paths");
+        fs::create_dir_all(&paths.namespace_dir).expect("create namespace dir");
+        fs::write(&paths.memories_path, content).expect("write");
+        DataFiles::load(&paths).expect("load data files")
+    }
+
+    fn item_line(id: &str, recorded_at: &str, updated_at: Option<&str>, revision: u32) -> String {
+        let mut v = json!({
+            "id": id,
+            "namespace": "u1/p1",
+            "recorded_at": recorded_at,
+            "keywords": ["k"],
+            "slice": format!("{id}-r{revision}"),
+            "diary": "d",
+            "revision": revision
+        });
+        if let Some(u) = updated_at {
+            v["updated_at"] = json!(u);
+        }
+        v.to_string()
+    }
+
+    #[test]
+    fn delta_export_should_include_changes_after_point() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let lines = [
+            item_line("a", "2025-01-01T00:00:00Z", None, 1),
+            item_line("b", "2025-01-02T00:00:00Z", None, 1),
+            item_line("c", "2025-01-03T00:00:00Z", None, 1),
+            item_line("a", "2025-01-01T00:00:00Z", Some("2025-02-01T00:00:00Z"), 2),
+            json!({"op":"delete","id":"b","namespace":"u1/p1","deleted_at":"2025-02-02T00:00:00Z"})
+                .to_string(),
+        ];
+        let files = write_files(dir.path(), lines.join("\n") + "\n");
+
+        let full = collect("u1/p1", &files, None).expect("full");
+        let ids: Vec<String> = full.records.iter().map(|r| r.to_value()["id"].to_string()).collect();
+        assert_eq!(ids, vec!["\"c\"", "\"a\""]);
+        assert_eq!(full.header.next_seq, 5);
+
+        let by_ts = collect("u1/p1", &files, Some("2025-01-15")).expect("since ts");
+        assert_eq!(by_ts.header.items, 1);
+        assert_eq!(by_ts.header.deletes, 1);
+        assert_eq!(by_ts.records[0].to_value()["slice"], "a-r2");
+
+        let by_seq = collect("u1/p1", &files, Some("4")).expect("since seq");
+        assert_eq!(by_seq.records.len(), 1);
+        assert_eq!(by_seq.records[0].to_value()["op"], "delete");
+
+        let none = collect("u1/p1", &files, Some("5")).expect("up to date");
+        assert!(none.records.is_empty());
+    }
+
+    #[test]
+    fn parse_should_accept_both_forms_and_reject_unknown_versions() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let files = write_files(dir.path(), item_line("a", "2025-01-01T00:00:00Z", None, 1) + "\n");
+        let dump = collect("u1/p1", &files, None).expect("collect");
+
+        let mut jsonl = Vec::new();
+        dump.write_jsonl(&mut jsonl).expect("write jsonl");
+        let parsed = ExportDump::parse(&String::from_utf8(jsonl).unwrap()).expect("parse jsonl");
+        assert_eq!(parsed.header.namespace, "u1/p1");
+        assert_eq!(parsed.records[0].id(), "a");
+
+        let mut json = Vec::new();
+        dump.write_json(&mut json, true).expect("write json");
+        let parsed = ExportDump::parse(&String::from_utf8(json).unwrap()).expect("parse json");
+        assert_eq!(parsed.records.len(), 1);
+
+        let mut doc = dump.to_document();
+        doc["format_version"] = json!(EXPORT_FORMAT_VERSION + 1);
+        let err = ExportDump::from_value(doc).unwrap_err();
+        assert!(err.contains("格式版本"), "{err}");
+
+        let err = ExportDump::parse(r#"{"format":"other","format_version":1}"#).unwrap_err();
+        assert!(err.contains("不支持的导出格式"), "{err}");
+    }
+
+    #[test]
+    fn json_document_should_flatten_header_and_keep_field_order() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let files = write_files(dir.path(), item_line("a", "2025-01-01T00:00:00Z", None, 1) + "\n");
+
+        let dump = collect("u1/p1", &files, None).expect("collect");
+        let doc = dump.to_document();
+        assert_eq!(doc["format"], EXPORT_FORMAT);
+        assert_eq!(doc["format_version"], EXPORT_FORMAT_VERSION);
+        assert_eq!(doc["records"][0]["slice"], "a-r1");
+
+        let mut jsonl = Vec::new();
+        dump.write_jsonl(&mut jsonl).expect("write jsonl");
+        let jsonl = String::from_utf8(jsonl).expect("utf8");
+        let record_line = jsonl.lines().nth(1).expect("record line");
+        assert!(record_line.starts_with(r#"{"id":"a","namespace":"u1/p1""#));
+    }
+
+    #[test]
+    fn markdown_and_csv_should_render_current_items() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let mut quoted: Value = serde_json::from_str(&item_line("b", "2025-01-02T00:00:00Z", None, 1)).unwrap();
+        quoted["slice"] = json!("说了 \"你好\", 然后\n离开");
+        let lines = [item_line("a", "2025-01-01T00:00:00Z", None, 1), quoted.to_string()];
+        let files = write_files(dir.path(), lines.join("\n") + "\n");
+        let dump = collect("u1/p1", &files, None).expect("collect");
+
+        let mut md = Vec::new();
+        dump.write_markdown(&mut md).unwrap();
+        let md = String::from_utf8(md).unwrap();
+        assert!(md.starts_with("# u1/p1\n"));
+        assert!(md.contains("## a-r1\n\n- id：`a`\n- 记录时间：2025-01-01T00:00:00Z\n- 关键字：k\n\nd\n"));
+        assert!(md.contains("## 说了 \"你好\", 然后 离开\n"));
+
+        let mut csv = Vec::new();
+        dump.write_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let rows: Vec<&str> = csv.trim_start_matches('\u{feff}').split("\r\n").collect();
+        assert_eq!(rows[0], CSV_COLUMNS.join(","));
+        assert_eq!(rows[1], "a,2025-01-01T00:00:00Z,,,1,k,,,,false,false,a-r1,d");
+        assert_eq!(rows[2], "b,2025-01-02T00:00:00Z,,,1,k,,,,false,false,\"说了 \"\"你好\"\", 然后\n离开\",d");
+    }
+}
@@ -0,0 +1,168 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// 存储格式版本号（整个存储根目录共享）。
+///
+/// 变更 JSONL 记录结构或目录布局等“不兼容旧程序”的改动时递增；
+/// 旧程序遇到更高版本会直接拒绝运行，避免互相覆盖索引导致反复重建。
+pub const STORE_FORMAT_VERSION: u32 = 1;
+
+/// 存储格式标记文件名（位于存储根目录）。
+pub const STORE_FORMAT_FILE: &str = "store_format.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoreFormat {
+    pub format_version: u32,
+    #[serde(default)]
+    pub written_by: String,
+}
+
+impl StoreFormat {
+    fn current() -> Self {
+        Self {
+            format_version: STORE_FORMAT_VERSION,
+            written_by: env!("CARGO_PKG_VERSION").to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MigrateReport {
+    pub from_version: u32,
+    pub to_version: u32,
+}
+
+/// 读取存储格式标记；不存在时返回 None（新存储或尚未打标记的旧存储）。
+pub fn read_store_format(root_dir: &Path) -> Result<Option<StoreFormat>, String> {
+    let path = root_dir.join(STORE_FORMAT_FILE);
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let text = fs::read_to_string(&path)
+        .map_err(|e| format!("read {STORE_FORMAT_FILE} failed: {e}"))?;
+    let format: StoreFormat = serde_json::from_str(&text)
+        .map_err(|e| format!("parse {STORE_FORMAT_FILE} failed: {e}"))?;
+    Ok(Some(format))
+}
+
+/// 只读校验：存储格式与当前程序不一致时返回错误。
+pub fn check_store_format(root_dir: &Path) -> Result<(), String> {
+    match read_store_format(root_dir)? {
+        Some(f) => check_version(f.format_version),
+        None => Ok(()),
+    }
+}
+
+/// 校验并在缺失时写入存储格式标记。
+///
+/// 未打标记的旧存储（v0）与 v1 的数据布局一致，因此直接补写标记即可。
+pub fn ensure_store_format(root_dir: &Path) -> Result<(), String> {
+    match read_store_format(root_dir)? {
+        Some(f) => check_version(f.format_version),
+        None => write_store_format(root_dir),
+    }
+}
+
+/// 将旧版本存储原地升级到当前格式版本（目前仅需补写/更新标记；索引由调用方重建）。
+pub fn migrate_store_format(root_dir: &Path) -> Result<MigrateReport, String> {
+    let from_version = read_store_format(root_dir)?
+        .map(|f| f.format_version)
+        .unwrap_or(0);
+
+    if from_version > STORE_FORMAT_VERSION {
+        return Err(newer_version_error(from_version));
+    }
+
+    if from_version != STORE_FORMAT_VERSION {
+        write_store_format(root_dir)?;
+    }
+
+    Ok(MigrateReport {
+        from_version,
+        to_version: STORE_FORMAT_VERSION,
+    })
+}
+
+fn check_version(version: u32) -> Result<(), String> {
+    if version > STORE_FORMAT_VERSION {
+        return Err(newer_version_error(version));
+    }
+    if version < STORE_FORMAT_VERSION {
+        return Err(format!(
+            "存储格式版本 {version} 低于当前程序支持的版本 {STORE_FORMAT_VERSION}，请先执行 `memory --cli migrate` 升级存储"
+        ));
+    }
+    Ok(())
+}
+
+fn newer_version_error(version: u32) -> String {
+    format!(
+        "存储格式版本 {version} 高于当前程序支持的版本 {STORE_FORMAT_VERSION}，请升级 memory 程序后再使用该存储目录"
+    )
+}
+
+fn write_store_format(root_dir: &Path) -> Result<(), String> {
+    fs::create_dir_all(root_dir).map_err(|e| format!("create store root failed: {e}"))?;
+
+    let json = serde_json::to_string_pretty(&StoreFormat::current())
+        .map_err(|e| format!("serialize {STORE_FORMAT_FILE} failed: {e}"))?;
+
+    let path = root_dir.join(STORE_FORMAT_FILE);
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, json).map_err(|e| format!("write {STORE_FORMAT_FILE} tmp failed: {e}"))?;
+
+    // Windows rename 不允许覆盖；做 best-effort 替换。
+    if let Err(e) = fs::rename(&tmp, &path) {
+        let _ = fs::remove_file(&path);
+        fs::rename(&tmp, &path).map_err(|_| format!("replace {STORE_FORMAT_FILE} failed: {e}"))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_version(root: &Path, version: u32) {
+        let json = serde_json::json!({ "format_version": version, "written_by": "test" });
+        fs::write(root.join(STORE_FORMAT_FILE), json.to_string()).expect("write format");
+    }
+
+    #[test]
+    fn ensure_should_stamp_missing_marker() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        ensure_store_format(dir.path()).expect("ensure");
+
+        let f = read_store_format(dir.path()).expect("read").expect("marker");
+        assert_eq!(f.format_version, STORE_FORMAT_VERSION);
+    }
+
+    #[test]
+    fn newer_store_should_be_refused() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        write_version(dir.path(), STORE_FORMAT_VERSION + 1);
+
+        let err = ensure_store_format(dir.path()).expect_err("should refuse");
+        assert!(err.contains("升级 memory 程序"), "unexpected err: {err}");
+
+        let err = migrate_store_format(dir.path()).expect_err("should refuse");
+        assert!(err.contains("升级 memory 程序"), "unexpected err: {err}");
+    }
+
+    #[test]
+    fn older_store_should_require_migrate() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        write_version(dir.path(), 0);
+
+        let err = check_store_format(dir.path()).expect_err("should refuse");
+        assert!(err.contains("migrate"), "unexpected err: {err}");
+
+        let report = migrate_store_format(dir.path()).expect("migrate");
+        assert_eq!(report.from_version, 0);
+        assert_eq!(report.to_version, STORE_FORMAT_VERSION);
+        check_store_format(dir.path()).expect("migrated");
+    }
+}
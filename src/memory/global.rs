@@ -0,0 +1,118 @@
+use crate::memory::lang::Lang;
+use crate::memory::model::{truncate_one_line, RecallItemOut};
+use crate::memory::time::{self, DateBoundKind};
+use serde_json::Value;
+
+/// 跨 namespace 召回的一条结果：记忆及其所属 namespace。
+#[derive(Debug, Clone)]
+pub struct GlobalHit {
+    pub namespace: String,
+    pub item: RecallItemOut,
+}
+
+impl GlobalHit {
+    /// 输出的记忆对象，附带 `namespace` 字段。
+    pub fn to_json(&self) -> Value {
+        let mut v = serde_json::to_value(&self.item).unwrap_or_default();
+        if let Some(obj) = v.as_object_mut() {
+            obj.insert("namespace".to_string(), Value::String(self.namespace.clone()));
+        }
+        v
+    }
+
+    /// 命中的关键字权重之和（未指定权重的关键字按 1 计）。
+    fn keyword_weight(&self) -> u32 {
+        self.item
+            .matched_keywords
+            .iter()
+            .flatten()
+            .map(|kw| self.item.keyword_weights.get(kw).copied().unwrap_or(1) as u32)
+            .sum()
+    }
+
+    fn time_ts(&self) -> i64 {
+        let t = self.item.occurred_at.as_deref().unwrap_or(&self.item.recorded_at);
+        time::parse_time_to_ts_and_canonical(t, DateBoundKind::Start)
+            .map(|x| x.0)
+            .unwrap_or_default()
+    }
+}
+
+/// 合并各 namespace 的结果：语义召回按相似度；有关键字时按关键字命中权重 → 重要度 → 时间；
+/// 否则按时间倒序（同单个 namespace 的 relevance 排序）。排序稳定，同分时保持 namespace 字典序。
+pub fn merge(hits: &mut [GlobalHit], semantic: bool, by_keywords: bool) {
+    let by_time = |a: &GlobalHit, b: &GlobalHit| b.time_ts().cmp(&a.time_ts());
+    hits.sort_by(|a, b| {
+        if semantic {
+            let (x, y) = (a.item.score.unwrap_or(0.0), b.item.score.unwrap_or(0.0));
+            return y.total_cmp(&x);
+        }
+        if !by_keywords {
+            return by_time(a, b);
+        }
+        b.keyword_weight()
+            .cmp(&a.keyword_weight())
+            .then_with(|| b.item.importance.unwrap_or(0).cmp(&a.item.importance.unwrap_or(0)))
+            .then_with(|| by_time(a, b))
+    });
+}
+
+/// 文本摘要：每条带 namespace 前缀；末尾提示跳过的记录数与无法读取的 namespace 数。
+pub fn render_text(
+    hits: &[GlobalHit],
+    searched: usize,
+    has_more: bool,
+    warnings: usize,
+    failed: usize,
+    lang: Lang,
+) -> String {
+    let mut lines = Vec::with_capacity(hits.len() + 2);
+    if hits.is_empty() {
+        lines.push(lang.pick(
+            format!("在 {searched} 个 namespace 中未命中记忆。"),
+            format!("No memories matched in {searched} namespaces."),
+        ));
+    } else {
+        lines.push(lang.pick(
+            format!("在 {searched} 个 namespace 中命中 {} 条记忆：", hits.len()),
+            format!("Matched {} memories in {searched} namespaces:", hits.len()),
+        ));
+    }
+    for (i, hit) in hits.iter().enumerate() {
+        let item = &hit.item;
+        let t = item.occurred_at.as_deref().unwrap_or(&item.recorded_at);
+        let kws = if item.keywords.is_empty() {
+            String::new()
+        } else {
+            format!(" keywords={}", item.keywords.join(","))
+        };
+        lines.push(format!(
+            "{}. ({}) [{}]{} id={} slice={}",
+            i + 1,
+            hit.namespace,
+            t,
+            kws,
+            item.id,
+            truncate_one_line(&item.slice, 120)
+        ));
+    }
+    if warnings > 0 {
+        lines.push(lang.pick(
+            format!("（{warnings} 条记录无法读取，已跳过）"),
+            format!("({warnings} unreadable records skipped)"),
+        ));
+    }
+    if failed > 0 {
+        lines.push(lang.pick(
+            format!("（{failed} 个 namespace 无法读取，已跳过，原因见 warnings）"),
+            format!("({failed} unreadable namespaces skipped, see warnings)"),
+        ));
+    }
+    if has_more {
+        lines.push(lang.pick(
+            "（还有更多结果：增大 offset 翻页）".to_string(),
+            "(more results: increase offset)".to_string(),
+        ));
+    }
+    lines.join("\n")
+}
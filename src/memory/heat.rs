@@ -0,0 +1,83 @@
+use std::collections::HashMap;
+
+/// 每累计这么多次查询，对所有计数做一次减半衰减，使“热度”反映近期而非历史总量。
+const DECAY_EVERY: u64 = 256;
+
+/// 倒排常驻内存的热点关键字个数。
+pub const PINNED_KEYWORDS: usize = 64;
+
+/// 关键字查询热度（进程内，不落盘）。
+///
+/// recall 每次命中关键字查询时记录一次；用于识别热点关键字：使用只读映射的全文倒排（`MEMORY_INDEX_MMAP`）时，
+/// 热度最高的 [`PINNED_KEYWORDS`] 个关键字的分词倒排解码后常驻内存，冷关键字仍按需从映射读取。
+#[derive(Debug, Clone, Default)]
+pub struct KeywordHeat {
+    scores: HashMap<String, f64>,
+    queries: u64,
+}
+
+impl KeywordHeat {
+    pub fn record(&mut self, keywords: &[String]) {
+        if keywords.is_empty() {
+            return;
+        }
+
+        for kw in keywords {
+            *self.scores.entry(kw.clone()).or_insert(0.0) += 1.0;
+        }
+
+        self.queries += 1;
+        if self.queries.is_multiple_of(DECAY_EVERY) {
+            self.scores.retain(|_, v| {
+                *v /= 2.0;
+                *v >= 0.5
+            });
+        }
+    }
+
+    /// 热度最高的前 n 个关键字（不含热度）。
+    pub fn top_keywords(&self, n: usize) -> Vec<String> {
+        self.top(n).into_iter().map(|(kw, _)| kw).collect()
+    }
+
+    /// 热度最高的前 n 个关键字（热度降序，同分按字典序）。
+    pub fn top(&self, n: usize) -> Vec<(String, f64)> {
+        let mut out: Vec<(String, f64)> = self
+            .scores
+            .iter()
+            .map(|(k, v)| (k.clone(), *v))
+            .collect();
+        out.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        out.truncate(n);
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn top_should_rank_by_recent_frequency() {
+        let mut heat = KeywordHeat::default();
+        heat.record(&["a".to_string(), "b".to_string()]);
+        heat.record(&["b".to_string()]);
+
+        let top = heat.top(1);
+        assert_eq!(top[0].0, "b");
+        assert_eq!(top[0].1, 2.0);
+    }
+
+    #[test]
+    fn decay_should_drop_cold_keywords() {
+        let mut heat = KeywordHeat::default();
+        heat.record(&["cold".to_string()]);
+        for _ in 1..DECAY_EVERY * 2 {
+            heat.record(&["hot".to_string()]);
+        }
+
+        let top = heat.top(10);
+        assert_eq!(top.len(), 1);
+        assert_eq!(top[0].0, "hot");
+    }
+}
@@ -1,11 +1,156 @@
-use crate::memory::model::MemoryItem;
+use crate::memory::calendar;
+use crate::memory::mapped::MappedPostings;
+use crate::memory::model::{KeywordMatchMode, KeywordSort, MemoryItem, MemoryKind};
+use crate::memory::segment;
+use crate::memory::time::{self, DateBoundKind};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use sha2::{Digest, Sha256};
+use icu_collator::{Collator, CollatorOptions};
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ops::Bound;
+use std::sync::Arc;
 
 /// 索引文件版本号。
 ///
 /// 变更索引规则（例如关键字归一化策略）时递增，以触发旧索引自动重建。
-pub const INDEX_VERSION: u32 = 1;
+/// v3：新增 query 子串匹配用的 trigram 倒排。
+/// v4：新增 slice/diary 中文分词倒排。
+/// v5：trigram / 分词倒排移出 index.json，单独存放于 postings.json（按需加载）。
+/// v6：条目记录星期与小时（`dow:` / `hour` 过滤）。
+/// v7：新增标签（tags）倒排。
+/// v8：条目记录记忆类型（kind）。
+/// v9：记录取代关系（supersedes），用于 recall 默认隐藏被取代的记忆。
+/// v10：记录过期时间（expires_at），recall 跳过已过期的记忆。
+/// v11：记录置顶标记（pinned）。
+/// v12：记录归档标记（archived）。
+pub const INDEX_VERSION: u32 = 12;
+
+/// 把一行原始字节（含换行符）并入已索引区域的链式校验和：`sha256(前值 hex || 行)`，首行前值为空。
+///
+/// 只依赖数据本身的分行，与写入批次、分段轮转无关：增量索引与整体重算的结果一致。
+pub fn chain_checksum(prev: Option<&str>, line: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(prev.unwrap_or_default().as_bytes());
+    hasher.update(line);
+    hasher.finalize().iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// 子串索引的 n-gram 长度（按字符计）。
+pub const NGRAM_LEN: usize = 3;
+
+/// query 自由文本匹配的检索文本：slice / diary / source 小写后以换行拼接。
+pub fn query_haystack(item: &MemoryItem) -> String {
+    format!(
+        "{}\n{}\n{}",
+        item.slice.to_lowercase(),
+        item.diary.to_lowercase(),
+        item.source.as_ref().map(|s| s.describe()).unwrap_or_default().to_lowercase()
+    )
+}
+
+/// 标签归一化：trim + lowercase + 去重（保持首次出现的顺序）；与关键字不同，不过滤时间类字符串。
+pub fn normalize_tags(tags: &[String]) -> Vec<String> {
+    let mut out: Vec<String> = Vec::new();
+    for tag in tags {
+        let norm = tag.trim().to_lowercase();
+        if !norm.is_empty() && !out.contains(&norm) {
+            out.push(norm);
+        }
+    }
+    out
+}
+
+/// 在有序词表中查找补全候选：前缀模式按字面前缀做范围扫描，子串模式遍历全部关键字。
+pub fn complete_keywords<'a, V>(
+    vocabulary: &'a BTreeMap<String, V>,
+    text: &'a str,
+    mode: KeywordMatchMode,
+) -> Vec<(&'a str, &'a V)> {
+    match mode {
+        KeywordMatchMode::Prefix => vocabulary
+            .range::<str, _>((Bound::Included(text), Bound::Unbounded))
+            .take_while(|(kw, _)| kw.starts_with(text))
+            .map(|(kw, v)| (kw.as_str(), v))
+            .collect(),
+        KeywordMatchMode::Contains => vocabulary
+            .iter()
+            .filter(|(kw, _)| kw.contains(text))
+            .map(|(kw, v)| (kw.as_str(), v))
+            .collect(),
+    }
+}
+
+/// 按 [`KeywordSort`] 排序 (关键字, 引用条数)；拼音排序使用 ICU 中文排序规则。
+pub fn sort_keywords<T>(items: &mut [T], sort: KeywordSort, key: impl Fn(&T) -> (&str, usize)) {
+    let by_length = |a: (&str, usize), b: (&str, usize)| {
+        a.0.chars()
+            .count()
+            .cmp(&b.0.chars().count())
+            .then_with(|| b.1.cmp(&a.1))
+            .then_with(|| a.0.cmp(b.0))
+    };
+    match sort {
+        KeywordSort::Length => items.sort_by(|a, b| by_length(key(a), key(b))),
+        KeywordSort::Alpha => items.sort_by(|a, b| key(a).0.cmp(key(b).0)),
+        KeywordSort::Frequency => items.sort_by(|a, b| {
+            let (a, b) = (key(a), key(b));
+            b.1.cmp(&a.1).then_with(|| by_length(a, b))
+        }),
+        KeywordSort::Pinyin => {
+            let collator = "zh"
+                .parse()
+                .ok()
+                .and_then(|locale| Collator::try_new(&locale, CollatorOptions::new()).ok());
+            items.sort_by(|a, b| {
+                let (a, b) = (key(a).0, key(b).0);
+                collator
+                    .as_ref()
+                    .map_or(Ordering::Equal, |c| c.compare(a, b))
+                    .then_with(|| a.cmp(b))
+            });
+        }
+    }
+}
+
+/// 关键字是否为通配符模式（含 `*`，匹配任意长度的字符，包括空串）。
+pub fn is_keyword_pattern(keyword: &str) -> bool {
+    keyword.contains('*')
+}
+
+/// 关键字是否匹配模式；不含 `*` 的模式要求完全相等。
+pub fn keyword_matches_pattern(pattern: &str, keyword: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = keyword.strip_prefix(first) else {
+        return false;
+    };
+    if !is_keyword_pattern(pattern) {
+        return rest.is_empty();
+    }
+
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        return true;
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(pos) => rest = &rest[pos + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+/// 提取文本中去重后的字符 trigram（调用方负责小写化）。
+pub fn text_trigrams(text: &str) -> HashSet<String> {
+    let chars: Vec<char> = text.chars().collect();
+    chars
+        .windows(NGRAM_LEN)
+        .map(|w| w.iter().collect())
+        .collect()
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IndexItem {
@@ -18,12 +163,68 @@ pub struct IndexItem {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub importance: Option<u8>,
     pub keywords: Vec<String>,
+    /// 关键字权重（仅记录权重不为 1 的关键字），用于 recall 排序。
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub keyword_weights: BTreeMap<String, u8>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<MemoryKind>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub supersedes: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_ts: Option<i64>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pinned: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub archived: bool,
+    /// `occurred_at ?? recorded_at` 的星期（0=周一 … 6=周日）与小时（0~23，仅日期时为 None）。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weekday: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hour: Option<u8>,
+    /// 被更新（有更新版本）或删除后置为 true；倒排/时间索引中不再引用该条目。
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub superseded: bool,
 }
 
 impl IndexItem {
     pub fn time_key_ts(&self) -> i64 {
         self.occurred_at_ts.unwrap_or(self.recorded_at_ts)
     }
+
+    pub fn is_expired(&self, now_ts: i64) -> bool {
+        self.expires_ts.is_some_and(|ts| ts <= now_ts)
+    }
+
+    pub fn keyword_weight(&self, keyword: &str) -> u32 {
+        self.keyword_weights.get(keyword).map_or(1, |&w| w as u32)
+    }
+}
+
+/// 一条记忆被 recall 返回的次数与最近时间（按 id 累计，跨修订保留）。
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccessStat {
+    pub count: u64,
+    pub last_recalled_at: String,
+}
+
+impl AccessStat {
+    /// 累加另一份统计（如尚未落盘的增量）：次数相加，最近时间取较晚者。
+    pub fn merge(&mut self, other: &AccessStat) {
+        self.count += other.count;
+        if other.last_recalled_at > self.last_recalled_at {
+            self.last_recalled_at = other.last_recalled_at.clone();
+        }
+    }
+}
+
+/// postings.json：trigram / 分词倒排。以 `indexed_up_to_offset` 与 index.json 对齐，不一致时整体重建。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FullTextPostings<'a> {
+    pub indexed_up_to_offset: u64,
+    pub trigram_postings: Cow<'a, HashMap<String, Vec<u32>>>,
+    pub term_postings: Cow<'a, HashMap<String, Vec<u32>>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -32,12 +233,54 @@ pub struct IndexData {
     pub namespace: String,
     pub memories_file: String,
     pub indexed_up_to_offset: u64,
+    /// `[0, indexed_up_to_offset)` 的链式校验和（见 [`chain_checksum`]）：打开 namespace 时核对，
+    /// 数据文件被外部改写或损坏（即使长度不变）时全量重建索引。旧索引没有该字段时打开时补算。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data_checksum: Option<String>,
 
     pub items: Vec<IndexItem>,
 
-    pub keyword_postings: HashMap<String, Vec<u32>>,
+    /// 关键字 -> items 下标；按关键字有序，供通配符（`erp*`）做前缀范围扫描。
+    pub keyword_postings: BTreeMap<String, Vec<u32>>,
+    /// 标签 -> items 下标：与关键字倒排分开，只用于 recall 的标签过滤，不参与关键字召回与排序。
+    #[serde(default)]
+    pub tag_postings: BTreeMap<String, Vec<u32>>,
+    /// trigram -> items 下标（升序）。条目被更新/删除后不做即时清理，查询时按 superseded 过滤；
+    /// 重建索引时自然收敛。体积较大，不写入 index.json（见 [`FullTextPostings`]）。
+    #[serde(skip)]
+    pub trigram_postings: HashMap<String, Vec<u32>>,
+    /// 分词 -> items 下标（升序）：slice/diary 经中文分词后的词项，清理策略与存放方式同 trigram_postings。
+    #[serde(skip)]
+    pub term_postings: HashMap<String, Vec<u32>>,
+    /// 全文倒排是否可用（内存中或已映射）；打开 namespace 时只读取 index.json，首次需要时再从 postings.json 加载。
+    #[serde(skip)]
+    pub full_text_loaded: bool,
+    /// 只读映射的全文倒排（`MEMORY_INDEX_MMAP`）；存在时查询直接读映射，上面两张表为空，写入前再展开。
+    #[serde(skip)]
+    pub mapped: Option<Arc<MappedPostings>>,
+    /// 使用映射时常驻内存的热点词项倒排（已解码）：查询热度最高的关键字不必每次从映射中解码。
+    #[serde(skip)]
+    pub pinned_terms: HashMap<String, Vec<u32>>,
     pub time_sorted: Vec<u32>,
     pub time_sorted_dirty: bool,
+
+    /// id -> 当前生效版本在 items 中的下标（仅包含未删除的记忆）。
+    #[serde(default)]
+    pub heads: HashMap<String, u32>,
+    /// 被取代的记忆 id -> 取代它的记忆 id（仅统计当前生效版本）。
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub superseded_by: BTreeMap<String, Vec<String>>,
+    /// id -> recall 返回统计：单独存放在 access.json（召回不改写 index.json），旧版 index.json 中的统计只读取用于迁移。
+    /// 不计入变更代数，重建索引时保留仍存在的记忆，删除时一并移除。
+    #[serde(default, skip_serializing)]
+    pub access: BTreeMap<String, AccessStat>,
+
+    /// 变更代数：每次写入/发现外部追加/重建时递增，用于生成缓存校验值（ETag）。
+    #[serde(default)]
+    pub generation: u64,
+    /// 最近一次变更的时间（Unix 秒），用于 Last-Modified。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_modified_ts: Option<i64>,
 }
 
 impl IndexData {
@@ -47,10 +290,123 @@ impl IndexData {
             namespace: namespace.to_string(),
             memories_file: "memories.jsonl".to_string(),
             indexed_up_to_offset: 0,
+            data_checksum: None,
             items: Vec::new(),
-            keyword_postings: HashMap::new(),
+            keyword_postings: BTreeMap::new(),
+            tag_postings: BTreeMap::new(),
+            trigram_postings: HashMap::new(),
+            term_postings: HashMap::new(),
+            full_text_loaded: true,
+            mapped: None,
+            pinned_terms: HashMap::new(),
             time_sorted: Vec::new(),
             time_sorted_dirty: false,
+            heads: HashMap::new(),
+            superseded_by: BTreeMap::new(),
+            access: BTreeMap::new(),
+            generation: 0,
+            last_modified_ts: None,
+        }
+    }
+
+    /// 记录一次 recall 返回（只统计仍存在的记忆），返回计入统计的 id。
+    pub fn record_access<'a>(&mut self, ids: &'a [String], now: &str) -> Vec<&'a String> {
+        let mut recorded = Vec::with_capacity(ids.len());
+        for id in ids {
+            if self.heads.contains_key(id) {
+                let stat = self.access.entry(id.clone()).or_default();
+                stat.count += 1;
+                stat.last_recalled_at = now.to_string();
+                recorded.push(id);
+            }
+        }
+        recorded
+    }
+
+    /// 记录一次变更：递增代数并刷新最近修改时间。
+    pub fn touch(&mut self, now_ts: i64) {
+        self.generation += 1;
+        self.last_modified_ts = Some(now_ts);
+    }
+
+    /// 缓存校验值：同一代数 + 同一已索引长度即视为内容未变化。
+    pub fn etag(&self) -> String {
+        format!("\"{}-{}\"", self.generation, self.indexed_up_to_offset)
+    }
+
+    /// 全文倒排的持久化形式（postings.json）。
+    pub fn full_text_postings(&self) -> FullTextPostings<'_> {
+        FullTextPostings {
+            indexed_up_to_offset: self.indexed_up_to_offset,
+            trigram_postings: Cow::Borrowed(&self.trigram_postings),
+            term_postings: Cow::Borrowed(&self.term_postings),
+        }
+    }
+
+    /// 装入 postings.json 的内容；与当前头部覆盖的数据范围不一致时返回 false（需重建）。
+    pub fn attach_full_text(&mut self, postings: FullTextPostings<'static>) -> bool {
+        if postings.indexed_up_to_offset != self.indexed_up_to_offset {
+            return false;
+        }
+        self.trigram_postings = postings.trigram_postings.into_owned();
+        self.term_postings = postings.term_postings.into_owned();
+        self.mapped = None;
+        self.pinned_terms.clear();
+        self.full_text_loaded = true;
+        true
+    }
+
+    /// 改用只读映射的全文倒排（释放内存中的两张表）；与头部覆盖范围不一致时返回 false。
+    pub fn attach_mapped(&mut self, mapped: MappedPostings) -> bool {
+        if mapped.indexed_up_to_offset() != self.indexed_up_to_offset {
+            return false;
+        }
+        self.trigram_postings = HashMap::new();
+        self.term_postings = HashMap::new();
+        self.mapped = Some(Arc::new(mapped));
+        self.pinned_terms.clear();
+        self.full_text_loaded = true;
+        true
+    }
+
+    /// 映射的全文倒排展开到内存（追加条目前调用）；未使用映射时不做任何事。
+    pub fn materialize_full_text(&mut self) {
+        if let Some(mapped) = self.mapped.take() {
+            (self.trigram_postings, self.term_postings) = mapped.to_owned_maps();
+            self.pinned_terms.clear();
+        }
+    }
+
+    /// 使用映射时，把给定词项（热点关键字）的倒排解码后常驻内存，其余词项的常驻副本释放、仍按需从映射读取。
+    /// 未使用映射时全部倒排已在内存中，不做任何事。
+    pub fn pin_terms(&mut self, terms: &[String]) {
+        let Some(mapped) = &self.mapped else {
+            return;
+        };
+        self.pinned_terms.retain(|term, _| terms.contains(term));
+        for term in terms {
+            if !self.pinned_terms.contains_key(term) {
+                if let Some(list) = mapped.term(term) {
+                    self.pinned_terms.insert(term.clone(), list);
+                }
+            }
+        }
+    }
+
+    fn trigram_list(&self, gram: &str) -> Option<Cow<'_, [u32]>> {
+        match &self.mapped {
+            Some(mapped) => mapped.trigram(gram).map(Cow::Owned),
+            None => self.trigram_postings.get(gram).map(|x| Cow::Borrowed(x.as_slice())),
+        }
+    }
+
+    fn term_list(&self, term: &str) -> Option<Cow<'_, [u32]>> {
+        match &self.mapped {
+            Some(mapped) => match self.pinned_terms.get(term) {
+                Some(list) => Some(Cow::Borrowed(list.as_slice())),
+                None => mapped.term(term).map(Cow::Owned),
+            },
+            None => self.term_postings.get(term).map(|x| Cow::Borrowed(x.as_slice())),
         }
     }
 
@@ -63,7 +419,21 @@ impl IndexData {
         occurred_at_ts: Option<i64>,
         keywords: Vec<String>,
     ) {
+        debug_assert!(self.full_text_loaded, "写入索引前需先加载全文倒排");
+        self.materialize_full_text();
+        // 同一 id 的新记录视为更新：旧版本退出索引，仅保留最新版本。
+        self.remove_by_id(&item.id);
+
         let idx = self.items.len() as u32;
+        self.heads.insert(item.id.clone(), idx);
+        let (weekday, hour) =
+            calendar::weekday_and_hour(item.occurred_at.as_deref().unwrap_or(&item.recorded_at));
+        let tags = normalize_tags(&item.tags);
+        let expires_ts = item
+            .expires_at
+            .as_deref()
+            .and_then(|s| time::parse_time_to_ts_and_canonical(s, DateBoundKind::End).ok())
+            .map(|x| x.0);
 
         self.items.push(IndexItem {
             id: item.id.clone(),
@@ -73,16 +443,175 @@ impl IndexData {
             occurred_at_ts,
             importance: item.importance,
             keywords: keywords.clone(),
+            keyword_weights: item.keyword_weights.clone(),
+            tags: tags.clone(),
+            kind: item.kind,
+            supersedes: item.supersedes.clone(),
+            expires_ts,
+            pinned: item.pinned,
+            archived: item.archived,
+            weekday,
+            hour,
+            superseded: false,
         });
 
         for kw in keywords {
             self.keyword_postings.entry(kw).or_default().push(idx);
         }
+        for tag in tags {
+            self.tag_postings.entry(tag).or_default().push(idx);
+        }
+        for gram in text_trigrams(&query_haystack(item)) {
+            self.trigram_postings.entry(gram).or_default().push(idx);
+        }
+        for term in segment::index_terms(&format!("{}\n{}", item.slice, item.diary)) {
+            self.term_postings.entry(term).or_default().push(idx);
+        }
+
+        if let Some(target) = item.supersedes.as_ref() {
+            self.superseded_by.entry(target.clone()).or_default().push(item.id.clone());
+        }
 
         self.time_sorted.push(idx);
         self.time_sorted_dirty = true;
     }
 
+    /// 将指定 id 的当前版本移出倒排与时间索引；返回是否存在该 id。
+    pub fn remove_by_id(&mut self, id: &str) -> bool {
+        let Some(idx) = self.heads.remove(id) else {
+            return false;
+        };
+
+        let Some(entry) = self.items.get_mut(idx as usize) else {
+            return false;
+        };
+        entry.superseded = true;
+
+        for (postings, keys) in [
+            (&mut self.keyword_postings, &entry.keywords),
+            (&mut self.tag_postings, &entry.tags),
+        ] {
+            for key in keys {
+                if let Some(list) = postings.get_mut(key) {
+                    list.retain(|&x| x != idx);
+                    if list.is_empty() {
+                        postings.remove(key);
+                    }
+                }
+            }
+        }
+        if let Some(target) = entry.supersedes.as_ref() {
+            if let Some(list) = self.superseded_by.get_mut(target) {
+                list.retain(|x| x != id);
+                if list.is_empty() {
+                    self.superseded_by.remove(target);
+                }
+            }
+        }
+        self.time_sorted.retain(|&x| x != idx);
+        true
+    }
+
+    /// 已被其它记忆取代、且仍然存在的记忆在 items 中的下标。
+    pub fn superseded_heads(&self) -> HashSet<u32> {
+        self.superseded_by
+            .keys()
+            .filter_map(|id| self.heads.get(id).copied())
+            .collect()
+    }
+
+    /// 同时带有全部给定标签的条目下标；未给定标签时返回 None（不过滤）。
+    pub fn tag_candidates(&self, tags: &[String]) -> Option<HashSet<u32>> {
+        let (first, rest) = tags.split_first()?;
+        let mut out: HashSet<u32> = self
+            .tag_postings
+            .get(first)
+            .map(|x| x.iter().copied().collect())
+            .unwrap_or_default();
+        for tag in rest {
+            let list = self.tag_postings.get(tag).map(Vec::as_slice).unwrap_or_default();
+            out.retain(|idx| list.contains(idx));
+        }
+        Some(out)
+    }
+
+    /// 按 query 的 trigram 求交集得到候选下标；query 不足一个 trigram 时返回 None（无法裁剪，需逐条匹配）。
+    ///
+    /// 候选只保证“可能包含”，最终仍需对原文做子串校验。
+    pub fn query_candidates(&self, query: &str) -> Option<HashSet<u32>> {
+        let grams = text_trigrams(&query.to_lowercase());
+        if grams.is_empty() {
+            return None;
+        }
+
+        let mut lists: Vec<Cow<'_, [u32]>> = Vec::with_capacity(grams.len());
+        for gram in &grams {
+            match self.trigram_list(gram) {
+                Some(list) => lists.push(list),
+                None => return Some(HashSet::new()),
+            }
+        }
+        lists.sort_by_key(|x| x.len());
+
+        let (first, rest) = lists.split_first()?;
+        let out = first
+            .iter()
+            .copied()
+            .filter(|&idx| self.is_live(idx))
+            .filter(|idx| rest.iter().all(|list| list.binary_search(idx).is_ok()))
+            .collect();
+        Some(out)
+    }
+
+    /// 关键字匹配：普通关键字精确查找；含 `*` 的通配符先按首个 `*` 之前的字面前缀做有序范围扫描，
+    /// 再逐个校验完整模式。返回命中的 (关键字, 倒排列表)。
+    pub fn keyword_matches<'a>(&'a self, pattern: &'a str) -> Vec<(&'a str, &'a [u32])> {
+        let Some(star) = pattern.find('*') else {
+            return self
+                .keyword_postings
+                .get_key_value(pattern)
+                .map(|(kw, list)| (kw.as_str(), list.as_slice()))
+                .into_iter()
+                .collect();
+        };
+
+        let prefix = &pattern[..star];
+        self.keyword_postings
+            .range::<str, _>((Bound::Included(prefix), Bound::Unbounded))
+            .take_while(|(kw, _)| kw.starts_with(prefix))
+            .filter(|(kw, _)| keyword_matches_pattern(pattern, kw))
+            .map(|(kw, list)| (kw.as_str(), list.as_slice()))
+            .collect()
+    }
+
+    /// 分词命中：返回包含该词项的当前有效条目下标。
+    pub fn term_hits(&self, term: &str) -> impl Iterator<Item = u32> + '_ {
+        let list = self.term_list(term).unwrap_or_default();
+        let live: Vec<u32> = list.iter().copied().filter(|&idx| self.is_live(idx)).collect();
+        live.into_iter()
+    }
+
+    /// 同时包含全部词项的当前有效条目；词项为空时返回空集合。
+    pub fn all_terms_candidates(&self, terms: &[String]) -> HashSet<u32> {
+        let Some((first, rest)) = terms.split_first() else {
+            return HashSet::new();
+        };
+        let Some(lists) = rest
+            .iter()
+            .map(|t| self.term_list(t))
+            .collect::<Option<Vec<_>>>()
+        else {
+            return HashSet::new();
+        };
+        self.term_hits(first)
+            .filter(|idx| lists.iter().all(|list| list.binary_search(idx).is_ok()))
+            .collect()
+    }
+
+    fn is_live(&self, idx: u32) -> bool {
+        self.items.get(idx as usize).is_some_and(|x| !x.superseded)
+    }
+
     pub fn ensure_time_sorted(&mut self) {
         if !self.time_sorted_dirty {
             return;
@@ -0,0 +1,253 @@
+use crate::memory::backup::sha256_hex;
+use crate::memory::store::StorePaths;
+use crate::memory::wal;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+/// commit.json：一次追加写入开始前落盘的提交意图，写入完成后删除。
+///
+/// 打开 namespace 时若仍存在，说明上次写入中途中断：数据不完整时截回写入前的长度。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingCommit {
+    /// 写入前活动文件（memories.jsonl）的长度。
+    pub start: u64,
+    /// 本批记录的总字节数。
+    pub len: u64,
+    /// 本批记录的 SHA-256。
+    pub sha256: String,
+    /// 启用 WAL 时，追加本批 WAL 条目前 wal.jsonl 的长度（回滚时一并截断）。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wal_len: Option<u64>,
+}
+
+/// 打开 namespace 时的恢复结果。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Recovery {
+    /// 没有中断的写入。
+    Clean,
+    /// 上次写入已完整落盘，只是未来得及删除提交意图。
+    Committed,
+    /// 截掉了中断写入留下的字节数（整批回滚，含已完整写出的行）。
+    RolledBack(u64),
+    /// 没有提交意图，但活动文件末尾有缺少换行且无法解析的残缺记录，已截掉的字节数。
+    TruncatedTail(u64),
+    /// 没有提交意图，活动文件末尾的记录完整但缺少换行：已补上换行，记录保留。
+    TerminatedTail,
+}
+
+/// 写入锁（commit.lock）：追加写入与打开时的恢复互斥，避免另一个进程把正在进行的写入当作中断写入截掉。
+///
+/// 使用操作系统的排他文件锁，进程退出（含崩溃）时自动释放；drop 时解锁。
+pub struct WriteLock {
+    _file: File,
+}
+
+/// 取得 namespace 的写入锁（阻塞等待其它进程释放）。
+pub fn lock(paths: &StorePaths) -> Result<WriteLock, String> {
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&paths.lock_path)
+        .map_err(|e| format!("open commit.lock failed: {e}"))?;
+    file.lock().map_err(|e| format!("lock commit.lock failed: {e}"))?;
+    Ok(WriteLock { _file: file })
+}
+
+/// 写入数据前调用：落盘提交意图（同步到磁盘，保证先于数据可见）。
+pub fn begin(paths: &StorePaths, commit: &PendingCommit) -> Result<(), String> {
+    let text = serde_json::to_vec(commit).map_err(|e| format!("serialize commit.json failed: {e}"))?;
+    let mut file = File::create(&paths.journal_path).map_err(|e| format!("create commit.json failed: {e}"))?;
+    file.write_all(&text)
+        .and_then(|_| file.sync_data())
+        .map_err(|e| format!("write commit.json failed: {e}"))
+}
+
+/// 数据完整写入（或已回滚）后调用。
+pub fn finish(paths: &StorePaths) {
+    let _ = fs::remove_file(&paths.journal_path);
+}
+
+/// 打开 namespace 时调用：按提交意图检测中断的写入并整批截断；没有提交意图时截掉末尾无法解析的残缺行。
+///
+/// 持有写入锁执行：其它进程正在追加时等待其完成，不会误截它的批次。
+pub fn recover(paths: &StorePaths) -> Result<Recovery, String> {
+    let _lock = lock(paths)?;
+    let pending = match fs::read(&paths.journal_path) {
+        Ok(bytes) => serde_json::from_slice::<PendingCommit>(&bytes).ok(),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => return Err(format!("read commit.json failed: {e}")),
+    };
+    let had_journal = paths.journal_path.exists();
+    let Ok(file_len) = fs::metadata(&paths.memories_path).map(|m| m.len()) else {
+        finish(paths);
+        return Ok(Recovery::Clean);
+    };
+
+    let recovery = match pending {
+        Some(commit) if file_len <= commit.start => {
+            rollback_wal(paths, &commit);
+            Recovery::Clean
+        }
+        Some(commit) if file_len >= commit.start + commit.len && batch_matches(paths, &commit)? => Recovery::Committed,
+        Some(commit) => {
+            truncate(paths, commit.start)?;
+            rollback_wal(paths, &commit);
+            Recovery::RolledBack(file_len - commit.start)
+        }
+        // 没有提交意图（或提交意图本身写了一半，数据尚未开始写入）：只检查残缺的末尾。
+        // 能完整解析的末行只是缺少换行（如手工编辑），补上换行后保留，以免后续追加与它连在一起。
+        None => match unterminated_tail(paths, file_len)? {
+            0 => Recovery::Clean,
+            n if tail_parses(paths, file_len - n)? => {
+                OpenOptions::new()
+                    .append(true)
+                    .open(&paths.memories_path)
+                    .and_then(|mut f| f.write_all(b"\n"))
+                    .map_err(|e| format!("append memories.jsonl failed: {e}"))?;
+                Recovery::TerminatedTail
+            }
+            n => {
+                truncate(paths, file_len - n)?;
+                Recovery::TruncatedTail(n)
+            }
+        },
+    };
+    if had_journal {
+        finish(paths);
+    }
+    Ok(recovery)
+}
+
+fn batch_matches(paths: &StorePaths, commit: &PendingCommit) -> Result<bool, String> {
+    let mut file = File::open(&paths.memories_path).map_err(|e| format!("open memories.jsonl failed: {e}"))?;
+    let mut buf = vec![0u8; commit.len as usize];
+    file.seek(SeekFrom::Start(commit.start))
+        .and_then(|_| file.read_exact(&mut buf))
+        .map_err(|e| format!("read memories.jsonl failed: {e}"))?;
+    Ok(sha256_hex(&buf) == commit.sha256)
+}
+
+/// 活动文件末尾缺少换行的字节数（0 表示末尾完整）：从末尾向前分块查找最后一个换行。
+fn unterminated_tail(paths: &StorePaths, file_len: u64) -> Result<u64, String> {
+    const CHUNK: u64 = 64 * 1024;
+    let mut file = File::open(&paths.memories_path).map_err(|e| format!("open memories.jsonl failed: {e}"))?;
+    let mut end = file_len;
+    let mut buf: Vec<u8> = Vec::new();
+    while end > 0 {
+        let start = end.saturating_sub(CHUNK);
+        buf.resize((end - start) as usize, 0);
+        file.seek(SeekFrom::Start(start))
+            .and_then(|_| file.read_exact(&mut buf))
+            .map_err(|e| format!("read memories.jsonl failed: {e}"))?;
+        if let Some(i) = buf.iter().rposition(|&b| b == b'\n') {
+            return Ok(file_len - (start + i as u64 + 1));
+        }
+        end = start;
+    }
+    Ok(file_len)
+}
+
+/// 从 `start` 到文件末尾的内容能否解析为一条 JSON 记录。
+fn tail_parses(paths: &StorePaths, start: u64) -> Result<bool, String> {
+    let mut file = File::open(&paths.memories_path).map_err(|e| format!("open memories.jsonl failed: {e}"))?;
+    let mut buf = Vec::new();
+    file.seek(SeekFrom::Start(start))
+        .and_then(|_| file.read_to_end(&mut buf))
+        .map_err(|e| format!("read memories.jsonl failed: {e}"))?;
+    let line = buf.strip_suffix(b"\r").unwrap_or(&buf);
+    Ok(serde_json::from_slice::<serde_json::Value>(line).is_ok_and(|v| v.is_object()))
+}
+
+fn truncate(paths: &StorePaths, len: u64) -> Result<(), String> {
+    OpenOptions::new()
+        .write(true)
+        .open(&paths.memories_path)
+        .and_then(|f| f.set_len(len))
+        .map_err(|e| format!("truncate memories.jsonl failed: {e}"))
+}
+
+fn rollback_wal(paths: &StorePaths, commit: &PendingCommit) {
+    if let Some(len) = commit.wal_len {
+        wal::truncate(&paths.wal_path, len);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup(data: &str) -> (tempfile::TempDir, StorePaths) {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = StorePaths::new(dir.path(), "u1/p1").unwrap();
+        fs::create_dir_all(&paths.namespace_dir).unwrap();
+        fs::write(&paths.memories_path, data).unwrap();
+        (dir, paths)
+    }
+
+    fn pending(start: u64, batch: &str) -> PendingCommit {
+        PendingCommit {
+            start,
+            len: batch.len() as u64,
+            sha256: sha256_hex(batch.as_bytes()),
+            wal_len: None,
+        }
+    }
+
+    #[test]
+    fn recover_should_roll_back_torn_batch_and_keep_completed_one() {
+        let head = "{\"id\":\"a\"}\n";
+        let batch = "{\"id\":\"b\"}\n{\"id\":\"c\"}\n";
+
+        // 第一行已完整写出、第二行写了一半：整批回滚。
+        let (_dir, paths) = setup(&format!("{head}{}", &batch[..15]));
+        begin(&paths, &pending(head.len() as u64, batch)).unwrap();
+        assert_eq!(recover(&paths).unwrap(), Recovery::RolledBack(15));
+        assert_eq!(fs::read_to_string(&paths.memories_path).unwrap(), head);
+        assert!(!paths.journal_path.exists());
+
+        let (_dir, paths) = setup(&format!("{head}{batch}"));
+        begin(&paths, &pending(head.len() as u64, batch)).unwrap();
+        assert_eq!(recover(&paths).unwrap(), Recovery::Committed);
+        assert_eq!(fs::read_to_string(&paths.memories_path).unwrap(), format!("{head}{batch}"));
+        assert!(!paths.journal_path.exists());
+    }
+
+    #[test]
+    fn recover_should_truncate_unterminated_tail_without_journal() {
+        let (_dir, paths) = setup("{\"id\":\"a\"}\n{\"id\":");
+        assert_eq!(recover(&paths).unwrap(), Recovery::TruncatedTail(6));
+        assert_eq!(fs::read_to_string(&paths.memories_path).unwrap(), "{\"id\":\"a\"}\n");
+        assert_eq!(recover(&paths).unwrap(), Recovery::Clean);
+
+        // 完整但缺少换行的末行保留，只补上换行。
+        let (_dir, paths) = setup("{\"id\":\"a\"}\n{\"id\":\"b\"}");
+        assert_eq!(recover(&paths).unwrap(), Recovery::TerminatedTail);
+        assert_eq!(fs::read_to_string(&paths.memories_path).unwrap(), "{\"id\":\"a\"}\n{\"id\":\"b\"}\n");
+        assert_eq!(recover(&paths).unwrap(), Recovery::Clean);
+    }
+
+    #[test]
+    fn recover_should_wait_for_in_flight_write_instead_of_truncating_it() {
+        let head = "{\"id\":\"a\"}\n";
+        let batch = "{\"id\":\"b\"}\n";
+        let (_dir, paths) = setup(&format!("{head}{}", &batch[..5]));
+
+        // 另一个写入者持有写入锁、批次只写了一半。
+        let writer = lock(&paths).unwrap();
+        begin(&paths, &pending(head.len() as u64, batch)).unwrap();
+        let opener = {
+            let paths = paths.clone();
+            std::thread::spawn(move || recover(&paths).unwrap())
+        };
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        assert!(!opener.is_finished());
+
+        fs::write(&paths.memories_path, format!("{head}{batch}")).unwrap();
+        finish(&paths);
+        drop(writer);
+        assert_eq!(opener.join().unwrap(), Recovery::Clean);
+        assert_eq!(fs::read_to_string(&paths.memories_path).unwrap(), format!("{head}{batch}"));
+    }
+}
@@ -0,0 +1,48 @@
+/// 文本摘要（`content.text`）使用的语言；不影响存储内容与结构化的 `data`。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Lang {
+    #[default]
+    Zh,
+    En,
+}
+
+impl Lang {
+    /// 接受 `zh` / `en` 及带地区后缀的写法（如 `zh-CN`、`en_US`），大小写不敏感。
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let t = text.trim().to_lowercase();
+        match t.split(['-', '_']).next().unwrap_or_default() {
+            "zh" => Ok(Self::Zh),
+            "en" => Ok(Self::En),
+            _ => Err(format!("不支持的 lang：{}（可选 zh / en）", text.trim())),
+        }
+    }
+
+    /// `MEMORY_LANG`：默认输出语言；未设置时为中文。
+    pub fn from_env() -> Result<Self, String> {
+        match std::env::var("MEMORY_LANG") {
+            Ok(v) if !v.trim().is_empty() => {
+                Self::parse(&v).map_err(|e| format!("MEMORY_LANG 无效：{e}"))
+            }
+            _ => Ok(Self::default()),
+        }
+    }
+
+    pub fn pick<T>(self, zh: T, en: T) -> T {
+        match self {
+            Self::Zh => zh,
+            Self::En => en,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_should_accept_region_suffix() {
+        assert_eq!(Lang::parse("zh-CN").unwrap(), Lang::Zh);
+        assert_eq!(Lang::parse(" EN_us ").unwrap(), Lang::En);
+        assert!(Lang::parse("fr").unwrap_err().contains("不支持的 lang"));
+    }
+}
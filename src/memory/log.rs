@@ -0,0 +1,98 @@
+use serde_json::{json, Value};
+use std::cell::RefCell;
+
+/// 日志级别（同 MCP `logging/setLevel` / RFC 5424，由低到高）。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Notice,
+    Warning,
+    Error,
+    Critical,
+    Alert,
+    Emergency,
+}
+
+impl LogLevel {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        match text.trim().to_lowercase().as_str() {
+            "debug" => Ok(Self::Debug),
+            "info" => Ok(Self::Info),
+            "notice" => Ok(Self::Notice),
+            "warning" => Ok(Self::Warning),
+            "error" => Ok(Self::Error),
+            "critical" => Ok(Self::Critical),
+            "alert" => Ok(Self::Alert),
+            "emergency" => Ok(Self::Emergency),
+            other => Err(format!(
+                "日志级别无效：{other}（可选 debug / info / notice / warning / error / critical / alert / emergency）"
+            )),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Debug => "debug",
+            Self::Info => "info",
+            Self::Notice => "notice",
+            Self::Warning => "warning",
+            Self::Error => "error",
+            Self::Critical => "critical",
+            Self::Alert => "alert",
+            Self::Emergency => "emergency",
+        }
+    }
+}
+
+/// 一条待推送的结构化日志：`data` 至少包含 `message`，其余为事件相关字段（namespace、耗时等）。
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    pub level: LogLevel,
+    pub logger: &'static str,
+    pub data: Value,
+}
+
+/// 未取走的日志条数上限：客户端长时间不发消息时丢弃最早的条目。
+const CAPTURE_MAX: usize = 256;
+
+/// recall 耗时超过该值（毫秒）时记录 warning。
+pub const SLOW_RECALL_MS: u128 = 500;
+
+thread_local! {
+    /// MCP server 的消息循环是单线程的：未开启收集时（CLI）日志只输出到 stderr。
+    static CAPTURED: RefCell<Option<Vec<LogEntry>>> = const { RefCell::new(None) };
+}
+
+/// 开启收集：之后的日志在 `take` 时作为 `notifications/message` 推送给客户端。
+pub fn capture() {
+    CAPTURED.with(|c| {
+        c.borrow_mut().get_or_insert_with(Vec::new);
+    });
+}
+
+/// 取出已收集的日志（未开启收集时为空）。
+pub fn take() -> Vec<LogEntry> {
+    CAPTURED.with(|c| c.borrow_mut().as_mut().map(std::mem::take).unwrap_or_default())
+}
+
+/// 记录一条日志：notice 及以上同时输出到 stderr（不影响 stdout 协议通道）。
+pub fn emit(level: LogLevel, logger: &'static str, message: String, fields: Value) {
+    if level >= LogLevel::Notice {
+        eprintln!("memory: {message}");
+    }
+    CAPTURED.with(|c| {
+        let mut captured = c.borrow_mut();
+        let Some(entries) = captured.as_mut() else {
+            return;
+        };
+        let mut data = json!({ "message": message });
+        if let (Some(data), Value::Object(fields)) = (data.as_object_mut(), fields) {
+            data.extend(fields);
+        }
+        if entries.len() >= CAPTURE_MAX {
+            entries.remove(0);
+        }
+        entries.push(LogEntry { level, logger, data });
+    });
+}
@@ -0,0 +1,197 @@
+use memmap2::Mmap;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::path::Path;
+
+/// postings.bin 文件头：魔数 + 格式版本。
+const MAGIC: &[u8; 8] = b"MEMPOST\0";
+const FORMAT_VERSION: u32 = 1;
+/// 头部：魔数(8) + 版本(4) + 保留(4) + indexed_up_to_offset(8) + 两张表的条目数(4+4)。
+const HEADER_LEN: usize = 32;
+/// 表条目：key 偏移(8) + 列表偏移(8) + key 长度(4) + 列表长度(4)。
+const ENTRY_LEN: usize = 24;
+
+/// 只读映射的全文倒排（postings.bin）：多个进程映射同一文件时共享操作系统页缓存，
+/// 查询时按 key 二分查找、只解码命中的列表，不会在每个进程中各自展开整份倒排。
+///
+/// 文件只通过“写临时文件 + rename”整体替换、从不原地修改，已有映射始终指向完整的旧版本。
+#[derive(Debug)]
+pub struct MappedPostings {
+    map: Mmap,
+    indexed_up_to_offset: u64,
+    trigram_count: usize,
+    term_count: usize,
+}
+
+impl MappedPostings {
+    /// 映射并校验 postings.bin；文件缺失、格式不符或越界时返回 None（调用方回退到 postings.json）。
+    pub fn open(path: &Path) -> Option<Self> {
+        let file = File::open(path).ok()?;
+        // 安全性：文件只会被整体替换（rename），不会被截断或原地改写。
+        let map = unsafe { Mmap::map(&file) }.ok()?;
+        if map.len() < HEADER_LEN || &map[..8] != MAGIC || read_u32(&map, 8)? != FORMAT_VERSION {
+            return None;
+        }
+
+        let out = Self {
+            indexed_up_to_offset: read_u64(&map, 16)?,
+            trigram_count: read_u32(&map, 24)? as usize,
+            term_count: read_u32(&map, 28)? as usize,
+            map,
+        };
+        let entries = out.trigram_count.checked_add(out.term_count)?;
+        if HEADER_LEN.checked_add(entries.checked_mul(ENTRY_LEN)?)? > out.map.len() {
+            return None;
+        }
+        for i in 0..entries {
+            out.entry(i)?;
+        }
+        Some(out)
+    }
+
+    pub fn indexed_up_to_offset(&self) -> u64 {
+        self.indexed_up_to_offset
+    }
+
+    pub fn trigram(&self, gram: &str) -> Option<Vec<u32>> {
+        self.lookup(0, self.trigram_count, gram)
+    }
+
+    pub fn term(&self, term: &str) -> Option<Vec<u32>> {
+        self.lookup(self.trigram_count, self.term_count, term)
+    }
+
+    /// 展开为内存中的倒排（写入前调用：追加新条目需要可修改的副本）。
+    pub fn to_owned_maps(&self) -> (HashMap<String, Vec<u32>>, HashMap<String, Vec<u32>>) {
+        let table = |start: usize, count: usize| -> HashMap<String, Vec<u32>> {
+            (start..start + count)
+                .filter_map(|i| self.entry(i))
+                .map(|(key, list)| (String::from_utf8_lossy(key).into_owned(), decode_list(list)))
+                .collect()
+        };
+        (
+            table(0, self.trigram_count),
+            table(self.trigram_count, self.term_count),
+        )
+    }
+
+    fn lookup(&self, start: usize, count: usize, key: &str) -> Option<Vec<u32>> {
+        let (mut lo, mut hi) = (0, count);
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            let (k, list) = self.entry(start + mid)?;
+            match k.cmp(key.as_bytes()) {
+                std::cmp::Ordering::Less => lo = mid + 1,
+                std::cmp::Ordering::Greater => hi = mid,
+                std::cmp::Ordering::Equal => return Some(decode_list(list)),
+            }
+        }
+        None
+    }
+
+    /// 第 i 个表条目：(key 字节, 列表字节)；越界时返回 None。
+    fn entry(&self, i: usize) -> Option<(&[u8], &[u8])> {
+        let at = HEADER_LEN + i * ENTRY_LEN;
+        let key_off = usize::try_from(read_u64(&self.map, at)?).ok()?;
+        let list_off = usize::try_from(read_u64(&self.map, at + 8)?).ok()?;
+        let key_len = read_u32(&self.map, at + 16)? as usize;
+        let list_len = read_u32(&self.map, at + 20)? as usize;
+        let key = self.map.get(key_off..key_off.checked_add(key_len)?)?;
+        let list = self.map.get(list_off..list_off.checked_add(list_len.checked_mul(4)?)?)?;
+        Some((key, list))
+    }
+}
+
+/// 将全文倒排写为 postings.bin（临时文件 + rename 整体替换）。
+pub fn write(
+    path: &Path,
+    indexed_up_to_offset: u64,
+    trigram_postings: &HashMap<String, Vec<u32>>,
+    term_postings: &HashMap<String, Vec<u32>>,
+) -> Result<(), String> {
+    let mut trigrams: Vec<(&String, &Vec<u32>)> = trigram_postings.iter().collect();
+    trigrams.sort_by(|a, b| a.0.as_bytes().cmp(b.0.as_bytes()));
+    let mut terms: Vec<(&String, &Vec<u32>)> = term_postings.iter().collect();
+    terms.sort_by(|a, b| a.0.as_bytes().cmp(b.0.as_bytes()));
+
+    let entries = trigrams.len() + terms.len();
+    let mut table: Vec<u8> = Vec::with_capacity(entries * ENTRY_LEN);
+    let mut data: Vec<u8> = Vec::new();
+    let data_start = (HEADER_LEN + entries * ENTRY_LEN) as u64;
+    for (key, list) in trigrams.iter().chain(terms.iter()) {
+        let key_off = data_start + data.len() as u64;
+        data.extend_from_slice(key.as_bytes());
+        // 列表按 4 字节对齐，便于按页读取。
+        while !data.len().is_multiple_of(4) {
+            data.push(0);
+        }
+        let list_off = data_start + data.len() as u64;
+        for idx in list.iter() {
+            data.extend_from_slice(&idx.to_le_bytes());
+        }
+        table.extend_from_slice(&key_off.to_le_bytes());
+        table.extend_from_slice(&list_off.to_le_bytes());
+        table.extend_from_slice(&(key.len() as u32).to_le_bytes());
+        table.extend_from_slice(&(list.len() as u32).to_le_bytes());
+    }
+
+    let mut out: Vec<u8> = Vec::with_capacity(HEADER_LEN + table.len() + data.len());
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    out.extend_from_slice(&0u32.to_le_bytes());
+    out.extend_from_slice(&indexed_up_to_offset.to_le_bytes());
+    out.extend_from_slice(&(trigrams.len() as u32).to_le_bytes());
+    out.extend_from_slice(&(terms.len() as u32).to_le_bytes());
+    out.extend_from_slice(&table);
+    out.extend_from_slice(&data);
+
+    // 每次写入使用独立的临时文件名：其他进程可能仍映射着旧文件。
+    let tmp = path.with_extension(format!("bin.{}.tmp", uuid::Uuid::new_v4()));
+    fs::write(&tmp, &out).map_err(|e| format!("write postings.bin tmp failed: {e}"))?;
+    if let Err(e) = fs::rename(&tmp, path) {
+        let _ = fs::remove_file(&tmp);
+        return Err(format!("replace postings.bin failed: {e}"));
+    }
+    Ok(())
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn read_u64(bytes: &[u8], at: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(bytes.get(at..at + 8)?.try_into().ok()?))
+}
+
+fn decode_list(bytes: &[u8]) -> Vec<u32> {
+    bytes
+        .chunks_exact(4)
+        .map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mapped_postings_should_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("postings.bin");
+        let trigrams: HashMap<String, Vec<u32>> =
+            [("abc".to_string(), vec![0, 2]), ("数据库".to_string(), vec![1])].into();
+        let terms: HashMap<String, Vec<u32>> = [("迁移".to_string(), vec![1, 3, 4])].into();
+        write(&path, 42, &trigrams, &terms).unwrap();
+
+        let mapped = MappedPostings::open(&path).unwrap();
+        assert_eq!(mapped.indexed_up_to_offset(), 42);
+        assert_eq!(mapped.trigram("数据库"), Some(vec![1]));
+        assert_eq!(mapped.trigram("abc"), Some(vec![0, 2]));
+        assert_eq!(mapped.trigram("迁移"), None);
+        assert_eq!(mapped.term("迁移"), Some(vec![1, 3, 4]));
+        assert_eq!(mapped.to_owned_maps(), (trigrams, terms));
+
+        fs::write(&path, b"MEMPOST\0garbage").unwrap();
+        assert!(MappedPostings::open(&path).is_none());
+    }
+}
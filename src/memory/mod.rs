@@ -1,15 +1,64 @@
+mod audit;
+mod backend;
+mod backup;
+mod blob;
+mod calendar;
+mod catalog;
+mod distill;
+mod embedding;
+mod export;
+mod format;
+mod global;
+mod heat;
 mod index;
+mod journal;
+mod lang;
+mod log;
+mod mapped;
 mod model;
+mod preview;
+mod query;
+mod prompt;
+mod resource;
+mod rotation;
+mod segment;
+#[cfg(feature = "sqlite")]
+mod sqlite;
 mod store;
+mod sync;
 mod time;
+mod wal;
 
-use crate::memory::store::{NamespaceState, StorePaths};
-use serde_json::{json, Value};
-use std::collections::HashMap;
+use crate::memory::export::ExportRecord;
+use crate::memory::model::{RecallCursor, TransactionStep, IMPORTANCE_HALF_LIFE_DAYS, RECALL_MAX_LIMIT};
+use crate::memory::preview::PendingPreviews;
+use crate::memory::backend::NamespaceHandle;
+#[cfg(feature = "sqlite")]
+use crate::memory::sqlite::SqliteStore;
+use crate::memory::store::{NamespaceState, RememberRecorded, StoreOptions, StorePaths};
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::path::{Path, PathBuf};
 
-pub use crate::memory::model::{RecallArgs, RememberArgs};
+pub use crate::memory::backup::Retention;
+pub use crate::memory::distill::{stage_chat_log, StagedDistill, DEFAULT_SESSION_GAP_MINUTES};
+pub use crate::memory::export::ExportDump;
+pub use crate::memory::lang::Lang;
+pub use crate::memory::log::{capture as capture_logs, LogEntry, LogLevel};
+pub use crate::memory::model::MemoryItem;
+pub use crate::memory::prompt::{prompt_accepts, prompt_definitions};
+pub use crate::memory::resource::resource_templates;
+pub use crate::memory::store::{ConflictAction, ConflictPolicy, NamespaceSettings, StorageBackend};
+pub use crate::memory::model::{
+    check_half_life_days, default_namespace, get_namespace, memory_item_schema, namespace_or, parse_metadata, source_schema, tombstone_schema, ArchiveArgs, ForgetArgs, KeywordMatchMode, KeywordSort, KeywordsCompleteArgs, MemoryKind, MemorySource, RecallArgs, RecallFormat, RecallGlobalArgs, RecallRanking, RememberArgs, TransactionArgs,
+    UpdateArgs, KEYWORD_WEIGHT_MAX, RECALL_DEFAULT_LIMIT, RECALL_LIMIT_CEILING,
+};
+
+/// 记录一条 error 级日志（stderr，开启收集时同时推送给客户端）。
+pub fn log_error(logger: &'static str, message: &str) {
+    log::emit(LogLevel::Error, logger, message.to_string(), Value::Null);
+}
 
 /// 解析并返回存储根目录。
 pub fn resolve_root_dir() -> PathBuf {
@@ -27,17 +76,198 @@ pub fn resolve_root_dir() -> PathBuf {
     std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."))
 }
 
+/// 随 MCP 连接而定的引擎设置：`initialize` 确定的写入者身份与结果字段、`logging/setLevel` 的日志级别。
+///
+/// 网络传输的多个会话共享一个引擎：每条消息处理前换入该会话的设置，处理后取回保存。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientState {
+    writer: Option<String>,
+    resource_links: bool,
+    structured_content: bool,
+    result_data: bool,
+    log_level: LogLevel,
+}
+
 /// Memory 引擎：按 namespace 管理 JSONL + 索引，并提供 remember/recall 操作。
 pub struct MemoryEngine {
     root_dir: PathBuf,
     namespaces: HashMap<String, NamespaceState>,
+    options: StoreOptions,
+    /// 默认输出语言（`MEMORY_LANG`）与本次调用的输出语言。
+    default_lang: Lang,
+    lang: Lang,
+    /// 已通知给客户端的各 namespace 变更代数（打开 namespace 时记录基线）。
+    notified_generations: HashMap<String, u64>,
+    /// remember_preview 登记、等待 remember 携带 preview_token 确认的记忆。
+    previews: PendingPreviews,
+    /// recall 结果是否附带每条记忆的 `resource_link` 内容块（客户端支持 MCP 资源时开启）。
+    resource_links: bool,
+    /// tools/call 结果的结构化字段：`structuredContent`（MCP 2025-06-18）与兼容字段 `data` 各自是否输出。
+    structured_content: bool,
+    result_data: bool,
+    /// 推送给客户端的最低日志级别（MCP `logging/setLevel`）。
+    log_level: LogLevel,
+    /// SQLite 与内存后端（`MEMORY_BACKEND=sqlite|memory`）已打开的 namespace。
+    #[cfg(feature = "sqlite")]
+    sqlite_stores: HashMap<String, SqliteStore>,
 }
 
 impl MemoryEngine {
     pub fn new(root_dir: PathBuf) -> Self {
+        let default_lang = Lang::from_env().unwrap_or_else(|e| {
+            eprintln!("memory: {e}（已使用默认语言 zh）");
+            Lang::default()
+        });
+
         Self {
             root_dir,
             namespaces: HashMap::new(),
+            options: StoreOptions::from_env(),
+            default_lang,
+            lang: default_lang,
+            notified_generations: HashMap::new(),
+            previews: PendingPreviews::default(),
+            resource_links: false,
+            structured_content: false,
+            result_data: true,
+            log_level: LogLevel::Warning,
+            #[cfg(feature = "sqlite")]
+            sqlite_stores: HashMap::new(),
+        }
+    }
+
+    /// 改用指定的存储后端（须在打开任何 namespace 之前调用），覆盖 `MEMORY_BACKEND`。
+    pub fn with_backend(mut self, backend: StorageBackend) -> Self {
+        self.options.backend = backend;
+        self
+    }
+
+    /// 当前使用的存储后端。
+    pub fn backend(&self) -> StorageBackend {
+        self.options.backend
+    }
+
+    /// 取出自上次调用以来发生变化的 namespace 及其当前变更代数（按 namespace 排序）。
+    ///
+    /// 已从缓存移除的 namespace（删除/重命名/迁移）返回 None，表示状态未知、需要整体刷新。
+    pub fn take_changed_generations(&mut self) -> Vec<(String, Option<u64>)> {
+        let mut out: Vec<(String, Option<u64>)> = Vec::new();
+        let open = self.namespaces.iter().map(|(ns, state)| (ns, state.generation()));
+        #[cfg(feature = "sqlite")]
+        let open = open.chain(self.sqlite_stores.iter().map(|(ns, store)| (ns, store.generation())));
+        for (ns, generation) in open {
+            if self.notified_generations.insert(ns.clone(), generation) != Some(generation) {
+                out.push((ns.clone(), Some(generation)));
+            }
+        }
+
+        let namespaces = &self.namespaces;
+        #[cfg(feature = "sqlite")]
+        let sqlite_stores = &self.sqlite_stores;
+        self.notified_generations.retain(|ns, _| {
+            #[cfg(feature = "sqlite")]
+            let open = namespaces.contains_key(ns) || sqlite_stores.contains_key(ns);
+            #[cfg(not(feature = "sqlite"))]
+            let open = namespaces.contains_key(ns);
+            if !open {
+                out.push((ns.clone(), None));
+            }
+            open
+        });
+
+        out.sort();
+        out
+    }
+
+    /// 退出前补写各已打开 namespace 尚未落盘的索引变更；单个 namespace 失败不影响其余，错误汇总返回。
+    pub fn flush(&mut self) -> Result<(), String> {
+        let mut failed: Vec<String> = Vec::new();
+        let mut namespaces: Vec<(&String, &mut NamespaceState)> = self.namespaces.iter_mut().collect();
+        namespaces.sort_by(|a, b| a.0.cmp(b.0));
+        for (ns, state) in namespaces {
+            if let Err(err) = state.flush() {
+                failed.push(format!("{ns}: {err}"));
+            }
+        }
+        if failed.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("flush index failed: {}", failed.join("; ")))
+        }
+    }
+
+    /// 取出自上次调用以来不低于当前级别的结构化日志（需先以 `capture_logs` 开启收集）。
+    pub fn take_logs(&mut self) -> Vec<LogEntry> {
+        let level = self.log_level;
+        log::take().into_iter().filter(|entry| entry.level >= level).collect()
+    }
+
+    /// 设置推送给客户端的最低日志级别（默认 warning）。
+    pub fn set_log_level(&mut self, level: LogLevel) {
+        self.log_level = level;
+    }
+
+    /// recall 结果附带 `memory://` 资源链接（MCP 协议版本支持 `resource_link` 内容块时由服务端开启）。
+    pub fn set_resource_links(&mut self, enabled: bool) {
+        self.resource_links = enabled;
+    }
+
+    /// 选择 tools/call 结果携带的结构化字段（由服务端按协商的协议版本设置）；默认只输出 `data`。
+    pub fn set_result_fields(&mut self, structured_content: bool, data: bool) {
+        self.structured_content = structured_content;
+        self.result_data = data;
+    }
+
+    /// tools/call 结果是否输出 `structuredContent` 与 `data`。
+    pub fn result_fields(&self) -> (bool, bool) {
+        (self.structured_content, self.result_data)
+    }
+
+    /// 设置后续调用的文本摘要语言；None 表示恢复默认语言。
+    pub fn use_lang(&mut self, lang: Option<Lang>) {
+        self.lang = lang.unwrap_or(self.default_lang);
+    }
+
+    /// 采用 MCP 客户端声明的名称作为写入者身份；已通过 `MEMORY_WRITER` 显式配置时保持不变。
+    pub fn set_client_writer(&mut self, client_name: &str) {
+        let name = client_name.trim();
+        if name.is_empty() || StoreOptions::env_writer().is_some() {
+            return;
+        }
+
+        self.set_writer(Some(name.to_string()));
+    }
+
+    /// 当前连接的设置（见 [`ClientState`]）。
+    pub fn client_state(&self) -> ClientState {
+        ClientState {
+            writer: self.options.writer.clone(),
+            resource_links: self.resource_links,
+            structured_content: self.structured_content,
+            result_data: self.result_data,
+            log_level: self.log_level,
+        }
+    }
+
+    /// 换入另一个连接的设置（写入者身份同步到已打开的 namespace）。
+    pub fn set_client_state(&mut self, state: ClientState) {
+        if state.writer != self.options.writer {
+            self.set_writer(state.writer);
+        }
+        self.resource_links = state.resource_links;
+        self.structured_content = state.structured_content;
+        self.result_data = state.result_data;
+        self.log_level = state.log_level;
+    }
+
+    fn set_writer(&mut self, writer: Option<String>) {
+        self.options.writer = writer;
+        for state in self.namespaces.values_mut() {
+            state.set_writer(self.options.writer.clone());
+        }
+        #[cfg(feature = "sqlite")]
+        for store in self.sqlite_stores.values_mut() {
+            store.set_writer(self.options.writer.clone());
         }
     }
 
@@ -57,7 +287,10 @@ impl MemoryEngine {
             "content": [
                 {
                     "type": "text",
-                    "text": format!("当前时间：{}（本地，UTC{}）｜{}（UTC）", local_rfc3339, local_offset_text, utc_rfc3339)
+                    "text": self.lang.pick(
+                        format!("当前时间：{}（本地，UTC{}）｜{}（UTC）", local_rfc3339, local_offset_text, utc_rfc3339),
+                        format!("Current time: {} (local, UTC{}) | {} (UTC)", local_rfc3339, local_offset_text, utc_rfc3339)
+                    )
                 }
             ],
             "data": {
@@ -71,182 +304,2382 @@ impl MemoryEngine {
     }
 
     pub fn remember(&mut self, args: RememberArgs) -> Result<Value, String> {
-        let state = self.get_or_open_namespace(&args.namespace)?;
+        let lang = self.lang;
+        let mut state = self.open_namespace(&args.namespace)?;
         let namespace = state.namespace().to_string();
         let recorded = state.append_memory(args)?;
+        Ok(remembered_output(lang, &namespace, recorded))
+    }
+
+    /// 两阶段写入的第一步：按 remember 的规则归一化、脱敏，并检查疑似重复与可复用的关键字，不写入任何内容。
+    ///
+    /// 返回的 `preview_token` 交给 remember 即按预览内容原样写入（一次性，10 分钟内有效）。
+    pub fn remember_preview(&mut self, args: RememberArgs) -> Result<Value, String> {
+        let lang = self.lang;
+        let state = self.get_or_open_namespace(&args.namespace)?;
+        let namespace = state.namespace().to_string();
+        let preview = state.preview_memory(args)?;
+        let item = preview.item;
+
+        let mut text = lang.pick(
+            format!("预览（未写入，namespace={namespace}）：{}", item.slice),
+            format!("Preview (not stored, namespace={namespace}): {}", item.slice),
+        );
+        if !preview.redactions.is_empty() {
+            let total: usize = preview.redactions.iter().map(|r| r.count).sum();
+            text.push_str(&lang.pick(
+                format!("\n- 已脱敏 {total} 处敏感信息"),
+                format!("\n- Redacted {total} sensitive value(s)"),
+            ));
+        }
+        for d in &preview.duplicates {
+            let kind = match (d.same_diary, lang) {
+                (true, Lang::Zh) => "完全相同",
+                (false, Lang::Zh) => "slice 相同",
+                (true, Lang::En) => "identical",
+                (false, Lang::En) => "same slice",
+            };
+            text.push_str(&lang.pick(
+                format!("\n- 疑似重复（{kind}）：{}", d.id),
+                format!("\n- Possible duplicate ({kind}): {}", d.id),
+            ));
+        }
+        if !preview.suggested_keywords.is_empty() {
+            let list: Vec<&str> = preview.suggested_keywords.iter().map(|(k, _)| k.as_str()).collect();
+            text.push_str(&lang.pick(
+                format!("\n- 可补充的已有关键字：{}", list.join(", ")),
+                format!("\n- Existing keywords you may add: {}", list.join(", ")),
+            ));
+        }
+
+        let redactions: Vec<Value> = preview
+            .redactions
+            .iter()
+            .map(|r| json!({ "field": r.field, "kind": r.kind, "count": r.count }))
+            .collect();
+        let duplicates: Vec<Value> = preview
+            .duplicates
+            .iter()
+            .map(|d| json!({ "id": d.id, "slice": d.slice, "same_diary": d.same_diary }))
+            .collect();
+        let suggested: Vec<Value> = preview
+            .suggested_keywords
+            .iter()
+            .map(|(k, n)| json!({ "keyword": k, "count": n }))
+            .collect();
+        let mut data = json!({
+            "namespace": namespace,
+            "keywords": item.keywords,
+            "keyword_weights": item.keyword_weights,
+            "tags": item.tags,
+            "kind": item.kind,
+            "slice": item.slice,
+            "diary": item.diary,
+            "occurred_at": item.occurred_at,
+            "importance": item.importance,
+            "source": item.source,
+            "metadata": item.metadata,
+            "expires_at": item.expires_at,
+            "pinned": item.pinned,
+            "redactions": redactions,
+            "duplicates": duplicates,
+            "suggested_keywords": suggested,
+            "new_keywords": preview.new_keywords,
+        });
+
+        let token = self.previews.insert(
+            namespace,
+            RememberArgs {
+                namespace: item.namespace,
+                keywords: item.keywords,
+                keyword_weights: item.keyword_weights,
+                tags: item.tags,
+                kind: item.kind,
+                slice: item.slice,
+                diary: item.diary,
+                occurred_at: item.occurred_at,
+                importance: item.importance,
+                source: item.source,
+                metadata: item.metadata,
+                relates_to: item.relates_to,
+                supersedes: item.supersedes,
+                expires_at: item.expires_at,
+                pinned: item.pinned,
+            },
+        );
+        text.push_str(&lang.pick(
+            format!("\n确认写入：remember {{ namespace, preview_token: \"{token}\" }}"),
+            format!("\nTo store it: remember {{ namespace, preview_token: \"{token}\" }}"),
+        ));
+
+        data["preview_token"] = json!(token);
+        Ok(json!({
+            "content": [
+                { "type": "text", "text": text }
+            ],
+            "data": data
+        }))
+    }
+
+    /// 两阶段写入的第二步：按 remember_preview 登记的内容原样写入；令牌一次性有效，且须属于同一 namespace。
+    pub fn remember_previewed(&mut self, namespace: &str, token: &str) -> Result<Value, String> {
+        let lang = self.lang;
+        let namespace = self.get_or_open_namespace(namespace)?.namespace().to_string();
+        let pending = self.previews.take(&namespace, token)?;
+        let state = self.get_or_open_namespace(&namespace)?;
+        let recorded = state.append_memory(pending.args)?;
+        Ok(remembered_output(lang, &namespace, recorded))
+    }
+
+    /// 批量记录：按 namespace 分组，每组一次追加写入 + 一次索引保存；逐条返回 id 或错误。
+    ///
+    /// `items` 中的 Err 为调用方解析参数时的错误，原样出现在对应位置的结果中。
+    pub fn remember_batch(&mut self, items: Vec<Result<RememberArgs, String>>) -> Result<Value, String> {
+        let total = items.len();
+        let mut results: Vec<Value> = vec![Value::Null; total];
+        let mut groups: Vec<(String, Vec<(usize, RememberArgs)>)> = Vec::new();
+
+        for (i, item) in items.into_iter().enumerate() {
+            match item {
+                Ok(args) => {
+                    let key = args.namespace.trim().to_string();
+                    match groups.iter_mut().find(|(ns, _)| *ns == key) {
+                        Some((_, group)) => group.push((i, args)),
+                        None => groups.push((key, vec![(i, args)])),
+                    }
+                }
+                Err(e) => results[i] = json!({ "index": i, "ok": false, "error": e }),
+            }
+        }
+
+        for (key, group) in groups {
+            let (indices, batch): (Vec<usize>, Vec<RememberArgs>) = group.into_iter().unzip();
+            let outcome = self.get_or_open_namespace(&key).and_then(|state| {
+                let namespace = state.namespace().to_string();
+                state.append_memories(batch).map(|x| (namespace, x))
+            });
+
+            match outcome {
+                Ok((namespace, recorded)) => {
+                    for (i, r) in indices.into_iter().zip(recorded) {
+                        results[i] = match r {
+                            Ok(r) => json!({
+                                "index": i,
+                                "ok": true,
+                                "id": r.id,
+                                "revision": r.revision,
+                                "namespace": namespace,
+                                "recorded_at": r.recorded_at,
+                                "occurred_at": r.occurred_at,
+                                "keywords": r.keywords
+                            }),
+                            Err(e) => json!({ "index": i, "ok": false, "error": e }),
+                        };
+                    }
+                }
+                Err(e) => {
+                    for i in indices {
+                        results[i] = json!({ "index": i, "ok": false, "error": e });
+                    }
+                }
+            }
+        }
+
+        let lang = self.lang;
+        let failed: Vec<&Value> = results.iter().filter(|x| x["ok"] != json!(true)).collect();
+        let written = total - failed.len();
+        let mut text = lang.pick(
+            format!("已批量记录 {written} 条记忆（共 {total} 条"),
+            format!("Remembered {written} of {total} memories"),
+        );
+        if failed.is_empty() {
+            text.push_str(lang.pick("）。", "."));
+        } else {
+            text.push_str(&lang.pick(
+                format!("，失败 {} 条）：", failed.len()),
+                format!(" ({} failed):", failed.len()),
+            ));
+            for x in &failed {
+                let sep = lang.pick("：", ": ");
+                text.push_str(&format!("\n- items[{}]{sep}{}", x["index"], x["error"].as_str().unwrap_or_default()));
+            }
+        }
+        let failed = failed.len();
 
         Ok(json!({
             "content": [
-                { "type": "text", "text": format!("已记录记忆：{}（namespace={}）", recorded.id, namespace) }
+                { "type": "text", "text": text }
             ],
             "data": {
-                "id": recorded.id,
-                "namespace": namespace,
-                "recorded_at": recorded.recorded_at,
-                "occurred_at": recorded.occurred_at,
-                "keywords": recorded.keywords
+                "written": total - failed,
+                "failed": failed,
+                "items": results
             }
         }))
     }
 
-    pub fn recall(&mut self, args: RecallArgs) -> Result<Value, String> {
-        let state = self.get_or_open_namespace(&args.namespace)?;
+    pub fn recall(&mut self, mut args: RecallArgs) -> Result<Value, String> {
+        // namespace 为模式（如 `u1/*`）时按跨 namespace 召回处理，data.namespace 保留原模式。
+        if store::is_namespace_pattern(&args.namespace) {
+            let pattern = args.namespace.trim().to_string();
+            let mut out = self.recall_global(RecallGlobalArgs {
+                namespaces: Some(pattern.clone()),
+                recall: args,
+            })?;
+            out["data"]["namespace"] = json!(pattern);
+            return Ok(out);
+        }
+
+        let lang = self.lang;
+        let mut state = self.open_namespace(&args.namespace)?;
         let namespace = state.namespace().to_string();
-        let result = state.recall(args)?;
+        let format = args.format;
+
+        let validators = state.cache_validators()?.scoped(&args.cache_scope(&namespace));
+        if args.if_none_match.as_deref() == Some(validators.etag.as_str()) {
+            return Ok(json!({
+                "content": [
+                    { "type": "text", "text": lang.pick(
+                        format!("namespace={}：记忆未变化（etag={}）。", namespace, validators.etag),
+                        format!("namespace={}: not modified (etag={}).", namespace, validators.etag)
+                    ) }
+                ],
+                "data": {
+                    "namespace": namespace,
+                    "not_modified": true,
+                    "generation": validators.generation,
+                    "etag": validators.etag,
+                    "last_modified": validators.last_modified
+                }
+            }));
+        }
+
+        if let Some(text) = args.cursor.as_deref() {
+            let cursor = RecallCursor::decode(text)?;
+            if cursor.generation != validators.generation {
+                return Err(format!(
+                    "cursor 已失效：namespace 在分页期间发生了变化（generation {} → {}），请从第一页重新召回",
+                    cursor.generation, validators.generation
+                ));
+            }
+            args.offset = cursor.offset;
+        }
+
+        let max_bytes = args.max_bytes;
+        let started = std::time::Instant::now();
+        let mut result = state.recall(args)?;
+        let elapsed_ms = started.elapsed().as_millis();
+        if elapsed_ms > log::SLOW_RECALL_MS {
+            log::emit(
+                LogLevel::Warning,
+                "memory.recall",
+                format!("namespace={namespace} recall 耗时 {elapsed_ms} ms（命中 {} 条）", result.total),
+                json!({ "namespace": namespace, "event": "slow_recall", "elapsed_ms": elapsed_ms, "total": result.total }),
+            );
+        }
+        if let Some(max_bytes) = max_bytes {
+            result.apply_byte_budget(max_bytes);
+        }
+        let returned: Vec<String> = result.items.iter().map(|x| x.id.clone()).collect();
+        state.record_recalled(&returned);
+        if result.has_more {
+            result.next_cursor = Some(
+                RecallCursor {
+                    generation: validators.generation,
+                    offset: result.offset + result.total,
+                }
+                .encode(),
+            );
+        }
 
+        if format == RecallFormat::Compact {
+            return Ok(json!({
+                "content": [
+                    { "type": "text", "text": result.render_compact_text(lang) }
+                ]
+            }));
+        }
+
+        let mut content = vec![json!({ "type": "text", "text": result.render_text_summary(lang) })];
+        if self.resource_links {
+            content.extend(resource::recall_links(&namespace, &result.items));
+        }
         Ok(json!({
-            "content": [
-                { "type": "text", "text": result.render_text_summary() }
-            ],
+            "content": content,
             "data": {
                 "namespace": namespace,
                 "total": result.total,
-                "items": result.items
+                "offset": result.offset,
+                "next_cursor": result.next_cursor,
+                "items": result.items,
+                "warnings": result.warnings,
+                "generation": validators.generation,
+                "etag": validators.etag,
+                "last_modified": validators.last_modified
             }
         }))
     }
 
-    pub fn keywords_list(&mut self, namespace: String) -> Result<Value, String> {
-        let input = namespace.trim();
-        let state = self.get_or_open_namespace(input)?;
-        let ns = state.namespace().to_string();
-        let keywords = state.list_keywords()?;
-        let total = keywords.len();
-
-        let text = if total == 0 {
-            format!("namespace={}：暂无关键字。", ns)
-        } else {
-            format!("namespace={}：共 {} 个关键字。", ns, total)
+    /// 跨 namespace 召回：在匹配的每个 namespace 中按同样的条件召回，合并排序后返回一页，每条附带 namespace。
+    ///
+    /// 各 namespace 固定使用 relevance 排序（语义召回按相似度），以便结果可以相互比较；分页只支持 offset。
+    pub fn recall_global(&mut self, args: RecallGlobalArgs) -> Result<Value, String> {
+        self.ensure_jsonl_backend()?;
+        format::check_store_format(&self.root_dir)?;
+        let namespaces = match args.namespaces.as_deref() {
+            Some(pattern) => store::match_namespaces(&self.root_dir, pattern)?,
+            None => store::discover_namespaces(&self.root_dir),
         };
 
+        let RecallGlobalArgs { recall, .. } = args;
+        if recall.cursor.is_some() || recall.if_none_match.is_some() {
+            return Err("跨 namespace 召回不支持 cursor / if_none_match（请用 offset 翻页）".to_string());
+        }
+        let (offset, limit, semantic) = (recall.offset, recall.limit, recall.semantic);
+        let by_keywords = !recall.keywords.is_empty();
+        let mut hits: Vec<global::GlobalHit> = Vec::new();
+        // 单个 namespace 读取失败（记录损坏、格式版本不符、冻结或被锁定等）只记入 warnings，不影响其余 namespace。
+        let mut warnings: Vec<Value> = Vec::new();
+        let mut failed = 0usize;
+        let mut has_more = false;
+        for ns in &namespaces {
+            let per_namespace = RecallArgs {
+                namespace: ns.clone(),
+                limit: offset.saturating_add(limit),
+                offset: 0,
+                ranking: Some(RecallRanking::Relevance),
+                format: RecallFormat::Full,
+                max_bytes: None,
+                ..recall.clone()
+            };
+            let result = match self.open_namespace(ns) {
+                Ok(mut state) => state.recall(per_namespace),
+                Err(e) => Err(e),
+            };
+            let result = match result {
+                Ok(result) => result,
+                Err(error) => {
+                    failed += 1;
+                    warnings.push(json!({ "namespace": ns, "error": error }));
+                    continue;
+                }
+            };
+            has_more |= result.has_more;
+            warnings.extend(
+                result
+                    .warnings
+                    .into_iter()
+                    .map(|warning| json!({ "namespace": ns, "warning": warning })),
+            );
+            hits.extend(result.items.into_iter().map(|item| global::GlobalHit {
+                namespace: ns.clone(),
+                item,
+            }));
+        }
+
+        global::merge(&mut hits, semantic, by_keywords);
+        has_more |= hits.len() > offset.saturating_add(limit);
+        let page: Vec<global::GlobalHit> = hits.into_iter().skip(offset).take(limit).collect();
+        for ns in &namespaces {
+            let returned: Vec<String> = page
+                .iter()
+                .filter(|hit| &hit.namespace == ns)
+                .map(|hit| hit.item.id.clone())
+                .collect();
+            if returned.is_empty() {
+                continue;
+            }
+            // 召回统计是尽力而为的：namespace 此时无法打开也不影响已合并的结果。
+            if let Ok(mut state) = self.open_namespace(ns) {
+                state.record_recalled(&returned);
+            }
+        }
+
+        let text = global::render_text(&page, namespaces.len(), has_more, warnings.len() - failed, failed, self.lang);
         Ok(json!({
             "content": [
                 { "type": "text", "text": text }
             ],
             "data": {
-                "namespace": ns,
-                "total": total,
-                "keywords": keywords
+                "namespaces": namespaces,
+                "total": page.len(),
+                "offset": offset,
+                "has_more": has_more,
+                "items": page.iter().map(global::GlobalHit::to_json).collect::<Vec<_>>(),
+                "warnings": warnings
             }
         }))
     }
 
-    pub fn keywords_list_global(&self) -> Result<Value, String> {
-        let stats = collect_global_keyword_stats(&self.root_dir);
-        let total = stats.keywords.len();
+    /// 读取 `memory://{namespace}/{id}` 资源：返回完整记录（JSON），格式同 MCP `resources/read` 的结果。
+    pub fn read_resource(&mut self, uri: &str) -> Result<Value, String> {
+        let (namespace, id) = resource::parse_memory_uri(uri)?;
+        let mut state = self.open_namespace(&namespace)?;
+        let item = state.get(&id)?;
+        let text = serde_json::to_string(&item).map_err(|e| e.to_string())?;
+        Ok(json!({
+            "contents": [
+                { "uri": resource::memory_uri(state.namespace(), &item.id), "mimeType": resource::MIME_TYPE, "text": text }
+            ]
+        }))
+    }
 
-        let text = if total == 0 {
-            "全局：暂无关键字。".to_string()
-        } else {
-            format!("全局：共 {} 个关键字，覆盖 {} 个 namespace。", total, stats.scanned_namespaces)
+    /// 列出记忆资源（MCP `resources/list`）：每个 namespace 最近的若干条记忆，按 namespace 分页。
+    ///
+    /// `cursor` 为上一页返回的 `nextCursor`（下一页起始的 namespace 序号）。只读索引，不计入召回统计。
+    pub fn list_resources(&mut self, cursor: Option<&str>) -> Result<Value, String> {
+        self.ensure_jsonl_backend()?;
+        format::check_store_format(&self.root_dir)?;
+        let start = match cursor {
+            Some(text) => text
+                .trim()
+                .parse::<usize>()
+                .map_err(|_| format!("cursor 无效：{text}"))?,
+            None => 0,
+        };
+
+        let namespaces = store::known_namespaces(&self.root_dir);
+        let end = namespaces.len().min(start + resource::LIST_NAMESPACES_PER_PAGE);
+        let mut resources: Vec<Value> = Vec::new();
+        for ns in namespaces.get(start..end).unwrap_or_default() {
+            let state = self.get_or_open_namespace(ns)?;
+            let result = state.recall(RecallArgs {
+                namespace: ns.clone(),
+                limit: resource::LIST_PER_NAMESPACE,
+                ..Default::default()
+            })?;
+            resources.extend(result.items.iter().map(|item| resource::memory_resource(ns, item)));
+        }
+
+        let mut out = json!({ "resources": resources });
+        if end < namespaces.len() {
+            out["nextCursor"] = json!(end.to_string());
+        }
+        Ok(out)
+    }
+
+    /// 生成 MCP `prompts/get` 的结果：召回相关记忆并插入 prompt 模板（参数均为字符串）。
+    ///
+    /// 与 recall 一样计入召回统计。
+    pub fn get_prompt(&mut self, name: &str, args: &Map<String, Value>) -> Result<Value, String> {
+        let lang = self.lang;
+        let arg = |key: &str| {
+            args.get(key)
+                .and_then(|x| x.as_str())
+                .map(|x| x.trim().to_string())
+                .filter(|x| !x.is_empty())
+        };
+        let namespace = arg("namespace")
+            .or_else(default_namespace)
+            .ok_or_else(|| "缺少参数：namespace".to_string())?;
+
+        let (description, recall_args) = match name {
+            prompt::REVIEW_RECENT => {
+                let days = match arg("days") {
+                    Some(text) => text
+                        .parse::<i64>()
+                        .ok()
+                        .filter(|n| (1..=365).contains(n))
+                        .ok_or_else(|| format!("days 必须是 1..=365 的整数：{text}"))?,
+                    None => prompt::DEFAULT_REVIEW_DAYS,
+                };
+                let (_, now) = time::now_rfc3339_and_ts();
+                let args = RecallArgs {
+                    namespace: namespace.clone(),
+                    start: Some(time::ts_to_rfc3339(now - days * 86_400)),
+                    limit: prompt::PROMPT_RECALL_LIMIT,
+                    include_diary: true,
+                    ..Default::default()
+                };
+                (Some(days), args)
+            }
+            prompt::SUMMARIZE_TOPIC => {
+                let topic = arg("topic").ok_or_else(|| "缺少参数：topic".to_string())?;
+                let args = RecallArgs {
+                    namespace: namespace.clone(),
+                    query: Some(topic),
+                    limit: prompt::PROMPT_RECALL_LIMIT,
+                    include_diary: true,
+                    ..Default::default()
+                };
+                (None, args)
+            }
+            other => return Err(format!("未知的 prompt：{other}")),
+        };
+
+        let mut state = self.open_namespace(&namespace)?;
+        let namespace = state.namespace().to_string();
+        let topic = recall_args.query.clone().unwrap_or_default();
+        let result = state.recall(recall_args)?;
+        let returned: Vec<String> = result.items.iter().map(|x| x.id.clone()).collect();
+        state.record_recalled(&returned);
+
+        let (description, text) = match description {
+            Some(days) => (
+                lang.pick(
+                    format!("回顾 {namespace} 最近 {days} 天的记忆"),
+                    format!("Review the last {days} days of memories in {namespace}"),
+                ),
+                prompt::review_recent_text(lang, &namespace, days, &result.items),
+            ),
+            None => (
+                lang.pick(
+                    format!("总结 {namespace} 中关于「{topic}」的记忆"),
+                    format!("Summarize memories about \"{topic}\" in {namespace}"),
+                ),
+                prompt::summarize_topic_text(lang, &namespace, &topic, &result.items),
+            ),
+        };
+        Ok(json!({
+            "description": description,
+            "messages": [
+                { "role": "user", "content": { "type": "text", "text": text } }
+            ]
+        }))
+    }
+
+    /// 参数补全（MCP `completion/complete`）：按已输入的前缀给出全部候选值（截断由调用方决定）。
+    ///
+    /// - `namespace` / `userId` / `projectId`：已存在的 namespace（`projectId` 限定在 `context` 的 `userId` 下）；
+    /// - `topic` / `keyword`：关键字（`context` 中的 namespace 存在时取其关键字，否则取全局词表），按引用条数降序；
+    /// - `days`：常用的回顾天数。
+    ///
+    /// 只读索引，不会创建不存在的 namespace。
+    pub fn complete_argument(&mut self, argument: &str, value: &str, context: &Map<String, Value>) -> Result<Vec<String>, String> {
+        let prefix = value.trim();
+        let context_arg = |key: &str| context.get(key).and_then(|x| x.as_str()).map(str::trim).filter(|x| !x.is_empty());
+        let values: Vec<String> = match argument {
+            "namespace" | "userId" | "projectId" => {
+                self.ensure_jsonl_backend()?;
+                format::check_store_format(&self.root_dir)?;
+                let namespaces = store::known_namespaces(&self.root_dir);
+                let mut values: Vec<String> = match argument {
+                    "namespace" => namespaces,
+                    "userId" => namespaces
+                        .iter()
+                        .filter_map(|ns| ns.split_once('/').map(|(user, _)| user.to_string()))
+                        .collect(),
+                    _ => namespaces
+                        .iter()
+                        .filter_map(|ns| ns.split_once('/'))
+                        .filter(|(user, _)| context_arg("userId").is_none_or(|x| x == *user))
+                        .map(|(_, project)| project.to_string())
+                        .collect(),
+                };
+                values.retain(|x| x.starts_with(prefix));
+                values.sort();
+                values.dedup();
+                values
+            }
+            "topic" | "keyword" => {
+                self.ensure_jsonl_backend()?;
+                format::check_store_format(&self.root_dir)?;
+                let text = prefix.to_lowercase();
+                let namespace = context_arg("namespace")
+                    .map(str::to_string)
+                    .filter(|ns| store::known_namespaces(&self.root_dir).contains(ns));
+                let mut matches: Vec<(String, usize)> = match namespace {
+                    Some(ns) => self
+                        .get_or_open_namespace(&ns)?
+                        .complete_keywords(&text, KeywordMatchMode::Prefix)?,
+                    None => {
+                        let vocabulary = collect_global_keyword_vocabulary(&self.root_dir);
+                        index::complete_keywords(&vocabulary.keywords, &text, KeywordMatchMode::Prefix)
+                            .into_iter()
+                            .map(|(kw, &(_, items))| (kw.to_string(), items))
+                            .collect()
+                    }
+                };
+                matches.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+                matches.into_iter().map(|(kw, _)| kw).collect()
+            }
+            "days" => ["1", "3", "7", "14", "30", "90", "180", "365"]
+                .into_iter()
+                .filter(|x| x.starts_with(prefix))
+                .map(str::to_string)
+                .collect(),
+            _ => Vec::new(),
         };
+        Ok(values)
+    }
+
+    /// 读取一条记忆。`if_none_match` 与当前 ETag（namespace 变更代数 + 记忆 id）相同时只返回 `not_modified`。
+    pub fn get(&mut self, namespace: String, id: String, if_none_match: Option<&str>) -> Result<Value, String> {
+        let mut state = self.open_namespace(namespace.trim())?;
+        let namespace = state.namespace().to_string();
+        let validators = state.cache_validators()?.scoped(&format!("get {}", id.trim()));
+        if if_none_match == Some(validators.etag.as_str()) {
+            return Ok(json!({
+                "content": [
+                    { "type": "text", "text": self.lang.pick(
+                        format!("namespace={} id={}：记忆未变化（etag={}）。", namespace, id.trim(), validators.etag),
+                        format!("namespace={} id={}: not modified (etag={}).", namespace, id.trim(), validators.etag)
+                    ) }
+                ],
+                "data": {
+                    "namespace": namespace,
+                    "not_modified": true,
+                    "generation": validators.generation,
+                    "etag": validators.etag,
+                    "last_modified": validators.last_modified
+                }
+            }));
+        }
+        let item = state.get(&id)?;
+        let superseded_by = state.superseded_by(&item.id);
+
+        let mut text = format!(
+            "id={} revision={} time={}\nkeywords: {}\nslice: {}\ndiary: {}",
+            item.id,
+            item.revision,
+            item.occurred_at.as_deref().unwrap_or(&item.recorded_at),
+            item.keywords.join(", "),
+            item.slice,
+            item.diary
+        );
+        if !item.tags.is_empty() {
+            text.push_str(&format!("\ntags: {}", item.tags.join(", ")));
+        }
+        if let Some(kind) = item.kind {
+            text.push_str(&format!("\nkind: {}", kind.as_str()));
+        }
+        if let Some(n) = item.importance {
+            text.push_str(&format!("\nimportance: {n}"));
+        }
+        if let Some(source) = item.source.as_ref() {
+            text.push_str(&format!("\nsource: {}", source.describe()));
+        }
+        if !item.metadata.is_empty() {
+            text.push_str(&format!("\nmetadata: {}", Value::Object(item.metadata.clone())));
+        }
+        if !item.relates_to.is_empty() {
+            text.push_str(&format!("\nrelates_to: {}", item.relates_to.join(", ")));
+        }
+        if let Some(id) = item.supersedes.as_deref() {
+            text.push_str(&format!("\nsupersedes: {id}"));
+        }
+        if let Some(at) = item.expires_at.as_deref() {
+            text.push_str(&format!("\nexpires_at: {at}"));
+        }
+        if item.pinned {
+            text.push_str("\npinned: true");
+        }
+        if item.archived {
+            text.push_str("\narchived: true");
+        }
+        if !superseded_by.is_empty() {
+            text.push_str(&format!("\nsuperseded_by: {}", superseded_by.join(", ")));
+        }
+        if let Some(writer) = item.written_by.as_deref() {
+            text.push_str(&format!("\nwritten_by: {writer}"));
+        }
 
         Ok(json!({
             "content": [
                 { "type": "text", "text": text }
             ],
             "data": {
-                "total": total,
-                "scanned_namespaces": stats.scanned_namespaces,
-                "keywords": stats.keywords
+                "namespace": namespace,
+                "item": item,
+                "superseded_by": superseded_by,
+                "generation": validators.generation,
+                "etag": validators.etag,
+                "last_modified": validators.last_modified
             }
         }))
     }
 
-    fn get_or_open_namespace(&mut self, namespace: &str) -> Result<&mut NamespaceState, String> {
-        let raw = namespace.trim();
-        if raw.is_empty() {
-            return Err("namespace 不能为空".to_string());
+    pub fn history(&mut self, namespace: String, id: String) -> Result<Value, String> {
+        let lang = self.lang;
+        let mut state = self.open_namespace(namespace.trim())?;
+        let namespace = state.namespace().to_string();
+        let history = state.history(&id)?;
+
+        let id = id.trim();
+        let mut lines: Vec<String> = vec![match lang {
+            Lang::Zh => format!(
+                "id={} 共 {} 个版本{}",
+                id,
+                history.versions.len(),
+                if history.deleted { "（已删除）" } else { "" }
+            ),
+            Lang::En => format!(
+                "id={} has {} version(s){}",
+                id,
+                history.versions.len(),
+                if history.deleted { " (deleted)" } else { "" }
+            ),
+        }];
+        for item in &history.versions {
+            lines.push(format!(
+                "- revision={} updated_at={} slice: {}",
+                item.revision,
+                item.updated_at.as_deref().unwrap_or(&item.recorded_at),
+                item.slice
+            ));
         }
 
-        let paths = StorePaths::new(&self.root_dir, raw)?;
-        let key = paths.namespace.clone();
+        Ok(json!({
+            "content": [
+                { "type": "text", "text": lines.join("\n") }
+            ],
+            "data": {
+                "namespace": namespace,
+                "id": id,
+                "deleted": history.deleted,
+                "versions": history.versions
+            }
+        }))
+    }
 
-        if !self.namespaces.contains_key(&key) {
-            let state = NamespaceState::open(paths)?;
-            self.namespaces.insert(key.clone(), state);
+    /// 最少被 recall 返回的记忆（找出长期无用的记忆，可据此归档或删除）。
+    pub fn least_recalled(&mut self, namespace: String, limit: usize) -> Result<Value, String> {
+        let lang = self.lang;
+        let state = self.get_or_open_namespace(namespace.trim())?;
+        let namespace = state.namespace().to_string();
+        let result = state.least_recalled(limit)?;
+
+        let mut lines: Vec<String> = vec![lang.pick(
+            format!("namespace={} 最少被召回的 {} 条记忆：", namespace, result.total),
+            format!("namespace={}: {} least recalled memories:", namespace, result.total),
+        )];
+        let never = lang.pick("从未", "never");
+        for item in &result.items {
+            lines.push(format!(
+                "- id={} recall_count={} last_recalled_at={} slice: {}",
+                item.id,
+                item.recall_count,
+                item.last_recalled_at.as_deref().unwrap_or(never),
+                item.slice
+            ));
+        }
+        for warning in &result.warnings {
+            lines.push(format!("! {warning}"));
         }
 
-        Ok(self
-            .namespaces
-            .get_mut(&key)
-            .expect("namespace exists"))
+        Ok(json!({
+            "content": [
+                { "type": "text", "text": lines.join("\n") }
+            ],
+            "data": {
+                "namespace": namespace,
+                "items": result.items,
+                "has_more": result.has_more,
+                "warnings": result.warnings
+            }
+        }))
     }
-}
 
-#[derive(Debug, Clone)]
-struct GlobalKeywordStats {
-    scanned_namespaces: usize,
-    keywords: Vec<Value>,
-}
+    /// 归档 / 取消归档：只为状态需要变化的记忆写入新修订（同一事务）。
+    pub fn archive(&mut self, args: ArchiveArgs) -> Result<Value, String> {
+        let lang = self.lang;
+        let state = self.get_or_open_namespace(args.namespace.trim())?;
+        let namespace = state.namespace().to_string();
+        let outcomes = state.set_archived(&args.ids, args.start.as_deref(), args.end.as_deref(), args.archived)?;
 
-fn collect_global_keyword_stats(root_dir: &Path) -> GlobalKeywordStats {
-    if !root_dir.exists() {
-        return GlobalKeywordStats {
-            scanned_namespaces: 0,
-            keywords: Vec::new(),
+        let text = match (lang, args.archived) {
+            (Lang::Zh, true) => format!("namespace={} 已归档 {} 条记忆", namespace, outcomes.len()),
+            (Lang::Zh, false) => format!("namespace={} 已取消归档 {} 条记忆", namespace, outcomes.len()),
+            (Lang::En, true) => format!("namespace={}: archived {} memories", namespace, outcomes.len()),
+            (Lang::En, false) => format!("namespace={}: unarchived {} memories", namespace, outcomes.len()),
         };
+        let items: Vec<Value> = outcomes
+            .iter()
+            .map(|x| json!({ "id": x.id, "revision": x.revision }))
+            .collect();
+        Ok(json!({
+            "content": [
+                { "type": "text", "text": text }
+            ],
+            "data": {
+                "namespace": namespace,
+                "archived": args.archived,
+                "changed": items
+            }
+        }))
     }
 
-    let mut namespaces_scanned = 0usize;
-    let mut keyword_namespaces: HashMap<String, usize> = HashMap::new();
-    let mut keyword_items: HashMap<String, usize> = HashMap::new();
+    /// 置顶 / 取消置顶：状态未变化时不写入新修订。
+    pub fn set_pinned(&mut self, namespace: String, id: String, pinned: bool) -> Result<Value, String> {
+        let lang = self.lang;
+        let state = self.get_or_open_namespace(namespace.trim())?;
+        let namespace = state.namespace().to_string();
+        let revision = state.set_pinned(&id, pinned)?;
 
-    let mut stack: Vec<PathBuf> = vec![root_dir.to_path_buf()];
-    while let Some(dir) = stack.pop() {
-        let entries = match fs::read_dir(&dir) {
-            Ok(v) => v,
-            Err(_) => continue,
+        let id = id.trim();
+        let text = match (lang, pinned) {
+            (Lang::Zh, true) => format!("已置顶 id={id} revision={revision}"),
+            (Lang::Zh, false) => format!("已取消置顶 id={id} revision={revision}"),
+            (Lang::En, true) => format!("Pinned id={id} revision={revision}"),
+            (Lang::En, false) => format!("Unpinned id={id} revision={revision}"),
         };
-
-        for entry in entries.flatten() {
-            let path = entry.path();
-            if path.is_dir() {
-                stack.push(path);
-                continue;
-            }
-
-            if path.file_name().and_then(|x| x.to_str()) != Some("index.json") {
-                continue;
+        Ok(json!({
+            "content": [
+                { "type": "text", "text": text }
+            ],
+            "data": {
+                "namespace": namespace,
+                "id": id,
+                "pinned": pinned,
+                "revision": revision
             }
+        }))
+    }
 
-            let text = match fs::read_to_string(&path) {
-                Ok(v) => v,
-                Err(_) => continue,
-            };
-            let index: index::IndexData = match serde_json::from_str(&text) {
-                Ok(v) => v,
-                Err(_) => continue,
-            };
-            if index.version != index::INDEX_VERSION {
-                continue;
-            }
+    pub fn purge_expired(&mut self, namespace: String) -> Result<Value, String> {
+        let lang = self.lang;
+        let state = self.get_or_open_namespace(namespace.trim())?;
+        let namespace = state.namespace().to_string();
+        let purged = state.purge_expired()?;
 
-            namespaces_scanned += 1;
-            for (kw, postings) in index.keyword_postings {
-                let kw = kw.trim().to_lowercase();
-                if kw.is_empty() || store::is_time_like_keyword(&kw) {
-                    continue;
-                }
-                *keyword_namespaces.entry(kw.clone()).or_insert(0) += 1;
-                *keyword_items.entry(kw).or_insert(0) += postings.len();
+        let text = match lang {
+            Lang::Zh => format!("namespace={} 已清除 {} 条过期记忆", namespace, purged.len()),
+            Lang::En => format!("namespace={}: purged {} expired memories", namespace, purged.len()),
+        };
+        Ok(json!({
+            "content": [
+                { "type": "text", "text": text }
+            ],
+            "data": {
+                "namespace": namespace,
+                "purged": purged
             }
-        }
+        }))
     }
 
-    let mut out: Vec<(String, usize, usize)> = Vec::new();
-    for (kw, ns_count) in keyword_namespaces {
-        let items = keyword_items.get(&kw).copied().unwrap_or(0);
-        out.push((kw, ns_count, items));
-    }
+    /// 压缩 memories.jsonl：只保留当前版本，整体替换文件并重建索引。
+    pub fn compact(&mut self, namespace: String) -> Result<Value, String> {
+        let lang = self.lang;
+        let state = self.get_or_open_namespace(namespace.trim())?;
+        let namespace = state.namespace().to_string();
+        let report = state.compact()?;
+        let removed = report.records_before - report.records_after;
+
+        let text = match lang {
+            Lang::Zh => format!(
+                "namespace={} 已压缩：{} 行 → {} 行（移除 {} 条失效记录），{} 字节 → {} 字节",
+                namespace, report.records_before, report.records_after, removed, report.bytes_before, report.bytes_after
+            ),
+            Lang::En => format!(
+                "namespace={}: compacted {} → {} records ({} dead removed), {} → {} bytes",
+                namespace, report.records_before, report.records_after, removed, report.bytes_before, report.bytes_after
+            ),
+        };
+        Ok(json!({
+            "content": [
+                { "type": "text", "text": text }
+            ],
+            "data": compact_entry(&namespace, &report)
+        }))
+    }
+
+    /// 压缩存储根目录下的全部 namespace；已冻结的 namespace 跳过（列在 `skipped` 中）。
+    pub fn compact_all(&mut self) -> Result<Value, String> {
+        let mut entries: Vec<Value> = Vec::new();
+        let mut skipped: Vec<String> = Vec::new();
+        let (mut removed, mut bytes_before, mut bytes_after) = (0usize, 0u64, 0u64);
+        for ns in store::discover_namespaces(&self.root_dir) {
+            if store::read_frozen(&StorePaths::new(&self.root_dir, &ns)?).is_some() {
+                skipped.push(ns);
+                continue;
+            }
+            let report = self.get_or_open_namespace(&ns)?.compact()?;
+            removed += report.records_before - report.records_after;
+            bytes_before += report.bytes_before;
+            bytes_after += report.bytes_after;
+            entries.push(compact_entry(&ns, &report));
+        }
+
+        let mut text = self.lang.pick(
+            format!(
+                "已压缩 {} 个 namespace：移除 {} 条失效记录，{} 字节 → {} 字节",
+                entries.len(),
+                removed,
+                bytes_before,
+                bytes_after
+            ),
+            format!(
+                "compacted {} namespaces: {} dead records removed, {} → {} bytes",
+                entries.len(),
+                removed,
+                bytes_before,
+                bytes_after
+            ),
+        );
+        if !skipped.is_empty() {
+            text.push_str(&self.lang.pick(
+                format!("；跳过已冻结的 {}", skipped.join(", ")),
+                format!("; skipped frozen {}", skipped.join(", ")),
+            ));
+        }
+        Ok(json!({
+            "content": [
+                { "type": "text", "text": text }
+            ],
+            "data": {
+                "namespaces": entries,
+                "skipped": skipped,
+                "records_removed": removed,
+                "bytes_before": bytes_before,
+                "bytes_after": bytes_after
+            }
+        }))
+    }
+
+    /// 丢弃索引后从数据文件全量重建；namespace 为 None 时重建存储根目录下的全部 namespace。
+    pub fn reindex(&mut self, namespace: Option<&str>) -> Result<Value, String> {
+        self.ensure_jsonl_backend()?;
+        format::ensure_store_format(&self.root_dir)?;
+        let namespaces = match namespace {
+            Some(ns) => vec![StorePaths::new(&self.root_dir, ns.trim())?.namespace],
+            None => store::discover_namespaces(&self.root_dir),
+        };
+
+        let mut entries: Vec<Value> = Vec::new();
+        for ns in &namespaces {
+            // 缓存的 NamespaceState 持有旧索引：先丢弃，下次访问时重新打开。
+            self.namespaces.remove(ns);
+            let items = NamespaceState::reindex(StorePaths::new(&self.root_dir, ns)?, self.options.clone())?;
+            entries.push(json!({ "namespace": ns, "items": items }));
+        }
+
+        let text = match namespace {
+            Some(_) => self.lang.pick(
+                format!("namespace={} 已重建索引（{} 条记忆）", namespaces[0], entries[0]["items"]),
+                format!("namespace={}: index rebuilt ({} memories)", namespaces[0], entries[0]["items"]),
+            ),
+            None => self.lang.pick(
+                format!("已重建 {} 个 namespace 的索引", entries.len()),
+                format!("rebuilt the index of {} namespaces", entries.len()),
+            ),
+        };
+        Ok(json!({
+            "content": [
+                { "type": "text", "text": text }
+            ],
+            "data": { "namespaces": entries }
+        }))
+    }
+
+    /// 重要度衰减：从未被召回的旧记忆按半衰期降低 importance（写入新修订）；half_life_days 未提供时使用服务端配置。
+    pub fn maintain(&mut self, namespace: String, half_life_days: Option<f64>, dry_run: bool) -> Result<Value, String> {
+        let lang = self.lang;
+        let half_life_days = half_life_days
+            .or(self.options.importance_half_life_days)
+            .unwrap_or(IMPORTANCE_HALF_LIFE_DAYS);
+        let state = self.get_or_open_namespace(namespace.trim())?;
+        let namespace = state.namespace().to_string();
+        let decayed = state.decay_importance(half_life_days, dry_run)?;
+
+        let text = match (lang, dry_run) {
+            (Lang::Zh, false) => format!(
+                "namespace={} 已衰减 {} 条记忆的重要度（半衰期 {} 天）",
+                namespace,
+                decayed.len(),
+                half_life_days
+            ),
+            (Lang::Zh, true) => format!(
+                "namespace={} 将衰减 {} 条记忆的重要度（半衰期 {} 天，未写入）",
+                namespace,
+                decayed.len(),
+                half_life_days
+            ),
+            (Lang::En, false) => format!(
+                "namespace={}: decayed importance of {} memories (half-life {} days)",
+                namespace,
+                decayed.len(),
+                half_life_days
+            ),
+            (Lang::En, true) => format!(
+                "namespace={}: would decay importance of {} memories (half-life {} days, dry run)",
+                namespace,
+                decayed.len(),
+                half_life_days
+            ),
+        };
+        Ok(json!({
+            "content": [
+                { "type": "text", "text": text }
+            ],
+            "data": {
+                "namespace": namespace,
+                "half_life_days": half_life_days,
+                "dry_run": dry_run,
+                "decayed": decayed
+            }
+        }))
+    }
+
+    pub fn stats(&mut self, namespace: String) -> Result<Value, String> {
+        let lang = self.lang;
+        let state = self.get_or_open_namespace(namespace.trim())?;
+        let namespace = state.namespace().to_string();
+        let stats = state.stats()?;
+
+        let importance: Vec<String> = stats
+            .importance
+            .iter()
+            .map(|(k, v)| format!("{k}:{v}"))
+            .collect();
+        let none = lang.pick("无", "none");
+        let coverage = match (&stats.earliest_occurred_at, &stats.latest_occurred_at) {
+            (Some(a), Some(b)) => format!("{a} ~ {b}"),
+            _ => none.to_string(),
+        };
+        let importance = if importance.is_empty() { none.to_string() } else { importance.join(", ") };
+        let text = match lang {
+            Lang::Zh => format!(
+                "namespace={}：{} 条记忆（{} 行记录，{} 字节），{} 个关键字\n重要度分布：{}\noccurred_at 覆盖：{}（{} 条带 occurred_at）\n索引：{}（generation={}）",
+                namespace,
+                stats.items,
+                stats.records,
+                stats.total_bytes,
+                stats.keywords,
+                importance,
+                coverage,
+                stats.items_with_occurred_at,
+                if stats.index.fresh { "最新" } else { "已同步外部变更" },
+                stats.index.generation
+            ),
+            Lang::En => format!(
+                "namespace={}: {} memories ({} records, {} bytes), {} keywords\nimportance: {}\noccurred_at coverage: {} ({} with occurred_at)\nindex: {} (generation={})",
+                namespace,
+                stats.items,
+                stats.records,
+                stats.total_bytes,
+                stats.keywords,
+                importance,
+                coverage,
+                stats.items_with_occurred_at,
+                if stats.index.fresh { "up to date" } else { "synced external changes" },
+                stats.index.generation
+            ),
+        };
+
+        Ok(json!({
+            "content": [
+                { "type": "text", "text": text }
+            ],
+            "data": {
+                "namespace": namespace,
+                "stats": stats
+            }
+        }))
+    }
+
+    pub fn health(&mut self, namespace: String) -> Result<Value, String> {
+        let lang = self.lang;
+        let state = self.get_or_open_namespace(namespace.trim())?;
+        let namespace = state.namespace().to_string();
+        let report = state.checksum_report()?;
+        let healthy = report.mismatched.is_empty() && report.unreadable.is_empty();
+
+        let mut text = lang.pick(
+            format!(
+                "namespace={}：共 {} 条，校验通过 {} 条，无 checksum（旧数据）{} 条",
+                namespace, report.total, report.verified, report.unsigned
+            ),
+            format!(
+                "namespace={}: {} total, {} verified, {} without checksum (legacy)",
+                namespace, report.total, report.verified, report.unsigned
+            ),
+        );
+        if !report.mismatched.is_empty() {
+            text.push_str(lang.pick("\n校验失败（疑似外部修改/损坏）：", "\nchecksum mismatch (modified or corrupted): "));
+            text.push_str(&report.mismatched.join(", "));
+        }
+        if !report.unreadable.is_empty() {
+            text.push_str(lang.pick("\n无法读取：", "\nunreadable: "));
+            text.push_str(&report.unreadable.join(", "));
+        }
+
+        Ok(json!({
+            "content": [
+                { "type": "text", "text": text }
+            ],
+            "data": {
+                "namespace": namespace,
+                "healthy": healthy,
+                "checksums": report
+            }
+        }))
+    }
+
+    /// 检查索引与数据文件的一致性（fsck）；`repair` 时截掉残缺的末尾并重建索引。
+    pub fn verify(&mut self, namespace: String, repair: bool) -> Result<Value, String> {
+        let lang = self.lang;
+        let state = self.get_or_open_namespace(namespace.trim())?;
+        let namespace = state.namespace().to_string();
+        let report = state.verify(repair)?;
+
+        let mut text = lang.pick(
+            format!("namespace={}：检查 {} 个索引条目", namespace, report.entries),
+            format!("namespace={}: checked {} index entries", namespace, report.entries),
+        );
+        if report.is_clean() {
+            text.push_str(lang.pick("，未发现问题", ", no problems found"));
+        }
+        for bad in &report.bad_entries {
+            text.push_str(&lang.pick(
+                format!("\n无法解析：id={} offset={}（{}）", bad.id, bad.offset, bad.reason),
+                format!("\nunparsable: id={} offset={} ({})", bad.id, bad.offset, bad.reason),
+            ));
+        }
+        if report.orphaned_postings > 0 {
+            text.push_str(&lang.pick(
+                format!("\n孤立的倒排项：{} 个", report.orphaned_postings),
+                format!("\norphaned postings: {}", report.orphaned_postings),
+            ));
+        }
+        if !report.duplicate_ids.is_empty() {
+            text.push_str(lang.pick("\n重复记录（同一 id 与修订号）：", "\nduplicate records (same id and revision): "));
+            text.push_str(&report.duplicate_ids.join(", "));
+        }
+        if report.truncated_tail_bytes > 0 {
+            text.push_str(&lang.pick(
+                format!("\n数据文件末尾有 {} 字节残缺记录", report.truncated_tail_bytes),
+                format!("\ntruncated tail: {} bytes", report.truncated_tail_bytes),
+            ));
+        }
+        if !report.repaired.is_empty() {
+            text.push_str(lang.pick("\n已修复：", "\nrepaired: "));
+            text.push_str(&report.repaired.join(", "));
+        }
+
+        Ok(json!({
+            "content": [
+                { "type": "text", "text": text }
+            ],
+            "data": {
+                "namespace": namespace,
+                "clean": report.is_clean(),
+                "report": report
+            }
+        }))
+    }
+
+    pub fn transaction(&mut self, args: TransactionArgs) -> Result<Value, String> {
+        let lang = self.lang;
+        let mut state = self.open_namespace(&args.namespace)?;
+        let namespace = state.namespace().to_string();
+        let outcomes = state.apply_transaction(args.steps)?;
+
+        let steps: Vec<Value> = outcomes
+            .iter()
+            .map(|x| json!({ "op": x.op, "id": x.id, "revision": x.revision }))
+            .collect();
+
+        Ok(json!({
+            "content": [
+                { "type": "text", "text": lang.pick(
+                    format!("事务已提交：{} 个步骤（namespace={}）", steps.len(), namespace),
+                    format!("Transaction committed: {} steps (namespace={})", steps.len(), namespace)
+                ) }
+            ],
+            "data": {
+                "namespace": namespace,
+                "steps": steps
+            }
+        }))
+    }
+
+    pub fn update(&mut self, namespace: String, args: UpdateArgs) -> Result<Value, String> {
+        let lang = self.lang;
+        let mut state = self.open_namespace(namespace.trim())?;
+        let namespace = state.namespace().to_string();
+        let id = args.id.clone();
+        let outcomes = state.apply_transaction(vec![TransactionStep::Update(args)])?;
+        let revision = outcomes.first().and_then(|x| x.revision);
+
+        Ok(json!({
+            "content": [
+                { "type": "text", "text": lang.pick(
+                    format!("已更新记忆：id={}，revision={}（namespace={}）", id, revision.unwrap_or_default(), namespace),
+                    format!("Updated memory: id={}, revision={} (namespace={})", id, revision.unwrap_or_default(), namespace)
+                ) }
+            ],
+            "data": {
+                "namespace": namespace,
+                "id": id,
+                "revision": revision
+            }
+        }))
+    }
+
+    pub fn forget(&mut self, args: ForgetArgs) -> Result<Value, String> {
+        let lang = self.lang;
+        let mut state = self.open_namespace(&args.namespace)?;
+        let namespace = state.namespace().to_string();
+        state.apply_transaction(vec![TransactionStep::Forget {
+            id: args.id.clone(),
+            revision: args.revision,
+        }])?;
+
+        Ok(json!({
+            "content": [
+                { "type": "text", "text": lang.pick(
+                    format!("已删除记忆：id={}（namespace={}）", args.id, namespace),
+                    format!("Forgot memory: id={} (namespace={})", args.id, namespace)
+                ) }
+            ],
+            "data": {
+                "namespace": namespace,
+                "id": args.id,
+                "forgotten": true
+            }
+        }))
+    }
+
+    pub fn keywords_list(&mut self, namespace: String, sort: KeywordSort) -> Result<Value, String> {
+        let lang = self.lang;
+        let input = namespace.trim();
+        if store::is_namespace_pattern(input) {
+            return self.keywords_list_matching(input, sort);
+        }
+        let state = self.get_or_open_namespace(input)?;
+        let ns = state.namespace().to_string();
+        let keywords = state.list_keywords(sort)?;
+        let total = keywords.len();
+        let hot_keywords: Vec<Value> = state
+            .hot_keywords(10)
+            .into_iter()
+            .map(|(keyword, score)| json!({ "keyword": keyword, "score": score }))
+            .collect();
+
+        let text = match (total, lang) {
+            (0, Lang::Zh) => format!("namespace={}：暂无关键字。", ns),
+            (0, Lang::En) => format!("namespace={}: no keywords yet.", ns),
+            (_, Lang::Zh) => format!("namespace={}：共 {} 个关键字。", ns, total),
+            (_, Lang::En) => format!("namespace={}: {} keywords.", ns, total),
+        };
+
+        Ok(json!({
+            "content": [
+                { "type": "text", "text": text }
+            ],
+            "data": {
+                "namespace": ns,
+                "total": total,
+                "sort": sort.as_str(),
+                "keywords": keywords,
+                "hot_keywords": hot_keywords
+            }
+        }))
+    }
+
+    /// keywords_list 的模式版本：合并匹配 namespace 的词表（引用条数与热度相加）。
+    fn keywords_list_matching(&mut self, pattern: &str, sort: KeywordSort) -> Result<Value, String> {
+        self.ensure_jsonl_backend()?;
+        let namespaces = store::match_namespaces(&self.root_dir, pattern)?;
+        let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+        let mut heat: HashMap<String, f64> = HashMap::new();
+        for ns in &namespaces {
+            let state = self.get_or_open_namespace(ns)?;
+            for (kw, n) in state.keyword_counts()? {
+                *counts.entry(kw).or_default() += n;
+            }
+            for (kw, score) in state.hot_keywords(10) {
+                *heat.entry(kw).or_default() += score;
+            }
+        }
+
+        let mut keywords: Vec<(String, usize)> = counts.into_iter().collect();
+        index::sort_keywords(&mut keywords, sort, |(kw, n)| (kw.as_str(), *n));
+        let keywords: Vec<String> = keywords.into_iter().map(|(kw, _)| kw).collect();
+        let mut hot: Vec<(String, f64)> = heat.into_iter().collect();
+        hot.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        hot.truncate(10);
+        let hot_keywords: Vec<Value> = hot
+            .into_iter()
+            .map(|(keyword, score)| json!({ "keyword": keyword, "score": score }))
+            .collect();
+
+        let total = keywords.len();
+        let text = self.lang.pick(
+            format!("namespace={}（{} 个 namespace）：共 {} 个关键字。", pattern, namespaces.len(), total),
+            format!("namespace={} ({} namespaces): {} keywords.", pattern, namespaces.len(), total),
+        );
+        Ok(json!({
+            "content": [
+                { "type": "text", "text": text }
+            ],
+            "data": {
+                "namespace": pattern,
+                "namespaces": namespaces,
+                "total": total,
+                "sort": sort.as_str(),
+                "keywords": keywords,
+                "hot_keywords": hot_keywords
+            }
+        }))
+    }
+
+    /// 关键字体检：列出疑似无效的关键字及合并/删除建议（最多 `limit` 条，`data.by_reason` 为全部统计）。
+    pub fn keywords_audit(&mut self, namespace: String, limit: usize) -> Result<Value, String> {
+        let lang = self.lang;
+        let state = self.get_or_open_namespace(namespace.trim())?;
+        let ns = state.namespace().to_string();
+        let (vocabulary, mut findings) = state.audit_keywords()?;
+
+        let mut by_reason: BTreeMap<&str, usize> = BTreeMap::new();
+        let mut by_action: BTreeMap<&str, usize> = BTreeMap::new();
+        for finding in &findings {
+            for reason in &finding.reasons {
+                *by_reason.entry(reason.as_str()).or_default() += 1;
+            }
+            let action = match finding.suggestion {
+                audit::Suggestion::Merge { .. } => "merge",
+                audit::Suggestion::Delete => "delete",
+                audit::Suggestion::Review => "review",
+            };
+            *by_action.entry(action).or_default() += 1;
+        }
+        let flagged = findings.len();
+        let truncated = flagged > limit;
+        findings.truncate(limit);
+
+        let count = |action: &str| by_action.get(action).copied().unwrap_or(0);
+        let mut text = lang.pick(
+            format!(
+                "namespace={}：共 {} 个关键字，可疑 {} 个（建议合并 {}、删除 {}、待确认 {}）。",
+                ns,
+                vocabulary,
+                flagged,
+                count("merge"),
+                count("delete"),
+                count("review")
+            ),
+            format!(
+                "namespace={}: {} keywords, {} flagged ({} to merge, {} to delete, {} to review).",
+                ns,
+                vocabulary,
+                flagged,
+                count("merge"),
+                count("delete"),
+                count("review")
+            ),
+        );
+        for finding in findings.iter().filter(|f| f.suggestion != audit::Suggestion::Review) {
+            let reasons: Vec<&str> = finding.reasons.iter().map(|r| r.as_str()).collect();
+            let action = match &finding.suggestion {
+                audit::Suggestion::Merge { into } => lang.pick(format!("合并到 {into}"), format!("merge into {into}")),
+                _ => lang.pick("删除", "delete").to_string(),
+            };
+            text.push_str(&format!(
+                "\n- {} ({}) → {} [{}]",
+                finding.keyword,
+                finding.count,
+                action,
+                reasons.join(",")
+            ));
+        }
+
+        Ok(json!({
+            "content": [
+                { "type": "text", "text": text }
+            ],
+            "data": {
+                "namespace": ns,
+                "vocabulary": vocabulary,
+                "flagged": flagged,
+                "by_reason": by_reason,
+                "by_action": by_action,
+                "truncated": truncated,
+                "findings": findings
+            }
+        }))
+    }
+
+    pub fn keywords_list_global(&self, sort: KeywordSort) -> Result<Value, String> {
+        self.ensure_jsonl_backend()?;
+        format::check_store_format(&self.root_dir)?;
+        let stats = collect_global_keyword_stats(&self.root_dir, sort);
+        let total = stats.keywords.len();
+
+        let text = match (total, self.lang) {
+            (0, Lang::Zh) => "全局：暂无关键字。".to_string(),
+            (0, Lang::En) => "Global: no keywords yet.".to_string(),
+            (_, Lang::Zh) => format!("全局：共 {} 个关键字，覆盖 {} 个 namespace。", total, stats.scanned_namespaces),
+            (_, Lang::En) => format!("Global: {} keywords across {} namespaces.", total, stats.scanned_namespaces),
+        };
+
+        Ok(json!({
+            "content": [
+                { "type": "text", "text": text }
+            ],
+            "data": {
+                "total": total,
+                "sort": sort.as_str(),
+                "scanned_namespaces": stats.scanned_namespaces,
+                "keywords": stats.keywords
+            }
+        }))
+    }
+
+    /// 关键字补全：按前缀/子串在 namespace（或全局）的有序词表中查找已有关键字，附带引用条数。
+    ///
+    /// 排序：以输入开头的关键字优先，其次引用条数多、长度短、字典序。
+    pub fn keywords_complete(&mut self, args: KeywordsCompleteArgs) -> Result<Value, String> {
+        let lang = self.lang;
+        let text = args.text.trim().to_lowercase();
+        if text.is_empty() {
+            return Err("text 不能为空".to_string());
+        }
+
+        // (关键字, 记忆条数, namespace 数（仅全局）)
+        let (namespace, mut matches) =
+            match args.namespace.as_deref() {
+                Some(ns) => {
+                    let state = self.get_or_open_namespace(ns)?;
+                    let matches = state
+                        .complete_keywords(&text, args.mode)?
+                        .into_iter()
+                        .map(|(kw, items)| (kw, items, None))
+                        .collect::<Vec<_>>();
+                    (Some(state.namespace().to_string()), matches)
+                }
+                None => {
+                    format::check_store_format(&self.root_dir)?;
+                    let vocabulary = collect_global_keyword_vocabulary(&self.root_dir);
+                    let matches = index::complete_keywords(&vocabulary.keywords, &text, args.mode)
+                        .into_iter()
+                        .map(|(kw, &(namespaces, items))| (kw.to_string(), items, Some(namespaces)))
+                        .collect();
+                    (None, matches)
+                }
+            };
+
+        matches.sort_by(|a, b| {
+            b.0.starts_with(&text)
+                .cmp(&a.0.starts_with(&text))
+                .then_with(|| b.1.cmp(&a.1))
+                .then_with(|| a.0.chars().count().cmp(&b.0.chars().count()))
+                .then_with(|| a.0.cmp(&b.0))
+        });
+        let total = matches.len();
+        matches.truncate(args.limit);
+
+        let scope = match (&namespace, lang) {
+            (Some(ns), _) => format!("namespace={ns}"),
+            (None, Lang::Zh) => "全局".to_string(),
+            (None, Lang::En) => "Global".to_string(),
+        };
+        let listed = matches
+            .iter()
+            .map(|(kw, items, _)| format!("{kw}({items})"))
+            .collect::<Vec<_>>()
+            .join(lang.pick("、", ", "));
+        let text_out = match (total, lang) {
+            (0, Lang::Zh) => format!("{scope}：没有匹配“{text}”的关键字。"),
+            (0, Lang::En) => format!("{scope}: no keywords match \"{text}\"."),
+            (_, Lang::Zh) => format!("{scope}：匹配“{text}”的关键字共 {total} 个：{listed}"),
+            (_, Lang::En) => format!("{scope}: {total} keywords match \"{text}\": {listed}"),
+        };
+
+        let keywords: Vec<Value> = matches
+            .into_iter()
+            .map(|(keyword, items, namespaces)| match namespaces {
+                Some(namespaces) => json!({ "keyword": keyword, "items": items, "namespaces": namespaces }),
+                None => json!({ "keyword": keyword, "items": items }),
+            })
+            .collect();
+
+        let mut data = json!({
+            "text": text,
+            "match": args.mode.as_str(),
+            "total": total,
+            "keywords": keywords
+        });
+        if let Some(ns) = namespace {
+            data["namespace"] = json!(ns);
+        }
+
+        Ok(json!({
+            "content": [
+                { "type": "text", "text": text_out }
+            ],
+            "data": data
+        }))
+    }
+
+    /// 将旧版本存储原地升级到当前格式，并重建所有 namespace 的索引。
+    /// 导出 namespace（只读，直接扫描 memories.jsonl）；`since` 为序号或时间点时只导出其后的变更。
+    pub fn export_dump(&self, namespace: &str, since: Option<&str>) -> Result<ExportDump, String> {
+        self.ensure_jsonl_backend()?;
+        format::check_store_format(&self.root_dir)?;
+        let paths = StorePaths::new(&self.root_dir, namespace.trim())?;
+        if !paths.memories_path.is_file() {
+            return Err(format!("namespace 不存在：{}", paths.namespace));
+        }
+
+        export::collect(&paths.namespace, &rotation::DataFiles::load(&paths)?, since)
+    }
+
+    /// 把 namespace（未指定时为全部）的数据与索引复制到 `out_dir` 下的时间戳快照，并按保留策略清理旧快照。
+    pub fn backup(&self, namespace: Option<&str>, out_dir: &Path, retention: Retention) -> Result<Value, String> {
+        self.ensure_jsonl_backend()?;
+        format::check_store_format(&self.root_dir)?;
+        let namespaces = match namespace {
+            Some(ns) => {
+                let paths = StorePaths::new(&self.root_dir, ns.trim())?;
+                if !paths.memories_path.is_file() {
+                    return Err(format!("namespace 不存在：{}", paths.namespace));
+                }
+                vec![paths.namespace]
+            }
+            None => store::discover_namespaces(&self.root_dir),
+        };
+
+        let report = backup::create_snapshot(&self.root_dir, &namespaces, out_dir, retention)?;
+        let mut text = self.lang.pick(
+            format!(
+                "已备份 {} 个 namespace（{} 个文件，{} 字节）到 {}",
+                report.namespaces.len(),
+                report.files,
+                report.bytes,
+                report.snapshot.display()
+            ),
+            format!(
+                "Backed up {} namespaces ({} files, {} bytes) to {}",
+                report.namespaces.len(),
+                report.files,
+                report.bytes,
+                report.snapshot.display()
+            ),
+        );
+        for path in &report.removed {
+            text.push_str(&self.lang.pick(
+                format!("\n已按保留策略删除：{}", path.display()),
+                format!("\nremoved by retention: {}", path.display()),
+            ));
+        }
+
+        Ok(json!({
+            "content": [
+                { "type": "text", "text": text }
+            ],
+            "data": report
+        }))
+    }
+
+    pub fn export(&self, namespace: &str, since: Option<&str>) -> Result<Value, String> {
+        let dump = self.export_dump(namespace, since)?;
+        let header = &dump.header;
+        let text = self.lang.pick(
+            format!(
+                "已导出 namespace={}：{} 条记忆，{} 条删除标记（{} v{}，next_seq={}）",
+                header.namespace, header.items, header.deletes, header.format, header.format_version, header.next_seq
+            ),
+            format!(
+                "Exported namespace={}: {} memories, {} tombstones ({} v{}, next_seq={})",
+                header.namespace, header.items, header.deletes, header.format, header.format_version, header.next_seq
+            ),
+        );
+
+        Ok(json!({
+            "content": [
+                { "type": "text", "text": text }
+            ],
+            "data": dump.to_document()
+        }))
+    }
+
+    /// 导入导出文档；未指定目标 namespace 时使用文档头中的 namespace。
+    ///
+    /// id 冲突（已存在且内容不同）交由 `resolve(现有, 导入)` 决定；按策略处理时传入 `ConflictPolicy::decide`。
+    /// `dry_run=true` 时只返回报告，不写入也不创建目标 namespace。
+    pub fn import(
+        &mut self,
+        namespace: Option<String>,
+        dump: ExportDump,
+        resolve: &mut dyn FnMut(&MemoryItem, &MemoryItem) -> ConflictAction,
+        dry_run: bool,
+    ) -> Result<Value, String> {
+        format::check_store_format(&self.root_dir)?;
+        let lang = self.lang;
+        let target = namespace
+            .map(|x| x.trim().to_string())
+            .filter(|x| !x.is_empty())
+            .unwrap_or_else(|| dump.header.namespace.clone());
+
+        // 预演不打开目标 namespace（打开会补写格式标记、恢复日志、落盘索引），只读取现有记忆计算报告。
+        let (namespace, report) = if dry_run {
+            self.ensure_jsonl_backend()?;
+            let paths = StorePaths::new(&self.root_dir, &target)?;
+            let existing = live_items(&paths)?;
+            let report = store::preview_import(&paths.namespace, dump.records, &existing, resolve)?;
+            (paths.namespace, report)
+        } else {
+            let state = self.get_or_open_namespace(&target)?;
+            let namespace = state.namespace().to_string();
+            (namespace, state.import_records(dump.records, resolve, dry_run)?)
+        };
+
+        let mut text = if dry_run {
+            lang.pick(
+                format!(
+                    "预演导入到 namespace={}（来源 {}，未写入）：将新增 {} 条，删除 {} 条，重复跳过 {} 条",
+                    namespace, dump.header.namespace, report.imported, report.deleted, report.duplicates
+                ),
+                format!(
+                    "Dry run into namespace={} (from {}, nothing written): would add {}, delete {}, skip {} duplicates",
+                    namespace, dump.header.namespace, report.imported, report.deleted, report.duplicates
+                ),
+            )
+        } else {
+            lang.pick(
+                format!(
+                    "已导入到 namespace={}（来源 {}）：新增 {} 条，删除 {} 条，重复跳过 {} 条",
+                    namespace, dump.header.namespace, report.imported, report.deleted, report.duplicates
+                ),
+                format!(
+                    "Imported into namespace={} (from {}): {} added, {} deleted, {} duplicates skipped",
+                    namespace, dump.header.namespace, report.imported, report.deleted, report.duplicates
+                ),
+            )
+        };
+        if !report.conflicts.is_empty() {
+            text.push_str(&lang.pick(
+                format!("\nid 冲突 {} 条：", report.conflicts.len()),
+                format!("\n{} id conflicts:", report.conflicts.len()),
+            ));
+            for decision in &report.conflicts {
+                let new_id = decision.new_id.as_deref().unwrap_or_default();
+                let action = match decision.action {
+                    ConflictAction::KeepExisting => lang.pick("保留现有版本", "kept existing").to_string(),
+                    ConflictAction::Replace => lang
+                        .pick("采用导入版本（追加为新修订）", "replaced with imported (new revision)")
+                        .to_string(),
+                    ConflictAction::Duplicate => {
+                        lang.pick(format!("另存副本 id={new_id}"), format!("duplicated as id={new_id}"))
+                    }
+                };
+                text.push_str(&format!("\n- {}{}{}", decision.id, lang.pick("：", ": "), action));
+            }
+        }
+        if !report.invalid.is_empty() {
+            text.push_str(&lang.pick(
+                format!("\nchecksum 校验失败 {} 条（未导入）：", report.invalid.len()),
+                format!("\n{} checksum mismatches (not imported): ", report.invalid.len()),
+            ));
+            text.push_str(&report.invalid.join(", "));
+        }
+
+        Ok(json!({
+            "content": [
+                { "type": "text", "text": text }
+            ],
+            "data": {
+                "namespace": namespace,
+                "source_namespace": dump.header.namespace,
+                "format_version": dump.header.format_version,
+                "dry_run": dry_run,
+                "report": report
+            }
+        }))
+    }
+
+    /// 列出全部 namespace（经根目录 catalog.json）；`refresh=true` 时遍历目录重建 catalog。
+    pub fn namespaces_list(&self, refresh: bool) -> Result<Value, String> {
+        self.ensure_jsonl_backend()?;
+        format::check_store_format(&self.root_dir)?;
+
+        let namespaces = store::list_namespaces(&self.root_dir, refresh)?;
+
+        let lang = self.lang;
+        let lines: Vec<String> = namespaces
+            .iter()
+            .map(|x| {
+                let last_write = x.last_write.as_deref().unwrap_or(lang.pick("未知", "unknown"));
+                match lang {
+                    Lang::Zh => format!(
+                        "- {}（{} 条，最近写入 {}{}）",
+                        x.namespace,
+                        x.items,
+                        last_write,
+                        if x.frozen { "，已冻结" } else { "" }
+                    ),
+                    Lang::En => format!(
+                        "- {} ({} memories, last write {}{})",
+                        x.namespace,
+                        x.items,
+                        last_write,
+                        if x.frozen { ", frozen" } else { "" }
+                    ),
+                }
+            })
+            .collect();
+        let text = match (lines.is_empty(), lang) {
+            (true, Lang::Zh) => "当前没有任何 namespace。".to_string(),
+            (true, Lang::En) => "No namespaces yet.".to_string(),
+            (false, Lang::Zh) => format!("共 {} 个 namespace：\n{}", lines.len(), lines.join("\n")),
+            (false, Lang::En) => format!("{} namespaces:\n{}", lines.len(), lines.join("\n")),
+        };
+
+        Ok(json!({
+            "content": [
+                { "type": "text", "text": text }
+            ],
+            "data": {
+                "total": namespaces.len(),
+                "namespaces": namespaces
+            }
+        }))
+    }
+
+    /// 冻结 namespace：写入标记文件，之后 remember/update/forget/transaction/restore 均被拒绝，recall 不受影响。
+    pub fn namespace_freeze(&mut self, namespace: String, reason: Option<String>) -> Result<Value, String> {
+        format::check_store_format(&self.root_dir)?;
+        let paths = StorePaths::new(&self.root_dir, namespace.trim())?;
+        let marker = store::freeze_namespace(&paths, reason)?;
+
+        Ok(json!({
+            "content": [
+                { "type": "text", "text": self.lang.pick(
+                    format!("已冻结 namespace={}（只读，仍可 recall）。", paths.namespace),
+                    format!("Froze namespace={} (read-only, recall still works).", paths.namespace)
+                ) }
+            ],
+            "data": {
+                "namespace": paths.namespace,
+                "frozen": true,
+                "frozen_at": marker.frozen_at,
+                "reason": marker.reason
+            }
+        }))
+    }
+
+    pub fn namespace_unfreeze(&mut self, namespace: String) -> Result<Value, String> {
+        format::check_store_format(&self.root_dir)?;
+        let paths = StorePaths::new(&self.root_dir, namespace.trim())?;
+        let was_frozen = store::unfreeze_namespace(&paths)?;
+
+        let text = match (was_frozen, self.lang) {
+            (true, Lang::Zh) => format!("已解除冻结 namespace={}。", paths.namespace),
+            (true, Lang::En) => format!("Unfroze namespace={}.", paths.namespace),
+            (false, Lang::Zh) => format!("namespace={} 未处于冻结状态。", paths.namespace),
+            (false, Lang::En) => format!("namespace={} is not frozen.", paths.namespace),
+        };
+
+        Ok(json!({
+            "content": [
+                { "type": "text", "text": text }
+            ],
+            "data": {
+                "namespace": paths.namespace,
+                "frozen": false,
+                "was_frozen": was_frozen
+            }
+        }))
+    }
+
+    /// 查看或整体替换 namespace 级设置（settings.json）；`update` 为 None 时只读取。
+    pub fn namespace_settings(&mut self, namespace: String, update: Option<NamespaceSettings>) -> Result<Value, String> {
+        format::check_store_format(&self.root_dir)?;
+        let paths = StorePaths::new(&self.root_dir, namespace.trim())?;
+        if !paths.memories_path.is_file() {
+            return Err(format!("namespace 不存在：{}", paths.namespace));
+        }
+        if let Some(settings) = update.as_ref() {
+            store::write_settings(&paths, settings)?;
+        }
+        let settings = store::read_settings(&paths)?;
+        let recall_max_limit = settings
+            .recall_max_limit
+            .or(self.options.recall_max_limit)
+            .unwrap_or(RECALL_MAX_LIMIT);
+
+        let verb = match (update.is_some(), self.lang) {
+            (true, Lang::Zh) => "已更新",
+            (true, Lang::En) => "Updated",
+            (false, Lang::Zh) => "当前",
+            (false, Lang::En) => "Current",
+        };
+        let text = self.lang.pick(
+            format!("{verb} namespace={} 设置：recall 单页上限 {recall_max_limit}。", paths.namespace),
+            format!("{verb} settings for namespace={}: recall page limit {recall_max_limit}.", paths.namespace),
+        );
+        Ok(json!({
+            "content": [
+                { "type": "text", "text": text }
+            ],
+            "data": {
+                "namespace": paths.namespace,
+                "settings": settings,
+                "effective": { "recall_max_limit": recall_max_limit }
+            }
+        }))
+    }
+
+    /// 重命名 namespace：移动目录并改写记录中的 namespace 字段，随后为新路径重建索引。
+    pub fn namespace_rename(&mut self, from: String, to: String) -> Result<Value, String> {
+        self.ensure_jsonl_backend()?;
+        format::check_store_format(&self.root_dir)?;
+        let src = StorePaths::new(&self.root_dir, from.trim())?;
+        let dst = StorePaths::new(&self.root_dir, to.trim())?;
+
+        self.namespaces.remove(&src.namespace);
+        self.namespaces.remove(&dst.namespace);
+        let records = store::rename_namespace(&self.root_dir, &src.namespace, &dst.namespace)?;
+        let _ = catalog::Catalog::remove(&self.root_dir, &src.namespace);
+
+        self.get_or_open_namespace(&dst.namespace)?.warm_up()?;
+        let items = store::summarize_namespace(&self.root_dir, &dst.namespace)?.items;
+
+        Ok(json!({
+            "content": [
+                { "type": "text", "text": self.lang.pick(
+                    format!("已将 namespace={} 重命名为 {}（改写 {} 行记录，当前 {} 条记忆）。", src.namespace, dst.namespace, records, items),
+                    format!("Renamed namespace={} to {} ({} records rewritten, {} memories).", src.namespace, dst.namespace, records, items)
+                ) }
+            ],
+            "data": {
+                "from": src.namespace,
+                "to": dst.namespace,
+                "records": records,
+                "items": items
+            }
+        }))
+    }
+
+    /// 删除整个 namespace 目录（memories.jsonl / index.json / wal.jsonl 等），并清理缓存。
+    pub fn namespace_delete(&mut self, namespace: String, confirm: bool) -> Result<Value, String> {
+        if !confirm {
+            return Err("namespace_delete 是不可恢复的操作：请显式传入 confirm=true".to_string());
+        }
+        self.ensure_jsonl_backend()?;
+
+        format::check_store_format(&self.root_dir)?;
+        let paths = StorePaths::new(&self.root_dir, namespace.trim())?;
+        if !paths.memories_path.is_file() {
+            return Err(format!("namespace 不存在：{}", paths.namespace));
+        }
+
+        self.namespaces.remove(&paths.namespace);
+        fs::remove_dir_all(&paths.namespace_dir)
+            .map_err(|e| format!("delete namespace dir failed: {e}"))?;
+        let _ = catalog::Catalog::remove(&self.root_dir, &paths.namespace);
+
+        // 用户目录下已无其它项目时一并清理（失败不影响结果）。
+        if let Some(user_dir) = paths.namespace_dir.parent() {
+            let _ = fs::remove_dir(user_dir);
+        }
+
+        Ok(json!({
+            "content": [
+                { "type": "text", "text": self.lang.pick(
+                    format!("已删除 namespace={}。", paths.namespace),
+                    format!("Deleted namespace={}.", paths.namespace)
+                ) }
+            ],
+            "data": {
+                "namespace": paths.namespace,
+                "deleted": true
+            }
+        }))
+    }
+
+    pub fn migrate(&mut self) -> Result<Value, String> {
+        self.ensure_jsonl_backend()?;
+        let report = format::migrate_store_format(&self.root_dir)?;
+
+        // 升级后缓存的 NamespaceState 可能持有旧索引，统一丢弃后重新打开。
+        self.namespaces.clear();
+
+        let namespaces = store::discover_namespaces(&self.root_dir);
+        for ns in &namespaces {
+            let paths = StorePaths::new(&self.root_dir, ns)?;
+            let mut state = NamespaceState::open_with(paths, self.options.clone())?;
+            state.rebuild_index()?;
+        }
+        store::list_namespaces(&self.root_dir, true)?;
+
+        let n = namespaces.len();
+        let text = match (report.from_version == report.to_version, self.lang) {
+            (true, Lang::Zh) => format!("存储格式已是最新版本 {}；已重建 {} 个 namespace 的索引。", report.to_version, n),
+            (true, Lang::En) => format!("Store format is already at version {}; rebuilt indexes of {} namespaces.", report.to_version, n),
+            (false, Lang::Zh) => format!(
+                "存储格式已从版本 {} 升级到 {}；已重建 {} 个 namespace 的索引。",
+                report.from_version, report.to_version, n
+            ),
+            (false, Lang::En) => format!(
+                "Store format upgraded from version {} to {}; rebuilt indexes of {} namespaces.",
+                report.from_version, report.to_version, n
+            ),
+        };
+
+        Ok(json!({
+            "content": [
+                { "type": "text", "text": text }
+            ],
+            "data": {
+                "from_version": report.from_version,
+                "to_version": report.to_version,
+                "namespaces": namespaces
+            }
+        }))
+    }
+
+    /// 启动时预热：并行打开并索引指定 namespace（`*` 表示全部已存在的 namespace）。
+    ///
+    /// 单个 namespace 失败不影响其它 namespace，失败项记录在报告中。
+    pub fn preload(&mut self, targets: &[String]) -> Result<PreloadReport, String> {
+        self.ensure_jsonl_backend()?;
+        format::ensure_store_format(&self.root_dir)?;
+
+        let mut wanted: Vec<String> = Vec::new();
+        for t in targets {
+            let t = t.trim();
+            if t.is_empty() {
+                continue;
+            }
+            if t == "*" {
+                wanted.extend(store::known_namespaces(&self.root_dir));
+            } else {
+                wanted.push(t.to_string());
+            }
+        }
+
+        let mut report = PreloadReport::default();
+        let mut jobs: Vec<StorePaths> = Vec::new();
+        for ns in wanted {
+            match StorePaths::new(&self.root_dir, &ns) {
+                Ok(paths) => {
+                    let dup = self.namespaces.contains_key(&paths.namespace)
+                        || jobs.iter().any(|x| x.namespace == paths.namespace);
+                    if !dup {
+                        jobs.push(paths);
+                    }
+                }
+                Err(e) => report.failed.push((ns, e)),
+            }
+        }
+
+        let workers = std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1)
+            .clamp(1, jobs.len().max(1));
+        let chunk = jobs.len().div_ceil(workers).max(1);
+        let options = self.options.clone();
+
+        let loaded: Vec<(String, Result<NamespaceState, String>)> = std::thread::scope(|scope| {
+            let handles: Vec<_> = jobs
+                .chunks(chunk)
+                .map(|part| {
+                    let options = options.clone();
+                    scope.spawn(move || {
+                        part.iter()
+                            .map(|paths| {
+                                let state = NamespaceState::open_with(paths.clone(), options.clone())
+                                    .and_then(|mut st| st.warm_up().map(|_| st))
+                                    .map(|mut st| {
+                                        st.maintain_on_open();
+                                        st
+                                    });
+                                (paths.namespace.clone(), state)
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+
+            handles
+                .into_iter()
+                .flat_map(|h| h.join().unwrap_or_default())
+                .collect()
+        });
+
+        for (ns, state) in loaded {
+            match state {
+                Ok(state) => {
+                    self.namespaces.insert(ns.clone(), state);
+                    report.loaded.push(ns);
+                }
+                Err(e) => report.failed.push((ns, e)),
+            }
+        }
+
+        report.loaded.sort();
+        Ok(report)
+    }
+
+    /// 依据 WAL 将 namespace 恢复到指定时间点（RFC3339 或 YYYY-MM-DD；日期按当天结束计）。
+    pub fn restore_at(&mut self, namespace: &str, at: &str) -> Result<Value, String> {
+        let (at_ts, at_canonical) = time::parse_time_to_ts_and_canonical(at, time::DateBoundKind::End)?;
+        let lang = self.lang;
+
+        let state = self.get_or_open_namespace(namespace)?;
+        let ns = state.namespace().to_string();
+        let records = state.restore_to(at_ts)?;
+
+        Ok(json!({
+            "content": [
+                { "type": "text", "text": lang.pick(
+                    format!("已将 namespace={} 恢复到 {}（共 {} 条记录）。", ns, at_canonical, records),
+                    format!("Restored namespace={} to {} ({} records).", ns, at_canonical, records)
+                ) }
+            ],
+            "data": {
+                "namespace": ns,
+                "at": at_canonical,
+                "records": records
+            }
+        }))
+    }
+
+    /// 从 backup 快照恢复 namespace（未指定时为快照中的全部）：校验后整体替换目录，并丢弃缓存的状态。
+    pub fn restore_from(&mut self, from: &Path, namespace: Option<&str>) -> Result<Value, String> {
+        self.ensure_jsonl_backend()?;
+        format::ensure_store_format(&self.root_dir)?;
+        let report = backup::restore_snapshot(&self.root_dir, from, namespace)?;
+
+        for ns in &report.namespaces {
+            self.namespaces.remove(ns);
+        }
+        store::list_namespaces(&self.root_dir, true)?;
+
+        let text = self.lang.pick(
+            format!(
+                "已从快照 {}（{}）恢复 {} 个 namespace：{}",
+                report.snapshot.display(),
+                report.created_at,
+                report.namespaces.len(),
+                report.namespaces.join(", ")
+            ),
+            format!(
+                "Restored {} namespaces from snapshot {} ({}): {}",
+                report.namespaces.len(),
+                report.snapshot.display(),
+                report.created_at,
+                report.namespaces.join(", ")
+            ),
+        );
+        Ok(json!({
+            "content": [
+                { "type": "text", "text": text }
+            ],
+            "data": report
+        }))
+    }
+
+    /// 与另一个存储根目录（如另一台机器同步过来的目录）双向合并 namespace（未指定时为两侧的全部）。
+    ///
+    /// 两侧按 id 交换对方缺少的记忆；同一 id 两侧都改动过时按最后变更时间取较新的一方（删除标记同样参与），
+    /// 写入后全量重建有变化一侧的索引。`dry_run` 只报告将要交换的记录。
+    pub fn sync(&mut self, remote_root: &Path, namespace: Option<&str>, dry_run: bool) -> Result<Value, String> {
+        self.ensure_jsonl_backend()?;
+        format::check_store_format(&self.root_dir)?;
+        format::check_store_format(remote_root)?;
+        let same_root = match (fs::canonicalize(&self.root_dir), fs::canonicalize(remote_root)) {
+            (Ok(a), Ok(b)) => a == b,
+            _ => false,
+        };
+        if same_root {
+            return Err("sync 的远端不能是当前存储目录".to_string());
+        }
+        if !remote_root.is_dir() {
+            return Err(format!("远端存储目录不存在：{}", remote_root.display()));
+        }
+
+        let namespaces: Vec<String> = match namespace {
+            Some(ns) => vec![StorePaths::new(&self.root_dir, ns.trim())?.namespace],
+            None => {
+                let mut all = store::discover_namespaces(&self.root_dir);
+                all.extend(store::discover_namespaces(remote_root));
+                all.sort();
+                all.dedup();
+                all
+            }
+        };
+
+        let mut reports: Vec<sync::NamespaceSyncReport> = Vec::new();
+        for ns in &namespaces {
+            let local_paths = StorePaths::new(&self.root_dir, ns)?;
+            let remote_paths = StorePaths::new(remote_root, ns)?;
+            let local = sync_dump(ns, &local_paths)?;
+            let remote = sync_dump(ns, &remote_paths)?;
+            let plan = sync::plan(local, remote);
+
+            let report = sync::NamespaceSyncReport {
+                namespace: ns.clone(),
+                pulled: plan.to_local.len(),
+                pushed: plan.to_remote.len(),
+                conflicts: plan.conflicts,
+            };
+            if report.pulled + report.pushed > 0 {
+                log::emit(
+                    LogLevel::Info,
+                    "memory.sync",
+                    format!(
+                        "namespace={ns} 同步{}：拉取 {} 条，推送 {} 条，冲突 {} 个",
+                        if dry_run { "（预演）" } else { "" },
+                        report.pulled,
+                        report.pushed,
+                        report.conflicts.len()
+                    ),
+                    json!({
+                        "namespace": ns,
+                        "event": "sync",
+                        "dry_run": dry_run,
+                        "pulled": report.pulled,
+                        "pushed": report.pushed,
+                        "conflicts": report.conflicts.len()
+                    }),
+                );
+            }
+            if !dry_run {
+                if !plan.to_remote.is_empty() {
+                    format::ensure_store_format(remote_root)?;
+                    let mut state = NamespaceState::open_with(remote_paths, self.options.clone())?;
+                    state.import_records(plan.to_remote, &mut |_, _| ConflictAction::Replace, false)?;
+                    state.rebuild_index()?;
+                }
+                if !plan.to_local.is_empty() {
+                    let state = self.get_or_open_namespace(ns)?;
+                    state.import_records(plan.to_local, &mut |_, _| ConflictAction::Replace, false)?;
+                    state.rebuild_index()?;
+                }
+            }
+            reports.push(report);
+        }
+
+        let pulled: usize = reports.iter().map(|r| r.pulled).sum();
+        let pushed: usize = reports.iter().map(|r| r.pushed).sum();
+        let conflicts: usize = reports.iter().map(|r| r.conflicts.len()).sum();
+        let mut text = self.lang.pick(
+            format!(
+                "{}与 {} 同步 {} 个 namespace：拉取 {} 条，推送 {} 条，冲突 {} 条",
+                if dry_run { "（预览）" } else { "已" },
+                remote_root.display(),
+                reports.len(),
+                pulled,
+                pushed,
+                conflicts
+            ),
+            format!(
+                "{}Synced {} namespaces with {}: {} pulled, {} pushed, {} conflicts",
+                if dry_run { "(dry run) " } else { "" },
+                reports.len(),
+                remote_root.display(),
+                pulled,
+                pushed,
+                conflicts
+            ),
+        );
+        for report in reports.iter().filter(|r| r.pulled + r.pushed > 0) {
+            text.push_str(&self.lang.pick(
+                format!("\n- {}：拉取 {}，推送 {}", report.namespace, report.pulled, report.pushed),
+                format!("\n- {}: {} pulled, {} pushed", report.namespace, report.pulled, report.pushed),
+            ));
+            for conflict in &report.conflicts {
+                let winner = match (conflict.winner, conflict.deleted) {
+                    (sync::SyncSide::Local, false) => self.lang.pick("采用本地版本", "kept local"),
+                    (sync::SyncSide::Remote, false) => self.lang.pick("采用远端版本", "took remote"),
+                    (sync::SyncSide::Local, true) => self.lang.pick("按本地删除", "deleted (local)"),
+                    (sync::SyncSide::Remote, true) => self.lang.pick("按远端删除", "deleted (remote)"),
+                };
+                text.push_str(&format!("\n  - {}{}{}", conflict.id, self.lang.pick("：", ": "), winner));
+            }
+        }
+
+        Ok(json!({
+            "content": [
+                { "type": "text", "text": text }
+            ],
+            "data": {
+                "remote": remote_root.display().to_string(),
+                "dry_run": dry_run,
+                "namespaces": reports
+            }
+        }))
+    }
+
+    /// 按配置的存储后端打开 namespace（两种后端共有的操作使用）。
+    fn open_namespace(&mut self, namespace: &str) -> Result<NamespaceHandle<'_>, String> {
+        match self.options.backend {
+            StorageBackend::Jsonl => Ok(NamespaceHandle::Jsonl(self.get_or_open_namespace(namespace)?)),
+            #[cfg(feature = "sqlite")]
+            backend @ (StorageBackend::Sqlite | StorageBackend::Memory) => {
+                let paths = StorePaths::new(&self.root_dir, namespace)?;
+                let key = paths.namespace.clone();
+                if !self.sqlite_stores.contains_key(&key) {
+                    let store = if backend == StorageBackend::Memory {
+                        SqliteStore::open_in_memory(paths, self.options.clone())?
+                    } else {
+                        format::ensure_store_format(&self.root_dir)?;
+                        SqliteStore::open_with(paths, self.options.clone())?
+                    };
+                    self.sqlite_stores.insert(key.clone(), store);
+                }
+                Ok(NamespaceHandle::Sqlite(
+                    self.sqlite_stores.get_mut(&key).expect("namespace exists"),
+                ))
+            }
+            #[cfg(not(feature = "sqlite"))]
+            StorageBackend::Sqlite | StorageBackend::Memory => Err(self.ensure_jsonl_backend().unwrap_err()),
+        }
+    }
+
+    /// JSONL 后端专有的操作（索引维护、导入导出、namespace 管理等）在 SQLite 后端下直接报错。
+    fn ensure_jsonl_backend(&self) -> Result<(), String> {
+        match self.options.backend {
+            StorageBackend::Jsonl => Ok(()),
+            StorageBackend::Sqlite | StorageBackend::Memory => Err(format!(
+                "{} 后端暂不支持该操作（目前支持 {}）",
+                self.options.backend.as_str(),
+                store::NON_JSONL_TOOLS.join(" / ")
+            )),
+        }
+    }
+
+    fn get_or_open_namespace(&mut self, namespace: &str) -> Result<&mut NamespaceState, String> {
+        let raw = namespace.trim();
+        if raw.is_empty() {
+            return Err("namespace 不能为空".to_string());
+        }
+        self.ensure_jsonl_backend()?;
+
+        let paths = StorePaths::new(&self.root_dir, raw)?;
+        let key = paths.namespace.clone();
+
+        if !self.namespaces.contains_key(&key) {
+            format::ensure_store_format(&self.root_dir)?;
+            let mut state = NamespaceState::open_with(paths, self.options.clone())?;
+            state.maintain_on_open();
+            self.notified_generations.entry(key.clone()).or_insert(state.generation());
+            self.namespaces.insert(key.clone(), state);
+        }
+
+        Ok(self
+            .namespaces
+            .get_mut(&key)
+            .expect("namespace exists"))
+    }
+}
+
+/// compact 结果中单个 namespace 的统计。
+fn compact_entry(namespace: &str, report: &store::CompactReport) -> Value {
+    json!({
+        "namespace": namespace,
+        "records_before": report.records_before,
+        "records_after": report.records_after,
+        "records_removed": report.records_before - report.records_after,
+        "bytes_before": report.bytes_before,
+        "bytes_after": report.bytes_after
+    })
+}
+
+/// sync 的一侧：按 id 折叠后的最终状态（含删除标记）；该侧没有此 namespace 时为空。
+fn sync_dump(namespace: &str, paths: &StorePaths) -> Result<ExportDump, String> {
+    if !paths.memories_path.is_file() {
+        return Ok(ExportDump::empty(namespace));
+    }
+    export::collect(namespace, &rotation::DataFiles::load(paths)?, Some("0"))
+}
+
+/// 只读地取出 namespace 当前的全部记忆（按 id）；namespace 不存在时为空。
+fn live_items(paths: &StorePaths) -> Result<HashMap<String, MemoryItem>, String> {
+    if !paths.memories_path.is_file() {
+        return Ok(HashMap::new());
+    }
+    let dump = export::collect(&paths.namespace, &rotation::DataFiles::load(paths)?, None)?;
+    Ok(dump
+        .records
+        .into_iter()
+        .filter_map(|record| match record {
+            ExportRecord::Item(item) => Some((item.id.clone(), *item)),
+            ExportRecord::Delete(_) => None,
+        })
+        .collect())
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PreloadReport {
+    pub loaded: Vec<String>,
+    pub failed: Vec<(String, String)>,
+}
+
+#[derive(Debug, Clone)]
+struct GlobalKeywordStats {
+    scanned_namespaces: usize,
+    keywords: Vec<Value>,
+}
+
+/// 全局关键字词表：关键字（有序）-> (出现的 namespace 数, 引用的记忆条数)。
+#[derive(Debug, Clone, Default)]
+struct GlobalKeywordVocabulary {
+    scanned_namespaces: usize,
+    keywords: BTreeMap<String, (usize, usize)>,
+}
+
+/// remember 与按预览确认写入共用的返回结构。
+fn remembered_output(lang: Lang, namespace: &str, recorded: RememberRecorded) -> Value {
+    json!({
+        "content": [
+            { "type": "text", "text": lang.pick(
+                format!("已记录记忆：{}（namespace={}）", recorded.id, namespace),
+                format!("Remembered: {} (namespace={})", recorded.id, namespace)
+            ) }
+        ],
+        "data": {
+            "id": recorded.id,
+            "revision": recorded.revision,
+            "namespace": namespace,
+            "recorded_at": recorded.recorded_at,
+            "occurred_at": recorded.occurred_at,
+            "keywords": recorded.keywords
+        }
+    })
+}
+
+fn collect_global_keyword_vocabulary(root_dir: &Path) -> GlobalKeywordVocabulary {
+    let mut vocabulary = GlobalKeywordVocabulary::default();
+    if !root_dir.exists() {
+        return vocabulary;
+    }
+
+    // 经 catalog 定位各 namespace 的 index.json，不再遍历整个目录树。
+    for ns in store::known_namespaces(root_dir) {
+        let Ok(paths) = StorePaths::new(root_dir, &ns) else {
+            continue;
+        };
+        let text = match fs::read_to_string(&paths.index_path) {
+            Ok(v) => v,
+            Err(_) => continue,
+        };
+        let index: index::IndexData = match serde_json::from_str(&text) {
+            Ok(v) => v,
+            Err(_) => continue,
+        };
+        if index.version != index::INDEX_VERSION {
+            continue;
+        }
+
+        vocabulary.scanned_namespaces += 1;
+        for (kw, postings) in index.keyword_postings {
+            let kw = kw.trim().to_lowercase();
+            if kw.is_empty() || store::is_time_like_keyword(&kw) {
+                continue;
+            }
+            let entry = vocabulary.keywords.entry(kw).or_default();
+            entry.0 += 1;
+            entry.1 += postings.len();
+        }
+    }
+
+    vocabulary
+}
+
+fn collect_global_keyword_stats(root_dir: &Path, sort: KeywordSort) -> GlobalKeywordStats {
+    let vocabulary = collect_global_keyword_vocabulary(root_dir);
+
+    let mut out: Vec<(String, usize, usize)> = vocabulary
+        .keywords
+        .into_iter()
+        .map(|(kw, (ns_count, items))| (kw, ns_count, items))
+        .collect();
 
-    out.sort_by(|a, b| {
-        a.0.chars()
-            .count()
-            .cmp(&b.0.chars().count())
-            .then_with(|| b.1.cmp(&a.1))
-            .then_with(|| a.0.cmp(&b.0))
-    });
+    index::sort_keywords(&mut out, sort, |(kw, _, items)| (kw.as_str(), *items));
 
     let keywords: Vec<Value> = out
         .into_iter()
@@ -254,7 +2687,1048 @@ fn collect_global_keyword_stats(root_dir: &Path) -> GlobalKeywordStats {
         .collect();
 
     GlobalKeywordStats {
-        scanned_namespaces: namespaces_scanned,
+        scanned_namespaces: vocabulary.scanned_namespaces,
         keywords,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn namespaces_list_should_report_counts() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let mut engine = MemoryEngine::new(dir.path().to_path_buf());
+
+        for (ns, n) in [("u1/p1", 2), ("u2/p9", 1)] {
+            for i in 0..n {
+                engine
+                    .remember(RememberArgs {
+                        namespace: ns.to_string(),
+                        keywords: vec!["k".to_string()],
+                        slice: format!("s{i}"),
+                        diary: "d".to_string(),
+                        occurred_at: None,
+                        importance: None,
+                        source: None,
+                        ..Default::default()
+                    })
+                    .expect("remember");
+            }
+        }
+
+        let out = engine.namespaces_list(false).expect("list");
+        let list = out["data"]["namespaces"].as_array().expect("namespaces");
+        let got: Vec<(&str, u64)> = list
+            .iter()
+            .map(|x| (x["namespace"].as_str().unwrap(), x["items"].as_u64().unwrap()))
+            .collect();
+        assert_eq!(got, vec![("u1/p1", 2), ("u2/p9", 1)]);
+        assert!(list[0]["last_write"].as_str().is_some());
+    }
+
+    #[test]
+    fn namespace_delete_should_require_confirm_and_evict_cache() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let mut engine = MemoryEngine::new(dir.path().to_path_buf());
+
+        for ns in ["u1/p1", "u1/p2"] {
+            engine
+                .remember(RememberArgs {
+                    namespace: ns.to_string(),
+                    keywords: vec!["k".to_string()],
+                    slice: "s".to_string(),
+                    diary: "d".to_string(),
+                    occurred_at: None,
+                    importance: None,
+                    source: None,
+                    ..Default::default()
+                })
+                .expect("remember");
+        }
+
+        let err = engine
+            .namespace_delete("u1/p1".to_string(), false)
+            .expect_err("confirm required");
+        assert!(err.contains("confirm"), "unexpected err: {err}");
+        assert!(dir.path().join("u1/p1/memories.jsonl").exists());
+
+        engine
+            .namespace_delete("u1/p1".to_string(), true)
+            .expect("delete");
+        assert!(!dir.path().join("u1/p1").exists());
+        assert!(dir.path().join("u1/p2/memories.jsonl").exists());
+        assert!(!engine.namespaces.contains_key("u1/p1"));
+
+        engine
+            .namespace_delete("u1/p1".to_string(), true)
+            .expect_err("already deleted");
+    }
+
+    #[test]
+    fn frozen_namespace_should_reject_writes_but_allow_recall() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let mut engine = MemoryEngine::new(dir.path().to_path_buf());
+        let remember = |engine: &mut MemoryEngine| {
+            engine.remember(RememberArgs {
+                namespace: "u1/p1".to_string(),
+                keywords: vec!["k".to_string()],
+                slice: "s".to_string(),
+                diary: "d".to_string(),
+                occurred_at: None,
+                importance: None,
+                source: None,
+                ..Default::default()
+            })
+        };
+
+        let out = remember(&mut engine).expect("remember");
+        let id = out["data"]["id"].as_str().expect("id").to_string();
+
+        engine
+            .namespace_freeze("u1/p1".to_string(), Some("归档".to_string()))
+            .expect("freeze");
+
+        let err = remember(&mut engine).expect_err("frozen");
+        assert!(err.contains("已冻结"), "unexpected err: {err}");
+        assert!(err.contains("归档"), "unexpected err: {err}");
+        engine
+            .forget(ForgetArgs {
+                namespace: "u1/p1".to_string(),
+                id: id.clone(),
+                revision: 1,
+            })
+            .expect_err("frozen");
+
+        let out = engine
+            .recall(RecallArgs {
+                namespace: "u1/p1".to_string(),
+                limit: 20,
+                ..Default::default()
+            })
+            .expect("recall");
+        assert_eq!(out["data"]["total"].as_u64(), Some(1));
+
+        let list = engine.namespaces_list(false).expect("list");
+        assert_eq!(list["data"]["namespaces"][0]["frozen"].as_bool(), Some(true));
+
+        let out = engine.namespace_unfreeze("u1/p1".to_string()).expect("unfreeze");
+        assert_eq!(out["data"]["was_frozen"].as_bool(), Some(true));
+        remember(&mut engine).expect("writable again");
+    }
+
+    #[test]
+    fn namespace_rename_should_move_records_and_keep_timestamps() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let mut engine = MemoryEngine::new(dir.path().to_path_buf());
+
+        let out = engine
+            .remember(RememberArgs {
+                namespace: "u1/old".to_string(),
+                keywords: vec!["k".to_string()],
+                slice: "s".to_string(),
+                diary: "d".to_string(),
+                occurred_at: None,
+                importance: None,
+                source: None,
+                ..Default::default()
+            })
+            .expect("remember");
+        let id = out["data"]["id"].as_str().expect("id").to_string();
+        let recorded_at = out["data"]["recorded_at"].as_str().expect("recorded_at").to_string();
+
+        engine
+            .namespace_rename("u1/old".to_string(), "u1/new".to_string())
+            .expect("rename");
+        assert!(!dir.path().join("u1/old").exists());
+
+        let out = engine.get("u1/new".to_string(), id.clone(), None).expect("get");
+        let item = &out["data"]["item"];
+        assert_eq!(item["namespace"].as_str(), Some("u1/new"));
+        assert_eq!(item["recorded_at"].as_str(), Some(recorded_at.as_str()));
+
+        let out = engine.health("u1/new".to_string()).expect("health");
+        assert_eq!(out["data"]["healthy"].as_bool(), Some(true));
+
+        engine
+            .remember(RememberArgs {
+                namespace: "u1/other".to_string(),
+                keywords: vec!["k".to_string()],
+                slice: "s".to_string(),
+                diary: "d".to_string(),
+                occurred_at: None,
+                importance: None,
+                source: None,
+                ..Default::default()
+            })
+            .expect("remember");
+        let err = engine
+            .namespace_rename("u1/new".to_string(), "u1/other".to_string())
+            .expect_err("target exists");
+        assert!(err.contains("已存在"), "unexpected err: {err}");
+    }
+
+    #[test]
+    fn stats_should_summarize_namespace() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let mut engine = MemoryEngine::new(dir.path().to_path_buf());
+
+        for (kw, occurred_at, importance) in [
+            ("a", Some("2025-01-02"), Some(3)),
+            ("b", Some("2025-03-04"), Some(3)),
+            ("c", None, None),
+        ] {
+            engine
+                .remember(RememberArgs {
+                    namespace: "u1/p1".to_string(),
+                    keywords: vec![kw.to_string(), "shared".to_string()],
+                    slice: "s".to_string(),
+                    diary: "d".to_string(),
+                    occurred_at: occurred_at.map(|x| x.to_string()),
+                    importance,
+                    source: None,
+                    ..Default::default()
+                })
+                .expect("remember");
+        }
+
+        let out = engine.stats("u1/p1".to_string()).expect("stats");
+        let stats = &out["data"]["stats"];
+        assert_eq!(stats["items"].as_u64(), Some(3));
+        assert_eq!(stats["keywords"].as_u64(), Some(4));
+        assert_eq!(stats["importance"]["3"].as_u64(), Some(2));
+        assert_eq!(stats["importance"]["none"].as_u64(), Some(1));
+        assert_eq!(stats["earliest_occurred_at"].as_str(), Some("2025-01-02T00:00:00Z"));
+        assert_eq!(stats["latest_occurred_at"].as_str(), Some("2025-03-04T00:00:00Z"));
+        assert_eq!(stats["index"]["fresh"].as_bool(), Some(true));
+        assert!(stats["total_bytes"].as_u64().unwrap() > 0);
+    }
+
+    #[test]
+    fn preload_should_open_all_namespaces_in_parallel() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let root = dir.path().to_path_buf();
+
+        {
+            let mut engine = MemoryEngine::new(root.clone());
+            for ns in ["u1/p1", "u1/p2", "u2/p1"] {
+                engine
+                    .remember(RememberArgs {
+                        namespace: ns.to_string(),
+                        keywords: vec!["k".to_string()],
+                        slice: "slice".to_string(),
+                        diary: "diary".to_string(),
+                        occurred_at: None,
+                        importance: None,
+                        source: None,
+                        ..Default::default()
+                    })
+                    .expect("remember");
+            }
+        }
+
+        let mut engine = MemoryEngine::new(root);
+        let report = engine
+            .preload(&["*".to_string(), "u1/p1".to_string(), "bad".to_string()])
+            .expect("preload");
+
+        assert_eq!(report.loaded, vec!["u1/p1", "u1/p2", "u2/p1"]);
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(engine.namespaces.len(), 3);
+    }
+
+    #[test]
+    fn import_should_dedupe_by_id_and_resolve_conflicts_by_policy() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let mut engine = MemoryEngine::new(dir.path().to_path_buf());
+
+        let mut ids = Vec::new();
+        for slice in ["s1", "s2"] {
+            let out = engine
+                .remember(RememberArgs {
+                    namespace: "u1/src".to_string(),
+                    keywords: vec!["k".to_string()],
+                    slice: slice.to_string(),
+                    diary: "d".to_string(),
+                    occurred_at: None,
+                    importance: None,
+                    source: None,
+                    ..Default::default()
+                })
+                .expect("remember");
+            ids.push(out["data"]["id"].as_str().expect("id").to_string());
+        }
+
+        let dump = engine.export_dump("u1/src", None).expect("export");
+
+        // 预演：不创建目标 namespace，之后的真实导入不受影响。
+        let out = engine
+            .import(Some("u1/dst".to_string()), dump.clone(), &mut |_, _| ConflictAction::KeepExisting, true)
+            .expect("dry run");
+        assert_eq!(out["data"]["dry_run"], json!(true));
+        assert_eq!(out["data"]["report"]["imported"], json!(2));
+        assert!(!dir.path().join("u1").join("dst").exists());
+
+        let import = |engine: &mut MemoryEngine, dump: ExportDump, policy: ConflictPolicy| {
+            engine
+                .import(Some("u1/dst".to_string()), dump, &mut |a, b| policy.decide(a, b), false)
+                .expect("import")
+        };
+        let out = import(&mut engine, dump.clone(), ConflictPolicy::KeepExisting);
+        assert_eq!(out["data"]["report"]["imported"], json!(2));
+        let item = engine.get("u1/dst".to_string(), ids[0].clone(), None).expect("get");
+        assert_eq!(item["data"]["item"]["namespace"], "u1/dst");
+        let health = engine.health("u1/dst".to_string()).expect("health");
+        assert_eq!(health["data"]["healthy"], json!(true));
+
+        let out = import(&mut engine, dump.clone(), ConflictPolicy::KeepExisting);
+        assert_eq!(out["data"]["report"]["imported"], json!(0));
+        assert_eq!(out["data"]["report"]["duplicates"], json!(2));
+        let out = engine
+            .import(Some("u1/dst".to_string()), dump.clone(), &mut |_, _| ConflictAction::KeepExisting, true)
+            .expect("dry run");
+        assert_eq!(out["data"]["report"]["duplicates"], json!(2));
+
+        engine
+            .update(
+                "u1/dst".to_string(),
+                UpdateArgs {
+                    id: ids[1].clone(),
+                    revision: 1,
+                    keywords: None,
+                    keyword_weights: Default::default(),
+                    tags: None,
+                    kind: None,
+                    slice: Some("changed".to_string()),
+                    diary: None,
+                    occurred_at: None,
+                    importance: None,
+                    source: None,
+                    metadata: None,
+                    relates_to: None,
+                    supersedes: None,
+                    expires_at: None,
+                    pinned: None,
+                    archived: None,
+                },
+            )
+            .expect("update");
+        let out = import(&mut engine, dump.clone(), ConflictPolicy::KeepExisting);
+        assert_eq!(
+            out["data"]["report"]["conflicts"],
+            json!([{ "id": ids[1].clone(), "action": "keep_existing" }])
+        );
+        let item = engine.get("u1/dst".to_string(), ids[1].clone(), None).expect("get");
+        assert_eq!(item["data"]["item"]["slice"], "changed");
+
+        // 目标中的版本更新过，keep-newest 应保留现有版本。
+        let out = import(&mut engine, dump.clone(), ConflictPolicy::KeepNewest);
+        assert_eq!(out["data"]["report"]["conflicts"][0]["action"], "keep_existing");
+
+        let out = import(&mut engine, dump, ConflictPolicy::Duplicate);
+        let decision = &out["data"]["report"]["conflicts"][0];
+        assert_eq!(decision["action"], "duplicate");
+        let new_id = decision["new_id"].as_str().expect("new_id").to_string();
+        let copy = engine.get("u1/dst".to_string(), new_id, None).expect("get copy");
+        assert_eq!(copy["data"]["item"]["slice"], "s2");
+        let item = engine.get("u1/dst".to_string(), ids[1].clone(), None).expect("get");
+        assert_eq!(item["data"]["item"]["slice"], "changed");
+
+        let records = fs::read_to_string(dir.path().join("u1/dst/memories.jsonl")).expect("read");
+        assert_eq!(records.lines().count(), 4);
+
+        // 来源中更新过的记忆：keep-newest 应以导入内容追加为新修订。
+        engine
+            .update(
+                "u1/src".to_string(),
+                UpdateArgs {
+                    id: ids[0].clone(),
+                    revision: 1,
+                    keywords: None,
+                    keyword_weights: Default::default(),
+                    tags: None,
+                    kind: None,
+                    slice: Some("newer".to_string()),
+                    diary: None,
+                    occurred_at: None,
+                    importance: None,
+                    source: None,
+                    metadata: None,
+                    relates_to: None,
+                    supersedes: None,
+                    expires_at: None,
+                    pinned: None,
+                    archived: None,
+                },
+            )
+            .expect("update src");
+        let dump = engine.export_dump("u1/src", None).expect("export");
+        let out = import(&mut engine, dump, ConflictPolicy::KeepNewest);
+        let decisions = out["data"]["report"]["conflicts"].as_array().expect("conflicts");
+        let replaced = decisions.iter().find(|x| x["id"] == ids[0].as_str()).expect("decision");
+        assert_eq!(replaced["action"], "replace");
+        let item = engine.get("u1/dst".to_string(), ids[0].clone(), None).expect("get");
+        assert_eq!(item["data"]["item"]["slice"], "newer");
+        assert_eq!(item["data"]["item"]["revision"], json!(2));
+    }
+
+    #[test]
+    fn import_dry_run_should_leave_existing_namespace_untouched() {
+        fn snapshot(root: &Path) -> BTreeMap<PathBuf, Vec<u8>> {
+            let mut out = BTreeMap::new();
+            let mut pending = vec![root.to_path_buf()];
+            while let Some(dir) = pending.pop() {
+                for entry in fs::read_dir(&dir).expect("read dir") {
+                    let path = entry.expect("entry").path();
+                    if path.is_dir() {
+                        pending.push(path);
+                    } else {
+                        out.insert(path.clone(), fs::read(&path).expect("read file"));
+                    }
+                }
+            }
+            out
+        }
+
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let mut ids = Vec::new();
+        {
+            let mut engine = MemoryEngine::new(dir.path().to_path_buf());
+            for (ns, slice) in [("u1/src", "s1"), ("u1/src", "s2")] {
+                let out = engine
+                    .remember(RememberArgs {
+                        namespace: ns.to_string(),
+                        keywords: vec!["k".to_string()],
+                        slice: slice.to_string(),
+                        diary: "d".to_string(),
+                        ..Default::default()
+                    })
+                    .expect("remember");
+                ids.push(out["data"]["id"].as_str().expect("id").to_string());
+            }
+            let dump = engine.export_dump("u1/src", None).expect("export");
+            engine
+                .import(Some("u1/dst".to_string()), dump, &mut |_, _| ConflictAction::KeepExisting, false)
+                .expect("import");
+            engine
+                .update(
+                    "u1/dst".to_string(),
+                    UpdateArgs { id: ids[1].clone(), revision: 1, slice: Some("changed".to_string()), ..Default::default() },
+                )
+                .expect("update");
+            engine.flush().expect("flush");
+        }
+        // 打开 namespace 会补写这些文件：预演之后它们仍应缺失。
+        let paths = StorePaths::new(dir.path(), "u1/dst").expect("paths");
+        fs::remove_file(&paths.index_path).expect("remove index");
+        fs::remove_file(dir.path().join(format::STORE_FORMAT_FILE)).expect("remove store format");
+        let before = snapshot(dir.path());
+
+        let mut engine = MemoryEngine::new(dir.path().to_path_buf());
+        let mut dump = engine.export_dump("u1/src", None).expect("export");
+        dump.records.push(ExportRecord::Delete(model::Tombstone {
+            op: model::Tombstone::OP_DELETE.to_string(),
+            id: ids[0].clone(),
+            namespace: "u1/src".to_string(),
+            deleted_at: "2024-01-01T00:00:00Z".to_string(),
+            written_by: None,
+        }));
+        let out = engine
+            .import(Some("u1/dst".to_string()), dump, &mut |_, _| ConflictAction::Replace, true)
+            .expect("dry run");
+        let report = &out["data"]["report"];
+        assert_eq!(report["deleted"], json!(1));
+        assert_eq!(report["conflicts"], json!([{ "id": ids[1].clone(), "action": "replace" }]));
+        assert_eq!(snapshot(dir.path()), before);
+    }
+
+    #[test]
+    fn compact_all_should_report_removed_records_and_skip_frozen() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let mut engine = MemoryEngine::new(dir.path().to_path_buf());
+        let mut ids = Vec::new();
+        for ns in ["u1/p1", "u1/p2", "u2/p1"] {
+            for slice in ["a", "b"] {
+                let out = engine
+                    .remember(RememberArgs {
+                        namespace: ns.to_string(),
+                        keywords: vec!["k".to_string()],
+                        slice: slice.to_string(),
+                        diary: "d".to_string(),
+                        ..Default::default()
+                    })
+                    .expect("remember");
+                ids.push((ns, out["data"]["id"].as_str().unwrap().to_string()));
+            }
+        }
+        for (ns, id) in ids.iter().step_by(2) {
+            engine
+                .forget(ForgetArgs {
+                    namespace: ns.to_string(),
+                    id: id.clone(),
+                    revision: 1,
+                })
+                .expect("forget");
+        }
+        engine.namespace_freeze("u2/p1".to_string(), None).expect("freeze");
+
+        let out = engine.compact_all().expect("compact all");
+        let data = &out["data"];
+        let compacted: Vec<(&str, u64)> = data["namespaces"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|x| (x["namespace"].as_str().unwrap(), x["records_removed"].as_u64().unwrap()))
+            .collect();
+        // 每个 namespace 去掉被删除记忆的旧版本，删除标记保留。
+        assert_eq!(compacted, vec![("u1/p1", 1), ("u1/p2", 1)]);
+        assert_eq!(data["skipped"], json!(["u2/p1"]));
+        assert_eq!(data["records_removed"].as_u64(), Some(2));
+        assert!(data["bytes_after"].as_u64().unwrap() < data["bytes_before"].as_u64().unwrap());
+    }
+
+    #[test]
+    fn recall_global_should_merge_namespaces_and_tag_items() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let mut engine = MemoryEngine::new(dir.path().to_path_buf());
+        for (ns, slice, importance) in [
+            ("u1/p1", "p1 的部署记录", 2),
+            ("u1/p2", "p2 的部署记录", 5),
+            ("u2/p1", "u2 的部署记录", 4),
+            ("u1/p2", "与部署无关", 5),
+        ] {
+            let keyword = if slice.contains("无关") { "其它" } else { "部署" };
+            engine
+                .remember(RememberArgs {
+                    namespace: ns.to_string(),
+                    keywords: vec![keyword.to_string()],
+                    slice: slice.to_string(),
+                    diary: "d".to_string(),
+                    importance: Some(importance),
+                    ..Default::default()
+                })
+                .expect("remember");
+        }
+
+        let recall = RecallArgs {
+            keywords: vec!["部署".to_string()],
+            limit: 2,
+            ..Default::default()
+        };
+        let out = engine
+            .recall_global(RecallGlobalArgs {
+                namespaces: None,
+                recall: recall.clone(),
+            })
+            .expect("recall_global");
+        let data = &out["data"];
+        assert_eq!(data["namespaces"], json!(["u1/p1", "u1/p2", "u2/p1"]));
+        let got: Vec<(&str, &str)> = data["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|x| (x["namespace"].as_str().unwrap(), x["slice"].as_str().unwrap()))
+            .collect();
+        assert_eq!(got, vec![("u1/p2", "p2 的部署记录"), ("u2/p1", "u2 的部署记录")]);
+        assert_eq!(data["has_more"], true);
+        assert!(out["content"][0]["text"].as_str().unwrap().contains("(u1/p2)"));
+
+        let out = engine
+            .recall_global(RecallGlobalArgs {
+                namespaces: Some("u1/*".to_string()),
+                recall: RecallArgs { offset: 1, ..recall },
+            })
+            .expect("recall_global u1/*");
+        assert_eq!(out["data"]["namespaces"], json!(["u1/p1", "u1/p2"]));
+        assert_eq!(out["data"]["items"][0]["namespace"], "u1/p1");
+        assert_eq!(out["data"]["has_more"], false);
+
+        let stats = engine.least_recalled("u2/p1".to_string(), 10).expect("least_recalled");
+        assert_eq!(stats["data"]["items"][0]["recall_count"].as_u64(), Some(1));
+    }
+
+    #[test]
+    fn recall_global_should_skip_unreadable_namespaces_with_warnings() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let mut engine = MemoryEngine::new(dir.path().to_path_buf());
+        for ns in ["u1/bad", "u1/ok"] {
+            engine
+                .remember(RememberArgs {
+                    namespace: ns.to_string(),
+                    keywords: vec!["部署".to_string()],
+                    slice: format!("{ns} 的部署记录"),
+                    diary: "d".to_string(),
+                    ..Default::default()
+                })
+                .expect("remember");
+        }
+        let paths = StorePaths::new(dir.path(), "u1/bad").expect("paths");
+        std::fs::write(&paths.segments_path, "not json").expect("corrupt segments.json");
+
+        let mut engine = MemoryEngine::new(dir.path().to_path_buf());
+        let out = engine
+            .recall_global(RecallGlobalArgs {
+                namespaces: None,
+                recall: RecallArgs {
+                    keywords: vec!["部署".to_string()],
+                    limit: 10,
+                    ..Default::default()
+                },
+            })
+            .expect("recall_global");
+        let data = &out["data"];
+        assert_eq!(data["namespaces"], json!(["u1/bad", "u1/ok"]));
+        assert_eq!(data["items"].as_array().unwrap().len(), 1);
+        assert_eq!(data["items"][0]["namespace"], "u1/ok");
+        let warnings = data["warnings"].as_array().unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0]["namespace"], "u1/bad");
+        assert!(warnings[0]["error"].as_str().unwrap().contains("segments.json"), "{warnings:?}");
+        assert!(out["content"][0]["text"].as_str().unwrap().contains("1 个 namespace 无法读取"));
+    }
+
+    #[test]
+    fn namespace_pattern_should_expand_in_recall_and_keywords_list() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let mut engine = MemoryEngine::new(dir.path().to_path_buf());
+        for (ns, keywords) in [("u1/p1", vec!["erp", "部署"]), ("u1/p2", vec!["erp"]), ("u2/p1", vec!["crm"])] {
+            engine
+                .remember(RememberArgs {
+                    namespace: ns.to_string(),
+                    keywords: keywords.into_iter().map(str::to_string).collect(),
+                    slice: format!("{ns} 的记录"),
+                    diary: "d".to_string(),
+                    ..Default::default()
+                })
+                .expect("remember");
+        }
+
+        let out = engine
+            .recall(RecallArgs {
+                namespace: "u1/*".to_string(),
+                limit: 10,
+                ..Default::default()
+            })
+            .expect("recall u1/*");
+        assert_eq!(out["data"]["namespace"], "u1/*");
+        let mut got: Vec<&str> = out["data"]["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|x| x["namespace"].as_str().unwrap())
+            .collect();
+        got.sort();
+        assert_eq!(got, vec!["u1/p1", "u1/p2"]);
+
+        let err = engine
+            .recall(RecallArgs {
+                namespace: "u1/*".to_string(),
+                limit: 10,
+                cursor: Some("x".to_string()),
+                ..Default::default()
+            })
+            .expect_err("cursor");
+        assert!(err.contains("cursor"), "unexpected err: {err}");
+
+        let out = engine.keywords_list("u1/*".to_string(), KeywordSort::Frequency).expect("keywords_list");
+        assert_eq!(out["data"]["namespaces"], json!(["u1/p1", "u1/p2"]));
+        assert_eq!(out["data"]["keywords"], json!(["erp", "部署"]));
+
+        let err = engine
+            .remember(RememberArgs {
+                namespace: "u1/*".to_string(),
+                keywords: vec!["k".to_string()],
+                slice: "s".to_string(),
+                diary: "d".to_string(),
+                ..Default::default()
+            })
+            .expect_err("pattern is not writable");
+        assert!(err.contains("namespace 模式"), "unexpected err: {err}");
+        assert!(!dir.path().join("u1").join("_").exists());
+    }
+
+    #[test]
+    fn namespaces_list_should_maintain_root_catalog() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let mut engine = MemoryEngine::new(dir.path().to_path_buf());
+        let remember = |engine: &mut MemoryEngine, ns: &str| {
+            engine
+                .remember(RememberArgs {
+                    namespace: ns.to_string(),
+                    keywords: vec!["k".to_string()],
+                    slice: "s".to_string(),
+                    diary: "d".to_string(),
+                    occurred_at: None,
+                    importance: None,
+                    source: None,
+                    ..Default::default()
+                })
+                .expect("remember");
+        };
+
+        remember(&mut engine, "u1/p1");
+        assert!(catalog::Catalog::load(dir.path()).is_none());
+        engine.namespaces_list(false).expect("list");
+        assert!(catalog::Catalog::load(dir.path()).is_some());
+
+        // 写入时维护：新 namespace 与条数变化直接进入 catalog。
+        remember(&mut engine, "u1/p2");
+        remember(&mut engine, "u1/p2");
+        let cat = catalog::Catalog::load(dir.path()).expect("catalog");
+        assert_eq!(cat.namespaces.get("u1/p2").map(|x| x.items), Some(2));
+
+        // 绕过 catalog 直接拷入的 namespace：refresh 后可见。
+        let copied = dir.path().join("u2/p9");
+        fs::create_dir_all(&copied).expect("mkdir");
+        fs::copy(dir.path().join("u1/p1/memories.jsonl"), copied.join("memories.jsonl")).expect("copy");
+        let out = engine.namespaces_list(false).expect("list");
+        assert_eq!(out["data"]["total"], json!(2));
+        let out = engine.namespaces_list(true).expect("refresh");
+        assert_eq!(out["data"]["total"], json!(3));
+
+        // 外部追加：按文件大小发现并刷新条目。
+        let line = fs::read_to_string(copied.join("memories.jsonl")).expect("read");
+        let mut item: MemoryItem = serde_json::from_str(line.trim()).expect("item");
+        item.id = "external".to_string();
+        fs::write(
+            copied.join("memories.jsonl"),
+            format!("{}{}\n", line, serde_json::to_string(&item).unwrap()),
+        )
+        .expect("append");
+        let out = engine.namespaces_list(false).expect("list");
+        let p9 = out["data"]["namespaces"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|x| x["namespace"] == "u2/p9")
+            .cloned()
+            .expect("u2/p9");
+        assert_eq!(p9["items"], json!(2));
+
+        engine.namespace_delete("u1/p1".to_string(), true).expect("delete");
+        let cat = catalog::Catalog::load(dir.path()).expect("catalog");
+        assert!(!cat.namespaces.contains_key("u1/p1"));
+    }
+
+    #[test]
+    fn keywords_list_should_support_sort_options() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let mut engine = MemoryEngine::new(dir.path().to_path_buf());
+        for keywords in [vec!["张三", "北京", "banana"], vec!["阿里巴巴", "apple", "北京"]] {
+            engine
+                .remember(RememberArgs {
+                    namespace: "u1/p1".to_string(),
+                    keywords: keywords.into_iter().map(|x| x.to_string()).collect(),
+                    slice: "s".to_string(),
+                    diary: "d".to_string(),
+                    ..Default::default()
+                })
+                .expect("remember");
+        }
+
+        let mut list = |sort: &str| -> Vec<String> {
+            let out = engine
+                .keywords_list("u1/p1".to_string(), KeywordSort::parse(sort).unwrap())
+                .expect("list");
+            assert_eq!(out["data"]["sort"], KeywordSort::parse(sort).unwrap().as_str());
+            serde_json::from_value(out["data"]["keywords"].clone()).unwrap()
+        };
+        assert_eq!(list("length"), ["北京", "张三", "阿里巴巴", "apple", "banana"]);
+        assert_eq!(list("alpha"), ["apple", "banana", "北京", "张三", "阿里巴巴"]);
+        assert_eq!(list("pinyin"), ["阿里巴巴", "北京", "张三", "apple", "banana"]);
+        assert_eq!(list("frequency")[0], "北京");
+        assert!(KeywordSort::parse("random").unwrap_err().contains("sort 不支持"));
+
+        let global = engine.keywords_list_global(KeywordSort::Pinyin).expect("global");
+        let names: Vec<&str> = global["data"]["keywords"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|x| x["keyword"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, ["阿里巴巴", "北京", "张三", "apple", "banana"]);
+    }
+
+    #[test]
+    fn keywords_audit_should_report_junk_with_affected_ids() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let mut engine = MemoryEngine::new(dir.path().to_path_buf());
+        let mut ids = Vec::new();
+        for keywords in [vec!["release", "x"], vec!["release", "部署"], vec!["releases", "部署"]] {
+            let out = engine
+                .remember(RememberArgs {
+                    namespace: "u1/p1".to_string(),
+                    keywords: keywords.into_iter().map(|x| x.to_string()).collect(),
+                    slice: "s".to_string(),
+                    diary: "d".to_string(),
+                    ..Default::default()
+                })
+                .expect("remember");
+            ids.push(out["data"]["id"].as_str().unwrap().to_string());
+        }
+
+        let out = engine.keywords_audit("u1/p1".to_string(), 100).expect("audit");
+        let data = &out["data"];
+        assert_eq!(data["vocabulary"], 4);
+        assert_eq!(data["flagged"], 2);
+        assert_eq!(data["by_action"], json!({ "merge": 1, "delete": 1 }));
+        assert_eq!(data["findings"][0]["keyword"], "releases");
+        assert_eq!(data["findings"][0]["suggestion"], json!({ "action": "merge", "into": "release" }));
+        assert_eq!(data["findings"][0]["ids"], json!([ids[2]]));
+        assert_eq!(data["findings"][1]["keyword"], "x");
+        assert_eq!(data["findings"][1]["reasons"], json!(["single_char", "one_off"]));
+        assert!(out["content"][0]["text"].as_str().unwrap().contains("releases (1) → 合并到 release"));
+
+        let limited = engine.keywords_audit("u1/p1".to_string(), 1).expect("audit");
+        assert_eq!(limited["data"]["truncated"], true);
+        assert_eq!(limited["data"]["findings"].as_array().map(Vec::len), Some(1));
+    }
+
+    #[test]
+    fn recall_byte_budget_should_cut_page_and_continue_with_cursor() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let mut engine = MemoryEngine::new(dir.path().to_path_buf());
+        for i in 0..5 {
+            engine
+                .remember(RememberArgs {
+                    namespace: "u1/p1".to_string(),
+                    keywords: vec!["预算".to_string()],
+                    slice: format!("{i}{}", "长".repeat(100)),
+                    diary: "d".to_string(),
+                    ..Default::default()
+                })
+                .expect("remember");
+        }
+        let page = |engine: &mut MemoryEngine, cursor: Option<String>, max_bytes: usize| {
+            engine
+                .recall(RecallArgs {
+                    namespace: "u1/p1".to_string(),
+                    limit: 50,
+                    max_bytes: Some(max_bytes),
+                    cursor,
+                    ..Default::default()
+                })
+                .expect("recall")
+        };
+
+        let mut seen: Vec<String> = Vec::new();
+        let mut cursor = None;
+        loop {
+            let out = page(&mut engine, cursor, 700);
+            let items = out["data"]["items"].as_array().unwrap();
+            assert!(!items.is_empty() && items.len() <= 2);
+            seen.extend(items.iter().map(|x| x["id"].as_str().unwrap().to_string()));
+            cursor = out["data"]["next_cursor"].as_str().map(str::to_string);
+            if cursor.is_none() {
+                break;
+            }
+        }
+        assert_eq!(seen.len(), 5);
+        assert_eq!(seen.iter().collect::<std::collections::HashSet<_>>().len(), 5);
+
+        // 单条超出预算时仍返回 1 条，保证翻页能前进。
+        let out = page(&mut engine, None, 1);
+        assert_eq!(out["data"]["items"].as_array().unwrap().len(), 1);
+        assert!(out["data"]["next_cursor"].is_string());
+    }
+
+    #[test]
+    fn recall_should_track_access_counts_without_bumping_generation() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let mut engine = MemoryEngine::new(dir.path().to_path_buf());
+        let mut ids: Vec<String> = Vec::new();
+        for keyword in ["数据库", "数据库", "部署"] {
+            let out = engine
+                .remember(RememberArgs {
+                    namespace: "u1/p1".to_string(),
+                    keywords: vec![keyword.to_string()],
+                    slice: keyword.to_string(),
+                    diary: "d".to_string(),
+                    ..Default::default()
+                })
+                .expect("remember");
+            ids.push(out["data"]["id"].as_str().unwrap().to_string());
+        }
+        let recall = |engine: &mut MemoryEngine| {
+            engine
+                .recall(RecallArgs {
+                    namespace: "u1/p1".to_string(),
+                    keywords: vec!["数据库".to_string()],
+                    limit: 20,
+                    ..Default::default()
+                })
+                .expect("recall")
+        };
+
+        let first = recall(&mut engine);
+        let item = &first["data"]["items"][0];
+        assert!(item.get("recall_count").is_none() && item.get("last_recalled_at").is_none());
+        let etag = first["data"]["etag"].clone();
+
+        let second = recall(&mut engine);
+        assert_eq!(second["data"]["etag"], etag, "访问统计不应改变 generation");
+        for item in second["data"]["items"].as_array().unwrap() {
+            assert_eq!(item["recall_count"], 1);
+            assert!(item["last_recalled_at"].is_string());
+        }
+
+        // 统计落盘（flush 或达到批量间隔时）：新引擎实例可读到。
+        engine.flush().expect("flush");
+        let mut reopened = MemoryEngine::new(dir.path().to_path_buf());
+        let out = reopened.least_recalled("u1/p1".to_string(), 2).expect("least_recalled");
+        let items = out["data"]["items"].as_array().unwrap();
+        assert_eq!(items[0]["id"], ids[2].as_str());
+        assert!(items[0].get("recall_count").is_none());
+        assert_eq!(items[1]["recall_count"], 2);
+        assert_eq!(out["data"]["has_more"], true);
+    }
+
+    #[test]
+    fn sync_should_merge_two_stores_and_resolve_conflicts_by_change_time() {
+        let desktop_dir = tempfile::TempDir::new().expect("create temp dir");
+        let laptop_dir = tempfile::TempDir::new().expect("create temp dir");
+        let mut desktop = MemoryEngine::new(desktop_dir.path().to_path_buf());
+        let mut laptop = MemoryEngine::new(laptop_dir.path().to_path_buf());
+        let remember = |engine: &mut MemoryEngine, slice: &str| {
+            let out = engine
+                .remember(RememberArgs {
+                    namespace: "u1/p1".to_string(),
+                    keywords: vec!["同步".to_string()],
+                    slice: slice.to_string(),
+                    diary: "d".to_string(),
+                    ..Default::default()
+                })
+                .expect("remember");
+            out["data"]["id"].as_str().unwrap().to_string()
+        };
+        let a = remember(&mut desktop, "台式机写入");
+        let b = remember(&mut laptop, "笔记本写入");
+
+        let out = desktop.sync(laptop_dir.path(), None, false).expect("sync");
+        assert_eq!(out["data"]["namespaces"][0]["pulled"], 1);
+        assert_eq!(out["data"]["namespaces"][0]["pushed"], 1);
+        let mut laptop = MemoryEngine::new(laptop_dir.path().to_path_buf());
+        assert!(laptop.get("u1/p1".to_string(), a.clone(), None).is_ok());
+        assert!(desktop.get("u1/p1".to_string(), b.clone(), None).is_ok());
+
+        // 笔记本更新 a、台式机删除 b：同步后两侧都取最后的变更。
+        laptop
+            .update(
+                "u1/p1".to_string(),
+                UpdateArgs {
+                    id: a.clone(),
+                    revision: 1,
+                    slice: Some("笔记本改过".to_string()),
+                    ..Default::default()
+                },
+            )
+            .expect("update");
+        desktop
+            .forget(ForgetArgs {
+                namespace: "u1/p1".to_string(),
+                id: b.clone(),
+                revision: 1,
+            })
+            .expect("forget");
+
+        let preview = desktop.sync(laptop_dir.path(), Some("u1/p1"), true).expect("dry run");
+        let conflicts = &preview["data"]["namespaces"][0]["conflicts"];
+        assert_eq!(conflicts.as_array().unwrap().len(), 2);
+        assert_eq!(desktop.get("u1/p1".to_string(), a.clone(), None).unwrap()["data"]["item"]["slice"], "台式机写入");
+
+        desktop.sync(laptop_dir.path(), Some("u1/p1"), false).expect("sync");
+        let mut laptop = MemoryEngine::new(laptop_dir.path().to_path_buf());
+        assert_eq!(desktop.get("u1/p1".to_string(), a.clone(), None).unwrap()["data"]["item"]["slice"], "笔记本改过");
+        assert!(laptop.get("u1/p1".to_string(), b, None).is_err());
+
+        // 已一致时再次同步无需交换任何记录。
+        let out = laptop.sync(desktop_dir.path(), None, false).expect("sync");
+        assert_eq!(out["data"]["namespaces"][0]["pulled"], 0);
+        assert_eq!(out["data"]["namespaces"][0]["pushed"], 0);
+        assert!(laptop.sync(laptop_dir.path(), None, false).is_err());
+    }
+
+
+    #[test]
+    fn sync_should_not_resurrect_memories_forgotten_and_compacted() {
+        let desktop_dir = tempfile::TempDir::new().expect("create temp dir");
+        let laptop_dir = tempfile::TempDir::new().expect("create temp dir");
+        let mut desktop = MemoryEngine::new(desktop_dir.path().to_path_buf());
+        let out = desktop
+            .remember(RememberArgs {
+                namespace: "u1/p1".to_string(),
+                keywords: vec!["同步".to_string()],
+                slice: "将被删除".to_string(),
+                diary: "d".to_string(),
+                ..Default::default()
+            })
+            .expect("remember");
+        let id = out["data"]["id"].as_str().unwrap().to_string();
+        desktop.sync(laptop_dir.path(), None, false).expect("sync");
+
+        desktop
+            .forget(ForgetArgs {
+                namespace: "u1/p1".to_string(),
+                id: id.clone(),
+                revision: 1,
+            })
+            .expect("forget");
+        desktop.compact("u1/p1".to_string()).expect("compact");
+
+        let out = desktop.sync(laptop_dir.path(), None, false).expect("sync");
+        assert_eq!(out["data"]["namespaces"][0]["pulled"], 0);
+        assert_eq!(out["data"]["namespaces"][0]["pushed"], 1);
+        assert!(desktop.get("u1/p1".to_string(), id.clone(), None).is_err());
+        let mut laptop = MemoryEngine::new(laptop_dir.path().to_path_buf());
+        assert!(laptop.get("u1/p1".to_string(), id, None).is_err());
+    }
+    #[test]
+    fn restore_from_backup_should_replace_namespace_and_reject_tampered_snapshot() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let backups = tempfile::TempDir::new().expect("create temp dir");
+        let mut engine = MemoryEngine::new(dir.path().to_path_buf());
+        let remember = |engine: &mut MemoryEngine, namespace: &str, slice: &str| {
+            let out = engine
+                .remember(RememberArgs {
+                    namespace: namespace.to_string(),
+                    keywords: vec!["备份".to_string()],
+                    slice: slice.to_string(),
+                    diary: "d".to_string(),
+                    ..Default::default()
+                })
+                .expect("remember");
+            out["data"]["id"].as_str().unwrap().to_string()
+        };
+        let kept = remember(&mut engine, "u1/p1", "备份前");
+        remember(&mut engine, "u1/p2", "另一个项目");
+        let out = engine
+            .backup(None, backups.path(), Retention::default())
+            .expect("backup");
+        assert_eq!(out["data"]["namespaces"], json!(["u1/p1", "u1/p2"]));
+        let snapshot = PathBuf::from(out["data"]["snapshot"].as_str().unwrap());
+
+        let later = remember(&mut engine, "u1/p1", "备份后");
+        engine
+            .forget(ForgetArgs {
+                namespace: "u1/p1".to_string(),
+                id: kept.clone(),
+                revision: 1,
+            })
+            .expect("forget");
+
+        // 给出备份输出目录时取最新快照；只恢复指定的 namespace。
+        let out = engine.restore_from(backups.path(), Some("u1/p1")).expect("restore");
+        assert_eq!(out["data"]["namespaces"], json!(["u1/p1"]));
+        assert!(engine.get("u1/p1".to_string(), kept.clone(), None).is_ok());
+        assert!(engine.get("u1/p1".to_string(), later, None).is_err());
+        assert!(!dir.path().join(".restore-tmp").exists() && !dir.path().join(".restore-old").exists());
+
+        // 快照文件被改动时拒绝恢复，存储保持原样。
+        let data = snapshot.join("u1/p1/memories.jsonl");
+        fs::write(&data, fs::read_to_string(&data).unwrap().replace("备份前", "篡改后")).unwrap();
+        let err = engine.restore_from(&snapshot, None).unwrap_err();
+        assert!(err.contains("u1/p1/memories.jsonl"), "{err}");
+        assert!(engine.get("u1/p1".to_string(), kept, None).is_ok());
+    }
+}
@@ -1,5 +1,27 @@
+use crate::memory::lang::Lang;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+
+/// 关键字权重范围：未指定时为 1（附带提及），数值越大越是该记忆的核心主题。
+pub const KEYWORD_WEIGHT_MAX: u8 = 10;
+/// recall 未指定 limit 时的条数。
+pub const RECALL_DEFAULT_LIMIT: usize = 20;
+/// recall 单页条数上限的默认值（可由 `MEMORY_RECALL_MAX_LIMIT` 与 namespace 的 settings.json 调整）。
+pub const RECALL_MAX_LIMIT: usize = 100;
+/// recall 中置顶记忆最多占用的条数（可由 `MEMORY_RECALL_PINNED_MAX` 调整，0 表示不置顶）。
+pub const RECALL_PINNED_MAX: usize = 5;
+/// decay 排序的默认半衰期（天，可由 `MEMORY_DECAY_HALF_LIFE_DAYS` 调整）：记忆每过一个半衰期得分减半。
+pub const DECAY_HALF_LIFE_DAYS: f64 = 30.0;
+/// 重要度衰减（maintain）的默认半衰期（天，可由 `MEMORY_IMPORTANCE_HALF_LIFE_DAYS` 调整）。
+pub const IMPORTANCE_HALF_LIFE_DAYS: f64 = 90.0;
+/// 可配置的单页条数上限的最大值：更大的批量读取请用分页或 export。
+pub const RECALL_LIMIT_CEILING: usize = 10_000;
+/// metadata 序列化后的最大字节数：只用于挂载少量结构化引用（工单号、文件路径、链接等）。
+pub const METADATA_MAX_BYTES: usize = 8 * 1024;
+/// 默认 namespace 的环境变量：设置后 tool 参数可省略 `namespace`（单用户部署）。
+pub const DEFAULT_NAMESPACE_ENV: &str = "MEMORY_DEFAULT_NAMESPACE";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MemoryItem {
@@ -9,35 +31,356 @@ pub struct MemoryItem {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub occurred_at: Option<String>,
     pub keywords: Vec<String>,
+    /// 关键字权重（仅记录权重不为 1 的关键字）。
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub keyword_weights: BTreeMap<String, u8>,
+    /// 组织用标签（如 `project-alpha`、`confidential`，已归一化为小写）：单独索引，只用于过滤，不参与关键字召回。
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<MemoryKind>,
     pub slice: String,
     pub diary: String,
+    /// diary 转存为 blob 时的引用（`sha256:<hex>`，此时 diary 为空）；读取时透明还原。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub diary_ref: Option<String>,
+    /// diary 以 zstd 压缩存储时的内容（base64，此时 diary 为空）；读取时透明解压。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub diary_zstd: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub importance: Option<u8>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub source: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<MemorySource>,
+    /// 调用方附加的自由结构化数据（如 `{"ticket":"ERP-12","path":"src/a.rs"}`）；不进入索引，可用 `metadata.key=value` 查询过滤。
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub metadata: Map<String, Value>,
+    /// 相关记忆的 id（单向链接，可用 get 跟随）。
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub relates_to: Vec<String>,
+    /// 被本记忆取代的旧记忆 id：旧记忆默认不再出现在 recall 结果中。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub supersedes: Option<String>,
+    /// 过期时间（RFC3339，或 YYYY-MM-DD 表示当天结束）：过期后不再出现在 recall 结果中，可用 purge_expired 清除。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<String>,
+    /// 置顶：关键字召回时排在最前，无关键字召回时不受时间顺序影响、始终优先返回（条数有上限）。
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pinned: bool,
+    /// 已归档：默认不再出现在 recall 结果中（`include_archived` 时返回），仍可 get。
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub archived: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<String>,
+    /// 修订号：新记录为 1，每次 update 递增；update/forget 需携带以做乐观并发校验。
+    #[serde(default = "default_revision")]
+    pub revision: u32,
+    /// 写入该版本的 agent 身份（`MEMORY_WRITER` 或 MCP 客户端名称）；未配置时缺省。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub written_by: Option<String>,
+    /// 内容校验和（`sha256:<hex>`，覆盖除自身外的全部字段）；旧数据可能缺失。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<String>,
 }
 
-#[derive(Debug, Clone)]
+fn default_revision() -> u32 {
+    1
+}
+
+impl MemoryItem {
+    pub const CHECKSUM_PREFIX: &'static str = "sha256:";
+
+    /// 计算内容校验和（序列化时去掉 checksum 字段本身，字段顺序由结构体定义固定）。
+    pub fn compute_checksum(&self) -> String {
+        let mut unsigned = self.clone();
+        unsigned.checksum = None;
+        let bytes = serde_json::to_vec(&unsigned).unwrap_or_default();
+
+        let digest = Sha256::digest(&bytes);
+        let hex: String = digest.iter().map(|b| format!("{b:02x}")).collect();
+        format!("{}{hex}", Self::CHECKSUM_PREFIX)
+    }
+
+    /// 写入前调用：按当前内容重新生成校验和。
+    pub fn seal(&mut self) {
+        self.checksum = Some(self.compute_checksum());
+    }
+
+    /// 校验内容是否与校验和一致；无校验和（旧数据）时返回 None。
+    pub fn verify_checksum(&self) -> Option<bool> {
+        self.checksum
+            .as_deref()
+            .map(|expected| expected == self.compute_checksum())
+    }
+}
+
+/// 记忆来源：指向原始对话/文档的结构化引用（类型、外部 id、显示名、链接）。
+///
+/// 兼容旧数据：来源为字符串时读作仅含 `label` 的来源；仅有 `label` 时也序列化回字符串，
+/// 因此旧记录的 checksum 与输出形态保持不变。
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "SourceRepr", into = "SourceRepr")]
+pub struct MemorySource {
+    /// 来源类型（小写），如 `conversation`、`document`、`distill`。
+    pub kind: Option<String>,
+    /// 来源系统中的 id（会话 id、文档 id 等）。
+    pub id: Option<String>,
+    pub label: Option<String>,
+    /// 回到原始内容的链接（需带 scheme，如 `https://`）。
+    pub url: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum SourceRepr {
+    Text(String),
+    Object {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        kind: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        label: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        url: Option<String>,
+    },
+}
+
+impl From<SourceRepr> for MemorySource {
+    fn from(repr: SourceRepr) -> Self {
+        match repr {
+            SourceRepr::Text(label) => Self::label(label),
+            SourceRepr::Object { kind, id, label, url } => Self { kind, id, label, url },
+        }
+    }
+}
+
+impl From<MemorySource> for SourceRepr {
+    fn from(source: MemorySource) -> Self {
+        match source {
+            MemorySource {
+                kind: None,
+                id: None,
+                label: Some(label),
+                url: None,
+            } => SourceRepr::Text(label),
+            MemorySource { kind, id, label, url } => SourceRepr::Object { kind, id, label, url },
+        }
+    }
+}
+
+impl MemorySource {
+    const FIELDS: [&'static str; 4] = ["kind", "id", "label", "url"];
+
+    pub fn label(label: impl Into<String>) -> Self {
+        Self {
+            label: Some(label.into()),
+            ..Default::default()
+        }
+    }
+
+    /// 解析 tool 入参：字符串视为 `label`，对象取 kind/id/label/url；字段去空白，全部为空时返回 None。
+    pub fn from_json(v: Option<&Value>) -> Result<Option<Self>, String> {
+        let source = match v {
+            None | Some(Value::Null) => return Ok(None),
+            Some(Value::String(text)) => Self::label(text.as_str()),
+            Some(Value::Object(map)) => {
+                if let Some(key) = map.keys().find(|k| !Self::FIELDS.contains(&k.as_str())) {
+                    return Err(format!("source 不支持字段：{key}（仅支持 kind/id/label/url）"));
+                }
+                let field = |key: &str| match map.get(key) {
+                    None | Some(Value::Null) => Ok(None),
+                    Some(Value::String(x)) => Ok(Some(x.clone())),
+                    Some(_) => Err(format!("source.{key} 必须是字符串")),
+                };
+                Self {
+                    kind: field("kind")?,
+                    id: field("id")?,
+                    label: field("label")?,
+                    url: field("url")?,
+                }
+            }
+            Some(_) => return Err("source 必须是字符串或 {kind,id,label,url} 对象".to_string()),
+        };
+        source.normalized()
+    }
+
+    /// 去除首尾空白、空字段置空，kind 转小写并校验 url；全部为空时返回 None。
+    pub fn normalized(self) -> Result<Option<Self>, String> {
+        let clean = |x: Option<String>| x.map(|x| x.trim().to_string()).filter(|x| !x.is_empty());
+        let out = Self {
+            kind: clean(self.kind).map(|x| x.to_lowercase()),
+            id: clean(self.id),
+            label: clean(self.label),
+            url: clean(self.url),
+        };
+        if let Some(url) = out.url.as_deref() {
+            let scheme = url.split_once(':').map(|x| x.0).unwrap_or_default();
+            let valid = scheme.chars().next().is_some_and(|c| c.is_ascii_alphabetic())
+                && scheme.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+            if !valid {
+                return Err(format!("source.url 必须带 scheme（如 https://）：{url}"));
+            }
+        }
+        Ok((out != Self::default()).then_some(out))
+    }
+
+    /// 可修改的文本字段（脱敏使用）。
+    pub fn texts_mut(&mut self) -> impl Iterator<Item = &mut String> {
+        [&mut self.label, &mut self.id, &mut self.url].into_iter().flatten()
+    }
+
+    /// 单行展示：`label [kind:id] url`（缺失部分省略）；旧的字符串来源原样返回。
+    pub fn describe(&self) -> String {
+        let tag = match (self.kind.as_deref(), self.id.as_deref()) {
+            (Some(kind), Some(id)) => Some(format!("[{kind}:{id}]")),
+            (Some(x), None) | (None, Some(x)) => Some(format!("[{x}]")),
+            (None, None) => None,
+        };
+        [self.label.clone(), tag, self.url.clone()]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// 删除标记（tombstone）：追加写入 memories.jsonl，索引重建时据此移除目标记忆。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tombstone {
+    pub op: String,
+    pub id: String,
+    pub namespace: String,
+    pub deleted_at: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub written_by: Option<String>,
+}
+
+impl Tombstone {
+    pub const OP_DELETE: &'static str = "delete";
+}
+
+/// memories.jsonl / 导出文档中一条记忆记录的 JSON Schema（与 `MemoryItem` 字段一一对应）。
+pub fn memory_item_schema() -> Value {
+    json!({
+        "type": "object",
+        "required": ["id", "namespace", "recorded_at", "keywords", "slice", "diary"],
+        "properties": {
+            "id": { "type": "string", "description": "记忆 id（UUID）；同一 id 的后续行为该记忆的新修订。" },
+            "namespace": { "type": "string", "description": "命名空间：{userId}/{projectId}。" },
+            "recorded_at": { "type": "string", "description": "记录时间（RFC3339，UTC）。" },
+            "occurred_at": { "type": "string", "description": "事件发生时间（RFC3339 或 YYYY-MM-DD）。" },
+            "keywords": {
+                "type": "array",
+                "items": { "type": "string" },
+                "description": "关键字（已归一化为小写）。"
+            },
+            "keyword_weights": {
+                "type": "object",
+                "additionalProperties": { "type": "integer", "minimum": 2, "maximum": KEYWORD_WEIGHT_MAX },
+                "description": "关键字权重（仅包含权重不为 1 的关键字）。"
+            },
+            "tags": {
+                "type": "array",
+                "items": { "type": "string" },
+                "description": "组织用标签（已归一化为小写）；单独索引，只用于过滤。"
+            },
+            "kind": { "enum": MemoryKind::names(), "description": "记忆类型。" },
+            "slice": { "type": "string", "description": "重要内容切片。" },
+            "diary": { "type": "string", "description": "AI 日记（转存为 blob 时为空）。" },
+            "diary_ref": { "type": "string", "description": "大篇幅 diary 的 blob 引用（sha256:<hex>，对应 namespace 目录下 blobs/ 中的文件）。" },
+            "diary_zstd": { "type": "string", "description": "zstd 压缩后的 diary（base64；MEMORY_COMPRESS=zstd 写入，此时 diary 为空）。" },
+            "importance": { "type": "integer", "minimum": 1, "maximum": 5 },
+            "source": source_schema(),
+            "metadata": { "type": "object", "description": "调用方附加的自由结构化数据。" },
+            "relates_to": { "type": "array", "items": { "type": "string" }, "description": "相关记忆的 id。" },
+            "supersedes": { "type": "string", "description": "被本记忆取代的旧记忆 id。" },
+            "expires_at": { "type": "string", "description": "过期时间（RFC3339 或 YYYY-MM-DD）；过期后不再参与 recall。" },
+            "pinned": { "type": "boolean", "default": false, "description": "置顶：recall 时优先返回。" },
+            "archived": { "type": "boolean", "default": false, "description": "已归档：默认不参与 recall。" },
+            "updated_at": { "type": "string", "description": "最近一次 update 的时间（RFC3339，UTC）。" },
+            "revision": { "type": "integer", "minimum": 1, "default": 1, "description": "修订号：新记录为 1，每次 update 递增。" },
+            "written_by": { "type": "string", "description": "写入该版本的 agent 身份。" },
+            "checksum": {
+                "type": "string",
+                "pattern": "^sha256:[0-9a-f]{64}$",
+                "description": "内容校验和，覆盖除自身外的全部字段。"
+            }
+        }
+    })
+}
+
+/// 来源字段的 JSON Schema：旧格式字符串或结构化对象。
+pub fn source_schema() -> Value {
+    json!({
+        "description": "来源（可选）：字符串（仅显示名，兼容旧格式）或 {kind,id,label,url} 对象，用于按来源过滤并回链到原始对话/文档。",
+        "oneOf": [
+            { "type": "string" },
+            {
+                "type": "object",
+                "additionalProperties": false,
+                "properties": {
+                    "kind": { "type": "string", "description": "来源类型（小写），如 conversation / document / distill。" },
+                    "id": { "type": "string", "description": "来源系统中的 id（会话 id、文档 id 等）。" },
+                    "label": { "type": "string", "description": "显示名。" },
+                    "url": { "type": "string", "description": "回到原始内容的链接（需带 scheme）。" }
+                }
+            }
+        ]
+    })
+}
+
+/// 删除标记（tombstone）记录的 JSON Schema。
+pub fn tombstone_schema() -> Value {
+    json!({
+        "type": "object",
+        "required": ["op", "id", "namespace", "deleted_at"],
+        "properties": {
+            "op": { "const": Tombstone::OP_DELETE },
+            "id": { "type": "string", "description": "被删除记忆的 id。" },
+            "namespace": { "type": "string" },
+            "deleted_at": { "type": "string", "description": "删除时间（RFC3339，UTC）。" },
+            "written_by": { "type": "string" }
+        }
+    })
+}
+
+#[derive(Debug, Clone, Default)]
 pub struct RememberArgs {
     pub namespace: String,
     pub keywords: Vec<String>,
+    /// 关键字权重（关键字 -> 1~10）；未列出的关键字权重为 1。
+    pub keyword_weights: BTreeMap<String, u8>,
+    pub tags: Vec<String>,
+    pub kind: Option<MemoryKind>,
     pub slice: String,
     pub diary: String,
     pub occurred_at: Option<String>,
     pub importance: Option<u8>,
-    pub source: Option<String>,
+    pub source: Option<MemorySource>,
+    pub metadata: Map<String, Value>,
+    pub relates_to: Vec<String>,
+    pub supersedes: Option<String>,
+    pub expires_at: Option<String>,
+    pub pinned: bool,
 }
 
 impl RememberArgs {
     pub fn from_json(v: &Value) -> Result<Self, String> {
-        let namespace = get_required_string(v, "namespace")?;
-        let keywords = get_string_array(v, "keywords")?;
+        let namespace = get_namespace(v)?;
+        let (keywords, keyword_weights) = get_weighted_keywords(v, "keywords")?;
+        let tags = get_optional_string_array(v, "tags")?.unwrap_or_default();
+        let kind = get_optional_kind(v)?;
         let slice = get_required_string(v, "slice")?;
         let diary = get_required_string(v, "diary")?;
 
         let occurred_at = get_optional_string(v, "occurred_at")?;
         let importance = get_optional_u8(v, "importance")?;
-        let source = get_optional_string(v, "source")?;
+        let source = MemorySource::from_json(v.get("source"))?;
+        let metadata = get_optional_metadata(v)?.unwrap_or_default();
+        let relates_to = get_optional_string_array(v, "relates_to")?.unwrap_or_default();
+        let supersedes = get_optional_string(v, "supersedes")?;
+        let expires_at = get_optional_string(v, "expires_at")?;
+        let pinned = get_optional_bool(v, "pinned")?.unwrap_or(false);
 
         if let Some(n) = importance {
             if !(1..=5).contains(&n) {
@@ -48,55 +391,592 @@ impl RememberArgs {
         Ok(Self {
             namespace,
             keywords,
+            keyword_weights,
+            tags,
+            kind,
             slice,
             diary,
             occurred_at,
             importance,
             source,
+            metadata,
+            relates_to,
+            supersedes,
+            expires_at,
+            pinned,
         })
     }
 }
 
+/// 更新已有记忆：仅覆盖提供的字段，其余字段沿用当前版本。
+#[derive(Debug, Clone, Default)]
+pub struct UpdateArgs {
+    pub id: String,
+    /// 调用方读取时看到的修订号；与当前版本不一致时拒绝更新。
+    pub revision: u32,
+    pub keywords: Option<Vec<String>>,
+    /// 随 keywords 一起整体替换；未提供 keywords 时忽略。
+    pub keyword_weights: BTreeMap<String, u8>,
+    /// 整体替换标签（空数组表示清空）。
+    pub tags: Option<Vec<String>>,
+    pub kind: Option<MemoryKind>,
+    pub slice: Option<String>,
+    pub diary: Option<String>,
+    pub occurred_at: Option<String>,
+    pub importance: Option<u8>,
+    pub source: Option<MemorySource>,
+    /// 整体替换 metadata（空对象表示清空）。
+    pub metadata: Option<Map<String, Value>>,
+    /// 整体替换相关记忆链接（空数组表示清空）。
+    pub relates_to: Option<Vec<String>>,
+    /// 替换被取代的记忆 id（空字符串表示清除）。
+    pub supersedes: Option<String>,
+    /// 替换过期时间（空字符串表示清除，即永不过期）。
+    pub expires_at: Option<String>,
+    pub pinned: Option<bool>,
+    pub archived: Option<bool>,
+}
+
+impl UpdateArgs {
+    pub fn from_json(v: &Value) -> Result<Self, String> {
+        let id = get_required_string(v, "id")?;
+        let revision = get_required_revision(v)?;
+        let (keywords, keyword_weights) = match v.get("keywords") {
+            None | Some(Value::Null) => (None, BTreeMap::new()),
+            Some(_) => {
+                let (keywords, weights) = get_weighted_keywords(v, "keywords")?;
+                (Some(keywords), weights)
+            }
+        };
+        let tags = get_optional_string_array(v, "tags")?;
+        let kind = get_optional_kind(v)?;
+        let slice = get_optional_string(v, "slice")?;
+        let diary = get_optional_string(v, "diary")?;
+        let occurred_at = get_optional_string(v, "occurred_at")?;
+        let importance = get_optional_u8(v, "importance")?;
+        let source = MemorySource::from_json(v.get("source"))?;
+        let metadata = get_optional_metadata(v)?;
+        let relates_to = get_optional_string_array(v, "relates_to")?;
+        let supersedes = match v.get("supersedes") {
+            None | Some(Value::Null) => None,
+            Some(Value::String(s)) => Some(s.trim().to_string()),
+            Some(_) => return Err("supersedes 必须是字符串".to_string()),
+        };
+        let expires_at = match v.get("expires_at") {
+            None | Some(Value::Null) => None,
+            Some(Value::String(s)) => Some(s.trim().to_string()),
+            Some(_) => return Err("expires_at 必须是字符串".to_string()),
+        };
+        let pinned = get_optional_bool(v, "pinned")?;
+        let archived = get_optional_bool(v, "archived")?;
+
+        if let Some(n) = importance {
+            if !(1..=5).contains(&n) {
+                return Err("importance 必须在 1~5".to_string());
+            }
+        }
+
+        Ok(Self {
+            id,
+            revision,
+            keywords,
+            keyword_weights,
+            tags,
+            kind,
+            slice,
+            diary,
+            occurred_at,
+            importance,
+            source,
+            metadata,
+            relates_to,
+            supersedes,
+            expires_at,
+            pinned,
+            archived,
+        })
+    }
+}
+
+/// 归档 / 取消归档：按 id 列表或时间范围（occurred_at ?? recorded_at）批量设置。
+#[derive(Debug, Clone, Default)]
+pub struct ArchiveArgs {
+    pub namespace: String,
+    pub ids: Vec<String>,
+    pub start: Option<String>,
+    pub end: Option<String>,
+    /// false 表示取消归档。
+    pub archived: bool,
+}
+
+impl ArchiveArgs {
+    pub fn from_json(v: &Value) -> Result<Self, String> {
+        let namespace = get_namespace(v)?;
+        let ids = get_optional_string_array(v, "ids")?.unwrap_or_default();
+        let start = get_optional_string(v, "start")?;
+        let end = get_optional_string(v, "end")?;
+        let archived = get_optional_bool(v, "archived")?.unwrap_or(true);
+        Ok(Self {
+            namespace,
+            ids,
+            start,
+            end,
+            archived,
+        })
+    }
+}
+
+/// 删除一条记忆（写入 tombstone）。
 #[derive(Debug, Clone)]
+pub struct ForgetArgs {
+    pub namespace: String,
+    pub id: String,
+    /// 调用方读取时看到的修订号；与当前版本不一致时拒绝删除。
+    pub revision: u32,
+}
+
+impl ForgetArgs {
+    pub fn from_json(v: &Value) -> Result<Self, String> {
+        let namespace = get_namespace(v)?;
+        let id = get_required_string(v, "id")?;
+        let revision = get_required_revision(v)?;
+        Ok(Self { namespace, id, revision })
+    }
+}
+
+/// keywords_complete 的匹配方式。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum KeywordMatchMode {
+    /// 关键字以输入开头（基于有序词表做范围扫描）。
+    #[default]
+    Prefix,
+    /// 关键字包含输入（子串）。
+    Contains,
+}
+
+impl KeywordMatchMode {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        match text.trim().to_lowercase().as_str() {
+            "" | "prefix" => Ok(Self::Prefix),
+            "contains" | "substring" => Ok(Self::Contains),
+            other => Err(format!("match 不支持：{other}（仅支持 prefix/contains）")),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Prefix => "prefix",
+            Self::Contains => "contains",
+        }
+    }
+}
+
+/// 记忆类型：帮助 agent 区分长期偏好与一次性事件，决定注入上下文的内容。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MemoryKind {
+    /// 客观事实（如“项目使用 PostgreSQL”）。
+    Fact,
+    /// 用户偏好（如“回答请用中文”）。
+    Preference,
+    /// 发生过的事件（如“周三完成了上线”）。
+    Event,
+    /// 待办任务。
+    Task,
+    /// 已做出的决策及理由。
+    Decision,
+}
+
+impl MemoryKind {
+    pub const ALL: [Self; 5] = [Self::Fact, Self::Preference, Self::Event, Self::Task, Self::Decision];
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let norm = text.trim().to_lowercase();
+        Self::ALL
+            .into_iter()
+            .find(|k| k.as_str() == norm)
+            .ok_or_else(|| format!("kind 不支持：{norm}（仅支持 fact/preference/event/task/decision）"))
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Fact => "fact",
+            Self::Preference => "preference",
+            Self::Event => "event",
+            Self::Task => "task",
+            Self::Decision => "decision",
+        }
+    }
+
+    /// tool 入参与记录 schema 中的枚举值。
+    pub fn names() -> Vec<&'static str> {
+        Self::ALL.iter().map(|k| k.as_str()).collect()
+    }
+}
+
+/// keywords_list / keywords_list_global 的排序方式。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum KeywordSort {
+    /// 字符数升序，同长度按引用条数降序、再按码位。
+    #[default]
+    Length,
+    /// 字典序（Unicode 码位）。
+    Alpha,
+    /// 中文按拼音排序（ICU 中文排序规则：汉字在前，其后拉丁字母按字母序）。
+    Pinyin,
+    /// 引用条数降序，同频按长度。
+    Frequency,
+}
+
+impl KeywordSort {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        match text.trim().to_lowercase().as_str() {
+            "" | "length" => Ok(Self::Length),
+            "alpha" | "alphabetical" => Ok(Self::Alpha),
+            "pinyin" | "collation" => Ok(Self::Pinyin),
+            "frequency" | "freq" => Ok(Self::Frequency),
+            other => Err(format!("sort 不支持：{other}（仅支持 length/alpha/pinyin/frequency）")),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Length => "length",
+            Self::Alpha => "alpha",
+            Self::Pinyin => "pinyin",
+            Self::Frequency => "frequency",
+        }
+    }
+}
+
+/// 关键字补全：namespace 为空时在全局词表中查找。
+#[derive(Debug, Clone, Default)]
+pub struct KeywordsCompleteArgs {
+    pub namespace: Option<String>,
+    /// 已归一化（trim + lowercase）的输入。
+    pub text: String,
+    pub mode: KeywordMatchMode,
+    pub limit: usize,
+}
+
+impl KeywordsCompleteArgs {
+    pub fn from_json(v: &Value) -> Result<Self, String> {
+        let namespace = get_optional_string(v, "namespace")?
+            .map(|x| x.trim().to_string())
+            .filter(|x| !x.is_empty());
+        let text = get_required_string(v, "text")?.to_lowercase();
+        let mode = match get_optional_string(v, "match")? {
+            Some(text) => KeywordMatchMode::parse(&text)?,
+            None => KeywordMatchMode::Prefix,
+        };
+        let limit = match get_optional_usize(v, "limit")?.unwrap_or(20) {
+            0 => 20,
+            n => n.min(100),
+        };
+
+        Ok(Self {
+            namespace,
+            text,
+            mode,
+            limit,
+        })
+    }
+}
+
+/// 事务中的单个步骤。
+#[derive(Debug, Clone)]
+pub enum TransactionStep {
+    Remember(RememberArgs),
+    Update(UpdateArgs),
+    Forget { id: String, revision: u32 },
+}
+
+/// 事务：同一 namespace 下的一组 remember/update/forget，要么全部生效，要么全部不生效。
+#[derive(Debug, Clone)]
+pub struct TransactionArgs {
+    pub namespace: String,
+    pub steps: Vec<TransactionStep>,
+}
+
+impl TransactionArgs {
+    pub fn from_json(v: &Value) -> Result<Self, String> {
+        let namespace = get_namespace(v)?;
+        let Some(raw_steps) = v.get("steps").and_then(|x| x.as_array()) else {
+            return Err("steps 必须是数组".to_string());
+        };
+        if raw_steps.is_empty() {
+            return Err("steps 不能为空".to_string());
+        }
+
+        let mut steps = Vec::with_capacity(raw_steps.len());
+        for (i, raw) in raw_steps.iter().enumerate() {
+            let Some(obj) = raw.as_object() else {
+                return Err(format!("steps[{i}] 必须是对象"));
+            };
+
+            // 步骤共享事务级 namespace；这里注入后复用各自的参数解析。
+            let mut obj = obj.clone();
+            obj.insert("namespace".to_string(), Value::String(namespace.clone()));
+            let step_value = Value::Object(obj);
+
+            let op = get_required_string(&step_value, "op")
+                .map_err(|e| format!("steps[{i}]: {e}"))?;
+            let step = match op.as_str() {
+                "remember" => RememberArgs::from_json(&step_value).map(TransactionStep::Remember),
+                "update" => UpdateArgs::from_json(&step_value).map(TransactionStep::Update),
+                "forget" => get_required_string(&step_value, "id").and_then(|id| {
+                    let revision = get_required_revision(&step_value)?;
+                    Ok(TransactionStep::Forget { id, revision })
+                }),
+                _ => Err(format!("不支持的 op：{op}（仅支持 remember/update/forget）")),
+            }
+            .map_err(|e| format!("steps[{i}]: {e}"))?;
+            steps.push(step);
+        }
+
+        Ok(Self { namespace, steps })
+    }
+}
+
+/// recall 输出格式。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RecallFormat {
+    /// 文本摘要 + data（结构化结果）。
+    #[default]
+    Full,
+    /// 每条一行 `date|importance|keywords|slice`，不返回 data，适合大量召回时节省 token。
+    Compact,
+}
+
+impl RecallFormat {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        match text.trim().to_lowercase().as_str() {
+            "" | "full" => Ok(Self::Full),
+            "compact" => Ok(Self::Compact),
+            other => Err(format!("format 不支持：{other}（仅支持 full/compact）")),
+        }
+    }
+}
+
+/// recall 排序方式（语义召回始终按相似度排序）。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RecallRanking {
+    /// 关键字命中权重 → 重要度 → 时间；无关键字时按时间倒序。
+    #[default]
+    Relevance,
+    /// 相关度 × 时间衰减（按半衰期指数衰减）× 访问加成（被召回次数越多越靠前）。
+    Decay,
+}
+
+impl RecallRanking {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        match text.trim().to_lowercase().as_str() {
+            "" | "relevance" => Ok(Self::Relevance),
+            "decay" => Ok(Self::Decay),
+            other => Err(format!("ranking 不支持：{other}（仅支持 relevance/decay）")),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Relevance => "relevance",
+            Self::Decay => "decay",
+        }
+    }
+}
+
+/// 校验 decay 半衰期（天）：必须是有限的正数。
+pub fn check_half_life_days(days: f64) -> Result<f64, String> {
+    if days.is_finite() && days > 0.0 {
+        Ok(days)
+    } else {
+        Err(format!("half_life_days 必须是正数：{days}"))
+    }
+}
+
+#[derive(Debug, Clone, Default)]
 pub struct RecallArgs {
     pub namespace: String,
     pub keywords: Vec<String>,
+    /// 仅返回同时带有全部这些标签的记忆。
+    pub tags: Vec<String>,
+    /// 仅返回属于这些类型之一的记忆（为空时不过滤）。
+    pub kinds: Vec<MemoryKind>,
     pub start: Option<String>,
     pub end: Option<String>,
     pub query: Option<String>,
+    /// 将 query 整体视为正则表达式（不解析布尔语法与时间表达式）。
+    pub regex: bool,
+    /// 语义召回：按 query 文本与记忆的向量相似度排序（需配置向量化后端）。
+    pub semantic: bool,
+    /// 单页条数；由引擎按生效的上限（服务端配置 / namespace 设置）截断。
     pub limit: usize,
+    /// 同时返回已被其它记忆取代（supersedes）的旧记忆；默认隐藏。
+    pub include_superseded: bool,
+    /// 同时返回已归档的记忆；默认跳过。
+    pub include_archived: bool,
+    /// 单页结果（items 序列化后）的字节预算：超出时提前截断本页并给出 next_cursor，至少返回 1 条。
+    pub max_bytes: Option<usize>,
     pub include_diary: bool,
+    pub format: RecallFormat,
+    /// 排序方式；None 时使用服务端默认（`MEMORY_RECALL_RANKING`，未配置时为 relevance）。
+    pub ranking: Option<RecallRanking>,
+    /// decay 排序的半衰期（天）；None 时使用服务端默认。
+    pub half_life_days: Option<f64>,
+    /// 条件召回：与当前 etag 相同时直接返回 not_modified，不再计算结果。
+    pub if_none_match: Option<String>,
+    /// 仅返回由指定 agent 写入（当前版本的 written_by）的记忆。
+    pub written_by: Option<String>,
+    /// 仅返回来源类型（source.kind，小写）等于该值的记忆。
+    pub source_kind: Option<String>,
+    /// 仅返回来源 id（source.id）等于该值的记忆。
+    pub source_id: Option<String>,
+    /// 分页：跳过前 offset 条命中结果。
+    pub offset: usize,
+    /// 分页：上一页返回的 next_cursor；提供时以其中的偏移为准（忽略 offset）。
+    pub cursor: Option<String>,
+}
+
+/// recall_global：在多个 namespace 中按同样的条件召回后合并结果。
+#[derive(Debug, Clone, Default)]
+pub struct RecallGlobalArgs {
+    /// namespace 模式（如 `u1/*`，两段各自可含 `*`）；None 时搜索存储根目录下的全部 namespace。
+    pub namespaces: Option<String>,
+    /// 各 namespace 共用的召回条件（namespace 字段由引擎逐个填入）。
+    pub recall: RecallArgs,
+}
+
+impl RecallGlobalArgs {
+    pub fn from_json(v: &Value) -> Result<Self, String> {
+        let namespaces = get_optional_string(v, "namespaces")?
+            .map(|x| x.trim().to_string())
+            .filter(|x| !x.is_empty());
+        let recall = RecallArgs::from_json_in(v, String::new())?;
+        Ok(Self { namespaces, recall })
+    }
+}
+
+/// recall 分页游标：绑定索引变更代数，数据在分页期间变化时拒绝继续翻页，保证结果确定。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecallCursor {
+    pub generation: u64,
+    pub offset: usize,
+}
+
+impl RecallCursor {
+    pub fn encode(&self) -> String {
+        format!("{}:{}", self.generation, self.offset)
+    }
+
+    pub fn decode(text: &str) -> Result<Self, String> {
+        let invalid = || format!("cursor 无效：{text}");
+        let (generation, offset) = text.trim().split_once(':').ok_or_else(invalid)?;
+        Ok(Self {
+            generation: generation.parse().map_err(|_| invalid())?,
+            offset: offset.parse().map_err(|_| invalid())?,
+        })
+    }
 }
 
 impl RecallArgs {
     pub fn from_json(v: &Value) -> Result<Self, String> {
-        let namespace = get_required_string(v, "namespace")?;
+        Self::from_json_in(v, get_namespace(v)?)
+    }
+
+    /// 缓存校验用的请求摘要：解析后的召回条件（namespace 取规范化后的值，不含 if_none_match）。
+    pub fn cache_scope(&self, namespace: &str) -> String {
+        let normalized = Self {
+            namespace: namespace.to_string(),
+            if_none_match: None,
+            ..self.clone()
+        };
+        format!("recall {normalized:?}")
+    }
+
+    /// 解析 namespace 以外的召回条件（namespace 由调用方给出）。
+    fn from_json_in(v: &Value, namespace: String) -> Result<Self, String> {
         let keywords = get_optional_string_array(v, "keywords")?.unwrap_or_default();
+        let tags = get_optional_string_array(v, "tags")?.unwrap_or_default();
+        let kinds = match v.get("kind") {
+            None | Some(Value::Null) => Vec::new(),
+            Some(Value::String(text)) => vec![MemoryKind::parse(text)?],
+            Some(_) => get_string_array(v, "kind")?
+                .iter()
+                .map(|x| MemoryKind::parse(x))
+                .collect::<Result<Vec<_>, _>>()?,
+        };
         let start = get_optional_string(v, "start")?;
         let end = get_optional_string(v, "end")?;
         let query = get_optional_string(v, "query")?;
+        let regex = v.get("regex").and_then(|x| x.as_bool()).unwrap_or(false);
+        let semantic = v.get("semantic").and_then(|x| x.as_bool()).unwrap_or(false);
 
-        let mut limit = get_optional_usize(v, "limit")?.unwrap_or(20);
-        if limit == 0 {
-            limit = 20;
-        }
-        if limit > 100 {
-            limit = 100;
-        }
+        let limit = match get_optional_usize(v, "limit")?.unwrap_or(RECALL_DEFAULT_LIMIT) {
+            0 => RECALL_DEFAULT_LIMIT,
+            n => n,
+        };
+        let max_bytes = get_optional_usize(v, "max_bytes")?.filter(|&n| n > 0);
+        let include_superseded = v
+            .get("include_superseded")
+            .and_then(|x| x.as_bool())
+            .unwrap_or(false);
+        let include_archived = v.get("include_archived").and_then(|x| x.as_bool()).unwrap_or(false);
 
         let include_diary = v
             .get("include_diary")
             .and_then(|x| x.as_bool())
             .unwrap_or(false);
 
+        let format = match get_optional_string(v, "format")? {
+            Some(text) => RecallFormat::parse(&text)?,
+            None => RecallFormat::Full,
+        };
+        let ranking = get_optional_string(v, "ranking")?
+            .map(|text| RecallRanking::parse(&text))
+            .transpose()?;
+        let half_life_days = match v.get("half_life_days") {
+            None | Some(Value::Null) => None,
+            Some(value) => {
+                let days = value.as_f64().ok_or_else(|| "half_life_days 必须是数字".to_string())?;
+                Some(check_half_life_days(days)?)
+            }
+        };
+
+        let if_none_match = get_optional_string(v, "if_none_match")?;
+        let offset = get_optional_usize(v, "offset")?.unwrap_or(0);
+        let cursor = get_optional_string(v, "cursor")?
+            .map(|x| x.trim().to_string())
+            .filter(|x| !x.is_empty());
+        let written_by = get_optional_string(v, "written_by")?
+            .map(|x| x.trim().to_string())
+            .filter(|x| !x.is_empty());
+        let source_kind = get_optional_string(v, "source_kind")?.map(|x| x.to_lowercase());
+        let source_id = get_optional_string(v, "source_id")?;
+
         Ok(Self {
             namespace,
             keywords,
+            tags,
+            kinds,
             start,
             end,
             query,
+            regex,
+            semantic,
             limit,
+            include_superseded,
+            include_archived,
+            max_bytes,
             include_diary,
+            format,
+            ranking,
+            half_life_days,
+            if_none_match,
+            written_by,
+            source_kind,
+            source_id,
+            offset,
+            cursor,
         })
     }
 }
@@ -104,35 +984,115 @@ impl RecallArgs {
 #[derive(Debug, Clone, Serialize)]
 pub struct RecallItemOut {
     pub id: String,
+    pub revision: u32,
     pub recorded_at: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub occurred_at: Option<String>,
     pub keywords: Vec<String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub keyword_weights: BTreeMap<String, u8>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub matched_keywords: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kind: Option<MemoryKind>,
     pub slice: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub diary: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub importance: Option<u8>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub source: Option<String>,
+    pub source: Option<MemorySource>,
+    #[serde(skip_serializing_if = "Map::is_empty")]
+    pub metadata: Map<String, Value>,
+    #[serde(skip_serializing_if = "MemoryLinks::is_empty")]
+    pub links: MemoryLinks,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub pinned: bool,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub archived: bool,
+    /// 本次之前被 recall 返回的次数与最近一次返回的时间（从未返回时省略）。
+    #[serde(skip_serializing_if = "is_zero")]
+    pub recall_count: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_recalled_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub written_by: Option<String>,
+    /// 语义召回的相似度（余弦，越大越相关）；其它召回方式不返回。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub score: Option<f32>,
+}
+
+/// recall 结果中一条记忆的关联：出链（relates_to / supersedes）与入链（superseded_by），可用 get 跟随。
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct MemoryLinks {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub relates_to: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub supersedes: Option<String>,
+    /// 取代了本记忆的新记忆 id（仅 include_superseded 时可能出现在结果中）。
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub superseded_by: Vec<String>,
+}
+
+impl MemoryLinks {
+    pub fn is_empty(&self) -> bool {
+        self.relates_to.is_empty() && self.supersedes.is_none() && self.superseded_by.is_empty()
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct RecallResult {
     pub total: usize,
     pub items: Vec<RecallItemOut>,
+    /// 读取失败而被跳过的记录（部分损坏时 recall 仍返回其余结果）。
+    pub warnings: Vec<String>,
+    /// 本页第一条结果在全部命中结果中的位置。
+    pub offset: usize,
+    /// 本页之后是否还有命中结果。
+    pub has_more: bool,
+    /// 下一页游标（has_more 时由调用方按索引变更代数生成）。
+    pub next_cursor: Option<String>,
 }
 
 impl RecallResult {
-    pub fn render_text_summary(&self) -> String {
+    /// 按字节预算截断本页：累计 items 序列化大小超过 `max_bytes` 时丢弃其后的条目（至少保留 1 条）并标记 has_more。
+    pub fn apply_byte_budget(&mut self, max_bytes: usize) {
+        let mut used = 0usize;
+        let keep = self
+            .items
+            .iter()
+            .position(|item| {
+                used += serde_json::to_vec(item).map(|x| x.len()).unwrap_or_default();
+                used > max_bytes
+            })
+            .map_or(self.items.len(), |i| i.max(1));
+        if keep < self.items.len() {
+            self.items.truncate(keep);
+            self.total = keep;
+            self.has_more = true;
+        }
+    }
+
+    pub fn render_text_summary(&self, lang: Lang) -> String {
+        let mut text = self.render_items_summary(lang);
+        self.append_footnotes(&mut text, lang);
+        text
+    }
+
+    fn render_items_summary(&self, lang: Lang) -> String {
         if self.items.is_empty() {
-            return "未命中记忆。".to_string();
+            return lang.pick("未命中记忆。", "No memories matched.").to_string();
         }
 
         let mut lines = Vec::with_capacity(self.items.len() + 1);
-        lines.push(format!("命中 {} 条记忆：", self.items.len()));
+        lines.push(lang.pick(
+            format!("命中 {} 条记忆：", self.items.len()),
+            format!("Matched {} memories:", self.items.len()),
+        ));
 
         for (i, item) in self.items.iter().enumerate() {
             let t = item.occurred_at.as_deref().unwrap_or(&item.recorded_at);
@@ -141,11 +1101,25 @@ impl RecallResult {
             } else {
                 format!(" keywords={}", item.keywords.join(","))
             };
+            let kind = item.kind.map(|k| format!(" kind={}", k.as_str())).unwrap_or_default();
+            let tags = if item.tags.is_empty() {
+                String::new()
+            } else {
+                format!(" tags={}", item.tags.join(","))
+            };
+            let by = item
+                .written_by
+                .as_deref()
+                .map(|x| format!(" by={x}"))
+                .unwrap_or_default();
             lines.push(format!(
-                "{}. [{}]{} id={} slice={}",
+                "{}. [{}]{}{}{}{} id={} slice={}",
                 i + 1,
                 t,
+                kind,
                 kws,
+                tags,
+                by,
                 item.id,
                 truncate_one_line(&item.slice, 120)
             ));
@@ -153,10 +1127,66 @@ impl RecallResult {
 
         lines.join("\n")
     }
+
+    /// 紧凑格式：首行为表头，其后每条一行 `date|importance|keywords|slice`。
+    pub fn render_compact_text(&self, lang: Lang) -> String {
+        let mut text = self.render_compact_items(lang);
+        self.append_footnotes(&mut text, lang);
+        text
+    }
+
+    fn append_footnotes(&self, text: &mut String, lang: Lang) {
+        if !self.warnings.is_empty() {
+            let n = self.warnings.len();
+            text.push_str(&lang.pick(
+                format!("\n（{n} 条记录无法读取，已跳过）"),
+                format!("\n({n} unreadable records skipped)"),
+            ));
+        }
+        if let Some(cursor) = self.next_cursor.as_deref() {
+            text.push_str(&lang.pick(
+                format!("\n（还有更多结果：cursor={cursor}）"),
+                format!("\n(more results: cursor={cursor})"),
+            ));
+        }
+    }
+
+    fn render_compact_items(&self, lang: Lang) -> String {
+        if self.items.is_empty() {
+            return lang.pick("未命中记忆。", "No memories matched.").to_string();
+        }
+
+        let mut lines = Vec::with_capacity(self.items.len() + 1);
+        lines.push("date|importance|keywords|slice".to_string());
+
+        for item in &self.items {
+            let t = item.occurred_at.as_deref().unwrap_or(&item.recorded_at);
+            let date: String = t.chars().take(10).collect();
+            let importance = item
+                .importance
+                .map(|x| x.to_string())
+                .unwrap_or_else(|| "-".to_string());
+            let slice = item
+                .slice
+                .replace(['\n', '\r'], " ")
+                .replace('|', "/")
+                .trim()
+                .to_string();
+            lines.push(format!(
+                "{}|{}|{}|{}",
+                date,
+                importance,
+                item.keywords.join(","),
+                slice
+            ));
+        }
+
+        lines.join("\n")
+    }
 }
 
-fn truncate_one_line(text: &str, max_len: usize) -> String {
-    let s = text.replace('\n', " ").replace('\r', " ").trim().to_string();
+pub fn truncate_one_line(text: &str, max_len: usize) -> String {
+    let s = text.replace(['\n', '\r'], " ").trim().to_string();
     if s.chars().count() <= max_len {
         return s;
     }
@@ -182,6 +1212,27 @@ fn get_required_string(v: &Value, key: &str) -> Result<String, String> {
     Ok(s)
 }
 
+/// 默认 namespace（`MEMORY_DEFAULT_NAMESPACE`，空白视为未设置）。
+pub fn default_namespace() -> Option<String> {
+    std::env::var(DEFAULT_NAMESPACE_ENV)
+        .ok()
+        .map(|x| x.trim().to_string())
+        .filter(|x| !x.is_empty())
+}
+
+/// 读取 `namespace` 参数；未传时使用默认 namespace，两者都没有时报错。
+pub fn get_namespace(v: &Value) -> Result<String, String> {
+    namespace_or(v, default_namespace())
+}
+
+/// 读取 `namespace` 参数，未传时使用 `default`。
+pub fn namespace_or(v: &Value, default: Option<String>) -> Result<String, String> {
+    match get_optional_string(v, "namespace")? {
+        Some(namespace) => Ok(namespace),
+        None => default.ok_or_else(|| format!("namespace 不能为空（未传入且未设置 {DEFAULT_NAMESPACE_ENV}）")),
+    }
+}
+
 fn get_optional_string(v: &Value, key: &str) -> Result<Option<String>, String> {
     Ok(v.get(key)
         .and_then(|x| x.as_str())
@@ -189,6 +1240,32 @@ fn get_optional_string(v: &Value, key: &str) -> Result<Option<String>, String> {
         .filter(|x| !x.is_empty()))
 }
 
+/// 解析 metadata 对象：只接受 JSON 对象，键不能为空，序列化后不超过 [`METADATA_MAX_BYTES`]。
+pub fn parse_metadata(v: &Value) -> Result<Map<String, Value>, String> {
+    let Value::Object(map) = v else {
+        return Err("metadata 必须是 JSON 对象".to_string());
+    };
+    if map.keys().any(|k| k.trim().is_empty()) {
+        return Err("metadata 的键不能为空".to_string());
+    }
+    let size = serde_json::to_vec(map).map(|x| x.len()).unwrap_or_default();
+    if size > METADATA_MAX_BYTES {
+        return Err(format!("metadata 过大：{size} 字节（最多 {METADATA_MAX_BYTES} 字节）"));
+    }
+    Ok(map.clone())
+}
+
+fn get_optional_metadata(v: &Value) -> Result<Option<Map<String, Value>>, String> {
+    match v.get("metadata") {
+        None | Some(Value::Null) => Ok(None),
+        Some(x) => parse_metadata(x).map(Some),
+    }
+}
+
+fn get_optional_kind(v: &Value) -> Result<Option<MemoryKind>, String> {
+    get_optional_string(v, "kind")?.map(|x| MemoryKind::parse(&x)).transpose()
+}
+
 fn get_string_array(v: &Value, key: &str) -> Result<Vec<String>, String> {
     let Some(arr) = v.get(key).and_then(|x| x.as_array()) else {
         return Err(format!("{key} 必须是字符串数组"));
@@ -201,6 +1278,45 @@ fn get_string_array(v: &Value, key: &str) -> Result<Vec<String>, String> {
         .collect())
 }
 
+/// 关键字数组：元素可为字符串，或 `{"keyword": "...", "weight": 1~10}`（weight 缺省为 1）。
+fn get_weighted_keywords(v: &Value, key: &str) -> Result<(Vec<String>, BTreeMap<String, u8>), String> {
+    let Some(arr) = v.get(key).and_then(|x| x.as_array()) else {
+        return Err(format!("{key} 必须是数组（元素为字符串或 {{keyword, weight}} 对象）"));
+    };
+
+    let mut keywords = Vec::with_capacity(arr.len());
+    let mut weights = BTreeMap::new();
+    for x in arr {
+        let (keyword, weight) = match x {
+            Value::String(s) => (s.trim().to_string(), None),
+            Value::Object(obj) => {
+                let keyword = obj
+                    .get("keyword")
+                    .and_then(|x| x.as_str())
+                    .map(|x| x.trim().to_string())
+                    .unwrap_or_default();
+                (keyword, obj.get("weight").filter(|x| !x.is_null()))
+            }
+            _ => continue,
+        };
+        if keyword.is_empty() {
+            continue;
+        }
+
+        if let Some(w) = weight {
+            let n = w
+                .as_u64()
+                .filter(|n| (1..=KEYWORD_WEIGHT_MAX as u64).contains(n))
+                .ok_or_else(|| format!("weight 必须是 1~{KEYWORD_WEIGHT_MAX} 的整数（keyword={keyword}）"))?;
+            if n > 1 {
+                weights.insert(keyword.clone(), n as u8);
+            }
+        }
+        keywords.push(keyword);
+    }
+    Ok((keywords, weights))
+}
+
 fn get_optional_string_array(v: &Value, key: &str) -> Result<Option<Vec<String>>, String> {
     let Some(value) = v.get(key) else {
         return Ok(None);
@@ -223,6 +1339,25 @@ fn get_optional_u8(v: &Value, key: &str) -> Result<Option<u8>, String> {
     Ok(None)
 }
 
+fn is_zero(n: &u64) -> bool {
+    *n == 0
+}
+
+fn get_optional_bool(v: &Value, key: &str) -> Result<Option<bool>, String> {
+    match v.get(key) {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::Bool(b)) => Ok(Some(*b)),
+        Some(_) => Err(format!("{key} 必须是布尔值")),
+    }
+}
+
+fn get_required_revision(v: &Value) -> Result<u32, String> {
+    match v.get("revision").and_then(|x| x.as_u64()) {
+        Some(n) if n >= 1 && n <= u32::MAX as u64 => Ok(n as u32),
+        _ => Err("revision 必须是正整数（取自 remember/recall 返回的 revision）".to_string()),
+    }
+}
+
 fn get_optional_usize(v: &Value, key: &str) -> Result<Option<usize>, String> {
     let Some(value) = v.get(key) else {
         return Ok(None);
@@ -0,0 +1,91 @@
+use crate::memory::lang::Lang;
+use crate::memory::model::RecallItemOut;
+use serde_json::{json, Value};
+
+/// 回顾最近记忆的 prompt。
+pub const REVIEW_RECENT: &str = "review_recent_memories";
+/// 总结某个主题相关记忆的 prompt。
+pub const SUMMARIZE_TOPIC: &str = "summarize_topic";
+/// prompt 中插入的记忆条数上限。
+pub const PROMPT_RECALL_LIMIT: usize = 20;
+/// review_recent_memories 默认回顾的天数。
+pub const DEFAULT_REVIEW_DAYS: i64 = 7;
+
+/// prompt 是否存在且接受该参数（参数补全时校验引用）。
+pub fn prompt_accepts(name: &str, argument: &str) -> bool {
+    prompt_definitions().iter().any(|p| {
+        p["name"] == name
+            && p["arguments"]
+                .as_array()
+                .is_some_and(|args| args.iter().any(|a| a["name"] == argument))
+    })
+}
+
+/// `prompts/list` 公布的全部 prompt（参数均为字符串，遵循 MCP prompt 约定）。
+pub fn prompt_definitions() -> Vec<Value> {
+    vec![
+        json!({
+            "name": REVIEW_RECENT,
+            "title": "回顾最近的记忆",
+            "description": "取出 namespace 最近若干天的记忆，请模型指出过时、重复或需要更新的内容。",
+            "arguments": [
+                { "name": "namespace", "description": "命名空间：{userId}/{projectId}。", "required": true },
+                { "name": "days", "description": "回顾最近多少天（按 occurred_at ?? recorded_at，默认 7，最大 365）。", "required": false }
+            ]
+        }),
+        json!({
+            "name": SUMMARIZE_TOPIC,
+            "title": "总结某个主题的记忆",
+            "description": "按主题检索 namespace 中的记忆（含 diary），请模型总结记得的内容。",
+            "arguments": [
+                { "name": "namespace", "description": "命名空间：{userId}/{projectId}。", "required": true },
+                { "name": "topic", "description": "主题（按 recall 的 query 全文检索）。", "required": true }
+            ]
+        }),
+    ]
+}
+
+/// review_recent_memories 的消息正文。
+pub fn review_recent_text(lang: Lang, namespace: &str, days: i64, items: &[RecallItemOut]) -> String {
+    let header = lang.pick(
+        format!("以下是 namespace={namespace} 最近 {days} 天的记忆（共 {} 条）：", items.len()),
+        format!("Here are the memories from the last {days} days in namespace={namespace} ({} total):", items.len()),
+    );
+    let ask = lang.pick(
+        "请逐条回顾：指出已经过时、彼此重复或相互矛盾的记忆，并给出需要更新（update）、归档（archive）或删除（forget）的建议（引用 id）。",
+        "Please review them one by one: point out memories that are outdated, duplicated or contradictory, and suggest which to update, archive or forget (cite ids).",
+    );
+    format!("{header}\n\n{}\n\n{ask}", render_items(lang, items))
+}
+
+/// summarize_topic 的消息正文。
+pub fn summarize_topic_text(lang: Lang, namespace: &str, topic: &str, items: &[RecallItemOut]) -> String {
+    let header = lang.pick(
+        format!("以下是 namespace={namespace} 中与「{topic}」相关的记忆（共 {} 条）：", items.len()),
+        format!("Here are the memories about \"{topic}\" in namespace={namespace} ({} total):", items.len()),
+    );
+    let ask = lang.pick(
+        format!("请总结你记得的关于「{topic}」的内容：按时间梳理关键事实与决定，标出仍未解决的问题；不要编造记忆中没有的信息。"),
+        format!("Please summarize what you remember about \"{topic}\": outline the key facts and decisions in chronological order and flag open questions; do not invent anything not in the memories."),
+    );
+    format!("{header}\n\n{}\n\n{ask}", render_items(lang, items))
+}
+
+/// 记忆列表：时间 + id + slice，有 diary 时附在下一行。
+fn render_items(lang: Lang, items: &[RecallItemOut]) -> String {
+    if items.is_empty() {
+        return lang.pick("（没有找到相关记忆）", "(no matching memories)").to_string();
+    }
+    items
+        .iter()
+        .map(|item| {
+            let t = item.occurred_at.as_deref().unwrap_or(&item.recorded_at);
+            let mut line = format!("- [{t}] id={} {}", item.id, item.slice);
+            if let Some(diary) = item.diary.as_deref().filter(|x| !x.trim().is_empty()) {
+                line.push_str(&format!("\n  diary: {}", diary.trim().replace('\n', "\n  ")));
+            }
+            line
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -0,0 +1,554 @@
+use crate::memory::index::{self, IndexData};
+use crate::memory::model::MemoryItem;
+use crate::memory::segment;
+use regex::{Regex, RegexBuilder};
+use serde_json::{Map, Value};
+use std::collections::HashSet;
+
+/// 字段过滤词的前缀：`metadata.key=value`。
+const METADATA_PREFIX: &str = "metadata.";
+
+/// 正则模式的最大长度（字符数）。
+pub const REGEX_MAX_CHARS: usize = 512;
+/// 编译后程序与惰性 DFA 缓存的大小上限：拒绝 `(a{100}){100}` 这类膨胀的模式。
+const REGEX_SIZE_LIMIT: usize = 1 << 20;
+/// 分组/重复的最大嵌套深度。
+const REGEX_NEST_LIMIT: u32 = 32;
+
+/// recall `query` 的文本部分（时间表达式已由调用方剥离）解析后的布尔表达式。
+///
+/// 语法：
+/// - 相邻的普通词组成一个词组（与旧行为一致：全部分词都出现在正文中，或整体作为子串出现）；
+/// - `AND` / `OR`（大写）连接子表达式，AND 优先级高于 OR；相邻的子表达式之间隐含 AND；
+/// - `"..."` 为短语：必须在正文中连续出现（短语内的空白可对应任意空白或无空白），不做分词匹配；
+/// - `-词`、`-"短语"` 或 `-( ... )` 表示排除；
+/// - `( ... )` 分组；
+/// - `metadata.key=value` 匹配 metadata 字段（键不区分大小写，`a.b` 访问嵌套对象；值为数组时任一元素相等即可），可与 `-` 组合排除。
+///
+/// `regex: true` 时整个 query 编译为 [`QueryExpr::Regex`]，不使用上述语法。
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueryExpr {
+    Term(QueryTerm),
+    And(Vec<QueryExpr>),
+    Or(Vec<QueryExpr>),
+    Not(Box<QueryExpr>),
+    Regex(QueryRegex),
+    Metadata(MetadataFilter),
+}
+
+/// `metadata.key=value` 过滤：metadata 不进入索引，读取记录后逐条校验。
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetadataFilter {
+    /// 已小写化的键路径（`ticket`、`repo.branch`）。
+    pub path: Vec<String>,
+    /// 已小写化的期望值。
+    pub value: String,
+}
+
+impl MetadataFilter {
+    fn parse(word: &str) -> Option<Self> {
+        let (path, value) = word.strip_prefix(METADATA_PREFIX)?.split_once('=')?;
+        let path: Vec<String> = path.split('.').map(str::to_string).collect();
+        if path.iter().any(String::is_empty) || value.is_empty() {
+            return None;
+        }
+        Some(Self {
+            path,
+            value: value.to_string(),
+        })
+    }
+
+    fn is_match(&self, metadata: &Map<String, Value>) -> bool {
+        let mut node: Option<&Value> = None;
+        let mut map = Some(metadata);
+        for key in &self.path {
+            node = map.and_then(|m| m.iter().find(|(k, _)| k.to_lowercase() == *key).map(|(_, v)| v));
+            map = node.and_then(Value::as_object);
+        }
+        let equals = |v: &Value| match v {
+            Value::String(s) => s.to_lowercase() == self.value,
+            Value::Number(n) => n.to_string() == self.value,
+            Value::Bool(b) => b.to_string() == self.value,
+            _ => false,
+        };
+        match node {
+            Some(Value::Array(list)) => list.iter().any(equals),
+            Some(v) => equals(v),
+            None => false,
+        }
+    }
+}
+
+/// 正则查询：分别匹配 slice/diary/source 原文（默认不区分大小写）；无法用倒排裁剪，逐条校验。
+#[derive(Debug, Clone)]
+pub struct QueryRegex(Regex);
+
+impl PartialEq for QueryRegex {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_str() == other.0.as_str()
+    }
+}
+
+impl QueryRegex {
+    fn is_match_item(&self, item: &MemoryItem) -> bool {
+        let source = item.source.as_ref().map(|s| s.describe()).unwrap_or_default();
+        [item.slice.as_str(), item.diary.as_str(), source.as_str()]
+            .iter()
+            .any(|text| self.0.is_match(text))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryTerm {
+    /// 已小写化的词组。
+    pub text: String,
+    /// 引号短语：只做连续匹配（忽略空白差异），不使用分词命中。
+    pub exact: bool,
+    /// 通过分词倒排确认命中的条目（全部分词都出现）；无需再做子串校验。
+    hits: HashSet<u32>,
+    /// 可能命中的条目（分词命中 ∪ trigram 候选）；None 表示无法裁剪。
+    candidates: Option<HashSet<u32>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Phrase(String),
+    Metadata(MetadataFilter),
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+impl QueryExpr {
+    /// 解析查询文本；文本为空时返回 None。
+    pub fn parse(text: &str) -> Result<Option<Self>, String> {
+        let tokens = tokenize(text)?;
+        if tokens.is_empty() {
+            return Ok(None);
+        }
+
+        let mut parser = Parser { tokens, pos: 0 };
+        let expr = parser.parse_or()?;
+        if let Some(token) = parser.peek() {
+            return Err(match token {
+                Token::Close => "query 括号不匹配：多余的 )".to_string(),
+                _ => "query 语法错误：无法解析的剩余内容".to_string(),
+            });
+        }
+        Ok(Some(expr))
+    }
+
+    /// 将文本编译为正则查询；文本为空时返回 None。长度、编译体积与嵌套深度超限时报错。
+    pub fn parse_regex(text: &str) -> Result<Option<Self>, String> {
+        let pattern = text.trim();
+        if pattern.is_empty() {
+            return Ok(None);
+        }
+        if pattern.chars().count() > REGEX_MAX_CHARS {
+            return Err(format!("regex 过长：最多 {REGEX_MAX_CHARS} 个字符"));
+        }
+        let re = RegexBuilder::new(pattern)
+            .case_insensitive(true)
+            .size_limit(REGEX_SIZE_LIMIT)
+            .dfa_size_limit(REGEX_SIZE_LIMIT)
+            .nest_limit(REGEX_NEST_LIMIT)
+            .build()
+            .map_err(|e| format!("regex 无效或过于复杂：{e}"))?;
+        Ok(Some(Self::Regex(QueryRegex(re))))
+    }
+
+    /// 用索引为每个词组计算分词命中与候选集合（读盘前裁剪用）。
+    pub fn bind(&mut self, index: &IndexData) {
+        match self {
+            Self::Term(term) if term.exact => {
+                // 短语的每一段都必须出现：逐段用 trigram 裁剪后求交集（过短的段无法裁剪）。
+                term.candidates = term
+                    .text
+                    .split_whitespace()
+                    .filter_map(|part| index.query_candidates(part))
+                    .reduce(|a, b| a.intersection(&b).copied().collect());
+            }
+            Self::Term(term) => {
+                term.hits = index.all_terms_candidates(&segment::query_terms(&term.text));
+                term.candidates = index.query_candidates(&term.text).map(|mut set| {
+                    set.extend(term.hits.iter().copied());
+                    set
+                });
+            }
+            Self::And(list) | Self::Or(list) => list.iter_mut().for_each(|x| x.bind(index)),
+            Self::Not(inner) => inner.bind(index),
+            Self::Regex(_) | Self::Metadata(_) => {}
+        }
+    }
+
+    /// 可能命中的条目集合；None 表示无法裁剪（需逐条校验）。
+    pub fn candidates(&self) -> Option<HashSet<u32>> {
+        match self {
+            Self::Term(term) => term.candidates.clone(),
+            Self::And(list) => list
+                .iter()
+                .filter_map(|x| x.candidates())
+                .reduce(|a, b| a.intersection(&b).copied().collect()),
+            Self::Or(list) => {
+                let mut out = HashSet::new();
+                for x in list {
+                    out.extend(x.candidates()?);
+                }
+                Some(out)
+            }
+            // 排除条件、正则与 metadata 过滤无法用正向倒排裁剪。
+            Self::Not(_) | Self::Regex(_) | Self::Metadata(_) => None,
+        }
+    }
+
+    /// 对已读取的条目做精确判定；`haystack` 为 [`index::query_haystack`] 的结果，`metadata` 为条目的 metadata。
+    pub fn matches(&self, idx: u32, haystack: &str, metadata: &Map<String, Value>) -> bool {
+        self.matches_with(haystack, metadata, &|term| term.hits.contains(&idx))
+    }
+
+    /// 同 [`Self::matches`]，但词组的分词命中由调用方判定（未经 [`Self::bind`] 绑定索引时使用）。
+    pub fn matches_with(
+        &self,
+        haystack: &str,
+        metadata: &Map<String, Value>,
+        term_hit: &dyn Fn(&QueryTerm) -> bool,
+    ) -> bool {
+        match self {
+            Self::Term(term) if term.exact => contains_phrase(haystack, &term.text),
+            Self::Term(term) => term_hit(term) || haystack.contains(&term.text),
+            Self::And(list) => list.iter().all(|x| x.matches_with(haystack, metadata, term_hit)),
+            Self::Or(list) => list.iter().any(|x| x.matches_with(haystack, metadata, term_hit)),
+            Self::Not(inner) => !inner.matches_with(haystack, metadata, term_hit),
+            Self::Regex(re) => re.0.is_match(haystack),
+            Self::Metadata(filter) => filter.is_match(metadata),
+        }
+    }
+
+    /// 不读盘即可确认命中（仅由分词命中组成的正向条件）；用于跳过逐条校验。
+    pub fn matches_by_index(&self, idx: u32) -> bool {
+        match self {
+            Self::Term(term) => term.hits.contains(&idx),
+            Self::And(list) => list.iter().all(|x| x.matches_by_index(idx)),
+            Self::Or(list) => list.iter().any(|x| x.matches_by_index(idx)),
+            Self::Not(_) | Self::Regex(_) | Self::Metadata(_) => false,
+        }
+    }
+}
+
+/// 短语的各段按顺序紧邻出现，段与段之间只允许空白（可以没有）。
+fn contains_phrase(haystack: &str, phrase: &str) -> bool {
+    let parts: Vec<&str> = phrase.split_whitespace().collect();
+    let Some((first, rest)) = parts.split_first() else {
+        return true;
+    };
+    haystack.match_indices(first).any(|(start, _)| {
+        let mut tail = &haystack[start + first.len()..];
+        rest.iter().all(|part| match tail.trim_start().strip_prefix(part) {
+            Some(t) => {
+                tail = t;
+                true
+            }
+            None => false,
+        })
+    })
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let segments: Vec<&str> = text.split('"').collect();
+    if segments.len().is_multiple_of(2) {
+        return Err("query 引号不匹配：缺少结束的 \"".to_string());
+    }
+
+    let mut tokens: Vec<Token> = Vec::new();
+    // 上一个 token 是否为可继续合并的普通词（排除词 `-x`、短语不与后续词合并）。
+    let mut group_open = false;
+    for (i, segment) in segments.iter().enumerate() {
+        if i % 2 == 1 {
+            let phrase = segment.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase();
+            if !phrase.is_empty() {
+                tokens.push(Token::Phrase(phrase));
+            }
+            group_open = false;
+            continue;
+        }
+
+        // 紧贴引号的 `-` 表示排除后面的短语：`-"旧 版"`。
+        let negate_phrase = i + 1 < segments.len()
+            && segment
+                .strip_suffix('-')
+                .is_some_and(|x| x.is_empty() || x.ends_with(char::is_whitespace) || x.ends_with('('));
+        let words = if negate_phrase { &segment[..segment.len() - 1] } else { segment };
+        tokenize_words(words, &mut tokens, &mut group_open);
+        if negate_phrase {
+            tokens.push(Token::Not);
+        }
+    }
+    Ok(tokens)
+}
+
+fn tokenize_words(text: &str, tokens: &mut Vec<Token>, group_open: &mut bool) {
+    for raw in text.split_whitespace() {
+        let mut rest = raw;
+        while let Some(r) = rest.strip_prefix('(') {
+            tokens.push(Token::Open);
+            rest = r;
+            *group_open = false;
+        }
+        if let Some(r) = rest.strip_prefix("-(") {
+            tokens.push(Token::Not);
+            tokens.push(Token::Open);
+            rest = r;
+            *group_open = false;
+        }
+
+        let mut closes = 0;
+        while let Some(r) = rest.strip_suffix(')') {
+            closes += 1;
+            rest = r;
+        }
+
+        match rest {
+            "" => {}
+            "AND" | "&&" => {
+                tokens.push(Token::And);
+                *group_open = false;
+            }
+            "OR" | "||" => {
+                tokens.push(Token::Or);
+                *group_open = false;
+            }
+            _ => match rest.strip_prefix('-').filter(|x| !x.is_empty()) {
+                Some(word) => {
+                    let word = word.to_lowercase();
+                    tokens.push(Token::Not);
+                    tokens.push(match MetadataFilter::parse(&word) {
+                        Some(filter) => Token::Metadata(filter),
+                        None => Token::Word(word),
+                    });
+                    *group_open = false;
+                }
+                None => {
+                    let word = rest.to_lowercase();
+                    if let Some(filter) = MetadataFilter::parse(&word) {
+                        // 字段过滤词不与相邻普通词合并。
+                        tokens.push(Token::Metadata(filter));
+                        *group_open = false;
+                    } else {
+                        match tokens.last_mut() {
+                            // 相邻普通词合并为一个词组。
+                            Some(Token::Word(prev)) if *group_open => {
+                                prev.push(' ');
+                                prev.push_str(&word);
+                            }
+                            _ => tokens.push(Token::Word(word)),
+                        }
+                        *group_open = true;
+                    }
+                }
+            },
+        }
+        if closes > 0 {
+            tokens.extend(std::iter::repeat_n(Token::Close, closes));
+            *group_open = false;
+        }
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn parse_or(&mut self) -> Result<QueryExpr, String> {
+        let mut list = vec![self.parse_and()?];
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            list.push(self.parse_and()?);
+        }
+        Ok(if list.len() == 1 { list.remove(0) } else { QueryExpr::Or(list) })
+    }
+
+    fn parse_and(&mut self) -> Result<QueryExpr, String> {
+        let mut list = vec![self.parse_unary()?];
+        loop {
+            match self.peek() {
+                Some(Token::And) => {
+                    self.pos += 1;
+                    list.push(self.parse_unary()?);
+                }
+                // 隐含 AND：如 `(a OR b) c`、`a -b`。
+                Some(Token::Word(_) | Token::Phrase(_) | Token::Metadata(_) | Token::Not | Token::Open) => {
+                    list.push(self.parse_unary()?)
+                }
+                _ => break,
+            }
+        }
+        Ok(if list.len() == 1 { list.remove(0) } else { QueryExpr::And(list) })
+    }
+
+    fn parse_unary(&mut self) -> Result<QueryExpr, String> {
+        match self.next() {
+            Some(Token::Not) => Ok(QueryExpr::Not(Box::new(self.parse_unary()?))),
+            Some(Token::Open) => {
+                let expr = self.parse_or()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(expr),
+                    _ => Err("query 括号不匹配：缺少 )".to_string()),
+                }
+            }
+            Some(Token::Word(text)) => Ok(QueryExpr::Term(QueryTerm {
+                text,
+                exact: false,
+                hits: HashSet::new(),
+                candidates: None,
+            })),
+            Some(Token::Phrase(text)) => Ok(QueryExpr::Term(QueryTerm {
+                text,
+                exact: true,
+                hits: HashSet::new(),
+                candidates: None,
+            })),
+            Some(Token::Metadata(filter)) => Ok(QueryExpr::Metadata(filter)),
+            Some(Token::And | Token::Or) => Err("query 语法错误：AND/OR 两侧都需要条件".to_string()),
+            Some(Token::Close) => Err("query 括号不匹配：多余的 )".to_string()),
+            None => Err("query 语法错误：表达式不完整".to_string()),
+        }
+    }
+}
+
+/// 读取后的条目是否满足查询（无查询时恒为 true）。
+pub fn item_matches(expr: Option<&QueryExpr>, idx: u32, item: &MemoryItem) -> bool {
+    expr.is_none_or(|e| match e {
+        // 正则匹配未小写化的原文，`(?-i)` 才能区分大小写；`^`/`$` 按字段分别生效。
+        QueryExpr::Regex(re) => re.is_match_item(item),
+        _ => e.matches_by_index(idx) || e.matches(idx, &index::query_haystack(item), &item.metadata),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn term(text: &str) -> QueryExpr {
+        QueryExpr::Term(QueryTerm {
+            text: text.to_string(),
+            exact: false,
+            hits: HashSet::new(),
+            candidates: None,
+        })
+    }
+
+    #[test]
+    fn parse_should_handle_operators_and_groups() {
+        assert_eq!(QueryExpr::parse("  ").unwrap(), None);
+        assert_eq!(QueryExpr::parse("上线 计划").unwrap(), Some(term("上线 计划")));
+        assert_eq!(
+            QueryExpr::parse("-旧版 上线 计划").unwrap(),
+            Some(QueryExpr::And(vec![QueryExpr::Not(Box::new(term("旧版"))), term("上线 计划")]))
+        );
+        assert_eq!(
+            QueryExpr::parse("ERP OR crm -旧版").unwrap(),
+            Some(QueryExpr::Or(vec![
+                term("erp"),
+                QueryExpr::And(vec![term("crm"), QueryExpr::Not(Box::new(term("旧版")))]),
+            ]))
+        );
+        assert_eq!(
+            QueryExpr::parse("(a OR b) AND -(c d)").unwrap(),
+            Some(QueryExpr::And(vec![
+                QueryExpr::Or(vec![term("a"), term("b")]),
+                QueryExpr::Not(Box::new(term("c d"))),
+            ]))
+        );
+
+        assert!(QueryExpr::parse("(a OR b").unwrap_err().contains("缺少 )"));
+        assert!(QueryExpr::parse("a)").unwrap_err().contains("多余的 )"));
+        assert!(QueryExpr::parse("a OR").unwrap_err().contains("不完整"));
+    }
+
+    #[test]
+    fn quoted_phrase_should_match_contiguously() {
+        let expr = QueryExpr::parse("\"库存 盘点\"").unwrap().unwrap();
+        assert!(matches!(&expr, QueryExpr::Term(t) if t.exact && t.text == "库存 盘点"));
+        assert!(expr.matches(0, "本周库存盘点完成", &Map::new()));
+        assert!(expr.matches(0, "库存  盘点", &Map::new()));
+        assert!(!expr.matches(0, "盘点库存", &Map::new()));
+        assert!(!expr.matches(0, "库存已经盘点", &Map::new()));
+
+        let expr = QueryExpr::parse("erp -\"旧 版\" OR (\"a b\")").unwrap().unwrap();
+        assert!(expr.matches(0, "erp 新版", &Map::new()));
+        assert!(!expr.matches(0, "erp 旧版", &Map::new()));
+        assert!(expr.matches(0, "a b", &Map::new()));
+        assert!(QueryExpr::parse("\"库存").unwrap_err().contains("引号不匹配"));
+    }
+
+    #[test]
+    fn matches_should_evaluate_against_haystack() {
+        let expr = QueryExpr::parse("(erp OR crm) -旧版").unwrap().unwrap();
+        assert!(expr.matches(0, "erp 新版上线", &Map::new()));
+        assert!(!expr.matches(0, "crm 旧版下线", &Map::new()));
+        assert!(!expr.matches(0, "oa 上线", &Map::new()));
+        assert_eq!(expr.candidates(), None);
+    }
+
+    #[test]
+    fn parse_regex_should_guard_pattern_size() {
+        assert_eq!(QueryExpr::parse_regex(" ").unwrap(), None);
+        let expr = QueryExpr::parse_regex(r"v\d+\.\d+").unwrap().unwrap();
+        assert_eq!(expr.candidates(), None);
+        assert!(expr.matches(0, "发布 V2.1", &Map::new()));
+        assert!(!expr.matches(0, "发布 v2", &Map::new()));
+
+        let long = "a".repeat(REGEX_MAX_CHARS + 1);
+        assert!(QueryExpr::parse_regex(&long).unwrap_err().contains("regex 过长"));
+        assert!(QueryExpr::parse_regex(r"(\w{100}){100}").unwrap_err().contains("过于复杂"));
+        assert!(QueryExpr::parse_regex("(").unwrap_err().contains("regex 无效"));
+    }
+
+    #[test]
+    fn metadata_filter_should_match_fields() {
+        let metadata: Map<String, Value> = serde_json::from_value(serde_json::json!({
+            "Ticket": "ERP-12",
+            "repo": { "branch": "main" },
+            "files": ["src/a.rs", "src/b.rs"],
+            "pr": 42
+        }))
+        .unwrap();
+        let hit = |query: &str| QueryExpr::parse(query).unwrap().unwrap().matches(0, "上线 计划", &metadata);
+
+        assert!(hit("metadata.ticket=erp-12"));
+        assert!(hit("metadata.repo.branch=main 上线"));
+        assert!(hit("metadata.files=src/b.rs"));
+        assert!(hit("metadata.pr=42"));
+        assert!(!hit("metadata.pr=43"));
+        assert!(!hit("-metadata.ticket=erp-12"));
+        assert!(hit("metadata.missing=x OR 计划"));
+        assert_eq!(
+            QueryExpr::parse("上线 metadata.pr=42 计划").unwrap(),
+            Some(QueryExpr::And(vec![
+                term("上线"),
+                QueryExpr::Metadata(MetadataFilter {
+                    path: vec!["pr".to_string()],
+                    value: "42".to_string()
+                }),
+                term("计划"),
+            ]))
+        );
+        // 不完整的字段过滤词按普通词处理。
+        assert_eq!(QueryExpr::parse("metadata.=x").unwrap(), Some(term("metadata.=x")));
+    }
+}
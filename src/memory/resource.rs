@@ -0,0 +1,77 @@
+use crate::memory::model::RecallItemOut;
+use serde_json::{json, Value};
+
+/// 记忆资源 URI 前缀：`memory://{userId}/{projectId}/{id}`。
+pub const URI_PREFIX: &str = "memory://";
+/// `resources/read` 返回的记忆内容类型（完整记录的 JSON）。
+pub const MIME_TYPE: &str = "application/json";
+
+/// 记忆的资源 URI。
+pub fn memory_uri(namespace: &str, id: &str) -> String {
+    format!("{URI_PREFIX}{namespace}/{id}")
+}
+
+/// 解析 `memory://{namespace}/{id}` 为 (namespace, id)；namespace 本身含 `/`，以最后一段为 id。
+pub fn parse_memory_uri(uri: &str) -> Result<(String, String), String> {
+    let invalid = || format!("资源 URI 无效：{uri}（应为 {URI_PREFIX}{{userId}}/{{projectId}}/{{id}}）");
+    let rest = uri.trim().strip_prefix(URI_PREFIX).ok_or_else(invalid)?;
+    let (namespace, id) = rest.rsplit_once('/').ok_or_else(invalid)?;
+    if namespace.is_empty() || id.is_empty() {
+        return Err(invalid());
+    }
+    Ok((namespace.to_string(), id.to_string()))
+}
+
+/// `memory://` 资源模板（`resources/templates/list`）。
+pub const URI_TEMPLATE: &str = "memory://{userId}/{projectId}/{id}";
+/// `resources/list` 中每个 namespace 列出的最近记忆条数。
+pub const LIST_PER_NAMESPACE: usize = 20;
+/// `resources/list` 每页包含的 namespace 个数（超出时返回 nextCursor）。
+pub const LIST_NAMESPACES_PER_PAGE: usize = 20;
+
+/// 一条记忆的资源描述（`resources/list` 的条目，也是 `resource_link` 内容块的主体）。
+pub fn memory_resource(namespace: &str, item: &RecallItemOut) -> Value {
+    json!({
+        "uri": memory_uri(namespace, &item.id),
+        "name": item.id,
+        "description": item.slice,
+        "mimeType": MIME_TYPE
+    })
+}
+
+/// `resources/templates/list` 公布的资源模板。
+pub fn resource_templates() -> Vec<Value> {
+    vec![json!({
+        "uriTemplate": URI_TEMPLATE,
+        "name": "memory",
+        "description": "按 namespace 与 id 读取一条记忆的完整记录（JSON）。",
+        "mimeType": MIME_TYPE
+    })]
+}
+
+/// recall 结果中每条记忆的 `resource_link` 内容块，供宿主从回答跳转到原始记录。
+pub fn recall_links(namespace: &str, items: &[RecallItemOut]) -> Vec<Value> {
+    items
+        .iter()
+        .map(|item| {
+            let mut link = memory_resource(namespace, item);
+            link["type"] = json!("resource_link");
+            link
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_uri_should_roundtrip() {
+        let uri = memory_uri("u1/p1", "0f8c");
+        assert_eq!(uri, "memory://u1/p1/0f8c");
+        assert_eq!(parse_memory_uri(&uri).unwrap(), ("u1/p1".to_string(), "0f8c".to_string()));
+        assert!(parse_memory_uri("file:///u1/p1/0f8c").is_err());
+        assert!(parse_memory_uri("memory://u1/p1/").is_err());
+        assert!(parse_memory_uri("memory://0f8c").is_err());
+    }
+}
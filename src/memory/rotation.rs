@@ -0,0 +1,320 @@
+use crate::memory::journal;
+use crate::memory::store::StorePaths;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+/// 第 n 个已封存分段的文件名（从 1 开始）：`memories-0001.jsonl`、`memories-0002.jsonl`……
+pub fn segment_file_name(n: usize) -> String {
+    format!("memories-{n:04}.jsonl")
+}
+
+/// 已封存的分段：只读，占据全局偏移 `[base, base + bytes)`。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Segment {
+    pub file: String,
+    pub base: u64,
+    pub bytes: u64,
+}
+
+/// segments.json：已封存分段（按写入顺序）。文件缺失时没有分段，全部数据都在 memories.jsonl 中。
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SegmentManifest {
+    pub segments: Vec<Segment>,
+}
+
+/// namespace 的数据文件：已封存分段 + 活动文件 memories.jsonl，按全局偏移首尾相接。
+///
+/// 索引中记录的是全局偏移：轮转只是把活动文件整体改名为下一个分段，偏移不变，索引无需改动；
+/// 追加写入、增量索引与 compact 都只涉及活动文件。
+#[derive(Debug, Clone)]
+pub struct DataFiles {
+    dir: PathBuf,
+    active: PathBuf,
+    manifest_path: PathBuf,
+    manifest: SegmentManifest,
+}
+
+impl DataFiles {
+    /// 读取分段清单（只读，不做修复）。
+    pub fn load(paths: &StorePaths) -> Result<Self, String> {
+        let mut out = Self {
+            dir: paths.namespace_dir.clone(),
+            active: paths.memories_path.clone(),
+            manifest_path: paths.segments_path.clone(),
+            manifest: SegmentManifest::default(),
+        };
+        out.reload()?;
+        Ok(out)
+    }
+
+    /// 重新读取分段清单（其他进程可能已轮转）。
+    pub fn reload(&mut self) -> Result<(), String> {
+        self.manifest = match fs::read_to_string(&self.manifest_path) {
+            Ok(text) => serde_json::from_str(&text).map_err(|e| format!("segments.json 无效：{e}"))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => SegmentManifest::default(),
+            Err(e) => return Err(format!("read segments.json failed: {e}")),
+        };
+        Ok(())
+    }
+
+    /// 打开 namespace 时调用：持写入锁补齐中断的轮转（见 [`DataFiles::finish_rotation`]）。
+    pub fn recover(paths: &StorePaths) -> Result<(), String> {
+        let _lock = journal::lock(paths)?;
+        Self::load(paths)?.finish_rotation()
+    }
+
+    /// 按磁盘上的清单补齐轮转（调用方需持有写入锁）：重新读取清单；轮转在“改名”与“写清单”之间中断时
+    /// （活动文件缺失或为空、下一个编号的分段已存在但未登记），把该分段补记到清单中；最后确保活动文件存在。
+    pub fn finish_rotation(&mut self) -> Result<(), String> {
+        self.reload()?;
+        let active_len = fs::metadata(&self.active).map_or(0, |m| m.len());
+        let name = segment_file_name(self.manifest.segments.len() + 1);
+        if active_len == 0 {
+            if let Ok(meta) = fs::metadata(self.dir.join(&name)) {
+                let base = self.active_base();
+                self.manifest.segments.push(Segment {
+                    file: name,
+                    base,
+                    bytes: meta.len(),
+                });
+                self.save_manifest()?;
+            }
+        }
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.active)
+            .map_err(|e| format!("create memories.jsonl failed: {e}"))?;
+        Ok(())
+    }
+
+    pub fn active_path(&self) -> &Path {
+        &self.active
+    }
+
+    pub fn sealed(&self) -> &[Segment] {
+        &self.manifest.segments
+    }
+
+    /// 活动文件起始处的全局偏移（= 全部已封存分段的总长度）。
+    pub fn active_base(&self) -> u64 {
+        self.manifest.segments.last().map_or(0, |s| s.base + s.bytes)
+    }
+
+    /// 全部数据的总长度（全局偏移的上界）。
+    pub fn total_len(&self) -> io::Result<u64> {
+        Ok(self.active_base() + fs::metadata(&self.active)?.len())
+    }
+
+    /// 从全局偏移处开始顺序读取（跨越分段边界）。
+    pub fn reader_from(&self, offset: u64) -> io::Result<Box<dyn Read>> {
+        let mut reader: Box<dyn Read> = Box::new(io::empty());
+        for seg in &self.manifest.segments {
+            if seg.base + seg.bytes <= offset {
+                continue;
+            }
+            let local = offset.saturating_sub(seg.base);
+            let mut file = File::open(self.dir.join(&seg.file))?;
+            file.seek(SeekFrom::Start(local))?;
+            reader = Box::new(reader.chain(file.take(seg.bytes - local)));
+        }
+        let mut file = File::open(&self.active)?;
+        file.seek(SeekFrom::Start(offset.saturating_sub(self.active_base())))?;
+        Ok(Box::new(reader.chain(file)))
+    }
+
+    /// 读取全局偏移处的一条记录（原始字节，含换行）。
+    pub fn read_at(&self, offset: u64, length: u32) -> io::Result<Vec<u8>> {
+        let segments = &self.manifest.segments;
+        let i = segments.partition_point(|s| s.base + s.bytes <= offset);
+        let (path, local) = match segments.get(i) {
+            Some(seg) => (self.dir.join(&seg.file), offset - seg.base),
+            None => (self.active.clone(), offset.saturating_sub(self.active_base())),
+        };
+
+        let mut file = File::open(path)?;
+        file.seek(SeekFrom::Start(local))?;
+        let mut buf = vec![0u8; length as usize];
+        file.read_exact(&mut buf)?;
+        Ok(buf)
+    }
+
+    /// 全部数据（分段 + 活动文件）。
+    pub fn read_all(&self) -> io::Result<Vec<u8>> {
+        let mut buf: Vec<u8> = Vec::new();
+        self.reader_from(0)?.read_to_end(&mut buf)?;
+        Ok(buf)
+    }
+
+    /// 全部非空记录行（去掉行尾的 `\r`）。
+    pub fn read_lines(&self) -> Result<Vec<String>, String> {
+        let bytes = self.read_all().map_err(|e| format!("read memories.jsonl failed: {e}"))?;
+        Ok(String::from_utf8_lossy(&bytes)
+            .lines()
+            .map(|x| x.trim_end_matches('\r'))
+            .filter(|x| !x.trim().is_empty())
+            .map(str::to_string)
+            .collect())
+    }
+
+    /// 活动文件达到 max_bytes 时轮转：改名为下一个分段、写入清单，再创建新的空活动文件；返回是否轮转。
+    ///
+    /// 调用方需持有写入锁：先按磁盘上的清单补齐其他进程已完成或中断的轮转，分段编号才不会冲突；
+    /// 改名后、写清单前中断时由 [`DataFiles::finish_rotation`] 补记。
+    pub fn rotate_if_needed(&mut self, max_bytes: u64) -> Result<bool, String> {
+        self.finish_rotation()?;
+        let len = fs::metadata(&self.active)
+            .map_err(|e| format!("stat memories.jsonl failed: {e}"))?
+            .len();
+        if max_bytes == 0 || len < max_bytes {
+            return Ok(false);
+        }
+
+        let name = segment_file_name(self.manifest.segments.len() + 1);
+        let target = self.dir.join(&name);
+        if target.exists() {
+            return Err(format!("分段文件已存在：{name}（segments.json 可能与目录内容不一致）"));
+        }
+        fs::rename(&self.active, &target).map_err(|e| format!("rotate memories.jsonl failed: {e}"))?;
+        let base = self.active_base();
+        self.manifest.segments.push(Segment { file: name, base, bytes: len });
+        self.save_manifest()?;
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.active)
+            .map_err(|e| format!("create memories.jsonl failed: {e}"))?;
+        Ok(true)
+    }
+
+    /// 只替换活动文件（临时文件 + rename），已封存的分段保持不变。
+    pub fn replace_active(&self, content: &[u8]) -> Result<(), String> {
+        let tmp = self.active.with_extension("jsonl.tmp");
+        fs::write(&tmp, content).map_err(|e| format!("write memories tmp failed: {e}"))?;
+        if let Err(e) = fs::rename(&tmp, &self.active) {
+            let _ = fs::remove_file(&self.active);
+            fs::rename(&tmp, &self.active).map_err(|_| format!("replace memories.jsonl failed: {e}"))?;
+        }
+        Ok(())
+    }
+
+    /// 用给定内容替换全部数据：写入活动文件后清空分段清单并删除已封存的分段。
+    ///
+    /// 在“替换活动文件”与“清空清单”之间中断时，旧分段会与新内容同时出现（同 id 以后写入者为准），不会丢数据。
+    pub fn replace_all(&mut self, content: &[u8]) -> Result<(), String> {
+        self.replace_active(content)?;
+        if self.manifest.segments.is_empty() {
+            return Ok(());
+        }
+        let sealed = std::mem::take(&mut self.manifest.segments);
+        self.save_manifest()?;
+        for seg in sealed {
+            let _ = fs::remove_file(self.dir.join(seg.file));
+        }
+        Ok(())
+    }
+
+    /// 逐行改写全部数据文件（namespace 改名时使用），并按改写后的长度更新分段清单；返回改写的记录行数。
+    pub fn rewrite_lines(&mut self, rewrite: impl Fn(&str) -> String) -> Result<usize, String> {
+        let rewrite_file = |path: &Path| -> Result<(String, usize), String> {
+            let text = fs::read_to_string(path).map_err(|e| format!("read {} failed: {e}", path.display()))?;
+            let mut out = String::with_capacity(text.len());
+            let mut records = 0usize;
+            for line in text.lines() {
+                let line = line.trim_end_matches('\r');
+                if line.trim().is_empty() {
+                    continue;
+                }
+                out.push_str(&rewrite(line));
+                out.push('\n');
+                records += 1;
+            }
+            Ok((out, records))
+        };
+
+        let mut total = 0usize;
+        let mut base = 0u64;
+        for seg in &mut self.manifest.segments {
+            let path = self.dir.join(&seg.file);
+            let (out, records) = rewrite_file(&path)?;
+            write_replace(&path, out.as_bytes())?;
+            seg.base = base;
+            seg.bytes = out.len() as u64;
+            base += seg.bytes;
+            total += records;
+        }
+        if !self.manifest.segments.is_empty() {
+            self.save_manifest()?;
+        }
+
+        let (out, records) = rewrite_file(&self.active)?;
+        self.replace_active(out.as_bytes())?;
+        Ok(total + records)
+    }
+
+    /// 保存清单（临时文件 + rename）；没有分段时删除清单文件。
+    fn save_manifest(&self) -> Result<(), String> {
+        if self.manifest.segments.is_empty() {
+            return match fs::remove_file(&self.manifest_path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => Err(format!("remove segments.json failed: {e}")),
+                _ => Ok(()),
+            };
+        }
+        let json =
+            serde_json::to_vec_pretty(&self.manifest).map_err(|e| format!("serialize segments.json failed: {e}"))?;
+        write_replace(&self.manifest_path, &json)
+    }
+}
+
+fn write_replace(path: &Path, content: &[u8]) -> Result<(), String> {
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, content).map_err(|e| format!("write {} failed: {e}", tmp.display()))?;
+    if let Err(e) = fs::rename(&tmp, path) {
+        let _ = fs::remove_file(path);
+        fs::rename(&tmp, path).map_err(|_| format!("replace {} failed: {e}", path.display()))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn data_files_should_read_across_segments_and_rotate() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = StorePaths::new(dir.path(), "u1/p1").unwrap();
+        fs::create_dir_all(&paths.namespace_dir).unwrap();
+        fs::write(&paths.memories_path, "aaaa\nbb\n").unwrap();
+
+        let mut files = DataFiles::load(&paths).unwrap();
+        assert!(!files.rotate_if_needed(100).unwrap());
+        assert!(files.rotate_if_needed(8).unwrap());
+        assert_eq!(files.active_base(), 8);
+        fs::write(&paths.memories_path, "ccc\n").unwrap();
+
+        assert_eq!(files.total_len().unwrap(), 12);
+        assert_eq!(files.read_at(5, 3).unwrap(), b"bb\n");
+        assert_eq!(files.read_at(8, 4).unwrap(), b"ccc\n");
+        let mut tail = String::new();
+        files.reader_from(5).unwrap().read_to_string(&mut tail).unwrap();
+        assert_eq!(tail, "bb\nccc\n");
+        assert_eq!(DataFiles::load(&paths).unwrap().sealed(), files.sealed());
+
+        // 改名后、写清单前中断：打开时补记分段。
+        fs::rename(&paths.memories_path, paths.namespace_dir.join(segment_file_name(2))).unwrap();
+        DataFiles::recover(&paths).unwrap();
+        fs::write(&paths.memories_path, "").unwrap();
+        let mut recovered = DataFiles::load(&paths).unwrap();
+        assert_eq!(recovered.active_base(), 12);
+        assert_eq!(recovered.read_lines().unwrap(), vec!["aaaa", "bb", "ccc"]);
+
+        recovered.replace_all(b"dd\n").unwrap();
+        assert!(recovered.sealed().is_empty());
+        assert!(!paths.segments_path.exists());
+        assert!(!paths.namespace_dir.join(segment_file_name(1)).exists());
+        assert_eq!(recovered.read_lines().unwrap(), vec!["dd"]);
+    }
+}
@@ -0,0 +1,56 @@
+use jieba_rs::Jieba;
+use std::collections::HashSet;
+use std::sync::OnceLock;
+
+/// 全局分词器：首次使用时加载内置词典（约百毫秒），之后复用。
+fn jieba() -> &'static Jieba {
+    static JIEBA: OnceLock<Jieba> = OnceLock::new();
+    JIEBA.get_or_init(Jieba::new)
+}
+
+/// 索引用分词：搜索引擎模式（长词同时切出其中的短词），结果小写、去重。
+///
+/// 只保留至少 2 个字符且含字母/数字/汉字的词，单字与标点不进入索引。
+pub fn index_terms(text: &str) -> HashSet<String> {
+    jieba()
+        .cut_for_search(text, true)
+        .into_iter()
+        .filter_map(normalize_term)
+        .collect()
+}
+
+/// 查询用分词：精确模式（较粗粒度，配合索引端的搜索引擎模式可命中长词与短词）。
+pub fn query_terms(text: &str) -> Vec<String> {
+    let mut seen: HashSet<String> = HashSet::new();
+    jieba()
+        .cut(text, true)
+        .into_iter()
+        .filter_map(normalize_term)
+        .filter(|x| seen.insert(x.clone()))
+        .collect()
+}
+
+fn normalize_term(token: &str) -> Option<String> {
+    let t = token.trim().to_lowercase();
+    if t.chars().count() < 2 || !t.chars().any(|c| c.is_alphanumeric()) {
+        return None;
+    }
+    Some(t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn terms_should_split_chinese_words() {
+        let terms = index_terms("我们一起推进了ERP项目的上线计划。");
+        for t in ["erp", "项目", "上线", "计划"] {
+            assert!(terms.contains(t), "missing {t}: {terms:?}");
+        }
+        assert!(!terms.contains("。"));
+        assert!(!terms.contains("了"));
+
+        assert_eq!(query_terms("上线计划 ERP"), vec!["上线", "计划", "erp"]);
+    }
+}
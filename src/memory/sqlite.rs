@@ -0,0 +1,876 @@
+use crate::memory::calendar;
+use crate::memory::index;
+use crate::memory::model::{
+    MemoryItem, RecallArgs, RecallItemOut, RecallRanking, RecallResult, RememberArgs, TransactionStep,
+    RECALL_MAX_LIMIT, RECALL_PINNED_MAX,
+};
+use crate::memory::query::{self, QueryExpr};
+use crate::memory::segment;
+use crate::memory::store::{
+    apply_update, build_new_item, check_new_links, check_revision, ensure_not_frozen, in_time_range, max_opt_i64,
+    min_opt_i64, normalize_keywords, parse_query_time_expr, promote_pinned, read_settings, recall_item_out,
+    CacheValidators, ItemFilter, MemoryHistory, QueryTimeExpr, RememberRecorded, StoreOptions, StorePaths,
+    TransactionOutcome,
+};
+use crate::memory::time::{self, DateBoundKind};
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::time::Duration;
+
+/// 数据库结构版本（meta.schema_version）。
+const SCHEMA_VERSION: i64 = 1;
+/// 其他进程持有写锁时的等待时间。
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+/// trigram 分词器只能匹配不少于 3 个字符的片段；更短的词不参与 FTS 预筛选。
+const FTS_MIN_CHARS: usize = 3;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS meta (key TEXT PRIMARY KEY, value INTEGER NOT NULL);
+CREATE TABLE IF NOT EXISTS revisions (
+    seq INTEGER PRIMARY KEY AUTOINCREMENT,
+    id TEXT NOT NULL,
+    record TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS revisions_id ON revisions (id, seq);
+CREATE TABLE IF NOT EXISTS memories (
+    id TEXT PRIMARY KEY,
+    seq INTEGER NOT NULL,
+    time_ts INTEGER NOT NULL,
+    weekday INTEGER,
+    hour INTEGER,
+    importance INTEGER NOT NULL DEFAULT 0,
+    kind TEXT,
+    supersedes TEXT,
+    expires_ts INTEGER,
+    pinned INTEGER NOT NULL DEFAULT 0,
+    archived INTEGER NOT NULL DEFAULT 0
+);
+CREATE INDEX IF NOT EXISTS memories_time ON memories (time_ts, seq);
+CREATE INDEX IF NOT EXISTS memories_supersedes ON memories (supersedes);
+CREATE TABLE IF NOT EXISTS keywords (
+    keyword TEXT NOT NULL,
+    id TEXT NOT NULL,
+    weight INTEGER NOT NULL,
+    PRIMARY KEY (keyword, id)
+) WITHOUT ROWID;
+CREATE INDEX IF NOT EXISTS keywords_id ON keywords (id);
+CREATE TABLE IF NOT EXISTS terms (
+    term TEXT NOT NULL,
+    id TEXT NOT NULL,
+    PRIMARY KEY (term, id)
+) WITHOUT ROWID;
+CREATE INDEX IF NOT EXISTS terms_id ON terms (id);
+CREATE VIRTUAL TABLE IF NOT EXISTS memories_fts USING fts5(body, tokenize = 'trigram');
+CREATE TABLE IF NOT EXISTS access (
+    id TEXT PRIMARY KEY,
+    count INTEGER NOT NULL,
+    last_recalled_at TEXT NOT NULL
+);
+";
+
+/// SQLite 后端的 namespace：记录、关键字/分词倒排与 FTS5 全文索引同在 memories.sqlite 中。
+///
+/// 每次写入（含 transaction 的全部步骤）在一个 SQLite 事务内完成；修订只追加到 revisions，
+/// memories 等表只保存各 id 的当前版本。数据库即为唯一数据源，多进程并发访问由 SQLite 加锁。
+pub struct SqliteStore {
+    paths: StorePaths,
+    conn: Connection,
+    options: StoreOptions,
+}
+
+/// recall 候选：当前版本的排序与过滤信息（不含记录内容）。
+struct Candidate {
+    id: String,
+    seq: i64,
+    time_ts: i64,
+    importance: i64,
+    pinned: bool,
+    weekday: Option<u8>,
+    hour: Option<u8>,
+}
+
+/// recall 的 SQL 过滤条件（`memories m` 上的 WHERE 子句与参数）。
+#[derive(Default)]
+struct Conditions {
+    clauses: Vec<String>,
+    params: Vec<SqlValue>,
+}
+
+impl Conditions {
+    fn push(&mut self, clause: impl Into<String>, params: impl IntoIterator<Item = SqlValue>) {
+        self.clauses.push(clause.into());
+        self.params.extend(params);
+    }
+
+    fn where_sql(&self) -> String {
+        if self.clauses.is_empty() {
+            String::new()
+        } else {
+            format!(" WHERE {}", self.clauses.join(" AND "))
+        }
+    }
+}
+
+impl SqliteStore {
+    pub fn open_with(paths: StorePaths, options: StoreOptions) -> Result<Self, String> {
+        fs::create_dir_all(&paths.namespace_dir).map_err(|e| format!("create namespace dir failed: {e}"))?;
+        let conn = Connection::open(&paths.sqlite_path).map_err(sql_err)?;
+        conn.busy_timeout(BUSY_TIMEOUT).map_err(sql_err)?;
+        conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get::<_, String>(0))
+            .map_err(sql_err)?;
+        Self::init(paths, conn, options)
+    }
+
+    /// 进程内的内存数据库（`MEMORY_BACKEND=memory`）：不创建任何文件，连接关闭即丢失。
+    pub fn open_in_memory(paths: StorePaths, options: StoreOptions) -> Result<Self, String> {
+        let conn = Connection::open_in_memory().map_err(sql_err)?;
+        Self::init(paths, conn, options)
+    }
+
+    /// 建表并校验结构版本。
+    fn init(paths: StorePaths, conn: Connection, options: StoreOptions) -> Result<Self, String> {
+        conn.execute_batch(SCHEMA).map_err(sql_err)?;
+
+        let version: Option<i64> = conn
+            .query_row("SELECT value FROM meta WHERE key = 'schema_version'", [], |row| row.get(0))
+            .optional()
+            .map_err(sql_err)?;
+        match version {
+            None => {
+                conn.execute(
+                    "INSERT INTO meta (key, value) VALUES ('schema_version', ?1), ('generation', 0)",
+                    params![SCHEMA_VERSION],
+                )
+                .map_err(sql_err)?;
+            }
+            Some(v) if v > SCHEMA_VERSION => {
+                return Err(format!(
+                    "memories.sqlite 的结构版本 {v} 高于当前程序支持的 {SCHEMA_VERSION}，请升级程序"
+                ));
+            }
+            Some(_) => {}
+        }
+
+        Ok(Self { paths, conn, options })
+    }
+
+    pub fn namespace(&self) -> &str {
+        &self.paths.namespace
+    }
+
+    pub fn set_writer(&mut self, writer: Option<String>) {
+        self.options.writer = writer;
+    }
+
+    /// 变更代数（每次写入事务递增）。
+    pub fn generation(&self) -> u64 {
+        meta_value(&self.conn, "generation").unwrap_or_default().max(0) as u64
+    }
+
+    pub fn append_memory(&mut self, args: RememberArgs) -> Result<RememberRecorded, String> {
+        ensure_not_frozen(&self.paths)?;
+        let item = build_new_item(&self.paths.namespace, args, self.options.writer.as_deref())?;
+        let tx = self.conn.transaction().map_err(sql_err)?;
+        check_new_links(&item, None, |id| head_exists(&tx, id))?;
+        put_item(&tx, &item)?;
+        bump_generation(&tx)?;
+        tx.commit().map_err(sql_err)?;
+
+        Ok(RememberRecorded {
+            id: item.id,
+            recorded_at: item.recorded_at,
+            occurred_at: item.occurred_at,
+            keywords: item.keywords,
+            revision: item.revision,
+        })
+    }
+
+    /// 在同一个 SQLite 事务中执行全部步骤；任一步骤失败时整体回滚。
+    pub fn apply_transaction(&mut self, steps: Vec<TransactionStep>) -> Result<Vec<TransactionOutcome>, String> {
+        ensure_not_frozen(&self.paths)?;
+        let writer = self.options.writer.clone();
+        let tx = self.conn.transaction().map_err(sql_err)?;
+
+        let mut outcomes: Vec<TransactionOutcome> = Vec::with_capacity(steps.len());
+        for (i, step) in steps.into_iter().enumerate() {
+            let outcome = apply_step(&tx, &self.paths.namespace, writer.as_deref(), step)
+                .map_err(|e| format!("steps[{i}]: {e}"))?;
+            outcomes.push(outcome);
+        }
+
+        bump_generation(&tx)?;
+        tx.commit().map_err(sql_err)?;
+        Ok(outcomes)
+    }
+
+    pub fn get(&mut self, id: &str) -> Result<MemoryItem, String> {
+        load_head(&self.conn, id.trim())?.ok_or_else(|| format!("记忆不存在：{}", id.trim()))
+    }
+
+    /// 按 id 读取全部修订（含已删除 id 的历史版本）。
+    pub fn history(&mut self, id: &str) -> Result<MemoryHistory, String> {
+        let id = id.trim();
+        let mut stmt = self
+            .conn
+            .prepare_cached("SELECT record FROM revisions WHERE id = ?1 ORDER BY seq")
+            .map_err(sql_err)?;
+        let versions = stmt
+            .query_map(params![id], |row| row.get::<_, String>(0))
+            .map_err(sql_err)?
+            .map(|line| parse_record(&line.map_err(sql_err)?))
+            .collect::<Result<Vec<_>, String>>()?;
+        if versions.is_empty() {
+            return Err(format!("记忆不存在：{id}"));
+        }
+        Ok(MemoryHistory {
+            versions,
+            deleted: !head_exists(&self.conn, id),
+        })
+    }
+
+    /// 取代了指定记忆的新记忆 id（未被取代时为空）。
+    pub fn superseded_by(&self, id: &str) -> Vec<String> {
+        superseded_by(&self.conn, id.trim()).unwrap_or_default()
+    }
+
+    pub fn cache_validators(&mut self) -> Result<CacheValidators, String> {
+        let generation = self.generation();
+        let max_seq: i64 = self
+            .conn
+            .query_row("SELECT COALESCE(MAX(seq), 0) FROM revisions", [], |row| row.get(0))
+            .map_err(sql_err)?;
+        Ok(CacheValidators {
+            generation,
+            etag: format!("\"{generation}-{max_seq}\""),
+            last_modified: meta_value(&self.conn, "last_modified_ts").map(time::ts_to_http_date),
+        })
+    }
+
+    /// 记录本次 recall 实际返回的记忆；与 JSONL 后端一样不递增变更代数，写入失败不影响 recall。
+    pub fn record_recalled(&mut self, ids: &[String]) {
+        if ids.is_empty() {
+            return;
+        }
+        let now = time::now_rfc3339_and_ts().0;
+        let Ok(tx) = self.conn.transaction() else {
+            return;
+        };
+        for id in ids {
+            let _ = tx.execute(
+                "INSERT INTO access (id, count, last_recalled_at) VALUES (?1, 1, ?2)
+                 ON CONFLICT (id) DO UPDATE SET count = count + 1, last_recalled_at = excluded.last_recalled_at",
+                params![id, now],
+            );
+        }
+        let _ = tx.commit();
+    }
+
+    /// 与 JSONL 后端相同的召回语义（关键字/分词命中排序、query、时间与分面过滤、置顶、分页）；
+    /// 候选由 SQL 索引与 FTS5 预筛选，只读取需要返回的记录。暂不支持 semantic 与 decay 排序。
+    pub fn recall(&mut self, mut args: RecallArgs) -> Result<RecallResult, String> {
+        if args.semantic {
+            return Err("SQLite 后端暂不支持 semantic 召回".to_string());
+        }
+        if args.ranking.unwrap_or(self.options.recall_ranking) == RecallRanking::Decay {
+            return Err("SQLite 后端暂不支持 decay 排序".to_string());
+        }
+        let max_limit = read_settings(&self.paths)?
+            .recall_max_limit
+            .or(self.options.recall_max_limit)
+            .unwrap_or(RECALL_MAX_LIMIT);
+        args.limit = args.limit.min(max_limit);
+
+        let keywords = normalize_keywords(args.keywords);
+        if let Some(bad) = keywords.iter().find(|kw| kw.chars().all(|c| c == '*')) {
+            return Err(format!("关键字通配符至少需要包含 1 个非 * 字符：{bad}"));
+        }
+        let keyword_filter: Option<&[String]> = (!keywords.is_empty()).then_some(keywords.as_slice());
+        let item_filter = ItemFilter {
+            written_by: args.written_by.as_deref(),
+            source_kind: args.source_kind.as_deref(),
+            source_id: args.source_id.as_deref(),
+        };
+        // 正则模式下 query 整体为模式：不剥离时间表达式。
+        let time_expr = if args.regex {
+            QueryTimeExpr::literal(args.query.clone())
+        } else {
+            parse_query_time_expr(args.query.as_deref())
+        };
+        let query = match time_expr.text.as_deref() {
+            Some(text) if args.regex => QueryExpr::parse_regex(text)?,
+            Some(text) => QueryExpr::parse(text)?,
+            None => None,
+        };
+        if args.regex && query.is_none() {
+            return Err("regex=true 时必须提供非空的 query".to_string());
+        }
+        let tags = index::normalize_tags(&args.tags);
+
+        let start_ts = match args.start.as_deref() {
+            Some(s) => Some(time::parse_time_to_ts_and_canonical(s, DateBoundKind::Start)?.0),
+            None => None,
+        };
+        let end_ts = match args.end.as_deref() {
+            Some(s) => Some(time::parse_time_to_ts_and_canonical(s, DateBoundKind::End)?.0),
+            None => None,
+        };
+        let start_ts = max_opt_i64(start_ts, time_expr.start_ts);
+        let end_ts = min_opt_i64(end_ts, time_expr.end_ts);
+
+        let mut conditions = Conditions::default();
+        conditions.push(
+            "(m.expires_ts IS NULL OR m.expires_ts > ?)",
+            [SqlValue::Integer(time::now_rfc3339_and_ts().1)],
+        );
+        if let Some(s) = start_ts {
+            conditions.push("m.time_ts >= ?", [SqlValue::Integer(s)]);
+        }
+        if let Some(e) = end_ts {
+            conditions.push("m.time_ts <= ?", [SqlValue::Integer(e)]);
+        }
+        if !args.include_archived {
+            conditions.push("m.archived = 0", []);
+        }
+        if !args.include_superseded {
+            conditions.push("NOT EXISTS (SELECT 1 FROM memories s WHERE s.supersedes = m.id)", []);
+        }
+        if !args.kinds.is_empty() {
+            let marks = vec!["?"; args.kinds.len()].join(", ");
+            conditions.push(
+                format!("m.kind IN ({marks})"),
+                args.kinds.iter().map(|k| SqlValue::Text(k.as_str().to_string())),
+            );
+        }
+        if let Some(expr) = query.as_ref() {
+            let mut phrases: Vec<String> = Vec::new();
+            required_fts_phrases(expr, &mut phrases);
+            if !phrases.is_empty() {
+                let pattern = phrases
+                    .iter()
+                    .map(|p| format!("\"{}\"", p.replace('"', "\"\"")))
+                    .collect::<Vec<_>>()
+                    .join(" AND ");
+                conditions.push(
+                    "m.seq IN (SELECT rowid FROM memories_fts WHERE memories_fts MATCH ?)",
+                    [SqlValue::Text(pattern)],
+                );
+            }
+        }
+
+        let calendar = &self.options.calendar;
+        let slot_allowed = |c: &Candidate| time_expr.slot_allowed_at(c.time_ts, c.weekday, c.hour, calendar);
+        let order: Vec<Candidate> = if keywords.is_empty() {
+            // 无关键字：按时间倒序（近 → 远）。
+            let sql = format!("{}{} ORDER BY m.time_ts DESC, m.seq DESC", CANDIDATE_SELECT, conditions.where_sql());
+            let mut stmt = self.conn.prepare(&sql).map_err(sql_err)?;
+            let rows = stmt
+                .query_map(params_from_iter(conditions.params.iter()), candidate_from_row)
+                .map_err(sql_err)?;
+            let mut out: Vec<Candidate> = Vec::new();
+            for row in rows {
+                let c = row.map_err(sql_err)?;
+                if slot_allowed(&c) {
+                    out.push(c);
+                }
+            }
+            out
+        } else {
+            // 有关键字：关键字命中权重和 + 分词命中数，其次重要度、时间（与 JSONL 后端一致）。
+            let counts = self.keyword_hits(&keywords)?;
+            conditions.push("m.id = ?", []);
+            let sql = format!("{}{}", CANDIDATE_SELECT, conditions.where_sql());
+            let mut stmt = self.conn.prepare(&sql).map_err(sql_err)?;
+            let mut scored: Vec<((u32, u32), Candidate)> = Vec::new();
+            for (id, hit) in counts {
+                let mut params = conditions.params.clone();
+                params.push(SqlValue::Text(id));
+                let found = stmt
+                    .query_row(params_from_iter(params.iter()), candidate_from_row)
+                    .optional()
+                    .map_err(sql_err)?;
+                if let Some(c) = found.filter(|c| in_time_range(c.time_ts, start_ts, end_ts) && slot_allowed(c)) {
+                    scored.push((hit, c));
+                }
+            }
+            scored.sort_by(|(ha, a), (hb, b)| {
+                hb.cmp(ha)
+                    .then_with(|| b.importance.cmp(&a.importance))
+                    .then_with(|| b.time_ts.cmp(&a.time_ts))
+                    .then_with(|| b.seq.cmp(&a.seq))
+            });
+            scored.into_iter().map(|x| x.1).collect()
+        };
+
+        let pinned_max = self.options.recall_pinned_max.unwrap_or(RECALL_PINNED_MAX);
+        let ranked = promote_pinned((0..order.len() as u32).collect(), pinned_max, |i| order[i as usize].pinned);
+
+        let want = args.offset.saturating_add(args.limit);
+        let mut results: Vec<RecallItemOut> = Vec::new();
+        let mut warnings: Vec<String> = Vec::new();
+        for i in ranked {
+            if results.len() > want {
+                break;
+            }
+            let candidate = &order[i as usize];
+            let item = match load_revision(&self.conn, candidate.seq) {
+                Ok(item) => item,
+                Err(e) => {
+                    warnings.push(format!("已跳过无法读取的记忆 id={}：{e}", candidate.id));
+                    continue;
+                }
+            };
+            if !tags.iter().all(|t| item.tags.contains(t))
+                || !item_filter.accepts(&item)
+                || !query_matches(query.as_ref(), &item)
+            {
+                continue;
+            }
+            results.push(recall_item_out(item, keyword_filter, args.include_diary));
+        }
+
+        let has_more = results.len() > want;
+        results.truncate(want);
+        results.drain(..args.offset.min(results.len()));
+        for item in &mut results {
+            item.links.superseded_by = superseded_by(&self.conn, &item.id)?;
+            let stat: Option<(u64, String)> = self
+                .conn
+                .query_row(
+                    "SELECT count, last_recalled_at FROM access WHERE id = ?1",
+                    params![item.id],
+                    |row| Ok((row.get::<_, i64>(0)?.max(0) as u64, row.get(1)?)),
+                )
+                .optional()
+                .map_err(sql_err)?;
+            if let Some((count, at)) = stat {
+                item.recall_count = count;
+                item.last_recalled_at = Some(at);
+            }
+        }
+
+        Ok(RecallResult {
+            total: results.len(),
+            items: results,
+            warnings,
+            offset: args.offset,
+            has_more,
+            next_cursor: None,
+        })
+    }
+
+    /// 关键字倒排与分词倒排求并集：id -> (关键字命中权重和, 分词命中数)。
+    /// 通配符只匹配关键字；同一条目命中多个展开关键字时取其中最大权重。
+    fn keyword_hits(&self, keywords: &[String]) -> Result<HashMap<String, (u32, u32)>, String> {
+        let mut by_keyword = self
+            .conn
+            .prepare_cached("SELECT id, weight FROM keywords WHERE keyword = ?1")
+            .map_err(sql_err)?;
+        let mut by_term = self
+            .conn
+            .prepare_cached("SELECT id FROM terms WHERE term = ?1")
+            .map_err(sql_err)?;
+
+        let mut counts: HashMap<String, (u32, u32)> = HashMap::new();
+        for kw in keywords {
+            let matched = if index::is_keyword_pattern(kw) {
+                self.expand_pattern(kw)?
+            } else {
+                vec![kw.clone()]
+            };
+            let mut best: HashMap<String, u32> = HashMap::new();
+            for keyword in matched {
+                let rows = by_keyword
+                    .query_map(params![keyword], |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?)))
+                    .map_err(sql_err)?;
+                for row in rows {
+                    let (id, weight) = row.map_err(sql_err)?;
+                    let slot = best.entry(id).or_default();
+                    *slot = (*slot).max(weight.max(0) as u32);
+                }
+            }
+            for (id, weight) in best {
+                counts.entry(id).or_default().0 += weight;
+            }
+            if index::is_keyword_pattern(kw) {
+                continue;
+            }
+            let rows = by_term
+                .query_map(params![kw], |row| row.get::<_, String>(0))
+                .map_err(sql_err)?;
+            for id in rows {
+                counts.entry(id.map_err(sql_err)?).or_default().1 += 1;
+            }
+        }
+        Ok(counts)
+    }
+
+    /// 通配符关键字展开为词表中匹配的关键字（按 `*` 之前的前缀做范围扫描）。
+    fn expand_pattern(&self, pattern: &str) -> Result<Vec<String>, String> {
+        let prefix = &pattern[..pattern.find('*').unwrap_or(pattern.len())];
+        let mut stmt = self
+            .conn
+            .prepare_cached("SELECT DISTINCT keyword FROM keywords WHERE keyword >= ?1 ORDER BY keyword")
+            .map_err(sql_err)?;
+        let rows = stmt
+            .query_map(params![prefix], |row| row.get::<_, String>(0))
+            .map_err(sql_err)?;
+        let mut out: Vec<String> = Vec::new();
+        for keyword in rows {
+            let keyword = keyword.map_err(sql_err)?;
+            if !keyword.starts_with(prefix) {
+                break;
+            }
+            if index::keyword_matches_pattern(pattern, &keyword) {
+                out.push(keyword);
+            }
+        }
+        Ok(out)
+    }
+}
+
+const CANDIDATE_SELECT: &str =
+    "SELECT m.id, m.seq, m.time_ts, m.importance, m.pinned, m.weekday, m.hour FROM memories m";
+
+fn candidate_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Candidate> {
+    Ok(Candidate {
+        id: row.get(0)?,
+        seq: row.get(1)?,
+        time_ts: row.get(2)?,
+        importance: row.get(3)?,
+        pinned: row.get(4)?,
+        weekday: row.get(5)?,
+        hour: row.get(6)?,
+    })
+}
+
+fn apply_step(
+    conn: &Connection,
+    namespace: &str,
+    writer: Option<&str>,
+    step: TransactionStep,
+) -> Result<TransactionOutcome, String> {
+    match step {
+        TransactionStep::Remember(args) => {
+            let item = build_new_item(namespace, args, writer)?;
+            check_new_links(&item, None, |id| head_exists(conn, id))?;
+            put_item(conn, &item)?;
+            Ok(TransactionOutcome {
+                op: "remember",
+                id: item.id,
+                revision: Some(item.revision),
+            })
+        }
+        TransactionStep::Update(args) => {
+            let current = load_head(conn, &args.id)?.ok_or_else(|| format!("记忆不存在：{}", args.id))?;
+            let item = apply_update(current.clone(), args, writer)?;
+            check_new_links(&item, Some(&current), |id| head_exists(conn, id))?;
+            put_item(conn, &item)?;
+            Ok(TransactionOutcome {
+                op: "update",
+                id: item.id,
+                revision: Some(item.revision),
+            })
+        }
+        TransactionStep::Forget { id, revision } => {
+            let current = load_head(conn, &id)?.ok_or_else(|| format!("记忆不存在：{id}"))?;
+            check_revision(&current, revision)?;
+            remove_head(conn, &current.id)?;
+            conn.execute("DELETE FROM access WHERE id = ?1", params![current.id])
+                .map_err(sql_err)?;
+            Ok(TransactionOutcome {
+                op: "forget",
+                id: current.id,
+                revision: None,
+            })
+        }
+    }
+}
+
+/// 追加一条修订并替换该 id 的当前版本及其倒排、全文索引。
+fn put_item(conn: &Connection, item: &MemoryItem) -> Result<(), String> {
+    let record = serde_json::to_string(item).map_err(|e| format!("serialize memory failed: {e}"))?;
+    conn.execute("INSERT INTO revisions (id, record) VALUES (?1, ?2)", params![item.id, record])
+        .map_err(sql_err)?;
+    let seq = conn.last_insert_rowid();
+    remove_head(conn, &item.id)?;
+
+    let recorded_ts = time::parse_time_to_ts_and_canonical(&item.recorded_at, DateBoundKind::Start)
+        .map(|x| x.0)
+        .unwrap_or(0);
+    let time_ts = item
+        .occurred_at
+        .as_deref()
+        .and_then(|s| time::parse_time_to_ts_and_canonical(s, DateBoundKind::Start).ok())
+        .map_or(recorded_ts, |x| x.0);
+    let (weekday, hour) = calendar::weekday_and_hour(item.occurred_at.as_deref().unwrap_or(&item.recorded_at));
+    let expires_ts = item
+        .expires_at
+        .as_deref()
+        .and_then(|s| time::parse_time_to_ts_and_canonical(s, DateBoundKind::End).ok())
+        .map(|x| x.0);
+    conn.execute(
+        "INSERT INTO memories (id, seq, time_ts, weekday, hour, importance, kind, supersedes, expires_ts, pinned, archived)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+        params![
+            item.id,
+            seq,
+            time_ts,
+            weekday,
+            hour,
+            item.importance.unwrap_or(0),
+            item.kind.map(|k| k.as_str()),
+            item.supersedes,
+            expires_ts,
+            item.pinned,
+            item.archived
+        ],
+    )
+    .map_err(sql_err)?;
+
+    for kw in normalize_keywords(item.keywords.clone()) {
+        let weight = item.keyword_weights.get(&kw).map_or(1, |&w| i64::from(w));
+        conn.execute(
+            "INSERT OR REPLACE INTO keywords (keyword, id, weight) VALUES (?1, ?2, ?3)",
+            params![kw, item.id, weight],
+        )
+        .map_err(sql_err)?;
+    }
+    for term in segment::index_terms(&format!("{}\n{}", item.slice, item.diary)) {
+        conn.execute("INSERT OR IGNORE INTO terms (term, id) VALUES (?1, ?2)", params![term, item.id])
+            .map_err(sql_err)?;
+    }
+    conn.execute(
+        "INSERT INTO memories_fts (rowid, body) VALUES (?1, ?2)",
+        params![seq, index::query_haystack(item)],
+    )
+    .map_err(sql_err)?;
+    Ok(())
+}
+
+/// 将指定 id 的当前版本移出 memories 与各倒排（修订保留在 revisions 中）。
+fn remove_head(conn: &Connection, id: &str) -> Result<(), String> {
+    let seq: Option<i64> = conn
+        .query_row("SELECT seq FROM memories WHERE id = ?1", params![id], |row| row.get(0))
+        .optional()
+        .map_err(sql_err)?;
+    let Some(seq) = seq else {
+        return Ok(());
+    };
+    conn.execute("DELETE FROM memories_fts WHERE rowid = ?1", params![seq])
+        .map_err(sql_err)?;
+    for sql in [
+        "DELETE FROM memories WHERE id = ?1",
+        "DELETE FROM keywords WHERE id = ?1",
+        "DELETE FROM terms WHERE id = ?1",
+    ] {
+        conn.execute(sql, params![id]).map_err(sql_err)?;
+    }
+    Ok(())
+}
+
+fn bump_generation(conn: &Connection) -> Result<(), String> {
+    conn.execute("UPDATE meta SET value = value + 1 WHERE key = 'generation'", [])
+        .map_err(sql_err)?;
+    conn.execute(
+        "INSERT OR REPLACE INTO meta (key, value) VALUES ('last_modified_ts', ?1)",
+        params![time::now_rfc3339_and_ts().1],
+    )
+    .map_err(sql_err)?;
+    Ok(())
+}
+
+fn meta_value(conn: &Connection, key: &str) -> Option<i64> {
+    conn.query_row("SELECT value FROM meta WHERE key = ?1", params![key], |row| row.get(0))
+        .ok()
+}
+
+fn head_exists(conn: &Connection, id: &str) -> bool {
+    conn.query_row("SELECT 1 FROM memories WHERE id = ?1", params![id], |_| Ok(()))
+        .optional()
+        .ok()
+        .flatten()
+        .is_some()
+}
+
+fn load_head(conn: &Connection, id: &str) -> Result<Option<MemoryItem>, String> {
+    let seq: Option<i64> = conn
+        .query_row("SELECT seq FROM memories WHERE id = ?1", params![id], |row| row.get(0))
+        .optional()
+        .map_err(sql_err)?;
+    seq.map(|seq| load_revision(conn, seq)).transpose()
+}
+
+fn load_revision(conn: &Connection, seq: i64) -> Result<MemoryItem, String> {
+    let record: String = conn
+        .query_row("SELECT record FROM revisions WHERE seq = ?1", params![seq], |row| row.get(0))
+        .map_err(sql_err)?;
+    parse_record(&record)
+}
+
+/// 解析记录并校验 checksum（与 JSONL 后端一致：内容与校验和不一致时报错）。
+fn parse_record(record: &str) -> Result<MemoryItem, String> {
+    let item: MemoryItem = serde_json::from_str(record).map_err(|e| format!("parse memory record failed: {e}"))?;
+    if item.verify_checksum() == Some(false) {
+        return Err(format!(
+            "记忆校验失败：id={} 内容与 checksum 不一致（可能被外部修改或损坏）",
+            item.id
+        ));
+    }
+    Ok(item)
+}
+
+fn superseded_by(conn: &Connection, id: &str) -> Result<Vec<String>, String> {
+    let mut stmt = conn
+        .prepare_cached("SELECT id FROM memories WHERE supersedes = ?1 ORDER BY seq")
+        .map_err(sql_err)?;
+    let rows = stmt
+        .query_map(params![id], |row| row.get::<_, String>(0))
+        .map_err(sql_err)?;
+    rows.map(|x| x.map_err(sql_err)).collect()
+}
+
+/// 查询表达式中必须出现的片段（只取顶层 AND 链上的词组），用于 FTS5 预筛选候选。
+///
+/// 词组的分词命中要求每个分词都出现在 slice/diary 中，子串匹配要求整个词组出现，
+/// 两种情况下每个分词都是正文的子串，因此按分词预筛选不会漏掉匹配项。
+fn required_fts_phrases(expr: &QueryExpr, out: &mut Vec<String>) {
+    match expr {
+        QueryExpr::And(list) => list.iter().for_each(|x| required_fts_phrases(x, out)),
+        QueryExpr::Term(term) => {
+            let parts: Vec<String> = if term.exact {
+                term.text.split_whitespace().map(str::to_string).collect()
+            } else {
+                segment::query_terms(&term.text)
+            };
+            out.extend(parts.into_iter().filter(|p| p.chars().count() >= FTS_MIN_CHARS));
+        }
+        _ => {}
+    }
+}
+
+/// 在记录上判断 query：词组的分词命中按记录自身的分词判定（与索引绑定后的语义一致）。
+fn query_matches(expr: Option<&QueryExpr>, item: &MemoryItem) -> bool {
+    expr.is_none_or(|e| match e {
+        QueryExpr::Regex(_) => query::item_matches(Some(e), 0, item),
+        _ => {
+            let terms: HashSet<String> = segment::index_terms(&format!("{}\n{}", item.slice, item.diary));
+            let term_hit = |term: &query::QueryTerm| {
+                let wanted = segment::query_terms(&term.text);
+                !wanted.is_empty() && wanted.iter().all(|t| terms.contains(t))
+            };
+            e.matches_with(&index::query_haystack(item), &item.metadata, &term_hit)
+        }
+    })
+}
+
+fn sql_err(e: rusqlite::Error) -> String {
+    format!("sqlite: {e}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::model::UpdateArgs;
+
+    fn open(dir: &std::path::Path) -> SqliteStore {
+        let paths = StorePaths::new(dir, "u1/p1").unwrap();
+        SqliteStore::open_with(paths, StoreOptions::default()).unwrap()
+    }
+
+    fn remember(store: &mut SqliteStore, keywords: &[&str], slice: &str) -> String {
+        store
+            .append_memory(RememberArgs {
+                namespace: "u1/p1".to_string(),
+                keywords: keywords.iter().map(|x| x.to_string()).collect(),
+                slice: slice.to_string(),
+                diary: format!("{slice}（详细）"),
+                ..Default::default()
+            })
+            .unwrap()
+            .id
+    }
+
+    fn recall(store: &mut SqliteStore, keywords: &[&str], query: Option<&str>) -> Vec<String> {
+        let result = store
+            .recall(RecallArgs {
+                namespace: "u1/p1".to_string(),
+                keywords: keywords.iter().map(|x| x.to_string()).collect(),
+                query: query.map(str::to_string),
+                limit: 10,
+                ..Default::default()
+            })
+            .unwrap();
+        result.items.into_iter().map(|x| x.slice).collect()
+    }
+
+    #[test]
+    fn sqlite_store_should_remember_recall_update_and_forget() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = open(dir.path());
+        let a = remember(&mut store, &["数据库", "迁移"], "数据库迁移到 PostgreSQL");
+        remember(&mut store, &["部署"], "周五部署上线");
+        remember(&mut store, &["deploy-script"], "deploy script 需要重写");
+
+        assert_eq!(recall(&mut store, &["数据库"], None), vec!["数据库迁移到 PostgreSQL"]);
+        assert_eq!(recall(&mut store, &["deploy*"], None), vec!["deploy script 需要重写"]);
+        assert_eq!(recall(&mut store, &[], Some("postgresql")), vec!["数据库迁移到 PostgreSQL"]);
+        assert_eq!(recall(&mut store, &[], Some("上线 OR 重写")).len(), 2);
+        assert_eq!(recall(&mut store, &[], None).len(), 3);
+
+        let outcomes = store
+            .apply_transaction(vec![TransactionStep::Update(UpdateArgs {
+                id: a.clone(),
+                revision: 1,
+                slice: Some("数据库迁移到 MySQL".to_string()),
+                diary: Some("改用 MySQL".to_string()),
+                ..Default::default()
+            })])
+            .unwrap();
+        assert_eq!(outcomes[0].revision, Some(2));
+        assert!(recall(&mut store, &[], Some("postgresql")).is_empty());
+        assert_eq!(store.get(&a).unwrap().slice, "数据库迁移到 MySQL");
+
+        let generation = store.generation();
+        store
+            .apply_transaction(vec![TransactionStep::Forget { id: a.clone(), revision: 2 }])
+            .unwrap();
+        assert!(store.generation() > generation);
+        assert!(store.get(&a).is_err());
+        let history = store.history(&a).unwrap();
+        assert_eq!(history.versions.len(), 2);
+        assert!(history.deleted);
+        assert!(recall(&mut store, &["数据库"], None).is_empty());
+
+        // 重新打开后数据仍在。
+        drop(store);
+        let mut store = open(dir.path());
+        assert_eq!(recall(&mut store, &[], None).len(), 2);
+    }
+
+    #[test]
+    fn sqlite_transaction_should_roll_back_on_failure() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = open(dir.path());
+        let before = store.cache_validators().unwrap();
+
+        let err = store
+            .apply_transaction(vec![
+                TransactionStep::Remember(RememberArgs {
+                    namespace: "u1/p1".to_string(),
+                    keywords: vec!["k".to_string()],
+                    slice: "应被回滚".to_string(),
+                    ..Default::default()
+                }),
+                TransactionStep::Forget {
+                    id: "missing".to_string(),
+                    revision: 1,
+                },
+            ])
+            .unwrap_err();
+        assert!(err.starts_with("steps[1]:"), "{err}");
+        assert!(recall(&mut store, &[], None).is_empty());
+        assert_eq!(store.cache_validators().unwrap().etag, before.etag);
+    }
+}
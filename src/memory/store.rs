@@ -1,18 +1,68 @@
-use crate::memory::index::{IndexData, INDEX_VERSION};
-use crate::memory::model::{MemoryItem, RecallArgs, RecallItemOut, RecallResult, RememberArgs};
+use crate::memory::audit::{self, KeywordFinding};
+use crate::memory::backup;
+use crate::memory::blob;
+use crate::memory::calendar::{self, Calendar};
+use crate::memory::catalog::{Catalog, CatalogEntry};
+use crate::memory::embedding::{self, EmbeddingCache, Embedder};
+use crate::memory::export::ExportRecord;
+use crate::memory::heat::{self, KeywordHeat};
+use crate::memory::index::{self, IndexData, IndexItem, INDEX_VERSION};
+use crate::memory::journal::{self, PendingCommit, Recovery};
+use crate::memory::log::{self, LogLevel};
+use crate::memory::mapped::{self, MappedPostings};
+use crate::memory::model::{
+    check_half_life_days, MemoryItem, MemoryLinks, RecallArgs, RecallItemOut, RecallRanking, RecallResult,
+    RememberArgs, Tombstone, TransactionStep, UpdateArgs, KeywordMatchMode, KeywordSort, DECAY_HALF_LIFE_DAYS,
+    IMPORTANCE_HALF_LIFE_DAYS, KEYWORD_WEIGHT_MAX, RECALL_LIMIT_CEILING, RECALL_MAX_LIMIT, RECALL_PINNED_MAX,
+};
+use crate::memory::preview::{self, Redaction};
+use crate::memory::query::{self, QueryExpr};
+use crate::memory::rotation::DataFiles;
+use crate::memory::segment;
 use crate::memory::time::{self, DateBoundKind};
-use std::collections::{HashMap, HashSet};
-use std::fs::{self, File, OpenOptions};
-use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use crate::memory::wal;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Write};
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Instant, SystemTime};
 use uuid::Uuid;
 
+/// 结构化日志（`notifications/message`）中存储层事件的 logger 名称。
+const LOGGER: &str = "memory.store";
+
 #[derive(Debug, Clone)]
 pub struct StorePaths {
+    pub root_dir: PathBuf,
     pub namespace: String,
     pub namespace_dir: PathBuf,
     pub memories_path: PathBuf,
     pub index_path: PathBuf,
+    /// 全文倒排（trigram / 分词），与 index.json 分开存放以便按需加载。
+    pub postings_path: PathBuf,
+    /// 全文倒排的紧凑二进制形式（`MEMORY_INDEX_MMAP` 启用时生成，供多进程只读映射共享）。
+    pub postings_bin_path: PathBuf,
+    /// 语义召回的向量缓存（按需生成，可随时删除）。
+    pub embeddings_path: PathBuf,
+    pub wal_path: PathBuf,
+    /// 写入中的提交意图（追加前写入、完成后删除；打开时存在即说明上次写入中断）。
+    pub journal_path: PathBuf,
+    /// 写入锁文件：追加写入与打开时的中断恢复互斥（跨进程）。
+    pub lock_path: PathBuf,
+    /// 召回统计（id -> 被 recall 返回的次数与最近时间），与 index.json 分开存放。
+    pub access_path: PathBuf,
+    /// 冻结标记文件：存在时该 namespace 只读（仍可 recall）。
+    pub frozen_path: PathBuf,
+    /// namespace 级设置（覆盖服务端配置，如 recall 单页上限）。
+    pub settings_path: PathBuf,
+    /// 已封存分段的清单（`MEMORY_SEGMENT_MAX_BYTES` 启用轮转后生成）。
+    pub segments_path: PathBuf,
+    /// SQLite 后端的数据库文件（记录与倒排同在一个文件中，`MEMORY_BACKEND=sqlite` 时使用）。
+    #[cfg(feature = "sqlite")]
+    pub sqlite_path: PathBuf,
 }
 
 impl StorePaths {
@@ -21,6 +71,10 @@ impl StorePaths {
         if raw.is_empty() {
             return Err("namespace 不能为空".to_string());
         }
+        // 模式不对应单个 namespace：避免 `*` 被净化成 `_` 后写入意料之外的目录。
+        if is_namespace_pattern(raw) {
+            return Err(format!("namespace 模式（含 *）只能用于 recall / recall_global / keywords_list：{raw}"));
+        }
 
         let parts = parse_namespace_components(raw)?;
         let namespace = parts.join("/");
@@ -32,19 +86,528 @@ impl StorePaths {
 
         let memories_path = namespace_dir.join("memories.jsonl");
         let index_path = namespace_dir.join("index.json");
+        let postings_path = namespace_dir.join("postings.json");
+        let postings_bin_path = namespace_dir.join("postings.bin");
+        let embeddings_path = namespace_dir.join("embeddings.json");
+        let wal_path = namespace_dir.join("wal.jsonl");
+        let journal_path = namespace_dir.join("commit.json");
+        let lock_path = namespace_dir.join("commit.lock");
+        let access_path = namespace_dir.join("access.json");
+        let frozen_path = namespace_dir.join("frozen.json");
+        let settings_path = namespace_dir.join("settings.json");
+        let segments_path = namespace_dir.join("segments.json");
 
         Ok(Self {
+            root_dir: root_dir.to_path_buf(),
             namespace,
-            namespace_dir,
             memories_path,
             index_path,
+            postings_path,
+            postings_bin_path,
+            embeddings_path,
+            wal_path,
+            journal_path,
+            lock_path,
+            access_path,
+            frozen_path,
+            settings_path,
+            segments_path,
+            #[cfg(feature = "sqlite")]
+            sqlite_path: namespace_dir.join("memories.sqlite"),
+            namespace_dir,
         })
     }
 }
 
+/// 存储层可选行为（由引擎统一构造后传给每个 namespace）。
+#[derive(Debug, Clone, Default)]
+pub struct StoreOptions {
+    /// 是否为每次写入额外记录 WAL（wal.jsonl），用于按时间点恢复。
+    pub wal: bool,
+    /// 工作日日历（`workdays:` 查询表达式使用）。
+    pub calendar: Arc<Calendar>,
+    /// 写入者身份：写入每条记录的 `written_by`。
+    pub writer: Option<String>,
+    /// 语义召回的向量化后端（`MEMORY_EMBEDDING_CONFIG`）。
+    pub embedder: Arc<Embedder>,
+    /// 全文倒排改为只读映射 postings.bin（多个 stdio 进程共享页缓存，而不是各持一份副本）。
+    pub mmap_index: bool,
+    /// recall 单页条数上限（`MEMORY_RECALL_MAX_LIMIT`）；None 时为 [`RECALL_MAX_LIMIT`]。namespace 的 settings.json 优先。
+    pub recall_max_limit: Option<usize>,
+    /// recall 中置顶记忆最多占用的条数（`MEMORY_RECALL_PINNED_MAX`）；None 时为 [`RECALL_PINNED_MAX`]。
+    pub recall_pinned_max: Option<usize>,
+    /// recall 未指定 ranking 时的默认排序方式（`MEMORY_RECALL_RANKING`）。
+    pub recall_ranking: RecallRanking,
+    /// decay 排序的半衰期（天，`MEMORY_DECAY_HALF_LIFE_DAYS`）；None 时为 [`DECAY_HALF_LIFE_DAYS`]。
+    pub decay_half_life_days: Option<f64>,
+    /// 打开 namespace 时自动执行一次重要度衰减（`MEMORY_MAINTAIN_ON_OPEN`）。
+    pub maintain_on_open: bool,
+    /// 重要度衰减的半衰期（天，`MEMORY_IMPORTANCE_HALF_LIFE_DAYS`）；None 时为 [`IMPORTANCE_HALF_LIFE_DAYS`]。
+    pub importance_half_life_days: Option<f64>,
+    /// 活动数据文件达到该字节数时封存为分段（`MEMORY_SEGMENT_MAX_BYTES`）；None 时不轮转。
+    pub segment_max_bytes: Option<u64>,
+    /// 存储后端（`MEMORY_BACKEND`）：默认 JSONL + 索引文件。
+    pub backend: StorageBackend,
+    /// 未转存为 blob 的 diary 以 zstd 压缩后写入（`MEMORY_COMPRESS=zstd`，需 `zstd` 特性）。
+    pub compress: bool,
+}
+
+/// 非 JSONL 后端目前支持的存储类 tool：其余 tool（`now` 除外）在 tools/list 中隐藏，调用时报错。
+pub const NON_JSONL_TOOLS: &[&str] = &["remember", "recall", "get", "history", "update", "forget", "transaction"];
+
+/// 存储后端：JSONL + 索引文件（默认），或单个 SQLite 文件（记录 + 关键字/分词倒排 + FTS5，需 `sqlite` 特性）。
+///
+/// `Memory` 使用同样的 SQLite 结构但存放在进程内存中，不读写数据目录，进程退出即丢失（供集成测试使用）。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StorageBackend {
+    #[default]
+    Jsonl,
+    Sqlite,
+    Memory,
+}
+
+impl StorageBackend {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        match text.trim().to_lowercase().as_str() {
+            "jsonl" => Ok(Self::Jsonl),
+            "sqlite" if cfg!(feature = "sqlite") => Ok(Self::Sqlite),
+            "memory" if cfg!(feature = "sqlite") => Ok(Self::Memory),
+            "sqlite" | "memory" => Err("当前构建未启用 sqlite 特性（需 cargo build --features sqlite）".to_string()),
+            other => Err(format!("未知的存储后端：{other}（可选 jsonl / sqlite / memory）")),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Jsonl => "jsonl",
+            Self::Sqlite => "sqlite",
+            Self::Memory => "memory",
+        }
+    }
+
+    /// 该后端是否支持某个 tool（见 [`NON_JSONL_TOOLS`]）。
+    pub fn supports_tool(self, name: &str) -> bool {
+        self == Self::Jsonl || name == "now" || NON_JSONL_TOOLS.contains(&name)
+    }
+}
+
+impl StoreOptions {
+    /// 从环境变量读取：`MEMORY_WAL=1|true|on` 启用 WAL；`MEMORY_HOLIDAYS_FILE` 指定节假日表；
+    /// `MEMORY_EMBEDDING_CONFIG` 指定向量化配置；`MEMORY_INDEX_MMAP=1` 以只读映射方式加载全文倒排；
+    /// `MEMORY_RECALL_MAX_LIMIT` 调整 recall 单页条数上限；`MEMORY_RECALL_PINNED_MAX` 调整置顶条数上限；
+    /// `MEMORY_RECALL_RANKING=decay` 默认按时间衰减排序，`MEMORY_DECAY_HALF_LIFE_DAYS` 调整半衰期；
+    /// `MEMORY_MAINTAIN_ON_OPEN=1` 打开 namespace 时衰减重要度，`MEMORY_IMPORTANCE_HALF_LIFE_DAYS` 调整其半衰期；
+    /// `MEMORY_SEGMENT_MAX_BYTES` 启用数据文件分段轮转；`MEMORY_BACKEND=sqlite` 改用 SQLite 存储后端；
+    /// `MEMORY_COMPRESS=zstd` 压缩写入的 diary。
+    pub fn from_env() -> Self {
+        let calendar = Calendar::from_env().unwrap_or_else(|e| {
+            eprintln!("memory: {e}（已忽略节假日表，仅按周末区分工作日）");
+            Calendar::default()
+        });
+        let embedder = Embedder::from_env().unwrap_or_else(|e| {
+            eprintln!("memory: {e}（语义召回未启用）");
+            Embedder::default()
+        });
+
+        Self {
+            wal: env_flag("MEMORY_WAL"),
+            calendar: Arc::new(calendar),
+            writer: Self::env_writer(),
+            embedder: Arc::new(embedder),
+            mmap_index: env_flag("MEMORY_INDEX_MMAP"),
+            recall_max_limit: Self::env_recall_max_limit(),
+            recall_pinned_max: Self::env_recall_pinned_max(),
+            recall_ranking: Self::env_recall_ranking(),
+            decay_half_life_days: Self::env_half_life_days("MEMORY_DECAY_HALF_LIFE_DAYS", DECAY_HALF_LIFE_DAYS),
+            maintain_on_open: env_flag("MEMORY_MAINTAIN_ON_OPEN"),
+            importance_half_life_days: Self::env_half_life_days(
+                "MEMORY_IMPORTANCE_HALF_LIFE_DAYS",
+                IMPORTANCE_HALF_LIFE_DAYS,
+            ),
+            segment_max_bytes: Self::env_segment_max_bytes(),
+            backend: Self::env_backend(),
+            compress: Self::env_compress(),
+        }
+    }
+
+    fn env_compress() -> bool {
+        let value = std::env::var("MEMORY_COMPRESS").unwrap_or_default();
+        match value.trim().to_lowercase().as_str() {
+            "" | "none" | "off" => false,
+            "zstd" if cfg!(feature = "zstd") => true,
+            "zstd" => {
+                eprintln!("memory: MEMORY_COMPRESS=zstd 需要以 `zstd` 特性编译（已按不压缩写入）");
+                false
+            }
+            other => {
+                eprintln!("memory: MEMORY_COMPRESS 无效：{other}（可选 zstd / none，已按不压缩写入）");
+                false
+            }
+        }
+    }
+
+    fn env_backend() -> StorageBackend {
+        let Some(value) = std::env::var("MEMORY_BACKEND").ok().filter(|x| !x.trim().is_empty()) else {
+            return StorageBackend::default();
+        };
+        StorageBackend::parse(&value).unwrap_or_else(|e| {
+            eprintln!("memory: MEMORY_BACKEND {e}（已使用默认后端 jsonl）");
+            StorageBackend::default()
+        })
+    }
+
+    fn env_segment_max_bytes() -> Option<u64> {
+        let value = std::env::var("MEMORY_SEGMENT_MAX_BYTES").ok()?;
+        match value.trim().parse::<u64>() {
+            Ok(0) => None,
+            Ok(n) => Some(n),
+            Err(_) => {
+                eprintln!("memory: MEMORY_SEGMENT_MAX_BYTES 不是非负整数：{value}（已关闭分段轮转）");
+                None
+            }
+        }
+    }
+
+    fn env_recall_ranking() -> RecallRanking {
+        let Ok(value) = std::env::var("MEMORY_RECALL_RANKING") else {
+            return RecallRanking::default();
+        };
+        RecallRanking::parse(&value).unwrap_or_else(|e| {
+            eprintln!("memory: MEMORY_RECALL_RANKING {e}（已使用默认排序 relevance）");
+            RecallRanking::default()
+        })
+    }
+
+    fn env_half_life_days(name: &str, default: f64) -> Option<f64> {
+        let value = std::env::var(name).ok()?;
+        let parsed = value
+            .trim()
+            .parse::<f64>()
+            .map_err(|_| format!("{name} 不是数字：{value}"))
+            .and_then(check_half_life_days);
+        match parsed {
+            Ok(days) => Some(days),
+            Err(e) => {
+                eprintln!("memory: {e}（已使用默认半衰期 {default} 天）");
+                None
+            }
+        }
+    }
+
+    fn env_recall_pinned_max() -> Option<usize> {
+        let value = std::env::var("MEMORY_RECALL_PINNED_MAX").ok()?;
+        match value.trim().parse::<usize>() {
+            Ok(n) => Some(n),
+            Err(_) => {
+                eprintln!("memory: MEMORY_RECALL_PINNED_MAX 不是非负整数：{value}（已使用默认值 {RECALL_PINNED_MAX}）");
+                None
+            }
+        }
+    }
+
+    fn env_recall_max_limit() -> Option<usize> {
+        let value = std::env::var("MEMORY_RECALL_MAX_LIMIT").ok()?;
+        let parsed = value
+            .trim()
+            .parse::<usize>()
+            .map_err(|_| format!("MEMORY_RECALL_MAX_LIMIT 不是整数：{value}"))
+            .and_then(check_recall_max_limit);
+        match parsed {
+            Ok(n) => Some(n),
+            Err(e) => {
+                eprintln!("memory: {e}（已使用默认上限 {RECALL_MAX_LIMIT}）");
+                None
+            }
+        }
+    }
+
+    /// `MEMORY_WRITER`：显式配置的写入者身份（优先于 MCP 客户端名称）。
+    pub fn env_writer() -> Option<String> {
+        std::env::var("MEMORY_WRITER")
+            .ok()
+            .map(|x| x.trim().to_string())
+            .filter(|x| !x.is_empty())
+    }
+}
+
+/// 校验可配置的 recall 单页上限：1 ~ [`RECALL_LIMIT_CEILING`]。
+fn check_recall_max_limit(n: usize) -> Result<usize, String> {
+    if (1..=RECALL_LIMIT_CEILING).contains(&n) {
+        Ok(n)
+    } else {
+        Err(format!("recall_max_limit 必须在 1~{RECALL_LIMIT_CEILING}：{n}"))
+    }
+}
+
+fn env_flag(key: &str) -> bool {
+    std::env::var(key)
+        .map(|v| matches!(v.trim().to_lowercase().as_str(), "1" | "true" | "on" | "yes"))
+        .unwrap_or(false)
+}
+
+/// checksum 体检结果。
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ChecksumReport {
+    pub total: usize,
+    pub verified: usize,
+    /// 旧数据未携带 checksum，无法校验。
+    pub unsigned: usize,
+    pub mismatched: Vec<String>,
+    pub unreadable: Vec<String>,
+}
+
+/// verify 发现的无法解析的索引条目。
+#[derive(Debug, Clone, Serialize)]
+pub struct BadEntry {
+    pub id: String,
+    pub offset: u64,
+    pub reason: String,
+}
+
+/// verify（fsck）体检结果：索引与数据文件的一致性。
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct VerifyReport {
+    /// 检查的索引条目数（含历史修订）。
+    pub entries: usize,
+    /// offset/length 读不出一条可解析、且 id 一致的记录的条目。
+    pub bad_entries: Vec<BadEntry>,
+    /// 指向越界或非当前版本条目的倒排、时间序与 heads 项数。
+    pub orphaned_postings: usize,
+    /// 同一 (id, revision) 在数据文件中出现多次的 id。
+    pub duplicate_ids: Vec<String>,
+    /// 活动文件末尾缺少换行的残缺字节数（写入中断）。
+    pub truncated_tail_bytes: u64,
+    /// `repair` 时执行的修复动作。
+    pub repaired: Vec<String>,
+}
+
+impl VerifyReport {
+    pub fn is_clean(&self) -> bool {
+        self.bad_entries.is_empty()
+            && self.orphaned_postings == 0
+            && self.duplicate_ids.is_empty()
+            && self.truncated_tail_bytes == 0
+    }
+}
+
+/// import 遇到 id 冲突（id 已存在且内容不同）时的处理策略。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// 保留现有版本，丢弃导入的记录。
+    #[default]
+    KeepExisting,
+    /// 比较最后变更时间（updated_at ?? recorded_at，相同时比较修订号），保留较新的一方；仍相同时保留现有版本。
+    KeepNewest,
+    /// 两者都保留：导入的记录以新 id 另存一份。
+    Duplicate,
+}
+
+impl ConflictPolicy {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        match text.trim().to_ascii_lowercase().replace('_', "-").as_str() {
+            "keep-existing" => Ok(Self::KeepExisting),
+            "keep-newest" => Ok(Self::KeepNewest),
+            "duplicate" => Ok(Self::Duplicate),
+            other => Err(format!(
+                "on_conflict 无效：{other}（可选 keep-newest / keep-existing / duplicate）"
+            )),
+        }
+    }
+
+    /// 按策略决定单个冲突的处理方式。
+    pub fn decide(self, existing: &MemoryItem, incoming: &MemoryItem) -> ConflictAction {
+        match self {
+            Self::KeepExisting => ConflictAction::KeepExisting,
+            Self::Duplicate => ConflictAction::Duplicate,
+            Self::KeepNewest => {
+                let newer = (change_ts(incoming), incoming.revision) > (change_ts(existing), existing.revision);
+                if newer {
+                    ConflictAction::Replace
+                } else {
+                    ConflictAction::KeepExisting
+                }
+            }
+        }
+    }
+}
+
+/// 单个冲突的处理结果。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictAction {
+    KeepExisting,
+    /// 以导入的内容追加为现有记忆的新修订。
+    Replace,
+    Duplicate,
+}
+
+/// 冲突处理记录（import 报告中的一项）。
+#[derive(Debug, Clone, Serialize)]
+pub struct ConflictDecision {
+    pub id: String,
+    pub action: ConflictAction,
+    /// action=duplicate 时另存副本的新 id。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_id: Option<String>,
+}
+
+/// import 结果。
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ImportReport {
+    /// 新增的记忆条数（不含冲突处理中的替换/副本）。
+    pub imported: usize,
+    /// 应用的删除标记条数（目标中存在对应记忆时才生效）。
+    pub deleted: usize,
+    /// 与目标中现有记忆内容完全一致而跳过的条数。
+    pub duplicates: usize,
+    /// id 已存在但内容不同的记录及其处理方式。
+    pub conflicts: Vec<ConflictDecision>,
+    /// checksum 与内容不一致的记录：视为损坏，未导入（记忆 id）。
+    pub invalid: Vec<String>,
+}
+
 pub struct NamespaceState {
     paths: StorePaths,
+    /// 数据文件（已封存分段 + 活动文件 memories.jsonl）。
+    files: DataFiles,
     index: IndexData,
+    options: StoreOptions,
+    heat: KeywordHeat,
+    /// 语义召回的向量缓存：首次语义召回时从 embeddings.json 读取。
+    embeddings: Option<EmbeddingCache>,
+    /// 本进程最近一次读取/写入后 index.json 的 (修改时间, 长度)：不一致说明被其他进程改写过。
+    index_stamp: Option<(SystemTime, u64)>,
+    /// 内存中的索引有尚未成功落盘的变更，退出前由 `flush` 补写。
+    index_dirty: bool,
+    /// 本进程尚未写入 access.json 的召回统计增量（已计入 `index.access`）。
+    access_pending: BTreeMap<String, index::AccessStat>,
+    /// 自上次写入 access.json 以来记录的 recall 次数，达到 [`ACCESS_SAVE_EVERY`] 时批量写入。
+    pending_recalls: usize,
+}
+
+/// 召回统计批量落盘的间隔（recall 次数）；其余在 `flush`（服务退出）或关闭 namespace 时补写。
+const ACCESS_SAVE_EVERY: usize = 32;
+
+impl Drop for NamespaceState {
+    /// 关闭 namespace 时补写尚未落盘的召回统计（尽力而为）。
+    fn drop(&mut self) {
+        if !self.access_pending.is_empty() {
+            let _ = self.save_access_stats();
+        }
+    }
+}
+
+/// compact 的结果：活动数据文件压缩前后的记录行数与字节数。
+#[derive(Debug, Clone, Serialize)]
+pub struct CompactReport {
+    pub records_before: usize,
+    pub records_after: usize,
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+}
+
+/// 一条记忆的重要度衰减（maintain）。
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportanceDecay {
+    pub id: String,
+    pub from: u8,
+    pub to: u8,
+    /// 写入后的修订号；dry_run 时为 None。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub revision: Option<u32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TransactionOutcome {
+    pub op: &'static str,
+    pub id: String,
+    /// 写入后的修订号；forget 步骤为 None。
+    pub revision: Option<u32>,
+}
+
+/// 供调用方做条件请求（ETag / Last-Modified）的缓存校验值。
+#[derive(Debug, Clone)]
+pub struct CacheValidators {
+    pub generation: u64,
+    pub etag: String,
+    /// HTTP-date 格式（例如 `Sun, 06 Nov 1994 08:49:37 GMT`）。
+    pub last_modified: Option<String>,
+}
+
+impl CacheValidators {
+    /// 限定到一次具体请求：ETag 附加请求摘要（如规范化后的召回条件）的哈希，
+    /// 同一 namespace 下不同条件或不同记忆的结果不共用校验值。
+    pub fn scoped(mut self, scope: &str) -> Self {
+        let digest = backup::sha256_hex(scope.as_bytes());
+        self.etag = format!("{}-{}\"", self.etag.trim_end_matches('"'), &digest[..16]);
+        self
+    }
+}
+
+/// namespace 统计（stats 工具）。
+#[derive(Debug, Clone, Serialize)]
+pub struct NamespaceStats {
+    /// 当前有效记忆条数（同 id 多版本只计一次，不含已删除）。
+    pub items: usize,
+    /// memories.jsonl 中的记录行数（含历史修订与删除标记）。
+    pub records: usize,
+    pub total_bytes: u64,
+    /// 已封存的分段数（未启用轮转时为 0）。
+    pub segments: usize,
+    pub keywords: usize,
+    /// 重要度分布：`"1"`~`"5"` 与 `"none"`。
+    pub importance: BTreeMap<String, usize>,
+    pub items_with_occurred_at: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub earliest_occurred_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latest_occurred_at: Option<String>,
+    pub index: IndexFreshness,
+}
+
+/// 索引新鲜度：统计前索引是否已覆盖数据文件的全部内容。
+#[derive(Debug, Clone, Serialize)]
+pub struct IndexFreshness {
+    /// 统计前是否已是最新（false 表示有外部追加/回退，已在本次统计中同步）。
+    pub fresh: bool,
+    pub indexed_bytes_before: u64,
+    pub generation: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_modified: Option<String>,
+}
+
+/// 追加到 memories.jsonl 的一条日志记录。
+enum LogRecord {
+    Item(Box<MemoryItem>),
+    Tombstone(Tombstone),
+}
+
+impl LogRecord {
+    fn id(&self) -> &str {
+        match self {
+            LogRecord::Item(item) => &item.id,
+            LogRecord::Tombstone(t) => &t.id,
+        }
+    }
+
+    fn to_line(&self) -> Result<Vec<u8>, String> {
+        let mut line = match self {
+            LogRecord::Item(item) => serde_json::to_vec(item),
+            LogRecord::Tombstone(t) => serde_json::to_vec(t),
+        }
+        .map_err(|e| format!("serialize memory item failed: {e}"))?;
+        line.push(b'\n');
+        Ok(line)
+    }
+}
+
+/// 一条记忆的全部修订（history 工具）。
+#[derive(Debug, Clone, Serialize)]
+pub struct MemoryHistory {
+    /// 按修订顺序（旧 -> 新）排列的各版本完整记录。
+    pub versions: Vec<MemoryItem>,
+    /// 该 id 当前是否已被删除（删除前的版本仍可查阅）。
+    pub deleted: bool,
 }
 
 pub struct RememberRecorded {
@@ -52,128 +615,1291 @@ pub struct RememberRecorded {
     pub recorded_at: String,
     pub occurred_at: Option<String>,
     pub keywords: Vec<String>,
+    pub revision: u32,
+}
+
+/// 预览时建议补充的关键字数上限与疑似重复的返回上限。
+const PREVIEW_SUGGEST_MAX: usize = 5;
+const PREVIEW_DUPLICATE_MAX: usize = 5;
+
+/// remember_preview 的结果：将要写入的记录（尚未分配正式 id）及写入前的检查结论。
+pub struct MemoryPreview {
+    pub item: MemoryItem,
+    pub redactions: Vec<Redaction>,
+    pub duplicates: Vec<PreviewDuplicate>,
+    /// 正文中出现、namespace 已在使用但未列入 keywords 的关键字（关键字, 使用条数），按使用条数降序。
+    pub suggested_keywords: Vec<(String, usize)>,
+    /// 本次 keywords 中尚未在 namespace 出现过的关键字（可能是新主题，也可能是拼写不一致）。
+    pub new_keywords: Vec<String>,
+}
+
+/// 与预览内容重复的已有记忆：slice 相同（忽略大小写与空白差异），`same_diary` 表示 diary 也相同。
+pub struct PreviewDuplicate {
+    pub id: String,
+    pub slice: String,
+    pub same_diary: bool,
 }
 
 impl NamespaceState {
+    #[cfg(test)]
     pub fn open(paths: StorePaths) -> Result<Self, String> {
+        Self::open_with(paths, StoreOptions::default())
+    }
+
+    pub fn open_with(paths: StorePaths, options: StoreOptions) -> Result<Self, String> {
         fs::create_dir_all(&paths.namespace_dir)
             .map_err(|e| format!("create namespace dir failed: {e}"))?;
 
-        if !paths.memories_path.exists() {
-            File::create(&paths.memories_path)
-                .map_err(|e| format!("create memories.jsonl failed: {e}"))?;
-        }
+        DataFiles::recover(&paths)?;
+        Self::recover_journal(&paths)?;
 
+        let files = DataFiles::load(&paths)?;
         let index = load_or_create_index(&paths)?;
-        Ok(Self { paths, index })
+        let mut state = Self::with_index(paths, files, index, options);
+        state.index_stamp = index_file_stamp(&state.paths);
+        state.verify_data_checksum()?;
+        state.update_catalog();
+        Ok(state)
+    }
+
+    /// 恢复上次中断的写入（回滚未完成的批次、截掉残缺的末行），并记录告警日志。
+    fn recover_journal(paths: &StorePaths) -> Result<(), String> {
+        match journal::recover(paths)? {
+            Recovery::Clean | Recovery::Committed => {}
+            Recovery::TerminatedTail => log::emit(
+                LogLevel::Warning,
+                LOGGER,
+                format!("namespace={} memories.jsonl 末行缺少换行，已补上", paths.namespace),
+                json!({ "namespace": paths.namespace, "event": "terminated_tail" }),
+            ),
+            Recovery::RolledBack(n) => log::emit(
+                LogLevel::Warning,
+                LOGGER,
+                format!("namespace={} 上次写入中断，已回滚未完成的批次（{n} 字节）", paths.namespace),
+                json!({ "namespace": paths.namespace, "event": "rollback", "bytes": n }),
+            ),
+            Recovery::TruncatedTail(n) => log::emit(
+                LogLevel::Warning,
+                LOGGER,
+                format!("namespace={} memories.jsonl 末尾有残缺记录，已截掉 {n} 字节", paths.namespace),
+                json!({ "namespace": paths.namespace, "event": "truncated_tail", "bytes": n }),
+            ),
+        }
+        Ok(())
+    }
+
+    fn with_index(paths: StorePaths, files: DataFiles, index: IndexData, options: StoreOptions) -> Self {
+        Self {
+            paths,
+            files,
+            index,
+            options,
+            heat: KeywordHeat::default(),
+            embeddings: None,
+            index_stamp: None,
+            index_dirty: false,
+            access_pending: BTreeMap::new(),
+            pending_recalls: 0,
+        }
+    }
+
+    /// 核对索引记录的数据区域校验和：数据被外部改写或损坏（长度不变也能发现）时全量重建索引，
+    /// 而不是继续按可能错位的偏移提供过期结果；旧索引没有校验和时补算并保存。
+    ///
+    /// 数据短于已索引长度的情况（文件回退）由 `sync_index` 处理。
+    fn verify_data_checksum(&mut self) -> Result<(), String> {
+        let end = self.index.indexed_up_to_offset;
+        let total = self
+            .files
+            .total_len()
+            .map_err(|e| format!("stat memories.jsonl failed: {e}"))?;
+        if end == 0 || total < end {
+            return Ok(());
+        }
+
+        let actual = region_checksum(&self.files, end).map_err(|e| format!("read memories.jsonl failed: {e}"))?;
+        match self.index.data_checksum.as_deref() {
+            Some(expected) if Some(expected) == actual.as_deref() => Ok(()),
+            Some(_) => {
+                log::emit(
+                    LogLevel::Warning,
+                    LOGGER,
+                    format!(
+                        "namespace={} 索引校验和与数据文件不一致（可能被外部修改或损坏），已全量重建索引",
+                        self.paths.namespace
+                    ),
+                    json!({ "namespace": self.paths.namespace, "event": "checksum_mismatch" }),
+                );
+                self.rebuild_index()
+            }
+            None => {
+                self.index.data_checksum = actual;
+                save_index_header(&self.paths, &self.index)?;
+                self.index_stamp = index_file_stamp(&self.paths);
+                Ok(())
+            }
+        }
+    }
+
+    /// 将当前条数 / 最近写入时间同步到根目录的 catalog.json（仅在索引已覆盖整个数据文件时）。
+    ///
+    /// catalog 只是加速列举的缓存：写入失败不影响本次操作，列举时会按文件大小自愈。
+    fn update_catalog(&self) {
+        let Ok(meta) = fs::metadata(self.files.active_path()) else {
+            return;
+        };
+        let bytes = self.files.active_base() + meta.len();
+        if bytes != self.index.indexed_up_to_offset {
+            return;
+        }
+
+        let entry = CatalogEntry {
+            items: self.index.heads.len(),
+            last_write: meta.modified().ok().map(time::system_time_to_rfc3339),
+            bytes,
+        };
+        let _ = Catalog::upsert(&self.paths.root_dir, &self.paths.namespace, entry);
     }
 
     pub fn namespace(&self) -> &str {
         &self.paths.namespace
     }
 
-    pub fn list_keywords(&mut self) -> Result<Vec<String>, String> {
+    /// 生效的 recall 单页上限：namespace 的 settings.json 优先，其次为服务端配置。
+    ///
+    /// 与冻结标记一样每次读取（不缓存），直接修改文件即可生效。
+    pub fn recall_max_limit(&self) -> Result<usize, String> {
+        let settings = read_settings(&self.paths)?;
+        Ok(settings
+            .recall_max_limit
+            .or(self.options.recall_max_limit)
+            .unwrap_or(RECALL_MAX_LIMIT))
+    }
+
+    /// 索引变更代数（每次写入/发现外部追加/重建时递增）。
+    pub fn generation(&self) -> u64 {
+        self.index.generation
+    }
+
+    /// 依据 WAL 将 memories.jsonl 恢复到指定时间点（含），并重建索引；返回恢复后的记录行数。
+    ///
+    /// 恢复后会追加一个新的 base 快照，之后仍可继续按时间点恢复。
+    pub fn restore_to(&mut self, at_ts: i64) -> Result<usize, String> {
+        ensure_not_frozen(&self.paths)?;
+        let lines = wal::replay_until(&self.paths.wal_path, at_ts)?;
+
+        self.replace_memories(&lines)?;
+
+        let count = lines.len();
+        wal::append_entry(&self.paths.wal_path, wal::WAL_KIND_BASE, lines)?;
+        Ok(count)
+    }
+
+    /// 将已过期的记忆从 memories.jsonl 中彻底清除（含其历史修订与删除标记），整体替换文件并重建索引；
+    /// 返回被清除的记忆 id（按 id 排序）。
+    pub fn purge_expired(&mut self) -> Result<Vec<String>, String> {
+        ensure_not_frozen(&self.paths)?;
         self.sync_index().map_err(|e| e.to_string())?;
 
-        let mut keywords: Vec<String> = self.index.keyword_postings.keys().cloned().collect();
-        keywords.sort_by(|a, b| {
-            a.chars()
-                .count()
-                .cmp(&b.chars().count())
-                .then_with(|| a.cmp(b))
-        });
-        Ok(keywords)
+        let now_ts = time::now_rfc3339_and_ts().1;
+        let mut expired: Vec<String> = self
+            .index
+            .heads
+            .iter()
+            .filter(|(_, &idx)| self.index.items[idx as usize].is_expired(now_ts))
+            .map(|(id, _)| id.clone())
+            .collect();
+        if expired.is_empty() {
+            return Ok(expired);
+        }
+        expired.sort();
+
+        let lines: Vec<String> = self
+            .files
+            .read_lines()?
+            .into_iter()
+            .filter(|line| {
+                let id = serde_json::from_str::<Value>(line)
+                    .ok()
+                    .and_then(|v| v.get("id").and_then(Value::as_str).map(str::to_string));
+                id.is_none_or(|id| expired.binary_search(&id).is_err())
+            })
+            .collect();
+        self.replace_memories(&lines)?;
+
+        // 清除前的 WAL 仍可恢复被清除的记忆；追加新快照，之后的时间点恢复以清除后的内容为基础。
+        if self.options.wal {
+            wal::append_entry(&self.paths.wal_path, wal::WAL_KIND_BASE, lines)?;
+        }
+        Ok(expired)
+    }
+
+    /// 压缩活动数据文件 memories.jsonl：只保留当前版本（去掉旧修订、重复的删除标记与已过期的记忆；
+    /// 每条已删除的记忆保留最后一条删除标记，供 sync 向另一侧传播删除），按原有顺序替换文件并全量重建索引
+    /// （偏移随之更新）。无法解析的行不在索引中，同样被丢弃。
+    ///
+    /// 已封存的分段只读、保持不变：目标仍有修订留在分段中的删除标记与过期记忆会被保留，
+    /// 否则重建索引时分段中的旧修订会重新成为当前版本。
+    pub fn compact(&mut self) -> Result<CompactReport, String> {
+        ensure_not_frozen(&self.paths)?;
+        self.sync_index().map_err(|e| e.to_string())?;
+
+        let base = self.files.active_base();
+        let content = fs::read(self.files.active_path()).map_err(|e| format!("read memories.jsonl failed: {e}"))?;
+        let sealed_ids: HashSet<&str> = self
+            .index
+            .items
+            .iter()
+            .filter(|item| item.offset < base)
+            .map(|item| item.id.as_str())
+            .collect();
+        let now_ts = time::now_rfc3339_and_ts().1;
+        let live: HashSet<u64> = self
+            .index
+            .heads
+            .values()
+            .map(|&idx| &self.index.items[idx as usize])
+            .filter(|item| item.offset >= base)
+            .filter(|item| !item.is_expired(now_ts) || sealed_ids.contains(item.id.as_str()))
+            .map(|item| item.offset)
+            .collect();
+
+        // 已删除记忆保留最后一条删除标记：同步（sync）需要据此把删除传播到仍持有该记忆的另一侧，
+        // 否则对方的副本会被当作本地缺失的记忆复制回来。
+        let mut last_tombstones: HashMap<String, u64> = HashMap::new();
+        let mut offset = base;
+        for raw in content.split_inclusive(|&b| b == b'\n') {
+            if let Ok(t) = serde_json::from_slice::<Tombstone>(raw.trim_ascii()) {
+                if t.op == Tombstone::OP_DELETE && !self.index.heads.contains_key(&t.id) {
+                    last_tombstones.insert(t.id, offset);
+                }
+            }
+            offset += raw.len() as u64;
+        }
+        let kept_tombstones: HashSet<u64> = last_tombstones.into_values().collect();
+
+        let mut records_before = 0usize;
+        let mut lines: Vec<String> = Vec::with_capacity(live.len() + kept_tombstones.len());
+        let mut offset = base;
+        for raw in content.split_inclusive(|&b| b == b'\n') {
+            let at = offset;
+            offset += raw.len() as u64;
+            let line = raw
+                .strip_suffix(b"\r\n")
+                .or_else(|| raw.strip_suffix(b"\n"))
+                .unwrap_or(raw);
+            if line.trim_ascii().is_empty() {
+                continue;
+            }
+            records_before += 1;
+
+            let keep = live.contains(&at)
+                || kept_tombstones.contains(&at)
+                || serde_json::from_slice::<Tombstone>(line)
+                    .is_ok_and(|t| t.op == Tombstone::OP_DELETE && sealed_ids.contains(t.id.as_str()));
+            if keep {
+                let line = std::str::from_utf8(line)
+                    .map_err(|_| format!("memories.jsonl 第 {at} 字节处的记录不是有效的 UTF-8"))?;
+                lines.push(line.to_string());
+            }
+        }
+        let records_after = lines.len();
+
+        let mut out = lines.join("\n");
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        self.files.replace_active(out.as_bytes())?;
+        self.rebuild_index()?;
+
+        // 压缩前的 WAL 仍可恢复旧修订；追加新快照，之后的时间点恢复以压缩后的内容为基础。
+        if self.options.wal {
+            wal::append_entry(&self.paths.wal_path, wal::WAL_KIND_BASE, self.files.read_lines()?)?;
+        }
+        Ok(CompactReport {
+            records_before,
+            records_after,
+            bytes_before: content.len() as u64,
+            bytes_after: out.len() as u64,
+        })
+    }
+
+    /// 用给定的记录行整体替换全部数据（写入 memories.jsonl 并合并掉已封存的分段），并全量重建索引。
+    fn replace_memories(&mut self, lines: &[String]) -> Result<(), String> {
+        let mut content = lines.join("\n");
+        if !content.is_empty() {
+            content.push('\n');
+        }
+
+        self.files.replace_all(content.as_bytes())?;
+        self.rebuild_index()
+    }
+
+    /// 确保全文倒排（trigram / 分词）已在内存中：从 postings.json 加载，缺失或不一致时全量重建索引。
+    fn ensure_full_text(&mut self) -> Result<(), String> {
+        if self.index.full_text_loaded {
+            return Ok(());
+        }
+        if self.options.mmap_index {
+            if let Some(mapped) = MappedPostings::open(&self.paths.postings_bin_path) {
+                if self.index.attach_mapped(mapped) {
+                    return Ok(());
+                }
+            }
+        }
+        if load_full_text(&self.paths, &mut self.index) {
+            self.share_full_text();
+            return Ok(());
+        }
+        self.rebuild_index()
+    }
+
+    /// 启用 `MEMORY_INDEX_MMAP` 时，把内存中的全文倒排写为 postings.bin 并改用只读映射（best-effort：
+    /// 写入或映射失败时保留内存副本，postings.json 仍是权威数据）。
+    fn share_full_text(&mut self) {
+        if !self.options.mmap_index || !self.index.full_text_loaded || self.index.mapped.is_some() {
+            return;
+        }
+        let written = mapped::write(
+            &self.paths.postings_bin_path,
+            self.index.indexed_up_to_offset,
+            &self.index.trigram_postings,
+            &self.index.term_postings,
+        );
+        if written.is_ok() {
+            if let Some(mapped) = MappedPostings::open(&self.paths.postings_bin_path) {
+                self.index.attach_mapped(mapped);
+            }
+        }
+    }
+
+    /// 丢弃现有索引并从 memories.jsonl 全量重建。
+    pub fn rebuild_index(&mut self) -> Result<(), String> {
+        let started = Instant::now();
+        self.reset_index();
+        incremental_index(&self.files, &mut self.index)
+            .map_err(|e| format!("rebuild index failed: {e}"))?;
+        let heads = &self.index.heads;
+        self.index.access.retain(|id, _| heads.contains_key(id));
+        self.persist_index()?;
+
+        let elapsed_ms = started.elapsed().as_millis();
+        log::emit(
+            LogLevel::Info,
+            LOGGER,
+            format!("namespace={} 已重建索引（{} 条记忆，{elapsed_ms} ms）", self.paths.namespace, self.index.heads.len()),
+            json!({
+                "namespace": self.paths.namespace,
+                "event": "index_rebuilt",
+                "items": self.index.heads.len(),
+                "elapsed_ms": elapsed_ms
+            }),
+        );
+        Ok(())
+    }
+
+    /// 丢弃 index.json 与全文倒排缓存，从数据文件全量重建索引（手工修改数据文件或索引格式升级后使用）。
+    /// 召回统计（access.json）保留其中仍存在的记忆；返回重建后的记忆条数。
+    pub fn reindex(paths: StorePaths, options: StoreOptions) -> Result<usize, String> {
+        if !paths.memories_path.is_file() {
+            return Err(format!("namespace={} 不存在", paths.namespace));
+        }
+        migrate_legacy_access(&paths)?;
+        for path in [&paths.index_path, &paths.postings_path, &paths.postings_bin_path] {
+            match fs::remove_file(path) {
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(format!("remove {} failed: {e}", path.display())),
+            }
+        }
+
+        // 不经 open_with：索引马上要全量重建，无需先加载、校验或落盘空索引；中断的写入仍先恢复。
+        DataFiles::recover(&paths)?;
+        Self::recover_journal(&paths)?;
+        let files = DataFiles::load(&paths)?;
+        let mut index = IndexData::new(&paths.namespace);
+        index.access = load_access(&paths);
+        let mut state = Self::with_index(paths, files, index, options);
+        state.rebuild_index()?;
+        Ok(state.index.heads.len())
+    }
+
+    /// 保存索引并同步 catalog 条目。
+    fn persist_index(&mut self) -> Result<(), String> {
+        // 只追加了删除标记等不影响全文倒排的记录时，映射的倒排仍需随头部推进偏移后重新落盘。
+        if self
+            .index
+            .mapped
+            .as_ref()
+            .is_some_and(|m| m.indexed_up_to_offset() != self.index.indexed_up_to_offset)
+        {
+            self.index.materialize_full_text();
+        }
+        self.index_dirty = true;
+        save_index(&self.paths, &self.index)?;
+        self.index_dirty = false;
+        self.index_stamp = index_file_stamp(&self.paths);
+        self.share_full_text();
+        self.update_catalog();
+        Ok(())
+    }
+
+    pub fn list_keywords(&mut self, sort: KeywordSort) -> Result<Vec<String>, String> {
+        let mut keywords = self.keyword_counts()?;
+        index::sort_keywords(&mut keywords, sort, |(kw, n)| (kw.as_str(), *n));
+        Ok(keywords.into_iter().map(|(kw, _)| kw).collect())
+    }
+
+    /// 关键字及引用它的记忆条数（按关键字字典序）。
+    pub fn keyword_counts(&mut self) -> Result<Vec<(String, usize)>, String> {
+        self.sync_index().map_err(|e| e.to_string())?;
+        Ok(self
+            .index
+            .keyword_postings
+            .iter()
+            .map(|(kw, list)| (kw.clone(), list.len()))
+            .collect())
+    }
+
+    /// 关键字体检：标记疑似无效的关键字（单字、纯数字、一次性用词、近似重复）并附带引用它们的记忆 id。
+    ///
+    /// 返回（词表大小, 可疑关键字）。
+    pub fn audit_keywords(&mut self) -> Result<(usize, Vec<KeywordFinding>), String> {
+        self.sync_index().map_err(|e| e.to_string())?;
+
+        let postings = &self.index.keyword_postings;
+        let counts: BTreeMap<String, usize> = postings.iter().map(|(kw, list)| (kw.clone(), list.len())).collect();
+        let mut findings = audit::audit_keywords(&counts);
+        for finding in &mut findings {
+            finding.ids = postings[&finding.keyword]
+                .iter()
+                .filter_map(|&idx| self.index.items.get(idx as usize))
+                .map(|item| item.id.clone())
+                .collect();
+        }
+        Ok((counts.len(), findings))
+    }
+
+    /// 关键字补全候选：(关键字, 当前引用该关键字的记忆条数)，按词表顺序返回。
+    pub fn complete_keywords(&mut self, text: &str, mode: KeywordMatchMode) -> Result<Vec<(String, usize)>, String> {
+        self.sync_index().map_err(|e| e.to_string())?;
+        Ok(index::complete_keywords(&self.index.keyword_postings, text, mode)
+            .into_iter()
+            .map(|(kw, list)| (kw.to_string(), list.len()))
+            .collect())
+    }
+
+    /// 当前进程内查询最频繁的关键字（热度降序）。
+    pub fn hot_keywords(&self, n: usize) -> Vec<(String, f64)> {
+        self.heat.top(n)
+    }
+
+    pub fn append_memory(&mut self, args: RememberArgs) -> Result<RememberRecorded, String> {
+        self.sync_index().map_err(|e| e.to_string())?;
+
+        let item = build_new_item(&self.paths.namespace, args, self.options.writer.as_deref())?;
+        self.check_links(&item, None, &HashMap::new())?;
+        let recorded = RememberRecorded {
+            id: item.id.clone(),
+            recorded_at: item.recorded_at.clone(),
+            occurred_at: item.occurred_at.clone(),
+            keywords: item.keywords.clone(),
+            revision: item.revision,
+        };
+
+        self.commit_records(vec![LogRecord::Item(Box::new(item))])?;
+        Ok(recorded)
+    }
+
+    /// 按 remember 的规则归一化、脱敏并检查重复与关键字，但不写入任何内容。
+    pub fn preview_memory(&mut self, mut args: RememberArgs) -> Result<MemoryPreview, String> {
+        self.sync_index().map_err(|e| e.to_string())?;
+        self.ensure_full_text()?;
+
+        let redactions = preview::redact_args(&mut args);
+        let item = build_new_item(&self.paths.namespace, args, self.options.writer.as_deref())?;
+
+        let slice_key = normalize_for_dedupe(&item.slice);
+        let diary_key = normalize_for_dedupe(&item.diary);
+        let candidates: Vec<u32> = match self.index.query_candidates(&slice_key) {
+            Some(set) => set.into_iter().collect(),
+            None => self.index.heads.values().copied().collect(),
+        };
+        let mut duplicates: Vec<(i64, PreviewDuplicate)> = Vec::new();
+        for idx in candidates {
+            let Ok(existing) = load_item_by_index(&self.files, &self.index, idx) else {
+                continue;
+            };
+            if normalize_for_dedupe(&existing.slice) != slice_key {
+                continue;
+            }
+            let ts = self.index.items[idx as usize].time_key_ts();
+            duplicates.push((
+                ts,
+                PreviewDuplicate {
+                    same_diary: normalize_for_dedupe(&existing.diary) == diary_key,
+                    id: existing.id,
+                    slice: existing.slice,
+                },
+            ));
+        }
+        // 完全相同（diary 也相同）的排在前面，其次按时间由近到远。
+        duplicates.sort_by(|a, b| b.1.same_diary.cmp(&a.1.same_diary).then_with(|| b.0.cmp(&a.0)));
+        let duplicates = duplicates
+            .into_iter()
+            .take(PREVIEW_DUPLICATE_MAX)
+            .map(|(_, d)| d)
+            .collect();
+
+        let terms = segment::index_terms(&format!("{}\n{}", item.slice, item.diary));
+        let mut suggested_keywords: Vec<(String, usize)> = terms
+            .into_iter()
+            .filter(|term| !item.keywords.contains(term))
+            .filter_map(|term| {
+                let count = self.index.keyword_postings.get(&term)?.len();
+                Some((term, count))
+            })
+            .collect();
+        suggested_keywords.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        suggested_keywords.truncate(PREVIEW_SUGGEST_MAX);
+
+        let new_keywords = item
+            .keywords
+            .iter()
+            .filter(|kw| !self.index.keyword_postings.contains_key(*kw))
+            .cloned()
+            .collect();
+
+        Ok(MemoryPreview {
+            item,
+            redactions,
+            duplicates,
+            suggested_keywords,
+            new_keywords,
+        })
+    }
+
+    /// 批量记录：逐条校验生成记录（单条失败不影响其它条），成功的记录一次性追加写入并只保存一次索引。
+    ///
+    /// 返回与输入一一对应的结果；追加写入本身失败时整体返回 Err（此时没有任何记录落盘）。
+    pub fn append_memories(
+        &mut self,
+        batch: Vec<RememberArgs>,
+    ) -> Result<Vec<Result<RememberRecorded, String>>, String> {
+        self.sync_index().map_err(|e| e.to_string())?;
+
+        let mut results = Vec::with_capacity(batch.len());
+        let mut records = Vec::new();
+        for args in batch {
+            let built = build_new_item(&self.paths.namespace, args, self.options.writer.as_deref())
+                .and_then(|item| self.check_links(&item, None, &HashMap::new()).map(|_| item));
+            match built {
+                Ok(item) => {
+                    results.push(Ok(RememberRecorded {
+                        id: item.id.clone(),
+                        recorded_at: item.recorded_at.clone(),
+                        occurred_at: item.occurred_at.clone(),
+                        keywords: item.keywords.clone(),
+                        revision: item.revision,
+                    }));
+                    records.push(LogRecord::Item(Box::new(item)));
+                }
+                Err(e) => results.push(Err(e)),
+            }
+        }
+
+        if !records.is_empty() {
+            self.commit_records(records)?;
+        }
+        Ok(results)
+    }
+
+    /// 原子地应用一组步骤：先全部校验并生成记录，再一次性追加写入与索引；任一步失败则不落盘。
+    pub fn apply_transaction(
+        &mut self,
+        steps: Vec<TransactionStep>,
+    ) -> Result<Vec<TransactionOutcome>, String> {
+        self.sync_index().map_err(|e| e.to_string())?;
+
+        // 事务内的中间状态：id -> Some(最新版本) / None(已删除)
+        let mut pending: HashMap<String, Option<MemoryItem>> = HashMap::new();
+        let mut records: Vec<LogRecord> = Vec::with_capacity(steps.len());
+        let mut outcomes: Vec<TransactionOutcome> = Vec::with_capacity(steps.len());
+
+        for (i, step) in steps.into_iter().enumerate() {
+            let (op, record) = self
+                .plan_step(step, &mut pending)
+                .map_err(|e| format!("steps[{i}]: {e}"))?;
+            let revision = match &record {
+                LogRecord::Item(item) => Some(item.revision),
+                LogRecord::Tombstone(_) => None,
+            };
+            outcomes.push(TransactionOutcome {
+                op,
+                id: record.id().to_string(),
+                revision,
+            });
+            records.push(record);
+        }
+
+        self.commit_records(records)?;
+        Ok(outcomes)
+    }
+
+    fn plan_step(
+        &self,
+        step: TransactionStep,
+        pending: &mut HashMap<String, Option<MemoryItem>>,
+    ) -> Result<(&'static str, LogRecord), String> {
+        match step {
+            TransactionStep::Remember(args) => {
+                let item = build_new_item(&self.paths.namespace, args, self.options.writer.as_deref())?;
+                self.check_links(&item, None, pending)?;
+                pending.insert(item.id.clone(), Some(item.clone()));
+                Ok(("remember", LogRecord::Item(Box::new(item))))
+            }
+            TransactionStep::Update(args) => {
+                let current = self.load_current_item(&args.id, pending)?;
+                let item = apply_update(current.clone(), args, self.options.writer.as_deref())?;
+                self.check_links(&item, Some(&current), pending)?;
+                pending.insert(item.id.clone(), Some(item.clone()));
+                Ok(("update", LogRecord::Item(Box::new(item))))
+            }
+            TransactionStep::Forget { id, revision } => {
+                let current = self.load_current_item(&id, pending)?;
+                check_revision(&current, revision)?;
+                pending.insert(current.id.clone(), None);
+                let (deleted_at, _) = time::now_rfc3339_and_ts();
+                Ok((
+                    "forget",
+                    LogRecord::Tombstone(Tombstone {
+                        op: Tombstone::OP_DELETE.to_string(),
+                        id: current.id,
+                        namespace: self.paths.namespace.clone(),
+                        deleted_at,
+                        written_by: self.options.writer.clone(),
+                    }),
+                ))
+            }
+        }
+    }
+
+    fn check_links(
+        &self,
+        item: &MemoryItem,
+        previous: Option<&MemoryItem>,
+        pending: &HashMap<String, Option<MemoryItem>>,
+    ) -> Result<(), String> {
+        check_new_links(item, previous, |id| match pending.get(id) {
+            Some(state) => state.is_some(),
+            None => self.index.heads.contains_key(id),
+        })
+    }
+
+    fn load_current_item(
+        &self,
+        id: &str,
+        pending: &HashMap<String, Option<MemoryItem>>,
+    ) -> Result<MemoryItem, String> {
+        if let Some(state) = pending.get(id) {
+            return state.clone().ok_or_else(|| format!("记忆不存在：{id}"));
+        }
+
+        let Some(&idx) = self.index.heads.get(id) else {
+            return Err(format!("记忆不存在：{id}"));
+        };
+        load_item_by_index(&self.files, &self.index, idx)
+    }
+
+    /// 一次性追加写入多条记录；写入失败时截断回写入前长度，保证不留下部分记录。
+    fn commit_records(&mut self, records: Vec<LogRecord>) -> Result<(), String> {
+        ensure_not_frozen(&self.paths)?;
+        // 写入锁覆盖读取文件长度到提交意图删除的整个过程：其它进程此时的追加会等待，
+        // 本批的起始偏移、回滚长度与崩溃恢复所用的提交意图都以锁内看到的文件末尾为准；
+        // 其它进程打开 namespace 时也会等待，而不是把本批当作中断写入回滚。
+        let lock = journal::lock(&self.paths)?;
+        // 其他进程可能轮转到一半就退出：先补记分段、补建活动文件。
+        self.files.finish_rotation()?;
+        // 先补上其他进程在此之前追加的记录，偏移与校验链才能接在磁盘上的真实末尾之后。
+        self.sync_index().map_err(|e| e.to_string())?;
+        // 写入会推进 indexed_up_to_offset：先装入全文倒排，保证 postings.json 与头部一同更新。
+        self.ensure_full_text()?;
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.files.active_path())
+            .map_err(|e| format!("open memories.jsonl failed: {e}"))?;
+
+        let local_start = file
+            .metadata()
+            .map_err(|e| format!("stat memories.jsonl failed: {e}"))?
+            .len();
+        // 索引使用跨分段的全局偏移。
+        let start = self.files.active_base() + local_start;
+
+        // 大篇幅 diary 先转存为 blob（早于 WAL 与数据写入），日志中只写引用；启用压缩时其余 diary 写入压缩形式。
+        // 索引仍使用完整内容。
+        let blobs = blob::blobs_dir(&self.paths.memories_path);
+        let mut buf: Vec<u8> = Vec::new();
+        let mut spans: Vec<(u64, u32)> = Vec::with_capacity(records.len());
+        let mut checksum = self.index.data_checksum.clone();
+        for record in &records {
+            let line = match record {
+                LogRecord::Item(item) => match blob::externalize(&blobs, item, self.options.compress)? {
+                    Some(stored) => LogRecord::Item(Box::new(stored)).to_line()?,
+                    None => record.to_line()?,
+                },
+                LogRecord::Tombstone(_) => record.to_line()?,
+            };
+            spans.push((start + buf.len() as u64, line.len() as u32));
+            checksum = Some(index::chain_checksum(checksum.as_deref(), &line));
+            buf.extend_from_slice(&line);
+        }
+
+        // 先落盘提交意图（含 WAL 的回滚点）：进程在写入中途崩溃时，下次打开按它整批截断，不留下残缺记录。
+        let wal_len = if self.options.wal {
+            wal::ensure_initialized(&self.paths.wal_path, &self.files)?;
+            Some(fs::metadata(&self.paths.wal_path).map_or(0, |m| m.len()))
+        } else {
+            None
+        };
+        journal::begin(
+            &self.paths,
+            &PendingCommit {
+                start: local_start,
+                len: buf.len() as u64,
+                sha256: backup::sha256_hex(&buf),
+                wal_len,
+            },
+        )?;
+
+        // WAL 先于数据写入；数据写入失败时一并回滚，保证两者一致。
+        let written = (|| {
+            if self.options.wal {
+                let lines = buf
+                    .split(|&b| b == b'\n')
+                    .filter(|x| !x.is_empty())
+                    .map(|x| String::from_utf8_lossy(x).into_owned())
+                    .collect();
+                wal::append_entry(&self.paths.wal_path, wal::WAL_KIND_COMMIT, lines)?;
+            }
+            // 数据落盘后才删除提交意图：否则崩溃后可能留下“已提交”却未持久化的批次。
+            file.write_all(&buf)
+                .and_then(|_| file.flush())
+                .and_then(|_| file.sync_data())
+                .map_err(|e| format!("append memories.jsonl failed: {e}"))
+        })();
+        if let Err(e) = written {
+            let _ = file.set_len(local_start);
+            if let Some(len) = wal_len {
+                wal::truncate(&self.paths.wal_path, len);
+            }
+            journal::finish(&self.paths);
+            return Err(e);
+        }
+        journal::finish(&self.paths);
+
+        for (record, (offset, length)) in records.iter().zip(spans) {
+            match record {
+                LogRecord::Item(item) => index_memory_item(&mut self.index, item, offset, length),
+                LogRecord::Tombstone(t) => {
+                    self.index.remove_by_id(&t.id);
+                    self.index.access.remove(&t.id);
+                }
+            }
+        }
+        self.index.indexed_up_to_offset = start + buf.len() as u64;
+        self.index.data_checksum = checksum;
+        self.index.touch(time::now_rfc3339_and_ts().1);
+
+        self.persist_index()?;
+        self.rotate_segments();
+        drop(lock);
+        Ok(())
+    }
+
+    /// 活动文件达到 `MEMORY_SEGMENT_MAX_BYTES` 时封存为新的分段（失败只打印警告，不影响已完成的写入）。
+    /// 在写入锁内调用。
+    fn rotate_segments(&mut self) {
+        let Some(max_bytes) = self.options.segment_max_bytes else {
+            return;
+        };
+        if let Err(e) = self.files.rotate_if_needed(max_bytes) {
+            log::emit(
+                LogLevel::Error,
+                LOGGER,
+                format!("namespace={} 数据文件轮转失败：{e}", self.paths.namespace),
+                json!({ "namespace": self.paths.namespace, "event": "rotation_failed" }),
+            );
+        }
+    }
+
+    /// 汇总 namespace 统计（完全基于索引，不读取记录内容）。
+    pub fn stats(&mut self) -> Result<NamespaceStats, String> {
+        let indexed_bytes_before = self.index.indexed_up_to_offset;
+        self.sync_index().map_err(|e| e.to_string())?;
+        let total_bytes = self.index.indexed_up_to_offset;
+
+        let mut importance: BTreeMap<String, usize> = BTreeMap::new();
+        let mut occurred: Vec<i64> = Vec::new();
+        for &idx in self.index.heads.values() {
+            let Some(item) = self.index.items.get(idx as usize) else {
+                continue;
+            };
+            let key = item
+                .importance
+                .map(|x| x.to_string())
+                .unwrap_or_else(|| "none".to_string());
+            *importance.entry(key).or_insert(0) += 1;
+            if let Some(ts) = item.occurred_at_ts {
+                occurred.push(ts);
+            }
+        }
+
+        Ok(NamespaceStats {
+            items: self.index.heads.len(),
+            records: self.index.items.len(),
+            total_bytes,
+            segments: self.files.sealed().len(),
+            keywords: self.index.keyword_postings.len(),
+            importance,
+            items_with_occurred_at: occurred.len(),
+            earliest_occurred_at: occurred.iter().min().map(|&ts| time::ts_to_rfc3339(ts)),
+            latest_occurred_at: occurred.iter().max().map(|&ts| time::ts_to_rfc3339(ts)),
+            index: IndexFreshness {
+                fresh: indexed_bytes_before == total_bytes,
+                indexed_bytes_before,
+                generation: self.index.generation,
+                last_modified: self.index.last_modified_ts.map(time::ts_to_http_date),
+            },
+        })
+    }
+
+    /// 逐条校验当前版本记录的 checksum，返回体检报告。
+    pub fn checksum_report(&mut self) -> Result<ChecksumReport, String> {
+        self.sync_index().map_err(|e| e.to_string())?;
+
+        let mut heads: Vec<(&String, &u32)> = self.index.heads.iter().collect();
+        heads.sort();
+
+        let mut report = ChecksumReport {
+            total: heads.len(),
+            ..Default::default()
+        };
+        let blobs = blob::blobs_dir(&self.paths.memories_path);
+        for (id, &idx) in heads {
+            match read_item_by_index(&self.files, &self.index, idx) {
+                Ok(item) => match item.verify_checksum() {
+                    Some(true) if blob::resolve(&blobs, &mut item.clone()).is_err() => report.unreadable.push(id.clone()),
+                    Some(true) => report.verified += 1,
+                    Some(false) => report.mismatched.push(id.clone()),
+                    None => report.unsigned += 1,
+                },
+                Err(_) => report.unreadable.push(id.clone()),
+            }
+        }
+        Ok(report)
+    }
+
+    /// 检查索引与数据文件的一致性：每个索引条目能否读出可解析的记录、倒排是否指向当前版本、
+    /// 数据中是否有重复的 (id, revision)、活动文件末尾是否有残缺行。
+    ///
+    /// `repair` 时截掉残缺的末尾并全量重建索引；重复记录只报告，不改动数据。
+    pub fn verify(&mut self, repair: bool) -> Result<VerifyReport, String> {
+        if repair {
+            ensure_not_frozen(&self.paths)?;
+        }
+        self.sync_index().map_err(|e| e.to_string())?;
+
+        let mut report = self.inspect()?;
+        if !repair || report.is_clean() {
+            return Ok(report);
+        }
+
+        if report.truncated_tail_bytes > 0 {
+            let path = self.files.active_path();
+            let len = fs::metadata(path)
+                .map_err(|e| format!("stat memories.jsonl failed: {e}"))?
+                .len();
+            OpenOptions::new()
+                .write(true)
+                .open(path)
+                .and_then(|f| f.set_len(len - report.truncated_tail_bytes))
+                .map_err(|e| format!("truncate memories.jsonl failed: {e}"))?;
+            report
+                .repaired
+                .push(format!("truncated_tail:{}", report.truncated_tail_bytes));
+        }
+        if report.truncated_tail_bytes > 0 || !report.bad_entries.is_empty() || report.orphaned_postings > 0 {
+            self.rebuild_index()?;
+            report.repaired.push("rebuild_index".to_string());
+        }
+        Ok(report)
+    }
+
+    fn inspect(&self) -> Result<VerifyReport, String> {
+        let index = &self.index;
+        let mut report = VerifyReport {
+            entries: index.items.len(),
+            ..Default::default()
+        };
+
+        for (idx, entry) in index.items.iter().enumerate() {
+            let reason = match read_item_by_index(&self.files, index, idx as u32) {
+                Ok(item) if item.id == entry.id => continue,
+                Ok(item) => format!("记录 id 为 {}，与索引不一致", item.id),
+                Err(e) => e,
+            };
+            report.bad_entries.push(BadEntry {
+                id: entry.id.clone(),
+                offset: entry.offset,
+                reason,
+            });
+        }
+
+        let is_head = |idx: u32| {
+            index
+                .items
+                .get(idx as usize)
+                .is_some_and(|item| !item.superseded && index.heads.get(&item.id) == Some(&idx))
+        };
+        report.orphaned_postings = index
+            .keyword_postings
+            .values()
+            .chain(index.tag_postings.values())
+            .flatten()
+            .chain(&index.time_sorted)
+            .filter(|&&idx| !is_head(idx))
+            .count()
+            + index
+                .heads
+                .iter()
+                .filter(|(id, &idx)| index.items.get(idx as usize).is_none_or(|item| &item.id != *id))
+                .count();
+
+        let mut reader = BufReader::new(self.files.reader_from(0).map_err(|e| format!("read memories.jsonl failed: {e}"))?);
+        let mut seen: HashSet<(String, u32)> = HashSet::new();
+        let mut duplicates: BTreeSet<String> = BTreeSet::new();
+        let mut buf: Vec<u8> = Vec::new();
+        loop {
+            buf.clear();
+            let n = reader
+                .read_until(b'\n', &mut buf)
+                .map_err(|e| format!("read memories.jsonl failed: {e}"))?;
+            if n == 0 {
+                break;
+            }
+            if !buf.ends_with(b"\n") {
+                report.truncated_tail_bytes = n as u64;
+                break;
+            }
+            if let Ok(item) = serde_json::from_slice::<MemoryItem>(buf.trim_ascii_end()) {
+                if !seen.insert((item.id.clone(), item.revision)) {
+                    duplicates.insert(item.id);
+                }
+            }
+        }
+        report.duplicate_ids = duplicates.into_iter().collect();
+        Ok(report)
+    }
+
+    /// 导入导出文档中的记录：按 id 去重（同 id 取最后一条），改写为当前 namespace 后一次性追加并增量索引。
+    ///
+    /// 已存在的 id：内容一致视为重复跳过；内容不同则交由 `resolve(现有, 导入)` 决定处理方式。
+    ///
+    /// `dry_run=true` 时只计算报告（冲突同样经 `resolve` 决定），不写入任何内容。
+    pub fn import_records(
+        &mut self,
+        records: Vec<ExportRecord>,
+        resolve: &mut dyn FnMut(&MemoryItem, &MemoryItem) -> ConflictAction,
+        dry_run: bool,
+    ) -> Result<ImportReport, String> {
+        self.sync_index().map_err(|e| e.to_string())?;
+
+        let (files, index) = (&self.files, &self.index);
+        let blobs = blob::blobs_dir(&self.paths.memories_path);
+        let mut existing = |id: &str| -> Result<Option<MemoryItem>, String> {
+            let Some(&idx) = index.heads.get(id) else {
+                return Ok(None);
+            };
+            let mut item = read_item_by_index(files, index, idx)?;
+            blob::resolve(&blobs, &mut item)?;
+            Ok(Some(item))
+        };
+        let (report, out) = plan_import(&self.paths.namespace, records, &mut existing, resolve)?;
+
+        if !out.is_empty() && !dry_run {
+            self.commit_records(out)?;
+        }
+        Ok(report)
+    }
+
+    /// 按 id 读取当前版本的完整记录（经 heads 偏移表定位，不扫描 JSONL）。
+    pub fn get(&mut self, id: &str) -> Result<MemoryItem, String> {
+        self.sync_index().map_err(|e| e.to_string())?;
+        self.load_current_item(id.trim(), &HashMap::new())
     }
 
-    pub fn append_memory(&mut self, args: RememberArgs) -> Result<RememberRecorded, String> {
-        if let Some(n) = args.importance {
-            if !(1..=5).contains(&n) {
-                return Err("importance 必须在 1~5".to_string());
-            }
+    /// 按 id 读取全部修订（含已删除 id 的历史版本）：修订只追加、从不覆盖，索引中保留各版本的偏移。
+    pub fn history(&mut self, id: &str) -> Result<MemoryHistory, String> {
+        self.sync_index().map_err(|e| e.to_string())?;
+        let id = id.trim();
+        let versions = self
+            .index
+            .items
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| entry.id == id)
+            .map(|(idx, _)| load_item_by_index(&self.files, &self.index, idx as u32))
+            .collect::<Result<Vec<_>, String>>()?;
+        if versions.is_empty() {
+            return Err(format!("记忆不存在：{id}"));
         }
+        Ok(MemoryHistory {
+            versions,
+            deleted: !self.index.heads.contains_key(id),
+        })
+    }
 
+    /// 置顶 / 取消置顶（作为一次 update 写入新修订，不要求调用方提供 revision）；返回最新修订号。
+    pub fn set_pinned(&mut self, id: &str, pinned: bool) -> Result<u32, String> {
         self.sync_index().map_err(|e| e.to_string())?;
+        let current = self.load_current_item(id.trim(), &HashMap::new())?;
+        if current.pinned == pinned {
+            return Ok(current.revision);
+        }
+        let outcomes = self.apply_transaction(vec![TransactionStep::Update(UpdateArgs {
+            id: current.id,
+            revision: current.revision,
+            pinned: Some(pinned),
+            ..Default::default()
+        })])?;
+        Ok(outcomes[0].revision.unwrap_or(current.revision + 1))
+    }
 
-        let namespace = self.paths.namespace.clone();
-        let (recorded_at, recorded_at_ts) = time::now_rfc3339_and_ts();
+    /// 归档 / 取消归档：选中 ids 列出的记忆与时间范围（occurred_at ?? recorded_at）内的全部记忆（两者取并集），
+    /// 状态需要变化的条目在一次事务中写入新修订；返回实际变更的条目（按 id 排序）。
+    pub fn set_archived(
+        &mut self,
+        ids: &[String],
+        start: Option<&str>,
+        end: Option<&str>,
+        archived: bool,
+    ) -> Result<Vec<TransactionOutcome>, String> {
+        if ids.is_empty() && start.is_none() && end.is_none() {
+            return Err("archive 需要提供 ids 或时间范围（start/end）".to_string());
+        }
+        self.sync_index().map_err(|e| e.to_string())?;
 
-        let (occurred_at, occurred_at_ts) = match args.occurred_at.as_deref() {
-            Some(text) => {
-                let (ts, canonical) = time::parse_time_to_ts_and_canonical(text, DateBoundKind::Start)?;
-                (Some(canonical), Some(ts))
+        let mut targets: Vec<String> = Vec::new();
+        for id in ids.iter().map(|x| x.trim()).filter(|x| !x.is_empty()) {
+            if !self.index.heads.contains_key(id) {
+                return Err(format!("记忆不存在：{id}"));
             }
-            None => (None, None),
-        };
-
-        let keywords = normalize_keywords(args.keywords);
-        if keywords.is_empty() {
-            return Err("keywords 不能为空".to_string());
+            targets.push(id.to_string());
         }
+        if start.is_some() || end.is_some() {
+            let start_ts = match start {
+                Some(s) => Some(time::parse_time_to_ts_and_canonical(s, DateBoundKind::Start)?.0),
+                None => None,
+            };
+            let end_ts = match end {
+                Some(s) => Some(time::parse_time_to_ts_and_canonical(s, DateBoundKind::End)?.0),
+                None => None,
+            };
+            targets.extend(
+                self.index
+                    .heads
+                    .iter()
+                    .filter(|(_, &idx)| in_time_range(self.index.items[idx as usize].time_key_ts(), start_ts, end_ts))
+                    .map(|(id, _)| id.clone()),
+            );
+        }
+        targets.sort();
+        targets.dedup();
 
-        let id = Uuid::new_v4().to_string();
-        let item = MemoryItem {
-            id: id.clone(),
-            namespace: namespace.clone(),
-            recorded_at: recorded_at.clone(),
-            occurred_at: occurred_at.clone(),
-            keywords: keywords.clone(),
-            slice: args.slice,
-            diary: args.diary,
-            importance: args.importance,
-            source: args.source,
-        };
-
-        let mut file = OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(&self.paths.memories_path)
-            .map_err(|e| format!("open memories.jsonl failed: {e}"))?;
+        let mut steps: Vec<TransactionStep> = Vec::new();
+        for id in targets {
+            let idx = self.index.heads[&id];
+            if self.index.items[idx as usize].archived == archived {
+                continue;
+            }
+            let current = load_item_by_index(&self.files, &self.index, idx)?;
+            steps.push(TransactionStep::Update(UpdateArgs {
+                id,
+                revision: current.revision,
+                archived: Some(archived),
+                ..Default::default()
+            }));
+        }
+        if steps.is_empty() {
+            return Ok(Vec::new());
+        }
+        self.apply_transaction(steps)
+    }
 
-        let offset = file
-            .metadata()
-            .map_err(|e| format!("stat memories.jsonl failed: {e}"))?
-            .len();
+    /// 重要度衰减：从未被 recall 返回、未置顶且重要度高于 1 的记忆，按半衰期把重要度衰减为
+    /// `round(importance × 0.5^(天数 / 半衰期))`（最低为 1），天数从最后一次修改（updated_at，
+    /// 没有时取 occurred_at ?? recorded_at）起算；需要变化的条目在一次事务中写入新修订。
+    /// dry_run 时只返回将要变化的条目。结果按 id 排序。
+    pub fn decay_importance(&mut self, half_life_days: f64, dry_run: bool) -> Result<Vec<ImportanceDecay>, String> {
+        let half_life_days = check_half_life_days(half_life_days)?;
+        self.sync_index().map_err(|e| e.to_string())?;
+        let now_ts = time::now_rfc3339_and_ts().1;
 
-        let mut line = serde_json::to_vec(&item)
-            .map_err(|e| format!("serialize memory item failed: {e}"))?;
-        line.push(b'\n');
-        let length = line.len() as u32;
-
-        file.write_all(&line)
-            .and_then(|_| file.flush())
-            .map_err(|e| format!("append memories.jsonl failed: {e}"))?;
-
-        self.index.add_memory_item(
-            &item,
-            offset,
-            length,
-            recorded_at_ts,
-            occurred_at_ts,
-            keywords.clone(),
-        );
-        self.index.indexed_up_to_offset = offset + length as u64;
+        let mut candidates: Vec<(String, u32)> = self
+            .index
+            .heads
+            .iter()
+            .filter(|(id, &idx)| {
+                let item = &self.index.items[idx as usize];
+                !item.pinned && item.importance.is_some_and(|imp| imp > 1) && !self.index.access.contains_key(*id)
+            })
+            .map(|(id, &idx)| (id.clone(), idx))
+            .collect();
+        candidates.sort();
+
+        let mut decays: Vec<ImportanceDecay> = Vec::new();
+        let mut steps: Vec<TransactionStep> = Vec::new();
+        for (id, idx) in candidates {
+            let current = load_item_by_index(&self.files, &self.index, idx)?;
+            let Some(from) = current.importance else {
+                continue;
+            };
+            let since_ts = match current.updated_at.as_deref() {
+                Some(text) => time::parse_time_to_ts_and_canonical(text, DateBoundKind::Start)?.0,
+                None => self.index.items[idx as usize].time_key_ts(),
+            };
+            let days = (now_ts - since_ts).max(0) as f64 / 86_400.0;
+            let to = (f64::from(from) * 0.5f64.powf(days / half_life_days)).round().max(1.0) as u8;
+            if to >= from {
+                continue;
+            }
+            decays.push(ImportanceDecay {
+                id: id.clone(),
+                from,
+                to,
+                revision: None,
+            });
+            steps.push(TransactionStep::Update(UpdateArgs {
+                id,
+                revision: current.revision,
+                importance: Some(to),
+                ..Default::default()
+            }));
+        }
+        if dry_run || steps.is_empty() {
+            return Ok(decays);
+        }
 
-        save_index(&self.paths, &self.index)?;
+        let outcomes = self.apply_transaction(steps)?;
+        for (decay, outcome) in decays.iter_mut().zip(outcomes) {
+            decay.revision = outcome.revision;
+        }
+        Ok(decays)
+    }
 
-        Ok(RememberRecorded {
-            id,
-            recorded_at,
-            occurred_at,
-            keywords,
-        })
+    /// 打开 namespace 后调用：启用 `MEMORY_MAINTAIN_ON_OPEN` 时执行一次重要度衰减（冻结的 namespace 跳过，失败只打印警告）。
+    pub fn maintain_on_open(&mut self) {
+        if !self.options.maintain_on_open || ensure_not_frozen(&self.paths).is_err() {
+            return;
+        }
+        let half_life = self.options.importance_half_life_days.unwrap_or(IMPORTANCE_HALF_LIFE_DAYS);
+        match self.decay_importance(half_life, false) {
+            Ok(decays) if !decays.is_empty() => log::emit(
+                LogLevel::Notice,
+                LOGGER,
+                format!("namespace={} 已衰减 {} 条记忆的重要度", self.namespace(), decays.len()),
+                json!({ "namespace": self.namespace(), "event": "importance_decayed", "items": decays.len() }),
+            ),
+            Ok(_) => {}
+            Err(e) => log::emit(
+                LogLevel::Error,
+                LOGGER,
+                format!("namespace={} 重要度衰减失败：{e}", self.namespace()),
+                json!({ "namespace": self.namespace(), "event": "importance_decay_failed" }),
+            ),
+        }
     }
 
-    pub fn recall(&mut self, args: RecallArgs) -> Result<RecallResult, String> {
+    pub fn recall(&mut self, mut args: RecallArgs) -> Result<RecallResult, String> {
+        args.limit = args.limit.min(self.recall_max_limit()?);
         self.sync_index().map_err(|e| e.to_string())?;
         self.index.ensure_time_sorted();
 
         let keywords = normalize_keywords(args.keywords);
-        let keyword_set: Option<HashSet<String>> = if keywords.is_empty() {
-            None
+        if let Some(bad) = keywords.iter().find(|kw| kw.chars().all(|c| c == '*')) {
+            return Err(format!("关键字通配符至少需要包含 1 个非 * 字符：{bad}"));
+        }
+        // 通配符不计入热度：热门关键字用于提示可复用的具体关键字。
+        let concrete: Vec<String> = keywords
+            .iter()
+            .filter(|kw| !index::is_keyword_pattern(kw))
+            .cloned()
+            .collect();
+        self.heat.record(&concrete);
+        let keyword_filter: Option<&[String]> = (!keywords.is_empty()).then_some(keywords.as_slice());
+        let item_filter = ItemFilter {
+            written_by: args.written_by.as_deref(),
+            source_kind: args.source_kind.as_deref(),
+            source_id: args.source_id.as_deref(),
+        };
+        if args.regex && args.semantic {
+            return Err("regex 与 semantic 不能同时启用".to_string());
+        }
+        // 正则模式下 query 整体为模式：不剥离时间表达式，时间范围只能用 start/end。
+        let time_expr = if args.regex {
+            QueryTimeExpr::literal(args.query.clone())
+        } else {
+            parse_query_time_expr(args.query.as_deref())
+        };
+        let query_text = time_expr.text.clone();
+        let mut query = match query_text.as_deref() {
+            // 语义召回以整段文本做向量相似度排序，不解析布尔语法。
+            _ if args.semantic => None,
+            Some(text) if args.regex => QueryExpr::parse_regex(text)?,
+            Some(text) => QueryExpr::parse(text)?,
+            None => None,
+        };
+        if args.regex && query.is_none() {
+            return Err("regex=true 时必须提供非空的 query".to_string());
+        }
+        // 关键字同时匹配正文分词、query 依赖 trigram/分词倒排：首次使用时加载。
+        if (query.is_some() && !args.regex) || !keywords.is_empty() {
+            self.ensure_full_text()?;
+        }
+        // 使用映射时热点关键字的分词倒排常驻内存（热度变化时随之替换）。
+        if self.index.mapped.is_some() && !concrete.is_empty() {
+            self.index.pin_terms(&self.heat.top_keywords(heat::PINNED_KEYWORDS));
+        }
+        // 词组的分词命中（全部分词都出现在 slice/diary 中，不要求相邻）无需再做子串校验；
+        // 其余用 trigram 索引裁剪候选，避免对必然不匹配的条目读盘。
+        if let Some(expr) = query.as_mut() {
+            expr.bind(&self.index);
+        }
+        let query = query.as_ref();
+        let query_candidates = query.and_then(QueryExpr::candidates);
+        let query_allowed =
+            |idx: u32| query_candidates.as_ref().is_none_or(|set| set.contains(&idx));
+        let calendar = Arc::clone(&self.options.calendar);
+        let slot_allowed = |item: &IndexItem| time_expr.slot_allowed(item, &calendar);
+        let tags = index::normalize_tags(&args.tags);
+        let tag_candidates = self.index.tag_candidates(&tags);
+        let hidden = if args.include_superseded {
+            HashSet::new()
         } else {
-            Some(keywords.iter().cloned().collect())
+            self.index.superseded_heads()
+        };
+        let now_ts = time::now_rfc3339_and_ts().1;
+        let facet_allowed = |idx: u32| {
+            !hidden.contains(&idx)
+                && !self.index.items[idx as usize].is_expired(now_ts)
+                && (args.include_archived || !self.index.items[idx as usize].archived)
+                && tag_candidates.as_ref().is_none_or(|set| set.contains(&idx))
+                && (args.kinds.is_empty()
+                    || self.index.items[idx as usize].kind.is_some_and(|k| args.kinds.contains(&k)))
         };
-        let (query, query_start_ts, query_end_ts) = parse_query_time_expr(args.query.as_deref());
 
         let start_ts = match args.start.as_deref() {
             Some(s) => Some(time::parse_time_to_ts_and_canonical(s, DateBoundKind::Start)?.0),
@@ -184,79 +1910,381 @@ impl NamespaceState {
             None => None,
         };
 
-        let start_ts = max_opt_i64(start_ts, query_start_ts);
-        let end_ts = min_opt_i64(end_ts, query_end_ts);
+        let start_ts = max_opt_i64(start_ts, time_expr.start_ts);
+        let end_ts = min_opt_i64(end_ts, time_expr.end_ts);
 
         if let (Some(s), Some(e)) = (start_ts, end_ts) {
             if s > e {
                 return Ok(RecallResult {
                     total: 0,
                     items: Vec::new(),
+                    warnings: Vec::new(),
+                    offset: args.offset,
+                    has_more: false,
+                    next_cursor: None,
                 });
             }
         }
 
+        // 分页：收集到 offset + limit 条后再多探测 1 条，用于判断是否还有下一页。
+        let want = args.offset.saturating_add(args.limit);
+        let pinned_max = self.options.recall_pinned_max.unwrap_or(RECALL_PINNED_MAX);
+        let decay_half_life = match args.ranking.unwrap_or(self.options.recall_ranking) {
+            RecallRanking::Relevance => None,
+            RecallRanking::Decay => Some(
+                args.half_life_days
+                    .or(self.options.decay_half_life_days)
+                    .unwrap_or(DECAY_HALF_LIFE_DAYS),
+            ),
+        };
+
+        if args.semantic {
+            let Some(text) = query_text.as_deref().map(str::trim).filter(|x| !x.is_empty()) else {
+                return Err("semantic=true 时必须提供 query（时间表达式之外的文本）".to_string());
+            };
+            // 关键字在语义召回中只做过滤：至少命中一个关键字（含通配符）的记忆参与排序。
+            let candidates: Vec<u32> = if keywords.is_empty() {
+                self.iter_time_candidates(start_ts, end_ts)
+            } else {
+                let mut set: HashSet<u32> = HashSet::new();
+                for kw in &keywords {
+                    for (_, list) in self.index.keyword_matches(kw) {
+                        set.extend(list.iter().copied());
+                    }
+                }
+                set.into_iter()
+                    .filter(|&idx| in_time_range(self.index.items[idx as usize].time_key_ts(), start_ts, end_ts))
+                    .collect()
+            };
+            let candidates: Vec<u32> = candidates
+                .into_iter()
+                .filter(|&idx| facet_allowed(idx) && slot_allowed(&self.index.items[idx as usize]))
+                .collect();
+
+            let mut warnings: Vec<String> = Vec::new();
+            let ranked = self.semantic_rank(text, candidates, &item_filter, &mut warnings)?;
+            let has_more = ranked.len() > want;
+            let mut items: Vec<RecallItemOut> = ranked
+                .into_iter()
+                .skip(args.offset)
+                .take(args.limit)
+                .map(|(score, item)| {
+                    let mut out = recall_item_out(item, keyword_filter, args.include_diary);
+                    out.score = Some(score);
+                    out
+                })
+                .collect();
+            self.attach_index_info(&mut items);
+            return Ok(RecallResult {
+                total: items.len(),
+                items,
+                warnings,
+                offset: args.offset,
+                has_more,
+                next_cursor: None,
+            });
+        }
         let mut results: Vec<RecallItemOut> = Vec::new();
+        let mut warnings: Vec<String> = Vec::new();
 
         if keywords.is_empty() {
-            // 无关键字：按时间索引倒序扫描（近 → 远）
-            let candidates = self.iter_time_candidates(start_ts, end_ts);
+            // 无关键字：置顶记忆优先，其余按时间索引倒序扫描（近 → 远）；
+            // decay 排序时先对全部候选按衰减得分排序（相关度按 1 计）。
+            let mut candidates = self.iter_time_candidates(start_ts, end_ts);
+            if let Some(half_life) = decay_half_life {
+                let mut scored: Vec<(u32, f64, i64)> = candidates
+                    .into_iter()
+                    .filter(|&idx| {
+                        query_allowed(idx) && facet_allowed(idx) && slot_allowed(&self.index.items[idx as usize])
+                    })
+                    .map(|idx| {
+                        let ts = self.index.items[idx as usize].time_key_ts();
+                        (idx, self.decay_score(idx, 1.0, now_ts, half_life), ts)
+                    })
+                    .collect();
+                // score desc, time desc, idx desc
+                scored.sort_by(|a, b| {
+                    b.1.total_cmp(&a.1)
+                        .then_with(|| b.2.cmp(&a.2))
+                        .then_with(|| b.0.cmp(&a.0))
+                });
+                candidates = scored.into_iter().map(|x| x.0).collect();
+            }
+            let candidates = promote_pinned(candidates, pinned_max, |idx| {
+                let item = &self.index.items[idx as usize];
+                item.pinned && query_allowed(idx) && facet_allowed(idx) && slot_allowed(item)
+            });
             for idx in candidates {
-                if results.len() >= args.limit {
+                if results.len() > want {
                     break;
                 }
-                if let Some(item) =
-                    self.try_load_item_for_recall(idx, None, &query, args.include_diary)?
-                {
-                    results.push(item);
+                if !query_allowed(idx) || !facet_allowed(idx) {
+                    continue;
+                }
+                if !slot_allowed(&self.index.items[idx as usize]) {
+                    continue;
+                }
+                match self.try_load_item_for_recall(
+                    idx,
+                    None,
+                    query,
+                    args.include_diary,
+                    &item_filter,
+                ) {
+                    Ok(Some(item)) => results.push(item),
+                    Ok(None) => {}
+                    Err(e) => warnings.push(self.unreadable_warning(idx, &e)),
                 }
             }
         } else {
-            // 有关键字：倒排索引求并集，并按命中数/重要度/时间排序
-            let mut counts: HashMap<u32, u32> = HashMap::new();
+            // 有关键字：关键字倒排与分词倒排求并集，按关键字命中权重和/分词命中数/重要度/时间排序
+            // （显式关键字命中优先于正文分词命中；未指定权重的关键字按 1 计）。
+            // 通配符只匹配关键字（不匹配正文分词），同一条目命中多个展开关键字时取其中最大权重。
+            let mut counts: HashMap<u32, (u32, u32)> = HashMap::new();
             for kw in &keywords {
-                if let Some(list) = self.index.keyword_postings.get(kw) {
+                let mut best: HashMap<u32, u32> = HashMap::new();
+                for (matched, list) in self.index.keyword_matches(kw) {
                     for &idx in list {
-                        *counts.entry(idx).or_insert(0) += 1;
+                        let weight = self.index.items[idx as usize].keyword_weight(matched);
+                        let slot = best.entry(idx).or_default();
+                        *slot = (*slot).max(weight);
                     }
                 }
+                for (idx, weight) in best {
+                    counts.entry(idx).or_default().0 += weight;
+                }
+                if index::is_keyword_pattern(kw) {
+                    continue;
+                }
+                for idx in self.index.term_hits(kw) {
+                    counts.entry(idx).or_default().1 += 1;
+                }
             }
 
-            let mut scored: Vec<(u32, u32, i64, u8)> = Vec::new();
+            let mut scored: Vec<(u32, (u32, u32), i64, u8)> = Vec::new();
             for (idx, hit) in counts {
+                if !query_allowed(idx) || !facet_allowed(idx) {
+                    continue;
+                }
                 let item = &self.index.items[idx as usize];
                 let ts = item.time_key_ts();
-                if !in_time_range(ts, start_ts, end_ts) {
+                if !in_time_range(ts, start_ts, end_ts) || !slot_allowed(item) {
                     continue;
                 }
                 let imp = item.importance.unwrap_or(0);
                 scored.push((idx, hit, ts, imp));
             }
 
-            scored.sort_by(|a, b| {
-                // hit desc, importance desc, time desc
-                b.1.cmp(&a.1)
-                    .then_with(|| b.3.cmp(&a.3))
-                    .then_with(|| b.2.cmp(&a.2))
+            let order: Vec<u32> = match decay_half_life {
+                None => {
+                    scored.sort_by(|a, b| {
+                        // hit desc, importance desc, time desc, idx desc（保证分页顺序稳定）
+                        b.1.cmp(&a.1)
+                            .then_with(|| b.3.cmp(&a.3))
+                            .then_with(|| b.2.cmp(&a.2))
+                            .then_with(|| b.0.cmp(&a.0))
+                    });
+                    scored.into_iter().map(|x| x.0).collect()
+                }
+                Some(half_life) => {
+                    // 相关度：关键字命中权重和 + 分词命中数的一半（显式关键字命中更重要）。
+                    let mut keyed: Vec<(f64, u8, i64, u32)> = scored
+                        .into_iter()
+                        .map(|(idx, hit, ts, imp)| {
+                            let relevance = f64::from(hit.0) + f64::from(hit.1) * 0.5;
+                            (self.decay_score(idx, relevance, now_ts, half_life), imp, ts, idx)
+                        })
+                        .collect();
+                    // score desc, importance desc, time desc, idx desc
+                    keyed.sort_by(|a, b| {
+                        b.0.total_cmp(&a.0)
+                            .then_with(|| b.1.cmp(&a.1))
+                            .then_with(|| b.2.cmp(&a.2))
+                            .then_with(|| b.3.cmp(&a.3))
+                    });
+                    keyed.into_iter().map(|x| x.3).collect()
+                }
+            };
+            let ranked = promote_pinned(order, pinned_max, |idx| {
+                self.index.items[idx as usize].pinned
             });
 
-            for (idx, _hit, _ts, _imp) in scored {
-                if results.len() >= args.limit {
+            for idx in ranked {
+                if results.len() > want {
                     break;
                 }
-                if let Some(item) = self.try_load_item_for_recall(
+                match self.try_load_item_for_recall(
                     idx,
-                    keyword_set.as_ref(),
-                    &query,
+                    keyword_filter,
+                    query,
                     args.include_diary,
-                )? {
-                    results.push(item);
+                    &item_filter,
+                ) {
+                    Ok(Some(item)) => results.push(item),
+                    Ok(None) => {}
+                    Err(e) => warnings.push(self.unreadable_warning(idx, &e)),
                 }
             }
         }
 
+        let has_more = results.len() > want;
+        results.truncate(want);
+        results.drain(..args.offset.min(results.len()));
+        self.attach_index_info(&mut results);
+
         let total = results.len();
-        Ok(RecallResult { total, items: results })
+        Ok(RecallResult {
+            total,
+            items: results,
+            warnings,
+            offset: args.offset,
+            has_more,
+            next_cursor: None,
+        })
+    }
+
+    /// decay 排序得分：相关度 × 0.5^(距今天数 / 半衰期) × (1 + ln(1 + 被召回次数))。
+    fn decay_score(&self, idx: u32, relevance: f64, now_ts: i64, half_life_days: f64) -> f64 {
+        let item = &self.index.items[idx as usize];
+        let age_days = (now_ts - item.time_key_ts()).max(0) as f64 / 86_400.0;
+        let count = self.index.access.get(&item.id).map_or(0, |stat| stat.count);
+        relevance * 0.5f64.powf(age_days / half_life_days) * (1.0 + (count as f64).ln_1p())
+    }
+
+    /// 补充索引中的信息：入链（取代了该记忆的新记忆 id）与本次之前的 recall 统计。
+    fn attach_index_info(&self, items: &mut [RecallItemOut]) {
+        for item in items {
+            if let Some(ids) = self.index.superseded_by.get(&item.id) {
+                item.links.superseded_by = ids.clone();
+            }
+            if let Some(stat) = self.index.access.get(&item.id) {
+                item.recall_count = stat.count;
+                item.last_recalled_at = Some(stat.last_recalled_at.clone());
+            }
+        }
+    }
+
+    /// 记录本次 recall 实际返回的记忆（次数 + 时间），不递增变更代数；
+    /// 统计是尽力而为的：写入失败不影响 recall，多个进程并发召回时可能少计。
+    ///
+    /// 统计先记在内存中，每 [`ACCESS_SAVE_EVERY`] 次 recall 批量合并写入 access.json（不改写 index.json，
+    /// 其他进程不会因召回而重新加载索引）；写入失败时保留增量，由后续批次或 `flush` 补写。
+    pub fn record_recalled(&mut self, ids: &[String]) {
+        if ids.is_empty() {
+            return;
+        }
+        let now = time::now_rfc3339_and_ts().0;
+        for id in self.index.record_access(ids, &now) {
+            let delta = self.access_pending.entry(id.clone()).or_default();
+            delta.count += 1;
+            delta.last_recalled_at = now.clone();
+        }
+        self.pending_recalls += 1;
+        if self.pending_recalls >= ACCESS_SAVE_EVERY {
+            let _ = self.save_access_stats();
+        }
+    }
+
+    /// 把本进程的召回统计增量合并进 access.json：持写入锁读取磁盘上的最新统计（可能含其他进程的计数），
+    /// 累加增量后整体替换；没有增量时只重新读取。
+    fn save_access_stats(&mut self) -> Result<(), String> {
+        if self.access_pending.is_empty() {
+            self.index.access = load_access(&self.paths);
+            return Ok(());
+        }
+        let lock = journal::lock(&self.paths)?;
+        let mut access = load_access(&self.paths);
+        for (id, delta) in &self.access_pending {
+            access.entry(id.clone()).or_default().merge(delta);
+        }
+        save_access(&self.paths, &access)?;
+        drop(lock);
+        self.index.access = access;
+        self.access_pending.clear();
+        self.pending_recalls = 0;
+        Ok(())
+    }
+
+    /// 重新读取索引后补上本进程尚未落盘的召回统计增量。
+    fn apply_pending_access(&mut self) {
+        for (id, delta) in &self.access_pending {
+            self.index.access.entry(id.clone()).or_default().merge(delta);
+        }
+    }
+
+    /// 补写尚未落盘的召回统计与索引变更（进程退出前调用）。
+    ///
+    /// index.json 已被其他进程改写时放弃本进程的索引变更：追加的记录会由对方增量索引。
+    pub fn flush(&mut self) -> Result<(), String> {
+        if !self.access_pending.is_empty() {
+            self.save_access_stats()?;
+        }
+        if !self.index_dirty {
+            return Ok(());
+        }
+        if index_file_stamp(&self.paths) != self.index_stamp {
+            self.index_dirty = false;
+            return Ok(());
+        }
+        self.persist_index()
+    }
+
+    /// 最少被 recall 返回的记忆（次数升序，其次最近返回时间、记忆时间由远到近），用于找出长期无用的记忆。
+    pub fn least_recalled(&mut self, limit: usize) -> Result<RecallResult, String> {
+        self.sync_index().map_err(|e| e.to_string())?;
+        // 合并其他进程与本进程尚未落盘的统计。
+        self.save_access_stats()?;
+
+        let mut order: Vec<(u64, &str, i64, u32)> = self
+            .index
+            .heads
+            .iter()
+            .map(|(id, &idx)| {
+                let stat = self.index.access.get(id);
+                (
+                    stat.map_or(0, |s| s.count),
+                    stat.map_or("", |s| s.last_recalled_at.as_str()),
+                    self.index.items[idx as usize].time_key_ts(),
+                    idx,
+                )
+            })
+            .collect();
+        order.sort();
+        let has_more = order.len() > limit;
+
+        let mut items: Vec<RecallItemOut> = Vec::new();
+        let mut warnings: Vec<String> = Vec::new();
+        for &(_, _, _, idx) in order.iter().take(limit) {
+            match load_item_by_index(&self.files, &self.index, idx) {
+                Ok(item) => items.push(recall_item_out(item, None, false)),
+                Err(e) => warnings.push(self.unreadable_warning(idx, &e)),
+            }
+        }
+        self.attach_index_info(&mut items);
+        Ok(RecallResult {
+            total: items.len(),
+            items,
+            warnings,
+            offset: 0,
+            has_more,
+            next_cursor: None,
+        })
+    }
+
+    /// 取代了指定记忆的新记忆 id（未被取代时为空）。
+    pub fn superseded_by(&self, id: &str) -> Vec<String> {
+        self.index.superseded_by.get(id.trim()).cloned().unwrap_or_default()
+    }
+
+    /// 单条记录读取失败时不让整个 recall 失败：跳过该条并生成告警。
+    fn unreadable_warning(&self, idx: u32, err: &str) -> String {
+        let id = self
+            .index
+            .items
+            .get(idx as usize)
+            .map(|x| x.id.as_str())
+            .unwrap_or("?");
+        format!("已跳过无法读取的记忆 id={id}：{err}")
     }
 
     fn iter_time_candidates(&self, start_ts: Option<i64>, end_ts: Option<i64>) -> Vec<u32> {
@@ -281,77 +2309,535 @@ impl NamespaceState {
             .collect()
     }
 
-    fn try_load_item_for_recall(
-        &self,
-        idx: u32,
-        keyword_set: Option<&HashSet<String>>,
-        query: &Option<String>,
-        include_diary: bool,
-    ) -> Result<Option<RecallItemOut>, String> {
-        let item = load_item_by_index(&self.paths.memories_path, &self.index, idx)?;
-
-        if let Some(q) = query {
-            let q = q.as_str();
-            let hay = format!(
-                "{}\n{}\n{}",
-                item.slice.to_lowercase(),
-                item.diary.to_lowercase(),
-                item.source.clone().unwrap_or_default().to_lowercase()
-            );
-            if !hay.contains(q) {
-                return Ok(None);
-            }
+    fn try_load_item_for_recall(
+        &self,
+        idx: u32,
+        keyword_filter: Option<&[String]>,
+        query: Option<&QueryExpr>,
+        include_diary: bool,
+        item_filter: &ItemFilter,
+    ) -> Result<Option<RecallItemOut>, String> {
+        let item = load_item_by_index(&self.files, &self.index, idx)?;
+
+        if !item_filter.accepts(&item) {
+            return Ok(None);
+        }
+
+        if !query::item_matches(query, idx, &item) {
+            return Ok(None);
+        }
+
+        Ok(Some(recall_item_out(item, keyword_filter, include_diary)))
+    }
+
+    /// 语义召回：用查询文本与候选记忆（slice + diary）的向量余弦相似度排序（降序，同分按时间由近到远）。
+    ///
+    /// 记忆的向量按内容 checksum 缓存在 embeddings.json，只为新增/变更的记忆调用向量化后端。
+    fn semantic_rank(
+        &mut self,
+        text: &str,
+        candidates: Vec<u32>,
+        item_filter: &ItemFilter,
+        warnings: &mut Vec<String>,
+    ) -> Result<Vec<(f32, MemoryItem)>, String> {
+        let embedder = Arc::clone(&self.options.embedder);
+        embedder.ensure_enabled()?;
+
+        let mut items: Vec<(i64, MemoryItem, String)> = Vec::new();
+        for idx in candidates {
+            match load_item_by_index(&self.files, &self.index, idx) {
+                Ok(item) if item_filter.accepts(&item) => {
+                    let checksum = item.checksum.clone().unwrap_or_else(|| item.compute_checksum());
+                    items.push((self.index.items[idx as usize].time_key_ts(), item, checksum));
+                }
+                Ok(_) => {}
+                Err(e) => warnings.push(self.unreadable_warning(idx, &e)),
+            }
+        }
+
+        let fingerprint = embedder.fingerprint();
+        let path = &self.paths.embeddings_path;
+        let cache = self
+            .embeddings
+            .get_or_insert_with(|| EmbeddingCache::load(path, &fingerprint));
+
+        // 查询文本与缺失向量的记忆一次性批量向量化（command 后端只启动一次进程）。
+        let missing: Vec<usize> = (0..items.len())
+            .filter(|&i| cache.get(&items[i].1.id, &items[i].2).is_none())
+            .collect();
+        let mut texts = vec![text.to_string()];
+        texts.extend(missing.iter().map(|&i| format!("{}\n{}", items[i].1.slice, items[i].1.diary)));
+        let mut vectors = embedder.embed(&texts)?.into_iter();
+        let query_vector = vectors.next().unwrap_or_default();
+        for (i, vector) in missing.iter().zip(vectors) {
+            let (_, item, checksum) = &items[*i];
+            cache.vectors.insert(
+                item.id.clone(),
+                embedding::CachedVector {
+                    checksum: checksum.clone(),
+                    vector,
+                },
+            );
+        }
+
+        let mut ranked: Vec<(f32, i64, MemoryItem)> = items
+            .into_iter()
+            .map(|(ts, item, checksum)| {
+                let score = cache
+                    .get(&item.id, &checksum)
+                    .map(|v| embedding::cosine(&query_vector, v))
+                    .unwrap_or(0.0);
+                (score, ts, item)
+            })
+            .collect();
+        ranked.sort_by(|a, b| b.0.total_cmp(&a.0).then_with(|| b.1.cmp(&a.1)));
+
+        if !missing.is_empty() {
+            // 顺带清理已删除记忆的向量；缓存写入失败不影响本次召回。
+            let heads = &self.index.heads;
+            cache.vectors.retain(|id, _| heads.contains_key(id));
+            if let Ok(text) = serde_json::to_string(&*cache) {
+                let _ = replace_file(&self.paths.embeddings_path, &text, "embeddings.json");
+            }
+        }
+
+        Ok(ranked.into_iter().map(|(score, _, item)| (score, item)).collect())
+    }
+
+    /// index.json 被其他进程（如服务运行期间的 CLI 单次调用）改写过时，丢弃内存中的索引并重新读取头部。
+    ///
+    /// 追加写入可由增量索引补齐，但 compact/purge 等整体改写、召回统计等只落盘在索引中的变更无法从数据文件
+    /// 推得；按文件修改时间与长度判断，全文倒排随后按需从 postings.json 重新加载。
+    fn reload_index_if_changed(&mut self) -> Result<(), String> {
+        let stamp = index_file_stamp(&self.paths);
+        if stamp.is_none() || stamp == self.index_stamp {
+            return Ok(());
+        }
+        self.index = load_or_create_index(&self.paths)?;
+        self.index_stamp = index_file_stamp(&self.paths);
+        self.apply_pending_access();
+        Ok(())
+    }
+
+    /// 清空索引（保留变更代数，避免重建后 ETag 回退与旧值相撞）。
+    fn reset_index(&mut self) {
+        let generation = self.index.generation;
+        let access = std::mem::take(&mut self.index.access);
+        self.index = IndexData::new(&self.paths.namespace);
+        self.index.generation = generation;
+        self.index.access = access;
+        self.index.touch(time::now_rfc3339_and_ts().1);
+    }
+
+    /// 更新写入者身份（MCP 客户端在 initialize 时声明名称后同步到已打开的 namespace）。
+    pub fn set_writer(&mut self, writer: Option<String>) {
+        self.options.writer = writer;
+    }
+
+    /// 预热：同步增量索引并整理时间排序，使首次 recall 不再承担索引开销。
+    pub fn warm_up(&mut self) -> Result<(), String> {
+        self.sync_index().map_err(|e| e.to_string())?;
+        self.index.ensure_time_sorted();
+        Ok(())
+    }
+
+    /// 返回当前索引的缓存校验值（会先同步外部追加）。
+    pub fn cache_validators(&mut self) -> Result<CacheValidators, String> {
+        self.sync_index().map_err(|e| e.to_string())?;
+        Ok(CacheValidators {
+            generation: self.index.generation,
+            etag: self.index.etag(),
+            last_modified: self.index.last_modified_ts.map(time::ts_to_http_date),
+        })
+    }
+
+    fn sync_index(&mut self) -> io::Result<()> {
+        // 其他进程可能已轮转出新的分段：每次重新读取清单（全局偏移不受轮转影响）。
+        self.files.reload().map_err(io::Error::other)?;
+        if !self.files.active_path().exists() {
+            // 活动文件缺失：其他进程正在轮转（持写入锁等它完成）或轮转到一半就退出了（补记分段）。
+            let _lock = journal::lock(&self.paths).map_err(io::Error::other)?;
+            self.files.finish_rotation().map_err(io::Error::other)?;
+        }
+        self.reload_index_if_changed().map_err(io::Error::other)?;
+        let file_len = self.files.total_len()?;
+
+        // 文件回退：重建索引
+        if file_len < self.index.indexed_up_to_offset {
+            self.reset_index();
+        }
+
+        if file_len == self.index.indexed_up_to_offset {
+            return Ok(());
+        }
+
+        self.ensure_full_text().map_err(io::Error::other)?;
+        incremental_index(&self.files, &mut self.index)?;
+        self.index.touch(time::now_rfc3339_and_ts().1);
+        self.persist_index().map_err(io::Error::other)?;
+        Ok(())
+    }
+}
+
+/// 按 id 折叠导入记录（同一 id 以最后一条为准），保持各 id 首次出现的顺序。
+fn latest_records(records: Vec<ExportRecord>) -> Vec<(String, ExportRecord)> {
+    let mut order: Vec<String> = Vec::new();
+    let mut latest: HashMap<String, ExportRecord> = HashMap::new();
+    for record in records {
+        let id = record.id().to_string();
+        if latest.insert(id.clone(), record).is_none() {
+            order.push(id);
+        }
+    }
+    order
+        .into_iter()
+        .filter_map(|id| latest.remove(&id).map(|record| (id, record)))
+        .collect()
+}
+
+/// 导入预演：按 `existing`（目标 namespace 当前的记忆，按 id）计算报告，不写入任何内容。
+pub fn preview_import(
+    namespace: &str,
+    records: Vec<ExportRecord>,
+    existing: &HashMap<String, MemoryItem>,
+    resolve: &mut dyn FnMut(&MemoryItem, &MemoryItem) -> ConflictAction,
+) -> Result<ImportReport, String> {
+    let mut lookup = |id: &str| Ok(existing.get(id).cloned());
+    plan_import(namespace, records, &mut lookup, resolve).map(|(report, _)| report)
+}
+
+/// 计算导入报告与需要追加的记录：`existing` 按 id 取目标中的当前版本（diary 已还原）。
+fn plan_import(
+    namespace: &str,
+    records: Vec<ExportRecord>,
+    existing: &mut dyn FnMut(&str) -> Result<Option<MemoryItem>, String>,
+    resolve: &mut dyn FnMut(&MemoryItem, &MemoryItem) -> ConflictAction,
+) -> Result<(ImportReport, Vec<LogRecord>), String> {
+    let mut report = ImportReport::default();
+    let mut out: Vec<LogRecord> = Vec::new();
+    for (id, record) in latest_records(records) {
+        match record {
+            ExportRecord::Item(mut item) => {
+                if item.verify_checksum() == Some(false) {
+                    report.invalid.push(id);
+                    continue;
+                }
+                item.namespace = namespace.to_string();
+                item.seal();
+
+                let Some(current) = existing(&id)? else {
+                    report.imported += 1;
+                    out.push(LogRecord::Item(item));
+                    continue;
+                };
+                if current.compute_checksum() == item.compute_checksum() {
+                    report.duplicates += 1;
+                    continue;
+                }
+
+                let action = resolve(&current, &item);
+                let mut new_id = None;
+                match action {
+                    ConflictAction::KeepExisting => {}
+                    ConflictAction::Replace => {
+                        item.revision = current.revision + 1;
+                        item.seal();
+                        out.push(LogRecord::Item(item));
+                    }
+                    ConflictAction::Duplicate => {
+                        item.id = Uuid::new_v4().to_string();
+                        item.revision = 1;
+                        item.seal();
+                        new_id = Some(item.id.clone());
+                        out.push(LogRecord::Item(item));
+                    }
+                }
+                report.conflicts.push(ConflictDecision { id, action, new_id });
+            }
+            ExportRecord::Delete(mut t) => {
+                if existing(&id)?.is_some() {
+                    t.namespace = namespace.to_string();
+                    report.deleted += 1;
+                    out.push(LogRecord::Tombstone(t));
+                }
+            }
+        }
+    }
+    Ok((report, out))
+}
+
+pub(super) fn build_new_item(namespace: &str, args: RememberArgs, writer: Option<&str>) -> Result<MemoryItem, String> {
+    if let Some(n) = args.importance {
+        if !(1..=5).contains(&n) {
+            return Err("importance 必须在 1~5".to_string());
+        }
+    }
+
+    let (recorded_at, _) = time::now_rfc3339_and_ts();
+
+    let occurred_at = match args.occurred_at.as_deref() {
+        Some(text) => Some(time::parse_time_to_ts_and_canonical(text, DateBoundKind::Start)?.1),
+        None => None,
+    };
+
+    let keywords = normalize_keywords(args.keywords);
+    if keywords.is_empty() {
+        return Err("keywords 不能为空".to_string());
+    }
+    let keyword_weights = normalize_keyword_weights(args.keyword_weights, &keywords);
+    let tags = index::normalize_tags(&args.tags);
+
+    let mut item = MemoryItem {
+        id: Uuid::new_v4().to_string(),
+        namespace: namespace.to_string(),
+        recorded_at,
+        occurred_at,
+        keywords,
+        keyword_weights,
+        tags,
+        kind: args.kind,
+        slice: args.slice,
+        diary: args.diary,
+        diary_ref: None,
+        diary_zstd: None,
+        importance: args.importance,
+        source: args.source,
+        metadata: args.metadata,
+        relates_to: normalize_link_ids(args.relates_to),
+        supersedes: args.supersedes.map(|x| x.trim().to_string()).filter(|x| !x.is_empty()),
+        expires_at: parse_expires_at(args.expires_at.as_deref())?,
+        pinned: args.pinned,
+        archived: false,
+        updated_at: None,
+        revision: 1,
+        written_by: writer.map(|x| x.to_string()),
+        checksum: None,
+    };
+    item.seal();
+    Ok(item)
+}
+
+/// recall 中需读取完整记录才能判断的过滤条件（写入者、来源）。
+pub(super) struct ItemFilter<'a> {
+    pub written_by: Option<&'a str>,
+    pub source_kind: Option<&'a str>,
+    pub source_id: Option<&'a str>,
+}
+
+impl ItemFilter<'_> {
+    pub fn accepts(&self, item: &MemoryItem) -> bool {
+        let source = item.source.as_ref();
+        let matches = |want: Option<&str>, got: Option<&str>| want.is_none() || want == got;
+        matches(self.written_by, item.written_by.as_deref())
+            && matches(self.source_kind, source.and_then(|s| s.kind.as_deref()))
+            && matches(self.source_id, source.and_then(|s| s.id.as_deref()))
+    }
+}
+
+/// 将读取到的记忆转为 recall 输出；`keyword_filter` 为本次查询的关键字（含通配符），用于标注命中的关键字。
+pub(super) fn recall_item_out(item: MemoryItem, keyword_filter: Option<&[String]>, include_diary: bool) -> RecallItemOut {
+    let matched_keywords = keyword_filter.map(|patterns| {
+        let mut out: Vec<String> = item
+            .keywords
+            .iter()
+            .filter(|kw| patterns.iter().any(|p| index::keyword_matches_pattern(p, kw)))
+            .cloned()
+            .collect();
+        out.sort_by(|a, b| {
+            a.chars()
+                .count()
+                .cmp(&b.chars().count())
+                .then_with(|| a.cmp(b))
+        });
+        out
+    });
+
+    RecallItemOut {
+        id: item.id,
+        revision: item.revision,
+        recorded_at: item.recorded_at,
+        occurred_at: item.occurred_at,
+        keywords: item.keywords,
+        keyword_weights: item.keyword_weights,
+        matched_keywords,
+        tags: item.tags,
+        kind: item.kind,
+        slice: item.slice,
+        diary: include_diary.then_some(item.diary),
+        importance: item.importance,
+        source: item.source,
+        metadata: item.metadata,
+        links: MemoryLinks {
+            relates_to: item.relates_to,
+            supersedes: item.supersedes,
+            superseded_by: Vec::new(),
+        },
+        expires_at: item.expires_at,
+        pinned: item.pinned,
+        archived: item.archived,
+        recall_count: 0,
+        last_recalled_at: None,
+        written_by: item.written_by,
+        score: None,
+    }
+}
+
+/// 置顶：把序列中前 cap 条置顶记忆移到最前，置顶与其余条目各自保持原有相对顺序。
+pub(super) fn promote_pinned(order: Vec<u32>, cap: usize, is_pinned: impl Fn(u32) -> bool) -> Vec<u32> {
+    if cap == 0 {
+        return order;
+    }
+    let mut pinned: Vec<u32> = Vec::new();
+    let mut rest: Vec<u32> = Vec::with_capacity(order.len());
+    for idx in order {
+        if pinned.len() < cap && is_pinned(idx) {
+            pinned.push(idx);
+        } else {
+            rest.push(idx);
+        }
+    }
+    pinned.extend(rest);
+    pinned
+}
+
+/// 规范化过期时间：空白视为不过期；日期（YYYY-MM-DD 等）保留为日期，表示当天结束时过期。
+fn parse_expires_at(text: Option<&str>) -> Result<Option<String>, String> {
+    match text.map(str::trim).filter(|x| !x.is_empty()) {
+        Some(text) => Ok(Some(time::parse_time_to_ts_and_canonical(text, DateBoundKind::End)?.1)),
+        None => Ok(None),
+    }
+}
+
+/// 重复检测用的归一化：小写并把连续空白压缩为单个空格。
+fn normalize_for_dedupe(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+/// 记忆最后一次变更的时间戳（updated_at ?? recorded_at）；无法解析时视为最旧。
+fn change_ts(item: &MemoryItem) -> i64 {
+    let text = item.updated_at.as_deref().unwrap_or(&item.recorded_at);
+    time::parse_time_to_ts_and_canonical(text, DateBoundKind::Start)
+        .map(|(ts, _)| ts)
+        .unwrap_or(i64::MIN)
+}
+
+pub(super) fn check_revision(current: &MemoryItem, expected: u32) -> Result<(), String> {
+    if current.revision != expected {
+        return Err(format!(
+            "revision 冲突：记忆 {} 当前修订号为 {}，调用方提供的是 {}（可能已被其它调用方修改，请重新读取后再操作）",
+            current.id, current.revision, expected
+        ));
+    }
+    Ok(())
+}
+
+pub(super) fn apply_update(mut item: MemoryItem, args: UpdateArgs, writer: Option<&str>) -> Result<MemoryItem, String> {
+    check_revision(&item, args.revision)?;
+    if let Some(n) = args.importance {
+        if !(1..=5).contains(&n) {
+            return Err("importance 必须在 1~5".to_string());
         }
+        item.importance = Some(n);
+    }
 
-        let matched_keywords = keyword_set.map(|set| {
-            let mut out: Vec<String> = item
-                .keywords
-                .iter()
-                .filter(|kw| set.contains(*kw))
-                .cloned()
-                .collect();
-            out.sort_by(|a, b| {
-                a.chars()
-                    .count()
-                    .cmp(&b.chars().count())
-                    .then_with(|| a.cmp(b))
-            });
-            out
-        });
+    if let Some(keywords) = args.keywords {
+        let keywords = normalize_keywords(keywords);
+        if keywords.is_empty() {
+            return Err("keywords 不能为空".to_string());
+        }
+        // 权重随关键字整体替换：未重新指定权重的关键字回到默认权重 1。
+        item.keyword_weights = normalize_keyword_weights(args.keyword_weights, &keywords);
+        item.keywords = keywords;
+    }
+    if let Some(tags) = args.tags {
+        item.tags = index::normalize_tags(&tags);
+    }
+    if args.kind.is_some() {
+        item.kind = args.kind;
+    }
+    if let Some(metadata) = args.metadata {
+        item.metadata = metadata;
+    }
+    if let Some(ids) = args.relates_to {
+        item.relates_to = normalize_link_ids(ids);
+    }
+    if let Some(id) = args.supersedes {
+        item.supersedes = Some(id).filter(|x| !x.is_empty());
+    }
+    if let Some(text) = args.expires_at.as_deref() {
+        item.expires_at = parse_expires_at(Some(text))?;
+    }
+    if let Some(pinned) = args.pinned {
+        item.pinned = pinned;
+    }
+    if let Some(archived) = args.archived {
+        item.archived = archived;
+    }
 
-        Ok(Some(RecallItemOut {
-            id: item.id,
-            recorded_at: item.recorded_at,
-            occurred_at: item.occurred_at,
-            keywords: item.keywords,
-            matched_keywords,
-            slice: item.slice,
-            diary: include_diary.then_some(item.diary),
-            importance: item.importance,
-            source: item.source,
-        }))
+    if let Some(text) = args.occurred_at.as_deref() {
+        item.occurred_at = Some(time::parse_time_to_ts_and_canonical(text, DateBoundKind::Start)?.1);
+    }
+    if let Some(slice) = args.slice {
+        item.slice = slice;
+    }
+    if let Some(diary) = args.diary {
+        item.diary = diary;
+    }
+    if let Some(source) = args.source {
+        item.source = Some(source);
     }
 
-    fn sync_index(&mut self) -> io::Result<()> {
-        let file_len = fs::metadata(&self.paths.memories_path)?.len();
+    item.updated_at = Some(time::now_rfc3339_and_ts().0);
+    item.revision += 1;
+    item.written_by = writer.map(|x| x.to_string());
+    item.seal();
+    Ok(item)
+}
 
-        // 文件回退：重建索引
-        if file_len < self.index.indexed_up_to_offset {
-            self.index = IndexData::new(&self.paths.namespace);
+/// 链接 id：trim、去空、去重（保持顺序）。
+/// 校验新增的链接指向当前存在的其它记忆（`previous` 中已有的链接不再校验，目标被删除后仍可更新其它字段）。
+pub(super) fn check_new_links(
+    item: &MemoryItem,
+    previous: Option<&MemoryItem>,
+    exists: impl Fn(&str) -> bool,
+) -> Result<(), String> {
+    let old_related = previous.map(|p| p.relates_to.as_slice()).unwrap_or_default();
+    let old_supersedes = previous.and_then(|p| p.supersedes.as_deref());
+
+    let added = item
+        .relates_to
+        .iter()
+        .filter(|id| !old_related.contains(id))
+        .map(|id| ("relates_to", id.as_str()))
+        .chain(
+            item.supersedes
+                .as_deref()
+                .filter(|id| Some(*id) != old_supersedes)
+                .map(|id| ("supersedes", id)),
+        );
+    for (field, id) in added {
+        if id == item.id {
+            return Err(format!("{field} 不能指向自身：{id}"));
         }
-
-        if file_len == self.index.indexed_up_to_offset {
-            return Ok(());
+        if !exists(id) {
+            return Err(format!("{field} 指向的记忆不存在：{id}"));
         }
+    }
+    Ok(())
+}
 
-        incremental_index(&self.paths.memories_path, &mut self.index)?;
-        save_index(&self.paths, &self.index)
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
-        Ok(())
+fn normalize_link_ids(ids: Vec<String>) -> Vec<String> {
+    let mut out: Vec<String> = Vec::new();
+    for id in ids {
+        let id = id.trim();
+        if !id.is_empty() && !out.iter().any(|x| x == id) {
+            out.push(id.to_string());
+        }
     }
+    out
 }
 
-fn normalize_keywords(keywords: Vec<String>) -> Vec<String> {
+pub(super) fn normalize_keywords(keywords: Vec<String>) -> Vec<String> {
     let mut seen: HashSet<String> = HashSet::new();
     let mut out: Vec<String> = Vec::new();
 
@@ -380,6 +2866,15 @@ fn normalize_keywords(keywords: Vec<String>) -> Vec<String> {
     out
 }
 
+/// 权重键按关键字同样归一化（小写），只保留仍在关键字列表中且权重大于 1 的项。
+fn normalize_keyword_weights(weights: BTreeMap<String, u8>, keywords: &[String]) -> BTreeMap<String, u8> {
+    weights
+        .into_iter()
+        .map(|(kw, w)| (kw.trim().to_lowercase(), w.min(KEYWORD_WEIGHT_MAX)))
+        .filter(|(kw, w)| *w > 1 && keywords.contains(kw))
+        .collect()
+}
+
 pub(super) fn is_time_like_keyword(text: &str) -> bool {
     let compact: String = text.chars().filter(|c| !c.is_whitespace()).collect();
     if compact.is_empty() {
@@ -417,39 +2912,21 @@ fn is_year_token_zh(text: &str) -> bool {
     let Some(num) = text.strip_suffix('年') else {
         return false;
     };
-    if num.len() != 4 || !num.chars().all(|c| c.is_ascii_digit()) {
-        return false;
-    }
-    let Ok(y) = num.parse::<i32>() else {
-        return false;
-    };
-    (1..=9999).contains(&y)
+    time::parse_zh_year(num).is_some()
 }
 
 fn is_month_token_zh(text: &str) -> bool {
     let Some(num) = text.strip_suffix('月') else {
         return false;
     };
-    if num.is_empty() || !num.chars().all(|c| c.is_ascii_digit()) {
-        return false;
-    }
-    let Ok(m) = num.parse::<u32>() else {
-        return false;
-    };
-    (1..=12).contains(&m)
+    time::parse_zh_small_number(num).is_some_and(|m| (1..=12).contains(&m))
 }
 
 fn is_day_token_zh(text: &str) -> bool {
-    let Some(num) = text.strip_suffix('日') else {
+    let Some(num) = text.strip_suffix('日').or_else(|| text.strip_suffix('号')) else {
         return false;
     };
-    if num.is_empty() || !num.chars().all(|c| c.is_ascii_digit()) {
-        return false;
-    }
-    let Ok(d) = num.parse::<u32>() else {
-        return false;
-    };
-    (1..=31).contains(&d)
+    time::parse_zh_small_number(num).is_some_and(|d| (1..=31).contains(&d))
 }
 
 fn parse_ymd_zh(text: &str) -> Option<(i32, u32, u32)> {
@@ -478,7 +2955,7 @@ fn parse_ymd_zh(text: &str) -> Option<(i32, u32, u32)> {
     Some((y, m, d))
 }
 
-fn in_time_range(ts: i64, start: Option<i64>, end: Option<i64>) -> bool {
+pub(super) fn in_time_range(ts: i64, start: Option<i64>, end: Option<i64>) -> bool {
     if let Some(s) = start {
         if ts < s {
             return false;
@@ -492,7 +2969,7 @@ fn in_time_range(ts: i64, start: Option<i64>, end: Option<i64>) -> bool {
     true
 }
 
-fn max_opt_i64(a: Option<i64>, b: Option<i64>) -> Option<i64> {
+pub(super) fn max_opt_i64(a: Option<i64>, b: Option<i64>) -> Option<i64> {
     match (a, b) {
         (Some(x), Some(y)) => Some(x.max(y)),
         (Some(x), None) => Some(x),
@@ -501,7 +2978,7 @@ fn max_opt_i64(a: Option<i64>, b: Option<i64>) -> Option<i64> {
     }
 }
 
-fn min_opt_i64(a: Option<i64>, b: Option<i64>) -> Option<i64> {
+pub(super) fn min_opt_i64(a: Option<i64>, b: Option<i64>) -> Option<i64> {
     match (a, b) {
         (Some(x), Some(y)) => Some(x.min(y)),
         (Some(x), None) => Some(x),
@@ -511,23 +2988,98 @@ fn min_opt_i64(a: Option<i64>, b: Option<i64>) -> Option<i64> {
 }
 
 fn strip_prefix_case_insensitive<'a>(text: &'a str, prefix: &str) -> Option<&'a str> {
-    if text.len() < prefix.len() {
+    if text.len() < prefix.len() || !text.is_char_boundary(prefix.len()) {
         return None;
     }
     let (head, tail) = text.split_at(prefix.len());
     head.eq_ignore_ascii_case(prefix).then_some(tail)
 }
 
-fn parse_query_time_expr(query: Option<&str>) -> (Option<String>, Option<i64>, Option<i64>) {
+/// query 中的时间表达式解析结果。
+#[derive(Debug, Default)]
+pub(super) struct QueryTimeExpr {
+    /// 剥离时间表达式后剩余的文本。
+    pub text: Option<String>,
+    pub start_ts: Option<i64>,
+    pub end_ts: Option<i64>,
+    /// `workdays:`：只保留工作日。
+    workdays_only: bool,
+    /// `dow:`：允许的星期（bit0=周一 … bit6=周日）；None 表示不限。
+    weekdays: Option<u8>,
+    /// `hour>=` / `hour<=` / `hour=`：允许的小时（bit h 表示 h 点）；None 表示不限。
+    hours: Option<u32>,
+}
+
+impl QueryTimeExpr {
+    /// 不解析时间表达式，整段作为查询文本（正则模式）。
+    pub fn literal(text: Option<String>) -> Self {
+        Self {
+            text,
+            ..Default::default()
+        }
+    }
+
+    /// 工作日、星期与小时条件（时间范围另行判断）；有小时条件时，只有日期的记忆不参与匹配。
+    fn slot_allowed(&self, item: &IndexItem, calendar: &Calendar) -> bool {
+        self.slot_allowed_at(item.time_key_ts(), item.weekday, item.hour, calendar)
+    }
+
+    /// 同 `slot_allowed`，直接给出时间戳（`occurred_at ?? recorded_at`）及其星期与小时。
+    pub fn slot_allowed_at(&self, ts: i64, weekday: Option<u8>, hour: Option<u8>, calendar: &Calendar) -> bool {
+        if self.workdays_only && !calendar.is_workday_ts(ts) {
+            return false;
+        }
+        let in_mask = |value: Option<u8>, mask: Option<u32>| {
+            mask.is_none_or(|m| value.is_some_and(|v| m & (1 << v) != 0))
+        };
+        in_mask(weekday, self.weekdays.map(u32::from)) && in_mask(hour, self.hours)
+    }
+}
+
+/// 解析 query 中的时间表达式（`time…` / `workdays:` / `dow:` / `hour…`）；多个条件同时生效（取交集）。
+pub(super) fn parse_query_time_expr(query: Option<&str>) -> QueryTimeExpr {
     let Some(q) = query.map(|x| x.trim()).filter(|x| !x.is_empty()) else {
-        return (None, None, None);
+        return QueryTimeExpr::default();
     };
 
     let mut start_ts: Option<i64> = None;
     let mut end_ts: Option<i64> = None;
+    let mut workdays_only = false;
+    let mut weekdays: Option<u8> = None;
+    let mut hours: Option<u32> = None;
     let mut text_tokens: Vec<&str> = Vec::new();
 
     for token in q.split_whitespace() {
+        // dow:<星期集合>：如 dow:mon..fri、dow:sat,sun、dow:周六..周日
+        if let Some(v) = strip_prefix_case_insensitive(token, "dow:") {
+            if let Some(mask) = calendar::parse_weekday_mask(v) {
+                weekdays = Some(weekdays.unwrap_or(u8::MAX) & mask);
+                continue;
+            }
+        }
+
+        // hour>=18 / hour<=9 / hour=9..12 / hour=22..2（跨午夜）
+        if let Some(v) = strip_prefix_case_insensitive(token, "hour") {
+            let parsed = [">=", "<=", "="]
+                .into_iter()
+                .find_map(|op| v.strip_prefix(op).map(|rest| (op, rest)))
+                .and_then(|(op, rest)| calendar::parse_hour_mask(op, rest));
+            if let Some(mask) = parsed {
+                hours = Some(hours.unwrap_or(u32::MAX) & mask);
+                continue;
+            }
+        }
+
+        // workdays:<区间>：限定区间并只保留工作日（周末/节假日表中的放假日除外，调休上班日计入）
+        if let Some(v) = strip_prefix_case_insensitive(token, "workdays:") {
+            if let Some((a_ts, b_ts)) = calendar::parse_period_ts(v) {
+                start_ts = max_opt_i64(start_ts, Some(a_ts));
+                end_ts = min_opt_i64(end_ts, Some(b_ts));
+                workdays_only = true;
+                continue;
+            }
+        }
+
         if let Some(v) = strip_prefix_case_insensitive(token, "time>=") {
             if let Ok((ts, _)) = time::parse_time_to_ts_and_canonical(v, DateBoundKind::Start) {
                 start_ts = max_opt_i64(start_ts, Some(ts));
@@ -568,11 +3120,404 @@ fn parse_query_time_expr(query: Option<&str>) -> (Option<String>, Option<i64>, O
         text_tokens.push(token);
     }
 
+    // 大小写在解析布尔表达式时处理（AND/OR 需为大写，词组会统一小写化）。
     let text = text_tokens.join(" ");
-    let text = text.trim().to_lowercase();
     let text = if text.is_empty() { None } else { Some(text) };
 
-    (text, start_ts, end_ts)
+    QueryTimeExpr {
+        text,
+        start_ts,
+        end_ts,
+        workdays_only,
+        weekdays,
+        hours,
+    }
+}
+
+/// 将 namespace 整体迁移到新名称：移动目录、改写 JSONL（及 WAL）中的 namespace 字段并删除旧索引。
+///
+/// recorded_at / id / revision 等字段保持不变；带 checksum 的记录按新内容重新生成校验和。
+/// 返回改写的记录行数。调用方负责清理缓存并重新打开（以重建索引）。
+pub fn rename_namespace(root_dir: &Path, from: &str, to: &str) -> Result<usize, String> {
+    let src = StorePaths::new(root_dir, from)?;
+    let dst = StorePaths::new(root_dir, to)?;
+
+    if src.namespace == dst.namespace {
+        return Err("新旧 namespace 相同".to_string());
+    }
+    if !src.memories_path.is_file() {
+        return Err(format!("namespace 不存在：{}", src.namespace));
+    }
+    ensure_not_frozen(&src)?;
+    if dst.namespace_dir.exists() {
+        return Err(format!("目标 namespace 已存在：{}", dst.namespace));
+    }
+
+    if let Some(parent) = dst.namespace_dir.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("create namespace dir failed: {e}"))?;
+    }
+    fs::rename(&src.namespace_dir, &dst.namespace_dir)
+        .map_err(|e| format!("move namespace dir failed: {e}"))?;
+    if let Some(user_dir) = src.namespace_dir.parent() {
+        let _ = fs::remove_dir(user_dir);
+    }
+
+    let records = DataFiles::load(&dst)?.rewrite_lines(|line| rewrite_namespace_line(line, &dst.namespace))?;
+    wal::rewrite_lines(&dst.wal_path, |line| rewrite_namespace_line(line, &dst.namespace))?;
+
+    // 索引中记录了旧 namespace 且偏移已变化：直接删除，下次打开时全量重建。
+    let _ = fs::remove_file(&dst.index_path);
+    let _ = fs::remove_file(&dst.postings_path);
+
+    Ok(records)
+}
+
+/// 改写单行记录的 namespace；无法识别的行原样保留。
+fn rewrite_namespace_line(line: &str, namespace: &str) -> String {
+    if let Ok(mut t) = serde_json::from_str::<Tombstone>(line) {
+        if t.op == Tombstone::OP_DELETE {
+            t.namespace = namespace.to_string();
+            return serde_json::to_string(&t).unwrap_or_else(|_| line.to_string());
+        }
+    }
+
+    if let Ok(mut item) = serde_json::from_str::<MemoryItem>(line) {
+        // 原本校验就不通过的记录保持原样，避免把损坏内容“洗白”。
+        let resign = item.verify_checksum() == Some(true);
+        item.namespace = namespace.to_string();
+        if resign {
+            item.seal();
+        }
+        return serde_json::to_string(&item).unwrap_or_else(|_| line.to_string());
+    }
+
+    line.to_string()
+}
+
+/// 冻结标记内容。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FrozenMarker {
+    pub frozen_at: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+/// 读取冻结标记；未冻结时返回 None（标记文件损坏也视为已冻结，宁可拒写）。
+pub fn read_frozen(paths: &StorePaths) -> Option<FrozenMarker> {
+    if !paths.frozen_path.exists() {
+        return None;
+    }
+    let marker = fs::read_to_string(&paths.frozen_path)
+        .ok()
+        .and_then(|text| serde_json::from_str::<FrozenMarker>(&text).ok());
+    Some(marker.unwrap_or(FrozenMarker {
+        frozen_at: String::new(),
+        reason: None,
+    }))
+}
+
+/// 写入冻结标记（namespace 必须已存在）。
+pub fn freeze_namespace(paths: &StorePaths, reason: Option<String>) -> Result<FrozenMarker, String> {
+    if !paths.memories_path.is_file() {
+        return Err(format!("namespace 不存在：{}", paths.namespace));
+    }
+
+    let marker = FrozenMarker {
+        frozen_at: time::now_rfc3339_and_ts().0,
+        reason,
+    };
+    let json = serde_json::to_string_pretty(&marker)
+        .map_err(|e| format!("serialize frozen.json failed: {e}"))?;
+    fs::write(&paths.frozen_path, json).map_err(|e| format!("write frozen.json failed: {e}"))?;
+    Ok(marker)
+}
+
+/// 移除冻结标记；返回此前是否处于冻结状态。
+pub fn unfreeze_namespace(paths: &StorePaths) -> Result<bool, String> {
+    if !paths.frozen_path.exists() {
+        return Ok(false);
+    }
+    fs::remove_file(&paths.frozen_path).map_err(|e| format!("remove frozen.json failed: {e}"))?;
+    Ok(true)
+}
+
+/// namespace 级设置（settings.json）；未设置的项沿用服务端配置。
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NamespaceSettings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recall_max_limit: Option<usize>,
+}
+
+impl NamespaceSettings {
+    pub fn is_empty(&self) -> bool {
+        self.recall_max_limit.is_none()
+    }
+}
+
+/// 读取 namespace 设置；文件缺失时为空设置，格式错误时报错（避免静默放宽或收紧上限）。
+pub fn read_settings(paths: &StorePaths) -> Result<NamespaceSettings, String> {
+    let text = match fs::read_to_string(&paths.settings_path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(NamespaceSettings::default()),
+        Err(e) => return Err(format!("read settings.json failed: {e}")),
+    };
+    let settings: NamespaceSettings = serde_json::from_str(&text)
+        .map_err(|e| format!("namespace={} 的 settings.json 无效：{e}", paths.namespace))?;
+    if let Some(n) = settings.recall_max_limit {
+        check_recall_max_limit(n).map_err(|e| format!("namespace={} 的 settings.json 无效：{e}", paths.namespace))?;
+    }
+    Ok(settings)
+}
+
+/// 写入 namespace 设置（namespace 必须已存在）；设置为空时删除文件。
+pub fn write_settings(paths: &StorePaths, settings: &NamespaceSettings) -> Result<(), String> {
+    if !paths.memories_path.is_file() {
+        return Err(format!("namespace 不存在：{}", paths.namespace));
+    }
+    if let Some(n) = settings.recall_max_limit {
+        check_recall_max_limit(n)?;
+    }
+    if settings.is_empty() {
+        return match fs::remove_file(&paths.settings_path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(format!("remove settings.json failed: {e}")),
+            _ => Ok(()),
+        };
+    }
+    let json = serde_json::to_string_pretty(settings).map_err(|e| format!("serialize settings.json failed: {e}"))?;
+    fs::write(&paths.settings_path, json).map_err(|e| format!("write settings.json failed: {e}"))
+}
+
+/// 每次写入前检查标记文件（不缓存），外部手动放置的标记同样生效。
+pub(super) fn ensure_not_frozen(paths: &StorePaths) -> Result<(), String> {
+    match read_frozen(paths) {
+        Some(marker) => {
+            let mut msg = format!("namespace={} 已冻结（只读）", paths.namespace);
+            if !marker.frozen_at.is_empty() {
+                msg.push_str(&format!("，冻结于 {}", marker.frozen_at));
+            }
+            if let Some(reason) = marker.reason.as_deref() {
+                msg.push_str(&format!("，原因：{reason}"));
+            }
+            msg.push_str("；如需写入请先 unfreeze");
+            Err(msg)
+        }
+        None => Ok(()),
+    }
+}
+
+/// namespace 概要：当前有效记忆条数与 memories.jsonl 的最近写入时间。
+#[derive(Debug, Clone, Serialize)]
+pub struct NamespaceSummary {
+    pub namespace: String,
+    pub items: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_write: Option<String>,
+    pub frozen: bool,
+}
+
+/// 只读汇总 namespace（不创建/改写索引）：索引与数据文件一致时直接取索引中的条数，
+/// 否则扫描 JSONL 统计（同 id 多版本只计一次，已删除的不计）。
+pub fn summarize_namespace(root_dir: &Path, namespace: &str) -> Result<NamespaceSummary, String> {
+    let paths = StorePaths::new(root_dir, namespace)?;
+    let meta = fs::metadata(&paths.memories_path)
+        .map_err(|e| format!("stat memories.jsonl failed: {e}"))?;
+    let last_write = meta.modified().ok().map(time::system_time_to_rfc3339);
+    let files = DataFiles::load(&paths)?;
+    let total_len = files.total_len().map_err(|e| format!("stat memories.jsonl failed: {e}"))?;
+
+    let fresh_index = fs::read_to_string(&paths.index_path)
+        .ok()
+        .and_then(|text| serde_json::from_str::<IndexData>(&text).ok())
+        .filter(|x| x.version == INDEX_VERSION && x.indexed_up_to_offset == total_len);
+
+    let items = match fresh_index {
+        Some(index) => index.heads.len(),
+        None => count_live_items(&files)?,
+    };
+
+    Ok(NamespaceSummary {
+        frozen: paths.frozen_path.exists(),
+        namespace: paths.namespace,
+        items,
+        last_write,
+    })
+}
+
+fn count_live_items(files: &DataFiles) -> Result<usize, String> {
+    let reader = files.reader_from(0).map_err(|e| format!("open memories.jsonl failed: {e}"))?;
+    let mut live: HashSet<String> = HashSet::new();
+
+    for line in BufReader::new(reader).lines() {
+        let line = line.map_err(|e| format!("read memories.jsonl failed: {e}"))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Ok(t) = serde_json::from_str::<Tombstone>(&line) {
+            if t.op == Tombstone::OP_DELETE {
+                live.remove(&t.id);
+                continue;
+            }
+        }
+        if let Ok(item) = serde_json::from_str::<MemoryItem>(&line) {
+            live.insert(item.id);
+        }
+    }
+
+    Ok(live.len())
+}
+
+/// 经根目录 catalog.json 列出全部 namespace（按字典序），避免每次遍历整个目录树。
+///
+/// catalog 缺失/损坏或 `refresh=true` 时遍历目录全量重建；已有条目按数据文件（含已封存分段）的总大小校验，
+/// 有外部写入的重新汇总，目录已不存在的移出。catalog 有变化时回写（失败不影响结果）。
+pub fn list_namespaces(root_dir: &Path, refresh: bool) -> Result<Vec<NamespaceSummary>, String> {
+    let (mut catalog, mut dirty) = match Catalog::load(root_dir).filter(|_| !refresh) {
+        Some(catalog) => (catalog, false),
+        None => {
+            let mut catalog = Catalog::default();
+            for ns in discover_namespaces(root_dir) {
+                if let Some(entry) = catalog_entry_from_disk(root_dir, &ns)? {
+                    catalog.namespaces.insert(ns, entry);
+                }
+            }
+            (catalog, true)
+        }
+    };
+
+    let mut out = Vec::with_capacity(catalog.namespaces.len());
+    let names: Vec<String> = catalog.namespaces.keys().cloned().collect();
+    for ns in names {
+        let paths = StorePaths::new(root_dir, &ns)?;
+        let Some(bytes) = data_len(&paths) else {
+            catalog.namespaces.remove(&ns);
+            dirty = true;
+            continue;
+        };
+
+        let stale = catalog.namespaces.get(&ns).is_none_or(|x| x.bytes != bytes);
+        if stale {
+            match catalog_entry_from_disk(root_dir, &ns)? {
+                Some(entry) => catalog.namespaces.insert(ns.clone(), entry),
+                None => catalog.namespaces.remove(&ns),
+            };
+            dirty = true;
+        }
+
+        if let Some(entry) = catalog.namespaces.get(&ns) {
+            out.push(NamespaceSummary {
+                frozen: paths.frozen_path.exists(),
+                namespace: ns,
+                items: entry.items,
+                last_write: entry.last_write.clone(),
+            });
+        }
+    }
+
+    if dirty {
+        let _ = catalog.save(root_dir);
+    }
+    Ok(out)
+}
+
+/// 已知 namespace 名称列表（经 catalog）；catalog 无法使用时退回遍历目录。
+pub fn known_namespaces(root_dir: &Path) -> Vec<String> {
+    match list_namespaces(root_dir, false) {
+        Ok(list) => list.into_iter().map(|x| x.namespace).collect(),
+        Err(_) => discover_namespaces(root_dir),
+    }
+}
+
+fn catalog_entry_from_disk(root_dir: &Path, namespace: &str) -> Result<Option<CatalogEntry>, String> {
+    let paths = StorePaths::new(root_dir, namespace)?;
+    let Some(bytes) = data_len(&paths) else {
+        return Ok(None);
+    };
+    let summary = summarize_namespace(root_dir, namespace)?;
+    Ok(Some(CatalogEntry {
+        items: summary.items,
+        last_write: summary.last_write,
+        bytes,
+    }))
+}
+
+/// 数据文件（已封存分段 + memories.jsonl）的总字节数；namespace 不存在时为 None。
+fn data_len(paths: &StorePaths) -> Option<u64> {
+    DataFiles::load(paths).ok()?.total_len().ok()
+}
+
+/// 扫描存储根目录，返回所有已存在的 namespace（canonical 形式，按字典序）。
+///
+/// 仅识别 `{userId}/{projectId}` 两级目录下包含 memories.jsonl 的目录。
+pub fn discover_namespaces(root_dir: &Path) -> Vec<String> {
+    let mut out: Vec<String> = Vec::new();
+
+    let Ok(users) = fs::read_dir(root_dir) else {
+        return out;
+    };
+    for user in users.flatten() {
+        let user_path = user.path();
+        if !user_path.is_dir() {
+            continue;
+        }
+        let Ok(projects) = fs::read_dir(&user_path) else {
+            continue;
+        };
+        for project in projects.flatten() {
+            let project_path = project.path();
+            if !project_path.join("memories.jsonl").is_file() {
+                continue;
+            }
+            let (Some(u), Some(p)) = (
+                user.file_name().to_str().map(|x| x.to_string()),
+                project.file_name().to_str().map(|x| x.to_string()),
+            ) else {
+                continue;
+            };
+            out.push(format!("{u}/{p}"));
+        }
+    }
+
+    out.sort();
+    out
+}
+
+/// namespace 参数是否为模式（含 `*`）：模式不对应单个目录，需经 `match_namespaces` 展开。
+pub fn is_namespace_pattern(namespace: &str) -> bool {
+    namespace.contains('*')
+}
+
+/// 列出匹配 namespace 模式的已存在 namespace（按字典序）。
+///
+/// 模式同样为 `{userId}/{projectId}` 两段（分隔符归一化规则同 namespace），每段可含 `*` 通配符，如 `u1/*`、`*/erp`。
+pub fn match_namespaces(root_dir: &Path, pattern: &str) -> Result<Vec<String>, String> {
+    let parts: Vec<String> = pattern
+        .trim()
+        .replace('\\', "/")
+        .split('/')
+        .map(str::trim)
+        .filter(|p| !p.is_empty() && *p != "." && *p != "..")
+        .map(|p| p.split('*').map(sanitize_pattern_literal).collect::<Vec<_>>().join("*"))
+        .collect();
+    let [user, project] = parts.as_slice() else {
+        return Err(format!("namespace 模式必须为 {{userId}}/{{projectId}}（每段可含 *）：{pattern}"));
+    };
+
+    Ok(discover_namespaces(root_dir)
+        .into_iter()
+        .filter(|ns| {
+            ns.split_once('/').is_some_and(|(u, p)| {
+                index::keyword_matches_pattern(user, u) && index::keyword_matches_pattern(project, p)
+            })
+        })
+        .collect())
+}
+
+/// 模式中 `*` 之间的字面部分：与 namespace 相同的非法字符替换（不去除首尾空格与点号）。
+fn sanitize_pattern_literal(input: &str) -> String {
+    input
+        .chars()
+        .map(|ch| if matches!(ch, '/' | '\\' | ':' | '?' | '"' | '<' | '>' | '|') { '_' } else { ch })
+        .collect()
 }
 
 fn parse_namespace_components(namespace: &str) -> Result<Vec<String>, String> {
@@ -626,7 +3571,24 @@ fn sanitize_path_component(input: &str) -> String {
     }
 }
 
+/// index.json 的 (修改时间, 长度)；文件不存在或无法读取元数据时为 None。
+fn index_file_stamp(paths: &StorePaths) -> Option<(SystemTime, u64)> {
+    let meta = fs::metadata(&paths.index_path).ok()?;
+    Some((meta.modified().ok()?, meta.len()))
+}
+
+/// 读取索引头部并附上召回统计（access.json；旧版统计仍在 index.json 中时先迁移）。
 fn load_or_create_index(paths: &StorePaths) -> Result<IndexData, String> {
+    let mut index = load_index_header(paths)?;
+    if !paths.access_path.exists() && !index.access.is_empty() {
+        save_access(paths, &index.access)?;
+    } else {
+        index.access = load_access(paths);
+    }
+    Ok(index)
+}
+
+fn load_index_header(paths: &StorePaths) -> Result<IndexData, String> {
     if !paths.index_path.exists() {
         let index = IndexData::new(&paths.namespace);
         save_index(paths, &index)?;
@@ -644,6 +3606,10 @@ fn load_or_create_index(paths: &StorePaths) -> Result<IndexData, String> {
         return Ok(index);
     }
 
+    // 只读取头部（时间索引 + 关键字倒排）；全文倒排在首次查询/写入时再加载。
+    // 空索引没有需要加载的内容。
+    index.full_text_loaded = index.items.is_empty();
+
     if index.namespace != paths.namespace {
         index.namespace = paths.namespace.clone();
         save_index(paths, &index)?;
@@ -652,57 +3618,109 @@ fn load_or_create_index(paths: &StorePaths) -> Result<IndexData, String> {
     Ok(index)
 }
 
+/// 读取 access.json；文件缺失或损坏时为空（召回统计只影响排序加成与 least_recalled，不阻止打开）。
+fn load_access(paths: &StorePaths) -> BTreeMap<String, index::AccessStat> {
+    fs::read_to_string(&paths.access_path)
+        .ok()
+        .and_then(|text| serde_json::from_str(&text).ok())
+        .unwrap_or_default()
+}
+
+fn save_access(paths: &StorePaths, access: &BTreeMap<String, index::AccessStat>) -> Result<(), String> {
+    let json = serde_json::to_string(access).map_err(|e| format!("serialize access.json failed: {e}"))?;
+    replace_file(&paths.access_path, &json, "access.json")
+}
+
+/// 旧版 index.json 中的召回统计迁移到 access.json（index.json 仍可解析且 access.json 不存在时）。
+fn migrate_legacy_access(paths: &StorePaths) -> Result<(), String> {
+    if paths.access_path.exists() {
+        return Ok(());
+    }
+    let legacy = fs::read_to_string(&paths.index_path)
+        .ok()
+        .and_then(|text| serde_json::from_str::<Value>(&text).ok())
+        .and_then(|mut v| serde_json::from_value::<BTreeMap<String, index::AccessStat>>(v.get_mut("access")?.take()).ok());
+    match legacy {
+        Some(access) if !access.is_empty() => save_access(paths, &access),
+        _ => Ok(()),
+    }
+}
+
+/// 读取 postings.json；文件缺失、损坏或与头部不一致时返回 false（调用方负责重建）。
+fn load_full_text(paths: &StorePaths, index: &mut IndexData) -> bool {
+    let Ok(text) = fs::read_to_string(&paths.postings_path) else {
+        return false;
+    };
+    match serde_json::from_str::<index::FullTextPostings<'static>>(&text) {
+        Ok(postings) => index.attach_full_text(postings),
+        Err(_) => false,
+    }
+}
+
+/// 保存索引：已加载全文倒排时先写 postings.json，再写 index.json（头部落后时下次打开会因偏移不一致而重建）。
 fn save_index(paths: &StorePaths, index: &IndexData) -> Result<(), String> {
+    // 使用映射时全文倒排自映射以来未变化（写入前会先展开），postings.json 无需重写。
+    if index.full_text_loaded && index.mapped.is_none() {
+        let json = serde_json::to_string(&index.full_text_postings())
+            .map_err(|e| format!("serialize postings.json failed: {e}"))?;
+        replace_file(&paths.postings_path, &json, "postings.json")?;
+    }
+
+    save_index_header(paths, index)
+}
+
+/// 只保存 index.json（全文倒排未变化时使用）。
+fn save_index_header(paths: &StorePaths, index: &IndexData) -> Result<(), String> {
     let json = serde_json::to_string_pretty(index)
         .map_err(|e| format!("serialize index.json failed: {e}"))?;
+    replace_file(&paths.index_path, &json, "index.json")
+}
 
-    let tmp = paths.index_path.with_extension("json.tmp");
-    fs::write(&tmp, json).map_err(|e| format!("write index tmp failed: {e}"))?;
+fn replace_file(path: &Path, content: &str, name: &str) -> Result<(), String> {
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, content).map_err(|e| format!("write {name} tmp failed: {e}"))?;
 
     // Windows rename 不允许覆盖；做 best-effort 替换。
-    if let Err(e) = fs::rename(&tmp, &paths.index_path) {
-        let _ = fs::remove_file(&paths.index_path);
-        fs::rename(&tmp, &paths.index_path)
-            .map_err(|_| format!("replace index.json failed: {e}"))?;
+    if let Err(e) = fs::rename(&tmp, path) {
+        let _ = fs::remove_file(path);
+        fs::rename(&tmp, path).map_err(|_| format!("replace {name} failed: {e}"))?;
     }
 
     Ok(())
 }
 
-fn incremental_index(memories_path: &Path, index: &mut IndexData) -> io::Result<()> {
-    let mut file = File::open(memories_path)?;
+/// 从 indexed_up_to_offset 起顺序读取并索引新记录（偏移为跨分段的全局偏移）。
+fn incremental_index(files: &DataFiles, index: &mut IndexData) -> io::Result<()> {
     let start = index.indexed_up_to_offset;
-    file.seek(SeekFrom::Start(start))?;
-
-    let mut reader = BufReader::new(file);
+    let blobs = blob::blobs_dir(files.active_path());
+    let mut reader = BufReader::new(files.reader_from(start)?);
     let mut offset = start;
     let mut buf: Vec<u8> = Vec::new();
 
     loop {
         buf.clear();
         let n = reader.read_until(b'\n', &mut buf)?;
-        if n == 0 {
+        // 缺少换行的末尾是正在写入（或中断）的记录：不计入已索引范围，完整后再索引。
+        if n == 0 || !buf.ends_with(b"\n") {
             break;
         }
 
         let length = n as u32;
+        index.data_checksum = Some(index::chain_checksum(index.data_checksum.as_deref(), &buf));
         let line = buf
             .strip_suffix(b"\r\n")
             .or_else(|| buf.strip_suffix(b"\n"))
             .unwrap_or(&buf);
 
-        if let Ok(item) = serde_json::from_slice::<MemoryItem>(line) {
-            let recorded_ts = time::parse_time_to_ts_and_canonical(&item.recorded_at, DateBoundKind::Start)
-                .map(|x| x.0)
-                .unwrap_or(0);
-            let occurred_ts = item
-                .occurred_at
-                .as_deref()
-                .and_then(|s| time::parse_time_to_ts_and_canonical(s, DateBoundKind::Start).ok())
-                .map(|x| x.0);
-
-            let keywords = normalize_keywords(item.keywords.clone());
-            index.add_memory_item(&item, offset, length, recorded_ts, occurred_ts, keywords);
+        if let Ok(mut item) = serde_json::from_slice::<MemoryItem>(line) {
+            // blob 缺失/损坏时仍按引用形式建索引（diary 不参与全文检索），读取时再报错。
+            let _ = blob::resolve(&blobs, &mut item);
+            index_memory_item(index, &item, offset, length);
+        } else if let Ok(t) = serde_json::from_slice::<Tombstone>(line) {
+            if t.op == Tombstone::OP_DELETE {
+                index.remove_by_id(&t.id);
+                index.access.remove(&t.id);
+            }
         }
 
         offset += length as u64;
@@ -712,17 +3730,56 @@ fn incremental_index(memories_path: &Path, index: &mut IndexData) -> io::Result<
     Ok(())
 }
 
-fn load_item_by_index(memories_path: &Path, index: &IndexData, idx: u32) -> Result<MemoryItem, String> {
+/// 重新计算 `[0, end)` 的链式校验和（与 `incremental_index` 相同的分行方式）。
+fn region_checksum(files: &DataFiles, end: u64) -> io::Result<Option<String>> {
+    let mut reader = BufReader::new(Read::take(files.reader_from(0)?, end));
+    let mut checksum: Option<String> = None;
+    let mut buf: Vec<u8> = Vec::new();
+    loop {
+        buf.clear();
+        if reader.read_until(b'\n', &mut buf)? == 0 {
+            break;
+        }
+        checksum = Some(index::chain_checksum(checksum.as_deref(), &buf));
+    }
+    Ok(checksum)
+}
+
+fn index_memory_item(index: &mut IndexData, item: &MemoryItem, offset: u64, length: u32) {
+    let recorded_ts = time::parse_time_to_ts_and_canonical(&item.recorded_at, DateBoundKind::Start)
+        .map(|x| x.0)
+        .unwrap_or(0);
+    let occurred_ts = item
+        .occurred_at
+        .as_deref()
+        .and_then(|s| time::parse_time_to_ts_and_canonical(s, DateBoundKind::Start).ok())
+        .map(|x| x.0);
+
+    let keywords = normalize_keywords(item.keywords.clone());
+    index.add_memory_item(item, offset, length, recorded_ts, occurred_ts, keywords);
+}
+
+/// 按索引读取记录并校验 checksum；内容与校验和不一致时报错，避免静默返回被篡改/损坏的数据。
+/// diary 为 blob 引用时一并还原（校验 blob 哈希）。
+fn load_item_by_index(files: &DataFiles, index: &IndexData, idx: u32) -> Result<MemoryItem, String> {
+    let mut item = read_item_by_index(files, index, idx)?;
+    if item.verify_checksum() == Some(false) {
+        return Err(format!(
+            "记忆校验失败：id={} 内容与 checksum 不一致（可能被外部修改或损坏）",
+            item.id
+        ));
+    }
+    blob::resolve(&blob::blobs_dir(files.active_path()), &mut item)?;
+    Ok(item)
+}
+
+fn read_item_by_index(files: &DataFiles, index: &IndexData, idx: u32) -> Result<MemoryItem, String> {
     let Some(entry) = index.items.get(idx as usize) else {
         return Err("索引越界".to_string());
     };
 
-    let mut file = File::open(memories_path).map_err(|e| format!("open memories.jsonl failed: {e}"))?;
-    file.seek(SeekFrom::Start(entry.offset))
-        .map_err(|e| format!("seek memories.jsonl failed: {e}"))?;
-
-    let mut buf = vec![0u8; entry.length as usize];
-    file.read_exact(&mut buf)
+    let buf = files
+        .read_at(entry.offset, entry.length)
         .map_err(|e| format!("read memories.jsonl failed: {e}"))?;
 
     let line = buf
@@ -734,4 +3791,5 @@ fn load_item_by_index(memories_path: &Path, index: &IndexData, idx: u32) -> Resu
 }
 
 #[cfg(test)]
+#[allow(clippy::err_expect)]
 mod tests;
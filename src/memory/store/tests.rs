@@ -1,4 +1,7 @@
 use super::*;
+use crate::memory::lang::Lang;
+use crate::memory::model::{MemoryKind, MemorySource};
+use crate::memory::rotation::segment_file_name;
 
 #[test]
 fn namespace_dir_should_prevent_traversal() {
@@ -54,7 +57,8 @@ fn remember_and_recall_by_keyword_and_time() {
             diary: "今天我们推进了项目里程碑。".to_string(),
             occurred_at: None,
             importance: Some(3),
-            source: Some("test".to_string()),
+            source: Some(MemorySource::label("test")),
+            ..Default::default()
         })
         .unwrap();
 
@@ -67,6 +71,7 @@ fn remember_and_recall_by_keyword_and_time() {
             occurred_at: Some("2025-05-01".to_string()),
             importance: Some(5),
             source: None,
+            ..Default::default()
         })
         .unwrap();
 
@@ -79,6 +84,7 @@ fn remember_and_recall_by_keyword_and_time() {
             query: None,
             limit: 20,
             include_diary: false,
+            ..Default::default()
         })
         .unwrap();
 
@@ -95,6 +101,7 @@ fn remember_and_recall_by_keyword_and_time() {
             query: None,
             limit: 20,
             include_diary: true,
+            ..Default::default()
         })
         .unwrap();
 
@@ -120,6 +127,7 @@ fn invalid_jsonl_line_should_be_skipped() {
             occurred_at: None,
             importance: None,
             source: None,
+            ..Default::default()
         })
         .unwrap();
 
@@ -144,6 +152,7 @@ fn invalid_jsonl_line_should_be_skipped() {
             query: None,
             limit: 20,
             include_diary: false,
+            ..Default::default()
         })
         .unwrap();
 
@@ -167,6 +176,7 @@ fn remember_empty_keywords_should_error() {
             occurred_at: None,
             importance: None,
             source: None,
+            ..Default::default()
         })
         .err()
         .expect("should error");
@@ -191,6 +201,7 @@ fn recall_query_time_expr_should_filter() {
             occurred_at: Some("2025-04-01".to_string()),
             importance: None,
             source: None,
+            ..Default::default()
         })
         .unwrap();
 
@@ -203,6 +214,7 @@ fn recall_query_time_expr_should_filter() {
             occurred_at: Some("2025-05-01".to_string()),
             importance: None,
             source: None,
+            ..Default::default()
         })
         .unwrap();
 
@@ -215,6 +227,7 @@ fn recall_query_time_expr_should_filter() {
             query: Some("time>=2025-05-01".to_string()),
             limit: 20,
             include_diary: false,
+            ..Default::default()
         })
         .unwrap();
 
@@ -241,6 +254,7 @@ fn recall_query_time_range_expr_should_filter() {
                 occurred_at: Some(occurred_at.to_string()),
                 importance: None,
                 source: None,
+                ..Default::default()
             })
             .unwrap();
     }
@@ -254,6 +268,7 @@ fn recall_query_time_range_expr_should_filter() {
             query: Some("time=2025-02-01..2025-02-28".to_string()),
             limit: 20,
             include_diary: false,
+            ..Default::default()
         })
         .unwrap();
 
@@ -281,18 +296,26 @@ fn remember_should_drop_time_like_keywords() {
                 "20日".to_string(),
                 "2025-08-20T10:00:00Z".to_string(),
                 "2025-08-20t10:00:00z".to_string(),
+                "二〇二五年八月".to_string(),
+                "八月".to_string(),
+                "二十日".to_string(),
+                "昨天".to_string(),
+                "上周".to_string(),
+                "2025-W34".to_string(),
+                "第34周".to_string(),
             ],
             slice: "slice".to_string(),
             diary: "diary".to_string(),
             occurred_at: None,
             importance: None,
             source: None,
+            ..Default::default()
         })
         .unwrap();
 
     assert_eq!(recorded.keywords, vec!["项目".to_string()]);
 
-    let keywords = state.list_keywords().unwrap();
+    let keywords = state.list_keywords(KeywordSort::Length).unwrap();
     assert_eq!(keywords, vec!["项目".to_string()]);
 }
 
@@ -313,6 +336,7 @@ fn remember_only_time_keywords_should_error() {
             occurred_at: None,
             importance: None,
             source: None,
+            ..Default::default()
         })
         .err()
         .expect("should error");
@@ -337,6 +361,7 @@ fn recall_start_end_should_accept_lowercase_rfc3339() {
             occurred_at: Some("2025-05-01".to_string()),
             importance: None,
             source: None,
+            ..Default::default()
         })
         .unwrap();
 
@@ -349,6 +374,7 @@ fn recall_start_end_should_accept_lowercase_rfc3339() {
             query: None,
             limit: 20,
             include_diary: false,
+            ..Default::default()
         })
         .unwrap();
 
@@ -373,9 +399,2601 @@ fn remember_importance_out_of_range_should_error() {
             occurred_at: None,
             importance: Some(6),
             source: None,
+            ..Default::default()
         })
         .err()
         .expect("should error");
 
     assert!(err.contains("importance"), "unexpected err: {err}");
 }
+
+#[test]
+fn discover_namespaces_should_list_two_level_dirs() {
+    let temp = tempfile::tempdir().unwrap();
+    let root = temp.path();
+
+    for ns in ["u2/p1", "u1/p2", "u1/p1"] {
+        NamespaceState::open(StorePaths::new(root, ns).unwrap()).unwrap();
+    }
+    fs::create_dir_all(root.join("u3")).unwrap();
+    fs::write(root.join("store_format.json"), "{}").unwrap();
+
+    assert_eq!(
+        discover_namespaces(root),
+        vec!["u1/p1".to_string(), "u1/p2".to_string(), "u2/p1".to_string()]
+    );
+}
+
+#[test]
+fn match_namespaces_should_expand_wildcards_per_segment() {
+    let temp = tempfile::tempdir().unwrap();
+    let root = temp.path();
+    for ns in ["u1/erp", "u1/crm", "u2/erp", "u10/erp"] {
+        NamespaceState::open(StorePaths::new(root, ns).unwrap()).unwrap();
+    }
+
+    assert_eq!(match_namespaces(root, "u1/*").unwrap(), vec!["u1/crm", "u1/erp"]);
+    assert_eq!(match_namespaces(root, r" \*\erp/ ").unwrap(), vec!["u1/erp", "u10/erp", "u2/erp"]);
+    assert_eq!(match_namespaces(root, "u1*/e*p").unwrap(), vec!["u1/erp", "u10/erp"]);
+    assert_eq!(match_namespaces(root, "u2/crm").unwrap(), Vec::<String>::new());
+    assert!(match_namespaces(root, "*").is_err());
+}
+
+#[test]
+fn recall_stats_should_batch_into_access_json_without_rewriting_index() {
+    let temp = tempfile::tempdir().unwrap();
+    let paths = StorePaths::new(temp.path(), "u1/p1").unwrap();
+    let mut state = NamespaceState::open(paths.clone()).unwrap();
+    let recorded = state
+        .append_memory(RememberArgs {
+            namespace: "u1/p1".to_string(),
+            keywords: vec!["k".to_string()],
+            slice: "slice".to_string(),
+            diary: "diary".to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+    let stamp = index_file_stamp(&paths);
+
+    // 未到批量间隔：只记在内存中。
+    state.record_recalled(std::slice::from_ref(&recorded.id));
+    assert!(!paths.access_path.exists());
+    for _ in 1..ACCESS_SAVE_EVERY {
+        state.record_recalled(std::slice::from_ref(&recorded.id));
+    }
+    assert_eq!(load_access(&paths).get(&recorded.id).map(|x| x.count), Some(ACCESS_SAVE_EVERY as u64));
+    // 召回统计不改写 index.json：其他进程不会因此重新加载索引。
+    assert_eq!(index_file_stamp(&paths), stamp);
+
+    // 临时文件路径被目录占用：写入失败，增量保留在内存中，flush 时补写。
+    let tmp = paths.access_path.with_extension("json.tmp");
+    fs::create_dir(&tmp).unwrap();
+    state.record_recalled(std::slice::from_ref(&recorded.id));
+    assert!(state.flush().is_err());
+    fs::remove_dir(&tmp).unwrap();
+    state.flush().unwrap();
+    let expected = ACCESS_SAVE_EVERY as u64 + 1;
+    assert_eq!(load_or_create_index(&paths).unwrap().access.get(&recorded.id).map(|x| x.count), Some(expected));
+
+    // 两个进程各自的增量合并累加，不互相覆盖；关闭 namespace 时补写。
+    let mut other = NamespaceState::open(paths.clone()).unwrap();
+    other.record_recalled(std::slice::from_ref(&recorded.id));
+    state.record_recalled(std::slice::from_ref(&recorded.id));
+    drop(other);
+    drop(state);
+    assert_eq!(load_access(&paths).get(&recorded.id).map(|x| x.count), Some(expected + 2));
+    assert_eq!(index_file_stamp(&paths), stamp);
+}
+
+#[test]
+fn legacy_access_stats_in_index_json_should_migrate_to_access_json() {
+    let temp = tempfile::tempdir().unwrap();
+    let paths = StorePaths::new(temp.path(), "u1/p1").unwrap();
+    let mut index = IndexData::new("u1/p1");
+    index.access.insert("a".to_string(), index::AccessStat { count: 3, last_recalled_at: "2025-01-01T00:00:00Z".to_string() });
+    fs::create_dir_all(&paths.namespace_dir).unwrap();
+    let mut legacy = serde_json::to_value(&index).unwrap();
+    legacy["access"] = serde_json::to_value(&index.access).unwrap();
+    fs::write(&paths.index_path, legacy.to_string()).unwrap();
+
+    assert_eq!(load_or_create_index(&paths).unwrap().access.get("a").map(|x| x.count), Some(3));
+    assert_eq!(load_access(&paths).get("a").map(|x| x.count), Some(3));
+}
+
+#[test]
+fn reindex_should_rebuild_from_data_and_keep_readable_access_stats() {
+    let temp = tempfile::tempdir().unwrap();
+    let paths = StorePaths::new(temp.path(), "u1/p1").unwrap();
+    let mut state = NamespaceState::open(paths.clone()).unwrap();
+    let mut ids = Vec::new();
+    for slice in ["a", "b"] {
+        let recorded = state
+            .append_memory(RememberArgs {
+                namespace: "u1/p1".to_string(),
+                keywords: vec!["k".to_string()],
+                slice: slice.to_string(),
+                diary: "diary".to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+        ids.push(recorded.id);
+    }
+    state.record_recalled(&ids[..1]);
+    drop(state);
+
+    assert_eq!(NamespaceState::reindex(paths.clone(), StoreOptions::default()).unwrap(), 2);
+    let index = load_or_create_index(&paths).unwrap();
+    assert_eq!(index.access.get(&ids[0]).map(|x| x.count), Some(1));
+
+    // 索引损坏时无法正常打开；reindex 直接丢弃后重建，单独存放的召回统计不受影响。
+    fs::write(&paths.index_path, "{ not json").unwrap();
+    assert!(NamespaceState::open(paths.clone()).is_err());
+    assert_eq!(NamespaceState::reindex(paths.clone(), StoreOptions::default()).unwrap(), 2);
+    assert_eq!(load_or_create_index(&paths).unwrap().access.get(&ids[0]).map(|x| x.count), Some(1));
+
+    let missing = StorePaths::new(temp.path(), "u1/none").unwrap();
+    assert!(NamespaceState::reindex(missing, StoreOptions::default()).is_err());
+}
+
+#[test]
+fn rebuild_index_should_restore_postings() {
+    let temp = tempfile::tempdir().unwrap();
+    let root = temp.path();
+
+    let paths = StorePaths::new(root, "u1/p1").unwrap();
+    let mut state = NamespaceState::open(paths.clone()).unwrap();
+    state
+        .append_memory(RememberArgs {
+            namespace: "u1/p1".to_string(),
+            keywords: vec!["k".to_string()],
+            slice: "slice".to_string(),
+            diary: "diary".to_string(),
+            occurred_at: None,
+            importance: None,
+            source: None,
+            ..Default::default()
+        })
+        .unwrap();
+
+    fs::write(&paths.index_path, "{}").unwrap();
+    let mut state = NamespaceState {
+        files: DataFiles::load(&paths).unwrap(),
+        paths: paths.clone(),
+        index: IndexData::new("u1/p1"),
+        options: StoreOptions::default(),
+        heat: KeywordHeat::default(),
+        embeddings: None,
+        index_stamp: None,
+        index_dirty: false,
+        access_pending: BTreeMap::new(),
+        pending_recalls: 0,
+    };
+    state.rebuild_index().unwrap();
+
+    let reopened = load_or_create_index(&paths).unwrap();
+    assert_eq!(reopened.items.len(), 1);
+    assert_eq!(reopened.keyword_postings.get("k"), Some(&vec![0]));
+}
+
+#[test]
+fn transaction_update_and_forget_should_survive_reindex() {
+    let temp = tempfile::tempdir().unwrap();
+    let root = temp.path();
+
+    let paths = StorePaths::new(root, "u1/p1").unwrap();
+    let mut state = NamespaceState::open(paths.clone()).unwrap();
+
+    let kept = state
+        .append_memory(RememberArgs {
+            namespace: "u1/p1".to_string(),
+            keywords: vec!["old".to_string()],
+            slice: "kept".to_string(),
+            diary: "diary".to_string(),
+            occurred_at: None,
+            importance: None,
+            source: None,
+            ..Default::default()
+        })
+        .unwrap();
+    let dropped = state
+        .append_memory(RememberArgs {
+            namespace: "u1/p1".to_string(),
+            keywords: vec!["old".to_string()],
+            slice: "dropped".to_string(),
+            diary: "diary".to_string(),
+            occurred_at: None,
+            importance: None,
+            source: None,
+            ..Default::default()
+        })
+        .unwrap();
+
+    let outcomes = state
+        .apply_transaction(vec![
+            TransactionStep::Update(UpdateArgs {
+                id: kept.id.clone(),
+                revision: kept.revision,
+                keywords: Some(vec!["new".to_string()]),
+                slice: Some("kept v2".to_string()),
+                ..Default::default()
+            }),
+            TransactionStep::Forget {
+                id: dropped.id.clone(),
+                revision: dropped.revision,
+            },
+        ])
+        .unwrap();
+    assert_eq!(outcomes.len(), 2);
+
+    let mut reopened = NamespaceState::open(paths).unwrap();
+    reopened.rebuild_index().unwrap();
+    assert_eq!(reopened.list_keywords(KeywordSort::Length).unwrap(), vec!["new".to_string()]);
+
+    let recalled = reopened
+        .recall(RecallArgs {
+            namespace: "u1/p1".to_string(),
+            keywords: vec![],
+            start: None,
+            end: None,
+            query: None,
+            limit: 20,
+            include_diary: false,
+            ..Default::default()
+        })
+        .unwrap();
+    assert_eq!(recalled.items.len(), 1);
+    assert_eq!(recalled.items[0].id, kept.id);
+    assert_eq!(recalled.items[0].slice, "kept v2");
+    assert_eq!(recalled.items[0].revision, 2);
+}
+
+#[test]
+fn failed_transaction_should_not_write_anything() {
+    let temp = tempfile::tempdir().unwrap();
+    let root = temp.path();
+
+    let paths = StorePaths::new(root, "u1/p1").unwrap();
+    let mut state = NamespaceState::open(paths.clone()).unwrap();
+
+    let err = state
+        .apply_transaction(vec![
+            TransactionStep::Remember(RememberArgs {
+                namespace: "u1/p1".to_string(),
+                keywords: vec!["k".to_string()],
+                slice: "slice".to_string(),
+                diary: "diary".to_string(),
+                occurred_at: None,
+                importance: None,
+                source: None,
+                ..Default::default()
+            }),
+            TransactionStep::Update(UpdateArgs {
+                id: "missing".to_string(),
+                revision: 1,
+                ..Default::default()
+            }),
+        ])
+        .expect_err("should error");
+    assert!(err.contains("steps[1]"), "unexpected err: {err}");
+
+    assert_eq!(fs::metadata(&paths.memories_path).unwrap().len(), 0);
+}
+
+#[test]
+fn stale_revision_should_conflict() {
+    let temp = tempfile::tempdir().unwrap();
+    let root = temp.path();
+
+    let paths = StorePaths::new(root, "u1/p1").unwrap();
+    let mut state = NamespaceState::open(paths).unwrap();
+
+    let recorded = state
+        .append_memory(RememberArgs {
+            namespace: "u1/p1".to_string(),
+            keywords: vec!["k".to_string()],
+            slice: "v1".to_string(),
+            diary: "diary".to_string(),
+            occurred_at: None,
+            importance: None,
+            source: None,
+            ..Default::default()
+        })
+        .unwrap();
+    assert_eq!(recorded.revision, 1);
+
+    let update = |slice: &str| {
+        TransactionStep::Update(UpdateArgs {
+            id: recorded.id.clone(),
+            revision: recorded.revision,
+            slice: Some(slice.to_string()),
+            ..Default::default()
+        })
+    };
+
+    // 第一个调用方基于 revision=1 修改成功；第二个调用方仍持有 revision=1，应被拒绝。
+    state.apply_transaction(vec![update("agent a")]).unwrap();
+    let err = state
+        .apply_transaction(vec![update("agent b")])
+        .expect_err("should conflict");
+    assert!(err.contains("revision 冲突"), "unexpected err: {err}");
+
+    let err = state
+        .apply_transaction(vec![TransactionStep::Forget {
+            id: recorded.id.clone(),
+            revision: 1,
+        }])
+        .expect_err("should conflict");
+    assert!(err.contains("revision 冲突"), "unexpected err: {err}");
+}
+
+#[test]
+fn restore_to_should_rewind_to_point_in_time() {
+    let temp = tempfile::tempdir().unwrap();
+    let root = temp.path();
+
+    let paths = StorePaths::new(root, "u1/p1").unwrap();
+    let mut state =
+        NamespaceState::open_with(paths.clone(), StoreOptions {
+            wal: true,
+            ..Default::default()
+        }).unwrap();
+
+    let remember = |slice: &str| RememberArgs {
+        namespace: "u1/p1".to_string(),
+        keywords: vec!["k".to_string()],
+        slice: slice.to_string(),
+        diary: "diary".to_string(),
+        occurred_at: None,
+        importance: None,
+        source: None,
+        ..Default::default()
+    };
+
+    state.append_memory(remember("first")).unwrap();
+
+    // 手动把 WAL 中已有条目的时间回拨，模拟“之后”才发生的第二次写入。
+    let wal_text = fs::read_to_string(&paths.wal_path).unwrap();
+    let rewound: Vec<String> = wal_text
+        .lines()
+        .map(|l| {
+            let mut e: wal::WalEntry = serde_json::from_str(l).unwrap();
+            e.ts_unix -= 100;
+            serde_json::to_string(&e).unwrap()
+        })
+        .collect();
+    fs::write(&paths.wal_path, rewound.join("\n") + "\n").unwrap();
+    let (_, now_ts) = time::now_rfc3339_and_ts();
+
+    state.append_memory(remember("second")).unwrap();
+
+    let restored = state.restore_to(now_ts - 50).unwrap();
+    assert_eq!(restored, 1);
+
+    let recalled = state
+        .recall(RecallArgs {
+            namespace: "u1/p1".to_string(),
+            keywords: vec!["k".to_string()],
+            start: None,
+            end: None,
+            query: None,
+            limit: 20,
+            include_diary: false,
+            ..Default::default()
+        })
+        .unwrap();
+    assert_eq!(recalled.items.len(), 1);
+    assert_eq!(recalled.items[0].slice, "first");
+}
+
+#[test]
+fn remember_should_accept_chinese_occurred_at() {
+    let temp = tempfile::tempdir().unwrap();
+    let root = temp.path();
+
+    let paths = StorePaths::new(root, "u1/p1").unwrap();
+    let mut state = NamespaceState::open(paths).unwrap();
+
+    let recorded = state
+        .append_memory(RememberArgs {
+            namespace: "u1/p1".to_string(),
+            keywords: vec!["k".to_string()],
+            slice: "slice".to_string(),
+            diary: "diary".to_string(),
+            occurred_at: Some("二〇二五年八月二十日".to_string()),
+            importance: None,
+            source: None,
+            ..Default::default()
+        })
+        .unwrap();
+    assert_eq!(recorded.occurred_at.as_deref(), Some("2025-08-20"));
+
+    let recalled = state
+        .recall(RecallArgs {
+            namespace: "u1/p1".to_string(),
+            keywords: vec![],
+            start: None,
+            end: None,
+            query: Some("time=二〇二五年八月".to_string()),
+            limit: 20,
+            include_diary: false,
+            ..Default::default()
+        })
+        .unwrap();
+    assert_eq!(recalled.items.len(), 1);
+}
+
+#[test]
+fn recall_workdays_expr_should_skip_weekends_and_holidays() {
+    let temp = tempfile::tempdir().unwrap();
+    let root = temp.path();
+
+    let holidays = root.join("holidays.json");
+    fs::write(&holidays, r#"{"holidays":["2025-05-01"],"workdays":["2025-04-27"]}"#).unwrap();
+    let options = StoreOptions {
+        calendar: Arc::new(Calendar::load(&holidays).unwrap()),
+        ..Default::default()
+    };
+
+    let paths = StorePaths::new(root, "u1/p1").unwrap();
+    let mut state = NamespaceState::open_with(paths, options).unwrap();
+
+    // 04-27 调休上班（周日）、05-01 节假日、05-03 周六、05-06 周二
+    for day in ["2025-04-27", "2025-05-01", "2025-05-03", "2025-05-06"] {
+        state
+            .append_memory(RememberArgs {
+                namespace: "u1/p1".to_string(),
+                keywords: vec!["k".to_string()],
+                slice: day.to_string(),
+                diary: "diary".to_string(),
+                occurred_at: Some(day.to_string()),
+                importance: None,
+                source: None,
+                ..Default::default()
+            })
+            .unwrap();
+    }
+
+    let recall = |state: &mut NamespaceState, keywords: Vec<String>, query: &str| {
+        let mut slices: Vec<String> = state
+            .recall(RecallArgs {
+                namespace: "u1/p1".to_string(),
+                keywords,
+                query: Some(query.to_string()),
+                limit: 20,
+                ..Default::default()
+            })
+            .unwrap()
+            .items
+            .into_iter()
+            .map(|x| x.slice)
+            .collect();
+        slices.sort();
+        slices
+    };
+
+    assert_eq!(recall(&mut state, vec![], "workdays:2025-05"), vec!["2025-05-06"]);
+    assert_eq!(
+        recall(&mut state, vec!["k".to_string()], "workdays:2025-04-27"),
+        vec!["2025-04-27"]
+    );
+}
+
+#[test]
+fn tampered_item_should_fail_checksum() {
+    let temp = tempfile::tempdir().unwrap();
+    let root = temp.path();
+
+    let paths = StorePaths::new(root, "u1/p1").unwrap();
+    let mut state = NamespaceState::open(paths.clone()).unwrap();
+
+    let mut ids = Vec::new();
+    for slice in ["slice-a", "slice-b"] {
+        let item = state
+            .append_memory(RememberArgs {
+                namespace: "u1/p1".to_string(),
+                keywords: vec!["k".to_string()],
+                slice: slice.to_string(),
+                diary: "diary".to_string(),
+                occurred_at: None,
+                importance: None,
+                source: None,
+                ..Default::default()
+            })
+            .unwrap();
+        ids.push(item.id);
+    }
+
+    // 等长替换，偏移不变，仅内容被篡改
+    let text = fs::read_to_string(&paths.memories_path).unwrap();
+    fs::write(&paths.memories_path, text.replace("slice-a", "slice-x")).unwrap();
+
+    let err = state.get(&ids[0]).expect_err("tampered");
+    assert!(err.contains("记忆校验失败"), "unexpected err: {err}");
+    let untouched = state.get(&ids[1]).expect("untouched item");
+    assert!(untouched
+        .checksum
+        .as_deref()
+        .unwrap()
+        .starts_with(MemoryItem::CHECKSUM_PREFIX));
+
+    let report = state.checksum_report().unwrap();
+    assert_eq!(report.total, 2);
+    assert_eq!(report.verified, 1);
+    assert_eq!(report.mismatched, vec![ids[0].clone()]);
+}
+
+#[test]
+fn recall_should_skip_unreadable_items_with_warnings() {
+    let temp = tempfile::tempdir().unwrap();
+    let root = temp.path();
+
+    let paths = StorePaths::new(root, "u1/p1").unwrap();
+    let mut state = NamespaceState::open(paths.clone()).unwrap();
+
+    for slice in ["slice-a", "slice-b"] {
+        state
+            .append_memory(RememberArgs {
+                namespace: "u1/p1".to_string(),
+                keywords: vec!["k".to_string()],
+                slice: slice.to_string(),
+                diary: "diary".to_string(),
+                occurred_at: None,
+                importance: None,
+                source: None,
+                ..Default::default()
+            })
+            .unwrap();
+    }
+
+    // 把第一行整体替换成等长的非法内容（偏移不变，索引仍指向该行）
+    let text = fs::read_to_string(&paths.memories_path).unwrap();
+    let first_len = text.find('\n').unwrap();
+    let corrupted = format!("{}{}", "#".repeat(first_len), &text[first_len..]);
+    fs::write(&paths.memories_path, corrupted).unwrap();
+
+    for keywords in [vec![], vec!["k".to_string()]] {
+        let result = state
+            .recall(RecallArgs {
+                namespace: "u1/p1".to_string(),
+                keywords,
+                limit: 20,
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(result.items.len(), 1);
+        assert_eq!(result.items[0].slice, "slice-b");
+        assert_eq!(result.warnings.len(), 1);
+        assert!(result.render_text_summary(Lang::Zh).contains("无法读取"));
+    }
+}
+
+#[test]
+fn recall_offset_should_page_without_overlap() {
+    let temp = tempfile::tempdir().unwrap();
+    let paths = StorePaths::new(temp.path(), "u1/p1").unwrap();
+    let mut state = NamespaceState::open(paths).unwrap();
+
+    for day in 1..=5 {
+        state
+            .append_memory(RememberArgs {
+                namespace: "u1/p1".to_string(),
+                keywords: vec!["k".to_string()],
+                slice: format!("day-{day}"),
+                diary: "diary".to_string(),
+                occurred_at: Some(format!("2025-01-0{day}")),
+                importance: None,
+                source: None,
+                ..Default::default()
+            })
+            .unwrap();
+    }
+
+    for keywords in [vec![], vec!["k".to_string()]] {
+        let page = |state: &mut NamespaceState, offset: usize| {
+            state
+                .recall(RecallArgs {
+                    namespace: "u1/p1".to_string(),
+                    keywords: keywords.clone(),
+                    limit: 2,
+                    offset,
+                    ..Default::default()
+                })
+                .unwrap()
+        };
+
+        let first = page(&mut state, 0);
+        let slices: Vec<&str> = first.items.iter().map(|x| x.slice.as_str()).collect();
+        assert_eq!(slices, vec!["day-5", "day-4"]);
+        assert!(first.has_more);
+
+        let last = page(&mut state, 4);
+        let slices: Vec<&str> = last.items.iter().map(|x| x.slice.as_str()).collect();
+        assert_eq!(slices, vec!["day-1"]);
+        assert_eq!(last.offset, 4);
+        assert!(!last.has_more);
+
+        assert!(page(&mut state, 10).items.is_empty());
+    }
+}
+
+#[test]
+fn recall_query_should_prune_candidates_by_trigram_before_reading() {
+    let temp = tempfile::tempdir().unwrap();
+    let paths = StorePaths::new(temp.path(), "u1/p1").unwrap();
+    let mut state = NamespaceState::open(paths.clone()).unwrap();
+
+    for slice in ["alpha report", "Beta 周报"] {
+        state
+            .append_memory(RememberArgs {
+                namespace: "u1/p1".to_string(),
+                keywords: vec!["k".to_string()],
+                slice: slice.to_string(),
+                diary: "diary".to_string(),
+                occurred_at: None,
+                importance: None,
+                source: None,
+                ..Default::default()
+            })
+            .unwrap();
+    }
+    assert!(state.index.trigram_postings.contains_key("a 周"));
+
+    // 损坏第一行：被 trigram 裁剪掉的条目不会被读取，因此不产生告警。
+    let text = fs::read_to_string(&paths.memories_path).unwrap();
+    let first_len = text.find('\n').unwrap();
+    let corrupted = format!("{}{}", "#".repeat(first_len), &text[first_len..]);
+    fs::write(&paths.memories_path, corrupted).unwrap();
+
+    for keywords in [vec![], vec!["k".to_string()]] {
+        let recall = |state: &mut NamespaceState, query: &str| {
+            state
+                .recall(RecallArgs {
+                    namespace: "u1/p1".to_string(),
+                    keywords: keywords.clone(),
+                    query: Some(query.to_string()),
+                    limit: 20,
+                    ..Default::default()
+                })
+                .unwrap()
+        };
+
+        let result = recall(&mut state, "BETA 周");
+        assert_eq!(result.items.len(), 1);
+        assert_eq!(result.items[0].slice, "Beta 周报");
+        assert!(result.warnings.is_empty(), "{:?}", result.warnings);
+
+        assert!(recall(&mut state, "gamma").items.is_empty());
+
+        // 不足一个 trigram 时无法裁剪，回退逐条匹配（会读到损坏行）。
+        let result = recall(&mut state, "周报");
+        assert_eq!(result.items.len(), 1);
+        assert_eq!(result.warnings.len(), 1);
+    }
+}
+
+#[test]
+fn recall_should_match_segmented_chinese_words_in_slice_and_diary() {
+    let temp = tempfile::tempdir().unwrap();
+    let paths = StorePaths::new(temp.path(), "u1/p1").unwrap();
+    let mut state = NamespaceState::open(paths).unwrap();
+
+    let text_hit = state
+        .append_memory(RememberArgs {
+            namespace: "u1/p1".to_string(),
+            keywords: vec!["erp".to_string()],
+            slice: "我们推进了ERP项目的上线计划".to_string(),
+            diary: "今天讨论了数据迁移方案。".to_string(),
+            occurred_at: None,
+            importance: None,
+            source: None,
+            ..Default::default()
+        })
+        .unwrap();
+    let keyword_hit = state
+        .append_memory(RememberArgs {
+            namespace: "u1/p1".to_string(),
+            keywords: vec!["上线".to_string()],
+            slice: "发布窗口".to_string(),
+            diary: "diary".to_string(),
+            occurred_at: None,
+            importance: None,
+            source: None,
+            ..Default::default()
+        })
+        .unwrap();
+
+    let recall = |state: &mut NamespaceState, keywords: Vec<&str>, query: Option<&str>| {
+        state
+            .recall(RecallArgs {
+                namespace: "u1/p1".to_string(),
+                keywords: keywords.into_iter().map(|x| x.to_string()).collect(),
+                query: query.map(|x| x.to_string()),
+                limit: 20,
+                ..Default::default()
+            })
+            .unwrap()
+            .items
+            .into_iter()
+            .map(|x| x.id)
+            .collect::<Vec<_>>()
+    };
+
+    // 关键字同时命中正文分词：显式关键字命中排在前面。
+    assert_eq!(
+        recall(&mut state, vec!["上线"], None),
+        vec![keyword_hit.id.clone(), text_hit.id.clone()]
+    );
+    assert_eq!(recall(&mut state, vec!["迁移"], None), vec![text_hit.id.clone()]);
+
+    // query 的分词不要求相邻/同序。
+    assert_eq!(recall(&mut state, vec![], Some("迁移 上线计划")), vec![text_hit.id.clone()]);
+    assert!(recall(&mut state, vec![], Some("迁移 发布")).is_empty());
+
+    // 更新后旧正文的分词不再命中。
+    state
+        .apply_transaction(vec![TransactionStep::Update(UpdateArgs {
+            id: text_hit.id.clone(),
+            revision: text_hit.revision,
+            diary: Some("改为下周评审".to_string()),
+            ..Default::default()
+        })])
+        .unwrap();
+    assert!(recall(&mut state, vec!["迁移"], None).is_empty());
+    assert_eq!(recall(&mut state, vec!["评审"], None), vec![text_hit.id]);
+}
+
+#[test]
+fn weighted_keywords_should_rank_central_memories_first() {
+    let temp = tempfile::tempdir().unwrap();
+    let paths = StorePaths::new(temp.path(), "u1/p1").unwrap();
+    let mut state = NamespaceState::open(paths).unwrap();
+
+    let remember = |state: &mut NamespaceState, keywords: &[&str], weights: &[(&str, u8)]| {
+        state
+            .append_memory(RememberArgs {
+                namespace: "u1/p1".to_string(),
+                keywords: keywords.iter().map(|x| x.to_string()).collect(),
+                keyword_weights: weights.iter().map(|(k, w)| (k.to_string(), *w)).collect(),
+                slice: "s".to_string(),
+                diary: "d".to_string(),
+                ..Default::default()
+            })
+            .unwrap()
+    };
+    // 两个关键字都命中但均为附带提及（1+1），不如一个核心关键字（3）。
+    let incidental = remember(&mut state, &["erp", "部署"], &[]);
+    let central = remember(&mut state, &["ERP", "财务"], &[("Erp", 3), ("未使用", 5)]);
+
+    let recalled = state
+        .recall(RecallArgs {
+            namespace: "u1/p1".to_string(),
+            keywords: vec!["erp".to_string(), "部署".to_string()],
+            limit: 20,
+            ..Default::default()
+        })
+        .unwrap();
+    let ids: Vec<_> = recalled.items.iter().map(|x| x.id.clone()).collect();
+    assert_eq!(ids, vec![central.id.clone(), incidental.id.clone()]);
+    // 权重键按关键字归一化，未出现在 keywords 中的权重被丢弃。
+    assert_eq!(
+        recalled.items[0].keyword_weights,
+        BTreeMap::from([("erp".to_string(), 3)])
+    );
+
+    // 替换关键字时权重一并替换。
+    state
+        .apply_transaction(vec![TransactionStep::Update(UpdateArgs {
+            id: central.id.clone(),
+            revision: central.revision,
+            keywords: Some(vec!["erp".to_string(), "财务".to_string()]),
+            ..Default::default()
+        })])
+        .unwrap();
+    let recalled = state
+        .recall(RecallArgs {
+            namespace: "u1/p1".to_string(),
+            keywords: vec!["erp".to_string(), "部署".to_string()],
+            limit: 20,
+            ..Default::default()
+        })
+        .unwrap();
+    assert_eq!(recalled.items[0].id, incidental.id);
+    assert!(recalled.items[1].keyword_weights.is_empty());
+}
+
+#[test]
+fn recall_should_expand_keyword_wildcards() {
+    let temp = tempfile::tempdir().unwrap();
+    let paths = StorePaths::new(temp.path(), "u1/p1").unwrap();
+    let mut state = NamespaceState::open(paths).unwrap();
+
+    let mut ids = HashMap::new();
+    for kw in ["erp", "erp迁移", "ERP-v2", "crm", "老erp"] {
+        let recorded = state
+            .append_memory(RememberArgs {
+                namespace: "u1/p1".to_string(),
+                keywords: vec![kw.to_string()],
+                slice: "s".to_string(),
+                diary: "d".to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+        ids.insert(kw.to_lowercase(), recorded.id);
+    }
+
+    let recall = |state: &mut NamespaceState, keywords: &[&str]| {
+        state
+            .recall(RecallArgs {
+                namespace: "u1/p1".to_string(),
+                keywords: keywords.iter().map(|x| x.to_string()).collect(),
+                limit: 20,
+                ..Default::default()
+            })
+            .map(|r| {
+                let mut out: Vec<String> = r
+                    .items
+                    .into_iter()
+                    .flat_map(|x| x.matched_keywords.unwrap_or_default())
+                    .collect();
+                out.sort();
+                out
+            })
+    };
+
+    assert_eq!(recall(&mut state, &["ERP*"]).unwrap(), vec!["erp", "erp-v2", "erp迁移"]);
+    assert_eq!(recall(&mut state, &["*erp"]).unwrap(), vec!["erp", "老erp"]);
+    assert_eq!(recall(&mut state, &["e*2", "crm"]).unwrap(), vec!["crm", "erp-v2"]);
+    assert!(recall(&mut state, &["xyz*"]).unwrap().is_empty());
+    assert!(recall(&mut state, &["**"]).unwrap_err().contains("非 * 字符"));
+
+    // 通配符命中按单个关键字计分：同分条目按时间倒序。
+    let top = state
+        .recall(RecallArgs {
+            namespace: "u1/p1".to_string(),
+            keywords: vec!["erp*".to_string()],
+            limit: 1,
+            ..Default::default()
+        })
+        .unwrap();
+    assert_eq!(top.items[0].id, ids["erp-v2"]);
+}
+
+#[test]
+fn recall_query_should_support_boolean_operators() {
+    let temp = tempfile::tempdir().unwrap();
+    let paths = StorePaths::new(temp.path(), "u1/p1").unwrap();
+    let mut state = NamespaceState::open(paths).unwrap();
+
+    let mut ids = Vec::new();
+    for slice in ["ERP 新版上线", "CRM 旧版下线", "ERP 旧版迁移", "OA 例会"] {
+        let recorded = state
+            .append_memory(RememberArgs {
+                namespace: "u1/p1".to_string(),
+                keywords: vec!["系统".to_string()],
+                slice: slice.to_string(),
+                diary: "d".to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+        ids.push(recorded.id);
+    }
+
+    let recall = |state: &mut NamespaceState, keywords: &[&str], query: &str| {
+        state
+            .recall(RecallArgs {
+                namespace: "u1/p1".to_string(),
+                keywords: keywords.iter().map(|x| x.to_string()).collect(),
+                query: Some(query.to_string()),
+                limit: 20,
+                ..Default::default()
+            })
+            .map(|r| {
+                let mut out: Vec<_> = r.items.into_iter().map(|x| x.id).collect();
+                out.sort_by_key(|id| ids.iter().position(|x| x == id));
+                out
+            })
+    };
+
+    assert_eq!(recall(&mut state, &[], "erp OR crm").unwrap(), ids[..3].to_vec());
+    assert_eq!(recall(&mut state, &[], "erp AND 旧版").unwrap(), vec![ids[2].clone()]);
+    assert_eq!(recall(&mut state, &["系统"], "-旧版").unwrap(), vec![ids[0].clone(), ids[3].clone()]);
+    assert_eq!(
+        recall(&mut state, &[], "(erp OR crm) -(旧版 迁移) time>=2000-01-01").unwrap(),
+        vec![ids[0].clone(), ids[1].clone()]
+    );
+    assert!(recall(&mut state, &[], "(erp OR crm").unwrap_err().contains("括号不匹配"));
+}
+
+#[test]
+fn full_text_postings_should_load_lazily_on_first_query() {
+    let temp = tempfile::tempdir().unwrap();
+    let paths = StorePaths::new(temp.path(), "u1/p1").unwrap();
+    let mut state = NamespaceState::open(paths.clone()).unwrap();
+    let recorded = state
+        .append_memory(RememberArgs {
+            namespace: "u1/p1".to_string(),
+            keywords: vec!["k".to_string()],
+            slice: "库存盘点完成".to_string(),
+            diary: "d".to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+
+    // index.json 只保存头部，全文倒排单独存放。
+    let header = fs::read_to_string(&paths.index_path).unwrap();
+    assert!(!header.contains("trigram_postings"));
+    assert!(paths.postings_path.is_file());
+
+    let query = |state: &mut NamespaceState, q: &str| {
+        state
+            .recall(RecallArgs {
+                namespace: "u1/p1".to_string(),
+                query: Some(q.to_string()),
+                limit: 20,
+                ..Default::default()
+            })
+            .unwrap()
+            .items
+            .len()
+    };
+
+    let mut state = NamespaceState::open(paths.clone()).unwrap();
+    assert!(!state.index.full_text_loaded);
+    state.get(&recorded.id).unwrap();
+    state.stats().unwrap();
+    assert!(!state.index.full_text_loaded);
+    assert_eq!(query(&mut state, "盘点"), 1);
+    assert!(state.index.full_text_loaded);
+
+    // postings.json 丢失或落后于头部时全量重建。
+    fs::remove_file(&paths.postings_path).unwrap();
+    let mut state = NamespaceState::open(paths.clone()).unwrap();
+    assert_eq!(query(&mut state, "盘点"), 1);
+    assert!(paths.postings_path.is_file());
+
+    let stale = fs::read_to_string(&paths.postings_path).unwrap();
+    let mut state = NamespaceState::open(paths.clone()).unwrap();
+    state
+        .apply_transaction(vec![TransactionStep::Forget {
+            id: recorded.id.clone(),
+            revision: recorded.revision,
+        }])
+        .unwrap();
+    fs::write(&paths.postings_path, &stale).unwrap();
+    let mut state = NamespaceState::open(paths.clone()).unwrap();
+    assert_eq!(query(&mut state, "盘点"), 0);
+    assert_ne!(fs::read_to_string(&paths.postings_path).unwrap(), stale);
+}
+
+#[test]
+fn recall_query_quoted_phrase_should_require_contiguous_text() {
+    let temp = tempfile::tempdir().unwrap();
+    let paths = StorePaths::new(temp.path(), "u1/p1").unwrap();
+    let mut state = NamespaceState::open(paths).unwrap();
+
+    let mut ids = Vec::new();
+    for diary in ["本周库存盘点已完成", "先盘点再核对库存"] {
+        let recorded = state
+            .append_memory(RememberArgs {
+                namespace: "u1/p1".to_string(),
+                keywords: vec!["仓库".to_string()],
+                slice: "s".to_string(),
+                diary: diary.to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+        ids.push(recorded.id);
+    }
+
+    let recall = |state: &mut NamespaceState, query: &str| {
+        let mut out: Vec<String> = state
+            .recall(RecallArgs {
+                namespace: "u1/p1".to_string(),
+                query: Some(query.to_string()),
+                limit: 20,
+                ..Default::default()
+            })
+            .unwrap()
+            .items
+            .into_iter()
+            .map(|x| x.id)
+            .collect();
+        out.sort_by_key(|id| ids.iter().position(|x| x == id));
+        out
+    };
+
+    assert_eq!(recall(&mut state, "库存 盘点"), ids);
+    assert_eq!(recall(&mut state, "\"库存 盘点\""), vec![ids[0].clone()]);
+    assert_eq!(recall(&mut state, "盘点 -\"库存盘点\""), vec![ids[1].clone()]);
+}
+
+#[test]
+fn recall_regex_should_match_original_text_per_field() {
+    let temp = tempfile::tempdir().unwrap();
+    let paths = StorePaths::new(temp.path(), "u1/p1").unwrap();
+    let mut state = NamespaceState::open(paths).unwrap();
+
+    let mut ids = Vec::new();
+    for (slice, source) in [("发布 V2.1 版本", None), ("发布计划", Some("ticket ERP-42"))] {
+        let recorded = state
+            .append_memory(RememberArgs {
+                namespace: "u1/p1".to_string(),
+                keywords: vec!["发布".to_string()],
+                slice: slice.to_string(),
+                diary: "d".to_string(),
+                source: source.map(MemorySource::label),
+                ..Default::default()
+            })
+            .unwrap();
+        ids.push(recorded.id);
+    }
+
+    let mut recall = |query: &str| {
+        state.recall(RecallArgs {
+            namespace: "u1/p1".to_string(),
+            query: Some(query.to_string()),
+            regex: true,
+            limit: 20,
+            ..Default::default()
+        })
+    };
+    let ids_of = |r: RecallResult| r.items.into_iter().map(|x| x.id).collect::<Vec<_>>();
+
+    assert_eq!(ids_of(recall(r"v\d+\.\d+").unwrap()), vec![ids[0].clone()]);
+    assert!(ids_of(recall(r"(?-i)v\d").unwrap()).is_empty());
+    assert_eq!(ids_of(recall(r"^ticket [a-z]+-\d+$").unwrap()), vec![ids[1].clone()]);
+    // 正则模式不解析布尔语法：OR 只是普通文本。
+    assert!(ids_of(recall("V2 OR 计划").unwrap()).is_empty());
+    assert!(recall("(").unwrap_err().contains("regex 无效"));
+    assert!(recall("  ").unwrap_err().contains("regex=true"));
+}
+
+#[test]
+fn recall_semantic_should_rank_by_similarity_and_cache_vectors() {
+    let temp = tempfile::tempdir().unwrap();
+    let paths = StorePaths::new(temp.path(), "u1/p1").unwrap();
+    let embeddings_path = paths.embeddings_path.clone();
+    let options = StoreOptions {
+        embedder: Arc::new(Embedder::Hash { dimensions: 256 }),
+        ..Default::default()
+    };
+    let mut state = NamespaceState::open_with(paths.clone(), options.clone()).unwrap();
+
+    let mut ids = Vec::new();
+    for (keyword, slice) in [("运维", "数据库迁移到新集群"), ("生活", "周末去爬山"), ("运维", "升级监控告警规则")] {
+        let recorded = state
+            .append_memory(RememberArgs {
+                namespace: "u1/p1".to_string(),
+                keywords: vec![keyword.to_string()],
+                slice: slice.to_string(),
+                diary: "d".to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+        ids.push(recorded.id);
+    }
+
+    let semantic = |keywords: Vec<String>| RecallArgs {
+        namespace: "u1/p1".to_string(),
+        keywords,
+        query: Some("把数据库迁移到集群".to_string()),
+        semantic: true,
+        limit: 20,
+        ..Default::default()
+    };
+    let out = state.recall(semantic(Vec::new())).unwrap();
+    assert_eq!(out.items.len(), 3);
+    assert_eq!(out.items[0].id, ids[0]);
+    assert!(out.items[0].score.unwrap() > out.items[1].score.unwrap());
+    assert!(embeddings_path.is_file());
+
+    // 关键字只做过滤。
+    let out = state.recall(semantic(vec!["生活".to_string()])).unwrap();
+    assert_eq!(out.items.iter().map(|x| x.id.clone()).collect::<Vec<_>>(), vec![ids[1].clone()]);
+
+    // 重新打开后复用缓存的向量，结果一致。
+    let mut reopened = NamespaceState::open_with(paths.clone(), options).unwrap();
+    assert_eq!(reopened.recall(semantic(Vec::new())).unwrap().items[0].id, ids[0]);
+
+    let mut disabled = NamespaceState::open(paths).unwrap();
+    assert!(disabled.recall(semantic(Vec::new())).unwrap_err().contains("语义召回未启用"));
+}
+
+#[test]
+fn recall_query_should_filter_by_weekday_and_hour() {
+    let temp = tempfile::tempdir().unwrap();
+    let paths = StorePaths::new(temp.path(), "u1/p1").unwrap();
+    let mut state = NamespaceState::open(paths).unwrap();
+
+    // 周五晚上、周六上午、周一晚上（本机时区的钟点）、仅日期的周日
+    let local = |d: u32, h: u32, m: u32| {
+        use chrono::TimeZone;
+        chrono::Local.with_ymd_and_hms(2025, 5, d, h, m, 0).unwrap().to_rfc3339()
+    };
+    let mut ids = Vec::new();
+    for occurred_at in [local(2, 20, 0), local(3, 9, 30), local(5, 19, 0), "2025-05-04".to_string()] {
+        let recorded = state
+            .append_memory(RememberArgs {
+                namespace: "u1/p1".to_string(),
+                keywords: vec!["k".to_string()],
+                slice: "复盘".to_string(),
+                diary: "d".to_string(),
+                occurred_at: Some(occurred_at),
+                ..Default::default()
+            })
+            .unwrap();
+        ids.push(recorded.id);
+    }
+
+    let mut recall = |query: &str| {
+        let mut out: Vec<String> = state
+            .recall(RecallArgs {
+                namespace: "u1/p1".to_string(),
+                query: Some(query.to_string()),
+                limit: 20,
+                ..Default::default()
+            })
+            .unwrap()
+            .items
+            .into_iter()
+            .map(|x| x.id)
+            .collect();
+        out.sort_by_key(|id| ids.iter().position(|x| x == id));
+        out
+    };
+
+    assert_eq!(recall("hour>=18"), vec![ids[0].clone(), ids[2].clone()]);
+    assert_eq!(recall("dow:sat,sun"), vec![ids[1].clone(), ids[3].clone()]);
+    assert_eq!(recall("dow:mon..fri hour>=18 复盘"), vec![ids[0].clone(), ids[2].clone()]);
+    assert_eq!(recall("dow:fri..sat hour=9..20"), vec![ids[0].clone(), ids[1].clone()]);
+    assert_eq!(recall("hour=22..2"), Vec::<String>::new());
+}
+
+#[test]
+fn large_identical_diaries_should_share_one_blob() {
+    let temp = tempfile::tempdir().unwrap();
+    let root = temp.path();
+
+    let paths = StorePaths::new(root, "u1/p1").unwrap();
+    let mut state = NamespaceState::open(paths.clone()).unwrap();
+    let diary = "周报模板：本周完成数据库迁移演练。\n".repeat(200);
+    for slice in ["第一周", "第二周"] {
+        state
+            .append_memory(RememberArgs {
+                namespace: "u1/p1".to_string(),
+                keywords: vec!["周报".to_string()],
+                slice: slice.to_string(),
+                diary: diary.clone(),
+                ..Default::default()
+            })
+            .unwrap();
+    }
+
+    let log = fs::read_to_string(&paths.memories_path).unwrap();
+    assert!(!log.contains("周报模板"));
+    assert_eq!(log.matches("\"diary_ref\":\"sha256:").count(), 2);
+    let blobs: Vec<_> = fs::read_dir(paths.namespace_dir.join("blobs"))
+        .unwrap()
+        .flatten()
+        .flat_map(|d| fs::read_dir(d.path()).unwrap().flatten())
+        .collect();
+    assert_eq!(blobs.len(), 1);
+
+    fs::remove_file(&paths.index_path).unwrap();
+    fs::remove_file(&paths.postings_path).unwrap();
+    let mut state = NamespaceState::open(paths.clone()).unwrap();
+    let out = state
+        .recall(RecallArgs {
+            namespace: "u1/p1".to_string(),
+            query: Some("迁移演练".to_string()),
+            include_diary: true,
+            limit: 10,
+            ..Default::default()
+        })
+        .unwrap();
+    assert_eq!(out.items.len(), 2);
+    assert!(out.items.iter().all(|x| x.diary.as_deref() == Some(diary.as_str())));
+    assert_eq!(state.checksum_report().unwrap().verified, 2);
+
+    let dump = crate::memory::export::collect("u1/p1", &state.files, None).unwrap();
+    assert!(dump.records.iter().all(|r| match r {
+        ExportRecord::Item(item) => item.diary == diary && item.diary_ref.is_none() && item.verify_checksum() == Some(true),
+        ExportRecord::Delete(_) => false,
+    }));
+
+    fs::write(blobs[0].path(), "被篡改").unwrap();
+    let mut state = NamespaceState::open(paths).unwrap();
+    assert_eq!(state.checksum_report().unwrap().unreadable.len(), 2);
+}
+
+#[cfg(feature = "zstd")]
+#[test]
+fn compressed_diaries_should_round_trip_through_offset_reads() {
+    let temp = tempfile::tempdir().unwrap();
+    let paths = StorePaths::new(temp.path(), "u1/p1").unwrap();
+    let options = StoreOptions {
+        compress: true,
+        ..Default::default()
+    };
+    let mut state = NamespaceState::open_with(paths.clone(), options.clone()).unwrap();
+    let diary = "复盘：本周完成数据库迁移演练，监控告警全部恢复。\n".repeat(20);
+    for slice in ["长篇", "短篇"] {
+        state
+            .append_memory(RememberArgs {
+                namespace: "u1/p1".to_string(),
+                keywords: vec!["复盘".to_string()],
+                slice: slice.to_string(),
+                diary: if slice == "长篇" { diary.clone() } else { "短".to_string() },
+                ..Default::default()
+            })
+            .unwrap();
+    }
+
+    let log = fs::read_to_string(&paths.memories_path).unwrap();
+    assert!(!log.contains("迁移演练"));
+    assert_eq!(log.matches("\"diary_zstd\":").count(), 1);
+
+    fs::remove_file(&paths.index_path).unwrap();
+    fs::remove_file(&paths.postings_path).unwrap();
+    let mut state = NamespaceState::open_with(paths, options).unwrap();
+    let out = state
+        .recall(RecallArgs {
+            namespace: "u1/p1".to_string(),
+            query: Some("迁移演练".to_string()),
+            include_diary: true,
+            limit: 10,
+            ..Default::default()
+        })
+        .unwrap();
+    assert_eq!(out.items.len(), 1);
+    assert_eq!(out.items[0].diary.as_deref(), Some(diary.as_str()));
+    assert_eq!(state.checksum_report().unwrap().verified, 2);
+}
+
+#[test]
+fn mmap_index_should_share_postings_across_processes() {
+    let temp = tempfile::tempdir().unwrap();
+    let paths = StorePaths::new(temp.path(), "u1/p1").unwrap();
+    let options = StoreOptions {
+        mmap_index: true,
+        ..Default::default()
+    };
+    let remember = |state: &mut NamespaceState, slice: &str| {
+        state
+            .append_memory(RememberArgs {
+                namespace: "u1/p1".to_string(),
+                keywords: vec!["k".to_string()],
+                slice: slice.to_string(),
+                diary: "d".to_string(),
+                ..Default::default()
+            })
+            .unwrap()
+    };
+    let query = |state: &mut NamespaceState, q: &str| {
+        state
+            .recall(RecallArgs {
+                namespace: "u1/p1".to_string(),
+                query: Some(q.to_string()),
+                limit: 20,
+                ..Default::default()
+            })
+            .unwrap()
+            .items
+            .len()
+    };
+
+    let mut writer = NamespaceState::open_with(paths.clone(), options.clone()).unwrap();
+    let first = remember(&mut writer, "库存盘点完成");
+    remember(&mut writer, "仓库盘点延期");
+    assert!(paths.postings_bin_path.is_file());
+    assert!(writer.index.mapped.is_some());
+
+    // 另一个进程：直接映射 postings.bin，不展开到内存。
+    let mut reader = NamespaceState::open_with(paths.clone(), options.clone()).unwrap();
+    assert_eq!(query(&mut reader, "盘点"), 2);
+    assert!(reader.index.mapped.is_some());
+    assert!(reader.index.trigram_postings.is_empty());
+
+    // 删除标记只推进偏移：映射随之刷新，其他进程无需重建。
+    writer
+        .apply_transaction(vec![TransactionStep::Forget {
+            id: first.id,
+            revision: 1,
+        }])
+        .unwrap();
+    assert_eq!(query(&mut reader, "盘点"), 1);
+    let mut plain = NamespaceState::open(paths.clone()).unwrap();
+    assert_eq!(query(&mut plain, "延期"), 1);
+    let mut fresh = NamespaceState::open_with(paths, options).unwrap();
+    assert_eq!(query(&mut fresh, "库存"), 0);
+    assert!(fresh.index.mapped.is_some());
+}
+
+#[test]
+fn hot_keywords_should_pin_decoded_postings_when_mapped() {
+    let temp = tempfile::tempdir().unwrap();
+    let paths = StorePaths::new(temp.path(), "u1/p1").unwrap();
+    let options = StoreOptions {
+        mmap_index: true,
+        ..Default::default()
+    };
+    let mut writer = NamespaceState::open_with(paths.clone(), options.clone()).unwrap();
+    for slice in ["数据库迁移完成", "数据库迁移回滚", "前端发布"] {
+        writer
+            .append_memory(RememberArgs {
+                namespace: "u1/p1".to_string(),
+                keywords: vec!["k".to_string()],
+                slice: slice.to_string(),
+                diary: "d".to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+    }
+    let recall = |state: &mut NamespaceState, keyword: &str| {
+        state
+            .recall(RecallArgs {
+                namespace: "u1/p1".to_string(),
+                keywords: vec![keyword.to_string()],
+                limit: 20,
+                ..Default::default()
+            })
+            .unwrap()
+            .items
+            .len()
+    };
+
+    let mut reader = NamespaceState::open_with(paths.clone(), options).unwrap();
+    assert!(reader.index.pinned_terms.is_empty());
+    assert_eq!(recall(&mut reader, "迁移"), 2);
+    assert!(reader.index.mapped.is_some());
+    assert_eq!(reader.index.pinned_terms.get("迁移").map(Vec::len), Some(2));
+    // 常驻副本与映射结果一致；正文中不存在的关键字没有倒排可常驻。
+    assert_eq!(recall(&mut reader, "迁移"), 2);
+    assert_eq!(recall(&mut reader, "不存在"), 0);
+    assert!(!reader.index.pinned_terms.contains_key("不存在"));
+
+    // 不使用映射时全部倒排本就在内存中，无需常驻副本。
+    let mut plain = NamespaceState::open(paths).unwrap();
+    assert_eq!(recall(&mut plain, "迁移"), 2);
+    assert!(plain.index.pinned_terms.is_empty());
+}
+
+#[test]
+fn externally_rewritten_index_should_be_reloaded_before_serving() {
+    let temp = tempfile::tempdir().unwrap();
+    let paths = StorePaths::new(temp.path(), "u1/p1").unwrap();
+    let remember = |state: &mut NamespaceState, slice: &str| {
+        state
+            .append_memory(RememberArgs {
+                namespace: "u1/p1".to_string(),
+                keywords: vec!["k".to_string()],
+                slice: slice.to_string(),
+                diary: "d".to_string(),
+                ..Default::default()
+            })
+            .unwrap()
+    };
+
+    // 服务端进程与 CLI 单次调用各自持有 namespace 状态。
+    let mut server = NamespaceState::open(paths.clone()).unwrap();
+    let a = remember(&mut server, "保留");
+    let b = remember(&mut server, "删除后压缩");
+    let mut cli = NamespaceState::open(paths.clone()).unwrap();
+
+    // CLI 落盘的召回统计（access.json）：服务端应读到 CLI 的计数。
+    cli.record_recalled(std::slice::from_ref(&a.id));
+    cli.flush().unwrap();
+    let out = server.least_recalled(10).unwrap();
+    let counts: Vec<(&str, u64)> = out.items.iter().map(|x| (x.id.as_str(), x.recall_count)).collect();
+    assert_eq!(counts, vec![(b.id.as_str(), 0), (a.id.as_str(), 1)]);
+
+    // CLI 删除并压缩后继续写入，数据文件重新变长：服务端不能按旧偏移增量索引。
+    cli.apply_transaction(vec![TransactionStep::Forget {
+        id: b.id.clone(),
+        revision: 1,
+    }])
+    .unwrap();
+    cli.compact().unwrap();
+    let c = remember(&mut cli, &"压缩后新增的较长内容".repeat(20));
+    let out = server
+        .recall(RecallArgs {
+            namespace: "u1/p1".to_string(),
+            limit: 10,
+            ..Default::default()
+        })
+        .unwrap();
+    let mut ids: Vec<String> = out.items.iter().map(|x| x.id.clone()).collect();
+    ids.sort();
+    let mut expected = vec![a.id, c.id];
+    expected.sort();
+    assert_eq!(ids, expected);
+    assert_eq!(server.verify(false).unwrap().bad_entries.len(), 0);
+}
+
+#[test]
+fn structured_source_should_filter_recall_and_keep_legacy_strings() {
+    let temp = tempfile::tempdir().unwrap();
+    let paths = StorePaths::new(temp.path(), "u1/p1").unwrap();
+    let mut state = NamespaceState::open(paths.clone()).unwrap();
+
+    let conversation = MemorySource::from_json(Some(&serde_json::json!({
+        "kind": " Conversation ", "id": "c-42", "label": "周会", "url": "https://chat.example.com/c/42"
+    })))
+    .unwrap();
+    for (slice, source) in [
+        ("周会确定上线日期", conversation.clone()),
+        ("需求文档更新", Some(MemorySource { kind: Some("document".to_string()), id: Some("d-1".to_string()), ..Default::default() })),
+        ("旧记录", Some(MemorySource::label("chat"))),
+    ] {
+        state
+            .append_memory(RememberArgs {
+                namespace: "u1/p1".to_string(),
+                keywords: vec!["上线".to_string()],
+                slice: slice.to_string(),
+                diary: "d".to_string(),
+                source,
+                ..Default::default()
+            })
+            .unwrap();
+    }
+
+    // 仅有 label 的来源仍写为字符串，旧记录的形态与 checksum 不变。
+    let text = fs::read_to_string(&paths.memories_path).unwrap();
+    assert!(text.contains(r#""source":"chat""#));
+    assert!(text.contains(r#""source":{"kind":"conversation","id":"c-42","label":"周会","url":"https://chat.example.com/c/42"}"#));
+
+    let recall = |state: &mut NamespaceState, kind: Option<&str>, id: Option<&str>| -> Vec<String> {
+        state
+            .recall(RecallArgs {
+                namespace: "u1/p1".to_string(),
+                limit: 20,
+                source_kind: kind.map(str::to_string),
+                source_id: id.map(str::to_string),
+                ..Default::default()
+            })
+            .unwrap()
+            .items
+            .into_iter()
+            .map(|x| x.slice)
+            .collect()
+    };
+    let mut reopened = NamespaceState::open(paths).unwrap();
+    assert_eq!(recall(&mut reopened, Some("conversation"), None), vec!["周会确定上线日期"]);
+    assert_eq!(recall(&mut reopened, None, Some("d-1")), vec!["需求文档更新"]);
+    assert!(recall(&mut reopened, Some("conversation"), Some("d-1")).is_empty());
+    assert_eq!(recall(&mut reopened, None, None).len(), 3);
+
+    let legacy = reopened
+        .recall(RecallArgs {
+            namespace: "u1/p1".to_string(),
+            query: Some("chat.example.com".to_string()),
+            limit: 20,
+            ..Default::default()
+        })
+        .unwrap();
+    assert_eq!(legacy.items.len(), 1, "query 同时检索来源的显示名、id 与链接");
+
+    assert_eq!(conversation.unwrap().describe(), "周会 [conversation:c-42] https://chat.example.com/c/42");
+    assert_eq!(MemorySource::from_json(Some(&serde_json::json!("  "))).unwrap(), None);
+    assert!(MemorySource::from_json(Some(&serde_json::json!({ "kind": "doc", "path": "x" }))).is_err());
+    assert!(MemorySource::from_json(Some(&serde_json::json!({ "url": "chat/42" }))).is_err());
+    assert!(MemorySource::from_json(Some(&serde_json::json!(42))).is_err());
+}
+
+#[test]
+fn tags_should_filter_recall_without_touching_keyword_index() {
+    let temp = tempfile::tempdir().unwrap();
+    let paths = StorePaths::new(temp.path(), "u1/p1").unwrap();
+    let mut state = NamespaceState::open(paths.clone()).unwrap();
+
+    let mut ids = Vec::new();
+    for (slice, tags) in [
+        ("alpha 排期", vec![" Project-Alpha ", "confidential", "project-alpha"]),
+        ("alpha 周报", vec!["project-alpha"]),
+        ("beta 排期", vec!["confidential"]),
+    ] {
+        let recorded = state
+            .append_memory(RememberArgs {
+                namespace: "u1/p1".to_string(),
+                keywords: vec!["排期".to_string()],
+                tags: tags.into_iter().map(str::to_string).collect(),
+                slice: slice.to_string(),
+                diary: "d".to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+        ids.push(recorded.id);
+    }
+    assert_eq!(state.get(&ids[0]).unwrap().tags, vec!["project-alpha", "confidential"]);
+    assert!(!state.index.keyword_postings.contains_key("confidential"));
+
+    let recall = |state: &mut NamespaceState, keywords: &[&str], tags: &[&str]| -> Vec<String> {
+        let mut out: Vec<String> = state
+            .recall(RecallArgs {
+                namespace: "u1/p1".to_string(),
+                keywords: keywords.iter().map(|x| x.to_string()).collect(),
+                tags: tags.iter().map(|x| x.to_string()).collect(),
+                limit: 20,
+                ..Default::default()
+            })
+            .unwrap()
+            .items
+            .into_iter()
+            .map(|x| x.slice)
+            .collect();
+        out.sort();
+        out
+    };
+    assert_eq!(recall(&mut state, &[], &["PROJECT-ALPHA"]), vec!["alpha 周报", "alpha 排期"]);
+    assert_eq!(recall(&mut state, &["排期"], &["confidential"]), vec!["alpha 排期", "beta 排期"]);
+    assert_eq!(recall(&mut state, &[], &["project-alpha", "confidential"]), vec!["alpha 排期"]);
+    assert!(recall(&mut state, &[], &["missing"]).is_empty());
+    // 标签不参与关键字召回。
+    assert!(recall(&mut state, &["confidential"], &[]).is_empty());
+
+    state
+        .apply_transaction(vec![TransactionStep::Update(UpdateArgs {
+            id: ids[2].clone(),
+            revision: 1,
+            tags: Some(vec![]),
+            ..Default::default()
+        })])
+        .unwrap();
+    let mut reopened = NamespaceState::open(paths).unwrap();
+    assert_eq!(recall(&mut reopened, &[], &["confidential"]), vec!["alpha 排期"]);
+}
+
+#[test]
+fn kind_should_filter_recall_and_survive_reindex() {
+    let temp = tempfile::tempdir().unwrap();
+    let paths = StorePaths::new(temp.path(), "u1/p1").unwrap();
+    let mut state = NamespaceState::open(paths.clone()).unwrap();
+
+    let mut ids = Vec::new();
+    for (slice, kind) in [
+        ("回答用中文", Some(MemoryKind::Preference)),
+        ("选用 PostgreSQL", Some(MemoryKind::Decision)),
+        ("周三上线", Some(MemoryKind::Event)),
+        ("未分类", None),
+    ] {
+        let recorded = state
+            .append_memory(RememberArgs {
+                namespace: "u1/p1".to_string(),
+                keywords: vec!["项目".to_string()],
+                kind,
+                slice: slice.to_string(),
+                diary: "d".to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+        ids.push(recorded.id);
+    }
+
+    let recall = |state: &mut NamespaceState, keywords: &[&str], kinds: &[MemoryKind]| -> Vec<String> {
+        let mut out: Vec<String> = state
+            .recall(RecallArgs {
+                namespace: "u1/p1".to_string(),
+                keywords: keywords.iter().map(|x| x.to_string()).collect(),
+                kinds: kinds.to_vec(),
+                limit: 20,
+                ..Default::default()
+            })
+            .unwrap()
+            .items
+            .into_iter()
+            .map(|x| x.slice)
+            .collect();
+        out.sort();
+        out
+    };
+    assert_eq!(recall(&mut state, &[], &[MemoryKind::Preference]), vec!["回答用中文"]);
+    assert_eq!(
+        recall(&mut state, &["项目"], &[MemoryKind::Decision, MemoryKind::Preference]),
+        vec!["回答用中文", "选用 PostgreSQL"]
+    );
+    assert_eq!(recall(&mut state, &["项目"], &[]).len(), 4);
+    assert!(recall(&mut state, &[], &[MemoryKind::Task]).is_empty());
+
+    state
+        .apply_transaction(vec![TransactionStep::Update(UpdateArgs {
+            id: ids[3].clone(),
+            revision: 1,
+            kind: Some(MemoryKind::Task),
+            ..Default::default()
+        })])
+        .unwrap();
+    let mut reopened = NamespaceState::open(paths).unwrap();
+    assert_eq!(recall(&mut reopened, &[], &[MemoryKind::Task]), vec!["未分类"]);
+    assert_eq!(reopened.get(&ids[0]).unwrap().kind, Some(MemoryKind::Preference));
+}
+
+#[test]
+fn kind_should_parse_from_json_args() {
+    let args = RecallArgs::from_json(&serde_json::json!({
+        "namespace": "u1/p1",
+        "kind": ["Fact", "decision"]
+    }))
+    .unwrap();
+    assert_eq!(args.kinds, vec![MemoryKind::Fact, MemoryKind::Decision]);
+    let args = RecallArgs::from_json(&serde_json::json!({ "namespace": "u1/p1", "kind": "task" })).unwrap();
+    assert_eq!(args.kinds, vec![MemoryKind::Task]);
+    assert!(RememberArgs::from_json(&serde_json::json!({
+        "namespace": "u1/p1", "keywords": ["k"], "slice": "s", "diary": "d", "kind": "note"
+    }))
+    .unwrap_err()
+    .contains("kind 不支持"));
+}
+
+#[test]
+fn metadata_should_roundtrip_and_filter_recall_query() {
+    let temp = tempfile::tempdir().unwrap();
+    let paths = StorePaths::new(temp.path(), "u1/p1").unwrap();
+    let mut state = NamespaceState::open(paths.clone()).unwrap();
+
+    let mut ids = Vec::new();
+    for (slice, metadata) in [
+        ("修复登录超时", serde_json::json!({ "ticket": "ERP-12", "path": "src/login.rs" })),
+        ("调整报表格式", serde_json::json!({ "ticket": "ERP-13" })),
+        ("无附加数据", serde_json::json!({})),
+    ] {
+        let recorded = state
+            .append_memory(RememberArgs {
+                namespace: "u1/p1".to_string(),
+                keywords: vec!["工单".to_string()],
+                slice: slice.to_string(),
+                diary: "d".to_string(),
+                metadata: metadata.as_object().unwrap().clone(),
+                ..Default::default()
+            })
+            .unwrap();
+        ids.push(recorded.id);
+    }
+    let log = std::fs::read_to_string(&paths.memories_path).unwrap();
+    assert_eq!(log.matches("\"metadata\"").count(), 2);
+
+    let recall = |state: &mut NamespaceState, query: &str| -> Vec<String> {
+        let mut out: Vec<String> = state
+            .recall(RecallArgs {
+                namespace: "u1/p1".to_string(),
+                query: Some(query.to_string()),
+                limit: 20,
+                ..Default::default()
+            })
+            .unwrap()
+            .items
+            .into_iter()
+            .map(|x| x.slice)
+            .collect();
+        out.sort();
+        out
+    };
+    assert_eq!(recall(&mut state, "metadata.ticket=erp-12"), vec!["修复登录超时"]);
+    assert_eq!(recall(&mut state, "报表 OR metadata.path=src/login.rs"), vec!["修复登录超时", "调整报表格式"]);
+    assert_eq!(recall(&mut state, "-metadata.ticket=erp-12"), vec!["无附加数据", "调整报表格式"]);
+
+    let out = state
+        .recall(RecallArgs {
+            namespace: "u1/p1".to_string(),
+            keywords: vec!["工单".to_string()],
+            limit: 20,
+            ..Default::default()
+        })
+        .unwrap();
+    let first = out.items.iter().find(|x| x.id == ids[0]).unwrap();
+    assert_eq!(first.metadata.get("path"), Some(&serde_json::json!("src/login.rs")));
+
+    state
+        .apply_transaction(vec![TransactionStep::Update(UpdateArgs {
+            id: ids[0].clone(),
+            revision: 1,
+            metadata: Some(Default::default()),
+            ..Default::default()
+        })])
+        .unwrap();
+    let mut reopened = NamespaceState::open(paths).unwrap();
+    assert!(reopened.get(&ids[0]).unwrap().metadata.is_empty());
+    assert!(recall(&mut reopened, "metadata.ticket=erp-12").is_empty());
+}
+
+#[test]
+fn recall_limit_should_follow_server_and_namespace_settings() {
+    let temp = tempfile::tempdir().unwrap();
+    let paths = StorePaths::new(temp.path(), "u1/p1").unwrap();
+    let mut state = NamespaceState::open(paths.clone()).unwrap();
+    for i in 0..130 {
+        state
+            .append_memory(RememberArgs {
+                namespace: "u1/p1".to_string(),
+                keywords: vec!["批量".to_string()],
+                slice: format!("第 {i} 条"),
+                diary: "d".to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+    }
+    let recall = |state: &mut NamespaceState, limit: usize| {
+        state
+            .recall(RecallArgs {
+                namespace: "u1/p1".to_string(),
+                keywords: vec!["批量".to_string()],
+                limit,
+                ..Default::default()
+            })
+            .unwrap()
+    };
+
+    let out = recall(&mut state, 500);
+    assert_eq!(out.total, RECALL_MAX_LIMIT);
+    assert!(out.has_more);
+
+    let mut state = NamespaceState::open_with(paths.clone(), StoreOptions {
+        recall_max_limit: Some(120),
+        ..Default::default()
+    })
+    .unwrap();
+    assert_eq!(recall(&mut state, 500).total, 120);
+
+    // namespace 设置优先于服务端配置，修改文件后立即生效。
+    write_settings(&paths, &NamespaceSettings { recall_max_limit: Some(1000) }).unwrap();
+    let out = recall(&mut state, 500);
+    assert_eq!(out.total, 130);
+    assert!(!out.has_more);
+    assert_eq!(recall(&mut state, 7).total, 7);
+
+    assert!(write_settings(&paths, &NamespaceSettings { recall_max_limit: Some(0) }).is_err());
+    std::fs::write(&paths.settings_path, r#"{"recall_max_limit": 999999}"#).unwrap();
+    assert!(state
+        .recall(RecallArgs {
+            namespace: "u1/p1".to_string(),
+            limit: 1,
+            ..Default::default()
+        })
+        .unwrap_err()
+        .contains("settings.json 无效"));
+
+    write_settings(&paths, &NamespaceSettings::default()).unwrap();
+    assert!(!paths.settings_path.exists());
+    assert_eq!(recall(&mut state, 500).total, 120);
+}
+
+#[test]
+fn supersedes_should_hide_old_memory_and_expose_links() {
+    let temp = tempfile::tempdir().unwrap();
+    let paths = StorePaths::new(temp.path(), "u1/p1").unwrap();
+    let mut state = NamespaceState::open(paths.clone()).unwrap();
+    let remember = |state: &mut NamespaceState, slice: &str, relates_to: Vec<String>, supersedes: Option<String>| {
+        state.append_memory(RememberArgs {
+            namespace: "u1/p1".to_string(),
+            keywords: vec!["数据库".to_string()],
+            slice: slice.to_string(),
+            diary: "d".to_string(),
+            relates_to,
+            supersedes,
+            ..Default::default()
+        })
+    };
+
+    let old = remember(&mut state, "选用 MySQL", vec![], None).unwrap().id;
+    let note = remember(&mut state, "压测报告", vec![], None).unwrap().id;
+    let new = remember(&mut state, "改用 PostgreSQL", vec![note.clone(), format!(" {note} ")], Some(old.clone()))
+        .unwrap()
+        .id;
+    let Err(err) = remember(&mut state, "x", vec!["missing".to_string()], None) else {
+        panic!("dangling relates_to should be rejected");
+    };
+    assert!(err.contains("relates_to 指向的记忆不存在"));
+
+    let recall = |state: &mut NamespaceState, include_superseded: bool| {
+        state
+            .recall(RecallArgs {
+                namespace: "u1/p1".to_string(),
+                keywords: vec!["数据库".to_string()],
+                include_superseded,
+                limit: 20,
+                ..Default::default()
+            })
+            .unwrap()
+            .items
+    };
+    let items = recall(&mut state, false);
+    assert_eq!(items.len(), 2);
+    let head = items.iter().find(|x| x.id == new).unwrap();
+    assert_eq!(head.links.relates_to, vec![note.clone()]);
+    assert_eq!(head.links.supersedes.as_deref(), Some(old.as_str()));
+
+    let items = recall(&mut state, true);
+    let hidden = items.iter().find(|x| x.id == old).unwrap();
+    assert_eq!(hidden.links.superseded_by, vec![new.clone()]);
+    assert_eq!(state.get(&old).unwrap().slice, "选用 MySQL");
+
+    // 撤销取代关系后旧记忆重新可见；重建索引后状态一致。
+    state
+        .apply_transaction(vec![TransactionStep::Update(UpdateArgs {
+            id: new.clone(),
+            revision: 1,
+            supersedes: Some(String::new()),
+            ..Default::default()
+        })])
+        .unwrap();
+    assert_eq!(recall(&mut state, false).len(), 3);
+    state
+        .apply_transaction(vec![TransactionStep::Update(UpdateArgs {
+            id: new.clone(),
+            revision: 2,
+            supersedes: Some(old.clone()),
+            ..Default::default()
+        })])
+        .unwrap();
+    std::fs::remove_file(&paths.index_path).unwrap();
+    let mut reopened = NamespaceState::open(paths).unwrap();
+    assert_eq!(recall(&mut reopened, false).len(), 2);
+    assert_eq!(reopened.superseded_by(&old), vec![new.clone()]);
+
+    // 删除取代者后旧记忆恢复可见。
+    reopened
+        .apply_transaction(vec![TransactionStep::Forget { id: new, revision: 3 }])
+        .unwrap();
+    assert_eq!(recall(&mut reopened, false).len(), 2);
+    assert!(reopened.superseded_by(&old).is_empty());
+}
+
+#[test]
+fn history_should_return_all_revisions_including_deleted() {
+    let temp = tempfile::tempdir().unwrap();
+    let paths = StorePaths::new(temp.path(), "u1/p1").unwrap();
+    let mut state = NamespaceState::open(paths.clone()).unwrap();
+    let id = state
+        .append_memory(RememberArgs {
+            namespace: "u1/p1".to_string(),
+            keywords: vec!["部署".to_string()],
+            slice: "v1".to_string(),
+            diary: "d".to_string(),
+            ..Default::default()
+        })
+        .unwrap()
+        .id;
+    for (revision, slice) in [(1, "v2"), (2, "v3")] {
+        state
+            .apply_transaction(vec![TransactionStep::Update(UpdateArgs {
+                id: id.clone(),
+                revision,
+                slice: Some(slice.to_string()),
+                ..Default::default()
+            })])
+            .unwrap();
+    }
+
+    let history = state.history(&id).unwrap();
+    assert!(!history.deleted);
+    let slices: Vec<(&str, u32)> = history.versions.iter().map(|x| (x.slice.as_str(), x.revision)).collect();
+    assert_eq!(slices, vec![("v1", 1), ("v2", 2), ("v3", 3)]);
+    assert!(history.versions[0].updated_at.is_none());
+    assert!(history.versions[2].updated_at.is_some());
+
+    state
+        .apply_transaction(vec![TransactionStep::Forget { id: id.clone(), revision: 3 }])
+        .unwrap();
+    assert!(state.get(&id).is_err());
+
+    // 重新打开（从磁盘索引加载）后删除前的版本仍可查阅。
+    let mut state = NamespaceState::open(paths).unwrap();
+    let history = state.history(&id).unwrap();
+    assert!(history.deleted);
+    assert_eq!(history.versions.len(), 3);
+    assert!(state.history("missing").unwrap_err().contains("记忆不存在"));
+}
+
+#[test]
+fn tombstone_should_keep_log_append_only_and_drop_target_incrementally() {
+    let temp = tempfile::tempdir().unwrap();
+    let paths = StorePaths::new(temp.path(), "u1/p1").unwrap();
+    let mut state = NamespaceState::open(paths.clone()).unwrap();
+    let remember = |state: &mut NamespaceState, slice: &str| {
+        state
+            .append_memory(RememberArgs {
+                namespace: "u1/p1".to_string(),
+                keywords: vec!["部署".to_string()],
+                slice: slice.to_string(),
+                diary: "d".to_string(),
+                ..Default::default()
+            })
+            .unwrap()
+    };
+    let kept = remember(&mut state, "蓝绿发布");
+    let dropped = remember(&mut state, "灰度发布");
+    let before = fs::read(&paths.memories_path).unwrap();
+
+    // 另一个进程删除：本进程只能通过增量索引看到追加的删除标记。
+    let mut other = NamespaceState::open(paths.clone()).unwrap();
+    other
+        .apply_transaction(vec![TransactionStep::Forget {
+            id: dropped.id.clone(),
+            revision: dropped.revision,
+        }])
+        .unwrap();
+
+    let after = fs::read(&paths.memories_path).unwrap();
+    assert!(after.starts_with(&before), "删除只能追加，不能改写已有记录");
+    let last: serde_json::Value = serde_json::from_slice(after[before.len()..].trim_ascii_end()).unwrap();
+    assert_eq!(last["op"], "delete");
+    assert_eq!(last["id"], dropped.id.as_str());
+
+    let recalled = state
+        .recall(RecallArgs {
+            namespace: "u1/p1".to_string(),
+            query: Some("发布".to_string()),
+            limit: 20,
+            ..Default::default()
+        })
+        .unwrap();
+    let ids: Vec<&str> = recalled.items.iter().map(|x| x.id.as_str()).collect();
+    assert_eq!(ids, vec![kept.id.as_str()]);
+
+    let head = state.index.heads[&kept.id];
+    assert!(!state.index.heads.contains_key(&dropped.id));
+    assert_eq!(state.index.time_sorted, vec![head]);
+    assert_eq!(state.index.keyword_postings.get("部署"), Some(&vec![head]));
+    assert_eq!(state.history(&dropped.id).unwrap().versions.len(), 1);
+}
+
+#[test]
+fn expired_memories_should_be_hidden_and_purged() {
+    let temp = tempfile::tempdir().unwrap();
+    let paths = StorePaths::new(temp.path(), "u1/p1").unwrap();
+    let mut state = NamespaceState::open(paths.clone()).unwrap();
+    let remember = |state: &mut NamespaceState, slice: &str, expires_at: Option<&str>| {
+        state
+            .append_memory(RememberArgs {
+                namespace: "u1/p1".to_string(),
+                keywords: vec!["值班".to_string()],
+                slice: slice.to_string(),
+                diary: "d".to_string(),
+                expires_at: expires_at.map(str::to_string),
+                ..Default::default()
+            })
+            .unwrap()
+    };
+    let kept = remember(&mut state, "长期", None);
+    let future = remember(&mut state, "下周", Some("2999-01-01"));
+    let expired = remember(&mut state, "上周", Some("2020-01-01T00:00:00+08:00"));
+    state
+        .apply_transaction(vec![TransactionStep::Update(UpdateArgs {
+            id: expired.id.clone(),
+            revision: 1,
+            slice: Some("上周 v2".to_string()),
+            ..Default::default()
+        })])
+        .unwrap();
+    assert_eq!(state.get(&future.id).unwrap().expires_at.as_deref(), Some("2999-01-01"));
+    assert_eq!(state.get(&expired.id).unwrap().expires_at.as_deref(), Some("2019-12-31T16:00:00Z"));
+
+    let recall = |state: &mut NamespaceState| -> Vec<String> {
+        let mut ids: Vec<String> = state
+            .recall(RecallArgs {
+                namespace: "u1/p1".to_string(),
+                keywords: vec!["值班".to_string()],
+                limit: 20,
+                ..Default::default()
+            })
+            .unwrap()
+            .items
+            .into_iter()
+            .map(|x| x.id)
+            .collect();
+        ids.sort();
+        ids
+    };
+    let mut live = vec![kept.id.clone(), future.id.clone()];
+    live.sort();
+    assert_eq!(recall(&mut state), live);
+
+    let before = fs::metadata(&paths.memories_path).unwrap().len();
+    assert_eq!(state.purge_expired().unwrap(), vec![expired.id.clone()]);
+    assert!(fs::metadata(&paths.memories_path).unwrap().len() < before);
+    let content = fs::read_to_string(&paths.memories_path).unwrap();
+    assert!(!content.contains(&expired.id));
+    assert!(state.history(&expired.id).is_err());
+    assert_eq!(recall(&mut state), live);
+    assert!(state.purge_expired().unwrap().is_empty());
+
+    // 撤销过期时间后不再被清除。
+    state
+        .apply_transaction(vec![TransactionStep::Update(UpdateArgs {
+            id: future.id.clone(),
+            revision: 1,
+            expires_at: Some(String::new()),
+            ..Default::default()
+        })])
+        .unwrap();
+    assert!(state.get(&future.id).unwrap().expires_at.is_none());
+    let mut reopened = NamespaceState::open(paths).unwrap();
+    assert_eq!(recall(&mut reopened), live);
+}
+
+#[test]
+fn pinned_memories_should_surface_first_up_to_cap() {
+    let temp = tempfile::tempdir().unwrap();
+    let paths = StorePaths::new(temp.path(), "u1/p1").unwrap();
+    let options = StoreOptions {
+        recall_pinned_max: Some(2),
+        ..Default::default()
+    };
+    let mut state = NamespaceState::open_with(paths.clone(), options).unwrap();
+    let mut ids: Vec<String> = Vec::new();
+    for (i, day) in ["2024-01-01", "2024-02-01", "2024-03-01", "2024-04-01", "2024-05-01"].iter().enumerate() {
+        let id = state
+            .append_memory(RememberArgs {
+                namespace: "u1/p1".to_string(),
+                keywords: vec!["部署".to_string()],
+                slice: format!("s{i}"),
+                diary: "d".to_string(),
+                occurred_at: Some(day.to_string()),
+                importance: Some(if i == 4 { 5 } else { 1 }),
+                pinned: i == 0,
+                ..Default::default()
+            })
+            .unwrap()
+            .id;
+        ids.push(id);
+    }
+    assert_eq!(state.set_pinned(&ids[1], true).unwrap(), 2);
+    assert_eq!(state.set_pinned(&ids[1], true).unwrap(), 2);
+    assert_eq!(state.set_pinned(&ids[2], true).unwrap(), 2);
+
+    let recall = |state: &mut NamespaceState, keywords: Vec<String>| -> Vec<String> {
+        state
+            .recall(RecallArgs {
+                namespace: "u1/p1".to_string(),
+                keywords,
+                limit: 20,
+                ..Default::default()
+            })
+            .unwrap()
+            .items
+            .into_iter()
+            .map(|x| x.id)
+            .collect()
+    };
+    // 无关键字：置顶（按时间由近到远，最多 2 条）在前，其余按时间。
+    let expected = vec![ids[2].clone(), ids[1].clone(), ids[4].clone(), ids[3].clone(), ids[0].clone()];
+    assert_eq!(recall(&mut state, vec![]), expected);
+    // 关键字：置顶优先于重要度。
+    let expected = vec![ids[2].clone(), ids[1].clone(), ids[4].clone(), ids[3].clone(), ids[0].clone()];
+    assert_eq!(recall(&mut state, vec!["部署".to_string()]), expected);
+
+    assert_eq!(state.set_pinned(&ids[2], false).unwrap(), 3);
+    let mut reopened = NamespaceState::open_with(
+        paths,
+        StoreOptions {
+            recall_pinned_max: Some(2),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let expected = vec![ids[1].clone(), ids[0].clone(), ids[4].clone(), ids[3].clone(), ids[2].clone()];
+    assert_eq!(recall(&mut reopened, vec![]), expected);
+    assert!(reopened.get(&ids[0]).unwrap().pinned);
+    assert!(!reopened.get(&ids[2]).unwrap().pinned);
+}
+
+#[test]
+fn archived_memories_should_be_skipped_unless_requested() {
+    let temp = tempfile::tempdir().unwrap();
+    let paths = StorePaths::new(temp.path(), "u1/p1").unwrap();
+    let mut state = NamespaceState::open(paths.clone()).unwrap();
+    let mut ids: Vec<String> = Vec::new();
+    for day in ["2024-01-10", "2024-02-10", "2024-06-10"] {
+        let id = state
+            .append_memory(RememberArgs {
+                namespace: "u1/p1".to_string(),
+                keywords: vec!["周报".to_string()],
+                slice: day.to_string(),
+                diary: "d".to_string(),
+                occurred_at: Some(day.to_string()),
+                ..Default::default()
+            })
+            .unwrap()
+            .id;
+        ids.push(id);
+    }
+
+    let recall = |state: &mut NamespaceState, include_archived: bool| -> Vec<(String, bool)> {
+        state
+            .recall(RecallArgs {
+                namespace: "u1/p1".to_string(),
+                include_archived,
+                limit: 20,
+                ..Default::default()
+            })
+            .unwrap()
+            .items
+            .into_iter()
+            .map(|x| (x.id, x.archived))
+            .collect()
+    };
+
+    let changed = state.set_archived(&[], Some("2024-01-01"), Some("2024-02-29"), true).unwrap();
+    assert_eq!(changed.len(), 2);
+    assert!(changed.iter().all(|x| x.revision == Some(2)));
+    assert_eq!(recall(&mut state, false), vec![(ids[2].clone(), false)]);
+    assert_eq!(
+        recall(&mut state, true),
+        vec![(ids[2].clone(), false), (ids[1].clone(), true), (ids[0].clone(), true)]
+    );
+    assert!(state.get(&ids[0]).unwrap().archived);
+
+    // 已归档的条目不重复写入；范围与 ids 取并集。
+    let changed = state.set_archived(&[ids[2].clone()], None, Some("2024-01-31"), true).unwrap();
+    assert_eq!(changed.iter().map(|x| x.id.as_str()).collect::<Vec<_>>(), vec![ids[2].as_str()]);
+    assert!(recall(&mut state, false).is_empty());
+
+    let changed = state.set_archived(&[ids[1].clone()], None, None, false).unwrap();
+    assert_eq!(changed[0].revision, Some(3));
+    let mut reopened = NamespaceState::open(paths).unwrap();
+    assert_eq!(recall(&mut reopened, false), vec![(ids[1].clone(), false)]);
+
+    assert!(reopened.set_archived(&[], None, None, true).is_err());
+    assert!(reopened.set_archived(&["missing".to_string()], None, None, true).unwrap_err().contains("记忆不存在"));
+}
+
+#[test]
+fn decay_ranking_should_favor_fresh_and_frequently_recalled_memories() {
+    let temp = tempfile::tempdir().unwrap();
+    let paths = StorePaths::new(temp.path(), "u1/p1").unwrap();
+    let mut state = NamespaceState::open(paths.clone()).unwrap();
+    let now_ts = time::now_rfc3339_and_ts().1;
+    // 依次为 90 天前（重要度最高）、20 天前、1 天前的记忆。
+    let mut ids: Vec<String> = Vec::new();
+    for (days, importance) in [(90, 5), (20, 3), (1, 1)] {
+        let id = state
+            .append_memory(RememberArgs {
+                namespace: "u1/p1".to_string(),
+                keywords: vec!["部署".to_string()],
+                slice: format!("{days} 天前"),
+                diary: "d".to_string(),
+                occurred_at: Some(time::ts_to_rfc3339(now_ts - days * 86_400)),
+                importance: Some(importance),
+                ..Default::default()
+            })
+            .unwrap()
+            .id;
+        ids.push(id);
+    }
+    let (old, mid, fresh) = (ids[0].clone(), ids[1].clone(), ids[2].clone());
+
+    let recall = |state: &mut NamespaceState, keywords: Vec<String>, ranking: Option<RecallRanking>| -> Vec<String> {
+        state
+            .recall(RecallArgs {
+                namespace: "u1/p1".to_string(),
+                keywords,
+                ranking,
+                half_life_days: Some(60.0),
+                limit: 20,
+                ..Default::default()
+            })
+            .unwrap()
+            .items
+            .into_iter()
+            .map(|x| x.id)
+            .collect()
+    };
+    let kw = || vec!["部署".to_string()];
+    assert_eq!(recall(&mut state, kw(), None), vec![old.clone(), mid.clone(), fresh.clone()]);
+    assert_eq!(
+        recall(&mut state, kw(), Some(RecallRanking::Decay)),
+        vec![fresh.clone(), mid.clone(), old.clone()]
+    );
+
+    // 常被召回的旧记忆获得访问加成：0.5^1.5 × (1 + ln 21) ≈ 1.43 > 0.5^(1/60) ≈ 0.99。
+    for _ in 0..20 {
+        state.record_recalled(std::slice::from_ref(&old));
+    }
+    assert_eq!(
+        recall(&mut state, kw(), Some(RecallRanking::Decay)),
+        vec![old.clone(), fresh.clone(), mid.clone()]
+    );
+
+    // 服务端默认 decay：未指定 ranking 的无关键字召回同样按衰减得分排序。
+    state.flush().unwrap();
+    let mut reopened = NamespaceState::open_with(
+        paths,
+        StoreOptions {
+            recall_ranking: RecallRanking::Decay,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(recall(&mut reopened, vec![], None), vec![old, fresh, mid]);
+}
+
+#[test]
+fn decay_importance_should_lower_old_unrecalled_memories_once() {
+    let temp = tempfile::tempdir().unwrap();
+    let paths = StorePaths::new(temp.path(), "u1/p1").unwrap();
+    let mut state = NamespaceState::open(paths).unwrap();
+    let now_ts = time::now_rfc3339_and_ts().1;
+    let mut remember = |days: i64, importance: Option<u8>, pinned: bool| -> String {
+        state
+            .append_memory(RememberArgs {
+                namespace: "u1/p1".to_string(),
+                keywords: vec!["k".to_string()],
+                slice: format!("{days} 天前"),
+                diary: "d".to_string(),
+                occurred_at: Some(time::ts_to_rfc3339(now_ts - days * 86_400)),
+                importance,
+                pinned,
+                ..Default::default()
+            })
+            .unwrap()
+            .id
+    };
+    let stale = remember(89, Some(5), false);
+    let recalled = remember(180, Some(4), false);
+    remember(180, Some(5), true);
+    remember(1, Some(5), false);
+    remember(180, None, false);
+    state.record_recalled(std::slice::from_ref(&recalled));
+
+    // 5 × 0.5^(89/90) ≈ 2.5 → 3（不取整 90 天：写入耗时超过一秒就会舍入为 2）；被召回过、置顶、新近与未设重要度的记忆不变。
+    let preview = state.decay_importance(90.0, true).unwrap();
+    assert_eq!(preview.len(), 1);
+    assert_eq!((preview[0].id.as_str(), preview[0].from, preview[0].to), (stale.as_str(), 5, 3));
+    assert_eq!(preview[0].revision, None);
+    assert_eq!(state.get(&stale).unwrap().importance, Some(5));
+
+    let applied = state.decay_importance(90.0, false).unwrap();
+    assert_eq!(applied[0].revision, Some(2));
+    let current = state.get(&stale).unwrap();
+    assert_eq!((current.importance, current.revision), (Some(3), 2));
+
+    // 衰减后 updated_at 刷新，再次执行不会重复衰减。
+    assert!(state.decay_importance(90.0, false).unwrap().is_empty());
+    assert!(state.decay_importance(0.0, true).is_err());
+}
+
+#[test]
+fn compact_should_keep_only_live_heads_and_reindex() {
+    let temp = tempfile::tempdir().unwrap();
+    let paths = StorePaths::new(temp.path(), "u1/p1").unwrap();
+    let mut state = NamespaceState::open(paths.clone()).unwrap();
+    let remember = |state: &mut NamespaceState, slice: &str, expires_at: Option<&str>| -> String {
+        state
+            .append_memory(RememberArgs {
+                namespace: "u1/p1".to_string(),
+                keywords: vec!["部署".to_string()],
+                slice: slice.to_string(),
+                diary: "d".to_string(),
+                expires_at: expires_at.map(str::to_string),
+                ..Default::default()
+            })
+            .unwrap()
+            .id
+    };
+    let edited = remember(&mut state, "v1", None);
+    let forgotten = remember(&mut state, "将删除", None);
+    let kept = remember(&mut state, "保留", None);
+    remember(&mut state, "已过期", Some("2020-01-01"));
+    for revision in 1..=2 {
+        state
+            .apply_transaction(vec![TransactionStep::Update(UpdateArgs {
+                id: edited.clone(),
+                revision,
+                slice: Some(format!("v{}", revision + 1)),
+                ..Default::default()
+            })])
+            .unwrap();
+    }
+    state
+        .apply_transaction(vec![TransactionStep::Forget {
+            id: forgotten.clone(),
+            revision: 1,
+        }])
+        .unwrap();
+
+    // 保留 edited、kept 的当前版本与 forgotten 的删除标记（供 sync 传播删除）。
+    let report = state.compact().unwrap();
+    assert_eq!((report.records_before, report.records_after), (7, 3));
+    assert!(report.bytes_after < report.bytes_before);
+    assert_eq!(fs::read_to_string(&paths.memories_path).unwrap().lines().count(), 3);
+
+    // 文件与索引都只剩当前版本，偏移随之更新。
+    let mut reopened = NamespaceState::open(paths).unwrap();
+    for state in [&mut state, &mut reopened] {
+        let current = state.get(&edited).unwrap();
+        assert_eq!((current.slice.as_str(), current.revision), ("v3", 3));
+        assert_eq!(state.history(&edited).unwrap().versions.len(), 1);
+        assert!(state.get(&forgotten).is_err());
+        let mut ids: Vec<String> = state
+            .recall(RecallArgs {
+                namespace: "u1/p1".to_string(),
+                keywords: vec!["部署".to_string()],
+                limit: 20,
+                ..Default::default()
+            })
+            .unwrap()
+            .items
+            .into_iter()
+            .map(|x| x.id)
+            .collect();
+        ids.sort();
+        let mut expected = vec![edited.clone(), kept.clone()];
+        expected.sort();
+        assert_eq!(ids, expected);
+    }
+}
+
+#[test]
+fn segment_rotation_should_keep_offsets_and_compact_only_active_file() {
+    let temp = tempfile::tempdir().unwrap();
+    let paths = StorePaths::new(temp.path(), "u1/p1").unwrap();
+    let remember = |state: &mut NamespaceState, slice: &str, expires_at: Option<&str>| -> String {
+        state
+            .append_memory(RememberArgs {
+                namespace: "u1/p1".to_string(),
+                keywords: vec!["部署".to_string()],
+                slice: slice.to_string(),
+                diary: "d".to_string(),
+                expires_at: expires_at.map(str::to_string),
+                ..Default::default()
+            })
+            .unwrap()
+            .id
+    };
+
+    // 每次写入后都轮转：两条记忆各自封存为一个分段。
+    let mut rotating = NamespaceState::open_with(
+        paths.clone(),
+        StoreOptions {
+            segment_max_bytes: Some(1),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let edited = remember(&mut rotating, "v1", None);
+    let forgotten = remember(&mut rotating, "将删除", None);
+    assert_eq!(rotating.stats().unwrap().segments, 2);
+    assert_eq!(fs::metadata(&paths.memories_path).unwrap().len(), 0);
+    let sealed: Vec<Vec<u8>> = (1..=2)
+        .map(|n| fs::read(paths.namespace_dir.join(segment_file_name(n))).unwrap())
+        .collect();
+
+    // 不轮转的实例继续写入活动文件；按全局偏移读取分段中的旧记录。
+    let mut state = NamespaceState::open(paths.clone()).unwrap();
+    assert_eq!(state.get(&forgotten).unwrap().slice, "将删除");
+    for revision in 1..=2 {
+        state
+            .apply_transaction(vec![TransactionStep::Update(UpdateArgs {
+                id: edited.clone(),
+                revision,
+                slice: Some(format!("v{}", revision + 1)),
+                ..Default::default()
+            })])
+            .unwrap();
+    }
+    state
+        .apply_transaction(vec![TransactionStep::Forget {
+            id: forgotten.clone(),
+            revision: 1,
+        }])
+        .unwrap();
+    let kept = remember(&mut state, "保留", None);
+    remember(&mut state, "已过期", Some("2020-01-01"));
+    assert_eq!(state.history(&edited).unwrap().versions.len(), 3);
+
+    // compact 只改写活动文件：分段中仍有旧版本的删除标记需保留，过期记忆直接丢弃。
+    let report = state.compact().unwrap();
+    assert_eq!((report.records_before, report.records_after), (5, 3));
+    for (n, bytes) in sealed.iter().enumerate() {
+        assert_eq!(&fs::read(paths.namespace_dir.join(segment_file_name(n + 1))).unwrap(), bytes);
+    }
+
+    // 轮转实例经增量同步看到新的写入。
+    let mut reopened = NamespaceState::open(paths).unwrap();
+    for state in [&mut state, &mut reopened, &mut rotating] {
+        assert_eq!(state.get(&edited).unwrap().slice, "v3");
+        assert_eq!(state.history(&edited).unwrap().versions.len(), 2);
+        assert!(state.get(&forgotten).is_err());
+        let mut ids: Vec<String> = state
+            .recall(RecallArgs {
+                namespace: "u1/p1".to_string(),
+                keywords: vec!["部署".to_string()],
+                limit: 20,
+                ..Default::default()
+            })
+            .unwrap()
+            .items
+            .into_iter()
+            .map(|x| x.id)
+            .collect();
+        ids.sort();
+        let mut expected = vec![edited.clone(), kept.clone()];
+        expected.sort();
+        assert_eq!(ids, expected);
+    }
+}
+
+#[test]
+fn index_checksum_should_detect_same_length_rewrite_and_rebuild() {
+    let temp = tempfile::tempdir().unwrap();
+    let paths = StorePaths::new(temp.path(), "u1/p1").unwrap();
+    let mut state = NamespaceState::open(paths.clone()).unwrap();
+    for kw in ["alpha", "bravo"] {
+        state
+            .append_memory(RememberArgs {
+                namespace: "u1/p1".to_string(),
+                keywords: vec![kw.to_string()],
+                slice: format!("slice-{kw}"),
+                ..Default::default()
+            })
+            .unwrap();
+    }
+    drop(state);
+    let index_text = fs::read_to_string(&paths.index_path).unwrap();
+    let expected = serde_json::from_str::<IndexData>(&index_text).unwrap().data_checksum;
+    assert!(expected.is_some());
+
+    // 旧索引没有校验和：打开时补算，与增量维护的值一致。
+    let mut legacy: Value = serde_json::from_str(&index_text).unwrap();
+    legacy.as_object_mut().unwrap().remove("data_checksum");
+    fs::write(&paths.index_path, legacy.to_string()).unwrap();
+    let mut state = NamespaceState::open(paths.clone()).unwrap();
+    assert_eq!(state.index.data_checksum, expected);
+    assert_eq!(state.list_keywords(KeywordSort::Alpha).unwrap(), vec!["alpha", "bravo"]);
+    drop(state);
+
+    // 等长改写：长度不变，只有校验和能发现索引已过期。
+    let text = fs::read_to_string(&paths.memories_path).unwrap();
+    fs::write(&paths.memories_path, text.replace("\"alpha\"", "\"gamma\"")).unwrap();
+    let mut state = NamespaceState::open(paths).unwrap();
+    assert_ne!(state.index.data_checksum, expected);
+    assert_eq!(state.list_keywords(KeywordSort::Alpha).unwrap(), vec!["bravo", "gamma"]);
+}
+
+#[test]
+fn verify_should_report_bad_entries_duplicates_and_torn_tail_then_repair() {
+    let temp = tempfile::tempdir().unwrap();
+    let paths = StorePaths::new(temp.path(), "u1/p1").unwrap();
+    let mut state = NamespaceState::open(paths.clone()).unwrap();
+    for kw in ["alpha", "bravo"] {
+        state
+            .append_memory(RememberArgs {
+                namespace: "u1/p1".to_string(),
+                keywords: vec![kw.to_string()],
+                slice: format!("slice-{kw}"),
+                ..Default::default()
+            })
+            .unwrap();
+    }
+    let report = state.verify(false).unwrap();
+    assert!(report.is_clean(), "{report:?}");
+    assert_eq!(report.entries, 2);
+
+    // 外部重复追加第一行，并留下一条写入中断的残缺记录。
+    let text = fs::read_to_string(&paths.memories_path).unwrap();
+    let first = text.lines().next().unwrap().to_string();
+    let first_id = serde_json::from_str::<MemoryItem>(&first).unwrap().id;
+    fs::write(&paths.memories_path, format!("{text}{first}\n{{\"id\":\"torn")).unwrap();
+
+    let report = state.verify(false).unwrap();
+    assert_eq!(report.duplicate_ids, vec![first_id.clone()]);
+    assert_eq!(report.truncated_tail_bytes, "{\"id\":\"torn".len() as u64);
+    assert!(report.bad_entries.is_empty());
+    assert!(report.repaired.is_empty());
+
+    // 索引条目指向错误的偏移。
+    state.index.items[1].offset += 1;
+    let report = state.verify(false).unwrap();
+    assert_eq!(report.bad_entries.len(), 1);
+    assert_eq!(report.bad_entries[0].offset, state.index.items[1].offset);
+
+    let report = state.verify(true).unwrap();
+    assert_eq!(report.repaired, vec!["truncated_tail:11", "rebuild_index"]);
+    assert!(fs::read_to_string(&paths.memories_path).unwrap().ends_with('\n'));
+
+    // 重复记录只报告，不改动数据。
+    let report = state.verify(false).unwrap();
+    assert!(report.bad_entries.is_empty() && report.orphaned_postings == 0 && report.truncated_tail_bytes == 0);
+    assert_eq!(report.duplicate_ids, vec![first_id.clone()]);
+    assert_eq!(state.get(&first_id).unwrap().keywords, vec!["alpha"]);
+}
+
+#[test]
+fn concurrent_writers_should_append_after_each_other_under_the_lock() {
+    let temp = tempfile::tempdir().unwrap();
+    let paths = StorePaths::new(temp.path(), "u1/p1").unwrap();
+    let args = |slice: &str| RememberArgs {
+        namespace: "u1/p1".to_string(),
+        keywords: vec!["并发".to_string()],
+        slice: slice.to_string(),
+        diary: "d".to_string(),
+        ..Default::default()
+    };
+
+    let mut state = NamespaceState::open(paths.clone()).unwrap();
+    state.append_memory(args("先写入")).unwrap();
+
+    // 另一个进程持有写入锁期间追加一批：本进程的写入需等它完成后再读取文件末尾。
+    let other = build_new_item("u1/p1", args("其他进程"), None).unwrap();
+    let other_line = LogRecord::Item(Box::new(other.clone())).to_line().unwrap();
+    let lock = journal::lock(&paths).unwrap();
+    let mine = std::thread::scope(|scope| {
+        let writer = scope.spawn(|| state.append_memory(args("本进程")).unwrap());
+        std::thread::sleep(std::time::Duration::from_millis(200));
+        let mut file = OpenOptions::new().append(true).open(&paths.memories_path).unwrap();
+        file.write_all(&other_line).unwrap();
+        drop(lock);
+        writer.join().unwrap()
+    });
+
+    let text = fs::read_to_string(&paths.memories_path).unwrap();
+    let slices: Vec<String> = text
+        .lines()
+        .map(|line| serde_json::from_str::<MemoryItem>(line).unwrap().slice)
+        .collect();
+    assert_eq!(slices, vec!["先写入", "其他进程", "本进程"]);
+    // 写入后的索引直接指向本批在文件中的真实位置，并已补上其他进程的记录。
+    let item = &state.index.items[state.index.heads[&mine.id] as usize];
+    assert_eq!(item.offset as usize, text.len() - item.length as usize);
+    assert_eq!(state.index.indexed_up_to_offset as usize, text.len());
+    assert!(state.index.heads.contains_key(&other.id));
+
+    let mut reopened = NamespaceState::open(paths).unwrap();
+    for state in [&mut state, &mut reopened] {
+        assert_eq!(state.get(&other.id).unwrap().slice, "其他进程");
+        assert_eq!(state.get(&mine.id).unwrap().slice, "本进程");
+        let total = state
+            .recall(RecallArgs {
+                namespace: "u1/p1".to_string(),
+                keywords: vec!["并发".to_string()],
+                limit: 20,
+                ..Default::default()
+            })
+            .unwrap()
+            .total;
+        assert_eq!(total, 3);
+    }
+}
+
+#[test]
+fn rotation_should_follow_other_writers_and_recover_interrupted_renames() {
+    let temp = tempfile::tempdir().unwrap();
+    let paths = StorePaths::new(temp.path(), "u1/p1").unwrap();
+    let options = StoreOptions {
+        segment_max_bytes: Some(1),
+        ..Default::default()
+    };
+    let remember = |state: &mut NamespaceState, slice: &str| -> String {
+        state
+            .append_memory(RememberArgs {
+                namespace: "u1/p1".to_string(),
+                keywords: vec!["轮转".to_string()],
+                slice: slice.to_string(),
+                diary: "d".to_string(),
+                ..Default::default()
+            })
+            .unwrap()
+            .id
+    };
+
+    // 两个实例各自轮转：后写入者按磁盘上的清单编号，不会与对方的分段冲突。
+    let mut a = NamespaceState::open_with(paths.clone(), options.clone()).unwrap();
+    let mut b = NamespaceState::open_with(paths.clone(), options.clone()).unwrap();
+    let mut ids = vec![remember(&mut a, "a1"), remember(&mut b, "b1"), remember(&mut a, "a2")];
+    assert_eq!(a.stats().unwrap().segments, 3);
+    assert_eq!(fs::metadata(&paths.memories_path).unwrap().len(), 0);
+
+    // 模拟另一个进程在“改名”之后、“写清单”之前退出。
+    let mut plain = NamespaceState::open(paths.clone()).unwrap();
+    ids.push(remember(&mut plain, "中断前"));
+    fs::rename(&paths.memories_path, paths.namespace_dir.join(segment_file_name(4))).unwrap();
+
+    // 仍在运行的实例写入时补记该分段；重新打开后同样可见。
+    ids.push(remember(&mut plain, "中断后"));
+    assert_eq!(plain.stats().unwrap().segments, 4);
+    let mut reopened = NamespaceState::open(paths).unwrap();
+    for state in [&mut plain, &mut reopened, &mut a, &mut b] {
+        for id in &ids {
+            state.get(id).unwrap();
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use crate::memory::export::{self, ExportDump, ExportRecord};
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap};
+
+/// 同步中胜出的一方。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncSide {
+    Local,
+    Remote,
+}
+
+/// 两侧都改动过同一 id 时的裁决（报告中的一项）。
+#[derive(Debug, Clone, Serialize)]
+pub struct SyncConflict {
+    pub id: String,
+    pub winner: SyncSide,
+    /// 胜出的一方是删除标记。
+    pub deleted: bool,
+}
+
+/// 一个 namespace 的同步计划：各自需要从对方补齐的记录（记忆当前版本或删除标记）。
+#[derive(Debug, Clone, Default)]
+pub struct SyncPlan {
+    pub to_local: Vec<ExportRecord>,
+    pub to_remote: Vec<ExportRecord>,
+    pub conflicts: Vec<SyncConflict>,
+}
+
+/// 单个 namespace 的同步结果。
+#[derive(Debug, Clone, Serialize)]
+pub struct NamespaceSyncReport {
+    pub namespace: String,
+    /// 从远端写入本地的记录数（含删除标记）。
+    pub pulled: usize,
+    /// 从本地写入远端的记录数（含删除标记）。
+    pub pushed: usize,
+    pub conflicts: Vec<SyncConflict>,
+}
+
+/// 对比两侧按 id 折叠后的最终状态（`export::collect` 的 `since=0` 结果），得出双向补齐的记录。
+///
+/// - 只在一侧存在的记忆复制到另一侧；只在一侧存在的删除标记无需传播（另一侧从未有过该记忆）。
+/// - 两侧都有但内容不同：最后变更时间（updated_at ?? recorded_at；删除标记取 deleted_at）较新的一方胜出，
+///   时间相同时删除优先，再依次比较修订号、checksum，保证两台机器各自执行得出相同结论。
+/// - 仅修订号不同（此前同步时在对方追加为新修订）视为已一致。
+pub fn plan(local: ExportDump, remote: ExportDump) -> SyncPlan {
+    let mut local: HashMap<String, ExportRecord> =
+        local.records.into_iter().map(|r| (r.id().to_string(), r)).collect();
+    let mut remote: HashMap<String, ExportRecord> =
+        remote.records.into_iter().map(|r| (r.id().to_string(), r)).collect();
+    let ids: BTreeSet<String> = local.keys().chain(remote.keys()).cloned().collect();
+
+    let mut plan = SyncPlan::default();
+    for id in ids {
+        match (local.remove(&id), remote.remove(&id)) {
+            (Some(record @ ExportRecord::Item(_)), None) => plan.to_remote.push(record),
+            (None, Some(record @ ExportRecord::Item(_))) => plan.to_local.push(record),
+            (Some(a), Some(b)) => {
+                if same_state(&a, &b) {
+                    continue;
+                }
+                let (winner, record) = if sync_key(&a) >= sync_key(&b) {
+                    (SyncSide::Local, a)
+                } else {
+                    (SyncSide::Remote, b)
+                };
+                plan.conflicts.push(SyncConflict {
+                    id,
+                    winner,
+                    deleted: matches!(record, ExportRecord::Delete(_)),
+                });
+                match winner {
+                    SyncSide::Local => plan.to_remote.push(record),
+                    SyncSide::Remote => plan.to_local.push(record),
+                }
+            }
+            _ => {}
+        }
+    }
+    plan
+}
+
+/// 两侧是否已一致：都已删除，或记忆内容相同（忽略修订号与 checksum）。
+fn same_state(a: &ExportRecord, b: &ExportRecord) -> bool {
+    match (a, b) {
+        (ExportRecord::Delete(_), ExportRecord::Delete(_)) => true,
+        (ExportRecord::Item(a), ExportRecord::Item(b)) => {
+            let mut a = a.as_ref().clone();
+            let mut b = b.as_ref().clone();
+            a.revision = 0;
+            b.revision = 0;
+            a.compute_checksum() == b.compute_checksum()
+        }
+        _ => false,
+    }
+}
+
+/// 冲突裁决的排序键：(最后变更时间, 是否删除, 修订号, checksum)。
+fn sync_key(record: &ExportRecord) -> (i64, bool, u32, String) {
+    let ts = export::change_ts(record).unwrap_or(i64::MIN);
+    match record {
+        ExportRecord::Item(item) => (ts, false, item.revision, item.compute_checksum()),
+        ExportRecord::Delete(_) => (ts, true, 0, String::new()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn dump(records: Vec<serde_json::Value>) -> ExportDump {
+        ExportDump::from_value(json!({
+            "format": export::EXPORT_FORMAT, "format_version": 1, "namespace": "u1/p1",
+            "exported_at": "2024-01-01T00:00:00Z", "next_seq": 0, "items": 0, "deletes": 0,
+            "records": records
+        }))
+        .unwrap()
+    }
+
+    fn item(id: &str, slice: &str, recorded_at: &str) -> serde_json::Value {
+        json!({
+            "id": id, "namespace": "u1/p1", "recorded_at": recorded_at,
+            "keywords": ["k"], "slice": slice, "diary": "d"
+        })
+    }
+
+    #[test]
+    fn plan_should_exchange_missing_ids_and_pick_newest_on_conflict() {
+        let local = dump(vec![
+            item("a", "只在本地", "2024-01-01T00:00:00Z"),
+            item("c", "本地旧版", "2024-01-01T00:00:00Z"),
+            item("d", "两侧相同", "2024-01-01T00:00:00Z"),
+            json!({ "op": "delete", "id": "e", "namespace": "u1/p1", "deleted_at": "2024-03-01T00:00:00Z" }),
+        ]);
+        let mut same = item("d", "两侧相同", "2024-01-01T00:00:00Z");
+        same["revision"] = json!(3);
+        let remote = dump(vec![
+            item("b", "只在远端", "2024-01-01T00:00:00Z"),
+            item("c", "远端新版", "2024-02-01T00:00:00Z"),
+            same,
+            item("e", "远端未删", "2024-02-01T00:00:00Z"),
+        ]);
+
+        let plan = plan(local, remote);
+        let ids = |records: &[ExportRecord]| records.iter().map(|r| r.id().to_string()).collect::<Vec<_>>();
+        assert_eq!(ids(&plan.to_local), vec!["b", "c"]);
+        assert_eq!(ids(&plan.to_remote), vec!["a", "e"]);
+        assert!(matches!(plan.to_remote[1], ExportRecord::Delete(_)));
+        let winners: Vec<(&str, SyncSide)> = plan.conflicts.iter().map(|c| (c.id.as_str(), c.winner)).collect();
+        assert_eq!(winners, vec![("c", SyncSide::Remote), ("e", SyncSide::Local)]);
+    }
+}
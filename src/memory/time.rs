@@ -1,4 +1,4 @@
-use chrono::{DateTime, Local, NaiveDate, TimeZone, Utc};
+use chrono::{DateTime, Datelike, Days, Local, Months, NaiveDate, TimeZone, Utc};
 
 #[derive(Debug, Clone, Copy)]
 pub enum DateBoundKind {
@@ -22,6 +22,28 @@ pub fn now_local_rfc3339_and_offset_seconds() -> (String, i32) {
     )
 }
 
+/// Unix 秒 -> HTTP-date（RFC 7231，例如 `Sun, 06 Nov 1994 08:49:37 GMT`）。
+pub fn ts_to_http_date(ts: i64) -> String {
+    Utc.timestamp_opt(ts, 0)
+        .single()
+        .unwrap_or_default()
+        .format("%a, %d %b %Y %H:%M:%S GMT")
+        .to_string()
+}
+
+/// Unix 秒 -> RFC3339（UTC，秒级）。
+pub fn ts_to_rfc3339(ts: i64) -> String {
+    Utc.timestamp_opt(ts, 0)
+        .single()
+        .unwrap_or_default()
+        .to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+}
+
+/// 文件时间等 SystemTime -> RFC3339（UTC，秒级）。
+pub fn system_time_to_rfc3339(t: std::time::SystemTime) -> String {
+    DateTime::<Utc>::from(t).to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+}
+
 pub fn parse_time_to_ts_and_canonical(
     input: &str,
     bound: DateBoundKind,
@@ -67,7 +89,208 @@ pub fn parse_time_to_ts_and_canonical(
         return Ok((dt.timestamp(), date.format("%Y-%m-%d").to_string()));
     }
 
-    Err("时间格式不支持：仅支持 RFC3339 或 YYYY-MM-DD".to_string())
+    // 周表达式（2025-W34 / 第34周 / 上周）、中文日期（含中文数字）与相对时间词（二〇二五年八月二十日 / 昨天 ...）
+    let today = Local::now().date_naive();
+    if let Some((start, end)) = parse_week_range(text, today).or_else(|| parse_zh_date_range(text, today)) {
+        let date = match bound {
+            DateBoundKind::Start => start,
+            DateBoundKind::End => end,
+        };
+        return date_bound_to_ts_and_canonical(date, bound);
+    }
+
+    Err("时间格式不支持：仅支持 RFC3339、YYYY-MM-DD、周（如 2025-W34、第34周）或中文日期（如 二〇二五年八月二十日、昨天、上周）".to_string())
+}
+
+fn date_bound_to_ts_and_canonical(date: NaiveDate, bound: DateBoundKind) -> Result<(i64, String), String> {
+    let (h, m, s) = match bound {
+        DateBoundKind::Start => (0, 0, 0),
+        DateBoundKind::End => (23, 59, 59),
+    };
+    let dt = Utc.from_utc_datetime(&date.and_hms_opt(h, m, s).ok_or_else(|| "无效日期".to_string())?);
+    Ok((dt.timestamp(), date.format("%Y-%m-%d").to_string()))
+}
+
+/// 解析中文日期/相对时间词，返回覆盖的日期区间（含首尾）。
+///
+/// 支持：`Y年M月D日|号`、`Y年M月`、`Y年`（年月日可用阿拉伯数字或中文数字），
+/// 以及 今天/昨天/前天/明天/后天、本月/上个月/下个月、今年/去年/前年/明年。
+/// 周相关写法见 [`parse_week_range`]。
+pub fn parse_zh_date_range(input: &str, today: NaiveDate) -> Option<(NaiveDate, NaiveDate)> {
+    let text: String = input.chars().filter(|c| !c.is_whitespace()).collect();
+    if text.is_empty() {
+        return None;
+    }
+
+    if let Some(range) = parse_zh_relative(&text, today) {
+        return Some(range);
+    }
+
+    let (y_part, rest) = text.split_once('年')?;
+    let year = parse_zh_year(y_part)?;
+
+    if rest.is_empty() {
+        let start = NaiveDate::from_ymd_opt(year, 1, 1)?;
+        let end = NaiveDate::from_ymd_opt(year, 12, 31)?;
+        return Some((start, end));
+    }
+
+    let (m_part, rest) = rest.split_once('月')?;
+    let month = parse_zh_small_number(m_part)?;
+    let month_start = NaiveDate::from_ymd_opt(year, month, 1)?;
+
+    if rest.is_empty() {
+        return Some((month_start, month_end(month_start)?));
+    }
+
+    let d_part = rest.strip_suffix('日').or_else(|| rest.strip_suffix('号'))?;
+    let day = parse_zh_small_number(d_part)?;
+    let date = NaiveDate::from_ymd_opt(year, month, day)?;
+    Some((date, date))
+}
+
+fn parse_zh_relative(text: &str, today: NaiveDate) -> Option<(NaiveDate, NaiveDate)> {
+    let day = |offset: i64| -> Option<(NaiveDate, NaiveDate)> {
+        let d = shift_days(today, offset)?;
+        Some((d, d))
+    };
+    let month = |offset: i32| -> Option<(NaiveDate, NaiveDate)> {
+        let first = today.with_day(1)?;
+        let start = if offset >= 0 {
+            first.checked_add_months(Months::new(offset as u32))?
+        } else {
+            first.checked_sub_months(Months::new(offset.unsigned_abs()))?
+        };
+        Some((start, month_end(start)?))
+    };
+    let year = |offset: i32| -> Option<(NaiveDate, NaiveDate)> {
+        let y = today.year() + offset;
+        Some((NaiveDate::from_ymd_opt(y, 1, 1)?, NaiveDate::from_ymd_opt(y, 12, 31)?))
+    };
+
+    match text {
+        "今天" | "今日" => day(0),
+        "昨天" | "昨日" => day(-1),
+        "前天" => day(-2),
+        "明天" | "明日" => day(1),
+        "后天" => day(2),
+        "本月" | "这个月" => month(0),
+        "上个月" | "上月" => month(-1),
+        "下个月" | "下月" => month(1),
+        "今年" => year(0),
+        "去年" => year(-1),
+        "前年" => year(-2),
+        "明年" => year(1),
+        _ => None,
+    }
+}
+
+/// 解析周表达式，返回该周周一 ~ 周日（ISO 周）。
+///
+/// 支持：`2025-W34`、`第34周`（当前 ISO 年）、`2025年第34周`，以及 本周/这周/上周/下周。
+pub fn parse_week_range(input: &str, today: NaiveDate) -> Option<(NaiveDate, NaiveDate)> {
+    let text: String = input.chars().filter(|c| !c.is_whitespace()).collect();
+
+    let relative = match text.as_str() {
+        "本周" | "这周" => Some(0),
+        "上周" => Some(-1),
+        "下周" => Some(1),
+        _ => None,
+    };
+    if let Some(offset) = relative {
+        let monday = shift_days(today, -(today.weekday().num_days_from_monday() as i64))?;
+        let start = shift_days(monday, offset * 7)?;
+        return Some((start, shift_days(start, 6)?));
+    }
+
+    let (year, week) = if let Some((y, w)) = text.split_once("-W").or_else(|| text.split_once("-w")) {
+        if y.len() != 4 || !y.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        if w.is_empty() || w.len() > 2 || !w.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        (y.parse().ok()?, w.parse().ok()?)
+    } else {
+        let (year_part, rest) = match text.split_once('年') {
+            Some((y, rest)) => (Some(y), rest),
+            None => (None, text.as_str()),
+        };
+        let num = rest.strip_prefix('第')?.strip_suffix('周')?;
+        let year = match year_part {
+            Some(y) => parse_zh_year(y)?,
+            None => today.iso_week().year(),
+        };
+        (year, parse_zh_small_number(num)?)
+    };
+
+    let start = NaiveDate::from_isoywd_opt(year, week, chrono::Weekday::Mon)?;
+    Some((start, shift_days(start, 6)?))
+}
+
+fn shift_days(date: NaiveDate, offset: i64) -> Option<NaiveDate> {
+    if offset >= 0 {
+        date.checked_add_days(Days::new(offset as u64))
+    } else {
+        date.checked_sub_days(Days::new(offset.unsigned_abs()))
+    }
+}
+
+fn month_end(month_start: NaiveDate) -> Option<NaiveDate> {
+    month_start
+        .checked_add_months(Months::new(1))?
+        .checked_sub_days(Days::new(1))
+}
+
+fn zh_digit(c: char) -> Option<u32> {
+    match c {
+        '0'..='9' => c.to_digit(10),
+        '〇' | '○' | '零' => Some(0),
+        '一' => Some(1),
+        '二' | '两' => Some(2),
+        '三' => Some(3),
+        '四' => Some(4),
+        '五' => Some(5),
+        '六' => Some(6),
+        '七' => Some(7),
+        '八' => Some(8),
+        '九' => Some(9),
+        _ => None,
+    }
+}
+
+/// 年份：4 位阿拉伯数字或逐位中文数字（如 二〇二五）。
+pub fn parse_zh_year(text: &str) -> Option<i32> {
+    let chars: Vec<char> = text.chars().collect();
+    if chars.len() != 4 {
+        return None;
+    }
+
+    let mut y: i32 = 0;
+    for c in chars {
+        y = y * 10 + zh_digit(c)? as i32;
+    }
+    (1..=9999).contains(&y).then_some(y)
+}
+
+/// 月/日等小数字：阿拉伯数字，或 一~九、十、十X、X十、X十Y 形式的中文数字。
+pub fn parse_zh_small_number(text: &str) -> Option<u32> {
+    if text.is_empty() {
+        return None;
+    }
+    if text.chars().all(|c| c.is_ascii_digit()) {
+        return text.parse().ok();
+    }
+
+    let chars: Vec<char> = text.chars().collect();
+    match chars.as_slice() {
+        ['十'] => Some(10),
+        [d] => zh_digit(*d).filter(|&n| n > 0),
+        ['十', d] => Some(10 + zh_digit(*d)?),
+        [t, '十'] => Some(zh_digit(*t)? * 10),
+        [t, '十', d] => Some(zh_digit(*t)? * 10 + zh_digit(*d)?),
+        _ => None,
+    }
 }
 
 fn patch_rfc3339_case(text: &str) -> Option<String> {
@@ -115,4 +338,70 @@ mod tests {
         assert_eq!(ts1, ts2);
         assert_eq!(c1, c2);
     }
+
+    #[test]
+    fn parse_zh_date_range_should_accept_chinese_numerals() {
+        let today = NaiveDate::from_ymd_opt(2025, 8, 20).unwrap();
+        let d = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+
+        assert_eq!(
+            parse_zh_date_range("二〇二五年八月", today),
+            Some((d(2025, 8, 1), d(2025, 8, 31)))
+        );
+        assert_eq!(
+            parse_zh_date_range("二〇二五年八月二十一日", today),
+            Some((d(2025, 8, 21), d(2025, 8, 21)))
+        );
+        assert_eq!(
+            parse_zh_date_range("2025 年 2 月", today),
+            Some((d(2025, 2, 1), d(2025, 2, 28)))
+        );
+        assert_eq!(parse_zh_date_range("二〇二五年十三月", today), None);
+    }
+
+    #[test]
+    fn parse_zh_date_range_should_resolve_relative_terms() {
+        // 2025-08-20 是周三
+        let today = NaiveDate::from_ymd_opt(2025, 8, 20).unwrap();
+        let d = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+
+        assert_eq!(parse_zh_date_range("昨天", today), Some((d(2025, 8, 19), d(2025, 8, 19))));
+        assert_eq!(parse_zh_date_range("上个月", today), Some((d(2025, 7, 1), d(2025, 7, 31))));
+        assert_eq!(parse_zh_date_range("去年", today), Some((d(2024, 1, 1), d(2024, 12, 31))));
+    }
+
+    #[test]
+    fn parse_week_range_should_follow_iso_weeks() {
+        // 2025-08-20 是周三，位于 2025-W34
+        let today = NaiveDate::from_ymd_opt(2025, 8, 20).unwrap();
+        let d = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+        let w34 = Some((d(2025, 8, 18), d(2025, 8, 24)));
+
+        assert_eq!(parse_week_range("2025-W34", today), w34);
+        assert_eq!(parse_week_range("第34周", today), w34);
+        assert_eq!(parse_week_range("二〇二五年第三十四周", today), w34);
+        assert_eq!(parse_week_range("本周", today), w34);
+        assert_eq!(parse_week_range("上周", today), Some((d(2025, 8, 11), d(2025, 8, 17))));
+        // ISO 周可能跨年：2025-W01 从 2024-12-30 开始
+        assert_eq!(parse_week_range("2025-W01", today), Some((d(2024, 12, 30), d(2025, 1, 5))));
+        assert_eq!(parse_week_range("2025-W54", today), None);
+    }
+
+    #[test]
+    fn parse_time_should_accept_zh_date_bounds() {
+        let (_, start) = parse_time_to_ts_and_canonical("二〇二五年八月", DateBoundKind::Start).unwrap();
+        let (_, end) = parse_time_to_ts_and_canonical("二〇二五年八月", DateBoundKind::End).unwrap();
+        assert_eq!(start, "2025-08-01");
+        assert_eq!(end, "2025-08-31");
+
+        let (_, start) = parse_time_to_ts_and_canonical("2025-W34", DateBoundKind::Start).unwrap();
+        let (_, end) = parse_time_to_ts_and_canonical("2025-W34", DateBoundKind::End).unwrap();
+        assert_eq!(start, "2025-08-18");
+        assert_eq!(end, "2025-08-24");
+    }
+
+    #[test]
+    fn ts_to_http_date_should_match_rfc7231() {
+        assert_eq!(ts_to_http_date(784111777), "Sun, 06 Nov 1994 08:49:37 GMT");
+    }
 }
@@ -0,0 +1,169 @@
+use crate::memory::rotation::DataFiles;
+use crate::memory::time;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+
+/// WAL 条目类型：base 为某一时刻 memories.jsonl 的完整快照；commit 为一次追加写入的记录。
+pub const WAL_KIND_BASE: &str = "base";
+pub const WAL_KIND_COMMIT: &str = "commit";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WalEntry {
+    pub ts: String,
+    pub ts_unix: i64,
+    pub kind: String,
+    /// memories.jsonl 中的原始行（不含换行符）。
+    pub lines: Vec<String>,
+}
+
+/// 追加一条 WAL 条目，返回写入前的文件长度（用于失败回滚）。
+pub fn append_entry(wal_path: &Path, kind: &str, lines: Vec<String>) -> Result<u64, String> {
+    let (ts, ts_unix) = time::now_rfc3339_and_ts();
+    let entry = WalEntry {
+        ts,
+        ts_unix,
+        kind: kind.to_string(),
+        lines,
+    };
+
+    let mut line =
+        serde_json::to_vec(&entry).map_err(|e| format!("serialize wal entry failed: {e}"))?;
+    line.push(b'\n');
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(wal_path)
+        .map_err(|e| format!("open wal.jsonl failed: {e}"))?;
+    let before = file
+        .metadata()
+        .map_err(|e| format!("stat wal.jsonl failed: {e}"))?
+        .len();
+
+    if let Err(e) = file.write_all(&line).and_then(|_| file.flush()) {
+        let _ = file.set_len(before);
+        return Err(format!("append wal.jsonl failed: {e}"));
+    }
+
+    Ok(before)
+}
+
+/// 将 WAL 截断回指定长度（数据写入失败时撤销刚追加的条目）。
+pub fn truncate(wal_path: &Path, len: u64) {
+    if let Ok(file) = OpenOptions::new().write(true).open(wal_path) {
+        let _ = file.set_len(len);
+    }
+}
+
+/// WAL 不存在时，以当前全部数据（含已封存的分段）写入一个 base 快照作为回放起点。
+pub fn ensure_initialized(wal_path: &Path, files: &DataFiles) -> Result<(), String> {
+    if wal_path.exists() {
+        return Ok(());
+    }
+
+    let lines = files.read_lines()?;
+    append_entry(wal_path, WAL_KIND_BASE, lines).map(|_| ())
+}
+
+/// 回放 WAL 到指定时间点（含），返回该时刻 memories.jsonl 应有的全部行。
+pub fn replay_until(wal_path: &Path, at_ts: i64) -> Result<Vec<String>, String> {
+    if !wal_path.exists() {
+        return Err("该 namespace 未启用 WAL（设置 MEMORY_WAL=1 后才会记录）".to_string());
+    }
+
+    let file = File::open(wal_path).map_err(|e| format!("open wal.jsonl failed: {e}"))?;
+    let reader = BufReader::new(file);
+
+    let mut lines: Option<Vec<String>> = None;
+    for raw in reader.lines() {
+        let raw = raw.map_err(|e| format!("read wal.jsonl failed: {e}"))?;
+        let Ok(entry) = serde_json::from_str::<WalEntry>(raw.trim()) else {
+            continue;
+        };
+        if entry.ts_unix > at_ts {
+            break;
+        }
+
+        if entry.kind == WAL_KIND_BASE {
+            lines = Some(entry.lines);
+        } else if let Some(current) = lines.as_mut() {
+            current.extend(entry.lines);
+        }
+    }
+
+    lines.ok_or_else(|| "指定时间早于 WAL 起点，无法恢复".to_string())
+}
+
+/// 逐条改写 WAL 中记录的数据行（例如 namespace 重命名），保留条目时间戳不变。
+pub fn rewrite_lines(wal_path: &Path, mut f: impl FnMut(&str) -> String) -> Result<(), String> {
+    if !wal_path.exists() {
+        return Ok(());
+    }
+
+    let text = fs::read_to_string(wal_path).map_err(|e| format!("read wal.jsonl failed: {e}"))?;
+    let mut out = String::with_capacity(text.len());
+    for raw in text.lines() {
+        let raw = raw.trim_end_matches('\r');
+        if raw.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<WalEntry>(raw) {
+            Ok(mut entry) => {
+                entry.lines = entry.lines.iter().map(|x| f(x)).collect();
+                let line = serde_json::to_string(&entry)
+                    .map_err(|e| format!("serialize wal entry failed: {e}"))?;
+                out.push_str(&line);
+            }
+            Err(_) => out.push_str(raw),
+        }
+        out.push('\n');
+    }
+
+    let tmp = wal_path.with_extension("jsonl.tmp");
+    fs::write(&tmp, out).map_err(|e| format!("write wal tmp failed: {e}"))?;
+    if let Err(e) = fs::rename(&tmp, wal_path) {
+        let _ = fs::remove_file(wal_path);
+        fs::rename(&tmp, wal_path).map_err(|_| format!("replace wal.jsonl failed: {e}"))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_entry(path: &Path, ts_unix: i64, kind: &str, lines: &[&str]) {
+        let entry = WalEntry {
+            ts: String::new(),
+            ts_unix,
+            kind: kind.to_string(),
+            lines: lines.iter().map(|x| x.to_string()).collect(),
+        };
+        let mut f = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .expect("open wal");
+        writeln!(f, "{}", serde_json::to_string(&entry).unwrap()).expect("write wal");
+    }
+
+    #[test]
+    fn replay_should_start_from_latest_base_before_point() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let wal = dir.path().join("wal.jsonl");
+
+        write_entry(&wal, 100, WAL_KIND_BASE, &["a"]);
+        write_entry(&wal, 200, WAL_KIND_COMMIT, &["b"]);
+        write_entry(&wal, 300, WAL_KIND_BASE, &["a"]);
+        write_entry(&wal, 400, WAL_KIND_COMMIT, &["c"]);
+
+        assert_eq!(replay_until(&wal, 250).unwrap(), vec!["a", "b"]);
+        assert_eq!(replay_until(&wal, 350).unwrap(), vec!["a"]);
+        assert_eq!(replay_until(&wal, 500).unwrap(), vec!["a", "c"]);
+
+        let err = replay_until(&wal, 50).expect_err("before base");
+        assert!(err.contains("WAL 起点"), "unexpected err: {err}");
+    }
+}
@@ -0,0 +1,187 @@
+use crate::mcp;
+use crate::memory;
+use serde_json::{json, Map, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const JSON_SCHEMA_DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// 一个独立的 JSON Schema 文件：相对路径 + 内容。
+#[derive(Debug, Clone)]
+pub struct SchemaFile {
+    pub path: String,
+    pub schema: Value,
+}
+
+/// 收集全部 schema：每个 tool 的 input/output，以及记忆记录（MemoryItem / 删除标记）。
+pub fn collect() -> Vec<SchemaFile> {
+    let mut files = Vec::new();
+
+    for tool in mcp::tool_definitions() {
+        let name = tool["name"].as_str().unwrap_or_default().to_string();
+        let description = tool["description"].as_str().unwrap_or_default();
+
+        files.push(SchemaFile {
+            path: format!("tools/{name}.input.schema.json"),
+            schema: standalone(&format!("{name} input"), description, tool["inputSchema"].clone()),
+        });
+        files.push(SchemaFile {
+            path: format!("tools/{name}.output.schema.json"),
+            schema: standalone(&format!("{name} output"), description, mcp::tool_result_schema(tool.get("outputSchema"))),
+        });
+    }
+
+    files.push(SchemaFile {
+        path: "records/memory-item.schema.json".to_string(),
+        schema: standalone("MemoryItem", "memories.jsonl / 导出文档中的一条记忆记录。", memory::memory_item_schema()),
+    });
+    files.push(SchemaFile {
+        path: "records/tombstone.schema.json".to_string(),
+        schema: standalone("Tombstone", "memories.jsonl / 增量导出中的删除标记。", memory::tombstone_schema()),
+    });
+
+    files
+}
+
+/// 汇总为单个 JSON 文档（stdout 输出用）：`{ "$schema", "tools": { name: { input, output } }, "records": {...} }`。
+pub fn bundle() -> Value {
+    let mut tools = Map::new();
+    for tool in mcp::tool_definitions() {
+        let name = tool["name"].as_str().unwrap_or_default().to_string();
+        tools.insert(
+            name,
+            json!({
+                "description": tool["description"],
+                "inputSchema": tool["inputSchema"],
+                "outputSchema": mcp::tool_result_schema(tool.get("outputSchema"))
+            }),
+        );
+    }
+
+    json!({
+        "$schema": JSON_SCHEMA_DIALECT,
+        "tools": tools,
+        "records": {
+            "MemoryItem": memory::memory_item_schema(),
+            "Tombstone": memory::tombstone_schema()
+        }
+    })
+}
+
+/// 可转换的 tool 清单目标格式。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ManifestTarget {
+    /// OpenAI tools / function calling：`[{"type":"function","function":{name,description,parameters}}]`
+    OpenAi,
+    /// Anthropic tool use：`[{name,description,input_schema}]`
+    Anthropic,
+}
+
+impl ManifestTarget {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        match text.trim().to_ascii_lowercase().as_str() {
+            "openai" => Ok(Self::OpenAi),
+            "anthropic" => Ok(Self::Anthropic),
+            other => Err(format!("不支持的清单格式：{other}（可选 openai / anthropic）")),
+        }
+    }
+}
+
+/// 将 MCP tool 定义转换为非 MCP agent 栈可直接使用的 tool 清单（参数 schema 与 inputSchema 相同）。
+pub fn manifest(target: ManifestTarget) -> Value {
+    let tools: Vec<Value> = mcp::tool_definitions()
+        .into_iter()
+        .map(|tool| match target {
+            ManifestTarget::OpenAi => json!({
+                "type": "function",
+                "function": {
+                    "name": tool["name"],
+                    "description": tool["description"],
+                    "parameters": tool["inputSchema"]
+                }
+            }),
+            ManifestTarget::Anthropic => json!({
+                "name": tool["name"],
+                "description": tool["description"],
+                "input_schema": tool["inputSchema"]
+            }),
+        })
+        .collect();
+    Value::Array(tools)
+}
+
+/// 将全部 schema 写入目录（按 `collect()` 的相对路径），返回写入的文件列表。
+pub fn write_all(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let mut written = Vec::new();
+    for file in collect() {
+        let path = dir.join(&file.path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("create dir failed ({}): {e}", parent.display()))?;
+        }
+
+        let text = serde_json::to_string_pretty(&file.schema).map_err(|e| e.to_string())?;
+        fs::write(&path, text + "\n").map_err(|e| format!("write schema failed ({}): {e}", path.display()))?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+fn standalone(title: &str, description: &str, schema: Value) -> Value {
+    let mut out = Map::new();
+    out.insert("$schema".to_string(), json!(JSON_SCHEMA_DIALECT));
+    out.insert("title".to_string(), json!(title));
+    if !description.is_empty() {
+        out.insert("description".to_string(), json!(description));
+    }
+    if let Value::Object(fields) = schema {
+        out.extend(fields);
+    }
+    Value::Object(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_all_should_emit_input_and_output_for_every_tool() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let written = write_all(dir.path()).expect("write");
+
+        let tools = mcp::tool_definitions();
+        assert_eq!(written.len(), tools.len() * 2 + 2);
+        for tool in &tools {
+            let name = tool["name"].as_str().expect("name");
+            for kind in ["input", "output"] {
+                let path = dir.path().join(format!("tools/{name}.{kind}.schema.json"));
+                let text = fs::read_to_string(&path).expect("read schema");
+                let v: Value = serde_json::from_str(&text).expect("json");
+                assert_eq!(v["$schema"], JSON_SCHEMA_DIALECT);
+                assert_eq!(v["type"], "object");
+            }
+        }
+
+        let text = fs::read_to_string(dir.path().join("records/memory-item.schema.json")).expect("read");
+        let v: Value = serde_json::from_str(&text).expect("json");
+        assert_eq!(v["title"], "MemoryItem");
+        assert!(v["properties"].get("checksum").is_some());
+    }
+
+    #[test]
+    fn manifest_should_wrap_input_schemas_per_target() {
+        let tools = mcp::tool_definitions();
+
+        let openai = manifest(ManifestTarget::OpenAi);
+        let openai = openai.as_array().expect("array");
+        assert_eq!(openai.len(), tools.len());
+        assert_eq!(openai[0]["type"], "function");
+        assert_eq!(openai[0]["function"]["name"], tools[0]["name"]);
+        assert_eq!(openai[0]["function"]["parameters"], tools[0]["inputSchema"]);
+
+        let anthropic = manifest(ManifestTarget::Anthropic);
+        assert_eq!(anthropic[0]["input_schema"], tools[0]["inputSchema"]);
+
+        assert_eq!(ManifestTarget::parse("OpenAI"), Ok(ManifestTarget::OpenAi));
+        assert!(ManifestTarget::parse("gemini").is_err());
+    }
+}
@@ -0,0 +1,173 @@
+//! 集成测试替身（需启用 `testing` feature，会一并启用 `sqlite`）。
+//!
+//! [`McpConnection`]：进程内的 MCP 连接，按行收发 JSON-RPC 消息，与 stdio server 走同一套处理逻辑，
+//! 但不经过真实的 stdin/stdout。默认使用内存存储后端（[`StorageBackend::Memory`]），不读写任何文件；
+//! 需要完整 JSONL 存储时可用 [`McpConnection::with_root`] 指向调用方准备的目录。
+
+use crate::mcp;
+use crate::memory::{MemoryEngine, StorageBackend};
+use serde_json::{json, Value};
+use std::path::PathBuf;
+
+/// 进程内 MCP 连接：自动分配请求 id，返回解析后的 JSON-RPC 响应。
+pub struct McpConnection {
+    engine: MemoryEngine,
+    next_id: i64,
+    /// 服务端推送、尚未被取走的通知（如 `notifications/memory/changed`）。
+    notifications: Vec<Value>,
+}
+
+impl McpConnection {
+    /// 使用内存存储：各 namespace 保存在进程内的 SQLite 内存库中，连接 drop 时丢弃。
+    ///
+    /// 内存后端与 SQLite 后端支持的 tool 相同（remember / recall / get / history / update / forget / transaction）。
+    pub fn new() -> Self {
+        // 内存后端不会创建目录；根目录只用于拼出 namespace 路径（读取冻结标记等时视为不存在）。
+        let root = std::env::temp_dir().join(format!("memory-mcp-in-memory-{}", uuid::Uuid::new_v4()));
+        Self::from_engine(MemoryEngine::new(root).with_backend(StorageBackend::Memory))
+    }
+
+    /// 使用调用方提供的存储根目录（JSONL 后端，不负责清理），例如在多个连接间共享同一份数据。
+    pub fn with_root(root_dir: PathBuf) -> Self {
+        Self::from_engine(MemoryEngine::new(root_dir))
+    }
+
+    fn from_engine(engine: MemoryEngine) -> Self {
+        Self {
+            engine,
+            next_id: 1,
+            notifications: Vec::new(),
+        }
+    }
+
+    /// 原样发送一行消息；通知或空行返回 None。工具执行失败时返回 Err（与 stdio server 行为一致：不写响应）。
+    pub fn send_line(&mut self, line: &str) -> Result<Option<Value>, String> {
+        let out = mcp::handle_stdin_line(&mut self.engine, line);
+        for notification in mcp::take_notifications(&mut self.engine) {
+            let v = serde_json::from_str(&notification).map_err(|e| format!("invalid notification json: {e}"))?;
+            self.notifications.push(v);
+        }
+
+        let Some(out) = out? else {
+            return Ok(None);
+        };
+        serde_json::from_str(&out)
+            .map(Some)
+            .map_err(|e| format!("invalid response json: {e}"))
+    }
+
+    /// 取走目前收到的全部服务端通知（按到达顺序）。
+    pub fn take_notifications(&mut self) -> Vec<Value> {
+        std::mem::take(&mut self.notifications)
+    }
+
+    /// 发送请求并返回 `result`；JSON-RPC error 响应转为 Err（错误消息）。
+    pub fn request(&mut self, method: &str, params: Value) -> Result<Value, String> {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let line = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }).to_string();
+        let Some(mut response) = self.send_line(&line)? else {
+            return Err(format!("{method}: 没有响应"));
+        };
+        if let Some(err) = response.get("error") {
+            let message = err.get("message").and_then(|x| x.as_str()).unwrap_or_default();
+            return Err(format!("{method}: {message}"));
+        }
+        Ok(response.get_mut("result").map(Value::take).unwrap_or(Value::Null))
+    }
+
+    /// 发送通知（无 id，不期待响应）。
+    pub fn notify(&mut self, method: &str, params: Value) -> Result<(), String> {
+        let line = json!({ "jsonrpc": "2.0", "method": method, "params": params }).to_string();
+        self.send_line(&line).map(|_| ())
+    }
+
+    /// 完成 initialize 握手（含 `notifications/initialized`），返回服务端的 initialize 结果。
+    pub fn initialize(&mut self, client_name: &str) -> Result<Value, String> {
+        let result = self.request(
+            "initialize",
+            json!({
+                "protocolVersion": "2025-06-18",
+                "clientInfo": { "name": client_name, "version": "test" },
+                "capabilities": {}
+            }),
+        )?;
+        self.notify("notifications/initialized", json!({}))?;
+        Ok(result)
+    }
+
+    pub fn list_tools(&mut self) -> Result<Vec<Value>, String> {
+        let result = self.request("tools/list", json!({}))?;
+        Ok(result
+            .get("tools")
+            .and_then(|x| x.as_array())
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    /// 调用 tool 并返回其结果（握手后为 `{ content, structuredContent? }`）。
+    pub fn call_tool(&mut self, name: &str, arguments: Value) -> Result<Value, String> {
+        self.request("tools/call", json!({ "name": name, "arguments": arguments }))
+    }
+}
+
+impl Default for McpConnection {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn connection_should_roundtrip_tools_in_memory() {
+        let mut conn = McpConnection::new();
+
+        let init = conn.initialize("harness").expect("initialize");
+        assert_eq!(init["serverInfo"]["name"], "Memory");
+        assert!(conn.list_tools().expect("tools").iter().any(|t| t["name"] == "recall"));
+
+        conn.call_tool(
+            "remember",
+            json!({ "namespace": "u1/p1", "keywords": ["k"], "slice": "s", "diary": "d" }),
+        )
+        .expect("remember");
+        let notes = conn.take_notifications();
+        assert_eq!(notes[0]["method"], mcp::CHANGED_NOTIFICATION);
+        assert!(conn.take_notifications().is_empty());
+        let recalled = conn
+            .call_tool("recall", json!({ "namespace": "u1/p1", "keywords": ["k"] }))
+            .expect("recall");
+        assert_eq!(recalled["structuredContent"]["items"][0]["written_by"], "harness");
+
+        let err = conn.call_tool("remember", json!({ "namespace": "u1/p1" })).unwrap_err();
+        assert!(!err.is_empty());
+        assert!(conn.request("nope", json!({})).unwrap_err().contains("method not found"));
+
+        // 每个连接各自一份内存存储。
+        let mut other = McpConnection::new();
+        let recalled = other
+            .call_tool("recall", json!({ "namespace": "u1/p1", "keywords": ["k"] }))
+            .expect("recall");
+        assert_eq!(recalled["data"]["total"], 0);
+    }
+
+    #[test]
+    fn connections_with_root_should_share_jsonl_store() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let mut writer = McpConnection::with_root(dir.path().to_path_buf());
+        writer
+            .call_tool("remember", json!({ "namespace": "u1/p1", "keywords": ["k"], "slice": "s", "diary": "d" }))
+            .expect("remember");
+        assert!(dir.path().join("u1/p1/memories.jsonl").is_file());
+
+        let mut reader = McpConnection::with_root(dir.path().to_path_buf());
+        let recalled = reader
+            .call_tool("recall", json!({ "namespace": "u1/p1", "keywords": ["k"] }))
+            .expect("recall");
+        assert_eq!(recalled["data"]["items"][0]["slice"], "s");
+    }
+}
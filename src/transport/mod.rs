@@ -0,0 +1,368 @@
+//! 传输层：网络传输的多个客户端会话共享同一个 [`MemoryEngine`]（按消息加锁串行处理），
+//! 各会话协商的设置（[`ClientState`]）在处理其消息时换入。
+//!
+//! - [`sse`]：2024-11-05 规范的 HTTP+SSE 传输（`GET /sse` + `POST /messages?sessionId=...`）；
+//! - `ws`：WebSocket 传输（需启用 `ws` 特性）；
+//! - [`stdio`]：stdio 传输（tokio 服务循环；按行 / `Content-Length` 头分帧）。
+
+pub mod sse;
+pub mod stdio;
+#[cfg(feature = "ws")]
+pub mod ws;
+
+use crate::mcp;
+use crate::memory::{self, ClientState, MemoryEngine};
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Read};
+use std::net::TcpStream;
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// 单个 HTTP 请求体的大小上限。
+const MAX_BODY_BYTES: usize = 16 * 1024 * 1024;
+
+/// 各传输共享的服务端状态：引擎、已连接的会话（会话 id → 会话）与允许的浏览器来源。
+#[derive(Clone)]
+pub struct Shared {
+    engine: Arc<Mutex<MemoryEngine>>,
+    sessions: Arc<Mutex<HashMap<String, Session>>>,
+    /// 新会话的初始设置（引擎启动时的设置）。
+    client: ClientState,
+    origins: Arc<Vec<String>>,
+}
+
+/// 已连接的会话：推送给该会话的消息队列，以及它在 initialize / logging/setLevel 中协商的引擎设置。
+struct Session {
+    outbox: Sender<String>,
+    client: ClientState,
+}
+
+impl Shared {
+    pub fn new(engine: MemoryEngine) -> Self {
+        Self {
+            client: engine.client_state(),
+            engine: Arc::new(Mutex::new(engine)),
+            sessions: Arc::new(Mutex::new(HashMap::new())),
+            origins: Arc::new(Vec::new()),
+        }
+    }
+
+    /// 在本机来源之外额外允许的 `Origin`（如 `https://app.example.com`，按完整来源精确匹配，不区分大小写）。
+    pub fn with_allowed_origins(mut self, origins: impl IntoIterator<Item = String>) -> Self {
+        let origins = origins
+            .into_iter()
+            .map(|x| x.trim().trim_end_matches('/').to_ascii_lowercase())
+            .filter(|x| !x.is_empty());
+        self.origins = Arc::new(origins.collect());
+        self
+    }
+
+    /// 请求的 `Origin` 是否允许：没有 `Origin` 头（非浏览器客户端）、本机来源与额外允许的来源放行，其余拒绝，
+    /// 防止任意网页借用户的浏览器访问本机服务（DNS rebinding / 跨站请求）。
+    pub fn origin_allowed(&self, origin: Option<&str>) -> bool {
+        let Some(origin) = origin else { return true };
+        let origin = origin.trim().trim_end_matches('/').to_ascii_lowercase();
+        is_local_origin(&origin) || self.origins.contains(&origin)
+    }
+
+    fn engine(&self) -> MutexGuard<'_, MemoryEngine> {
+        // 处理消息时 panic 不应让其余会话全部失效：引擎状态均已落盘，继续使用即可。
+        self.engine.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn sessions(&self) -> MutexGuard<'_, HashMap<String, Session>> {
+        self.sessions.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// 登记新会话，返回会话 id。
+    pub fn open_session(&self, outbox: Sender<String>) -> String {
+        let id = uuid::Uuid::new_v4().simple().to_string();
+        let client = self.client.clone();
+        self.sessions().insert(id.clone(), Session { outbox, client });
+        id
+    }
+
+    pub fn close_session(&self, id: &str) {
+        self.sessions().remove(id);
+    }
+
+    pub fn has_session(&self, id: &str) -> bool {
+        self.sessions().contains_key(id)
+    }
+
+    /// 等待进行中的消息处理完毕并补写未落盘的索引；返回的锁应持有到进程退出，之后的消息不再写入。
+    pub fn shutdown(&self) -> MutexGuard<'_, MemoryEngine> {
+        let mut engine = self.engine();
+        if let Err(err) = engine.flush() {
+            eprintln!("memory: {err}");
+        }
+        engine
+    }
+
+    /// 处理会话发来的一条消息：响应与日志通知发给该会话，namespace 变更通知广播给全部会话。
+    ///
+    /// 处理期间引擎使用该会话自己的设置（写入者身份、协议相关的结果字段、日志级别），互不覆盖。
+    ///
+    /// 处理失败（如工具执行出错）的请求以 JSON-RPC 错误响应推送给该会话；
+    /// 返回 Err 表示消息不是合法 JSON（已记录 error 日志并推送给该会话）。
+    pub fn dispatch(&self, session: &str, line: &str) -> Result<(), String> {
+        memory::capture_logs();
+        let client = match self.sessions().get(session) {
+            Some(entry) => entry.client.clone(),
+            None => self.client.clone(),
+        };
+        let (response, logs, changes, client) = {
+            let mut engine = self.engine();
+            engine.set_client_state(client);
+            let response = mcp::handle_stdin_line(&mut engine, line).or_else(|err| {
+                mcp::report_error(&err);
+                match serde_json::from_str::<serde_json::Value>(line) {
+                    Ok(message) => Ok(mcp::error_response(message.get("id").cloned(), &err).map(|v| v.to_string())),
+                    Err(_) => Err(err),
+                }
+            });
+            let logs = mcp::take_log_notifications(&mut engine);
+            let changes = mcp::take_change_notifications(&mut engine);
+            (response, logs, changes, engine.client_state())
+        };
+
+        let mut sessions = self.sessions();
+        if let Some(entry) = sessions.get_mut(session) {
+            entry.client = client;
+            if let Ok(Some(line)) = &response {
+                let _ = entry.outbox.send(line.clone());
+            }
+            for line in logs {
+                let _ = entry.outbox.send(line);
+            }
+        }
+        for line in &changes {
+            for entry in sessions.values() {
+                let _ = entry.outbox.send(line.clone());
+            }
+        }
+        response.map(|_| ())
+    }
+}
+
+/// 在后台线程运行网络传输（阻塞的 `serve`），直到它返回或收到终止信号；收到信号时等待进行中的消息处理完毕、
+/// 补写索引后返回 Ok（调用方随即退出进程）。
+pub fn serve_until_shutdown<F>(shared: Shared, serve: F) -> io::Result<()>
+where
+    F: FnOnce(Shared) -> io::Result<()> + Send + 'static,
+{
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+    let (done_tx, done_rx) = tokio::sync::oneshot::channel();
+    let server = shared.clone();
+    std::thread::spawn(move || {
+        let _ = done_tx.send(serve(server));
+    });
+
+    let result = runtime.block_on(async {
+        tokio::select! {
+            result = done_rx => result.unwrap_or(Ok(())),
+            _ = shutdown_signal() => Ok(()),
+        }
+    });
+    let _engine = shared.shutdown();
+    result
+}
+
+/// 等待终止信号：SIGINT / SIGTERM（Unix），Ctrl+C / 关闭控制台 / 注销 / 关机（Windows）。
+///
+/// 无法注册信号处理时永不完成（退回到由 stdin 结束或进程被终止来退出）。
+pub async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let (Ok(mut int), Ok(mut term)) = (signal(SignalKind::interrupt()), signal(SignalKind::terminate())) else {
+            return std::future::pending().await;
+        };
+        tokio::select! {
+            _ = int.recv() => {}
+            _ = term.recv() => {}
+        }
+    }
+
+    #[cfg(windows)]
+    {
+        use tokio::signal::windows;
+        let (Ok(mut c), Ok(mut close), Ok(mut logoff), Ok(mut shutdown)) = (
+            windows::ctrl_c(),
+            windows::ctrl_close(),
+            windows::ctrl_logoff(),
+            windows::ctrl_shutdown(),
+        ) else {
+            return std::future::pending().await;
+        };
+        tokio::select! {
+            _ = c.recv() => {}
+            _ = close.recv() => {}
+            _ = logoff.recv() => {}
+            _ = shutdown.recv() => {}
+        }
+    }
+
+    #[cfg(not(any(unix, windows)))]
+    std::future::pending::<()>().await
+}
+
+/// 解析出的 HTTP/1.1 请求（只保留传输需要的部分）。
+#[derive(Debug)]
+pub struct HttpRequest {
+    pub method: String,
+    /// 路径（不含查询串）。
+    pub path: String,
+    pub query: HashMap<String, String>,
+    /// 头部名称统一为小写。
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+/// 读取一个 HTTP 请求（请求行、头部与按 Content-Length 读取的请求体）。
+pub fn read_request(stream: &TcpStream) -> io::Result<HttpRequest> {
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+    let mut reader = BufReader::new(stream);
+
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next().ok_or_else(|| invalid("empty request line"))?.to_string();
+    let target = parts.next().ok_or_else(|| invalid("missing request target"))?;
+    let (path, query) = match target.split_once('?') {
+        Some((path, query)) => (path.to_string(), parse_query(query)),
+        None => (target.to_string(), HashMap::new()),
+    };
+
+    let mut headers = HashMap::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            break;
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.insert(name.trim().to_ascii_lowercase(), value.trim().to_string());
+        }
+    }
+
+    let len = match headers.get("content-length") {
+        Some(text) => text.parse::<usize>().map_err(|_| invalid("invalid content-length"))?,
+        None => 0,
+    };
+    if len > MAX_BODY_BYTES {
+        return Err(invalid("request body too large"));
+    }
+    let mut body = vec![0u8; len];
+    reader.read_exact(&mut body)?;
+
+    Ok(HttpRequest {
+        method,
+        path,
+        query,
+        headers,
+        body,
+    })
+}
+
+fn parse_query(query: &str) -> HashMap<String, String> {
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+/// 本机来源：`http(s)://localhost`、`127.0.0.1` 或 `[::1]`（任意端口）。
+fn is_local_origin(origin: &str) -> bool {
+    let Some(authority) = origin.strip_prefix("http://").or_else(|| origin.strip_prefix("https://")) else {
+        return false;
+    };
+    let host = match authority.strip_prefix('[') {
+        Some(rest) => rest.split_once(']').map(|(host, _)| host).unwrap_or(""),
+        None => authority.split(':').next().unwrap_or(""),
+    };
+    matches!(host, "localhost" | "127.0.0.1" | "::1")
+}
+
+/// 已放行请求的 CORS 响应头：回显请求的 `Origin`（不使用通配符）；没有 `Origin` 时为空。
+pub fn cors_headers(origin: Option<&str>) -> String {
+    match origin {
+        Some(origin) => format!("Access-Control-Allow-Origin: {origin}\r\nVary: Origin\r\n"),
+        None => String::new(),
+    }
+}
+
+/// 写出一个完整的 HTTP 响应（短连接）；`origin` 为已放行请求的 `Origin`。
+pub fn write_response(mut stream: &TcpStream, status: &str, content_type: &str, origin: Option<&str>, body: &str) -> io::Result<()> {
+    use std::io::Write;
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\n{}Connection: close\r\n\r\n{body}",
+        body.len(),
+        cors_headers(origin)
+    )?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+    use std::sync::mpsc::{self, Receiver};
+
+    #[test]
+    fn sessions_should_keep_their_own_protocol_and_writer() {
+        let dir = tempfile::tempdir().unwrap();
+        let shared = Shared::new(MemoryEngine::new(dir.path().to_path_buf()));
+        let connect = || {
+            let (tx, rx) = mpsc::channel();
+            (shared.open_session(tx), rx)
+        };
+        let call = |session: &str, rx: &Receiver<String>, message: Value| -> Value {
+            shared.dispatch(session, &message.to_string()).unwrap();
+            rx.try_iter()
+                .map(|line| serde_json::from_str::<Value>(&line).unwrap())
+                .find(|response| response["id"] == message["id"])
+                .expect("response")
+        };
+        let initialize = |version: &str, name: &str| {
+            json!({ "jsonrpc": "2.0", "id": 0, "method": "initialize", "params": {
+                "protocolVersion": version, "clientInfo": { "name": name, "version": "1" }
+            } })
+        };
+        let remember = |slice: &str| {
+            json!({ "jsonrpc": "2.0", "id": 1, "method": "tools/call", "params": { "name": "remember", "arguments": {
+                "namespace": "u1/p1", "keywords": ["k"], "slice": slice, "diary": "d"
+            } } })
+        };
+        let get = |id: &str| {
+            json!({ "jsonrpc": "2.0", "id": 2, "method": "tools/call", "params": { "name": "get", "arguments": {
+                "namespace": "u1/p1", "id": id
+            } } })
+        };
+
+        // 旧协议的客户端先连接，新协议的客户端随后连接：双方的设置互不覆盖。
+        let (old, old_rx) = connect();
+        let (new, new_rx) = connect();
+        call(&old, &old_rx, initialize("2024-11-05", "agent-old"));
+        call(&new, &new_rx, initialize("2025-06-18", "agent-new"));
+
+        let by_old = call(&old, &old_rx, remember("旧协议写入"));
+        assert!(by_old["result"].get("structuredContent").is_none(), "{by_old}");
+        let old_id = by_old["result"]["data"]["id"].as_str().unwrap().to_string();
+
+        let by_new = call(&new, &new_rx, remember("新协议写入"));
+        assert!(by_new["result"].get("data").is_none(), "{by_new}");
+        let new_id = by_new["result"]["structuredContent"]["id"].as_str().unwrap().to_string();
+
+        let item = |response: Value| response["result"]["data"]["item"].clone();
+        let old_item = item(call(&old, &old_rx, get(&old_id)));
+        assert_eq!(old_item["written_by"], "agent-old");
+        let new_item = item(call(&old, &old_rx, get(&new_id)));
+        assert_eq!(new_item["written_by"], "agent-new");
+    }
+}
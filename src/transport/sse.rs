@@ -0,0 +1,247 @@
+//! HTTP+SSE 传输（MCP 2024-11-05）：
+//!
+//! 1. 客户端 `GET /sse` 建立事件流，服务端首先发送 `event: endpoint`，`data` 为本会话的消息地址
+//!    （`/messages?sessionId=...`）；
+//! 2. 客户端向该地址 `POST` JSON-RPC 消息（单条或批量），服务端立即返回 `202 Accepted`，
+//!    响应与通知随后以 `event: message` 从事件流推送。
+//!
+//! 带 `Origin` 头的请求只有来源在允许列表中（默认仅本机）时才处理，否则返回 `403`。
+
+use crate::transport::{self, Shared};
+use std::io::{self, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+pub const SSE_PATH: &str = "/sse";
+pub const MESSAGES_PATH: &str = "/messages";
+
+/// 事件流空闲时发送注释行保活的间隔（同时用于发现已断开的连接）。
+const KEEPALIVE: Duration = Duration::from_secs(15);
+
+/// 绑定地址并开始服务（阻塞）。
+pub fn run(addr: impl ToSocketAddrs, shared: Shared) -> io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    eprintln!("memory: SSE transport listening on http://{}{SSE_PATH}", listener.local_addr()?);
+    serve(listener, shared);
+    Ok(())
+}
+
+/// 在已绑定的监听器上服务：每个连接一个线程。
+pub fn serve(listener: TcpListener, shared: Shared) {
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else { continue };
+        let shared = shared.clone();
+        thread::spawn(move || {
+            if let Err(e) = handle_connection(&stream, &shared) {
+                let _ = transport::write_response(&stream, "400 Bad Request", "text/plain; charset=utf-8", None, &e.to_string());
+            }
+        });
+    }
+}
+
+fn handle_connection(stream: &TcpStream, shared: &Shared) -> io::Result<()> {
+    let request = transport::read_request(stream)?;
+    let origin = request.headers.get("origin").map(String::as_str);
+    if !shared.origin_allowed(origin) {
+        return transport::write_response(stream, "403 Forbidden", "text/plain; charset=utf-8", None, "origin not allowed");
+    }
+    match (request.method.as_str(), request.path.as_str()) {
+        ("GET", SSE_PATH) => stream_events(stream, shared, origin),
+        ("POST", MESSAGES_PATH) => {
+            let Some(session) = request.query.get("sessionId").filter(|id| shared.has_session(id)) else {
+                return transport::write_response(stream, "404 Not Found", "text/plain; charset=utf-8", origin, "unknown session");
+            };
+            let text = String::from_utf8_lossy(&request.body);
+            match shared.dispatch(session, &text) {
+                Ok(()) => transport::write_response(stream, "202 Accepted", "text/plain; charset=utf-8", origin, "Accepted"),
+                Err(e) => transport::write_response(stream, "400 Bad Request", "text/plain; charset=utf-8", origin, &e),
+            }
+        }
+        ("OPTIONS", _) => {
+            let mut stream = stream;
+            write!(
+                stream,
+                "HTTP/1.1 204 No Content\r\n{}Access-Control-Allow-Methods: GET, POST, OPTIONS\r\nAccess-Control-Allow-Headers: Content-Type\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                transport::cors_headers(origin)
+            )?;
+            stream.flush()
+        }
+        _ => transport::write_response(stream, "404 Not Found", "text/plain; charset=utf-8", origin, "not found"),
+    }
+}
+
+/// 事件流：发送 endpoint 事件后转发本会话队列中的消息，直到客户端断开。
+fn stream_events(mut stream: &TcpStream, shared: &Shared, origin: Option<&str>) -> io::Result<()> {
+    let (outbox, inbox) = mpsc::channel::<String>();
+    let session = shared.open_session(outbox);
+
+    let result = (|| -> io::Result<()> {
+        write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\n{}Connection: keep-alive\r\n\r\n",
+            transport::cors_headers(origin)
+        )?;
+        write!(stream, "event: endpoint\ndata: {MESSAGES_PATH}?sessionId={session}\n\n")?;
+        stream.flush()?;
+        loop {
+            match inbox.recv_timeout(KEEPALIVE) {
+                Ok(message) => write!(stream, "event: message\ndata: {message}\n\n")?,
+                Err(RecvTimeoutError::Timeout) => write!(stream, ": keepalive\n\n")?,
+                Err(RecvTimeoutError::Disconnected) => return Ok(()),
+            }
+            stream.flush()?;
+        }
+    })();
+
+    shared.close_session(&session);
+    // 连接已断开时写错误属于正常结束。
+    match result {
+        Err(e) if matches!(e.kind(), io::ErrorKind::BrokenPipe | io::ErrorKind::ConnectionReset) => Ok(()),
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::MemoryEngine;
+    use serde_json::{json, Value};
+    use std::io::{BufRead, BufReader, Read};
+
+    fn post(addr: std::net::SocketAddr, path: &str, body: &str) -> String {
+        post_with(addr, path, "", body)
+    }
+
+    /// `headers` 为额外的请求头（每行以 `\r\n` 结尾）。
+    fn post_with(addr: std::net::SocketAddr, path: &str, headers: &str, body: &str) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "POST {path} HTTP/1.1\r\nHost: localhost\r\n{headers}Content-Type: application/json\r\nContent-Length: {}\r\n\r\n{body}",
+            body.len()
+        )
+        .unwrap();
+        let mut out = String::new();
+        stream.read_to_string(&mut out).unwrap();
+        out
+    }
+
+    /// 读取下一个事件，返回 (event, data)。
+    fn next_event(reader: &mut impl BufRead) -> (String, String) {
+        let (mut event, mut data) = (String::new(), String::new());
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            let line = line.trim_end();
+            if line.is_empty() && !event.is_empty() {
+                return (event, data);
+            }
+            if let Some(v) = line.strip_prefix("event: ") {
+                event = v.to_string();
+            } else if let Some(v) = line.strip_prefix("data: ") {
+                data = v.to_string();
+            }
+        }
+    }
+
+    #[test]
+    fn sse_session_should_receive_responses_for_posted_messages() {
+        let dir = tempfile::tempdir().unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let shared = Shared::new(MemoryEngine::new(dir.path().to_path_buf()));
+        thread::spawn(move || serve(listener, shared));
+
+        let mut events = TcpStream::connect(addr).unwrap();
+        write!(events, "GET {SSE_PATH} HTTP/1.1\r\nHost: localhost\r\nAccept: text/event-stream\r\n\r\n").unwrap();
+        let mut reader = BufReader::new(events);
+        let (event, endpoint) = next_event(&mut reader);
+        assert_eq!(event, "endpoint");
+        assert!(endpoint.starts_with("/messages?sessionId="));
+
+        let init = json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": { "protocolVersion": "2024-11-05" } });
+        assert!(post(addr, &endpoint, &init.to_string()).starts_with("HTTP/1.1 202"));
+        let (event, data) = next_event(&mut reader);
+        assert_eq!(event, "message");
+        let response: Value = serde_json::from_str(&data).unwrap();
+        assert_eq!(response["id"], 1);
+        assert_eq!(response["result"]["protocolVersion"], "2024-11-05");
+
+        assert!(post(addr, "/messages?sessionId=nope", "{}").starts_with("HTTP/1.1 404"));
+        assert!(post(addr, &endpoint, "{not json").starts_with("HTTP/1.1 400"));
+    }
+
+    #[test]
+    fn sse_should_reject_foreign_origins_and_echo_allowed_ones() {
+        let dir = tempfile::tempdir().unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let shared = Shared::new(MemoryEngine::new(dir.path().to_path_buf()))
+            .with_allowed_origins(["https://App.Example.com/".to_string()]);
+        thread::spawn(move || serve(listener, shared));
+
+        let mut events = TcpStream::connect(addr).unwrap();
+        write!(events, "GET {SSE_PATH} HTTP/1.1\r\nHost: localhost\r\nOrigin: http://localhost:5173\r\n\r\n").unwrap();
+        let mut reader = BufReader::new(events);
+        let mut head = String::new();
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if line.trim_end().is_empty() {
+                break;
+            }
+            head.push_str(&line);
+        }
+        assert!(head.starts_with("HTTP/1.1 200"));
+        assert!(head.contains("Access-Control-Allow-Origin: http://localhost:5173\r\n"));
+        let (_, endpoint) = next_event(&mut reader);
+
+        let foreign = post_with(addr, &endpoint, "Origin: https://evil.example\r\n", "{}");
+        assert!(foreign.starts_with("HTTP/1.1 403"));
+        assert!(!foreign.contains("Access-Control-Allow-Origin"));
+        assert!(post_with(addr, &endpoint, "Origin: http://localhost.evil.example\r\n", "{}").starts_with("HTTP/1.1 403"));
+        assert!(post_with(addr, &endpoint, "Origin: null\r\n", "{}").starts_with("HTTP/1.1 403"));
+
+        let listed = post_with(addr, &endpoint, "Origin: https://app.example.com\r\n", "");
+        assert!(listed.starts_with("HTTP/1.1 202"));
+        assert!(listed.contains("Access-Control-Allow-Origin: https://app.example.com\r\n"));
+        let plain = post(addr, &endpoint, "");
+        assert!(plain.starts_with("HTTP/1.1 202"));
+        assert!(!plain.contains("Access-Control-Allow-Origin"));
+    }
+
+    #[test]
+    fn sse_should_push_tool_failures_as_jsonrpc_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let shared = Shared::new(MemoryEngine::new(dir.path().to_path_buf()));
+        thread::spawn(move || serve(listener, shared));
+
+        let mut events = TcpStream::connect(addr).unwrap();
+        write!(events, "GET {SSE_PATH} HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        let mut reader = BufReader::new(events);
+        let (_, endpoint) = next_event(&mut reader);
+
+        let call = json!({
+            "jsonrpc": "2.0",
+            "id": 7,
+            "method": "tools/call",
+            "params": { "name": "get", "arguments": { "namespace": "u1/p1", "id": "missing" } }
+        });
+        assert!(post(addr, &endpoint, &call.to_string()).starts_with("HTTP/1.1 202"));
+        loop {
+            let (event, data) = next_event(&mut reader);
+            assert_eq!(event, "message");
+            let message: Value = serde_json::from_str(&data).unwrap();
+            // 错误日志通知也会推送给本会话。
+            if message.get("id").is_some() {
+                assert_eq!(message["id"], 7);
+                assert_eq!(message["error"]["code"], crate::mcp::HANDLER_ERROR_CODE);
+                break;
+            }
+        }
+    }
+}
@@ -0,0 +1,349 @@
+//! stdio 传输：
+//!
+//! - 消息分帧：默认每行一条 JSON（MCP 规范），也支持 LSP 风格的 `Content-Length` 头部分帧；
+//! - [`serve`]：基于 tokio 的服务循环。读取、引擎处理与写出互不阻塞：耗时的 recall / compact 执行期间
+//!   仍持续读取请求，不需要引擎的消息（工具列表等）直接应答；需要引擎的消息按到达顺序在专用的阻塞线程上执行，
+//!   全部输出经单一写出任务串行写回 stdout。
+
+use crate::mcp;
+use crate::memory::{self, MemoryEngine};
+use std::future::Future;
+use std::io::{self, BufRead, Write};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::mpsc;
+
+/// 消息分帧方式（`--framing`）。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Framing {
+    /// 按第一条消息自动判断：以 `Content-Length:` 开头时按头部分帧，否则按行。
+    #[default]
+    Auto,
+    Lines,
+    ContentLength,
+}
+
+impl Framing {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        match text.trim().to_lowercase().as_str() {
+            "" | "auto" => Ok(Self::Auto),
+            "lines" | "ndjson" => Ok(Self::Lines),
+            "content-length" | "lsp" => Ok(Self::ContentLength),
+            other => Err(format!("framing 无效：{other}（可选 auto / lines / content-length）")),
+        }
+    }
+}
+
+/// 从 stdin 逐条读取消息；`Auto` 在读到第一条消息时确定分帧方式，之后的响应使用相同分帧写回。
+pub struct StdioReader<R> {
+    reader: R,
+    framing: Framing,
+}
+
+impl<R: BufRead> StdioReader<R> {
+    pub fn new(reader: R, framing: Framing) -> Self {
+        Self { reader, framing }
+    }
+
+    /// 当前生效的分帧方式（`Auto` 表示尚未读到消息）。
+    pub fn framing(&self) -> Framing {
+        self.framing
+    }
+
+    /// 读取下一条消息；输入结束时返回 None。
+    pub fn next_message(&mut self) -> io::Result<Option<String>> {
+        loop {
+            let mut line = String::new();
+            if self.reader.read_line(&mut line)? == 0 {
+                return Ok(None);
+            }
+            let text = line.trim().to_string();
+            if text.is_empty() {
+                continue;
+            }
+
+            if self.framing == Framing::Auto {
+                self.framing = if header_value(&text, "content-length").is_some() {
+                    Framing::ContentLength
+                } else {
+                    Framing::Lines
+                };
+            }
+            return match self.framing {
+                Framing::ContentLength => self.read_framed(&text).map(Some),
+                _ => Ok(Some(text)),
+            };
+        }
+    }
+
+    /// 读取剩余头部（首行已读出）与按 Content-Length 指定长度的消息体。
+    fn read_framed(&mut self, first: &str) -> io::Result<String> {
+        let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+        let mut len = header_value(first, "content-length").map(str::to_string);
+        loop {
+            let mut line = String::new();
+            if self.reader.read_line(&mut line)? == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            let text = line.trim();
+            if text.is_empty() {
+                break;
+            }
+            if let Some(value) = header_value(text, "content-length") {
+                len = Some(value.to_string());
+            }
+        }
+
+        let len = len.ok_or_else(|| invalid("missing Content-Length header".to_string()))?;
+        let len: usize = len
+            .parse()
+            .map_err(|_| invalid(format!("invalid Content-Length: {len}")))?;
+        let mut body = vec![0u8; len];
+        self.reader.read_exact(&mut body)?;
+        String::from_utf8(body).map_err(|e| invalid(format!("message is not utf-8: {e}")))
+    }
+}
+
+fn header_value<'a>(line: &'a str, name: &str) -> Option<&'a str> {
+    let (key, value) = line.split_once(':')?;
+    key.trim().eq_ignore_ascii_case(name).then(|| value.trim())
+}
+
+/// 按分帧方式写出一条消息并刷新。
+pub fn write_message(out: &mut impl Write, framing: Framing, message: &str) -> io::Result<()> {
+    match framing {
+        Framing::ContentLength => write!(out, "Content-Length: {}\r\n\r\n{message}", message.len())?,
+        Framing::Auto | Framing::Lines => writeln!(out, "{message}")?,
+    }
+    out.flush()
+}
+
+/// 运行 stdio MCP server，直到 stdin 结束或收到终止信号（SIGTERM / SIGINT，Windows 控制台关闭等事件）。
+///
+/// 两种情况都会先处理完已读入的请求并写出响应，再补写未落盘的索引后返回。
+pub fn serve(engine: MemoryEngine, framing: Framing) -> io::Result<()> {
+    let runtime = tokio::runtime::Builder::new_multi_thread().enable_all().build()?;
+    let mut stdout = tokio::io::stdout();
+    let result = runtime.block_on(run(
+        engine,
+        io::BufReader::new(io::stdin()),
+        &mut stdout,
+        framing,
+        super::shutdown_signal(),
+    ));
+    // 收到信号时读取线程仍阻塞在 stdin 上，不等待它结束。
+    runtime.shutdown_background();
+    result
+}
+
+/// 服务循环：`input` 在阻塞线程上读取（stdin 没有真正的异步读取），输出写到 `output`；`shutdown` 完成时停止读取。
+async fn run<R, W>(
+    mut engine: MemoryEngine,
+    input: R,
+    output: &mut W,
+    framing: Framing,
+    shutdown: impl Future<Output = ()>,
+) -> io::Result<()>
+where
+    R: BufRead + Send + 'static,
+    W: AsyncWrite + Unpin,
+{
+    // 输出队列：(分帧方式, 消息)；分帧方式在读到第一条消息时才确定（Auto）。
+    let (out_tx, mut out_rx) = mpsc::unbounded_channel::<(Framing, String)>();
+    // 工作队列：None 表示输入结束或收到终止信号，处理完此前已入队的请求后停止。
+    let (work_tx, mut work_rx) = mpsc::unbounded_channel::<Option<(Framing, String)>>();
+
+    let reader_out = out_tx.clone();
+    let reader_work = work_tx.clone();
+    let backend = engine.backend();
+    tokio::task::spawn_blocking(move || {
+        let mut reader = StdioReader::new(input, framing);
+        loop {
+            let text = match reader.next_message() {
+                Ok(Some(text)) => text,
+                Ok(None) => break,
+                Err(err) => {
+                    // 分帧损坏（如 Content-Length 无效）后无法再定位下一条消息的边界。
+                    eprintln!("memory: read stdin failed: {err}");
+                    break;
+                }
+            };
+            let framing = reader.framing();
+            match mcp::handle_without_engine(&text, backend) {
+                Some(Some(response)) => {
+                    let _ = reader_out.send((framing, response));
+                }
+                Some(None) => {}
+                None => {
+                    if reader_work.send(Some((framing, text))).is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+        let _ = reader_work.send(None);
+    });
+
+    let mut worker = tokio::task::spawn_blocking(move || {
+        // 日志按线程收集：引擎只在本线程上使用。
+        memory::capture_logs();
+        while let Some(Some((framing, text))) = work_rx.blocking_recv() {
+            match mcp::handle_stdin_line(&mut engine, &text) {
+                Ok(Some(response)) => {
+                    let _ = out_tx.send((framing, response));
+                }
+                Ok(None) => {}
+                // 无法处理的输入（如非法 JSON）：不写入响应，作为 error 日志随通知推送（同时输出到 stderr）。
+                Err(err) => mcp::report_error(&err),
+            }
+            // 本条消息产生的日志与变更通知（写入、发现外部追加、删除/重命名等）。
+            for notification in mcp::take_notifications(&mut engine) {
+                let _ = out_tx.send((framing, notification));
+            }
+        }
+        if let Err(err) = engine.flush() {
+            eprintln!("memory: {err}");
+        }
+    });
+
+    let mut shutdown = std::pin::pin!(shutdown);
+    let mut stopping = false;
+    loop {
+        tokio::select! {
+            message = out_rx.recv() => match message {
+                Some((framing, message)) => write_framed(output, framing, &message).await?,
+                None => break,
+            },
+            _ = &mut shutdown, if !stopping => {
+                stopping = true;
+                let _ = work_tx.send(None);
+            }
+            _ = &mut worker => {
+                // 工作线程结束（stdin 结束或收到信号）后，写出剩余消息即可退出，不再等待读取线程。
+                while let Ok((framing, message)) = out_rx.try_recv() {
+                    write_framed(output, framing, &message).await?;
+                }
+                break;
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn write_framed<W: AsyncWrite + Unpin>(output: &mut W, framing: Framing, message: &str) -> io::Result<()> {
+    let mut buf = Vec::with_capacity(message.len() + 32);
+    write_message(&mut buf, framing, message)?;
+    output.write_all(&buf).await?;
+    output.flush().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn auto_framing_should_detect_content_length_headers() {
+        let body = "{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"ping\"}";
+        let input = format!("Content-Length: {}\r\nContent-Type: application/json\r\n\r\n{body}Content-Length: 2\r\n\r\n{{}}", body.len());
+        let mut reader = StdioReader::new(input.as_bytes(), Framing::Auto);
+        assert_eq!(reader.next_message().unwrap().as_deref(), Some(body));
+        assert_eq!(reader.framing(), Framing::ContentLength);
+        assert_eq!(reader.next_message().unwrap().as_deref(), Some("{}"));
+        assert_eq!(reader.next_message().unwrap(), None);
+
+        let mut reader = StdioReader::new("\n{\"id\":1}\n{\"id\":2}\n".as_bytes(), Framing::Auto);
+        assert_eq!(reader.next_message().unwrap().as_deref(), Some("{\"id\":1}"));
+        assert_eq!(reader.framing(), Framing::Lines);
+        assert_eq!(reader.next_message().unwrap().as_deref(), Some("{\"id\":2}"));
+
+        let mut out = Vec::new();
+        write_message(&mut out, Framing::ContentLength, "{}").unwrap();
+        assert_eq!(out, b"Content-Length: 2\r\n\r\n{}");
+    }
+
+    #[test]
+    fn serve_should_answer_every_request_in_order_for_the_engine() {
+        let dir = tempfile::tempdir().unwrap();
+        let engine = MemoryEngine::new(dir.path().to_path_buf());
+        let remember = serde_json::json!({ "jsonrpc": "2.0", "id": 1, "method": "tools/call", "params": { "name": "remember", "arguments": {
+            "namespace": "u1/p1", "keywords": ["k"], "slice": "s", "diary": "d"
+        } } });
+        let recall = serde_json::json!({ "jsonrpc": "2.0", "id": 2, "method": "tools/call", "params": { "name": "recall", "arguments": {
+            "namespace": "u1/p1", "keywords": ["k"]
+        } } });
+        let list = serde_json::json!({ "jsonrpc": "2.0", "id": 3, "method": "tools/list" });
+        let input = format!("{remember}\n{recall}\n{list}\n");
+
+        let runtime = tokio::runtime::Builder::new_multi_thread().build().unwrap();
+        let mut out: Vec<u8> = Vec::new();
+        runtime
+            .block_on(run(engine, io::Cursor::new(input.into_bytes()), &mut out, Framing::Auto, std::future::pending()))
+            .unwrap();
+
+        let messages: Vec<serde_json::Value> = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let ids: Vec<i64> = messages.iter().filter_map(|m| m["id"].as_i64()).collect();
+        // 需要引擎的请求按到达顺序处理；tools/list 不经过引擎，可能先于它们写出。
+        let engine_ids: Vec<i64> = ids.iter().copied().filter(|&id| id != 3).collect();
+        assert_eq!(engine_ids, vec![1, 2]);
+        assert!(ids.contains(&3));
+        let recalled = messages.iter().find(|m| m["id"] == 2).unwrap();
+        assert_eq!(recalled["result"]["data"]["total"], 1);
+    }
+
+    /// 读完给定输入后触发终止信号并一直阻塞（模拟 stdin 保持打开）。
+    struct OpenStdin {
+        input: io::Cursor<Vec<u8>>,
+        signal: Option<tokio::sync::oneshot::Sender<()>>,
+        _hold: std::sync::mpsc::Sender<()>,
+        block: std::sync::mpsc::Receiver<()>,
+    }
+
+    impl io::Read for OpenStdin {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = self.input.read(buf)?;
+            if n > 0 {
+                return Ok(n);
+            }
+            if let Some(signal) = self.signal.take() {
+                let _ = signal.send(());
+            }
+            let _ = self.block.recv();
+            Ok(0)
+        }
+    }
+
+    #[test]
+    fn serve_should_finish_read_requests_and_exit_on_shutdown_signal() {
+        let dir = tempfile::tempdir().unwrap();
+        let engine = MemoryEngine::new(dir.path().to_path_buf());
+        let remember = serde_json::json!({ "jsonrpc": "2.0", "id": 1, "method": "tools/call", "params": { "name": "remember", "arguments": {
+            "namespace": "u1/p1", "keywords": ["k"], "slice": "s", "diary": "d"
+        } } });
+        let (signal_tx, signal_rx) = tokio::sync::oneshot::channel();
+        let (hold, block) = std::sync::mpsc::channel();
+        let input = OpenStdin {
+            input: io::Cursor::new(format!("{remember}\n").into_bytes()),
+            signal: Some(signal_tx),
+            _hold: hold,
+            block,
+        };
+
+        let runtime = tokio::runtime::Builder::new_multi_thread().build().unwrap();
+        let mut out: Vec<u8> = Vec::new();
+        let shutdown = async {
+            let _ = signal_rx.await;
+        };
+        runtime
+            .block_on(run(engine, io::BufReader::new(input), &mut out, Framing::Auto, shutdown))
+            .unwrap();
+        runtime.shutdown_background();
+
+        let text = String::from_utf8(out).unwrap();
+        let response: serde_json::Value = serde_json::from_str(text.lines().next().unwrap()).unwrap();
+        assert_eq!(response["id"], 1);
+        assert!(response["result"]["data"]["id"].is_string());
+    }
+}
@@ -0,0 +1,157 @@
+//! WebSocket 传输（需启用 `ws` 特性）：每条文本帧是一条 JSON-RPC 消息（单条或批量），
+//! 响应与通知同样以文本帧推送。每个连接是一个会话，全部会话共享同一个引擎。
+//!
+//! 握手请求带 `Origin` 头时按与 HTTP+SSE 相同的允许列表检查（默认仅本机），不允许的来源以 `403` 拒绝握手。
+
+use crate::transport::Shared;
+use std::io;
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::Duration;
+use tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tungstenite::http::StatusCode;
+use tungstenite::{Message, WebSocket};
+
+/// 读取等待的最长时间：超时后转发其它会话广播给本会话的通知，再继续读取。
+const POLL: Duration = Duration::from_millis(100);
+
+/// 绑定地址并开始服务（阻塞）。
+pub fn run(addr: impl ToSocketAddrs, shared: Shared) -> io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    eprintln!("memory: WebSocket transport listening on ws://{}", listener.local_addr()?);
+    serve(listener, shared);
+    Ok(())
+}
+
+/// 在已绑定的监听器上服务：每个连接一个线程。
+pub fn serve(listener: TcpListener, shared: Shared) {
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else { continue };
+        let shared = shared.clone();
+        thread::spawn(move || {
+            if let Err(e) = handle_connection(stream, &shared) {
+                eprintln!("memory: WebSocket connection closed: {e}");
+            }
+        });
+    }
+}
+
+fn handle_connection(stream: TcpStream, shared: &Shared) -> Result<(), String> {
+    // 回调签名由 tungstenite 规定。
+    #[allow(clippy::result_large_err)]
+    let check_origin = |request: &Request, response: Response| -> Result<Response, ErrorResponse> {
+        let origin = request.headers().get("origin").map(|v| v.to_str().unwrap_or("null"));
+        if shared.origin_allowed(origin) {
+            return Ok(response);
+        }
+        let mut rejected = ErrorResponse::new(Some("origin not allowed".to_string()));
+        *rejected.status_mut() = StatusCode::FORBIDDEN;
+        Err(rejected)
+    };
+    let mut socket = tungstenite::accept_hdr(stream, check_origin).map_err(|e| format!("handshake failed: {e}"))?;
+    socket
+        .get_ref()
+        .set_read_timeout(Some(POLL))
+        .map_err(|e| e.to_string())?;
+
+    let (outbox, inbox) = mpsc::channel::<String>();
+    let session = shared.open_session(outbox);
+    let result = pump(&mut socket, shared, &session, &inbox);
+    shared.close_session(&session);
+    result
+}
+
+fn pump(socket: &mut WebSocket<TcpStream>, shared: &Shared, session: &str, inbox: &Receiver<String>) -> Result<(), String> {
+    loop {
+        while let Ok(line) = inbox.try_recv() {
+            socket.send(Message::text(line)).map_err(|e| e.to_string())?;
+        }
+        match socket.read() {
+            // 无法解析的消息已作为 error 日志推送给本会话。
+            Ok(Message::Text(text)) => {
+                let _ = shared.dispatch(session, &text);
+            }
+            Ok(Message::Binary(bytes)) => {
+                let _ = shared.dispatch(session, &String::from_utf8_lossy(&bytes));
+            }
+            Ok(Message::Close(_)) => return Ok(()),
+            // ping/pong 由 tungstenite 自动应答。
+            Ok(_) => {}
+            Err(tungstenite::Error::Io(e)) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {}
+            Err(tungstenite::Error::ConnectionClosed | tungstenite::Error::AlreadyClosed) => return Ok(()),
+            Err(e) => return Err(e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::MemoryEngine;
+    use serde_json::{json, Value};
+
+    #[test]
+    fn sessions_should_share_engine_and_receive_change_broadcasts() {
+        let dir = tempfile::tempdir().unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let shared = Shared::new(MemoryEngine::new(dir.path().to_path_buf()));
+        thread::spawn(move || serve(listener, shared));
+
+        let connect = || tungstenite::connect(format!("ws://{addr}")).unwrap().0;
+        let recv = |socket: &mut WebSocket<_>| -> Value {
+            loop {
+                if let Message::Text(text) = socket.read().unwrap() {
+                    return serde_json::from_str(&text).unwrap();
+                }
+            }
+        };
+        let mut a = connect();
+        let mut b = connect();
+
+        let remember = json!({ "jsonrpc": "2.0", "id": "w1", "method": "tools/call", "params": { "name": "remember", "arguments": {
+            "namespace": "u1/p1", "keywords": ["k"], "slice": "经由 WebSocket", "diary": "d"
+        } } });
+        a.send(Message::text(remember.to_string())).unwrap();
+        let response = recv(&mut a);
+        assert_eq!(response["id"], "w1");
+        assert!(response["result"]["data"]["id"].is_string());
+        assert_eq!(recv(&mut a)["method"], "notifications/memory/changed");
+        assert_eq!(recv(&mut b)["params"]["namespace"], "u1/p1");
+
+        let recall = json!({ "jsonrpc": "2.0", "id": 2, "method": "tools/call", "params": { "name": "recall", "arguments": {
+            "namespace": "u1/p1", "keywords": ["k"]
+        } } });
+        b.send(Message::text(recall.to_string())).unwrap();
+        let response = recv(&mut b);
+        assert_eq!(response["result"]["data"]["items"][0]["slice"], "经由 WebSocket");
+    }
+
+    #[test]
+    fn handshake_should_reject_foreign_origins() {
+        use tungstenite::client::IntoClientRequest;
+
+        let dir = tempfile::tempdir().unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let shared = Shared::new(MemoryEngine::new(dir.path().to_path_buf()))
+            .with_allowed_origins(["https://app.example.com".to_string()]);
+        thread::spawn(move || serve(listener, shared));
+
+        // 握手结果的状态码：成功为 101。
+        let with_origin = |origin: &str| {
+            let mut request = format!("ws://{addr}").into_client_request().unwrap();
+            request.headers_mut().insert("Origin", origin.parse().unwrap());
+            match tungstenite::connect(request) {
+                Ok(_) => StatusCode::SWITCHING_PROTOCOLS,
+                Err(tungstenite::Error::Http(response)) => response.status(),
+                Err(e) => panic!("handshake failed: {e}"),
+            }
+        };
+        assert_eq!(with_origin("https://evil.example"), StatusCode::FORBIDDEN);
+        assert_eq!(with_origin("http://127.0.0.1:8080"), StatusCode::SWITCHING_PROTOCOLS);
+        assert_eq!(with_origin("https://app.example.com"), StatusCode::SWITCHING_PROTOCOLS);
+        assert!(tungstenite::connect(format!("ws://{addr}")).is_ok());
+    }
+}